@@ -1,4 +1,8 @@
-use std::{error::Error as StdError, fmt, io};
+use std::{
+    error::Error as StdError,
+    fmt, io,
+    path::{Path, PathBuf},
+};
 
 /// `Result<T>` provides a simplified result type with a common error type
 pub type Result<T> = std::result::Result<T, Error>;
@@ -6,42 +10,254 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// Define common error wrapper type
 #[derive(Debug)]
 pub enum Error {
+    /// An error indicating that the given commit has already been pushed to a remote.
+    AlreadyPushed(String),
+
+    /// An error indicating that the given revision matches more than one object.
+    AmbiguousRevision(String, String),
+
+    /// An error indicating that bisecting failed for the given reason.
+    BisectFailed(String),
+
     /// An error indicating that the given branch was not found.
     BranchNotFound(String),
 
+    /// An error indicating that the given directory is not empty and not the target repo.
+    DirNotEmpty(String),
+
+    /// An error indicating that the given worktree has uncommitted changes.
+    DirtyWorktree(String),
+
     /// An error indicating that only fast forwards are allowed.
     FastForwardOnly,
 
+    /// An error indicating that fetching from the given remotes failed with the given errors.
+    FetchFailed(Vec<(String, Error)>),
+
     /// An error from fungus which might contain more errors
     Fungus(fungus::FuError),
 
     /// Git2 wrapped error
     Git2(git2::Error),
 
+    /// An error indicating that the given hook exited with a failure and the given output.
+    HookFailed(String, String),
+
+    /// An error indicating that the given bundle is invalid for the given reason.
+    InvalidBundle(String, String),
+
+    /// An error indicating that the given hook name isn't one git runs.
+    InvalidHook(String),
+
+    /// An error indicating that the given search pattern is invalid.
+    InvalidPattern(String),
+
+    /// An error indicating that the given value isn't a valid semantic version.
+    InvalidVersion(String),
+
+    /// An error indicating that fetching the LFS object of the given path failed for the given reason.
+    LfsFailed(String, String),
+
+    /// An error indicating that the given paths have conflicts that need to be resolved.
+    MergeConflicts(Vec<PathBuf>),
+
+    /// An error indicating that the given commits a bundle requires are missing from the repo.
+    MissingPrerequisites(Vec<String>),
+
     /// An error indicating that no message was found.
     NoMessageWasFound,
 
     // Progress error occurred with indicatif
     Progress(io::Error),
 
+    /// An error indicating that the given commit message isn't a conventional commit.
+    NotConventional(String),
+
+    /// An error indicating that the patch doesn't apply to the given paths.
+    PatchConflicts(Vec<PathBuf>),
+
+    /// An error indicating that the given path or pathspec didn't match any files.
+    PathNotFound(String),
+
+    /// An error indicating that the remote rejected the push of the given ref.
+    PushRejected(String, String),
+
+    /// An error indicating that the given remote ref moved from the expected value of a lease.
+    RemoteMoved(String),
+
     /// An error indicating that the given repo was not found.
     RepoNotFound(String),
 
+    /// An error indicating that the given revision was not found.
+    RevisionNotFound(String),
+
+    /// An error indicating that signing failed with the given reason.
+    SigningFailed(String),
+
+    /// An error indicating that the given tag was not found.
+    TagNotFound(String),
+
+    /// An error indicating that the given branch has no upstream branch set.
+    UpstreamNotSet(String),
+
+    /// An error indicating that the repo's origin URL doesn't match the expected URL.
+    UrlMismatch(String, String),
+
     /// An error indicating that the URL was not set for the repo.
     UrlNotSet,
+
+    /// An error indicating that verification failed with the given reason.
+    VerifyFailed(String),
+
+    /// An error indicating that the given worktree is locked.
+    WorktreeLocked(String),
 }
 
 impl Error {
+    /// Return an error indicating that the given commit has already been pushed to a remote.
+    pub fn already_pushed<T: AsRef<str>>(commit: T) -> Error {
+        Error::AlreadyPushed(commit.as_ref().to_string())
+    }
+
+    /// Return an error indicating that the given revision matches more than one object.
+    pub fn ambiguous_revision<T: AsRef<str>, U: AsRef<str>>(rev: T, reason: U) -> Error {
+        Error::AmbiguousRevision(rev.as_ref().to_string(), reason.as_ref().to_string())
+    }
+
+    /// Return an error indicating that bisecting failed for the given reason.
+    pub fn bisect_failed<T: AsRef<str>>(reason: T) -> Error {
+        Error::BisectFailed(reason.as_ref().to_string())
+    }
+
     /// Return an error indicating that the given branch was not found.
     pub fn branch_not_found<T: AsRef<str>>(pkg: T) -> Error {
         Error::BranchNotFound(pkg.as_ref().to_string())
     }
 
+    /// Return an error indicating that the given directory is not empty.
+    pub fn dir_not_empty<T: AsRef<Path>>(path: T) -> Error {
+        Error::DirNotEmpty(path.as_ref().display().to_string())
+    }
+
+    /// Return an error indicating that the given worktree has uncommitted changes.
+    pub fn dirty_worktree<T: AsRef<Path>>(path: T) -> Error {
+        Error::DirtyWorktree(path.as_ref().display().to_string())
+    }
+
+    /// Return an error indicating that fetching from the given remotes failed with the given
+    /// errors.
+    pub fn fetch_failed<T: AsRef<str>>(failures: Vec<(T, Error)>) -> Error {
+        Error::FetchFailed(failures.into_iter().map(|(x, y)| (x.as_ref().to_string(), y)).collect())
+    }
+
+    /// Return an error indicating that the given hook exited with a failure and the given output.
+    pub fn hook_failed<T: AsRef<str>, U: AsRef<str>>(name: T, output: U) -> Error {
+        Error::HookFailed(name.as_ref().to_string(), output.as_ref().to_string())
+    }
+
+    /// Return an error indicating that the given bundle is invalid for the given reason.
+    pub fn invalid_bundle<T: AsRef<Path>, U: AsRef<str>>(path: T, reason: U) -> Error {
+        Error::InvalidBundle(path.as_ref().display().to_string(), reason.as_ref().to_string())
+    }
+
+    /// Return an error indicating that the given hook name isn't one git runs.
+    pub fn invalid_hook<T: AsRef<str>>(name: T) -> Error {
+        Error::InvalidHook(name.as_ref().to_string())
+    }
+
+    /// Return an error indicating that the given search pattern is invalid.
+    pub fn invalid_pattern<T: AsRef<str>>(reason: T) -> Error {
+        Error::InvalidPattern(reason.as_ref().to_string())
+    }
+
+    /// Return an error indicating that the given value isn't a valid semantic version.
+    pub fn invalid_version<T: AsRef<str>>(value: T) -> Error {
+        Error::InvalidVersion(value.as_ref().to_string())
+    }
+
+    /// Return an error indicating that fetching the LFS object of the given path failed for the
+    /// given reason.
+    pub fn lfs_failed<T: AsRef<Path>, U: AsRef<str>>(path: T, reason: U) -> Error {
+        Error::LfsFailed(path.as_ref().display().to_string(), reason.as_ref().to_string())
+    }
+
+    /// Return an error indicating that the given paths have conflicts that need to be resolved.
+    pub fn merge_conflicts<T: AsRef<Path>>(paths: &[T]) -> Error {
+        Error::MergeConflicts(paths.iter().map(|x| x.as_ref().to_path_buf()).collect())
+    }
+
+    /// Return an error indicating that the given commits a bundle requires are missing from the
+    /// repo.
+    pub fn missing_prerequisites<T: AsRef<str>>(commits: &[T]) -> Error {
+        Error::MissingPrerequisites(commits.iter().map(|x| x.as_ref().to_string()).collect())
+    }
+
+    /// Return an error indicating that the given commit message isn't a conventional commit.
+    pub fn not_conventional<T: AsRef<str>>(summary: T) -> Error {
+        Error::NotConventional(summary.as_ref().to_string())
+    }
+
+    /// Return an error indicating that the patch doesn't apply to the given paths.
+    pub fn patch_conflicts<T: AsRef<Path>>(paths: &[T]) -> Error {
+        Error::PatchConflicts(paths.iter().map(|x| x.as_ref().to_path_buf()).collect())
+    }
+
+    /// Return an error indicating that the given path or pathspec didn't match any files.
+    pub fn path_not_found<T: AsRef<str>>(path: T) -> Error {
+        Error::PathNotFound(path.as_ref().to_string())
+    }
+
+    /// Return an error indicating that the remote rejected the push of the given ref.
+    pub fn push_rejected<T: AsRef<str>, U: AsRef<str>>(refname: T, reason: U) -> Error {
+        Error::PushRejected(refname.as_ref().to_string(), reason.as_ref().to_string())
+    }
+
+    /// Return an error indicating that the given remote ref moved from the expected value of a
+    /// lease.
+    pub fn remote_moved<T: AsRef<str>>(refname: T) -> Error {
+        Error::RemoteMoved(refname.as_ref().to_string())
+    }
+
     /// Return an error indicating that the given repo was not found.
     pub fn repo_not_found<T: AsRef<str>>(repo: T) -> Error {
         Error::RepoNotFound(repo.as_ref().to_string())
     }
 
+    /// Return an error indicating that the given revision was not found.
+    pub fn revision_not_found<T: AsRef<str>>(rev: T) -> Error {
+        Error::RevisionNotFound(rev.as_ref().to_string())
+    }
+
+    /// Return an error indicating that signing failed with the given reason.
+    pub fn signing_failed<T: AsRef<str>>(reason: T) -> Error {
+        Error::SigningFailed(reason.as_ref().to_string())
+    }
+
+    /// Return an error indicating that the given tag was not found.
+    pub fn tag_not_found<T: AsRef<str>>(tag: T) -> Error {
+        Error::TagNotFound(tag.as_ref().to_string())
+    }
+
+    /// Return an error indicating that the given branch has no upstream branch set.
+    pub fn upstream_not_set<T: AsRef<str>>(branch: T) -> Error {
+        Error::UpstreamNotSet(branch.as_ref().to_string())
+    }
+
+    /// Return an error indicating that the repo's origin URL doesn't match the expected URL.
+    pub fn url_mismatch<T: AsRef<str>, U: AsRef<str>>(expected: T, actual: U) -> Error {
+        Error::UrlMismatch(expected.as_ref().to_string(), actual.as_ref().to_string())
+    }
+
+    /// Return an error indicating that verification failed with the given reason.
+    pub fn verify_failed<T: AsRef<str>>(reason: T) -> Error {
+        Error::VerifyFailed(reason.as_ref().to_string())
+    }
+
+    /// Return an error indicating that the given worktree is locked.
+    pub fn worktree_locked<T: AsRef<str>>(name: T) -> Error {
+        Error::WorktreeLocked(name.as_ref().to_string())
+    }
+
     /// Implemented directly on the `Error` type to reduce casting required
     pub fn is<T: StdError+'static>(&self) -> bool {
         self.as_ref().is::<T>()
@@ -66,14 +282,49 @@ impl Error {
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            Error::AlreadyPushed(ref commit) => write!(f, "commit has already been pushed to a remote: {}", commit),
+            Error::AmbiguousRevision(ref rev, ref reason) => write!(f, "ambiguous revision {}: {}", rev, reason),
+            Error::BisectFailed(ref reason) => write!(f, "bisect failed: {}", reason),
             Error::BranchNotFound(ref pkg) => write!(f, "failed to find branch: {}", pkg),
+            Error::DirNotEmpty(ref path) => write!(f, "directory is not empty and not the target repo: {}", path),
+            Error::DirtyWorktree(ref path) => write!(f, "worktree has uncommitted changes: {}", path),
             Error::FastForwardOnly => write!(f, "only fast-forward supported"),
+            Error::FetchFailed(ref failures) => {
+                let failures: Vec<String> = failures.iter().map(|(x, y)| format!("{}: {}", x, y)).collect();
+                write!(f, "failed to fetch remotes: {}", failures.join("; "))
+            },
             Error::Fungus(ref err) => write!(f, "{}", err),
             Error::Git2(ref err) => write!(f, "{}", err),
+            Error::HookFailed(ref name, ref output) => write!(f, "{} hook failed: {}", name, output),
+            Error::InvalidBundle(ref path, ref reason) => write!(f, "invalid bundle {}: {}", path, reason),
+            Error::InvalidHook(ref name) => write!(f, "invalid hook name: {}", name),
+            Error::InvalidPattern(ref reason) => write!(f, "invalid pattern: {}", reason),
+            Error::InvalidVersion(ref value) => write!(f, "invalid semantic version: {}", value),
+            Error::LfsFailed(ref path, ref reason) => write!(f, "failed to fetch lfs object for {}: {}", path, reason),
+            Error::MergeConflicts(ref paths) => {
+                let paths: Vec<String> = paths.iter().map(|x| x.display().to_string()).collect();
+                write!(f, "merge conflicts in: {}", paths.join(", "))
+            },
+            Error::MissingPrerequisites(ref commits) => write!(f, "repo is missing commits the bundle requires: {}", commits.join(", ")),
             Error::NoMessageWasFound => write!(f, "no message was found for commit"),
+            Error::NotConventional(ref summary) => write!(f, "not a conventional commit: {}", summary),
+            Error::PatchConflicts(ref paths) => {
+                let paths: Vec<String> = paths.iter().map(|x| x.display().to_string()).collect();
+                write!(f, "patch does not apply to: {}", paths.join(", "))
+            },
+            Error::PathNotFound(ref path) => write!(f, "failed to find path: {}", path),
             Error::RepoNotFound(ref repo) => write!(f, "failed to find repo: {}", repo),
+            Error::RevisionNotFound(ref rev) => write!(f, "failed to find revision: {}", rev),
             Error::Progress(ref err) => write!(f, "{}", err),
+            Error::PushRejected(ref refname, ref reason) => write!(f, "remote rejected push of {}: {}", refname, reason),
+            Error::RemoteMoved(ref refname) => write!(f, "remote ref moved since the lease was taken: {}", refname),
+            Error::SigningFailed(ref reason) => write!(f, "failed to sign: {}", reason),
+            Error::TagNotFound(ref tag) => write!(f, "failed to find tag: {}", tag),
+            Error::UpstreamNotSet(ref branch) => write!(f, "no upstream set for branch: {}", branch),
+            Error::UrlMismatch(ref expected, ref actual) => write!(f, "repo origin url {} doesn't match expected url {}", actual, expected),
             Error::UrlNotSet => write!(f, "no url was set for the repo"),
+            Error::VerifyFailed(ref reason) => write!(f, "failed to verify: {}", reason),
+            Error::WorktreeLocked(ref name) => write!(f, "worktree is locked: {}", name),
         }
     }
 }
@@ -81,15 +332,41 @@ impl std::fmt::Display for Error {
 impl AsRef<dyn StdError> for Error {
     fn as_ref(&self) -> &(dyn StdError+'static) {
         match *self {
+            Error::AlreadyPushed(_) => self,
+            Error::AmbiguousRevision(..) => self,
+            Error::BisectFailed(_) => self,
             Error::BranchNotFound(_) => self,
+            Error::DirNotEmpty(_) => self,
+            Error::DirtyWorktree(_) => self,
             Error::FastForwardOnly => self,
+            Error::FetchFailed(_) => self,
             // Unwrap a fungus error so it is transparent
             Error::Fungus(ref err) => err.as_ref(),
             Error::Git2(ref err) => err,
+            Error::HookFailed(..) => self,
+            Error::InvalidBundle(..) => self,
+            Error::InvalidHook(_) => self,
+            Error::InvalidPattern(_) => self,
+            Error::InvalidVersion(_) => self,
+            Error::LfsFailed(..) => self,
+            Error::MergeConflicts(_) => self,
+            Error::MissingPrerequisites(_) => self,
             Error::NoMessageWasFound => self,
+            Error::NotConventional(_) => self,
+            Error::PatchConflicts(_) => self,
+            Error::PathNotFound(_) => self,
             Error::RepoNotFound(_) => self,
+            Error::RevisionNotFound(_) => self,
             Error::Progress(ref err) => err,
+            Error::PushRejected(..) => self,
+            Error::RemoteMoved(_) => self,
+            Error::SigningFailed(_) => self,
+            Error::TagNotFound(_) => self,
+            Error::UpstreamNotSet(_) => self,
+            Error::UrlMismatch(..) => self,
             Error::UrlNotSet => self,
+            Error::VerifyFailed(_) => self,
+            Error::WorktreeLocked(_) => self,
         }
     }
 }
@@ -97,15 +374,41 @@ impl AsRef<dyn StdError> for Error {
 impl AsMut<dyn StdError> for Error {
     fn as_mut(&mut self) -> &mut (dyn StdError+'static) {
         match *self {
+            Error::AlreadyPushed(_) => self,
+            Error::AmbiguousRevision(..) => self,
+            Error::BisectFailed(_) => self,
             Error::BranchNotFound(_) => self,
+            Error::DirNotEmpty(_) => self,
+            Error::DirtyWorktree(_) => self,
             Error::FastForwardOnly => self,
+            Error::FetchFailed(_) => self,
             // Unwrap a fungus error so it is transparent
             Error::Fungus(ref mut err) => err.as_mut(),
             Error::Git2(ref mut err) => err,
+            Error::HookFailed(..) => self,
+            Error::InvalidBundle(..) => self,
+            Error::InvalidHook(_) => self,
+            Error::InvalidPattern(_) => self,
+            Error::InvalidVersion(_) => self,
+            Error::LfsFailed(..) => self,
+            Error::MergeConflicts(_) => self,
+            Error::MissingPrerequisites(_) => self,
             Error::NoMessageWasFound => self,
+            Error::NotConventional(_) => self,
+            Error::PatchConflicts(_) => self,
+            Error::PathNotFound(_) => self,
             Error::RepoNotFound(_) => self,
+            Error::RevisionNotFound(_) => self,
             Error::Progress(ref mut err) => err,
+            Error::PushRejected(..) => self,
+            Error::RemoteMoved(_) => self,
+            Error::SigningFailed(_) => self,
+            Error::TagNotFound(_) => self,
+            Error::UpstreamNotSet(_) => self,
+            Error::UrlMismatch(..) => self,
             Error::UrlNotSet => self,
+            Error::VerifyFailed(_) => self,
+            Error::WorktreeLocked(_) => self,
         }
     }
 }
@@ -136,6 +439,36 @@ mod tests {
 
     #[test]
     fn test_errors() {
+        // AlreadyPushed(String)
+        let mut err = git::Error::AlreadyPushed("foo".to_string());
+        assert_eq!(git::Error::already_pushed("foo").to_string(), err.to_string());
+        assert_eq!("commit has already been pushed to a remote: foo", err.to_string());
+        assert_eq!("commit has already been pushed to a remote: foo", err.as_ref().to_string());
+        assert_eq!("commit has already been pushed to a remote: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // AmbiguousRevision(String, String)
+        let mut err = git::Error::AmbiguousRevision("foo".to_string(), "bar".to_string());
+        assert_eq!(git::Error::ambiguous_revision("foo", "bar").to_string(), err.to_string());
+        assert_eq!("ambiguous revision foo: bar", err.to_string());
+        assert_eq!("ambiguous revision foo: bar", err.as_ref().to_string());
+        assert_eq!("ambiguous revision foo: bar", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // BisectFailed(String)
+        let mut err = git::Error::BisectFailed("foo".to_string());
+        assert_eq!(git::Error::bisect_failed("foo").to_string(), err.to_string());
+        assert_eq!("bisect failed: foo", err.to_string());
+        assert_eq!("bisect failed: foo", err.as_ref().to_string());
+        assert_eq!("bisect failed: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
         // Error::BranchNotFound(String)
         let mut err = git::Error::BranchNotFound("foo".to_string());
         assert_eq!(git::Error::branch_not_found("foo").to_string(), err.to_string());
@@ -146,6 +479,26 @@ mod tests {
         assert!(err.downcast_mut::<git::Error>().is_some());
         assert!(err.source().is_none());
 
+        // DirNotEmpty(String)
+        let mut err = git::Error::DirNotEmpty("foo".to_string());
+        assert_eq!(git::Error::dir_not_empty("foo").to_string(), err.to_string());
+        assert_eq!("directory is not empty and not the target repo: foo", err.to_string());
+        assert_eq!("directory is not empty and not the target repo: foo", err.as_ref().to_string());
+        assert_eq!("directory is not empty and not the target repo: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // DirtyWorktree(String)
+        let mut err = git::Error::DirtyWorktree("foo".to_string());
+        assert_eq!(git::Error::dirty_worktree("foo").to_string(), err.to_string());
+        assert_eq!("worktree has uncommitted changes: foo", err.to_string());
+        assert_eq!("worktree has uncommitted changes: foo", err.as_ref().to_string());
+        assert_eq!("worktree has uncommitted changes: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
         // FastForwardOnly,
         let mut err = git::Error::FastForwardOnly;
         assert_eq!("only fast-forward supported", err.to_string());
@@ -156,6 +509,27 @@ mod tests {
         assert!(err.downcast_mut::<git::Error>().is_some());
         assert!(err.source().is_none());
 
+        // FetchFailed(Vec<(String, Error)>)
+        let mut err = git::Error::FetchFailed(vec![
+            ("foo".to_string(), git::Error::UrlNotSet),
+            ("bar".to_string(), git::Error::FastForwardOnly),
+        ]);
+        let msg = "failed to fetch remotes: foo: no url was set for the repo; bar: only fast-forward supported";
+        assert_eq!(
+            git::Error::fetch_failed(vec![
+                ("foo", git::Error::UrlNotSet),
+                ("bar", git::Error::FastForwardOnly)
+            ])
+            .to_string(),
+            err.to_string()
+        );
+        assert_eq!(msg, err.to_string());
+        assert_eq!(msg, err.as_ref().to_string());
+        assert_eq!(msg, err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
         // Fungus(fungus::FuError),
         let mut err = git::Error::from(FuError::from(FileError::FailedToExtractString));
         assert_eq!("failed to extract string from file", err.to_string());
@@ -174,6 +548,86 @@ mod tests {
         assert!(err.downcast_mut::<git2::Error>().is_some());
         assert!(err.source().is_none());
 
+        // HookFailed(String, String)
+        let mut err = git::Error::HookFailed("foo".to_string(), "bar".to_string());
+        assert_eq!(git::Error::hook_failed("foo", "bar").to_string(), err.to_string());
+        assert_eq!("foo hook failed: bar", err.to_string());
+        assert_eq!("foo hook failed: bar", err.as_ref().to_string());
+        assert_eq!("foo hook failed: bar", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // InvalidBundle(String, String)
+        let mut err = git::Error::InvalidBundle("foo".to_string(), "bar".to_string());
+        assert_eq!(git::Error::invalid_bundle("foo", "bar").to_string(), err.to_string());
+        assert_eq!("invalid bundle foo: bar", err.to_string());
+        assert_eq!("invalid bundle foo: bar", err.as_ref().to_string());
+        assert_eq!("invalid bundle foo: bar", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // InvalidHook(String)
+        let mut err = git::Error::InvalidHook("foo".to_string());
+        assert_eq!(git::Error::invalid_hook("foo").to_string(), err.to_string());
+        assert_eq!("invalid hook name: foo", err.to_string());
+        assert_eq!("invalid hook name: foo", err.as_ref().to_string());
+        assert_eq!("invalid hook name: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // InvalidPattern(String)
+        let mut err = git::Error::InvalidPattern("foo".to_string());
+        assert_eq!(git::Error::invalid_pattern("foo").to_string(), err.to_string());
+        assert_eq!("invalid pattern: foo", err.to_string());
+        assert_eq!("invalid pattern: foo", err.as_ref().to_string());
+        assert_eq!("invalid pattern: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // InvalidVersion(String)
+        let mut err = git::Error::InvalidVersion("foo".to_string());
+        assert_eq!(git::Error::invalid_version("foo").to_string(), err.to_string());
+        assert_eq!("invalid semantic version: foo", err.to_string());
+        assert_eq!("invalid semantic version: foo", err.as_ref().to_string());
+        assert_eq!("invalid semantic version: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // LfsFailed(String, String)
+        let mut err = git::Error::LfsFailed("foo".to_string(), "bar".to_string());
+        assert_eq!(git::Error::lfs_failed("foo", "bar").to_string(), err.to_string());
+        assert_eq!("failed to fetch lfs object for foo: bar", err.to_string());
+        assert_eq!("failed to fetch lfs object for foo: bar", err.as_ref().to_string());
+        assert_eq!("failed to fetch lfs object for foo: bar", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // MergeConflicts(Vec<PathBuf>)
+        let mut err = git::Error::MergeConflicts(vec![PathBuf::from("foo"), PathBuf::from("bar")]);
+        assert_eq!(git::Error::merge_conflicts(&["foo", "bar"]).to_string(), err.to_string());
+        assert_eq!("merge conflicts in: foo, bar", err.to_string());
+        assert_eq!("merge conflicts in: foo, bar", err.as_ref().to_string());
+        assert_eq!("merge conflicts in: foo, bar", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // MissingPrerequisites(Vec<String>)
+        let mut err = git::Error::MissingPrerequisites(vec!["foo".to_string(), "bar".to_string()]);
+        assert_eq!(git::Error::missing_prerequisites(&["foo", "bar"]).to_string(), err.to_string());
+        assert_eq!("repo is missing commits the bundle requires: foo, bar", err.to_string());
+        assert_eq!("repo is missing commits the bundle requires: foo, bar", err.as_ref().to_string());
+        assert_eq!("repo is missing commits the bundle requires: foo, bar", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
         // NoMessageWasFound,
         let mut err = git::Error::NoMessageWasFound;
         assert_eq!("no message was found for commit", err.to_string());
@@ -183,6 +637,36 @@ mod tests {
         assert!(err.downcast_mut::<git::Error>().is_some());
         assert!(err.source().is_none());
 
+        // NotConventional(String)
+        let mut err = git::Error::NotConventional("foo".to_string());
+        assert_eq!(git::Error::not_conventional("foo").to_string(), err.to_string());
+        assert_eq!("not a conventional commit: foo", err.to_string());
+        assert_eq!("not a conventional commit: foo", err.as_ref().to_string());
+        assert_eq!("not a conventional commit: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // PatchConflicts(Vec<PathBuf>)
+        let mut err = git::Error::PatchConflicts(vec![PathBuf::from("foo"), PathBuf::from("bar")]);
+        assert_eq!(git::Error::patch_conflicts(&["foo", "bar"]).to_string(), err.to_string());
+        assert_eq!("patch does not apply to: foo, bar", err.to_string());
+        assert_eq!("patch does not apply to: foo, bar", err.as_ref().to_string());
+        assert_eq!("patch does not apply to: foo, bar", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // PathNotFound(String)
+        let mut err = git::Error::PathNotFound("foo".to_string());
+        assert_eq!(git::Error::path_not_found("foo").to_string(), err.to_string());
+        assert_eq!("failed to find path: foo", err.to_string());
+        assert_eq!("failed to find path: foo", err.as_ref().to_string());
+        assert_eq!("failed to find path: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
         // Progress(io::Error),
         let mut err = git::Error::from(io::Error::new(io::ErrorKind::AlreadyExists, "foo"));
         assert_eq!("foo", err.to_string());
@@ -192,6 +676,26 @@ mod tests {
         assert!(err.downcast_mut::<io::Error>().is_some());
         assert!(err.source().is_none());
 
+        // PushRejected(String, String),
+        let mut err = git::Error::PushRejected("foo".to_string(), "bar".to_string());
+        assert_eq!(git::Error::push_rejected("foo", "bar").to_string(), err.to_string());
+        assert_eq!("remote rejected push of foo: bar", err.to_string());
+        assert_eq!("remote rejected push of foo: bar", err.as_ref().to_string());
+        assert_eq!("remote rejected push of foo: bar", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // RemoteMoved(String),
+        let mut err = git::Error::RemoteMoved("foo".to_string());
+        assert_eq!(git::Error::remote_moved("foo").to_string(), err.to_string());
+        assert_eq!("remote ref moved since the lease was taken: foo", err.to_string());
+        assert_eq!("remote ref moved since the lease was taken: foo", err.as_ref().to_string());
+        assert_eq!("remote ref moved since the lease was taken: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
         // RepoNotFound(String),
         let mut err = git::Error::RepoNotFound("foo".to_string());
         assert_eq!(git::Error::repo_not_found("foo").to_string(), err.to_string());
@@ -202,6 +706,56 @@ mod tests {
         assert!(err.downcast_mut::<git::Error>().is_some());
         assert!(err.source().is_none());
 
+        // RevisionNotFound(String),
+        let mut err = git::Error::RevisionNotFound("foo".to_string());
+        assert_eq!(git::Error::revision_not_found("foo").to_string(), err.to_string());
+        assert_eq!("failed to find revision: foo", err.to_string());
+        assert_eq!("failed to find revision: foo", err.as_ref().to_string());
+        assert_eq!("failed to find revision: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // SigningFailed(String),
+        let mut err = git::Error::SigningFailed("foo".to_string());
+        assert_eq!(git::Error::signing_failed("foo").to_string(), err.to_string());
+        assert_eq!("failed to sign: foo", err.to_string());
+        assert_eq!("failed to sign: foo", err.as_ref().to_string());
+        assert_eq!("failed to sign: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // TagNotFound(String),
+        let mut err = git::Error::TagNotFound("foo".to_string());
+        assert_eq!(git::Error::tag_not_found("foo").to_string(), err.to_string());
+        assert_eq!("failed to find tag: foo", err.to_string());
+        assert_eq!("failed to find tag: foo", err.as_ref().to_string());
+        assert_eq!("failed to find tag: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // UpstreamNotSet(String),
+        let mut err = git::Error::UpstreamNotSet("foo".to_string());
+        assert_eq!(git::Error::upstream_not_set("foo").to_string(), err.to_string());
+        assert_eq!("no upstream set for branch: foo", err.to_string());
+        assert_eq!("no upstream set for branch: foo", err.as_ref().to_string());
+        assert_eq!("no upstream set for branch: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // UrlMismatch(String, String),
+        let mut err = git::Error::UrlMismatch("foo".to_string(), "bar".to_string());
+        assert_eq!(git::Error::url_mismatch("foo", "bar").to_string(), err.to_string());
+        assert_eq!("repo origin url bar doesn't match expected url foo", err.to_string());
+        assert_eq!("repo origin url bar doesn't match expected url foo", err.as_ref().to_string());
+        assert_eq!("repo origin url bar doesn't match expected url foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
         // UrlNotSet,
         let mut err = git::Error::UrlNotSet;
         assert_eq!("no url was set for the repo", err.to_string());
@@ -211,5 +765,25 @@ mod tests {
         assert!(err.downcast_ref::<git::Error>().is_some());
         assert!(err.downcast_mut::<git::Error>().is_some());
         assert!(err.source().is_none());
+
+        // VerifyFailed(String)
+        let mut err = git::Error::VerifyFailed("foo".to_string());
+        assert_eq!(git::Error::verify_failed("foo").to_string(), err.to_string());
+        assert_eq!("failed to verify: foo", err.to_string());
+        assert_eq!("failed to verify: foo", err.as_ref().to_string());
+        assert_eq!("failed to verify: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
+
+        // WorktreeLocked(String)
+        let mut err = git::Error::WorktreeLocked("foo".to_string());
+        assert_eq!(git::Error::worktree_locked("foo").to_string(), err.to_string());
+        assert_eq!("worktree is locked: foo", err.to_string());
+        assert_eq!("worktree is locked: foo", err.as_ref().to_string());
+        assert_eq!("worktree is locked: foo", err.as_mut().to_string());
+        assert!(err.downcast_ref::<git::Error>().is_some());
+        assert!(err.downcast_mut::<git::Error>().is_some());
+        assert!(err.source().is_none());
     }
 }
@@ -2,17 +2,140 @@ use crate::error::*;
 use fungus::prelude::*;
 use git2::{
     self,
-    build::{CheckoutBuilder, RepoBuilder},
-    FetchOptions, RemoteCallbacks, Repository,
+    build::{CheckoutBuilder, CloneLocal, RepoBuilder},
+    ErrorClass, IndexEntryExtendedFlag, IndexEntryFlag, Pathspec, PathspecFlags, PushOptions, RemoteCallbacks, Repository,
 };
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use std::{
+    cell::RefCell,
     path::{Path, PathBuf},
     thread,
+    time::Duration,
 };
 
+mod apply;
+mod archive;
+mod attributes;
+mod auth;
+pub mod batch;
+mod bisect;
+mod blame;
+mod branch;
+mod bundle;
+pub mod changelog;
+mod checkout;
+mod cherry_pick;
+mod clean;
+mod commit;
+mod config;
+mod conflict;
+pub mod conventional;
+mod describe;
+mod diff;
+mod fetch;
+mod fsck;
+mod graph;
+mod hooks;
+mod ignore;
+mod index;
+mod lfs;
+mod log;
+mod ls_remote;
+mod maintenance;
+mod merge;
+#[cfg(feature = "netrc")]
+mod netrc;
+mod notes;
+mod proxy;
+mod push;
+mod rebase;
+mod reflog;
+mod release;
+mod remote;
+mod reset;
+mod resolve;
+mod restore;
+mod revert;
+mod rewrite;
+mod search;
+mod sign;
+mod sparse;
+mod stash;
+mod stats;
+mod status;
+mod submodule;
+mod tag;
+mod tls;
+mod verify;
+mod version;
+mod worktree;
+
+pub use apply::*;
+pub use archive::*;
+pub use attributes::*;
+pub use auth::*;
+pub use bisect::*;
+pub use blame::*;
+pub use branch::*;
+pub use bundle::*;
+pub use checkout::*;
+pub use cherry_pick::*;
+pub use clean::*;
+pub use commit::*;
+pub use config::*;
+pub use conflict::*;
+pub use describe::*;
+pub use diff::*;
+pub use fetch::*;
+pub use fsck::*;
+pub use graph::*;
+pub use hooks::*;
+pub use ignore::*;
+pub use index::*;
+pub use lfs::*;
+pub use log::*;
+pub use ls_remote::*;
+pub use maintenance::*;
+pub use merge::*;
+pub use notes::*;
+pub use proxy::*;
+pub use push::*;
+pub use rebase::*;
+pub use reflog::*;
+pub use release::*;
+pub use remote::*;
+pub use reset::*;
+pub use resolve::*;
+pub use restore::*;
+pub use revert::*;
+pub use rewrite::*;
+pub use search::*;
+pub use sign::*;
+pub use sparse::*;
+pub use stash::*;
+pub use stats::*;
+pub use status::*;
+pub use submodule::*;
+pub use tag::*;
+pub use tls::*;
+pub use verify::*;
+pub use version::*;
+pub use worktree::*;
+
 const TMPDIR: &str = "git";
 
+// Progress callback shared between multiple git operations
+type SharedProgress<'a> = RefCell<Box<dyn FnMut(u64, u64)+'a>>;
+
+// Network settings shared by all remote operations
+#[derive(Clone, Debug, Default)]
+struct Net {
+    auth: Auth,   // Credentials for network operations
+    proxy: Proxy, // Proxy for network operations
+    tls: Tls,            // TLS settings for https transports
+    rewrite: UrlRewrite, // Url rewrite rules
+}
+
 /// Git repository
 #[derive(Default)]
 pub struct RepoGroup<'a> {
@@ -59,11 +182,8 @@ impl<'a> RepoGroup<'a> {
     /// ```
     pub fn with_progress(mut self, yes: bool) -> Self {
         if yes {
-            let progress = MultiProgress::new();
-            let mut style = ProgressStyle::default_bar();
-            style = style.progress_chars("=>-").template("[{elapsed_precise}][{bar:50.cyan/blue}] {pos:>7}/{len:7} ({eta}) - {msg}");
-            self.progress = Some(progress);
-            self.style = Some(style);
+            self.progress = Some(MultiProgress::new());
+            self.style = Some(progress_style());
         }
         self
     }
@@ -98,13 +218,10 @@ impl<'a> RepoGroup<'a> {
             // Note: I had to make 'path' and 'url' owned types for the thread lifetime to work
             let path = repo.path_val().to_path_buf();
             let url = repo.url_val().ok_or(Error::UrlNotSet)?.to_string();
+            let net = repo.net.clone();
+            let lfs = repo.lfs;
 
-            if self.progress.is_none() {
-                threads.push(thread::spawn(move || {
-                    Repo::new(path).unwrap().url(url).clone().unwrap();
-                }));
-            } else {
-                let progress = self.progress.as_ref().unwrap();
+            if let Some(progress) = &self.progress {
                 let progress_bar = progress.add(ProgressBar::new(0).with_style(self.style.as_ref().unwrap().clone()));
                 progress_bar.set_message(&url);
                 let msg = url.clone();
@@ -112,9 +229,12 @@ impl<'a> RepoGroup<'a> {
                 thread::spawn(move || {
                     let mut xfer_init = false;
                     let mut check_init = false;
+                    let mut lfs_init = false;
                     Repo::new(path)
                         .unwrap()
                         .url(url)
+                        .net(net)
+                        .lfs(lfs)
                         .xfer_progress(|total, cur| {
                             if !xfer_init {
                                 progress_bar.set_length(total);
@@ -129,22 +249,32 @@ impl<'a> RepoGroup<'a> {
                             }
                             progress_bar.set_position(cur);
                         })
+                        .lfs_progress(|total, cur| {
+                            if !lfs_init {
+                                progress_bar.set_length(total);
+                                lfs_init = true;
+                            }
+                            progress_bar.set_position(cur);
+                        })
                         .clone()
                         .unwrap();
 
                     progress_bar.finish_with_message(&msg);
                 });
+            } else {
+                threads.push(thread::spawn(move || {
+                    Repo::new(path).unwrap().url(url).net(net).lfs(lfs).clone().unwrap();
+                }));
             }
         }
 
         // Wait for other threads to finish.
-        if self.progress.is_none() {
+        if let Some(progress) = &self.progress {
+            progress.join()?;
+        } else {
             for thread in threads {
                 thread.join().unwrap();
             }
-        } else {
-            let progress = self.progress.as_ref().unwrap();
-            progress.join()?;
         }
         Ok(())
     }
@@ -177,13 +307,10 @@ impl<'a> RepoGroup<'a> {
             // Note: I had to make 'path' and 'url' owned types for the thread lifetime to work
             let path = repo.path_val().to_path_buf();
             let url = repo.url_val().ok_or(Error::UrlNotSet)?.to_string();
+            let net = repo.net.clone();
+            let lfs = repo.lfs;
 
-            if self.progress.is_none() {
-                threads.push(thread::spawn(move || {
-                    Repo::new(path).unwrap().url(url).clone().unwrap();
-                }));
-            } else {
-                let progress = self.progress.as_ref().unwrap();
+            if let Some(progress) = &self.progress {
                 let progress_bar = progress.add(ProgressBar::new(0).with_style(self.style.as_ref().unwrap().clone()));
                 progress_bar.set_message(&url);
                 let msg = url.clone();
@@ -192,9 +319,12 @@ impl<'a> RepoGroup<'a> {
                     let mut xfer_init = false;
                     let mut check_init = false;
                     let mut update_init = false;
+                    let mut lfs_init = false;
                     Repo::new(path)
                         .unwrap()
                         .url(url)
+                        .net(net)
+                        .lfs(lfs)
                         .xfer_progress(|total, cur| {
                             if !xfer_init {
                                 progress_bar.set_length(total);
@@ -216,37 +346,77 @@ impl<'a> RepoGroup<'a> {
                             }
                             progress_bar.set_position(cur);
                         })
+                        .lfs_progress(|total, cur| {
+                            if !lfs_init {
+                                progress_bar.set_length(total);
+                                lfs_init = true;
+                            }
+                            progress_bar.set_position(cur);
+                        })
                         .update()
                         .unwrap();
 
                     progress_bar.finish_with_message(&msg);
                 });
+            } else {
+                threads.push(thread::spawn(move || {
+                    Repo::new(path).unwrap().url(url).net(net).lfs(lfs).update().unwrap();
+                }));
             }
         }
 
         // Wait for other threads to finish.
-        if self.progress.is_none() {
+        if let Some(progress) = &self.progress {
+            progress.join()?;
+        } else {
             for thread in threads {
                 thread.join().unwrap();
             }
-        } else {
-            let progress = self.progress.as_ref().unwrap();
-            progress.join()?;
         }
         Ok(())
     }
 }
 
+/// Local clone optimizations to use when the repo url refers to the local filesystem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LocalClone {
+    /// Bypass the git-aware transport for local paths but not `file://` urls (default)
+    #[default]
+    Auto,
+
+    /// Bypass the git-aware transport even for `file://` urls hardlinking objects when possible
+    Hardlink,
+
+    /// Bypass the git-aware transport even for `file://` urls copying objects without hardlinks
+    Copy,
+
+    /// Never bypass the git-aware transport
+    Transport,
+}
+
 /// Git repository
 #[derive(Default)]
 pub struct Repo<'a> {
     path: PathBuf,                                          // Repo location on disk
     url: Option<String>,                                    // Repo location on the network
+    remote: Option<String>,                                 // Remote to push to
     branch_only: bool,                                      // Clone only the given branch
     branch: Option<String>,                                 // Specific branch to work with
+    bare: bool,                                             // Clone as a bare repository
+    depth: u32,                                             // Shallow clone history depth
+    mirror: bool,                                           // Clone as a bare mirror of all refs
+    submodules: bool,                                       // Recursively update submodules
+    lfs: bool,                                              // Replace LFS pointers with their content
+    sparse: Vec<String>,                                    // Sparse checkout path patterns
+    retries: u32,                                           // Retry failed clones this many times
+    pull: PullOptions,                                      // Pull strategy used when updating
+    refspecs: Vec<String>,                                  // Additional refspecs to fetch
+    local: LocalClone,                                      // Local clone optimizations
+    net: Net,                                               // Network settings
     xfer_progress: Option<Box<dyn FnMut(u64, u64)+'a>>,     // Transfer progress callback
     update_progress: Option<Box<dyn FnMut(u64, u64)+'a>>,   // Update progress callback
     checkout_progress: Option<Box<dyn FnMut(u64, u64)+'a>>, // Checkout progress callback
+    lfs_progress: Option<Box<dyn FnMut(u64, u64)+'a>>,      // LFS download progress callback
 }
 
 impl<'a> Repo<'a> {
@@ -254,6 +424,67 @@ impl<'a> Repo<'a> {
     // Field getters/setters
     // ---------------------------------------------------------------------------------------------
 
+    /// Returns the credential chain used to authenticate this repo's network operations.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().auth(git::Auth::new().agent(false)).auth_val().agent_val(), false);
+    /// ```
+    pub fn auth_val(&self) -> &Auth {
+        &self.net.auth
+    }
+
+    /// Returns the proxy configuration used for this repo's network operations.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().proxy(git::Proxy::None).proxy_val(), &git::Proxy::None);
+    /// ```
+    pub fn proxy_val(&self) -> &Proxy {
+        &self.net.proxy
+    }
+
+    /// Returns the TLS settings used for this repo's https transports.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().tls(git::Tls::new().insecure(true)).tls_val().insecure_val(), true);
+    /// ```
+    pub fn tls_val(&self) -> &Tls {
+        &self.net.tls
+    }
+
+    /// Returns the url rewrite rules applied to this repo's url.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let rewrite = git::UrlRewrite::new().instead_of("https://github.com/", "gh:");
+    /// assert_eq!(git::Repo::new("foo").unwrap().url_rewrite(rewrite).url_rewrite_val().is_empty(), false);
+    /// ```
+    pub fn url_rewrite_val(&self) -> &UrlRewrite {
+        &self.net.rewrite
+    }
+
+    /// Returns the remote to push to for this repo. Defaults to `origin` when not set.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().remote("upstream").remote_val(), "upstream");
+    /// ```
+    pub fn remote_val(&self) -> &str {
+        self.remote.as_deref().unwrap_or("origin")
+    }
+
     /// Returns the target branch for this repo. Defaults to `master` internally when not set.
     ///
     /// ### Examples
@@ -278,6 +509,128 @@ impl<'a> Repo<'a> {
         self.branch_only
     }
 
+    /// Returns the bare flag's value for this repo.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().bare(true).bare_val(), true);
+    /// ```
+    pub fn bare_val(&self) -> bool {
+        self.bare
+    }
+
+    /// Returns the history depth to shallow clone this repo with, zero for the full history.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().depth(1).depth_val(), 1);
+    /// ```
+    pub fn depth_val(&self) -> u32 {
+        self.depth
+    }
+
+    /// Returns the mirror flag's value for this repo.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().mirror(true).mirror_val(), true);
+    /// ```
+    pub fn mirror_val(&self) -> bool {
+        self.mirror
+    }
+
+    /// Returns the submodules flag's value for this repo.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().with_submodules(true).submodules_val(), true);
+    /// ```
+    pub fn submodules_val(&self) -> bool {
+        self.submodules
+    }
+
+    /// Returns the LFS flag's value for this repo.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().lfs(true).lfs_val(), true);
+    /// ```
+    pub fn lfs_val(&self) -> bool {
+        self.lfs
+    }
+
+    /// Returns the local clone optimizations to use for this repo.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().local(git::LocalClone::Hardlink).local_val(), git::LocalClone::Hardlink);
+    /// ```
+    pub fn local_val(&self) -> LocalClone {
+        self.local
+    }
+
+    /// Returns the number of times a failed clone will be retried.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().retries(3).retries_val(), 3);
+    /// ```
+    pub fn retries_val(&self) -> u32 {
+        self.retries
+    }
+
+    /// Returns the sparse checkout patterns for this repo.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().sparse(&["docs"]).sparse_val(), &["docs".to_string()]);
+    /// ```
+    pub fn sparse_val(&self) -> &[String] {
+        &self.sparse
+    }
+
+    /// Returns the pull options used when updating this repo.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::PullOptions::new().strategy(git::PullStrategy::Rebase);
+    /// assert_eq!(git::Repo::new("foo").unwrap().pull(opts).pull_val().strategy_val(), git::PullStrategy::Rebase);
+    /// ```
+    pub fn pull_val(&self) -> &PullOptions {
+        &self.pull
+    }
+
+    /// Returns the additional refspecs fetched for this repo.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let refspecs = ["+refs/notes/*:refs/notes/*"];
+    /// assert_eq!(git::Repo::new("foo").unwrap().refspecs(&refspecs).refspecs_val(), &[refspecs[0].to_string()]);
+    /// ```
+    pub fn refspecs_val(&self) -> &[String] {
+        &self.refspecs
+    }
+
     /// Returns the local location on disk for this repo
     ///
     /// ### Examples
@@ -302,6 +655,61 @@ impl<'a> Repo<'a> {
         self.url.as_deref()
     }
 
+    /// Set the credential chain used to authenticate this repo's network operations.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().auth(git::Auth::new().agent(false)).auth_val().agent_val(), false);
+    /// ```
+    pub fn auth(mut self, auth: Auth) -> Self {
+        self.net.auth = auth;
+        self
+    }
+
+    /// Set the proxy configuration to use for this repo's network operations. Defaults to
+    /// detecting the proxy from git config and the environment.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().proxy(git::Proxy::None).proxy_val(), &git::Proxy::None);
+    /// ```
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.net.proxy = proxy;
+        self
+    }
+
+    /// Set the TLS settings to use for this repo's https transports.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().tls(git::Tls::new().insecure(true)).tls_val().insecure_val(), true);
+    /// ```
+    pub fn tls(mut self, tls: Tls) -> Self {
+        self.net.tls = tls;
+        self
+    }
+
+    /// Set url rewrite rules to apply to this repo's url before cloning in addition to any
+    /// `url.<base>.insteadOf` rules in git config e.g. to redirect to an internal mirror.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let rewrite = git::UrlRewrite::new().instead_of("https://git.example.com/mirror/", "https://github.com/");
+    /// let repo = git::Repo::new("foo").unwrap().url("https://github.com/phR0ze/alpine-base").url_rewrite(rewrite);
+    /// ```
+    pub fn url_rewrite(mut self, rewrite: UrlRewrite) -> Self {
+        self.net.rewrite = rewrite;
+        self
+    }
+
     /// Set the branch to target for this repo
     ///
     /// ### Examples
@@ -331,6 +739,204 @@ impl<'a> Repo<'a> {
         self
     }
 
+    /// Set the branch to target and only fetch and track that branch's refs. This is
+    /// the equivalent of `git clone --single-branch --branch <branch>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let repo = git::Repo::new("foo").unwrap().single_branch("foobar");
+    /// assert_eq!(repo.branch_val(), Some("foobar"));
+    /// assert_eq!(repo.branch_only_val(), true);
+    /// ```
+    pub fn single_branch<T>(self, branch: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.branch(branch).branch_only(true)
+    }
+
+    /// Set to clone as a bare repository i.e. without a working directory.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().bare(true).bare_val(), true);
+    /// ```
+    pub fn bare(mut self, yes: bool) -> Self {
+        self.bare = yes;
+        self
+    }
+
+    /// Set to create a shallow clone with its history truncated to the given number of commits,
+    /// the same as `git clone --depth <depth>`. Zero clones the full history (default).
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().depth(1).depth_val(), 1);
+    /// ```
+    pub fn depth(mut self, depth: u32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Set to replace LFS pointers with their content once a clone or update completes, the
+    /// same as `git lfs pull`. See `lfs_pull` for where the objects come from. Downloads
+    /// report through the LFS progress callback.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().lfs(true).lfs_val(), true);
+    /// ```
+    pub fn lfs(mut self, yes: bool) -> Self {
+        self.lfs = yes;
+        self
+    }
+
+    /// Set the local clone optimizations to use when the url refers to the local filesystem.
+    /// Bypassing the git-aware transport makes local clones much faster by copying or
+    /// hardlinking the object database directly rather than negotiating a pack.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().local(git::LocalClone::Hardlink).local_val(), git::LocalClone::Hardlink);
+    /// ```
+    pub fn local(mut self, mode: LocalClone) -> Self {
+        self.local = mode;
+        self
+    }
+
+    /// Set to clone as a mirror. A mirror is a bare repository that maps all remote refs
+    /// directly to local refs rather than remote tracking branches, the equivalent of
+    /// `git clone --mirror`. Takes precedence over `branch_only`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().mirror(true).mirror_val(), true);
+    /// ```
+    pub fn mirror(mut self, yes: bool) -> Self {
+        self.mirror = yes;
+        self
+    }
+
+    /// Set the pull options used by `update` to reconcile local commits with the fetched ones.
+    /// By default only fast-forwards are allowed.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::PullOptions::new().strategy(git::PullStrategy::Merge);
+    /// assert_eq!(git::Repo::new("foo").unwrap().pull(opts).pull_val().strategy_val(), git::PullStrategy::Merge);
+    /// ```
+    pub fn pull(mut self, opts: PullOptions) -> Self {
+        self.pull = opts;
+        self
+    }
+
+    /// Set additional refspecs to fetch along with the branches e.g. `+refs/notes/*:refs/notes/*`
+    /// or `+refs/pull/*/head:refs/remotes/origin/pr/*`. On `clone` they're recorded as fetch
+    /// refspecs of `origin` so later fetches include them and on `update` they're fetched before
+    /// the branch is updated.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let repo = git::Repo::new("foo").unwrap().refspecs(&["+refs/notes/*:refs/notes/*"]);
+    /// assert_eq!(repo.refspecs_val(), &["+refs/notes/*:refs/notes/*".to_string()]);
+    /// ```
+    pub fn refspecs<T, U>(mut self, refspecs: T) -> Self
+    where
+        T: IntoIterator<Item=U>,
+        U: AsRef<str>,
+    {
+        self.refspecs = refspecs.into_iter().map(|x| x.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Set the remote to push to for this repo
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().remote("upstream").remote_val(), "upstream");
+    /// ```
+    pub fn remote<T>(mut self, remote: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.remote = Some(remote.as_ref().to_string());
+        self
+    }
+
+    // Set all network settings at once e.g. to carry them across threads
+    fn net(mut self, net: Net) -> Self {
+        self.net = net;
+        self
+    }
+
+    /// Set the number of times to retry a clone that failed due to a network or transport
+    /// error. Anything a failed attempt leaves behind is removed before retrying only when the
+    /// clone created the target directory. An existing target directory is never removed so
+    /// the clone fails instead if an attempt leaves it with content.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().retries(3).retries_val(), 3);
+    /// ```
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Set the path patterns to sparsely checkout. Only files matching the given pathspec
+    /// patterns e.g. `docs` or `src/*.rs` will be materialized in the working directory.
+    /// The patterns are recorded in `info/sparse-checkout` and all other index entries are
+    /// flagged as skip-worktree so the git cli treats the missing files as intentional.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().sparse(&["docs"]).sparse_val(), &["docs".to_string()]);
+    /// ```
+    pub fn sparse<T, U>(mut self, patterns: T) -> Self
+    where
+        T: IntoIterator<Item=U>,
+        U: AsRef<str>,
+    {
+        self.sparse = patterns.into_iter().map(|x| x.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Set to recursively initialize and update all submodules, including nested ones, once
+    /// the clone completes. Submodules report through the same progress callbacks.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Repo::new("foo").unwrap().with_submodules(true).submodules_val(), true);
+    /// ```
+    pub fn with_submodules(mut self, yes: bool) -> Self {
+        self.submodules = yes;
+        self
+    }
+
     /// Set the remote location for this repo
     ///
     /// ### Examples
@@ -379,6 +985,22 @@ impl<'a> Repo<'a> {
         self
     }
 
+    /// Set the LFS download progress callback to use which is given the total and smudged bytes.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// git::Repo::new("foo").unwrap().lfs(true).lfs_progress(|_total, _cur| {});
+    /// ```
+    pub fn lfs_progress<T>(mut self, func: T) -> Self
+    where
+        T: FnMut(u64, u64)+'a,
+    {
+        self.lfs_progress = Some(Box::new(func));
+        self
+    }
+
     /// Set the update progress callback to use.
     ///
     /// ### Examples
@@ -452,10 +1074,28 @@ impl<'a> Repo<'a> {
     /// assert!(sys::remove_all(&tmpdir).is_ok());
     /// ```
     pub fn clone(mut self) -> Result<PathBuf> {
+        // Note: callbacks must outlive the builder as they're shared with submodule updates
+        let xfer = self.xfer_progress.take().map(RefCell::new);
+        let checkout = self.checkout_progress.take().map(RefCell::new);
         let mut builder = RepoBuilder::new();
+        builder.bare(self.bare || self.mirror);
+        builder.clone_local(match self.local {
+            LocalClone::Auto => CloneLocal::Auto,
+            LocalClone::Hardlink => CloneLocal::Local,
+            LocalClone::Copy => CloneLocal::NoLinks,
+            LocalClone::Transport => CloneLocal::None,
+        });
+
+        // Mirror all remote refs directly into local refs
+        if self.mirror {
+            builder.remote_create(|repo, name, url| {
+                let remote = repo.remote_with_fetch(name, url, "+refs/*:refs/*")?;
+                repo.config()?.set_bool(&format!("remote.{}.mirror", name), true)?;
+                Ok(remote)
+            });
 
         // Clone only the target branch if set
-        if self.branch_only {
+        } else if self.branch_only {
             let branch = match &self.branch {
                 Some(x) => x.clone(),
                 None => "master".to_string(),
@@ -481,34 +1121,112 @@ impl<'a> Repo<'a> {
             });
         }
 
-        // Transfer progress callback
-        if self.xfer_progress.is_some() {
-            let mut xfer = self.xfer_progress.take().unwrap();
-            let mut callback = RemoteCallbacks::new();
-            callback.transfer_progress(move |stats| {
-                xfer(stats.total_objects() as u64, stats.indexed_objects() as u64);
-                true
-            });
-            let mut fetchopts = FetchOptions::new();
-            fetchopts.remote_callbacks(callback);
-            builder.fetch_options(fetchopts);
+        // Credentials, transfer progress callback and shallow clone depth
+        let mut fetchopts = fetch_opts(&self.net, xfer.as_ref());
+        if self.depth > 0 {
+            fetchopts.depth(self.depth as i32);
         }
+        builder.fetch_options(fetchopts);
 
-        // Checkout progress callback
-        if self.checkout_progress.is_some() {
-            let mut checkout = self.checkout_progress.take().unwrap();
-            let mut checkout_bldr = CheckoutBuilder::new();
-            checkout_bldr.progress(move |_, cur, total| checkout(total as u64, cur as u64));
+        // Checkout progress callback and sparse checkout paths
+        if checkout.is_some() || !self.sparse.is_empty() {
+            let mut checkout_bldr = checkout.as_ref().map(checkout_opts).unwrap_or_default();
+            for pattern in &self.sparse {
+                checkout_bldr.path(pattern);
+            }
             builder.with_checkout(checkout_bldr);
         }
 
-        let url = self.url_val().ok_or(Error::UrlNotSet)?;
+        let url = self.net.rewrite.rewrite(self.url_val().ok_or(Error::UrlNotSet)?);
         let path = self.path_val();
-        builder.clone(url, path)?;
+
+        // Retry transient failures backing off a little more each time. Only a target directory
+        // this clone created is removed between attempts so existing content is never lost.
+        let existed = path.exists();
+        let mut attempt = 0;
+        let repo = loop {
+            match self.net.auth.settle(|| builder.clone(&url, path)) {
+                Ok(repo) => break repo,
+                Err(err) if attempt < self.retries && is_transient(&err) => {
+                    attempt += 1;
+                    if !existed && path.exists() {
+                        sys::remove_all(path)?;
+                    } else if existed && !sys::paths(path)?.is_empty() {
+                        return Err(err.into());
+                    }
+                    thread::sleep(Duration::from_secs(attempt as u64));
+                },
+                Err(err) => return Err(err.into()),
+            }
+        };
+
+        // Record the sparse checkout for git tooling
+        if !self.sparse.is_empty() && !repo.is_bare() {
+            sparse_index(&repo, &self.sparse)?;
+        }
+
+        // Fetch any additional refspecs recording them for later fetches
+        if !self.refspecs.is_empty() {
+            for refspec in &self.refspecs {
+                repo.remote_add_fetch("origin", refspec)?;
+            }
+            let mut remote = repo.find_remote("origin")?;
+            self.net.auth.settle(|| remote.fetch(&self.refspecs, Some(&mut fetch_opts(&self.net, xfer.as_ref())), None))?;
+        }
+
+        // Recursively update submodules
+        if self.submodules {
+            update_submodules(&repo, &self.net, xfer.as_ref(), checkout.as_ref())?;
+        }
+
+        // Replace LFS pointers with their content
+        if self.lfs && !repo.is_bare() {
+            let lfs_progress = self.lfs_progress.take().map(RefCell::new);
+            lfs::smudge(&repo, lfs_progress.as_ref())?;
+        }
         Ok(self.path.clone())
     }
 
-    /// Update the given repo, cloning the repo if it doesn't exist.
+    /// Clone the repo locally, allowing the target directory to already exist. The target
+    /// directory must be empty or already contain a clone of this repo as determined by
+    /// comparing its origin URL, in which case it's left untouched. Any other directory
+    /// content results in an `Error::DirNotEmpty` or `Error::UrlMismatch` error.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_repo_clone_or_verify_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// let src = tmpdir.mash("src");
+    /// let dst = tmpdir.mash("dst");
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &src).is_ok());
+    /// assert!(sys::mkdir(&dst).is_ok());
+    /// assert!(git::Repo::new(&dst).unwrap().url(src.to_string().unwrap()).clone_or_verify().is_ok());
+    /// assert!(git::Repo::new(&dst).unwrap().url(src.to_string().unwrap()).clone_or_verify().is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn clone_or_verify(self) -> Result<PathBuf> {
+        let url = self.net.rewrite.rewrite(self.url_val().ok_or(Error::UrlNotSet)?);
+        let path = self.path_val();
+        if sys::is_dir(path) && !sys::paths(path)?.is_empty() {
+            if !is_repo(path) {
+                return Err(Error::dir_not_empty(path));
+            }
+            let repo = Repository::open(path)?;
+            let remote = repo.find_remote("origin")?;
+            let origin = remote.url().unwrap_or_default();
+            if trim_url(origin) != trim_url(&url) {
+                return Err(Error::url_mismatch(&url, origin));
+            }
+            return Ok(self.path);
+        }
+        self.clone()
+    }
+
+    /// Update the given repo, cloning the repo if it doesn't exist. Local commits that have
+    /// diverged from the fetched commits are reconciled using the pull options' strategy,
+    /// failing with `Error::FastForwardOnly` by default.
     ///
     /// ### Examples
     /// ```
@@ -527,43 +1245,178 @@ impl<'a> Repo<'a> {
         if !is_repo(self.path_val()) {
             return self.clone();
         } else {
-            let mut fetch_opts = Default::default();
+            // Fetch the latest with optional progress callback
+            let xfer = self.update_progress.take().map(RefCell::new);
+            let mut fetch_opts = fetch_opts(&self.net, xfer.as_ref());
             let repo = Repository::open(self.path_val())?;
 
-            // Fetch the latest with optional progress callback
-            if self.update_progress.is_some() {
-                let mut xfer = self.xfer_progress.take().unwrap();
-                let mut callback = RemoteCallbacks::new();
-                callback.transfer_progress(move |stats| {
-                    xfer(stats.total_objects() as u64, stats.indexed_objects() as u64);
-                    true
-                });
-                let mut fetchopts = FetchOptions::new();
-                fetchopts.remote_callbacks(callback);
-                fetch_opts = fetchopts;
+            // Fetch any additional refspecs first as FETCH_HEAD must reflect master
+            let mut remote = repo.find_remote("origin")?;
+            if !self.refspecs.is_empty() {
+                self.net.auth.settle(|| remote.fetch(&self.refspecs, Some(&mut fetch_opts), None))?;
             }
 
             // Fetch the latest from origin/master
-            repo.find_remote("origin")?.fetch(&["master"], Some(&mut fetch_opts), None)?;
+            self.net.auth.settle(|| remote.fetch(&["master"], Some(&mut fetch_opts), None))?;
             let fetch_head = repo.find_reference("FETCH_HEAD")?;
             let fetch_commit = repo.reference_to_annotated_commit(&fetch_head)?;
             let (analysis, _) = repo.merge_analysis(&[&fetch_commit])?;
 
             // Check if we need to update or not
-            if analysis.is_up_to_date() {
-                return Ok(self.path);
-            } else if analysis.is_fast_forward() {
+            if analysis.is_fast_forward() {
                 let refname = "refs/heads/master";
-                let mut reference = repo.find_reference(&refname)?;
+                let mut reference = repo.find_reference(refname)?;
                 reference.set_target(fetch_commit.id(), "Fast-Forward")?;
-                repo.set_head(&refname)?;
+                repo.set_head(refname)?;
                 repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
-            } else {
-                return Err(Error::FastForwardOnly);
+            } else if !analysis.is_up_to_date() {
+                let oid = fetch_commit.id().to_string();
+                match self.pull.strategy_val() {
+                    PullStrategy::FastForwardOnly => return Err(Error::FastForwardOnly),
+                    PullStrategy::Merge => {
+                        let message = match self.pull.message_val() {
+                            Some(message) => message.to_string(),
+                            None => format!("Merge branch 'master' of {}", repo.find_remote("origin")?.url().unwrap_or_default()),
+                        };
+                        merge(self.path_val(), &oid, &MergeOptions::new().message(message))?;
+                    },
+                    PullStrategy::Rebase => {
+                        rebase(self.path_val(), &oid, None, |_| {})?;
+                    },
+                }
+            }
+
+            // Replace LFS pointers with their content including any left by an earlier clone
+            if self.lfs {
+                let lfs_progress = self.lfs_progress.take().map(RefCell::new);
+                lfs::smudge(&repo, lfs_progress.as_ref())?;
             }
         }
         Ok(self.path)
     }
+
+    // Push the given refspecs to the repo's remote reporting progress through the transfer
+    // progress callback. Refs the remote rejects are reported as an error.
+    fn push<T: AsRef<str>>(&mut self, refspecs: &[T]) -> Result<()> {
+        match self.push_statuses(refspecs)?.into_iter().find(|x| !x.is_ok()) {
+            Some(status) => Err(Error::push_rejected(status.refname, status.rejected.unwrap_or_default())),
+            None => Ok(()),
+        }
+    }
+
+    // Push the given refspecs to the repo's remote reporting progress through the transfer
+    // progress callback and returning the status the remote gave for each ref.
+    fn push_statuses<T: AsRef<str>>(&mut self, refspecs: &[T]) -> Result<Vec<PushStatus>> {
+        let xfer = self.xfer_progress.take().map(RefCell::new);
+        let statuses = RefCell::new(vec![]);
+        let repo = Repository::open(self.path_val())?;
+        let mut remote = push_remote(&repo, self.remote_val())?;
+        let refspecs = expand_refspecs(&repo, refspecs)?;
+        self.net.auth.settle(|| remote.push(&refspecs, Some(&mut push_opts(&self.net, xfer.as_ref(), &statuses))))?;
+        Ok(statuses.into_inner())
+    }
+}
+
+// Progress bar style used for all terminal progress
+fn progress_style() -> ProgressStyle {
+    ProgressStyle::default_bar().progress_chars("=>-").template("[{elapsed_precise}][{bar:50.cyan/blue}] {pos:>7}/{len:7} ({eta}) - {msg}")
+}
+
+// Build remote callbacks that authenticate using the given network settings and optionally
+// report transfer progress through the given callback
+fn remote_callbacks<'a, 'b>(net: &Net, xfer: Option<&'b SharedProgress<'a>>) -> RemoteCallbacks<'b> {
+    let mut callback = RemoteCallbacks::new();
+    callback.credentials(net.auth.callback());
+    net.tls.apply(&mut callback);
+    if let Some(xfer) = xfer {
+        callback.transfer_progress(move |stats| {
+            (xfer.borrow_mut())(stats.total_objects() as u64, stats.indexed_objects() as u64);
+            true
+        });
+    }
+    callback
+}
+
+// Build fetch options that use the given network settings and optionally report transfer
+// progress through the given callback
+fn fetch_opts<'a, 'b>(net: &Net, xfer: Option<&'b SharedProgress<'a>>) -> git2::FetchOptions<'b> {
+    let mut fetchopts = git2::FetchOptions::new();
+    fetchopts.remote_callbacks(remote_callbacks(net, xfer));
+    fetchopts.proxy_options(net.proxy.options());
+    fetchopts
+}
+
+// Build push options that use the given network settings, optionally report transfer progress
+// through the given callback and record the status the remote reports for each ref
+fn push_opts<'a, 'b>(net: &Net, xfer: Option<&'b SharedProgress<'a>>, statuses: &'b RefCell<Vec<PushStatus>>) -> PushOptions<'b> {
+    let mut callback = remote_callbacks(net, None);
+    if let Some(xfer) = xfer {
+        callback.push_transfer_progress(move |cur, total, _| (xfer.borrow_mut())(total as u64, cur as u64));
+    }
+    callback.push_update_reference(move |refname, status| {
+        statuses.borrow_mut().push(PushStatus { refname: refname.to_string(), rejected: status.map(|x| x.to_string()) });
+        Ok(())
+    });
+    let mut pushopts = PushOptions::new();
+    pushopts.remote_callbacks(callback);
+    pushopts.proxy_options(net.proxy.options());
+    pushopts
+}
+
+// Build checkout options that report checkout progress through the given callback
+fn checkout_opts<'a, 'b>(checkout: &'b SharedProgress<'a>) -> CheckoutBuilder<'b> {
+    let mut checkout_bldr = CheckoutBuilder::new();
+    checkout_bldr.progress(move |_, cur, total| (checkout.borrow_mut())(total as u64, cur as u64));
+    checkout_bldr
+}
+
+// Trim the optional trailing slash and .git suffix from the given url for comparison
+fn trim_url(url: &str) -> &str {
+    let url = url.trim_end_matches('/');
+    url.strip_suffix(".git").unwrap_or(url)
+}
+
+// Returns true if the given error was caused by a network or transport failure that might
+// succeed if retried.
+fn is_transient(err: &git2::Error) -> bool {
+    matches!(err.class(), ErrorClass::Net | ErrorClass::Http | ErrorClass::Ssl | ErrorClass::Ssh | ErrorClass::Os | ErrorClass::Zlib)
+}
+
+// Record the sparse checkout patterns the way git does and flag all index entries that
+// don't match them as skip-worktree
+fn sparse_index(repo: &Repository, patterns: &[String]) -> Result<()> {
+    repo.config()?.set_bool("core.sparseCheckout", true)?;
+    let info = sys::mkdir(repo.path().mash("info"))?;
+    sys::write(info.mash("sparse-checkout"), format!("{}\n", patterns.join("\n")))?;
+
+    // Checking out with a pathspec leaves the other entries out of the index entirely
+    let pathspec = Pathspec::new(patterns.iter())?;
+    let mut index = repo.index()?;
+    index.read_tree(&repo.head()?.peel_to_tree()?)?;
+    for mut entry in index.iter().collect::<Vec<_>>() {
+        let path = PathBuf::from(String::from_utf8_lossy(&entry.path).to_string());
+        if !pathspec.matches_path(&path, PathspecFlags::DEFAULT) {
+            entry.flags |= IndexEntryFlag::EXTENDED.bits();
+            entry.flags_extended |= IndexEntryExtendedFlag::SKIP_WORKTREE.bits();
+            index.add(&entry)?;
+        }
+    }
+    index.write()?;
+    Ok(())
+}
+
+// Recursively initialize and update all submodules of the given repo
+fn update_submodules(repo: &Repository, net: &Net, xfer: Option<&SharedProgress>, checkout: Option<&SharedProgress>) -> Result<()> {
+    for mut submodule in repo.submodules()? {
+        let mut opts = git2::SubmoduleUpdateOptions::new();
+        opts.fetch(fetch_opts(net, xfer));
+        if let Some(checkout) = checkout {
+            opts.checkout(checkout_opts(checkout));
+        }
+        net.auth.settle(|| submodule.update(true, Some(&mut opts)))?;
+        update_submodules(&submodule.open()?, net, xfer, checkout)?;
+    }
+    Ok(())
 }
 
 /// Returns true if the `path` directory is a repositiory
@@ -610,7 +1463,7 @@ where
 
     // Test for the remote branch
     let refspec = format!("+refs/heads/{0:}:refs/remotes/origin/{0:}", branch.as_ref());
-    remote.fetch(&[&refspec], None, None)?;
+    remote.fetch(&[&refspec], Some(&mut fetch_opts(&Net::default(), None)), None)?;
     repo.find_reference("FETCH_HEAD")?;
     Ok(())
 }
@@ -618,6 +1471,7 @@ where
 // Unit tests
 // -------------------------------------------------------------------------------------------------
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use crate::prelude::*;
 
@@ -628,6 +1482,48 @@ mod tests {
         temp.mash(path.as_ref())
     }
 
+    // Stage everything in the working directory and commit it
+    fn commit_all(repo: &git2::Repository, msg: &str) {
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parents = repo.head().ok().map(|x| x.peel_to_commit().unwrap()).into_iter().collect::<Vec<_>>();
+        repo.commit(Some("HEAD"), &sig, &sig, msg, &tree, &parents.iter().collect::<Vec<_>>()).unwrap();
+    }
+
+    // Git daemon serving repos over the smart protocol that's killed when dropped
+    struct Daemon(std::process::Child);
+
+    impl Drop for Daemon {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    // Serve the repos in the given directory with git daemon returning the url of the given repo
+    fn daemon(path: &Path, repo: &str) -> (Daemon, String) {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        // Run git-daemon directly as `git daemon` leaves it running when killed
+        let exec_path = std::process::Command::new("git").arg("--exec-path").output().unwrap().stdout;
+        let child = std::process::Command::new(PathBuf::from(String::from_utf8(exec_path).unwrap().trim()).mash("git-daemon"))
+            .args(["--export-all", "--reuseaddr", "--listen=127.0.0.1"])
+            .arg(format!("--port={}", port))
+            .arg(format!("--base-path={}", path.display()))
+            .arg(path)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let daemon = Daemon(child);
+        while std::net::TcpStream::connect(("127.0.0.1", port)).is_err() {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        (daemon, format!("git://127.0.0.1:{}/{}", port, repo))
+    }
+
     #[test]
     fn test_repogroup() {
         let group = git::RepoGroup::new();
@@ -658,6 +1554,298 @@ mod tests {
         assert_eq!(repo.branch_only_val(), true);
     }
 
+    #[test]
+    fn test_repo_single_branch() {
+        let repo = git::Repo::new("foo").unwrap().single_branch("foobar");
+        assert_eq!(repo.branch_val(), Some("foobar"));
+        assert_eq!(repo.branch_only_val(), true);
+
+        // Only the target branch is tracked after cloning
+        let tmpdir = setup("git_repo_single_branch");
+        let src = tmpdir.mash("src");
+        let dst = tmpdir.mash("dst");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+        {
+            let repo = git2::Repository::open(&src).unwrap();
+            let head = repo.head().unwrap().peel_to_commit().unwrap();
+            repo.branch("other", &head, false).unwrap();
+        }
+        assert!(git::Repo::new(&dst).unwrap().url(src.to_string().unwrap()).single_branch("master").clone().is_ok());
+        let repo = git2::Repository::open(&dst).unwrap();
+        assert!(repo.find_reference("refs/remotes/origin/master").is_ok());
+        assert!(repo.find_reference("refs/remotes/origin/other").is_err());
+        let remote = repo.find_remote("origin").unwrap();
+        let refspecs: Vec<String> = remote.fetch_refspecs().unwrap().iter().flatten().map(|x| x.to_string()).collect();
+        assert_eq!(refspecs, vec!["+refs/heads/master:refs/remotes/origin/master".to_string()]);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_repo_bare_and_mirror() {
+        assert_eq!(git::Repo::new("foo").unwrap().bare_val(), false);
+        assert_eq!(git::Repo::new("foo").unwrap().bare(true).bare_val(), true);
+        assert_eq!(git::Repo::new("foo").unwrap().mirror_val(), false);
+        assert_eq!(git::Repo::new("foo").unwrap().mirror(true).mirror_val(), true);
+
+        let tmpdir = setup("git_repo_bare_and_mirror");
+        let src = tmpdir.mash("src");
+        let bare = tmpdir.mash("bare");
+        let mirror = tmpdir.mash("mirror");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+
+        // Bare clone has no working directory
+        assert!(git::Repo::new(&bare).unwrap().url(src.to_string().unwrap()).bare(true).clone().is_ok());
+        let repo = git2::Repository::open(&bare).unwrap();
+        assert_eq!(repo.is_bare(), true);
+        assert_eq!(bare.mash("README.md").exists(), false);
+        assert!(repo.find_reference("refs/remotes/origin/master").is_ok());
+
+        // Mirror clone maps all refs directly
+        assert!(git::Repo::new(&mirror).unwrap().url(src.to_string().unwrap()).mirror(true).clone().is_ok());
+        let repo = git2::Repository::open(&mirror).unwrap();
+        assert_eq!(repo.is_bare(), true);
+        assert!(repo.find_reference("refs/heads/master").is_ok());
+        assert!(repo.find_reference("refs/remotes/origin/master").is_ok());
+        assert!(repo.find_reference("refs/tags/3.11").is_ok());
+        assert_eq!(repo.config().unwrap().get_bool("remote.origin.mirror").unwrap(), true);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_repo_with_submodules() {
+        assert_eq!(git::Repo::new("foo").unwrap().submodules_val(), false);
+        assert_eq!(git::Repo::new("foo").unwrap().with_submodules(true).submodules_val(), true);
+
+        let tmpdir = setup("git_repo_with_submodules");
+        let (nested, sub, parent) = (tmpdir.mash("nested"), tmpdir.mash("sub"), tmpdir.mash("parent"));
+        let (dst1, dst2) = (tmpdir.mash("dst1"), tmpdir.mash("dst2"));
+        assert!(sys::remove_all(&tmpdir).is_ok());
+
+        // Create a chain of repos parent -> sub -> nested
+        let mut prev: Option<PathBuf> = None;
+        for path in [&nested, &sub, &parent].iter() {
+            let repo = git2::Repository::init(path).unwrap();
+            assert!(sys::write(path.mash("README.md"), path.base().unwrap()).is_ok());
+            if let Some(prev) = &prev {
+                let mut submodule = repo.submodule(&prev.to_string().unwrap(), Path::new(&prev.base().unwrap()), true).unwrap();
+                submodule.clone(None).unwrap();
+                submodule.add_finalize().unwrap();
+            }
+            commit_all(&repo, "Initial commit");
+            prev = Some(path.to_path_buf());
+        }
+
+        // Without submodules
+        assert!(git::Repo::new(&dst1).unwrap().url(parent.to_string().unwrap()).clone().is_ok());
+        assert_eq!(dst1.mash("sub/README.md").exists(), false);
+
+        // With submodules and progress
+        let mut checkouts = 0;
+        assert!(git::Repo::new(&dst2).unwrap().url(parent.to_string().unwrap()).with_submodules(true).checkout_progress(|_, _| checkouts += 1).clone().is_ok());
+        assert!(checkouts > 3);
+        assert_eq!(sys::readstring(dst2.mash("sub/README.md")).unwrap(), "sub");
+        assert_eq!(sys::readstring(dst2.mash("sub/nested/README.md")).unwrap(), "nested");
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_repo_clone_or_verify() {
+        let tmpdir = setup("git_repo_clone_or_verify");
+        let src = tmpdir.mash("src");
+        let dst = tmpdir.mash("dst");
+        let other = tmpdir.mash("other");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+        let url = src.to_string().unwrap();
+
+        // Empty existing directory is cloned into
+        assert!(sys::mkdir(&dst).is_ok());
+        assert_eq!(git::Repo::new(&dst).unwrap().url(&url).clone_or_verify().unwrap(), dst);
+        assert_eq!(dst.mash("README.md").exists(), true);
+
+        // Same repo is verified and left untouched
+        assert_eq!(git::Repo::new(&dst).unwrap().url(&url).clone_or_verify().unwrap(), dst);
+        assert_eq!(git::Repo::new(&dst).unwrap().url(format!("{}.git/", &url)).clone_or_verify().unwrap(), dst);
+
+        // Different repo is an error
+        let err = git::Repo::new(&dst).unwrap().url("foo").clone_or_verify().unwrap_err();
+        assert_eq!(err.to_string(), git::Error::url_mismatch("foo", &url).to_string());
+
+        // Non repo content is an error
+        assert!(sys::mkdir(&other).is_ok());
+        assert!(sys::touch(other.mash("file")).is_ok());
+        let err = git::Repo::new(&other).unwrap().url(&url).clone_or_verify().unwrap_err();
+        assert_eq!(err.to_string(), git::Error::dir_not_empty(&other).to_string());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_repo_auth() {
+        assert_eq!(git::Repo::new("foo").unwrap().auth_val().agent_val(), true);
+        assert_eq!(git::Repo::new("foo").unwrap().auth(git::Auth::new().agent(false)).auth_val().agent_val(), false);
+    }
+
+    #[test]
+    fn test_repo_proxy() {
+        assert_eq!(git::Repo::new("foo").unwrap().proxy_val(), &git::Proxy::Auto);
+        assert_eq!(git::Repo::new("foo").unwrap().proxy(git::Proxy::None).proxy_val(), &git::Proxy::None);
+
+        // Network operations go through the explicit proxy
+        let tmpdir = setup("git_repo_proxy");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let err = git::Repo::new(&tmpdir).unwrap().url("https://example.invalid/foo.git").proxy(git::Proxy::url("http://127.0.0.1:1")).clone().unwrap_err();
+        assert_eq!(err.to_string().contains("127.0.0.1"), true);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_repo_tls() {
+        assert_eq!(git::Repo::new("foo").unwrap().tls_val().insecure_val(), false);
+        assert_eq!(git::Repo::new("foo").unwrap().tls(git::Tls::new().insecure(true)).tls_val().insecure_val(), true);
+    }
+
+    #[test]
+    fn test_repo_local() {
+        assert_eq!(git::Repo::new("foo").unwrap().local_val(), git::LocalClone::Auto);
+        assert_eq!(git::Repo::new("foo").unwrap().local(git::LocalClone::Copy).local_val(), git::LocalClone::Copy);
+
+        let tmpdir = setup("git_repo_local");
+        let src = tmpdir.mash("src");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+        let url = format!("file://{}", src.to_string().unwrap());
+        let pack = "objects/pack/pack-171a18c9fb044199b704adb1f573d6b7a9c09c47.pack";
+
+        // Hardlinked objects even for file:// urls
+        let dst = tmpdir.mash("hardlink");
+        assert!(git::Repo::new(&dst).unwrap().url(&url).local(git::LocalClone::Hardlink).clone().is_ok());
+        assert_eq!(dst.mash(".git").mash(pack).metadata().unwrap().nlink() > 1, true);
+        assert_eq!(dst.mash("README.md").exists(), true);
+
+        // Copied objects
+        let dst = tmpdir.mash("copy");
+        assert!(git::Repo::new(&dst).unwrap().url(&url).local(git::LocalClone::Copy).clone().is_ok());
+        assert_eq!(dst.mash(".git").mash(pack).metadata().unwrap().nlink(), 1);
+
+        // Git-aware transport negotiates a new pack
+        let dst = tmpdir.mash("transport");
+        assert!(git::Repo::new(&dst).unwrap().url(&url).local(git::LocalClone::Transport).clone().is_ok());
+        assert_eq!(dst.mash(".git").mash(pack).exists(), false);
+        assert_eq!(dst.mash("README.md").exists(), true);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_repo_depth() {
+        assert_eq!(git::Repo::new("foo").unwrap().depth_val(), 0);
+        assert_eq!(git::Repo::new("foo").unwrap().depth(2).depth_val(), 2);
+
+        let tmpdir = setup("git_repo_depth");
+        let src = tmpdir.mash("src");
+        let dst = tmpdir.mash("dst");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+        let (_daemon, url) = daemon(&tmpdir, "src");
+        let count = |path: &Path| {
+            let repo = git2::Repository::open(path).unwrap();
+            let mut walk = repo.revwalk().unwrap();
+            walk.push_head().unwrap();
+            walk.count()
+        };
+        assert!(count(&src) > 2);
+
+        // History is truncated to the given depth
+        assert!(git::Repo::new(&dst).unwrap().url(&url).depth(2).clone().is_ok());
+        assert_eq!(count(&dst), 2);
+        assert_eq!(git2::Repository::open(&dst).unwrap().is_shallow(), true);
+        assert_eq!(dst.mash("README.md").exists(), true);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_repo_retries() {
+        assert_eq!(git::Repo::new("foo").unwrap().retries_val(), 0);
+        assert_eq!(git::Repo::new("foo").unwrap().retries(3).retries_val(), 3);
+
+        let tmpdir = setup("git_repo_retries");
+        let src = tmpdir.mash("src");
+        let dst = tmpdir.mash("dst");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+
+        // Nothing listens on the port so every attempt fails with a transient error
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let url = format!("git://127.0.0.1:{}/src", port);
+
+        // Directories the clone created are cleaned up between and after attempts
+        assert!(git::Repo::new(&dst).unwrap().url(&url).retries(1).clone().is_err());
+        assert_eq!(dst.exists(), false);
+        assert!(git::Repo::new(&dst).unwrap().url(&url).bare(true).retries(1).clone().is_err());
+        assert_eq!(dst.exists(), false);
+
+        // An existing empty directory is kept
+        assert!(sys::mkdir(&dst).is_ok());
+        assert!(git::Repo::new(&dst).unwrap().url(&url).retries(1).clone().is_err());
+        assert_eq!(dst.is_dir(), true);
+        assert!(git::Repo::new(&dst).unwrap().url(src.to_string().unwrap()).retries(1).clone().is_ok());
+        assert_eq!(git::Repo::new(&dst).unwrap().last_msg().unwrap(), "Use the workflow name for the badge".to_string());
+
+        // A complete clone is left untouched
+        assert!(git::Repo::new(&dst).unwrap().url(src.to_string().unwrap()).retries(1).clone().is_err());
+        assert_eq!(dst.mash("README.md").exists(), true);
+
+        // A repo without commits yet isn't mistaken for a partial clone and removed
+        let fresh = tmpdir.mash("fresh");
+        assert!(git2::Repository::init(&fresh).is_ok());
+        assert!(git::Repo::new(&fresh).unwrap().url(src.to_string().unwrap()).retries(1).clone().is_err());
+        assert_eq!(git::is_repo(&fresh), true);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_repo_sparse() {
+        assert!(git::Repo::new("foo").unwrap().sparse_val().is_empty());
+        assert_eq!(git::Repo::new("foo").unwrap().sparse(vec!["docs", "*.md"]).sparse_val(), &["docs".to_string(), "*.md".to_string()]);
+
+        let tmpdir = setup("git_repo_sparse");
+        let src = tmpdir.mash("src");
+        let dst = tmpdir.mash("dst");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+
+        // Only the matching paths are checked out
+        assert!(git::Repo::new(&dst).unwrap().url(src.to_string().unwrap()).sparse(["config", "*.md"]).clone().is_ok());
+        assert_eq!(dst.mash("README.md").exists(), true);
+        assert_eq!(dst.mash("config").is_dir(), true);
+        assert_eq!(dst.mash("Dockerfile").exists(), false);
+        assert_eq!(dst.mash("LICENSE").exists(), false);
+
+        // Sparse checkout is recorded for git tooling
+        let repo = git2::Repository::open(&dst).unwrap();
+        assert_eq!(repo.config().unwrap().get_bool("core.sparseCheckout").unwrap(), true);
+        assert_eq!(sys::readlines(dst.mash(".git/info/sparse-checkout")).unwrap(), vec!["config".to_string(), "*.md".to_string()]);
+        let index = repo.index().unwrap();
+        let skipped = |path: &str| {
+            let entry = index.get_path(Path::new(path), 0).unwrap();
+            git2::IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended).is_skip_worktree()
+        };
+        assert_eq!(skipped("Dockerfile"), true);
+        assert_eq!(skipped("LICENSE"), true);
+        assert_eq!(skipped("README.md"), false);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
     #[test]
     fn test_repo_path() {
         assert_eq!(git::Repo::new("foo").unwrap().path_val(), Path::new("foo").abs().unwrap().as_path());
@@ -846,6 +2034,107 @@ mod tests {
         assert!(sys::remove_all(&tmpdir).is_ok());
     }
 
+    #[test]
+    fn test_repo_update_pull() {
+        let tmpdir = setup("git_repo_update_pull");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+
+        // Bare remote with an upstream commit not yet in the local clones
+        let remote = tmpdir.mash("remote");
+        let url = remote.to_string().unwrap();
+        assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        let clone = |name: &str| {
+            let path = tmpdir.mash(name);
+            assert!(git::Repo::new(&path).unwrap().url(&url).clone().is_ok());
+            let repo = git2::Repository::open(&path).unwrap();
+            repo.config().unwrap().set_str("user.name", "test").unwrap();
+            repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+            (path, repo)
+        };
+        let (merged, merged_repo) = clone("merged");
+        let (rebased, rebased_repo) = clone("rebased");
+        let (upstream, upstream_repo) = clone("upstream");
+        assert!(sys::write(upstream.mash("foo"), "foo").is_ok());
+        commit_all(&upstream_repo, "Add foo");
+        upstream_repo.find_remote("origin").unwrap().push(&["refs/heads/master"], None).unwrap();
+        let upstream_head = upstream_repo.head().unwrap().target().unwrap();
+
+        // Fast-forward only by default
+        assert!(sys::write(merged.mash("bar"), "bar").is_ok());
+        commit_all(&merged_repo, "Add bar");
+        let local_head = merged_repo.head().unwrap().target().unwrap();
+        let err = git::Repo::new(&merged).unwrap().url(&url).update().unwrap_err();
+        assert_eq!(err.to_string(), git::Error::FastForwardOnly.to_string());
+        assert_eq!(merged_repo.head().unwrap().target(), Some(local_head));
+
+        // Merge
+        let opts = git::PullOptions::new().strategy(git::PullStrategy::Merge);
+        assert!(git::Repo::new(&merged).unwrap().url(&url).pull(opts).update().is_ok());
+        let head = merged_repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.parent_ids().collect::<Vec<_>>(), vec![local_head, upstream_head]);
+        assert_eq!(head.message().unwrap(), format!("Merge branch 'master' of {}", url));
+        assert_eq!(sys::readstring(merged.mash("foo")).unwrap(), "foo");
+        assert_eq!(sys::readstring(merged.mash("bar")).unwrap(), "bar");
+
+        // Rebase
+        assert!(sys::write(rebased.mash("baz"), "baz").is_ok());
+        commit_all(&rebased_repo, "Add baz");
+        let opts = git::PullOptions::new().strategy(git::PullStrategy::Rebase);
+        assert!(git::Repo::new(&rebased).unwrap().url(&url).pull(opts).update().is_ok());
+        let head = rebased_repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.parent_ids().collect::<Vec<_>>(), vec![upstream_head]);
+        assert_eq!(head.message(), Some("Add baz"));
+        assert_eq!(rebased_repo.head().unwrap().shorthand(), Some("master"));
+        assert_eq!(sys::readstring(rebased.mash("foo")).unwrap(), "foo");
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_repo_refspecs() {
+        let tmpdir = setup("git_repo_refspecs");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+
+        // Bare remote with notes and pull request refs
+        let remote = tmpdir.mash("remote");
+        let url = remote.to_string().unwrap();
+        assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        let bare = git2::Repository::open(&remote).unwrap();
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let head = bare.head().unwrap().peel_to_commit().unwrap();
+        let parent = head.parent_id(0).unwrap();
+        bare.note(&sig, &sig, None, head.id(), "reviewed", false).unwrap();
+        bare.reference("refs/pull/1/head", parent, false, "").unwrap();
+        let refspecs = ["+refs/notes/*:refs/notes/*", "+refs/pull/*/head:refs/remotes/origin/pr/*"];
+
+        // Clone fetches and records the refspecs
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(&url).refspecs(refspecs).clone().is_ok());
+        let repo = git2::Repository::open(&local).unwrap();
+        assert_eq!(repo.find_note(None, head.id()).unwrap().message(), Some("reviewed"));
+        assert_eq!(repo.refname_to_id("refs/remotes/origin/pr/1").unwrap(), parent);
+        assert_eq!(git::remotes(&local).unwrap()[0].fetch[1..], refspecs);
+
+        // Update fetches them before updating the branch
+        let commit = bare.commit(Some("HEAD"), &sig, &sig, "Empty", &head.tree().unwrap(), &[&head]).unwrap();
+        bare.reference("refs/pull/2/head", commit, false, "").unwrap();
+        assert!(git::Repo::new(&local).unwrap().url(&url).refspecs(refspecs).update().is_ok());
+        assert_eq!(repo.refname_to_id("refs/remotes/origin/pr/2").unwrap(), commit);
+        assert_eq!(repo.head().unwrap().target(), Some(commit));
+
+        // Push notes back
+        repo.note(&sig, &sig, None, commit, "approved", false).unwrap();
+        assert!(git::Repo::new(&local).unwrap().push_refs(&["refs/notes/*:refs/notes/*"]).is_ok());
+        assert_eq!(bare.find_note(None, commit).unwrap().message(), Some("approved"));
+
+        // Invalid refspecs
+        assert!(git::Repo::new(&local).unwrap().url(&url).refspecs(["bad refspec"]).update().is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
     #[test]
     fn test_update_with_progress() {
         let tmpdir = setup("git_repo_update_with_progress");
@@ -0,0 +1,152 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{ApplyOptions, Diff, Repository};
+
+/// Location a patch is applied to by `apply_patch`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ApplyLocation {
+    /// Apply to the worktree only, the same as `git apply`
+    #[default]
+    Worktree,
+
+    /// Apply to the index only leaving the worktree as is, the same as `git apply --cached`
+    Index,
+
+    /// Apply to both the index and the worktree, the same as `git apply --index`
+    Both,
+}
+
+/// Apply the given unified patch text e.g. as generated by `diff` to the repo's worktree, index
+/// or both. The whole patch is checked first so that nothing is applied when it doesn't apply
+/// cleanly, in which case `Error::PatchConflicts` is returned with the paths that conflict.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_apply_patch_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(sys::write(tmpdir.mash("README.md"), "foo\n").is_ok());
+/// let patch = git::diff(&tmpdir, "HEAD~1", "HEAD", &git::DiffOptions::new()).unwrap().patch;
+/// assert!(git::apply_patch(&tmpdir, &patch, git::ApplyLocation::Worktree).is_err());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn apply_patch<T, U>(path: T, patch: U, location: ApplyLocation) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let diff = Diff::from_buffer(patch.as_ref().as_bytes())?;
+    let location = match location {
+        ApplyLocation::Worktree => git2::ApplyLocation::WorkDir,
+        ApplyLocation::Index => git2::ApplyLocation::Index,
+        ApplyLocation::Both => git2::ApplyLocation::Both,
+    };
+
+    // Check each file on its own to report all the conflicting paths
+    if let Err(err) = repo.apply(&diff, location, Some(ApplyOptions::new().check(true))) {
+        let mut conflicts = vec![];
+        for (i, delta) in diff.deltas().enumerate() {
+            let mut n = 0;
+            let mut opts = ApplyOptions::new();
+            opts.check(true).delta_callback(|_| {
+                n += 1;
+                n - 1 == i
+            });
+            if repo.apply(&diff, location, Some(&mut opts)).is_err() {
+                if let Some(path) = delta.new_file().path().or_else(|| delta.old_file().path()) {
+                    conflicts.push(path.to_path_buf());
+                }
+            }
+        }
+        return Err(if conflicts.is_empty() { err.into() } else { Error::patch_conflicts(&conflicts) });
+    }
+    repo.apply(&diff, location, None)?;
+    Ok(())
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Commit the given content to the given files
+    fn commit(path: &Path, files: &[(&str, &str)]) {
+        for (file, content) in files.iter() {
+            assert!(sys::write(path.mash(file), content).is_ok());
+        }
+        assert!(git::CommitBuilder::new(path).stage_all(true).author("foo", "foo@example.com").message("Update").commit().is_ok());
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let tmpdir = setup("git_apply_patch");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        let staged = |path: &str| repo.status_file(Path::new(path)).unwrap();
+        let reset = || repo.reset(&repo.head().unwrap().peel(git2::ObjectType::Commit).unwrap(), git2::ResetType::Hard, None).unwrap();
+
+        // Generate a patch on a feature branch to backport to master
+        commit(&tmpdir, &[("foo", "1\n2\n3\n"), ("bar", "a\nb\n")]);
+        assert!(git::branch_create(&tmpdir, "feature", None, true).is_ok());
+        commit(
+            &tmpdir,
+            &[
+                ("foo", "1\ntwo\n3\n"),
+                ("bar", "a\nbee\n"),
+                ("baz", "new\n"),
+            ],
+        );
+        let patch = git::diff(&tmpdir, "HEAD~1", "HEAD", &git::DiffOptions::new()).unwrap().patch;
+        assert!(git::Repo::new(&tmpdir).unwrap().checkout("master", false).is_ok());
+
+        // Worktree only
+        assert!(git::apply_patch(&tmpdir, &patch, git::ApplyLocation::Worktree).is_ok());
+        assert_eq!(sys::readstring(tmpdir.mash("foo")).unwrap(), "1\ntwo\n3\n");
+        assert_eq!(sys::readstring(tmpdir.mash("baz")).unwrap(), "new\n");
+        assert_eq!(staged("foo"), git2::Status::WT_MODIFIED);
+        reset();
+        assert!(sys::remove(tmpdir.mash("baz")).is_ok());
+
+        // Index only
+        assert!(git::apply_patch(&tmpdir, &patch, git::ApplyLocation::Index).is_ok());
+        assert_eq!(sys::readstring(tmpdir.mash("foo")).unwrap(), "1\n2\n3\n");
+        assert_eq!(staged("foo"), git2::Status::INDEX_MODIFIED | git2::Status::WT_MODIFIED);
+        assert_eq!(staged("baz"), git2::Status::INDEX_NEW | git2::Status::WT_DELETED);
+        reset();
+
+        // Index and worktree
+        assert!(git::apply_patch(&tmpdir, &patch, git::ApplyLocation::Both).is_ok());
+        assert_eq!(sys::readstring(tmpdir.mash("bar")).unwrap(), "a\nbee\n");
+        assert_eq!(staged("bar"), git2::Status::INDEX_MODIFIED);
+        assert_eq!(staged("baz"), git2::Status::INDEX_NEW);
+
+        // Conflicts are reported and nothing is applied
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).message("Backport").commit().is_ok());
+        commit(&tmpdir, &[("foo", "1\n2\n3\n")]);
+        let err = git::apply_patch(&tmpdir, &patch, git::ApplyLocation::Both).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::patch_conflicts(&["bar"]).to_string());
+        assert_eq!(sys::readstring(tmpdir.mash("foo")).unwrap(), "1\n2\n3\n");
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+
+        // Errors
+        assert!(git::apply_patch(&tmpdir, "not a patch", git::ApplyLocation::Worktree).is_err());
+        assert!(git::apply_patch(tmpdir.mash("missing"), &patch, git::ApplyLocation::Worktree).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,775 @@
+use super::{to_worktree_eol, TreeAttrs};
+use crate::error::*;
+use ::tar::{Builder, EntryType, Header};
+use flate2::{
+    write::{DeflateEncoder, GzEncoder},
+    Compression, Crc,
+};
+use fungus::prelude::*;
+use git2::{Commit, Config, ObjectType, Oid, Repository, Time, Tree};
+use std::{
+    fs::File,
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Format of an archive written by `archive`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ArchiveFormat {
+    /// Uncompressed tar archive e.g. `foo.tar`
+    #[default]
+    Tar,
+
+    /// Gzip compressed tar archive e.g. `foo.tar.gz` or `foo.tgz`
+    TarGz,
+
+    /// Zip archive with deflate compressed files e.g. `foo.zip`
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Returns the format matching the extension of the given path i.e. `.tar`, `.tar.gz`, `.tgz`
+    /// or `.zip`, or None if it isn't an archive.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::ArchiveFormat::from_path("foo-1.0.0.tar.gz"), Some(git::ArchiveFormat::TarGz));
+    /// assert_eq!(git::ArchiveFormat::from_path("foo-1.0.0.txt"), None);
+    /// ```
+    pub fn from_path<T: AsRef<Path>>(path: T) -> Option<ArchiveFormat> {
+        let name = path.as_ref().file_name()?.to_str()?.to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+// Revision being archived with the sources of its files' attributes and placeholder values
+struct Revision<'a> {
+    repo: &'a Repository,
+    attrs: TreeAttrs,
+    config: Config,
+    commit: Option<Commit<'a>>,
+}
+
+// File, directory or symlink to be archived
+struct ArchiveEntry {
+    path: String,
+    mode: u32,
+    data: Vec<u8>,
+}
+
+impl ArchiveEntry {
+    fn is_dir(&self) -> bool {
+        self.mode & 0o170000 == 0o040000
+    }
+
+    fn is_link(&self) -> bool {
+        self.mode & 0o170000 == 0o120000
+    }
+}
+
+/// Write an archive of the files of the given revision to the given output file the same as `git
+/// archive --format=<format> [--prefix=<prefix>] -o <output> <rev>`. Paths with the
+/// `export-ignore` attribute are left out, looked up from the revision's `.gitattributes` files
+/// and `info/attributes` rather than the worktree's or index's. Files get the line endings of a
+/// checkout according to their `text` and `eol` attributes, and files with `export-subst` have
+/// their `$Format:<placeholders>$` keywords expanded for commits e.g. `%H`, `%h`, `%an`, `%ad`
+/// or `%s` with other placeholders left as is. The prefix is prepended
+/// to every path e.g. `foo-1.0.0/` to extract into a directory. Entries get the revision's commit
+/// time and git's default permissions, and tar archives record the commit id in a pax header
+/// readable by `git get-tar-commit-id`. Returns the number of files archived.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_archive_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let tarball = tmpdir.mash("../git_archive_doc.tar.gz");
+/// assert!(git::archive(&tmpdir, "3.11", git::ArchiveFormat::TarGz, &tarball, Some("alpine-base-3.11/")).unwrap() > 0);
+/// assert!(tar::extract_all(&tarball, tmpdir.mash("extracted")).is_ok());
+/// assert!(tmpdir.mash("extracted/alpine-base-3.11/README.md").exists());
+/// assert!(sys::remove(&tarball).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn archive<T, U, V>(path: T, rev: U, format: ArchiveFormat, output: V, prefix: Option<&str>) -> Result<usize>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<Path>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let object = repo.revparse_single(rev.as_ref()).map_err(|_| Error::revision_not_found(rev.as_ref()))?;
+    let tree = object.peel_to_tree()?;
+    let commit = object.peel_to_commit().ok();
+    let mtime = match commit.as_ref() {
+        Some(commit) => commit.committer().when().seconds().max(0) as u64,
+        None => SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or_default(),
+    };
+    let id = commit.as_ref().map(|x| x.id());
+
+    // Gather the entries in tree order with directories before their contents
+    let prefix = prefix.unwrap_or_default();
+    let mut entries = vec![];
+    if prefix.ends_with('/') {
+        entries.push(ArchiveEntry { path: prefix.to_string(), mode: 0o040000, data: vec![] });
+    }
+    let rev = Revision { repo: &repo, attrs: TreeAttrs::new(&repo, &tree)?, config: repo.config()?.snapshot()?, commit };
+    tree_entries(&rev, &tree, "", prefix, &mut entries)?;
+    let files = entries.iter().filter(|x| !x.is_dir()).count();
+
+    let file = File::create(output.as_ref()).map_err(FuError::from)?;
+    match format {
+        ArchiveFormat::Tar => {
+            write_tar(file, &entries, mtime, id)?;
+        },
+        ArchiveFormat::TarGz => {
+            let encoder = write_tar(GzEncoder::new(file, Compression::default()), &entries, mtime, id)?;
+            encoder.finish().map_err(FuError::from)?;
+        },
+        ArchiveFormat::Zip => write_zip(file, &entries, mtime)?,
+    };
+    Ok(files)
+}
+
+// Collect the entries of the given tree recursively skipping those with `export-ignore`.
+// Submodules are archived as empty directories the same as git does. Returns true if the tree
+// has any files, ignored or not, as git only leaves out directories without any.
+fn tree_entries(rev: &Revision, tree: &Tree, base: &str, prefix: &str, entries: &mut Vec<ArchiveEntry>) -> Result<bool> {
+    let mut files = false;
+    for entry in tree.iter() {
+        let name = entry.name().ok_or_else(|| git2::Error::from_str("invalid utf-8 path in tree"))?;
+        let path = format!("{}{}", base, name);
+        let attrs = rev.attrs.get(Path::new(&path), entry.kind() != Some(ObjectType::Blob))?;
+        files |= entry.kind() != Some(ObjectType::Tree);
+        if attrs.export_ignore {
+            continue;
+        }
+        match entry.kind() {
+            Some(ObjectType::Tree) => {
+                entries.push(ArchiveEntry { path: format!("{}{}/", prefix, path), mode: 0o040000, data: vec![] });
+                let subtree = rev.repo.find_tree(entry.id())?;
+                if tree_entries(rev, &subtree, &format!("{}/", path), prefix, entries)? {
+                    files = true;
+                } else {
+                    entries.pop();
+                }
+            },
+            Some(ObjectType::Blob) => {
+                let mode = entry.filemode() as u32;
+                let mut data = rev.repo.find_blob(entry.id())?.content().to_vec();
+
+                // Only regular files are converted the same as git does
+                if mode & 0o170000 == 0o100000 {
+                    data = to_worktree_eol(&rev.config, &attrs, data);
+                    if let (true, Some(commit)) = (attrs.export_subst, rev.commit.as_ref()) {
+                        data = export_subst(rev.repo, commit, &data)?;
+                    }
+                }
+                entries.push(ArchiveEntry { path: format!("{}{}", prefix, path), mode, data });
+            },
+            _ => entries.push(ArchiveEntry { path: format!("{}{}/", prefix, path), mode: 0o040000, data: vec![] }),
+        }
+    }
+    Ok(files)
+}
+
+// Expand the `$Format:<placeholders>$` keywords of the given content with the details of the
+// given commit the same as git does for files with the `export-subst` attribute
+fn export_subst(repo: &Repository, commit: &Commit, data: &[u8]) -> Result<Vec<u8>> {
+    let mut expanded = vec![];
+    let mut rest = data;
+    while let Some(start) = rest.windows(8).position(|x| x == b"$Format:") {
+        let end = match rest[start + 8..].iter().position(|x| *x == b'$') {
+            Some(end) => start + 8 + end,
+            None => break,
+        };
+        expanded.extend_from_slice(&rest[..start]);
+        expanded.extend_from_slice(format_commit(repo, commit, &String::from_utf8_lossy(&rest[start + 8..end]))?.as_bytes());
+        rest = &rest[end + 1..];
+    }
+    expanded.extend_from_slice(rest);
+    Ok(expanded)
+}
+
+// Returns the given pretty format expanded with the details of the given commit the same as
+// `git log --format=<format>` for its common placeholders, leaving others as is
+fn format_commit(repo: &Repository, commit: &Commit, format: &str) -> Result<String> {
+    let short = |oid: Oid| -> Result<String> { Ok(repo.find_object(oid, None)?.short_id()?.as_str().unwrap_or_default().to_string()) };
+    let mut formatted = String::new();
+    let mut rest = format;
+    while let Some(i) = rest.find('%') {
+        formatted.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        let (value, len) = match rest.as_bytes() {
+            [b'%', ..] => (Some("%".to_string()), 1),
+            [b'n', ..] => (Some("\n".to_string()), 1),
+            [b'H', ..] => (Some(commit.id().to_string()), 1),
+            [b'h', ..] => (Some(short(commit.id())?), 1),
+            [b'T', ..] => (Some(commit.tree_id().to_string()), 1),
+            [b't', ..] => (Some(short(commit.tree_id())?), 1),
+            [b'P', ..] => (Some(commit.parent_ids().map(|x| x.to_string()).collect::<Vec<_>>().join(" ")), 1),
+            [b'p', ..] => (Some(commit.parent_ids().map(short).collect::<Result<Vec<_>>>()?.join(" ")), 1),
+            [b's', ..] => (Some(commit.summary().unwrap_or_default().to_string()), 1),
+            [who @ (b'a' | b'c'), what @ (b'n' | b'e' | b'd' | b't' | b'I'), ..] => {
+                let sig = if *who == b'a' { commit.author() } else { commit.committer() };
+                let value = match what {
+                    b'n' => sig.name().unwrap_or_default().to_string(),
+                    b'e' => sig.email().unwrap_or_default().to_string(),
+                    b'd' => git_date(sig.when(), false),
+                    b't' => sig.when().seconds().to_string(),
+                    _ => git_date(sig.when(), true),
+                };
+                (Some(value), 2)
+            },
+            _ => (None, 0),
+        };
+        match value {
+            Some(value) => {
+                formatted.push_str(&value);
+                rest = &rest[len..];
+            },
+            None => formatted.push('%'),
+        }
+    }
+    formatted.push_str(rest);
+    Ok(formatted)
+}
+
+// Returns the given time in its own timezone in git's default date format e.g. `Thu Apr 7
+// 15:13:13 2005 -0700`, or in strict ISO 8601 format e.g. `2005-04-07T15:13:13-07:00`
+fn git_date(time: Time, iso: bool) -> String {
+    let offset = time.offset_minutes() as i64;
+    let secs = time.seconds() + offset * 60;
+    let days = secs.div_euclid(86400);
+    let rem = secs.rem_euclid(86400);
+    let (hour, min, sec) = (rem / 3600, rem % 3600 / 60, rem % 60);
+    let (year, month, day) = civil_date(days);
+    let sign = if offset < 0 { '-' } else { '+' };
+    let (tzh, tzm) = (offset.abs() / 60, offset.abs() % 60);
+    match iso {
+        true => format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}{:02}:{:02}", year, month, day, hour, min, sec, sign, tzh, tzm),
+        false => {
+            let weekday = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"][(days + 4).rem_euclid(7) as usize];
+            let month = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"][month as usize - 1];
+            format!("{} {} {} {:02}:{:02}:{:02} {} {}{:02}{:02}", weekday, month, day, hour, min, sec, year, sign, tzh, tzm)
+        },
+    }
+}
+
+// Write the given entries as a tar archive to the given writer with git's default umask of 002
+fn write_tar<W: Write>(writer: W, entries: &[ArchiveEntry], mtime: u64, commit: Option<Oid>) -> Result<W> {
+    let mut builder = Builder::new(writer);
+    if let Some(oid) = commit {
+        let record = format!("52 comment={}\n", oid);
+        let mut header = tar_header(EntryType::XGlobalHeader, 0o666, mtime);
+        header.set_path("pax_global_header").map_err(FuError::from)?;
+        header.set_size(record.len() as u64);
+        header.set_cksum();
+        builder.append(&header, record.as_bytes()).map_err(FuError::from)?;
+    }
+    for entry in entries {
+        if entry.is_dir() {
+            let mut header = tar_header(EntryType::Directory, 0o775, mtime);
+            builder.append_data(&mut header, &entry.path, io::empty()).map_err(FuError::from)?;
+        } else if entry.is_link() {
+            let mut header = tar_header(EntryType::Symlink, 0o777, mtime);
+            let target = String::from_utf8_lossy(&entry.data).to_string();
+            builder.append_link(&mut header, &entry.path, target).map_err(FuError::from)?;
+        } else {
+            let mode = if entry.mode & 0o111 != 0 { 0o775 } else { 0o664 };
+            let mut header = tar_header(EntryType::Regular, mode, mtime);
+            header.set_size(entry.data.len() as u64);
+            builder.append_data(&mut header, &entry.path, entry.data.as_slice()).map_err(FuError::from)?;
+        }
+    }
+    Ok(builder.into_inner().map_err(FuError::from)?)
+}
+
+// Returns a tar header of the given type owned by root the same as git does
+fn tar_header(kind: EntryType, mode: u32, mtime: u64) -> Header {
+    let mut header = Header::new_ustar();
+    header.set_entry_type(kind);
+    header.set_mode(mode);
+    header.set_mtime(mtime);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_size(0);
+    if let Some(header) = header.as_ustar_mut() {
+        header.uname[..4].copy_from_slice(b"root");
+        header.gname[..4].copy_from_slice(b"root");
+    }
+    header
+}
+
+// Write the given entries as a zip archive to the given writer. Files are deflated unless that
+// doesn't make them smaller. Executables and symlinks get unix modes the same as git does.
+fn write_zip<W: Write>(mut writer: W, entries: &[ArchiveEntry], mtime: u64) -> Result<()> {
+    let too_big = || git2::Error::from_str("archive is too big for the zip format");
+    let (time, date) = dos_time(mtime);
+    let mut central = vec![];
+    let mut offset: u64 = 0;
+    for entry in entries {
+        let mut crc = Crc::new();
+        crc.update(&entry.data);
+        let (method, data) = match entry.is_dir() || entry.data.is_empty() {
+            true => (0u16, entry.data.clone()),
+            false => {
+                let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+                encoder.write_all(&entry.data).map_err(FuError::from)?;
+                let deflated = encoder.finish().map_err(FuError::from)?;
+                match deflated.len() < entry.data.len() {
+                    true => (8, deflated),
+                    false => (0, entry.data.clone()),
+                }
+            },
+        };
+        let attrs: u32 = match entry.mode {
+            _ if entry.is_dir() => 0x10,
+            _ if entry.is_link() => 0o120777 << 16,
+            x if x & 0o111 != 0 => 0o100755 << 16,
+            _ => 0,
+        };
+        if offset > u32::MAX as u64 || data.len() > u32::MAX as usize || entry.data.len() > u32::MAX as usize {
+            return Err(too_big().into());
+        }
+
+        // Fields shared by the local and central headers
+        let mut common = vec![];
+        common.extend_from_slice(&10u16.to_le_bytes()); // Version needed to extract
+        common.extend_from_slice(&0x0800u16.to_le_bytes()); // Flags i.e. utf-8 names
+        common.extend_from_slice(&method.to_le_bytes());
+        common.extend_from_slice(&time.to_le_bytes());
+        common.extend_from_slice(&date.to_le_bytes());
+        common.extend_from_slice(&crc.sum().to_le_bytes());
+        common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(entry.data.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(entry.path.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes()); // Extra field length
+
+        let mut local = vec![];
+        local.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        local.extend_from_slice(&common);
+        local.extend_from_slice(entry.path.as_bytes());
+        writer.write_all(&local).map_err(FuError::from)?;
+        writer.write_all(&data).map_err(FuError::from)?;
+
+        central.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        central.extend_from_slice(&(if attrs >> 16 != 0 { 0x0317u16 } else { 0x0014 }).to_le_bytes()); // Made by unix or dos
+        central.extend_from_slice(&common);
+        central.extend_from_slice(&0u16.to_le_bytes()); // Comment length
+        central.extend_from_slice(&0u16.to_le_bytes()); // Disk number
+        central.extend_from_slice(&0u16.to_le_bytes()); // Internal attributes
+        central.extend_from_slice(&attrs.to_le_bytes()); // External attributes
+        central.extend_from_slice(&(offset as u32).to_le_bytes());
+        central.extend_from_slice(entry.path.as_bytes());
+        offset += (local.len() + data.len()) as u64;
+    }
+    if entries.len() > u16::MAX as usize || offset > u32::MAX as u64 {
+        return Err(too_big().into());
+    }
+
+    // End of central directory
+    let mut end = vec![];
+    end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes()); // Disk number
+    end.extend_from_slice(&0u16.to_le_bytes()); // Disk with the central directory
+    end.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    end.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    end.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    end.extend_from_slice(&(offset as u32).to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes()); // Comment length
+    writer.write_all(&central).map_err(FuError::from)?;
+    writer.write_all(&end).map_err(FuError::from)?;
+    writer.flush().map_err(FuError::from)?;
+    Ok(())
+}
+
+// Returns the given time in seconds since the epoch as MS-DOS time and date in UTC. Times before
+// 1980 can't be represented so are clamped to it.
+fn dos_time(secs: u64) -> (u16, u16) {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    let (hour, min, sec) = (rem / 3600, rem % 3600 / 60, rem % 60);
+
+    let (year, month, day) = civil_date(days);
+    if year < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let time = ((hour << 11) | (min << 5) | (sec / 2)) as u16;
+    let date = (((year - 1980).min(127) << 9) | (month << 5) | day) as u16;
+    (time, date)
+}
+
+// Returns the year, month and day of the given number of days since the epoch
+fn civil_date(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + if month <= 2 { 1 } else { 0 }, month, day)
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Returns the output of the given command
+    fn run(path: &Path, cmd: &str, args: &[&str]) -> String {
+        let output = Command::new(cmd).args(args).current_dir(path).output().unwrap();
+        assert_eq!(output.status.success(), true, "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    // Returns the sorted listing of the given tar file with permissions, sizes and times
+    fn tar_listing(path: &Path, tarball: &Path) -> Vec<String> {
+        let output = run(path, "tar", &["--numeric-owner", "-tvf", &tarball.to_string().unwrap()]);
+        let mut lines: Vec<String> = output.lines().map(|x| x.split_whitespace().collect::<Vec<_>>().join(" ")).collect();
+        lines.sort();
+        lines
+    }
+
+    #[test]
+    fn test_archive() {
+        let repo = setup("git_archive");
+        let tmpdir = setup("git_archive_out");
+        assert!(sys::remove_all(&repo).is_ok());
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(repo.mash("../../alpine-base.tgz"), &repo).is_ok());
+        assert!(sys::mkdir(&tmpdir).is_ok());
+
+        // Add an executable, a symlink, a nested file and ignored paths
+        assert!(sys::write(sys::mkdir(repo.mash("bin")).unwrap().mash("run.sh"), "#!/bin/sh\n").is_ok());
+        assert!(sys::chmod(repo.mash("bin/run.sh"), 0o755).is_ok());
+        assert!(sys::symlink(repo.mash("link"), "README.md").is_ok());
+        assert!(sys::write(sys::mkdir(repo.mash("docs/dev")).unwrap().mash("notes.md"), "notes").is_ok());
+        assert!(sys::write(repo.mash("secret.txt"), "secret").is_ok());
+        assert!(sys::write(repo.mash(".gitattributes"), "secret.txt export-ignore\ndocs/dev export-ignore\n").is_ok());
+        assert!(git::CommitBuilder::new(&repo).stage_all(true).author("foo", "foo@example.com").message("Add files").commit().is_ok());
+
+        // Tar matches git with and without a prefix
+        for prefix in &[None, Some("foo-1.0.0/")] {
+            let ours = tmpdir.mash("ours.tar");
+            let theirs = tmpdir.mash("theirs.tar");
+            let files = git::archive(&repo, "HEAD", git::ArchiveFormat::Tar, &ours, *prefix).unwrap();
+            let mut args = vec![
+                "archive".to_string(),
+                "--format=tar".to_string(),
+                "-o".to_string(),
+                theirs.to_string().unwrap(),
+            ];
+            if let Some(prefix) = prefix {
+                args.push(format!("--prefix={}", prefix));
+            }
+            args.push("HEAD".to_string());
+            run(&repo, "git", &args.iter().map(|x| x.as_str()).collect::<Vec<_>>());
+            assert_eq!(tar_listing(&tmpdir, &ours), tar_listing(&tmpdir, &theirs));
+            assert_eq!(files, tar_listing(&tmpdir, &ours).iter().filter(|x| !x.starts_with('d')).count());
+            let commit = Command::new("git").arg("get-tar-commit-id").stdin(std::fs::File::open(&ours).unwrap()).output().unwrap();
+            assert_eq!(String::from_utf8(commit.stdout).unwrap().trim(), run(&repo, "git", &["rev-parse", "HEAD"]).trim());
+        }
+
+        // Ignored paths are left out and the extracted contents match the revision
+        let ours = tmpdir.mash("ours.tar.gz");
+        assert!(git::archive(&repo, "HEAD", git::ArchiveFormat::TarGz, &ours, Some("foo/")).is_ok());
+        assert_eq!(fungus::enc::gzip::is_gzipped(&ours).unwrap(), true);
+        let extracted = tmpdir.mash("extracted");
+        assert!(tar::extract_all(&ours, &extracted).is_ok());
+        assert_eq!(extracted.mash("foo/secret.txt").exists(), false);
+        assert_eq!(extracted.mash("foo/docs/dev").exists(), false);
+        assert_eq!(sys::readstring(extracted.mash("foo/bin/run.sh")).unwrap(), "#!/bin/sh\n");
+        assert_eq!(sys::is_exec(extracted.mash("foo/bin/run.sh")), true);
+        assert_eq!(std::fs::read_link(extracted.mash("foo/link")).unwrap(), PathBuf::from("README.md"));
+        assert_eq!(sys::readstring(extracted.mash("foo/README.md")).unwrap(), sys::readstring(repo.mash("README.md")).unwrap());
+
+        // Zip matches git's listing and passes an integrity check
+        let ours = tmpdir.mash("ours.zip");
+        let theirs = tmpdir.mash("theirs.zip");
+        assert!(git::archive(&repo, "HEAD", git::ArchiveFormat::Zip, &ours, Some("foo/")).is_ok());
+        run(
+            &repo,
+            "git",
+            &[
+                "archive",
+                "--format=zip",
+                "--prefix=foo/",
+                "-o",
+                &theirs.to_string().unwrap(),
+                "HEAD",
+            ],
+        );
+        let listing = |zip: &Path| {
+            let mut names = run(
+                &tmpdir,
+                "python3",
+                &[
+                    "-c",
+                    "import sys,zipfile;[print(x.filename, oct(x.external_attr >> 16), x.file_size, x.CRC) for x in zipfile.ZipFile(sys.argv[1]).infolist()]",
+                    &zip.to_string().unwrap(),
+                ],
+            )
+            .lines()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(listing(&ours), listing(&theirs));
+        run(
+            &tmpdir,
+            "python3",
+            &[
+                "-c",
+                "import sys,zipfile;assert zipfile.ZipFile(sys.argv[1]).testzip() is None",
+                &ours.to_string().unwrap(),
+            ],
+        );
+
+        // Older revisions and errors
+        assert!(git::archive(&repo, "3.11", git::ArchiveFormat::Tar, tmpdir.mash("old.tar"), None).unwrap() > 0);
+        assert_eq!(git::archive(&repo, "missing", git::ArchiveFormat::Tar, tmpdir.mash("missing.tar"), None).unwrap_err().to_string(), git::Error::revision_not_found("missing").to_string());
+
+        assert!(sys::remove_all(&repo).is_ok());
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    // Returns the sorted tar listings of our and git's archive of the given revision with the
+    // given prefix
+    fn tar_listings(repo: &Path, tmpdir: &Path, rev: &str, prefix: Option<&str>) -> (Vec<String>, Vec<String>) {
+        let (ours, theirs) = (tmpdir.mash("ours.tar"), tmpdir.mash("theirs.tar"));
+        let files = git::archive(repo, rev, git::ArchiveFormat::Tar, &ours, prefix).unwrap();
+        let output = theirs.to_string().unwrap();
+        let prefix = prefix.map(|x| format!("--prefix={}", x));
+        let mut args = vec!["archive", "--format=tar", "-o", &output];
+        args.extend(prefix.as_deref());
+        args.push(rev);
+        run(repo, "git", &args);
+        let listing = tar_listing(tmpdir, &ours);
+        assert_eq!(files, listing.iter().filter(|x| !x.starts_with('d')).count());
+        (listing, tar_listing(tmpdir, &theirs))
+    }
+
+    #[test]
+    fn test_archive_export_ignore() {
+        let repo = setup("git_archive_export_ignore");
+        let tmpdir = setup("git_archive_export_ignore_out");
+        assert!(sys::remove_all(&repo).is_ok());
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(repo.mash("../../alpine-base.tgz"), &repo).is_ok());
+        assert!(sys::mkdir(&tmpdir).is_ok());
+
+        // Glob, directory and nested patterns and an unset attribute overriding a glob
+        assert!(sys::write(repo.mash("debug.log"), "debug").is_ok());
+        assert!(sys::write(sys::mkdir(repo.mash("logs")).unwrap().mash("keep.log"), "keep").is_ok());
+        assert!(sys::write(sys::mkdir(repo.mash("build/out")).unwrap().mash("app"), "app").is_ok());
+        assert!(sys::write(sys::mkdir(repo.mash("docs")).unwrap().mash("private.md"), "private").is_ok());
+        assert!(sys::write(repo.mash("docs/public.md"), "public").is_ok());
+        assert!(sys::write(repo.mash("docs/.gitattributes"), "private.md export-ignore\n").is_ok());
+        assert!(sys::write(repo.mash(".gitattributes"), "*.log export-ignore\nlogs/keep.log -export-ignore\nbuild/ export-ignore\n").is_ok());
+        assert!(git::CommitBuilder::new(&repo).stage_all(true).author("foo", "foo@example.com").message("Add files").commit().is_ok());
+
+        // Same entries as git with ignored directories and their contents left out entirely
+        let (ours, theirs) = tar_listings(&repo, &tmpdir, "HEAD", None);
+        assert_eq!(ours, theirs);
+        let names: Vec<&str> = ours.iter().map(|x| x.rsplit(' ').next().unwrap()).collect();
+        assert_eq!(names.contains(&"debug.log"), false);
+        assert_eq!(names.contains(&"logs/keep.log"), true);
+        assert_eq!(names.iter().any(|x| x.starts_with("build")), false);
+        assert_eq!(names.contains(&"docs/private.md"), false);
+        assert_eq!(names.contains(&"docs/public.md"), true);
+        assert_eq!(names.contains(&"docs/.gitattributes"), true);
+
+        // Directories with only ignored files are kept while those with only ignored directories
+        // are left out the same as git
+        assert!(sys::remove(repo.mash("docs/public.md")).is_ok());
+        assert!(git::CommitBuilder::new(&repo).stage_all(true).author("foo", "foo@example.com").message("Remove public").commit().is_ok());
+        assert!(sys::write(repo.mash("docs/.gitattributes"), "* export-ignore\n").is_ok());
+        assert!(git::CommitBuilder::new(&repo).stage_all(true).author("foo", "foo@example.com").message("Ignore docs").commit().is_ok());
+        let (ours, theirs) = tar_listings(&repo, &tmpdir, "HEAD", Some("foo/"));
+        assert_eq!(ours, theirs);
+        assert_eq!(ours.iter().any(|x| x.ends_with(" foo/docs/")), true);
+        assert_eq!(ours.iter().any(|x| x.contains("foo/docs/private.md")), false);
+        assert!(sys::write(repo.mash(".gitattributes"), "*.log export-ignore\nlogs/keep.log -export-ignore\nbuild/ export-ignore\ndocs export-ignore\n").is_ok());
+        assert!(sys::write(sys::mkdir(repo.mash("pkg/build")).unwrap().mash("app"), "app").is_ok());
+        assert!(git::CommitBuilder::new(&repo).stage_all(true).author("foo", "foo@example.com").message("Add pkg").commit().is_ok());
+        let (ours, theirs) = tar_listings(&repo, &tmpdir, "HEAD", Some("foo/"));
+        assert_eq!(ours, theirs);
+        assert_eq!(ours.iter().any(|x| x.contains("foo/docs") || x.contains("foo/pkg")), false);
+
+        // Attributes come from the archived revision not the worktree or index the same as git
+        assert!(sys::write(repo.mash(".gitattributes"), "README.md export-ignore\n").is_ok());
+        run(&repo, "git", &["add", ".gitattributes"]);
+        assert!(sys::write(repo.mash(".gitattributes"), "LICENSE export-ignore\n").is_ok());
+        let (ours, theirs) = tar_listings(&repo, &tmpdir, "HEAD", None);
+        assert_eq!(ours, theirs);
+        assert_eq!(ours.iter().any(|x| x.ends_with(" README.md")), true);
+        assert_eq!(ours.iter().any(|x| x.contains("debug.log") || x.contains("build/")), false);
+        for rev in &["HEAD~1", "HEAD~3", "3.11"] {
+            let (ours, theirs) = tar_listings(&repo, &tmpdir, rev, None);
+            assert_eq!(ours, theirs, "{}", rev);
+        }
+        let (ours, _) = tar_listings(&repo, &tmpdir, "HEAD~3", None);
+        assert_eq!(ours.iter().any(|x| x.ends_with(" docs/private.md")), false);
+        assert_eq!(ours.iter().any(|x| x.ends_with(" build/out/app")), false);
+        let (ours, _) = tar_listings(&repo, &tmpdir, "HEAD~1", None);
+        assert_eq!(ours.iter().any(|x| x.ends_with(" docs/")), true);
+
+        // Bare repos use the archived revision's attributes the same as git
+        let bare = tmpdir.mash("bare.git");
+        run(&tmpdir, "git", &["clone", "-q", "--bare", &repo.to_string().unwrap(), &bare.to_string().unwrap()]);
+        for rev in &["HEAD", "HEAD~3"] {
+            let (ours, theirs) = tar_listings(&bare, &tmpdir, rev, None);
+            assert_eq!(ours, theirs, "{}", rev);
+            assert_eq!(ours.iter().any(|x| x.contains("debug.log")), false);
+        }
+
+        assert!(sys::remove_all(&repo).is_ok());
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_archive_conversions() {
+        let repo = setup("git_archive_conversions");
+        let tmpdir = setup("git_archive_conversions_out");
+        assert!(sys::remove_all(&repo).is_ok());
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(repo.mash("../../alpine-base.tgz"), &repo).is_ok());
+        assert!(sys::mkdir(&tmpdir).is_ok());
+
+        // Returns our and git's archive of the given revision extracted
+        let extracted = |rev: &str| {
+            let (ours, theirs) = (tmpdir.mash("ours"), tmpdir.mash("theirs"));
+            assert!(sys::remove_all(&ours).is_ok());
+            assert!(sys::remove_all(&theirs).is_ok());
+            assert!(git::archive(&repo, rev, git::ArchiveFormat::Tar, tmpdir.mash("ours.tar"), Some("ours/")).is_ok());
+            run(&repo, "git", &["archive", "--prefix=theirs/", "-o", &tmpdir.mash("theirs.tar").to_string().unwrap(), rev]);
+            run(&tmpdir, "tar", &["-xf", "ours.tar"]);
+            run(&tmpdir, "tar", &["-xf", "theirs.tar"]);
+            (ours, theirs)
+        };
+        let assert_same = |ours: &Path, theirs: &Path| {
+            let files = sys::all_files(theirs).unwrap();
+            assert_eq!(sys::all_files(ours).unwrap().len(), files.len());
+            for file in files {
+                let name = file.trim_prefix(theirs);
+                assert_eq!(std::fs::read(ours.mash(&name)).unwrap(), std::fs::read(&file).unwrap(), "{}", name.display());
+            }
+        };
+
+        // Line ending attributes and placeholders in a commit with a timezone
+        let attributes = "*.bat eol=crlf\n*.txt text\nauto.md text=auto\nbin.dat -text eol=crlf\nVERSION export-subst\n";
+        assert!(sys::write(repo.mash(".gitattributes"), attributes).is_ok());
+        assert!(sys::write(repo.mash("run.bat"), "echo foo\necho bar\n").is_ok());
+        assert!(sys::write(repo.mash("notes.txt"), "foo\r\nbar\n").is_ok());
+        assert!(sys::write(repo.mash("auto.md"), "foo\nbar\n").is_ok());
+        assert!(sys::write(repo.mash("bin.dat"), "foo\nbar\n").is_ok());
+        let version = "$Format:%H %h %T %t %P %p %an <%ae> %ad %at %aI %cn %ce %cd %ct %cI %s%n%% %zz$\n$Format:%H";
+        assert!(sys::write(repo.mash("VERSION"), version).is_ok());
+        run(&repo, "git", &["add", "-A"]);
+        let output = Command::new("git")
+            .args(["-c", "user.name=foo", "-c", "user.email=foo@example.com", "commit", "-q", "-m", "Add conversions"])
+            .env("GIT_AUTHOR_DATE", "2005-04-07T05:03:03-0700")
+            .env("GIT_COMMITTER_DATE", "2020-11-01T23:04:05+0530")
+            .current_dir(&repo)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.success(), true);
+
+        // Same content as git with LF converted to CRLF and placeholders expanded
+        let (ours, theirs) = extracted("HEAD");
+        assert_same(&ours, &theirs);
+        assert_eq!(sys::readstring(ours.mash("run.bat")).unwrap(), "echo foo\r\necho bar\r\n");
+        assert_eq!(sys::readstring(ours.mash("bin.dat")).unwrap(), "foo\nbar\n");
+        let version = sys::readstring(ours.mash("VERSION")).unwrap();
+        assert!(version.contains(" foo <foo@example.com> Thu Apr 7 05:03:03 2005 -0700 1112875383 2005-04-07T05:03:03-07:00 "), "{}", version);
+        assert!(version.contains(" Sun Nov 1 23:04:05 2020 +0530 "), "{}", version);
+        assert!(version.ends_with(" Add conversions\n% %zz\n$Format:%H"), "{}", version);
+
+        // Config line endings apply to text files the same as git
+        for (key, value) in &[("core.eol", "crlf"), ("core.autocrlf", "true"), ("core.autocrlf", "input")] {
+            run(&repo, "git", &["config", key, value]);
+            let (ours, theirs) = extracted("HEAD");
+            assert_same(&ours, &theirs);
+            run(&repo, "git", &["config", "--unset", key]);
+        }
+        run(&repo, "git", &["config", "core.autocrlf", "true"]);
+        let (ours, _) = extracted("HEAD");
+        assert_eq!(sys::readstring(ours.mash("notes.txt")).unwrap(), "foo\r\nbar\r\n");
+        assert_eq!(sys::readstring(ours.mash("auto.md")).unwrap(), "foo\r\nbar\r\n");
+
+        // Trees have no commit to expand placeholders with
+        let (ours, theirs) = extracted("HEAD^{tree}");
+        assert_same(&ours, &theirs);
+        assert_eq!(sys::readstring(ours.mash("VERSION")).unwrap().starts_with("$Format:%H %h"), true);
+
+        assert!(sys::remove_all(&repo).is_ok());
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_archive_prefix() {
+        let repo = setup("git_archive_prefix");
+        let tmpdir = setup("git_archive_prefix_out");
+        assert!(sys::remove_all(&repo).is_ok());
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(repo.mash("../../alpine-base.tgz"), &repo).is_ok());
+        assert!(sys::mkdir(&tmpdir).is_ok());
+
+        // Same entries as git for nested, file name and empty prefixes
+        for prefix in &[None, Some(""), Some("foo/"), Some("foo/bar/"), Some("foo-")] {
+            let (ours, theirs) = tar_listings(&repo, &tmpdir, "HEAD", *prefix);
+            assert_eq!(ours, theirs, "{:?}", prefix);
+            let names: Vec<&str> = ours.iter().map(|x| x.rsplit(' ').next().unwrap()).collect();
+            let readme = format!("{}README.md", prefix.unwrap_or_default());
+            assert!(names.contains(&readme.as_str()), "{:?}", names);
+            assert!(names.iter().all(|x| x.starts_with(prefix.unwrap_or_default())));
+        }
+
+        // File name prefixes don't add a directory
+        let (ours, _) = tar_listings(&repo, &tmpdir, "HEAD", Some("foo-"));
+        assert_eq!(ours.iter().any(|x| x.ends_with(" foo-/") || x.ends_with(" foo/")), false);
+
+        // Extracting places everything under the prefix
+        let tarball = tmpdir.mash("ours.tar.gz");
+        assert!(git::archive(&repo, "HEAD", git::ArchiveFormat::TarGz, &tarball, Some("foo/bar/")).is_ok());
+        let extracted = tmpdir.mash("extracted");
+        assert!(tar::extract_all(&tarball, &extracted).is_ok());
+        assert_eq!(sys::paths(&extracted).unwrap(), vec![extracted.mash("foo")]);
+        assert_eq!(sys::paths(extracted.mash("foo")).unwrap(), vec![extracted.mash("foo/bar")]);
+        assert_eq!(sys::readstring(extracted.mash("foo/bar/README.md")).unwrap(), sys::readstring(repo.mash("README.md")).unwrap());
+
+        // Zip entries get the same prefix
+        let zip = tmpdir.mash("ours.zip");
+        assert!(git::archive(&repo, "HEAD", git::ArchiveFormat::Zip, &zip, Some("foo-")).is_ok());
+        let names = run(&tmpdir, "python3", &["-c", "import sys,zipfile;[print(x) for x in zipfile.ZipFile(sys.argv[1]).namelist()]", &zip.to_string().unwrap()]);
+        assert!(names.lines().all(|x| x.starts_with("foo-")), "{}", names);
+
+        assert!(sys::remove_all(&repo).is_ok());
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,333 @@
+use super::TMPDIR;
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{AttrCheckFlags, Index, Repository, Tree};
+use std::process::{Command, Stdio};
+
+/// Value of a gitattribute for a path as returned by `attr`
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum AttrValue {
+    /// The attribute isn't specified for the path
+    #[default]
+    Unspecified,
+
+    /// The attribute is set e.g. `text`
+    Set,
+
+    /// The attribute is unset e.g. `-text`
+    Unset,
+
+    /// The attribute is set to the given value e.g. `eol=lf`
+    Value(String),
+}
+
+impl AttrValue {
+    /// Returns true if the attribute is set e.g. `export-ignore`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::AttrValue::Set.is_set(), true);
+    /// assert_eq!(git::AttrValue::Unset.is_set(), false);
+    /// ```
+    pub fn is_set(&self) -> bool {
+        *self == AttrValue::Set
+    }
+
+    /// Returns the attribute's value if it was set to one e.g. `lf` for `eol=lf`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::AttrValue::Value("lf".to_string()).value(), Some("lf"));
+    /// assert_eq!(git::AttrValue::Set.value(), None);
+    /// ```
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            AttrValue::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Attributes that change how git handles a path as returned by `attrs`
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct FileAttrs {
+    pub text: AttrValue,        // Line ending normalization e.g. `text`, `-text` or `text=auto`
+    pub eol: Option<String>,    // Line ending used in the worktree e.g. `lf` or `crlf`
+    pub diff: AttrValue,        // Diff driver e.g. `diff=rust`, or unset to diff as binary
+    pub filter: Option<String>, // Filter driver run on checkout and add e.g. `lfs`
+    pub export_ignore: bool,    // True if the path is left out of archives
+    pub export_subst: bool,     // True if placeholders are expanded in archives
+}
+
+impl FileAttrs {
+    /// Returns true if the path is diffed as binary e.g. with the `binary` macro or `-diff`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let attrs = git::FileAttrs { diff: git::AttrValue::Unset, ..Default::default() };
+    /// assert_eq!(attrs.is_binary(), true);
+    /// ```
+    pub fn is_binary(&self) -> bool {
+        self.diff == AttrValue::Unset
+    }
+}
+
+/// Get the value of the given attribute for the given path relative to the top of the repo
+/// according to the repo's `.gitattributes` files, `info/attributes` and the global and system
+/// attributes, the same as `git check-attr <name> -- <file>`. The worktree's attributes files
+/// take precedence over the index's. The path doesn't need to exist.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_attr_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(sys::write(tmpdir.mash(".gitattributes"), "*.sh eol=lf\n").is_ok());
+/// assert_eq!(git::attr(&tmpdir, "build.sh", "eol").unwrap(), git::AttrValue::Value("lf".to_string()));
+/// assert_eq!(git::attr(&tmpdir, "README.md", "eol").unwrap(), git::AttrValue::Unspecified);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn attr<T, U, V>(path: T, file: U, name: V) -> Result<AttrValue>
+where
+    T: AsRef<Path>,
+    U: AsRef<Path>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    get_attr(&repo, file.as_ref(), name.as_ref(), AttrCheckFlags::FILE_THEN_INDEX)
+}
+
+/// Get the attributes that change how git handles the given path relative to the top of the repo
+/// i.e. `text`, `eol`, `diff`, `filter`, `export-ignore` and `export-subst`. See `attr` for how
+/// they're looked up.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_attrs_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(sys::write(tmpdir.mash(".gitattributes"), "*.png binary\n.github export-ignore\n").is_ok());
+/// assert_eq!(git::attrs(&tmpdir, "logo.png").unwrap().is_binary(), true);
+/// assert_eq!(git::attrs(&tmpdir, ".github").unwrap().export_ignore, true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn attrs<T, U>(path: T, file: U) -> Result<FileAttrs>
+where
+    T: AsRef<Path>,
+    U: AsRef<Path>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    file_attrs(&repo, file.as_ref(), AttrCheckFlags::FILE_THEN_INDEX)
+}
+
+// Attribute lookups against the `.gitattributes` files of a tree rather than those of the
+// worktree or index, the same as `git archive` does for the revision it archives
+pub(crate) struct TreeAttrs {
+    repo: Repository,
+    workdir: PathBuf,
+}
+
+impl TreeAttrs {
+    // libgit2 only reads attributes from the worktree, the index or HEAD so the tree is read into
+    // the in-memory index of a separate handle on the repo. It only knows a path is a directory,
+    // for patterns like `build/`, when the path is a directory in the worktree so the handle gets
+    // a scratch worktree of the tree's directories.
+    pub(crate) fn new(repo: &Repository, tree: &Tree) -> Result<Self> {
+        let workdir = user::temp_dir(TMPDIR)?;
+        let attrs = Self { repo: Repository::open(repo.path())?, workdir };
+        attrs.repo.set_workdir(&attrs.workdir, false)?;
+        let mut index = Index::new()?;
+        index.read_tree(tree)?;
+        attrs.repo.set_index(&mut index)?;
+        Ok(attrs)
+    }
+
+    // Returns the attributes that change how git handles the given path in the tree
+    pub(crate) fn get(&self, file: &Path, is_dir: bool) -> Result<FileAttrs> {
+        if is_dir {
+            sys::mkdir(self.workdir.mash(file))?;
+        }
+        file_attrs(&self.repo, file, AttrCheckFlags::INDEX_ONLY)
+    }
+}
+
+impl Drop for TreeAttrs {
+    fn drop(&mut self) {
+        let _ = sys::remove_all(&self.workdir);
+    }
+}
+
+// Returns the attributes that change how git handles the given path
+fn file_attrs(repo: &Repository, file: &Path, flags: AttrCheckFlags) -> Result<FileAttrs> {
+    Ok(FileAttrs {
+        text: get_attr(repo, file, "text", flags)?,
+        eol: get_attr(repo, file, "eol", flags)?.value().map(|x| x.to_string()),
+        diff: get_attr(repo, file, "diff", flags)?,
+        filter: get_attr(repo, file, "filter", flags)?.value().map(|x| x.to_string()),
+        export_ignore: get_attr(repo, file, "export-ignore", flags)?.is_set(),
+        export_subst: get_attr(repo, file, "export-subst", flags)?.is_set(),
+    })
+}
+
+// Returns the textconv command of the diff driver for the given path if it has one
+pub(crate) fn textconv(repo: &Repository, file: &Path) -> Result<Option<String>> {
+    Ok(match get_attr(repo, file, "diff", AttrCheckFlags::FILE_THEN_INDEX)? {
+        AttrValue::Value(driver) => repo.config()?.get_string(&format!("diff.{}.textconv", driver)).ok(),
+        _ => None,
+    })
+}
+
+// Convert the given content to text with the given textconv command the same as git does by
+// running it through the shell with a temporary file holding the content
+pub(crate) fn run_textconv(repo: &Repository, cmd: &str, content: &[u8]) -> Result<Vec<u8>> {
+    let tmpfile = repo.path().mash(format!("textconv-{}", std::process::id()));
+    std::fs::write(&tmpfile, content).map_err(FuError::from)?;
+    let output = Command::new("sh")
+        .args(["-c", &format!("{} \"$@\"", cmd), cmd])
+        .arg(&tmpfile)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|x| git2::Error::from_str(&format!("failed to run textconv {}: {}", cmd, x)));
+    sys::remove(&tmpfile)?;
+    let output = output?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(git2::Error::from_str(&format!("textconv {} failed: {}", cmd, err)).into());
+    }
+    Ok(output.stdout)
+}
+
+// Convert the line endings of the given content for the worktree according to its `text` and
+// `eol` attributes and the `core.autocrlf` and `core.eol` config the same as git does on checkout
+// and for archives. Only lone LFs are converted and `text=auto` leaves binary content and content
+// already having CRs as is.
+pub(crate) fn to_worktree_eol(config: &git2::Config, attrs: &FileAttrs, data: Vec<u8>) -> Vec<u8> {
+    let autocrlf = config.get_string("core.autocrlf").map(|x| x.to_lowercase()).unwrap_or_default();
+    let (autocrlf, input) = (["true", "yes", "on", "1"].contains(&autocrlf.as_str()), autocrlf == "input");
+    let text_crlf = || autocrlf || (!input && config.get_string("core.eol").map(|x| x.eq_ignore_ascii_case("crlf")).unwrap_or(false));
+    let (crlf, auto) = match (&attrs.text, attrs.eol.as_deref()) {
+        (AttrValue::Unset, _) => (false, false),
+        (AttrValue::Value(text), eol) if text == "auto" => (eol == Some("crlf") || (eol.is_none() && text_crlf()), true),
+        (_, Some(eol)) => (eol == "crlf", false),
+        (AttrValue::Set, None) => (text_crlf(), false),
+        (_, None) => (autocrlf, true),
+    };
+    if !crlf {
+        return data;
+    }
+
+    // Gather the same stats git decides by
+    let (mut lonelf, mut crlfs, mut lonecr, mut nul) = (0, 0, 0, false);
+    let (mut printable, mut nonprintable) = (0usize, 0usize);
+    for (i, c) in data.iter().enumerate() {
+        match c {
+            b'\r' if data.get(i + 1) == Some(&b'\n') => crlfs += 1,
+            b'\r' => lonecr += 1,
+            b'\n' if i == 0 || data[i - 1] != b'\r' => lonelf += 1,
+            b'\n' => {},
+            0 => nul = true,
+            8 | 9 | 12 | 27 => printable += 1,
+            0..=31 | 127 => nonprintable += 1,
+            _ => printable += 1,
+        }
+    }
+    if data.last() == Some(&26) {
+        nonprintable -= 1;
+    }
+    let binary = nul || lonecr > 0 || (printable >> 7) < nonprintable;
+    if lonelf == 0 || (auto && (crlfs > 0 || binary)) {
+        return data;
+    }
+
+    let mut converted = Vec::with_capacity(data.len() + lonelf);
+    for (i, c) in data.iter().enumerate() {
+        if *c == b'\n' && (i == 0 || data[i - 1] != b'\r') {
+            converted.push(b'\r');
+        }
+        converted.push(*c);
+    }
+    converted
+}
+
+// Returns the value of the given attribute for the given path looked up from the given sources
+fn get_attr(repo: &Repository, file: &Path, name: &str, flags: AttrCheckFlags) -> Result<AttrValue> {
+    let value = repo.get_attr_bytes(file, name, flags)?;
+    Ok(match git2::AttrValue::from_bytes(value) {
+        git2::AttrValue::True => AttrValue::Set,
+        git2::AttrValue::False => AttrValue::Unset,
+        git2::AttrValue::String(value) => AttrValue::Value(value.to_string()),
+        git2::AttrValue::Bytes(value) => AttrValue::Value(String::from_utf8_lossy(value).to_string()),
+        git2::AttrValue::Unspecified => AttrValue::Unspecified,
+    })
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_attrs() {
+        let tmpdir = setup("git_attrs");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let attributes = "* text=auto\n*.bat eol=crlf\n*.png binary\n*.psd filter=lfs diff=lfs -text\n*.rs diff=rust\n.github export-ignore\nVERSION export-subst\n";
+        assert!(sys::write(tmpdir.mash(".gitattributes"), attributes).is_ok());
+        assert!(sys::write(sys::mkdir(tmpdir.mash("docs")).unwrap().mash(".gitattributes"), "*.bat -text\n").is_ok());
+
+        // Single attributes
+        assert_eq!(git::attr(&tmpdir, "run.bat", "eol").unwrap(), git::AttrValue::Value("crlf".to_string()));
+        assert_eq!(git::attr(&tmpdir, "run.bat", "text").unwrap(), git::AttrValue::Value("auto".to_string()));
+        assert_eq!(git::attr(&tmpdir, "docs/run.bat", "text").unwrap(), git::AttrValue::Unset);
+        assert_eq!(git::attr(&tmpdir, "logo.png", "diff").unwrap(), git::AttrValue::Unset);
+        assert_eq!(git::attr(&tmpdir, "src/main.rs", "diff").unwrap().value(), Some("rust"));
+        assert_eq!(git::attr(&tmpdir, ".github", "export-ignore").unwrap(), git::AttrValue::Set);
+        assert_eq!(git::attr(&tmpdir, "README.md", "missing").unwrap(), git::AttrValue::Unspecified);
+
+        // All attributes of a path
+        assert_eq!(
+            git::attrs(&tmpdir, "art/logo.psd").unwrap(),
+            git::FileAttrs {
+                text: git::AttrValue::Unset,
+                eol: None,
+                diff: git::AttrValue::Value("lfs".to_string()),
+                filter: Some("lfs".to_string()),
+                export_ignore: false,
+                export_subst: false,
+            }
+        );
+        assert_eq!(git::attrs(&tmpdir, "logo.png").unwrap().is_binary(), true);
+        assert_eq!(git::attrs(&tmpdir, "README.md").unwrap().is_binary(), false);
+        assert_eq!(git::attrs(&tmpdir, "VERSION").unwrap().export_subst, true);
+        assert_eq!(git::attrs(&tmpdir, ".github").unwrap().export_ignore, true);
+
+        // Committed attributes apply once the worktree file is gone
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add attributes").commit().is_ok());
+        assert!(sys::remove(tmpdir.mash(".gitattributes")).is_ok());
+        assert_eq!(git::attrs(&tmpdir, "logo.png").unwrap().is_binary(), true);
+
+        // Errors
+        assert!(git::attrs(tmpdir.mash("../missing"), "README.md").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,191 @@
+use super::{progress_style, Repo};
+use crate::error::*;
+use fungus::prelude::*;
+use indicatif::{MultiProgress, ProgressBar};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+// Clone job to be picked up by a worker
+struct Job {
+    url: String,
+    path: PathBuf,
+    bar: Option<ProgressBar>,
+}
+
+/// Clone the given `(url, path)` pairs concurrently using at most `workers` threads. When
+/// `progress` is true a progress bar is displayed for each repo along with an overall bar
+/// tracking the number of repos completed. All clones are attempted even if some fail, in
+/// which case the first failure is returned once the others complete.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_batch_clone_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// let src = tmpdir.mash("src");
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &src).is_ok());
+/// let src = src.to_string().unwrap();
+/// let repos = vec![(&src, tmpdir.mash("repo1")), (&src, tmpdir.mash("repo2"))];
+/// assert!(git::batch::clone(repos, 2, false).is_ok());
+/// assert_eq!(tmpdir.mash("repo1/README.md").exists(), true);
+/// assert_eq!(tmpdir.mash("repo2/README.md").exists(), true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn clone<T, U, V>(repos: T, workers: usize, progress: bool) -> Result<()>
+where
+    T: IntoIterator<Item=(U, V)>,
+    U: AsRef<str>,
+    V: AsRef<Path>,
+{
+    let multi = if progress { Some(MultiProgress::new()) } else { None };
+
+    // Queue up the jobs creating progress bars up front so they display in order
+    let mut jobs = VecDeque::new();
+    for (url, path) in repos {
+        let url = url.as_ref().to_string();
+        let path = path.as_ref().abs()?;
+        let bar = multi.as_ref().map(|x| {
+            let bar = x.add(ProgressBar::new(0).with_style(progress_style()));
+            bar.set_message(&url);
+            bar
+        });
+        jobs.push_back(Job { url, path, bar });
+    }
+    if jobs.is_empty() {
+        return Ok(());
+    }
+    let total = jobs.len() as u64;
+    let overall = multi.as_ref().map(|x| {
+        let bar = x.add(ProgressBar::new(total).with_style(progress_style()));
+        bar.set_message("repos cloned");
+        bar
+    });
+
+    // Spin up the workers to pull jobs off the queue until it's empty
+    let jobs = Arc::new(Mutex::new(jobs));
+    let mut threads = Vec::new();
+    for _ in 0..workers.max(1).min(total as usize) {
+        let jobs = jobs.clone();
+        let overall = overall.clone();
+        threads.push(thread::spawn(move || -> Result<()> {
+            let mut result = Ok(());
+            loop {
+                let job = jobs.lock().unwrap().pop_front();
+                match job {
+                    Some(job) => {
+                        if let Err(err) = clone_job(job) {
+                            if result.is_ok() {
+                                result = Err(err);
+                            }
+                        }
+                        if let Some(overall) = &overall {
+                            overall.inc(1);
+                            if overall.position() >= total {
+                                overall.finish();
+                            }
+                        }
+                    },
+                    None => break,
+                }
+            }
+            result
+        }));
+    }
+
+    // Wait for the workers to finish
+    if let Some(multi) = &multi {
+        multi.join()?;
+    }
+    let mut result = Ok(());
+    for thread in threads {
+        let res = thread.join().unwrap();
+        if result.is_ok() {
+            result = res;
+        }
+    }
+    result
+}
+
+// Clone the given job's repo reporting progress on the job's progress bar if set
+fn clone_job(job: Job) -> Result<()> {
+    let repo = Repo::new(&job.path)?.url(&job.url);
+    match job.bar {
+        Some(bar) => {
+            let (xfer_bar, check_bar) = (bar.clone(), bar.clone());
+            let (mut xfer_init, mut check_init) = (false, false);
+            let result = repo
+                .xfer_progress(move |total, cur| {
+                    if !xfer_init {
+                        xfer_bar.set_length(total);
+                        xfer_init = true;
+                    }
+                    xfer_bar.set_position(cur);
+                })
+                .checkout_progress(move |total, cur| {
+                    if !check_init {
+                        check_bar.set_length(total);
+                        check_init = true;
+                    }
+                    check_bar.set_position(cur);
+                })
+                .clone();
+            match result {
+                Ok(_) => bar.finish_with_message(&job.url),
+                Err(ref err) => bar.abandon_with_message(&format!("{}: {}", job.url, err)),
+            }
+            result.map(|_| ())
+        },
+        None => repo.clone().map(|_| ()),
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_batch_clone() {
+        let tmpdir = setup("git_batch_clone");
+        let src = tmpdir.mash("src");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+        let src = src.to_string().unwrap();
+
+        // More repos than workers
+        let repos: Vec<(String, PathBuf)> = (0..5).map(|i| (src.clone(), tmpdir.mash(format!("repo{}", i)))).collect();
+        assert!(git::batch::clone(repos.clone(), 2, false).is_ok());
+        for (_, path) in &repos {
+            assert_eq!(path.mash("README.md").exists(), true);
+        }
+
+        // Failures are reported after the other repos are cloned
+        let repos = vec![
+            (src.clone(), tmpdir.mash("progress1")),
+            (tmpdir.mash("missing").to_string().unwrap(), tmpdir.mash("progress2")),
+            (src, tmpdir.mash("progress3")),
+        ];
+        assert!(git::batch::clone(repos, 2, true).is_err());
+        assert_eq!(tmpdir.mash("progress1/README.md").exists(), true);
+        assert_eq!(tmpdir.mash("progress2").exists(), false);
+        assert_eq!(tmpdir.mash("progress3/README.md").exists(), true);
+
+        // Nothing to do
+        assert!(git::batch::clone(Vec::<(String, PathBuf)>::new(), 2, true).is_ok());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
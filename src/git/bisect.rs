@@ -0,0 +1,485 @@
+use super::{is_dirty, resolve_commit};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{build::CheckoutBuilder, Oid, Repository, Sort};
+use std::process::Command;
+
+/// Verdict for a commit tested while bisecting
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BisectMark {
+    /// The commit doesn't have the regression
+    Good,
+
+    /// The commit has the regression
+    Bad,
+
+    /// The commit can't be tested e.g. it doesn't build
+    Skip,
+}
+
+/// Next step of a bisect as returned by `Bisect::next`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BisectStep {
+    /// The given commit should be tested and marked next
+    Test(Oid),
+
+    /// The given commit is the first bad commit
+    Found(Oid),
+}
+
+/// Bisect driver for finding the commit that introduced a regression with a binary search of the
+/// commits between a bad commit and one or more good commits, the same as `git bisect`. Unlike
+/// `git bisect` no state is kept in the repo so HEAD is only moved while `run` tests commits.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_bisect_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let mut bisect = git::Bisect::new(&tmpdir);
+/// assert!(bisect.bad("HEAD").is_ok());
+/// assert!(bisect.good("HEAD~4").is_ok());
+/// let bad = git::resolve(&tmpdir, "HEAD~2").unwrap();
+/// let good: Vec<_> = ["HEAD~3", "HEAD~4"].iter().map(|x| git::resolve(&tmpdir, x).unwrap()).collect();
+/// let first = bisect.run(false, |oid| Ok(if good.contains(&oid) { git::BisectMark::Good } else { git::BisectMark::Bad })).unwrap();
+/// assert_eq!(first, bad);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Bisect {
+    path: PathBuf,    // Repo location on disk
+    bad: Option<Oid>, // Newest commit known to have the regression
+    good: Vec<Oid>,   // Commits known not to have the regression
+    skip: Vec<Oid>,   // Commits that can't be tested
+}
+
+impl Bisect {
+    /// Create a new bisect driver for the repo at the given path.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Bisect::new("foo").bad_val(), None);
+    /// ```
+    pub fn new<T: AsRef<Path>>(path: T) -> Self {
+        Self { path: path.as_ref().to_path_buf(), bad: None, good: vec![], skip: vec![] }
+    }
+
+    /// Mark the given revision as bad i.e. having the regression, replacing any previous bad
+    /// commit, the same as `git bisect bad <rev>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_bisect_bad_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let mut bisect = git::Bisect::new(&tmpdir);
+    /// assert!(bisect.bad("HEAD").is_ok());
+    /// assert_eq!(bisect.bad_val(), Some(git::resolve(&tmpdir, "HEAD").unwrap()));
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn bad<T: AsRef<str>>(&mut self, rev: T) -> Result<()> {
+        let oid = self.resolve(rev.as_ref())?;
+        self.mark(oid, BisectMark::Bad);
+        Ok(())
+    }
+
+    /// Mark the given revision as good i.e. not having the regression, the same as `git bisect
+    /// good <rev>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_bisect_good_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let mut bisect = git::Bisect::new(&tmpdir);
+    /// assert!(bisect.good("HEAD~2").is_ok());
+    /// assert_eq!(bisect.good_val(), &[git::resolve(&tmpdir, "HEAD~2").unwrap()]);
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn good<T: AsRef<str>>(&mut self, rev: T) -> Result<()> {
+        let oid = self.resolve(rev.as_ref())?;
+        self.mark(oid, BisectMark::Good);
+        Ok(())
+    }
+
+    /// Mark the given revision as untestable so that it isn't suggested again, the same as `git
+    /// bisect skip <rev>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_bisect_skip_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let mut bisect = git::Bisect::new(&tmpdir);
+    /// assert!(bisect.skip("HEAD~1").is_ok());
+    /// assert_eq!(bisect.skip_val(), &[git::resolve(&tmpdir, "HEAD~1").unwrap()]);
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn skip<T: AsRef<str>>(&mut self, rev: T) -> Result<()> {
+        let oid = self.resolve(rev.as_ref())?;
+        self.mark(oid, BisectMark::Skip);
+        Ok(())
+    }
+
+    /// Mark the given commit, typically the one returned by `next`, with the given verdict.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let mut bisect = git::Bisect::new("foo");
+    /// bisect.mark(git2::Oid::zero(), git::BisectMark::Bad);
+    /// assert_eq!(bisect.bad_val(), Some(git2::Oid::zero()));
+    /// ```
+    pub fn mark(&mut self, oid: Oid, mark: BisectMark) {
+        match mark {
+            BisectMark::Good => self.good.push(oid),
+            BisectMark::Bad => self.bad = Some(oid),
+            BisectMark::Skip => self.skip.push(oid),
+        }
+    }
+
+    /// Returns the commit currently marked as bad if any.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Bisect::new("foo").bad_val(), None);
+    /// ```
+    pub fn bad_val(&self) -> Option<Oid> {
+        self.bad
+    }
+
+    /// Returns the commits marked as good.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert!(git::Bisect::new("foo").good_val().is_empty());
+    /// ```
+    pub fn good_val(&self) -> &[Oid] {
+        &self.good
+    }
+
+    /// Returns the commits marked as untestable.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert!(git::Bisect::new("foo").skip_val().is_empty());
+    /// ```
+    pub fn skip_val(&self) -> &[Oid] {
+        &self.skip
+    }
+
+    /// Returns the next commit to test, chosen to split the commits that may have introduced
+    /// the regression as evenly as possible, or the first bad commit once it's been narrowed down
+    /// to a single commit. `Error::BisectFailed` is returned if a bad and a good commit haven't
+    /// been marked, the bad commit is reachable from a good commit or only skipped commits remain.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_bisect_next_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let mut bisect = git::Bisect::new(&tmpdir);
+    /// assert!(bisect.next().is_err());
+    /// assert!(bisect.bad("HEAD").is_ok());
+    /// assert!(bisect.good("HEAD~1").is_ok());
+    /// assert_eq!(bisect.next().unwrap(), git::BisectStep::Found(git::resolve(&tmpdir, "HEAD").unwrap()));
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn next(&self) -> Result<BisectStep> {
+        let bad = self.bad.ok_or_else(|| Error::bisect_failed("no bad commit was marked"))?;
+        if self.good.is_empty() {
+            return Err(Error::bisect_failed("no good commit was marked"));
+        }
+        let repo = Repository::open(&self.path)?;
+
+        // Commits that may have introduced the regression are those reachable from the bad
+        // commit but not from any good commit
+        let candidates = self.reachable(&repo, bad)?;
+        if candidates.is_empty() {
+            return Err(Error::bisect_failed(format!("bad commit {} is reachable from a good commit", bad)));
+        }
+        let testable: Vec<Oid> = candidates.iter().filter(|x| **x != bad && !self.skip.contains(x)).copied().collect();
+        if testable.is_empty() {
+            if candidates.len() == 1 {
+                return Ok(BisectStep::Found(bad));
+            }
+            let oids: Vec<String> = candidates.iter().map(|x| x.to_string()).collect();
+            return Err(Error::bisect_failed(format!("first bad commit could be any of: {}", oids.join(", "))));
+        }
+
+        // Choose the commit with the number of candidates reachable from it closest to half
+        let total = candidates.len();
+        let mut best = (testable[0], 0);
+        for oid in testable {
+            let count = self.reachable(&repo, oid)?.len();
+            let score = count.min(total - count);
+            if score > best.1 {
+                best = (oid, score);
+            }
+        }
+        Ok(BisectStep::Test(best.0))
+    }
+
+    /// Bisect to the first bad commit by calling the given closure to test each commit returned
+    /// by `next` in turn and marking it with the verdict returned. When `checkout` is set each
+    /// commit is checked out with a detached HEAD before it's tested, which is refused if the
+    /// worktree has uncommitted changes, and the original HEAD is restored afterwards the same as
+    /// `git bisect reset`. Returns the first bad commit.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_bisect_run_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let mut bisect = git::Bisect::new(&tmpdir);
+    /// assert!(bisect.bad("HEAD").is_ok());
+    /// assert!(bisect.good("HEAD~4").is_ok());
+    /// let first = bisect.run(true, |_| Ok(git::BisectMark::Bad)).unwrap();
+    /// assert_eq!(first, git::resolve(&tmpdir, "HEAD~3").unwrap());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn run<F>(&mut self, checkout: bool, mut test: F) -> Result<Oid>
+    where
+        F: FnMut(Oid) -> Result<BisectMark>,
+    {
+        if !checkout {
+            return self.bisect(test);
+        }
+        if is_dirty(&self.path)? {
+            return Err(Error::dirty_worktree(&self.path));
+        }
+        let repo = Repository::open(&self.path)?;
+        let head = repo.head()?;
+        let branch = head.name().filter(|_| head.is_branch()).map(|x| x.to_string());
+        let orig = head.peel_to_commit()?;
+
+        // Restore the original HEAD whatever the outcome
+        let result = self.bisect(|oid| {
+            repo.checkout_tree(&repo.find_object(oid, None)?, Some(CheckoutBuilder::new().safe()))?;
+            repo.set_head_detached(oid)?;
+            test(oid)
+        });
+        repo.checkout_tree(orig.as_object(), Some(CheckoutBuilder::new().safe()))?;
+        match &branch {
+            Some(refname) => repo.set_head(refname)?,
+            None => repo.set_head_detached(orig.id())?,
+        }
+        result
+    }
+
+    /// Bisect to the first bad commit by checking out each commit in turn and running the given
+    /// command with the given arguments from the top of the worktree to test it, the same as `git
+    /// bisect run`. An exit code of 0 marks the commit good, 125 skips it and any other code
+    /// below 128 marks it bad while any other outcome aborts the bisect with
+    /// `Error::BisectFailed`. The original HEAD is restored afterwards. Returns the first bad
+    /// commit.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_bisect_run_command_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let mut bisect = git::Bisect::new(&tmpdir);
+    /// assert!(bisect.bad("HEAD").is_ok());
+    /// assert!(bisect.good("HEAD~4").is_ok());
+    /// let first = bisect.run_command("false", &[] as &[&str]).unwrap();
+    /// assert_eq!(first, git::resolve(&tmpdir, "HEAD~3").unwrap());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn run_command<T, U>(&mut self, cmd: T, args: &[U]) -> Result<Oid>
+    where
+        T: AsRef<str>,
+        U: AsRef<str>,
+    {
+        let dir = self.path.clone();
+        self.run(true, |_| {
+            let status = Command::new(cmd.as_ref())
+                .args(args.iter().map(|x| x.as_ref()))
+                .current_dir(&dir)
+                .status()
+                .map_err(|x| Error::bisect_failed(format!("failed to run {}: {}", cmd.as_ref(), x)))?;
+            match status.code() {
+                Some(0) => Ok(BisectMark::Good),
+                Some(125) => Ok(BisectMark::Skip),
+                Some(code) if code < 128 => Ok(BisectMark::Bad),
+                Some(code) => Err(Error::bisect_failed(format!("{} exited with {}", cmd.as_ref(), code))),
+                None => Err(Error::bisect_failed(format!("{} was killed by a signal", cmd.as_ref()))),
+            }
+        })
+    }
+
+    // Mark each commit returned by `next` with the verdict of the given test until the first
+    // bad commit is found
+    fn bisect<F>(&mut self, mut test: F) -> Result<Oid>
+    where
+        F: FnMut(Oid) -> Result<BisectMark>,
+    {
+        loop {
+            match self.next()? {
+                BisectStep::Found(oid) => return Ok(oid),
+                BisectStep::Test(oid) => {
+                    let mark = test(oid)?;
+                    self.mark(oid, mark);
+                },
+            }
+        }
+    }
+
+    // Returns the commits reachable from the given commit but not from any good commit
+    fn reachable(&self, repo: &Repository, oid: Oid) -> Result<Vec<Oid>> {
+        let mut walk = repo.revwalk()?;
+        walk.set_sorting(Sort::TOPOLOGICAL)?;
+        walk.push(oid)?;
+        for good in &self.good {
+            walk.hide(*good)?;
+        }
+        Ok(walk.collect::<std::result::Result<Vec<_>, _>>()?)
+    }
+
+    // Resolve the given revision to a commit
+    fn resolve(&self, rev: &str) -> Result<Oid> {
+        let repo = Repository::open(&self.path)?;
+        resolve_commit(&repo, rev)
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Initialize a new repo with a commit for each version from 0 to the given count with the
+    // version recorded in the VERSION file returning the commit ids
+    fn init(path: &Path, count: usize) -> Vec<git2::Oid> {
+        let repo = git2::Repository::init(path).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        (0..count)
+            .map(|i| {
+                assert!(sys::write(path.mash("VERSION"), i.to_string()).is_ok());
+                git::CommitBuilder::new(path).stage_all(true).message(format!("Version {}", i)).commit().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bisect() {
+        let tmpdir = setup("git_bisect");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let oids = init(&tmpdir, 20);
+        let version = |oid: git2::Oid| oids.iter().position(|x| *x == oid).unwrap();
+
+        // Incomplete marks
+        let mut bisect = git::Bisect::new(&tmpdir);
+        assert_eq!(bisect.next().unwrap_err().to_string(), git::Error::bisect_failed("no bad commit was marked").to_string());
+        assert!(bisect.bad("HEAD").is_ok());
+        assert_eq!(bisect.next().unwrap_err().to_string(), git::Error::bisect_failed("no good commit was marked").to_string());
+        assert!(bisect.good("missing").is_err());
+
+        // Binary search of the history
+        assert!(bisect.good("HEAD~19").is_ok());
+        assert_eq!(bisect.next().unwrap(), git::BisectStep::Test(oids[10]));
+        let mut tested = vec![];
+        let first = bisect
+            .clone()
+            .run(false, |oid| {
+                tested.push(version(oid));
+                Ok(if version(oid) >= 13 { git::BisectMark::Bad } else { git::BisectMark::Good })
+            })
+            .unwrap();
+        assert_eq!(first, oids[13]);
+        assert!(tested.len() <= 5);
+
+        // Skipped commits are avoided until only they remain
+        bisect.mark(oids[12], git::BisectMark::Good);
+        bisect.mark(oids[14], git::BisectMark::Bad);
+        bisect.mark(oids[13], git::BisectMark::Skip);
+        let err = bisect.next().unwrap_err();
+        assert_eq!(err.to_string(), git::Error::bisect_failed(format!("first bad commit could be any of: {}, {}", oids[14], oids[13])).to_string());
+
+        // Bad commit reachable from a good commit
+        let mut bisect = git::Bisect::new(&tmpdir);
+        bisect.mark(oids[5], git::BisectMark::Bad);
+        bisect.mark(oids[6], git::BisectMark::Good);
+        assert!(bisect.next().is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_bisect_run() {
+        let tmpdir = setup("git_bisect_run");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let oids = init(&tmpdir, 10);
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let mut bisect = git::Bisect::new(&tmpdir);
+        assert!(bisect.bad("master").is_ok());
+        assert!(bisect.good(oids[0].to_string()).is_ok());
+
+        // Commits are checked out for the closure with HEAD restored afterwards
+        let first = bisect
+            .clone()
+            .run(true, |oid| {
+                assert_eq!(repo.head().unwrap().target(), Some(oid));
+                let version: usize = sys::readstring(tmpdir.mash("VERSION")).unwrap().parse().unwrap();
+                Ok(if version >= 7 { git::BisectMark::Bad } else { git::BisectMark::Good })
+            })
+            .unwrap();
+        assert_eq!(first, oids[7]);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("master"));
+        assert_eq!(sys::readstring(tmpdir.mash("VERSION")).unwrap(), "9");
+
+        // Closure errors abort the bisect still restoring HEAD
+        let err = bisect.clone().run(true, |_| Err(git::Error::bisect_failed("foo"))).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::bisect_failed("foo").to_string());
+        assert_eq!(repo.head().unwrap().shorthand(), Some("master"));
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+
+        // Command exit codes mark commits
+        let script = "v=$(cat VERSION); [ $v -eq 5 ] && exit 125; [ $v -lt 4 ]";
+        let first = bisect.clone().run_command("sh", &["-c", script]).unwrap();
+        assert_eq!(first, oids[4]);
+        let err = bisect.clone().run_command("sh", &["-c", "exit 130"]).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::bisect_failed("sh exited with 130").to_string());
+        assert_eq!(repo.head().unwrap().shorthand(), Some("master"));
+
+        // Dirty worktree is refused
+        assert!(sys::write(tmpdir.mash("VERSION"), "foo").is_ok());
+        let err = bisect.run(true, |_| Ok(git::BisectMark::Bad)).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::dirty_worktree(&tmpdir).to_string());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{BlameOptions, Oid, Repository};
+
+/// Blame details for a run of consecutive lines last changed by the same commit as returned by
+/// `blame`
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlameHunk {
+    pub oid: Oid,       // Id of the commit that last changed the lines
+    pub author: String, // Commit author's name
+    pub email: String,  // Commit author's email
+    pub time: i64,      // Author time in seconds since the epoch
+    pub start: usize,   // First line of the hunk starting at 1
+    pub lines: usize,   // Number of lines in the hunk
+}
+
+/// Returns the blame hunks for the given file, relative to the repo root, as of the given
+/// revision or HEAD when not given. The hunks cover every line of the file in order.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_blame_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let hunks = git::blame(&tmpdir, "README.md", None).unwrap();
+/// assert_eq!(hunks[0].start, 1);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn blame<T, U>(path: T, file: U, rev: Option<&str>) -> Result<Vec<BlameHunk>>
+where
+    T: AsRef<Path>,
+    U: AsRef<Path>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let mut opts = BlameOptions::new();
+    if let Some(rev) = rev {
+        opts.newest_commit(repo.revparse_single(rev)?.peel_to_commit()?.id());
+    }
+    let blame = repo.blame_file(file.as_ref(), Some(&mut opts))?;
+    Ok(blame
+        .iter()
+        .map(|hunk| {
+            let sig = hunk.final_signature();
+            BlameHunk {
+                oid: hunk.final_commit_id(),
+                author: sig.name().unwrap_or_default().to_string(),
+                email: sig.email().unwrap_or_default().to_string(),
+                time: sig.when().seconds(),
+                start: hunk.final_start_line(),
+                lines: hunk.lines_in_hunk(),
+            }
+        })
+        .collect())
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Returns the commit id git itself blames for each line of the given file
+    fn git_blame(path: &Path, rev: &str, file: &str) -> Vec<String> {
+        let output = Command::new("git").args(["blame", "-l", "-s", rev, "--", file]).current_dir(path).output().unwrap();
+        assert_eq!(output.status.success(), true);
+        String::from_utf8(output.stdout).unwrap().lines().map(|x| x.split_whitespace().next().unwrap().trim_start_matches('^').to_string()).collect()
+    }
+
+    // Returns the commit id for each line covered by the given hunks
+    fn lines(hunks: &[git::BlameHunk]) -> Vec<String> {
+        let mut lines = vec![];
+        for hunk in hunks {
+            assert_eq!(hunk.start, lines.len() + 1);
+            lines.extend((0..hunk.lines).map(|_| hunk.oid.to_string()));
+        }
+        lines
+    }
+
+    #[test]
+    fn test_blame() {
+        let tmpdir = setup("git_blame");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+
+        // HEAD matches git
+        let hunks = git::blame(&tmpdir, "README.md", None).unwrap();
+        assert_eq!(lines(&hunks), git_blame(&tmpdir, "HEAD", "README.md"));
+        let commit = repo.find_commit(hunks[0].oid).unwrap();
+        assert_eq!(hunks[0].author, commit.author().name().unwrap());
+        assert_eq!(hunks[0].email, commit.author().email().unwrap());
+        assert_eq!(hunks[0].time, commit.author().when().seconds());
+
+        // Older revision matches git
+        let hunks = git::blame(&tmpdir, "Dockerfile", Some("HEAD~10")).unwrap();
+        assert_eq!(lines(&hunks), git_blame(&tmpdir, "HEAD~10", "Dockerfile"));
+
+        // Errors
+        assert!(git::blame(&tmpdir, "missing", None).is_err());
+        assert!(git::blame(&tmpdir, "README.md", Some("missing")).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,837 @@
+use super::{index_clear, Repo};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{build::CheckoutBuilder, Branch, BranchType, Oid, Repository};
+
+/// Branch details as returned by `branches`
+#[derive(Clone, Debug, PartialEq)]
+pub struct BranchInfo {
+    pub name: String,             // Short name e.g. `master` or `origin/master`
+    pub remote: bool,             // True for remote-tracking branches
+    pub upstream: Option<String>, // Short name of the upstream branch if tracking one
+    pub oid: Oid,                 // Id of the last commit on the branch
+    pub time: i64,                // Last commit time in seconds since the epoch
+    pub author: String,           // Last commit author's name
+    pub is_head: bool,            // True if the branch is checked out
+}
+
+/// Filter for selecting branches with `branches`. By default all local and remote-tracking
+/// branches are selected.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let filter = git::BranchFilter::new().local().pattern("feature/*").merged("master");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BranchFilter {
+    kind: Option<BranchType>, // Only local or only remote-tracking branches
+    merged: Option<String>,   // Only branches merged into this revision
+    pattern: Option<Regex>,   // Only branches matching this glob pattern
+}
+
+impl BranchFilter {
+    /// Create a new filter selecting all branches.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let filter = git::BranchFilter::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Select only local branches.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let filter = git::BranchFilter::new().local();
+    /// ```
+    pub fn local(mut self) -> Self {
+        self.kind = Some(BranchType::Local);
+        self
+    }
+
+    /// Select only remote-tracking branches.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let filter = git::BranchFilter::new().remote();
+    /// ```
+    pub fn remote(mut self) -> Self {
+        self.kind = Some(BranchType::Remote);
+        self
+    }
+
+    /// Select only branches whose last commit is reachable from the given revision i.e. the
+    /// equivalent of `git branch --merged <rev>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let filter = git::BranchFilter::new().merged("master");
+    /// ```
+    pub fn merged<T: AsRef<str>>(mut self, rev: T) -> Self {
+        self.merged = Some(rev.as_ref().to_string());
+        self
+    }
+
+    /// Select only branches whose short name matches the given glob pattern where `*` matches
+    /// any sequence of characters and `?` any single character e.g. `feature/*`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let filter = git::BranchFilter::new().pattern("feature/*");
+    /// ```
+    pub fn pattern<T: AsRef<str>>(mut self, glob: T) -> Self {
+        let re = glob.as_ref().chars().fold(String::from("^"), |mut re, c| {
+            match c {
+                '*' => re.push_str(".*"),
+                '?' => re.push('.'),
+                _ => {
+                    if "\\.+*?()|[]{}^$#&-~".contains(c) {
+                        re.push('\\');
+                    }
+                    re.push(c);
+                },
+            }
+            re
+        });
+        self.pattern = Some(Regex::new(&format!("{}$", re)).unwrap());
+        self
+    }
+}
+
+/// List the repo's branches selected by the given filter sorted by name with local branches
+/// first followed by remote-tracking branches. Symbolic refs like `origin/HEAD` are skipped.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_branches_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let branches = git::branches(&tmpdir, &git::BranchFilter::new().local()).unwrap();
+/// assert_eq!(branches[0].name, "master");
+/// assert_eq!(branches[0].is_head, true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn branches<T: AsRef<Path>>(path: T, filter: &BranchFilter) -> Result<Vec<BranchInfo>> {
+    let repo = Repository::open(path.as_ref())?;
+    let merged = match &filter.merged {
+        Some(rev) => Some(repo.revparse_single(rev)?.peel_to_commit()?.id()),
+        None => None,
+    };
+
+    let mut branches = Vec::new();
+    for branch in repo.branches(filter.kind)? {
+        let (branch, kind) = branch?;
+        let oid = match branch.get().target() {
+            Some(oid) => oid,
+            None => continue,
+        };
+        let name = match branch.name()? {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        if let Some(pattern) = &filter.pattern {
+            if !pattern.is_match(&name) {
+                continue;
+            }
+        }
+        if let Some(into) = merged {
+            if !is_merged(&repo, oid, into)? {
+                continue;
+            }
+        }
+        let commit = repo.find_commit(oid)?;
+        let upstream = match branch.upstream() {
+            Ok(upstream) => upstream.name()?.map(|x| x.to_string()),
+            Err(_) => None,
+        };
+        branches.push(BranchInfo {
+            name,
+            remote: kind == BranchType::Remote,
+            upstream,
+            oid,
+            time: commit.time().seconds(),
+            author: commit.author().name().unwrap_or_default().to_string(),
+            is_head: branch.is_head(),
+        });
+    }
+    branches.sort_by(|x, y| (x.remote, &x.name).cmp(&(y.remote, &y.name)));
+    Ok(branches)
+}
+
+/// Create the local branch `name` from HEAD, or from the given revision e.g. a branch, tag or
+/// commit, optionally checking it out. Checking out is done safely i.e. it will fail rather
+/// than overwrite local modifications.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_branch_create_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::branch_create(&tmpdir, "foo", Some("3.11"), true).is_ok());
+/// assert_eq!(git2::Repository::open(&tmpdir).unwrap().head().unwrap().shorthand(), Some("foo"));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn branch_create<T, U>(path: T, name: U, from: Option<&str>, checkout: bool) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let commit = match from {
+        Some(rev) => repo.revparse_single(rev)?.peel_to_commit()?,
+        None => repo.head()?.peel_to_commit()?,
+    };
+    let branch = repo.branch(name.as_ref(), &commit, false)?;
+    if checkout {
+        let refname = branch.get().name().ok_or_else(|| Error::branch_not_found(name.as_ref()))?.to_string();
+        repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+        repo.set_head(&refname)?;
+    }
+    Ok(())
+}
+
+/// Create a new orphan branch, with no parent history, and point HEAD at it so that the next
+/// commit becomes the initial commit of the branch. The worktree is left untouched. When `clear`
+/// is set the index is emptied as well so that the initial commit only includes newly staged
+/// files, e.g. when generating a `gh-pages` style branch.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_branch_orphan_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "test").unwrap();
+/// repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+/// assert!(git::branch_orphan(&tmpdir, "gh-pages", true).is_ok());
+/// assert!(sys::write(tmpdir.mash("index.html"), "foo").is_ok());
+/// assert!(git::stage(&tmpdir, &["index.html"]).is_ok());
+/// let oid = git::commit(&tmpdir, "Initial gh-pages commit", None).unwrap();
+/// assert_eq!(repo.find_commit(oid).unwrap().parent_count(), 0);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn branch_orphan<T, U>(path: T, name: U, clear: bool) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let refname = format!("refs/heads/{}", name.as_ref());
+    if !Branch::name_is_valid(name.as_ref())? {
+        return Err(git2::Error::from_str(&format!("'{}' is not a valid branch name", name.as_ref())).into());
+    }
+    if repo.find_reference(&refname).is_ok() {
+        return Err(git2::Error::from_str(&format!("a branch named '{}' already exists", name.as_ref())).into());
+    }
+    repo.set_head(&refname)?;
+    if clear {
+        index_clear(path)?;
+    }
+    Ok(())
+}
+
+/// Delete the given local branch. The branch currently checked out can't be deleted.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_branch_delete_local_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::branch_create(&tmpdir, "foo", None, false).is_ok());
+/// assert!(git::branch_delete_local(&tmpdir, "foo").is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn branch_delete_local<T, U>(path: T, name: U) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let mut branch = repo.find_branch(name.as_ref(), BranchType::Local).map_err(|_| Error::branch_not_found(name.as_ref()))?;
+    branch.delete()?;
+    Ok(())
+}
+
+/// Rename the given local branch moving its reflog and tracking configuration along with it.
+/// HEAD follows the branch if it was checked out. If the branch tracked a remote branch of the
+/// old name and the remote also has a branch of the new name, as is the case after renaming
+/// e.g. `master` to `main` on the remote, the upstream is rewritten to track the new name.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_branch_rename_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::branch_rename(&tmpdir, "master", "main").is_ok());
+/// assert_eq!(git2::Repository::open(&tmpdir).unwrap().head().unwrap().shorthand(), Some("main"));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn branch_rename<T, U, V>(path: T, old: U, new: V) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let (old, new) = (old.as_ref(), new.as_ref());
+    let repo = Repository::open(path.as_ref())?;
+    let mut branch = repo.find_branch(old, BranchType::Local).map_err(|_| Error::branch_not_found(old))?;
+    branch.rename(new, false)?;
+
+    // Track the remote's renamed branch if it exists
+    let mut config = repo.config()?;
+    let remote = config.get_string(&format!("branch.{}.remote", new));
+    let merge = config.get_string(&format!("branch.{}.merge", new));
+    if let (Ok(remote), Ok(merge)) = (remote, merge) {
+        if merge == format!("refs/heads/{}", old) && repo.find_branch(&format!("{}/{}", remote, new), BranchType::Remote).is_ok() {
+            config.set_str(&format!("branch.{}.merge", new), &format!("refs/heads/{}", new))?;
+        }
+    }
+    Ok(())
+}
+
+/// Set the upstream branch the given local branch tracks e.g. `origin/feature` so that pulls and
+/// ahead/behind calculations work. Passing `None` unsets the upstream.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_branch_set_upstream_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::branch_create(&tmpdir, "foo", None, false).is_ok());
+/// assert!(git::branch_set_upstream(&tmpdir, "foo", Some("master")).is_ok());
+/// assert_eq!(git::branch_upstream(&tmpdir, "foo").unwrap(), Some("master".to_string()));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn branch_set_upstream<T, U>(path: T, branch: U, upstream: Option<&str>) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let mut branch = repo.find_branch(branch.as_ref(), BranchType::Local).map_err(|_| Error::branch_not_found(branch.as_ref()))?;
+    branch.set_upstream(upstream)?;
+    Ok(())
+}
+
+/// Returns the short name of the upstream branch the given local branch tracks if any e.g.
+/// `origin/master`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_branch_upstream_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::branch_create(&tmpdir, "foo", None, false).is_ok());
+/// assert_eq!(git::branch_upstream(&tmpdir, "foo").unwrap(), None);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn branch_upstream<T, U>(path: T, branch: U) -> Result<Option<String>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let branch = repo.find_branch(branch.as_ref(), BranchType::Local).map_err(|_| Error::branch_not_found(branch.as_ref()))?;
+    let upstream = match branch.upstream() {
+        Ok(upstream) => upstream.name()?.map(|x| x.to_string()),
+        Err(_) => None,
+    };
+    Ok(upstream)
+}
+
+/// Returns the number of commits the given local branch is `(ahead, behind)` its upstream
+/// branch by, computed by walking the commit graph from both tips to their merge base. A
+/// branch that is only behind can be fast-forwarded.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_ahead_behind_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::branch_create(&tmpdir, "foo", Some("HEAD~2"), false).is_ok());
+/// assert!(git::branch_set_upstream(&tmpdir, "foo", Some("master")).is_ok());
+/// assert_eq!(git::ahead_behind(&tmpdir, "foo").unwrap(), (0, 2));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn ahead_behind<T, U>(path: T, branch: U) -> Result<(usize, usize)>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let local = repo.find_branch(branch.as_ref(), BranchType::Local).map_err(|_| Error::branch_not_found(branch.as_ref()))?;
+    let upstream = local.upstream().map_err(|_| Error::upstream_not_set(branch.as_ref()))?;
+    let local = local.get().peel_to_commit()?.id();
+    let upstream = upstream.get().peel_to_commit()?.id();
+    Ok(repo.graph_ahead_behind(local, upstream)?)
+}
+
+/// Returns the name of the repo's default branch rather than assuming `master`. The branch
+/// `origin/HEAD` points to is used if set, then the `init.defaultBranch` config value, `main`
+/// and `master` in that order if the local branch exists. A repo with none of these falls back
+/// to `init.defaultBranch` or `master` as git would for a new repo.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_default_branch_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::default_branch(&tmpdir).unwrap(), "master");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn default_branch<T: AsRef<Path>>(path: T) -> Result<String> {
+    let repo = Repository::open(path.as_ref())?;
+
+    // Remote's default branch as recorded by clone
+    let prefix = "refs/remotes/origin/";
+    if let Ok(head) = repo.find_reference(&format!("{}HEAD", prefix)) {
+        if let Some(name) = head.symbolic_target().and_then(|x| x.strip_prefix(prefix)) {
+            return Ok(name.to_string());
+        }
+    }
+
+    // Configured default then conventional names
+    let config = repo.config()?.get_string("init.defaultBranch").ok();
+    for name in config.iter().map(|x| x.as_str()).chain(vec!["main", "master"]) {
+        if repo.find_branch(name, BranchType::Local).is_ok() {
+            return Ok(name.to_string());
+        }
+    }
+    Ok(config.unwrap_or_else(|| "master".to_string()))
+}
+
+/// Returns true if the given branch is fully merged into `into`, i.e. its last commit is
+/// reachable from `into`, making it safe to delete. The branch may be local or remote-tracking
+/// e.g. `origin/foo` with local branches taking precedence, while `into` may be any revision.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_branch_is_merged_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::branch_create(&tmpdir, "foo", Some("HEAD~2"), false).is_ok());
+/// assert_eq!(git::branch_is_merged(&tmpdir, "foo", "master").unwrap(), true);
+/// assert_eq!(git::branch_is_merged(&tmpdir, "master", "foo").unwrap(), false);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn branch_is_merged<T, U, V>(path: T, branch: U, into: V) -> Result<bool>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let branch = repo
+        .find_branch(branch.as_ref(), BranchType::Local)
+        .or_else(|_| repo.find_branch(branch.as_ref(), BranchType::Remote))
+        .map_err(|_| Error::branch_not_found(branch.as_ref()))?;
+    let oid = branch.get().peel_to_commit()?.id();
+    let into = repo.revparse_single(into.as_ref()).map_err(|_| Error::branch_not_found(into.as_ref()))?.peel_to_commit()?.id();
+    is_merged(&repo, oid, into)
+}
+
+// Returns true if the given commit is reachable from the given target commit
+pub(crate) fn is_merged(repo: &Repository, oid: Oid, into: Oid) -> Result<bool> {
+    Ok(oid == into || repo.graph_descendant_of(into, oid)?)
+}
+
+impl<'a> Repo<'a> {
+    /// Delete the given branch on the repo's remote, `origin` by default, by pushing a delete
+    /// refspec using the repo's network settings and transfer progress callback. The local
+    /// remote-tracking branch is removed as well.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_branch_delete_remote_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+    /// let remote = tmpdir.mash("remote");
+    /// assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+    /// assert!(git::branch_create(&remote, "foo", None, false).is_ok());
+    /// let local = tmpdir.mash("local");
+    /// assert!(git::Repo::new(&local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+    /// assert!(git::Repo::new(&local).unwrap().branch_delete_remote("foo").is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn branch_delete_remote<T: AsRef<str>>(mut self, name: T) -> Result<()> {
+        let name = name.as_ref();
+        self.push(&[format!(":refs/heads/{}", name)])?;
+
+        // Remove the stale remote-tracking branch
+        let repo = Repository::open(self.path_val())?;
+        if let Ok(mut branch) = repo.find_branch(&format!("{}/{}", self.remote_val(), name), BranchType::Remote) {
+            branch.delete()?;
+        }
+        Ok(())
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_branch_create() {
+        let tmpdir = setup("git_branch_create");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let head = repo.head().unwrap().target().unwrap();
+        let tag = repo.revparse_single("3.11").unwrap().peel_to_commit().unwrap().id();
+
+        // From HEAD without checkout
+        assert!(git::branch_create(&tmpdir, "foo", None, false).is_ok());
+        assert_eq!(repo.find_branch("foo", git2::BranchType::Local).unwrap().get().target(), Some(head));
+        assert_eq!(repo.head().unwrap().shorthand(), Some("master"));
+
+        // Existing branch is an error
+        assert!(git::branch_create(&tmpdir, "foo", None, false).is_err());
+
+        // From a revision with checkout
+        assert!(git::branch_create(&tmpdir, "bar", Some("3.11"), true).is_ok());
+        assert_eq!(repo.head().unwrap().shorthand(), Some("bar"));
+        assert_eq!(repo.head().unwrap().target(), Some(tag));
+        assert_eq!(repo.statuses(None).unwrap().is_empty(), true);
+
+        // Invalid revision
+        assert!(git::branch_create(&tmpdir, "baz", Some("missing"), false).is_err());
+        assert!(repo.find_branch("baz", git2::BranchType::Local).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_branch_orphan() {
+        let tmpdir = setup("git_branch_orphan");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+
+        // Keeping the index commits the current tree without history
+        let tree = repo.head().unwrap().peel_to_tree().unwrap().id();
+        assert!(git::branch_orphan(&tmpdir, "foo", false).is_ok());
+        assert!(repo.head().is_err());
+        let oid = git::commit(&tmpdir, "Initial foo", None).unwrap();
+        let commit = repo.find_commit(oid).unwrap();
+        assert_eq!(commit.parent_count(), 0);
+        assert_eq!(commit.tree_id(), tree);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("foo"));
+
+        // Clearing the index commits only newly staged files
+        assert!(git::branch_orphan(&tmpdir, "gh-pages", true).is_ok());
+        assert_eq!(tmpdir.mash("README.md").exists(), true);
+        assert!(sys::write(tmpdir.mash("index.html"), "foo").is_ok());
+        assert!(git::stage(&tmpdir, &["index.html"]).is_ok());
+        let oid = git::commit(&tmpdir, "Initial gh-pages", None).unwrap();
+        let commit = repo.find_commit(oid).unwrap();
+        assert_eq!(commit.parent_count(), 0);
+        assert_eq!(commit.tree().unwrap().len(), 1);
+        assert_eq!(commit.tree().unwrap().get_name("index.html").is_some(), true);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("gh-pages"));
+        assert_eq!(repo.reflog("HEAD").unwrap().get(0).unwrap().message(), Some("commit (initial): Initial gh-pages"));
+
+        // Existing and invalid names
+        assert!(git::branch_orphan(&tmpdir, "master", false).is_err());
+        assert!(git::branch_orphan(&tmpdir, "foo..bar", false).is_err());
+        assert_eq!(repo.head().unwrap().shorthand(), Some("gh-pages"));
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_branch_delete() {
+        let tmpdir = setup("git_branch_delete");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+
+        // Bare remote with a couple branches and a clone of it
+        let remote = tmpdir.mash("remote");
+        assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        assert!(git::branch_create(&remote, "foo", None, false).is_ok());
+        assert!(git::branch_create(&remote, "bar", None, false).is_ok());
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+        let repo = git2::Repository::open(&local).unwrap();
+        assert!(repo.find_branch("origin/foo", git2::BranchType::Remote).is_ok());
+
+        // Local
+        assert!(git::branch_create(&local, "foo", None, false).is_ok());
+        assert!(git::branch_delete_local(&local, "foo").is_ok());
+        assert!(repo.find_branch("foo", git2::BranchType::Local).is_err());
+        assert_eq!(git::branch_delete_local(&local, "foo").unwrap_err().to_string(), git::Error::branch_not_found("foo").to_string());
+        assert!(git::branch_delete_local(&local, "master").is_err());
+
+        // Remote with progress
+        assert!(git::Repo::new(&local).unwrap().xfer_progress(|_, _| {}).branch_delete_remote("foo").is_ok());
+        assert!(git2::Repository::open(&remote).unwrap().find_branch("foo", git2::BranchType::Local).is_err());
+        assert!(git2::Repository::open(&remote).unwrap().find_branch("bar", git2::BranchType::Local).is_ok());
+        assert!(repo.find_branch("origin/foo", git2::BranchType::Remote).is_err());
+
+        // Missing remote
+        assert!(git::Repo::new(&local).unwrap().remote("missing").branch_delete_remote("bar").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_branch_rename() {
+        let tmpdir = setup("git_branch_rename");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+
+        // Remote that already has the renamed branch
+        let remote = tmpdir.mash("remote");
+        assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        assert!(git::branch_create(&remote, "main", None, false).is_ok());
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+        let repo = git2::Repository::open(&local).unwrap();
+        let head = repo.head().unwrap().target();
+
+        // HEAD, reflog and upstream follow the branch
+        assert!(git::branch_rename(&local, "master", "main").is_ok());
+        assert_eq!(repo.head().unwrap().shorthand(), Some("main"));
+        assert_eq!(repo.head().unwrap().target(), head);
+        assert!(repo.find_branch("master", git2::BranchType::Local).is_err());
+        assert_eq!(repo.reflog("refs/heads/main").unwrap().is_empty(), false);
+        let branch = repo.find_branch("main", git2::BranchType::Local).unwrap();
+        assert_eq!(branch.upstream().unwrap().name().unwrap(), Some("origin/main"));
+
+        // Upstream is left alone when the remote has no branch of the new name
+        assert!(git::branch_rename(&local, "main", "foo").is_ok());
+        let branch = repo.find_branch("foo", git2::BranchType::Local).unwrap();
+        assert_eq!(branch.upstream().unwrap().name().unwrap(), Some("origin/main"));
+
+        // Missing branch and existing target
+        assert_eq!(git::branch_rename(&local, "missing", "bar").unwrap_err().to_string(), git::Error::branch_not_found("missing").to_string());
+        assert!(git::branch_create(&local, "bar", None, false).is_ok());
+        assert!(git::branch_rename(&local, "foo", "bar").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_branches() {
+        let tmpdir = setup("git_branches");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(tmpdir.mash("src").to_string().unwrap()).clone().is_ok());
+        let repo = git2::Repository::open(&local).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert!(git::branch_create(&local, "feature/foo", Some("HEAD~2"), false).is_ok());
+        assert!(git::branch_create(&local, "feature/bar", None, false).is_ok());
+
+        // Unmerged branch
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = head.tree().unwrap();
+        let oid = repo.commit(Some("refs/heads/feature/bar"), &sig, &sig, "unmerged", &tree, &[&head]).unwrap();
+
+        // All branches with details
+        let branches = git::branches(&local, &git::BranchFilter::new()).unwrap();
+        let names: Vec<&str> = branches.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, vec!["feature/bar", "feature/foo", "master", "origin/master"]);
+        assert_eq!(branches[0].oid, oid);
+        assert_eq!(branches[0].author, "test");
+        assert_eq!(branches[0].upstream, None);
+        assert_eq!(branches[2].is_head, true);
+        assert_eq!(branches[2].remote, false);
+        assert_eq!(branches[2].upstream, Some("origin/master".to_string()));
+        assert_eq!(branches[2].time, head.time().seconds());
+        assert_eq!(branches[3].remote, true);
+        assert_eq!(branches[3].is_head, false);
+
+        // Filtered
+        let names = |filter: git::BranchFilter| git::branches(&local, &filter).unwrap().into_iter().map(|x| x.name).collect::<Vec<_>>();
+        assert_eq!(names(git::BranchFilter::new().local()), vec!["feature/bar", "feature/foo", "master"]);
+        assert_eq!(names(git::BranchFilter::new().remote()), vec!["origin/master"]);
+        assert_eq!(names(git::BranchFilter::new().pattern("feature/*")), vec!["feature/bar", "feature/foo"]);
+        assert_eq!(names(git::BranchFilter::new().pattern("f?ature/f*")), vec!["feature/foo"]);
+        assert_eq!(names(git::BranchFilter::new().pattern("feature.*")), Vec::<String>::new());
+        assert_eq!(names(git::BranchFilter::new().local().merged("master")), vec!["feature/foo", "master"]);
+        assert!(git::branches(&local, &git::BranchFilter::new().merged("missing")).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_branch_upstream() {
+        let tmpdir = setup("git_branch_upstream");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(tmpdir.mash("src").to_string().unwrap()).clone().is_ok());
+
+        // Cloned branch tracks the remote
+        assert_eq!(git::branch_upstream(&local, "master").unwrap(), Some("origin/master".to_string()));
+
+        // Set, change and unset
+        assert!(git::branch_create(&local, "foo", None, false).is_ok());
+        assert_eq!(git::branch_upstream(&local, "foo").unwrap(), None);
+        assert!(git::branch_set_upstream(&local, "foo", Some("origin/master")).is_ok());
+        assert_eq!(git::branch_upstream(&local, "foo").unwrap(), Some("origin/master".to_string()));
+        let config = git2::Repository::open(&local).unwrap().config().unwrap().snapshot().unwrap();
+        assert_eq!(config.get_str("branch.foo.remote").unwrap(), "origin");
+        assert_eq!(config.get_str("branch.foo.merge").unwrap(), "refs/heads/master");
+        assert!(git::branch_set_upstream(&local, "foo", Some("master")).is_ok());
+        assert_eq!(git::branch_upstream(&local, "foo").unwrap(), Some("master".to_string()));
+        assert!(git::branch_set_upstream(&local, "foo", None).is_ok());
+        assert_eq!(git::branch_upstream(&local, "foo").unwrap(), None);
+
+        // Errors
+        assert!(git::branch_set_upstream(&local, "foo", Some("origin/missing")).is_err());
+        assert_eq!(git::branch_upstream(&local, "missing").unwrap_err().to_string(), git::Error::branch_not_found("missing").to_string());
+        assert!(git::branch_set_upstream(&local, "missing", None).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_ahead_behind() {
+        let tmpdir = setup("git_ahead_behind");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(tmpdir.mash("src").to_string().unwrap()).clone().is_ok());
+        let repo = git2::Repository::open(&local).unwrap();
+
+        // Up to date with upstream
+        assert_eq!(git::ahead_behind(&local, "master").unwrap(), (0, 0));
+
+        // Behind
+        assert!(git::branch_create(&local, "foo", Some("HEAD~3"), false).is_ok());
+        assert!(git::branch_set_upstream(&local, "foo", Some("origin/master")).is_ok());
+        assert_eq!(git::ahead_behind(&local, "foo").unwrap(), (0, 3));
+
+        // Diverged
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let parent = repo.revparse_single("foo").unwrap().peel_to_commit().unwrap();
+        let tree = parent.tree().unwrap();
+        let oid = repo.commit(Some("refs/heads/foo"), &sig, &sig, "one", &tree, &[&parent]).unwrap();
+        let parent = repo.find_commit(oid).unwrap();
+        repo.commit(Some("refs/heads/foo"), &sig, &sig, "two", &tree, &[&parent]).unwrap();
+        assert_eq!(git::ahead_behind(&local, "foo").unwrap(), (2, 3));
+
+        // Errors
+        assert!(git::branch_create(&local, "bar", None, false).is_ok());
+        assert_eq!(git::ahead_behind(&local, "bar").unwrap_err().to_string(), git::Error::upstream_not_set("bar").to_string());
+        assert_eq!(git::ahead_behind(&local, "missing").unwrap_err().to_string(), git::Error::branch_not_found("missing").to_string());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_default_branch() {
+        let tmpdir = setup("git_default_branch");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let src = tmpdir.mash("src");
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+
+        // Conventional names
+        assert_eq!(git::default_branch(&src).unwrap(), "master");
+        assert!(git::branch_create(&src, "main", None, false).is_ok());
+        assert_eq!(git::default_branch(&src).unwrap(), "main");
+
+        // Configured default takes precedence when it exists
+        let repo = git2::Repository::open(&src).unwrap();
+        repo.config().unwrap().set_str("init.defaultBranch", "trunk").unwrap();
+        assert_eq!(git::default_branch(&src).unwrap(), "main");
+        assert!(git::branch_create(&src, "trunk", None, false).is_ok());
+        assert_eq!(git::default_branch(&src).unwrap(), "trunk");
+
+        // Remote's HEAD takes precedence over everything
+        assert!(git::branch_create(&src, "develop", None, true).is_ok());
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(src.to_string().unwrap()).clone().is_ok());
+        git2::Repository::open(&local).unwrap().config().unwrap().set_str("init.defaultBranch", "trunk").unwrap();
+        assert_eq!(git::default_branch(&local).unwrap(), "develop");
+
+        // Empty repo falls back to the configured default or master
+        let empty = tmpdir.mash("empty");
+        let repo = git2::Repository::init(&empty).unwrap();
+        assert_eq!(git::default_branch(&empty).unwrap(), "master");
+        repo.config().unwrap().set_str("init.defaultBranch", "trunk").unwrap();
+        assert_eq!(git::default_branch(&empty).unwrap(), "trunk");
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_branch_is_merged() {
+        let tmpdir = setup("git_branch_is_merged");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+        assert!(git::branch_create(tmpdir.mash("src"), "old", Some("HEAD~3"), false).is_ok());
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(tmpdir.mash("src").to_string().unwrap()).clone().is_ok());
+        let repo = git2::Repository::open(&local).unwrap();
+
+        // Same commit and ancestors are merged
+        assert_eq!(git::branch_is_merged(&local, "master", "master").unwrap(), true);
+        assert_eq!(git::branch_is_merged(&local, "origin/old", "master").unwrap(), true);
+        assert_eq!(git::branch_is_merged(&local, "master", "origin/old").unwrap(), false);
+
+        // Local branch with new work isn't merged
+        assert!(git::branch_create(&local, "foo", None, false).is_ok());
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let parent = repo.revparse_single("foo").unwrap().peel_to_commit().unwrap();
+        repo.commit(Some("refs/heads/foo"), &sig, &sig, "one", &parent.tree().unwrap(), &[&parent]).unwrap();
+        assert_eq!(git::branch_is_merged(&local, "foo", "master").unwrap(), false);
+        assert_eq!(git::branch_is_merged(&local, "foo", "origin/master").unwrap(), false);
+        assert_eq!(git::branch_is_merged(&local, "master", "foo").unwrap(), true);
+
+        // Errors
+        assert_eq!(git::branch_is_merged(&local, "missing", "master").unwrap_err().to_string(), git::Error::branch_not_found("missing").to_string());
+        assert_eq!(git::branch_is_merged(&local, "foo", "missing").unwrap_err().to_string(), git::Error::branch_not_found("missing").to_string());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
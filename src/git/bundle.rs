@@ -0,0 +1,656 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{build::CheckoutBuilder, ObjectType, Oid, PackBuilder, Repository, RevparseMode, Revwalk};
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+};
+
+/// Ref recorded in a bundle as listed in `BundleHeader` or updated by `bundle_fetch`
+#[derive(Clone, Debug, PartialEq)]
+pub struct BundleRef {
+    pub name: String, // Full ref name e.g. `refs/heads/master` or `HEAD`
+    pub oid: Oid,     // Id of the object the ref points to
+}
+
+/// Header of a bundle listing its refs and the commits it requires as returned by
+/// `bundle_create`, `bundle_header` and `bundle_verify`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BundleHeader {
+    pub refs: Vec<BundleRef>,     // Refs the bundle provides
+    pub prerequisites: Vec<Oid>,  // Commits a repo needs to have to use the bundle
+}
+
+/// Create a bundle of the given revisions in the given output file for transferring commits
+/// without a network connection, the same as `git bundle create <output> <revs>`. Revisions may
+/// be refs e.g. `master` or `v1.0.0`, ranges e.g. `v1.0.0..master` to include only the commits
+/// since `v1.0.0`, exclusions e.g. `^v1.0.0`, or `--all` for every ref. Refs among the revisions
+/// are recorded in the bundle unless their commit is excluded. The commits a range leaves out
+/// that the included commits build on are recorded as prerequisites the receiving repo needs.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_bundle_create_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let bundle = tmpdir.mash("../git_bundle_create_doc.bundle");
+/// let header = git::bundle_create(&tmpdir, &bundle, &["3.11..master"]).unwrap();
+/// assert_eq!(header.refs[0].name, "refs/heads/master");
+/// assert_eq!(header.prerequisites.len(), 1);
+/// assert!(sys::remove(&bundle).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn bundle_create<T, U, V>(path: T, output: U, revs: &[V]) -> Result<BundleHeader>
+where
+    T: AsRef<Path>,
+    U: AsRef<Path>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let mut header = BundleHeader::default();
+    let mut include = vec![];
+    let mut exclude = vec![];
+
+    // Resolve the revisions into the commits to include and exclude and the refs to record
+    for rev in revs.iter().map(|x| x.as_ref()) {
+        if rev == "--all" {
+            if let Ok(head) = repo.head() {
+                add_ref(&mut header, "HEAD", head.resolve()?.target());
+            }
+            for reference in repo.references()? {
+                let reference = reference?;
+                if let (Some(name), Some(oid)) = (reference.name(), reference.resolve()?.target()) {
+                    add_ref(&mut header, name, Some(oid));
+                    include.push(oid);
+                }
+            }
+        } else if let Some(rev) = rev.strip_prefix('^') {
+            exclude.push(resolve(&repo, rev)?.id());
+        } else {
+            let spec = repo.revparse(rev).map_err(|_| Error::revision_not_found(rev))?;
+            let mut name = rev;
+            if spec.mode().contains(RevparseMode::RANGE) {
+                if spec.mode().contains(RevparseMode::MERGE_BASE) {
+                    return Err(Error::invalid_bundle(output, format!("symmetric difference {} isn't supported", rev)));
+                }
+                exclude.extend(spec.from().map(|x| x.id()));
+                name = rev.split("..").nth(1).filter(|x| !x.is_empty()).unwrap_or("HEAD");
+            }
+            let object = spec.to().or_else(|| spec.from()).ok_or_else(|| Error::revision_not_found(rev))?;
+            include.push(object.id());
+            if name == "HEAD" {
+                add_ref(&mut header, "HEAD", Some(object.id()));
+            } else if let Ok(reference) = repo.resolve_reference_from_short_name(name) {
+                add_ref(&mut header, reference.name().unwrap_or(name), Some(object.id()));
+            }
+        }
+    }
+
+    // Find the commits to include dropping refs to excluded commits the same as git
+    let mut packbuilder = repo.packbuilder()?;
+    let mut walk = revwalk(&repo, &mut packbuilder, &include, &exclude)?;
+    let mut commits = HashSet::new();
+    for oid in walk.by_ref() {
+        commits.insert(oid?);
+    }
+    header.refs.retain(|x| repo.find_commit(x.oid).is_err() || commits.contains(&x.oid));
+    if header.refs.is_empty() {
+        return Err(Error::invalid_bundle(output, "refusing to create a bundle without refs"));
+    }
+
+    // Find the excluded commits the included ones build on
+    let mut prerequisites = vec![];
+    for oid in commits.iter() {
+        for parent in repo.find_commit(*oid)?.parent_ids() {
+            if !commits.contains(&parent) && !prerequisites.contains(&parent) {
+                prerequisites.push(parent);
+            }
+        }
+    }
+    prerequisites.sort();
+    header.prerequisites = prerequisites;
+
+    // Write the header followed by the pack, the tags and loose objects are already added
+    let mut walk = revwalk(&repo, &mut repo.packbuilder()?, &include, &exclude)?;
+    packbuilder.insert_walk(&mut walk)?;
+    let mut writer = BufWriter::new(File::create(output.as_ref()).map_err(FuError::from)?);
+    let mut text = "# v2 git bundle\n".to_string();
+    for oid in header.prerequisites.iter() {
+        text += &format!("-{} {}\n", oid, repo.find_commit(*oid)?.summary().unwrap_or_default());
+    }
+    for reference in header.refs.iter() {
+        text += &format!("{} {}\n", reference.oid, reference.name);
+    }
+    text += "\n";
+    writer.write_all(text.as_bytes()).map_err(FuError::from)?;
+    let mut result = Ok(());
+    packbuilder.foreach(|data| {
+        result = writer.write_all(data);
+        result.is_ok()
+    })?;
+    result.map_err(FuError::from)?;
+    writer.flush().map_err(FuError::from)?;
+    Ok(header)
+}
+
+/// Read the header of the given bundle listing its refs and prerequisites without a repo, the
+/// same as `git bundle list-heads <bundle>`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_bundle_header_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let bundle = tmpdir.mash("../git_bundle_header_doc.bundle");
+/// assert!(git::bundle_create(&tmpdir, &bundle, &["master"]).is_ok());
+/// let header = git::bundle_header(&bundle).unwrap();
+/// assert_eq!(header.refs[0].name, "refs/heads/master");
+/// assert_eq!(header.prerequisites, vec![]);
+/// assert!(sys::remove(&bundle).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn bundle_header<T: AsRef<Path>>(bundle: T) -> Result<BundleHeader> {
+    Ok(read_header(bundle.as_ref())?.0)
+}
+
+/// Check that the given bundle is valid and that the given repo has the commits it requires, the
+/// same as `git bundle verify <bundle>`. Returns the bundle's header.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_bundle_verify_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let bundle = tmpdir.mash("../git_bundle_verify_doc.bundle");
+/// assert!(git::bundle_create(&tmpdir, &bundle, &["3.11..master"]).is_ok());
+/// assert!(git::bundle_verify(&tmpdir, &bundle).is_ok());
+/// assert!(sys::remove(&bundle).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn bundle_verify<T, U>(path: T, bundle: U) -> Result<BundleHeader>
+where
+    T: AsRef<Path>,
+    U: AsRef<Path>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let (header, _) = read_header(bundle.as_ref())?;
+    check_prerequisites(&repo, &header)?;
+    Ok(header)
+}
+
+/// Fetch the given bundle's objects into the given repo and update refs according to the given
+/// refspecs, the same as `git fetch <bundle> <refspecs>`, e.g.
+/// `+refs/heads/*:refs/remotes/origin/*` to update the remote tracking branches from the
+/// bundle's branches. Refspecs without a `+` only allow fast-forward updates and no tag changes.
+/// The repo needs to have the bundle's prerequisites. Returns the refs that were updated.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_bundle_fetch_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let bundle = tmpdir.mash("../git_bundle_fetch_doc.bundle");
+/// assert!(git::bundle_create(&tmpdir, &bundle, &["master"]).is_ok());
+/// let refs = git::bundle_fetch(&tmpdir, &bundle, &["refs/heads/master:refs/heads/copy"]).unwrap();
+/// assert_eq!(refs[0].name, "refs/heads/copy");
+/// assert!(sys::remove(&bundle).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn bundle_fetch<T, U, V>(path: T, bundle: U, refspecs: &[V]) -> Result<Vec<BundleRef>>
+where
+    T: AsRef<Path>,
+    U: AsRef<Path>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let bundle = bundle.as_ref();
+    let (header, mut reader) = read_header(bundle)?;
+    check_prerequisites(&repo, &header)?;
+
+    // Work out the ref updates and refuse them all if any isn't allowed
+    let current = repo.head().ok().filter(|_| !repo.is_bare()).and_then(|x| x.name().map(|x| x.to_string()));
+    let mut updates = vec![];
+    for reference in header.refs.iter() {
+        for refspec in refspecs.iter().map(|x| x.as_ref()) {
+            let force = refspec.starts_with('+');
+            let dst = match map_ref(refspec.trim_start_matches('+'), &reference.name) {
+                Some(dst) => dst,
+                None => continue,
+            };
+            if current.as_deref() == Some(dst.as_str()) {
+                return Err(Error::invalid_bundle(bundle, format!("refusing to fetch into the current branch {}", dst)));
+            }
+            if !updates.iter().any(|(x, _, _): &(String, Oid, bool)| *x == dst) {
+                updates.push((dst, reference.oid, force));
+            }
+        }
+    }
+
+    // Import the pack which may be thin i.e. use the prerequisites as delta bases
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic).map_err(|_| Error::invalid_bundle(bundle, "missing pack data"))?;
+    if &magic != b"PACK" {
+        return Err(Error::invalid_bundle(bundle, "missing pack data"));
+    }
+    let odb = repo.odb()?;
+    let mut writer = odb.packwriter()?;
+    writer.write_all(&magic).map_err(FuError::from)?;
+    io::copy(&mut reader, &mut writer).map_err(FuError::from)?;
+    writer.commit()?;
+
+    // Update the refs now that the objects exist
+    let mut updated = vec![];
+    for (dst, oid, force) in updates.iter() {
+        if let Ok(old) = repo.refname_to_id(dst) {
+            if old == *oid {
+                continue;
+            }
+            if !force && (dst.starts_with("refs/tags/") || !repo.graph_descendant_of(*oid, old).unwrap_or(false)) {
+                return Err(Error::invalid_bundle(bundle, format!("rejected non fast-forward update of {}", dst)));
+            }
+        }
+    }
+    for (dst, oid, _) in updates {
+        if repo.refname_to_id(&dst).ok() == Some(oid) {
+            continue;
+        }
+        repo.reference(&dst, oid, true, &format!("fetch: {}", bundle.display()))?;
+        updated.push(BundleRef { name: dst, oid });
+    }
+    Ok(updated)
+}
+
+/// Clone the given bundle into the given directory the same as `git clone <bundle> <dst>`. The
+/// bundle's branches become remote tracking branches of `origin`, which points at the bundle so
+/// later bundles can be fetched the same way, and its tags are copied. The branch the bundle's
+/// HEAD points to is checked out, or `master`, `main` or the first branch if it has no HEAD.
+/// The bundle can't have prerequisites. Returns the path of the new repo.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_bundle_clone_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+/// let bundle = tmpdir.mash("repo.bundle");
+/// assert!(git::bundle_create(tmpdir.mash("src"), &bundle, &["--all"]).is_ok());
+/// assert!(git::bundle_clone(&bundle, tmpdir.mash("dst")).is_ok());
+/// assert!(tmpdir.mash("dst/README.md").exists());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn bundle_clone<T, U>(bundle: T, dst: U) -> Result<PathBuf>
+where
+    T: AsRef<Path>,
+    U: AsRef<Path>,
+{
+    let bundle = bundle.as_ref().abs()?;
+    let dst = dst.as_ref().abs()?;
+    let header = bundle_header(&bundle)?;
+    if !header.prerequisites.is_empty() {
+        return Err(Error::missing_prerequisites(&header.prerequisites.iter().map(|x| x.to_string()).collect::<Vec<_>>()));
+    }
+    if sys::is_dir(&dst) && !sys::paths(&dst)?.is_empty() {
+        return Err(Error::dir_not_empty(&dst));
+    }
+
+    // Fetch everything as remote tracking branches and tags of origin
+    let repo = Repository::init(&dst)?;
+    let url = bundle.to_string()?;
+    repo.remote_with_fetch("origin", &url, "+refs/heads/*:refs/remotes/origin/*")?;
+    bundle_fetch(&dst, &bundle, &["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"])?;
+
+    // Checkout the branch HEAD points to
+    let branches: Vec<&BundleRef> = header.refs.iter().filter(|x| x.name.starts_with("refs/heads/")).collect();
+    let head = header.refs.iter().find(|x| x.name == "HEAD");
+    let branch = match head {
+        Some(head) => {
+            let mut matching = branches.iter().filter(|x| x.oid == head.oid);
+            matching.clone().find(|x| x.name == "refs/heads/master").or_else(|| matching.next()).copied()
+        },
+        None => ["refs/heads/master", "refs/heads/main"]
+            .iter()
+            .find_map(|x| branches.iter().find(|y| y.name == *x))
+            .or_else(|| branches.first())
+            .copied(),
+    };
+    match branch {
+        Some(branch) => {
+            let name = branch.name.trim_start_matches("refs/heads/");
+            repo.reference(&branch.name, branch.oid, true, &format!("clone: from {}", url))?;
+            repo.reference_symbolic("refs/remotes/origin/HEAD", &format!("refs/remotes/origin/{}", name), true, "clone")?;
+            let mut config = repo.config()?;
+            config.set_str(&format!("branch.{}.remote", name), "origin")?;
+            config.set_str(&format!("branch.{}.merge", name), &branch.name)?;
+            repo.set_head(&branch.name)?;
+        },
+        None => match head {
+            Some(head) => repo.set_head_detached(head.oid)?,
+            None => return Ok(dst),
+        },
+    }
+    repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+    Ok(dst)
+}
+
+// Add the given ref to the header if it has a target and isn't already there
+fn add_ref(header: &mut BundleHeader, name: &str, oid: Option<Oid>) {
+    if let Some(oid) = oid {
+        if !header.refs.iter().any(|x| x.name == name) {
+            header.refs.push(BundleRef { name: name.to_string(), oid });
+        }
+    }
+}
+
+// Resolve the given revision to an object
+fn resolve<'a>(repo: &'a Repository, rev: &str) -> Result<git2::Object<'a>> {
+    repo.revparse_single(rev).map_err(|_| Error::revision_not_found(rev))
+}
+
+// Returns a walk of the commits reachable from the given objects but not the excluded ones. Tag
+// objects are added to the pack directly as are trees and blobs that aren't reached via commits.
+fn revwalk<'a>(repo: &'a Repository, packbuilder: &mut PackBuilder, include: &[Oid], exclude: &[Oid]) -> Result<Revwalk<'a>> {
+    let mut walk = repo.revwalk()?;
+    for oid in include {
+        let mut object = repo.find_object(*oid, None)?;
+        while let Some(tag) = object.as_tag() {
+            packbuilder.insert_object(tag.id(), None)?;
+            object = tag.target()?;
+        }
+        match object.kind() {
+            Some(ObjectType::Commit) => walk.push(object.id())?,
+            _ => packbuilder.insert_recursive(object.id(), None)?,
+        }
+    }
+    for oid in exclude {
+        if let Ok(commit) = repo.find_object(*oid, None)?.peel_to_commit() {
+            walk.hide(commit.id())?;
+        }
+    }
+    Ok(walk)
+}
+
+// Read the header of the given bundle leaving the reader at the start of the pack
+fn read_header(bundle: &Path) -> Result<(BundleHeader, BufReader<File>)> {
+    let mut reader = BufReader::new(File::open(bundle).map_err(FuError::from)?);
+    let mut header = BundleHeader::default();
+    let invalid = |reason: &str| Error::invalid_bundle(bundle, reason);
+    let mut line = String::new();
+    let mut first = true;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(|_| invalid("unreadable header"))? == 0 {
+            return Err(invalid("truncated header"));
+        }
+        let text = line.trim_end_matches('\n');
+        if first {
+            if text != "# v2 git bundle" && text != "# v3 git bundle" {
+                return Err(invalid("not a v2 or v3 bundle"));
+            }
+            first = false;
+        } else if text.is_empty() {
+            break;
+        } else if let Some(capability) = text.strip_prefix('@') {
+            if capability != "object-format=sha1" {
+                return Err(invalid(&format!("unsupported capability {}", capability)));
+            }
+        } else if let Some(prerequisite) = text.strip_prefix('-') {
+            let oid = prerequisite.split(' ').next().unwrap_or_default();
+            header.prerequisites.push(Oid::from_str(oid).map_err(|_| invalid(&format!("invalid prerequisite {}", oid)))?);
+        } else {
+            let mut parts = text.splitn(2, ' ');
+            let oid = parts.next().and_then(|x| Oid::from_str(x).ok()).ok_or_else(|| invalid(&format!("invalid ref {}", text)))?;
+            let name = parts.next().filter(|x| !x.is_empty()).ok_or_else(|| invalid(&format!("invalid ref {}", text)))?;
+            header.refs.push(BundleRef { name: name.to_string(), oid });
+        }
+    }
+    Ok((header, reader))
+}
+
+// Fail with the bundle's prerequisites the repo doesn't have
+fn check_prerequisites(repo: &Repository, header: &BundleHeader) -> Result<()> {
+    let missing: Vec<String> = header.prerequisites.iter().filter(|x| repo.find_commit(**x).is_err()).map(|x| x.to_string()).collect();
+    match missing.is_empty() {
+        true => Ok(()),
+        false => Err(Error::missing_prerequisites(&missing)),
+    }
+}
+
+// Returns the destination of the given ref according to the given `src:dst` refspec without its
+// force flag. Patterns with a `*` in both sides map the matched part.
+fn map_ref(refspec: &str, name: &str) -> Option<String> {
+    let (src, dst) = refspec.split_once(':')?;
+    if dst.is_empty() {
+        return None;
+    }
+    match src.split_once('*') {
+        Some((prefix, suffix)) => {
+            let matched = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            Some(dst.replacen('*', matched, 1))
+        },
+        None if src == name => Some(dst.to_string()),
+        None => None,
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Returns the output of the given git command
+    fn git(path: &Path, args: &[&str]) -> String {
+        let output = Command::new("git").args(args).current_dir(path).output().unwrap();
+        assert_eq!(output.status.success(), true, "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    // Commit a new file with the given name
+    fn commit(path: &Path, name: &str) -> git2::Oid {
+        assert!(sys::write(path.mash(name), name).is_ok());
+        git::CommitBuilder::new(path).stage_all(true).author("foo", "foo@example.com").message(format!("Add {}", name)).commit().unwrap()
+    }
+
+    #[test]
+    fn test_bundle() {
+        let tmpdir = setup("git_bundle");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let src = tmpdir.mash("src");
+        let dst = tmpdir.mash("dst");
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+        git(&src, &["-c", "user.name=foo", "-c", "user.email=foo@example.com", "tag", "-a", "v1.0.0", "-m", "Release"]);
+
+        // Full bundle matches what git reads from it
+        let full = tmpdir.mash("full.bundle");
+        let header = git::bundle_create(&src, &full, &["--all"]).unwrap();
+        assert_eq!(header.prerequisites, vec![]);
+        assert_eq!(header.refs[0].name, "HEAD");
+        assert!(header.refs.iter().any(|x| x.name == "refs/tags/3.11"));
+        assert_eq!(git::bundle_header(&full).unwrap(), header);
+        let heads: Vec<String> = header.refs.iter().map(|x| format!("{} {}", x.oid, x.name)).collect();
+        assert_eq!(git(&src, &["bundle", "list-heads", &full.to_string().unwrap()]).lines().collect::<Vec<_>>(), heads);
+        git(&src, &["bundle", "verify", &full.to_string().unwrap()]);
+
+        // Clone from the bundle
+        assert_eq!(git::bundle_clone(&full, &dst).unwrap(), dst);
+        assert_eq!(git(&dst, &["rev-parse", "HEAD"]), git(&src, &["rev-parse", "HEAD"]));
+        assert_eq!(git(&dst, &["rev-parse", "--abbrev-ref", "HEAD@{upstream}"]).trim(), "origin/master");
+        assert_eq!(git(&dst, &["rev-parse", "v1.0.0"]), git(&src, &["rev-parse", "v1.0.0"]));
+        assert_eq!(git(&dst, &["config", "remote.origin.url"]).trim(), full.to_string().unwrap());
+        assert_eq!(git(&dst, &["status", "--porcelain"]), "");
+        git(&dst, &["fsck", "--full", "--strict"]);
+        assert!(git::bundle_clone(&full, &dst).is_err());
+
+        // Incremental bundle with the previous tip as its prerequisite
+        let base = git(&src, &["rev-parse", "master"]).trim().to_string();
+        commit(&src, "foo");
+        let tip = commit(&src, "bar");
+        let inc = tmpdir.mash("inc.bundle");
+        let header = git::bundle_create(&src, &inc, &[format!("{}..master", base)]).unwrap();
+        assert_eq!(header.prerequisites, vec![git2::Oid::from_str(&base).unwrap()]);
+        assert_eq!(header.refs, vec![git::BundleRef { name: "refs/heads/master".to_string(), oid: tip }]);
+        git(&dst, &["bundle", "verify", &inc.to_string().unwrap()]);
+        assert!(git::bundle_verify(&dst, &inc).is_ok());
+        let refs = git::bundle_fetch(&dst, &inc, &["+refs/heads/*:refs/remotes/origin/*"]).unwrap();
+        assert_eq!(refs, vec![git::BundleRef { name: "refs/remotes/origin/master".to_string(), oid: tip }]);
+        assert_eq!(git::bundle_fetch(&dst, &inc, &["+refs/heads/*:refs/remotes/origin/*"]).unwrap(), vec![]);
+        git(&dst, &["fsck", "--full", "--strict"]);
+
+        // Git's thin bundles can be fetched
+        let next = commit(&src, "baz");
+        let thin = tmpdir.mash("thin.bundle");
+        git(&src, &["bundle", "create", &thin.to_string().unwrap(), &format!("{}..master", tip)]);
+        let refs = git::bundle_fetch(&dst, &thin, &["refs/heads/master:refs/remotes/origin/master"]).unwrap();
+        assert_eq!(refs[0].oid, next);
+        git(&dst, &["fsck", "--full", "--strict"]);
+
+        // Non fast-forward updates need to be forced
+        let err = git::bundle_fetch(&dst, &full, &["refs/heads/master:refs/remotes/origin/master"]).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::invalid_bundle(&full, "rejected non fast-forward update of refs/remotes/origin/master").to_string());
+        assert_eq!(git(&dst, &["rev-parse", "origin/master"]).trim(), next.to_string());
+        assert_eq!(git::bundle_fetch(&dst, &full, &["+refs/heads/master:refs/remotes/origin/master"]).unwrap().len(), 1);
+        assert!(git::bundle_fetch(&dst, &full, &["refs/heads/master:refs/heads/master"]).is_err());
+
+        // Missing prerequisites
+        let other = tmpdir.mash("other");
+        assert!(git2::Repository::init(&other).is_ok());
+        let err = git::bundle_fetch(&other, &inc, &["+refs/heads/*:refs/heads/*"]).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::missing_prerequisites(&[&base]).to_string());
+        assert!(git::bundle_verify(&other, &inc).is_err());
+        assert!(git::bundle_clone(&inc, tmpdir.mash("clone")).is_err());
+
+        // Errors
+        assert!(sys::write(tmpdir.mash("bad.bundle"), "foo\n").is_ok());
+        assert!(git::bundle_header(tmpdir.mash("bad.bundle")).is_err());
+        assert!(git::bundle_create(&src, tmpdir.mash("empty.bundle"), &["master..master"]).is_err());
+        assert!(git::bundle_create(&src, tmpdir.mash("empty.bundle"), &["HEAD~1"]).is_err());
+        assert!(git::bundle_create(&src, tmpdir.mash("missing.bundle"), &["missing"]).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_bundle_matches_git() {
+        let tmpdir = setup("git_bundle_matches_git");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let src = tmpdir.mash("src");
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+        git(&src, &["-c", "user.name=foo", "-c", "user.email=foo@example.com", "tag", "-a", "v1.0.0", "-m", "Release"]);
+        commit(&src, "foo");
+
+        // Same refs and prerequisites as git records for the same revisions
+        for revs in [vec!["3.11..master"], vec!["^3.11", "master"], vec!["master", "v1.0.0"], vec!["HEAD~2..master", "3.11"]] {
+            let (ours, theirs) = (tmpdir.mash("ours.bundle"), tmpdir.mash("theirs.bundle"));
+            let header = git::bundle_create(&src, &ours, &revs).unwrap();
+            let mut args = vec!["bundle", "create", "-q"];
+            let path = theirs.to_string().unwrap();
+            args.push(&path);
+            args.extend(revs.iter());
+            git(&src, &args);
+            assert_eq!(header, git::bundle_header(&theirs).unwrap(), "{:?}", revs);
+            assert_eq!(git::bundle_header(&ours).unwrap(), header);
+            git(&src, &["bundle", "verify", &ours.to_string().unwrap()]);
+        }
+
+        // Git clones and fetches our bundles
+        let full = tmpdir.mash("full.bundle");
+        assert!(git::bundle_create(&src, &full, &["--all"]).is_ok());
+        git(&tmpdir, &["clone", "-q", &full.to_string().unwrap(), "theirs"]);
+        let theirs = tmpdir.mash("theirs");
+        assert_eq!(git(&theirs, &["rev-parse", "HEAD"]), git(&src, &["rev-parse", "HEAD"]));
+        assert_eq!(git(&theirs, &["rev-parse", "v1.0.0"]), git(&src, &["rev-parse", "v1.0.0"]));
+        let tip = commit(&src, "bar");
+        let inc = tmpdir.mash("inc.bundle");
+        assert!(git::bundle_create(&src, &inc, &["HEAD~1..master"]).is_ok());
+        git(&theirs, &["fetch", "-q", &inc.to_string().unwrap(), "master:refs/remotes/origin/master"]);
+        assert_eq!(git(&theirs, &["rev-parse", "origin/master"]).trim(), tip.to_string());
+        git(&theirs, &["fsck", "--full", "--strict"]);
+
+        // We clone git's v3 bundles
+        let v3 = tmpdir.mash("v3.bundle");
+        git(&src, &["bundle", "create", "-q", "--version=3", &v3.to_string().unwrap(), "--all"]);
+        assert!(std::fs::read(&v3).unwrap().starts_with(b"# v3 git bundle\n@object-format=sha1\n"));
+        let ours = tmpdir.mash("ours");
+        assert!(git::bundle_clone(&v3, &ours).is_ok());
+        assert_eq!(git(&ours, &["rev-parse", "HEAD"]).trim(), tip.to_string());
+        assert_eq!(git(&ours, &["for-each-ref", "--format=%(objectname) %(refname:short)", "refs/tags"]), git(&src, &["for-each-ref", "--format=%(objectname) %(refname:short)", "refs/tags"]));
+        git(&ours, &["fsck", "--full", "--strict"]);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_bundle_fetch_rules() {
+        let tmpdir = setup("git_bundle_fetch_rules");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let src = tmpdir.mash("src");
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+        let bundle = tmpdir.mash("repo.bundle");
+        assert!(git::bundle_create(&src, &bundle, &["--all"]).is_ok());
+        let dst = tmpdir.mash("dst");
+        assert!(git::bundle_clone(&bundle, &dst).is_ok());
+
+        // Fetching into the checked out branch is refused without updating anything
+        let base = git(&src, &["rev-parse", "master"]).trim().to_string();
+        let tip = commit(&src, "foo");
+        assert!(git::bundle_create(&src, &bundle, &["master"]).is_ok());
+        let err = git::bundle_fetch(&dst, &bundle, &["refs/heads/*:refs/remotes/origin/*", "refs/heads/master:refs/heads/master"]).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::invalid_bundle(&bundle, "refusing to fetch into the current branch refs/heads/master").to_string());
+        assert_eq!(git(&dst, &["rev-parse", "origin/master"]).trim(), base);
+
+        // Refspecs only update the refs they match with the first matching refspec winning
+        let refs = git::bundle_fetch(&dst, &bundle, &["refs/heads/master:refs/heads/copy", "+refs/heads/master:refs/heads/other"]).unwrap();
+        assert_eq!(
+            refs,
+            vec![
+                git::BundleRef { name: "refs/heads/copy".to_string(), oid: tip },
+                git::BundleRef { name: "refs/heads/other".to_string(), oid: tip },
+            ]
+        );
+        assert!(git::bundle_fetch(&dst, &bundle, &["refs/tags/*:refs/tags/*"]).unwrap().is_empty());
+
+        // Tags are never moved without forcing
+        git(&src, &["tag", "-f", "3.11", "master"]);
+        assert!(git::bundle_create(&src, &bundle, &["3.11"]).is_ok());
+        let err = git::bundle_fetch(&dst, &bundle, &["refs/tags/*:refs/tags/*"]).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::invalid_bundle(&bundle, "rejected non fast-forward update of refs/tags/3.11").to_string());
+        assert_eq!(git::bundle_fetch(&dst, &bundle, &["+refs/tags/*:refs/tags/*"]).unwrap().len(), 1);
+        assert_eq!(git(&dst, &["rev-parse", "3.11"]).trim(), tip.to_string());
+        git(&dst, &["fsck", "--full", "--strict"]);
+
+        // Invalid headers
+        let invalid = |content: &[u8], reason: &str| {
+            let path = tmpdir.mash("invalid.bundle");
+            assert!(std::fs::write(&path, content).is_ok());
+            assert_eq!(git::bundle_header(&path).unwrap_err().to_string(), git::Error::invalid_bundle(&path, reason).to_string());
+        };
+        invalid(b"# v4 git bundle\n\n", "not a v2 or v3 bundle");
+        invalid(b"# v3 git bundle\n@object-format=sha256\n\n", "unsupported capability object-format=sha256");
+        invalid(b"# v2 git bundle\n-foo\n\n", "invalid prerequisite foo");
+        invalid(b"# v2 git bundle\nfoo refs/heads/master\n\n", "invalid ref foo refs/heads/master");
+        invalid(format!("# v2 git bundle\n{}\n\n", tip).as_bytes(), &format!("invalid ref {}", tip));
+        invalid(b"# v2 git bundle\n", "truncated header");
+        assert!(git::bundle_fetch(&dst, tmpdir.mash("invalid.bundle"), &["refs/*:refs/*"]).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
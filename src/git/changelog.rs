@@ -0,0 +1,287 @@
+//! Generate changelogs from the [Conventional Commits](https://www.conventionalcommits.org) made
+//! between two revisions grouped into breaking changes, features and fixes and rendered with a
+//! pluggable template.
+use super::{conventional, resolve_commit};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{Oid, Repository, Sort};
+
+/// Changelog entry for a single conventional commit
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub oid: Oid,                     // Id of the commit
+    pub commit: conventional::Commit, // Parsed conventional commit
+}
+
+impl Entry {
+    /// Returns the abbreviated commit id.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let commit = git::conventional::parse("fix: foo").unwrap();
+    /// let entry = git::changelog::Entry { oid: git2::Oid::zero(), commit };
+    /// assert_eq!(entry.short(), "0000000");
+    /// ```
+    pub fn short(&self) -> String {
+        self.oid.to_string()[..7].to_string()
+    }
+
+    /// Returns the description of the breaking change i.e. the breaking change footer when
+    /// given else the commit's description.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let commit = git::conventional::parse("feat!: foo\n\nBREAKING CHANGE: bar").unwrap();
+    /// let entry = git::changelog::Entry { oid: git2::Oid::zero(), commit };
+    /// assert_eq!(entry.breaking_description(), "bar");
+    /// ```
+    pub fn breaking_description(&self) -> &str {
+        self.commit.footer("BREAKING CHANGE").or_else(|| self.commit.footer("BREAKING-CHANGE")).unwrap_or(&self.commit.description)
+    }
+}
+
+/// Conventional commits between two revisions grouped for rendering, newest first. Breaking
+/// changes are listed under their type as well.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Changelog {
+    pub title: String,        // Title of the changelog e.g. the version being released
+    pub breaking: Vec<Entry>, // Breaking changes
+    pub features: Vec<Entry>, // `feat` commits
+    pub fixes: Vec<Entry>,    // `fix` commits
+    pub other: Vec<Entry>,    // Any other conventional commits e.g. `docs` or `chore`
+}
+
+impl Changelog {
+    /// Returns true if there are no entries at all.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::changelog::Changelog::default().is_empty(), true);
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.breaking.is_empty() && self.features.is_empty() && self.fixes.is_empty() && self.other.is_empty()
+    }
+
+    /// Render the changelog with the given template.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let log = git::changelog::Changelog { title: "v1.0.0".to_string(), ..Default::default() };
+    /// assert_eq!(log.render(&|log: &git::changelog::Changelog| format!("# {}", log.title)), "# v1.0.0");
+    /// ```
+    pub fn render<T: Template+?Sized>(&self, template: &T) -> String {
+        template.render(self)
+    }
+
+    /// Render the changelog as Markdown with the default `Markdown` template.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let log = git::changelog::Changelog { title: "v1.0.0".to_string(), ..Default::default() };
+    /// assert_eq!(log.to_markdown(), "## v1.0.0\n");
+    /// ```
+    pub fn to_markdown(&self) -> String {
+        self.render(&Markdown)
+    }
+}
+
+/// Template for rendering a changelog. Implemented for closures taking the changelog so that
+/// custom rendering doesn't require a new type.
+pub trait Template {
+    /// Render the given changelog
+    fn render(&self, log: &Changelog) -> String;
+}
+
+impl<F> Template for F
+where
+    F: Fn(&Changelog) -> String,
+{
+    fn render(&self, log: &Changelog) -> String {
+        self(log)
+    }
+}
+
+/// Default Markdown template rendering a heading with the title followed by a section per
+/// non-empty group of breaking changes, features and bug fixes. Other commits are left out.
+///
+/// ```text
+/// ## v1.1.0
+///
+/// ### Breaking Changes
+/// - **api:** drop v1 endpoints (abcdef1)
+///
+/// ### Features
+/// - **api:** drop v1 endpoints (abcdef1)
+/// - add foo (1234567)
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Markdown;
+
+impl Template for Markdown {
+    fn render(&self, log: &Changelog) -> String {
+        let mut out = format!("## {}\n", log.title);
+        let sections: [(&str, &[Entry], bool); 3] = [
+            ("Breaking Changes", &log.breaking, true),
+            ("Features", &log.features, false),
+            ("Bug Fixes", &log.fixes, false),
+        ];
+        for (heading, entries, breaking) in sections.iter() {
+            if entries.is_empty() {
+                continue;
+            }
+            out += &format!("\n### {}\n", heading);
+            for entry in entries.iter() {
+                let description = if *breaking { entry.breaking_description() } else { &entry.commit.description };
+                match &entry.commit.scope {
+                    Some(scope) => out += &format!("- **{}:** {} ({})\n", scope, description, entry.short()),
+                    None => out += &format!("- {} ({})\n", description, entry.short()),
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Generate the changelog for the conventional commits reachable from `to` but not from `from`,
+/// or all commits reachable from `to` when `from` isn't given, e.g. between two release tags.
+/// Commits that aren't conventional commits are skipped. The title defaults to `to`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_changelog_generate_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+/// assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("feat: add foo").commit().is_ok());
+/// let log = git::changelog::generate(&tmpdir, Some("3.11"), "HEAD").unwrap();
+/// assert_eq!(log.features[0].commit.description, "add foo");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn generate<T, U>(path: T, from: Option<&str>, to: U) -> Result<Changelog>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    revwalk.push(resolve_commit(&repo, to.as_ref())?)?;
+    if let Some(from) = from {
+        revwalk.hide(resolve_commit(&repo, from)?)?;
+    }
+
+    let mut log = Changelog { title: to.as_ref().to_string(), ..Default::default() };
+    for oid in revwalk {
+        let oid = oid?;
+        let commit = match conventional::parse(repo.find_commit(oid)?.message().unwrap_or_default()) {
+            Ok(commit) => commit,
+            Err(_) => continue,
+        };
+        let entry = Entry { oid, commit };
+        if entry.commit.breaking {
+            log.breaking.push(entry.clone());
+        }
+        match entry.commit.kind.to_lowercase().as_str() {
+            "feat" => log.features.push(entry),
+            "fix" => log.fixes.push(entry),
+            _ => log.other.push(entry),
+        }
+    }
+    Ok(log)
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use git::changelog::{self, Changelog};
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Commit a change to the given file with the given message returning the short id
+    fn commit(path: &Path, file: &str, message: &str) -> String {
+        assert!(sys::write(path.mash(file), message).is_ok());
+        let oid = git::CommitBuilder::new(path).stage_all(true).author("foo", "foo@example.com").message(message).commit().unwrap();
+        oid.to_string()[..7].to_string()
+    }
+
+    #[test]
+    fn test_generate() {
+        let tmpdir = setup("git_changelog_generate");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        assert!(git::tag_lightweight(&tmpdir, "v1.0.0", "HEAD").is_ok());
+
+        // Nothing between the same revisions
+        let log = changelog::generate(&tmpdir, Some("v1.0.0"), "HEAD").unwrap();
+        assert_eq!(log.is_empty(), true);
+        assert_eq!(log.to_markdown(), "## HEAD\n");
+
+        // Grouped newest first skipping non conventional commits
+        let fix1 = commit(&tmpdir, "foo", "fix(foo): handle empty input");
+        let feat1 = commit(&tmpdir, "bar", "feat: add bar");
+        commit(&tmpdir, "foo", "Update foo");
+        commit(&tmpdir, "foo", "docs: document foo");
+        let feat2 = commit(&tmpdir, "baz", "feat(api)!: drop v1 endpoints\n\nBREAKING CHANGE: v1 clients must upgrade");
+        assert!(git::tag_lightweight(&tmpdir, "v2.0.0", "HEAD").is_ok());
+        let fix2 = commit(&tmpdir, "foo", "fix: later fix");
+
+        let log = changelog::generate(&tmpdir, Some("v1.0.0"), "v2.0.0").unwrap();
+        assert_eq!(log.title, "v2.0.0");
+        assert_eq!(log.breaking.len(), 1);
+        assert_eq!(log.breaking[0].short(), feat2);
+        assert_eq!(log.features.iter().map(|x| x.short()).collect::<Vec<_>>(), vec![feat2.clone(), feat1.clone()]);
+        assert_eq!(log.fixes.iter().map(|x| x.short()).collect::<Vec<_>>(), vec![fix1.clone()]);
+        assert_eq!(log.other.len(), 1);
+        assert_eq!(log.other[0].commit.kind, "docs");
+
+        // Markdown
+        let expected = format!(
+            "## v2.0.0\n\n### Breaking Changes\n- **api:** v1 clients must upgrade ({})\n\n### Features\n- **api:** drop v1 endpoints ({})\n- add bar ({})\n\n### Bug Fixes\n- **foo:** handle empty input ({})\n",
+            feat2, feat2, feat1, fix1
+        );
+        assert_eq!(log.to_markdown(), expected);
+
+        // Custom template
+        let template = |log: &Changelog| format!("{}: {} features, {} fixes", log.title, log.features.len(), log.fixes.len());
+        assert_eq!(log.render(&template), "v2.0.0: 2 features, 1 fixes");
+        struct Plain;
+        impl changelog::Template for Plain {
+            fn render(&self, log: &Changelog) -> String {
+                log.fixes.iter().map(|x| x.commit.description.clone()).collect::<Vec<_>>().join(",")
+            }
+        }
+        assert_eq!(changelog::generate(&tmpdir, Some("v2.0.0"), "HEAD").unwrap().render(&Plain), "later fix");
+        let templates: Vec<Box<dyn changelog::Template>> = vec![Box::new(Plain), Box::new(changelog::Markdown)];
+        assert_eq!(log.render(templates[0].as_ref()), "handle empty input");
+
+        // Whole history
+        let log = changelog::generate(&tmpdir, None, "HEAD").unwrap();
+        assert_eq!(log.fixes.iter().map(|x| x.short()).collect::<Vec<_>>(), vec![fix2, fix1]);
+
+        // Errors
+        assert!(changelog::generate(&tmpdir, Some("missing"), "HEAD").is_err());
+        assert!(changelog::generate(&tmpdir, None, "missing").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
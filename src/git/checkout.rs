@@ -0,0 +1,179 @@
+use super::{checkout_opts, Repo};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{BranchType, IndexEntryExtendedFlag, Repository, Status, StatusOptions};
+use std::cell::RefCell;
+
+/// Returns true if the repo's worktree or index has changes to tracked files. Untracked and
+/// ignored files are not considered as checkouts leave them in place, nor are files missing from
+/// a sparse checkout.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_is_dirty_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+/// assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+/// assert_eq!(git::is_dirty(&tmpdir).unwrap(), true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn is_dirty<T: AsRef<Path>>(path: T) -> Result<bool> {
+    has_changes(path.as_ref(), false)
+}
+
+// Returns true if the repo's worktree or index has changes to tracked files or optionally
+// untracked files. Ignored files and files missing from a sparse checkout are never considered.
+pub(crate) fn has_changes(path: &Path, untracked: bool) -> Result<bool> {
+    let repo = Repository::open(path)?;
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(untracked).include_ignored(false);
+    let statuses = repo.statuses(Some(&mut opts))?;
+    if statuses.is_empty() {
+        return Ok(false);
+    }
+
+    // libgit2 reports skip-worktree files missing from the worktree as deleted
+    let index = repo.index()?;
+    for entry in statuses.iter() {
+        let skipped = match (entry.status(), entry.path()) {
+            (Status::WT_DELETED, Some(path)) => index.get_path(Path::new(path), 0).map(|x| IndexEntryExtendedFlag::from_bits_truncate(x.flags_extended).is_skip_worktree()).unwrap_or(false),
+            _ => false,
+        };
+        if !skipped {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+impl<'a> Repo<'a> {
+    /// Checkout the given branch, tag or commit reporting progress through the checkout
+    /// progress callback. Local branches are checked out as HEAD while anything else detaches
+    /// HEAD at the resolved commit. Checkout is refused if the worktree has uncommitted changes
+    /// unless `force` is set in which case the changes are discarded.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_checkout_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// assert!(git::Repo::new(&tmpdir).unwrap().checkout("3.11", false).is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn checkout<T: AsRef<str>>(mut self, rev: T, force: bool) -> Result<()> {
+        let rev = rev.as_ref();
+        let repo = Repository::open(self.path_val())?;
+        if !force && is_dirty(self.path_val())? {
+            return Err(Error::dirty_worktree(self.path_val()));
+        }
+
+        // Resolve the target preferring local branches
+        let branch = repo.find_branch(rev, BranchType::Local).ok().and_then(|x| x.get().name().map(|y| y.to_string()));
+        let commit = match &branch {
+            Some(refname) => repo.find_reference(refname)?.peel_to_commit()?,
+            None => repo.revparse_single(rev).map_err(|_| Error::branch_not_found(rev))?.peel_to_commit()?,
+        };
+
+        // Update the worktree then point HEAD at the target
+        let checkout = self.checkout_progress.take().map(RefCell::new);
+        let mut opts = checkout.as_ref().map(checkout_opts).unwrap_or_default();
+        if force {
+            opts.force();
+        } else {
+            opts.safe();
+        }
+        repo.checkout_tree(commit.as_object(), Some(&mut opts))?;
+        match &branch {
+            Some(refname) => repo.set_head(refname)?,
+            None => repo.set_head_detached(commit.id())?,
+        }
+        Ok(())
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_is_dirty() {
+        let tmpdir = setup("git_is_dirty");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+
+        // Untracked files don't count
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+        assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+
+        // Modified and staged files do
+        assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), true);
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force())).unwrap();
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("foo")).unwrap();
+        index.write().unwrap();
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), true);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_checkout() {
+        let tmpdir = setup("git_checkout");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let head = repo.head().unwrap().target().unwrap();
+        let old = repo.revparse_single("HEAD~5").unwrap().id();
+
+        // Commit detaches HEAD and reports progress
+        let mut progress = 0;
+        assert!(git::Repo::new(&tmpdir).unwrap().checkout_progress(|_, _| progress += 1).checkout("HEAD~5", false).is_ok());
+        assert!(progress > 0);
+        assert_eq!(repo.head_detached().unwrap(), true);
+        assert_eq!(repo.head().unwrap().target(), Some(old));
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+
+        // Tag detaches HEAD
+        assert!(git::Repo::new(&tmpdir).unwrap().checkout("3.11", false).is_ok());
+        assert_eq!(repo.head_detached().unwrap(), true);
+
+        // Branch attaches HEAD
+        assert!(git::Repo::new(&tmpdir).unwrap().checkout("master", false).is_ok());
+        assert_eq!(repo.head_detached().unwrap(), false);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("master"));
+        assert_eq!(repo.head().unwrap().target(), Some(head));
+
+        // Dirty worktree is refused unless forced
+        assert!(git::branch_create(&tmpdir, "foo", Some("HEAD~5"), false).is_ok());
+        assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+        assert_eq!(git::Repo::new(&tmpdir).unwrap().checkout("foo", false).unwrap_err().to_string(), git::Error::dirty_worktree(&tmpdir).to_string());
+        assert_eq!(repo.head().unwrap().shorthand(), Some("master"));
+        assert!(git::Repo::new(&tmpdir).unwrap().checkout("foo", true).is_ok());
+        assert_eq!(repo.head().unwrap().shorthand(), Some("foo"));
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+
+        // Missing
+        assert_eq!(git::Repo::new(&tmpdir).unwrap().checkout("missing", false).unwrap_err().to_string(), git::Error::branch_not_found("missing").to_string());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,197 @@
+use super::{conflicts_error, is_dirty, resolve_commit, write_commit};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{build::CheckoutBuilder, CherrypickOptions, Oid, Repository, Sort};
+
+/// Apply the changes of the given commit onto HEAD creating a new commit with the original
+/// author and message, the same as `git cherry-pick`. The configured `user.name` and
+/// `user.email` are used as the committer. When `annotate` is set a `(cherry picked from commit
+/// <id>)` line is appended to the message, the same as `git cherry-pick -x`. Cherry picking is
+/// refused if the worktree has uncommitted changes. On conflicts `Error::MergeConflicts` is
+/// returned and the repo is left mid cherry pick with the conflicts in the worktree and index.
+/// Returns the id of the new commit.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_cherry_pick_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "test").unwrap();
+/// repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+/// assert!(git::branch_create(&tmpdir, "release", Some("HEAD~1"), true).is_ok());
+/// assert!(git::cherry_pick(&tmpdir, "master", true).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn cherry_pick<T, U>(path: T, rev: U, annotate: bool) -> Result<Oid>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let path = path.as_ref();
+    let repo = Repository::open(path)?;
+    if is_dirty(path)? {
+        return Err(Error::dirty_worktree(path));
+    }
+    let oid = resolve_commit(&repo, rev.as_ref())?;
+    apply(&repo, oid, annotate)
+}
+
+/// Cherry pick the commits reachable from `to` but not from `from` onto HEAD oldest first, the
+/// same as `git cherry-pick <from>..<to>`. Stops at the first conflict leaving the repo mid
+/// cherry pick. Returns the ids of the new commits in the order they were created.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_cherry_pick_range_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "test").unwrap();
+/// repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+/// assert!(git::branch_create(&tmpdir, "release", Some("HEAD~2"), true).is_ok());
+/// assert_eq!(git::cherry_pick_range(&tmpdir, "HEAD", "master", false).unwrap().len(), 2);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn cherry_pick_range<T, U, V>(path: T, from: U, to: V, annotate: bool) -> Result<Vec<Oid>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let path = path.as_ref();
+    let repo = Repository::open(path)?;
+    if is_dirty(path)? {
+        return Err(Error::dirty_worktree(path));
+    }
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    revwalk.push(resolve_commit(&repo, to.as_ref())?)?;
+    revwalk.hide(resolve_commit(&repo, from.as_ref())?)?;
+    let commits = revwalk.collect::<std::result::Result<Vec<Oid>, git2::Error>>()?;
+
+    let mut oids = vec![];
+    for oid in commits {
+        oids.push(apply(&repo, oid, annotate)?);
+    }
+    Ok(oids)
+}
+
+// Cherry pick the given commit onto HEAD
+fn apply(repo: &Repository, oid: Oid, annotate: bool) -> Result<Oid> {
+    let commit = repo.find_commit(oid)?;
+    let mut opts = CherrypickOptions::new();
+    let mut checkout = CheckoutBuilder::new();
+    checkout.safe().allow_conflicts(true).conflict_style_merge(true);
+    opts.checkout_builder(checkout);
+    repo.cherrypick(&commit, Some(&mut opts))?;
+
+    let mut index = repo.index()?;
+    if index.has_conflicts() {
+        return Err(conflicts_error(&index)?);
+    }
+    let mut message = commit.message().unwrap_or_default().to_string();
+    if annotate {
+        message = format!("{}\n\n(cherry picked from commit {})\n", message.trim_end(), oid);
+    }
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let head = repo.head()?.peel_to_commit()?;
+    let oid = write_commit(repo, &commit.author(), &repo.signature()?, &message, &tree, &[&head], None, false)?;
+    repo.cleanup_state()?;
+    Ok(oid)
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Extract the test repo with a test identity
+    fn init(path: &Path) -> git2::Repository {
+        assert!(sys::remove_all(path).is_ok());
+        assert!(tar::extract_all(path.mash("../../alpine-base.tgz"), path).is_ok());
+        let repo = git2::Repository::open(path).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        repo
+    }
+
+    // Commit the given content to the given file as a different author
+    fn commit(path: &Path, file: &str, content: &str) -> git2::Oid {
+        assert!(sys::write(path.mash(file), content).is_ok());
+        git::CommitBuilder::new(path).stage_all(true).author("foo", "foo@example.com").message(format!("Fix {}", file)).commit().unwrap()
+    }
+
+    // Checkout the given branch
+    fn checkout(path: &Path, branch: &str) {
+        assert!(git::Repo::new(path).unwrap().checkout(branch, false).is_ok());
+    }
+
+    #[test]
+    fn test_cherry_pick() {
+        let tmpdir = setup("git_cherry_pick");
+        let repo = init(&tmpdir);
+        assert!(git::branch_create(&tmpdir, "release", Some("HEAD~3"), false).is_ok());
+        let foo = commit(&tmpdir, "foo", "foo");
+        let bar = commit(&tmpdir, "bar", "bar");
+        let baz = commit(&tmpdir, "baz", "baz");
+        checkout(&tmpdir, "release");
+        let base = repo.head().unwrap().target().unwrap();
+
+        // Single commit keeping the original author
+        let oid = git::cherry_pick(&tmpdir, foo.to_string(), false).unwrap();
+        let picked = repo.find_commit(oid).unwrap();
+        assert_eq!(picked.parent_id(0).unwrap(), base);
+        assert_eq!(picked.message(), Some("Fix foo"));
+        assert_eq!(picked.author().name(), Some("foo"));
+        assert_eq!(picked.author().when(), repo.find_commit(foo).unwrap().author().when());
+        assert_eq!(picked.committer().name(), Some("test"));
+        assert_eq!(repo.head().unwrap().shorthand(), Some("release"));
+        assert_eq!(repo.head().unwrap().target(), Some(oid));
+        assert_eq!(sys::readstring(tmpdir.mash("foo")).unwrap(), "foo");
+        assert_eq!(tmpdir.mash("bar").exists(), false);
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+
+        // Annotated range oldest first
+        let oids = git::cherry_pick_range(&tmpdir, foo.to_string(), "master", true).unwrap();
+        assert_eq!(oids.len(), 2);
+        assert_eq!(repo.find_commit(oids[0]).unwrap().message().unwrap(), format!("Fix bar\n\n(cherry picked from commit {})\n", bar));
+        assert_eq!(repo.find_commit(oids[1]).unwrap().message().unwrap(), format!("Fix baz\n\n(cherry picked from commit {})\n", baz));
+        assert_eq!(repo.find_commit(oids[1]).unwrap().parent_id(0).unwrap(), oids[0]);
+        assert_eq!(repo.head().unwrap().target(), Some(oids[1]));
+        assert_eq!(sys::readstring(tmpdir.mash("baz")).unwrap(), "baz");
+
+        // Conflicts leave the repo mid cherry pick
+        checkout(&tmpdir, "master");
+        let theirs = commit(&tmpdir, "README.md", "theirs");
+        checkout(&tmpdir, "release");
+        let head = commit(&tmpdir, "README.md", "ours");
+        let err = git::cherry_pick(&tmpdir, theirs.to_string(), false).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::merge_conflicts(&["README.md"]).to_string());
+        assert_eq!(repo.state(), git2::RepositoryState::CherryPick);
+        assert_eq!(repo.head().unwrap().target(), Some(head));
+
+        // Dirty worktree and missing commit
+        let err = git::cherry_pick(&tmpdir, "master", false).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::dirty_worktree(&tmpdir).to_string());
+        repo.cleanup_state().unwrap();
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force())).unwrap();
+        assert!(git::cherry_pick(&tmpdir, "missing", false).is_err());
+        assert!(git::cherry_pick_range(&tmpdir, "missing", "master", false).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
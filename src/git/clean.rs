@@ -0,0 +1,347 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::Repository;
+
+/// Options for removing untracked files with `clean`
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let opts = git::CleanOptions::new().dry_run(true).directories(true).ignored(true);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CleanOptions {
+    dry_run: bool,      // Only report what would be removed
+    directories: bool,  // Remove untracked directories as well as files
+    ignored: bool,      // Remove ignored files as well as untracked ones
+    paths: Vec<String>, // Pathspecs to limit the clean to
+}
+
+impl CleanOptions {
+    /// Create new clean options that remove untracked files only, the same as `git clean -f`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::CleanOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set to only report what would be removed without removing anything, the same as
+    /// `git clean -n`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::CleanOptions::new().dry_run(true).dry_run_val(), true);
+    /// ```
+    pub fn dry_run(mut self, yes: bool) -> Self {
+        self.dry_run = yes;
+        self
+    }
+
+    /// Get whether only what would be removed is reported.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::CleanOptions::new().dry_run_val(), false);
+    /// ```
+    pub fn dry_run_val(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Set to remove untracked directories as well as files, the same as `git clean -d`.
+    /// Directories containing a nested repo are never removed.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::CleanOptions::new().directories(true).directories_val(), true);
+    /// ```
+    pub fn directories(mut self, yes: bool) -> Self {
+        self.directories = yes;
+        self
+    }
+
+    /// Get whether untracked directories are removed.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::CleanOptions::new().directories_val(), false);
+    /// ```
+    pub fn directories_val(&self) -> bool {
+        self.directories
+    }
+
+    /// Set to remove ignored files as well as untracked ones, the same as `git clean -x`.
+    /// Ignored directories are only removed if `directories` is also set.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::CleanOptions::new().ignored(true).ignored_val(), true);
+    /// ```
+    pub fn ignored(mut self, yes: bool) -> Self {
+        self.ignored = yes;
+        self
+    }
+
+    /// Get whether ignored files are removed.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::CleanOptions::new().ignored_val(), false);
+    /// ```
+    pub fn ignored_val(&self) -> bool {
+        self.ignored
+    }
+
+    /// Set the pathspecs to limit the clean to e.g. `build` or `*.log`, the same as
+    /// `git clean -- <pathspecs>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::CleanOptions::new().paths(&["build"]);
+    /// assert_eq!(opts.paths_val(), &["build".to_string()]);
+    /// ```
+    pub fn paths<T, U>(mut self, paths: T) -> Self
+    where
+        T: IntoIterator<Item=U>,
+        U: AsRef<str>,
+    {
+        self.paths = paths.into_iter().map(|x| x.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Get the pathspecs the clean is limited to. Empty when all paths are included.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert!(git::CleanOptions::new().paths_val().is_empty());
+    /// ```
+    pub fn paths_val(&self) -> &[String] {
+        &self.paths
+    }
+}
+
+/// Remove the repo's untracked files and optionally directories and ignored files, the same as
+/// `git clean -f`. Returns the paths relative to the worktree that were removed, or would be
+/// with `dry_run`, sorted by path. Directories are reported as a single path rather than by the
+/// files they contain. Untracked directories holding ignored files or nested repos are kept with
+/// only their untracked contents removed.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_clean_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+/// assert_eq!(git::clean(&tmpdir, &git::CleanOptions::new()).unwrap(), vec![PathBuf::from("foo")]);
+/// assert_eq!(tmpdir.mash("foo").exists(), false);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn clean<T: AsRef<Path>>(path: T, opts: &CleanOptions) -> Result<Vec<PathBuf>> {
+    let repo = Repository::open(path.as_ref())?;
+    let workdir = repo.workdir().ok_or_else(|| git2::Error::from_str("cannot clean a bare repository"))?.to_path_buf();
+    let mut statusopts = git2::StatusOptions::new();
+    statusopts.include_untracked(true).recurse_untracked_dirs(false).include_ignored(opts.ignored).recurse_ignored_dirs(false);
+    for path in opts.paths.iter() {
+        statusopts.pathspec(path);
+    }
+
+    // Untracked directories are reported with a trailing slash
+    let mut paths = vec![];
+    for entry in repo.statuses(Some(&mut statusopts))?.iter() {
+        let status = entry.status();
+        let name = String::from_utf8_lossy(entry.path_bytes()).to_string();
+        if !status.is_wt_new() && !status.is_ignored() {
+            continue;
+        }
+        match name.ends_with('/') {
+            true if opts.directories => paths.extend(clean_dir(&repo, &workdir, Path::new(name.trim_end_matches('/')), opts.ignored)?),
+            true => {},
+            false => paths.push(PathBuf::from(name)),
+        }
+    }
+    paths.sort();
+
+    if !opts.dry_run {
+        for path in paths.iter() {
+            let path = workdir.mash(path);
+            if path.is_dir() {
+                sys::remove_all(&path)?;
+            } else {
+                sys::remove(&path)?;
+            }
+        }
+    }
+    Ok(paths)
+}
+
+// Returns the paths to remove for the given untracked or ignored directory i.e. the directory
+// itself when everything in it is to be removed, otherwise the untracked contents it can lose
+// while keeping any ignored files, unless they are being removed as well, and nested repos the
+// same as git.
+fn clean_dir(repo: &Repository, workdir: &Path, dir: &Path, ignored: bool) -> Result<Vec<PathBuf>> {
+    if workdir.mash(dir).mash(".git").exists() {
+        return Ok(vec![]);
+    }
+    let mut paths = vec![];
+    let mut keep = false;
+    for entry in fs::read_dir(workdir.mash(dir)).map_err(FuError::from)? {
+        let entry = entry.map_err(FuError::from)?;
+        let path = dir.join(entry.file_name());
+        let is_dir = entry.file_type().map_err(FuError::from)?.is_dir();
+        if !ignored && repo.is_path_ignored(if is_dir { format!("{}/", path.to_string()?) } else { path.to_string()? })? {
+            keep = true;
+        } else if is_dir {
+            let sub = clean_dir(repo, workdir, &path, ignored)?;
+            keep |= sub != [path.clone()];
+            paths.extend(sub);
+        } else {
+            paths.push(path);
+        }
+    }
+    match keep {
+        true => Ok(paths),
+        false => Ok(vec![dir.to_path_buf()]),
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_clean() {
+        let tmpdir = setup("git_clean");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let paths = |x: &[&str]| x.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert!(git::clean(&tmpdir, &git::CleanOptions::new()).unwrap().is_empty());
+
+        // Untracked and ignored files and directories
+        assert!(sys::write(tmpdir.mash(".git/info/exclude"), "*.log\nbuild/\n").is_ok());
+        assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+        assert!(sys::write(tmpdir.mash("config/bar"), "bar").is_ok());
+        assert!(sys::write(tmpdir.mash("debug.log"), "log").is_ok());
+        assert!(sys::mkdir(tmpdir.mash("dir/sub")).is_ok());
+        assert!(sys::write(tmpdir.mash("dir/sub/baz"), "baz").is_ok());
+        assert!(sys::mkdir(tmpdir.mash("build")).is_ok());
+        assert!(sys::write(tmpdir.mash("build/out"), "out").is_ok());
+        assert!(git2::Repository::init(tmpdir.mash("nested")).is_ok());
+        assert!(sys::write(tmpdir.mash("README.md"), "modified").is_ok());
+
+        // Dry runs report without removing
+        let opts = git::CleanOptions::new().dry_run(true);
+        assert_eq!(git::clean(&tmpdir, &opts).unwrap(), paths(&["config/bar", "foo"]));
+        assert_eq!(git::clean(&tmpdir, &opts.clone().directories(true)).unwrap(), paths(&["config/bar", "dir", "foo"]));
+        assert_eq!(git::clean(&tmpdir, &opts.clone().ignored(true)).unwrap(), paths(&["config/bar", "debug.log", "foo"]));
+        let all = opts.clone().directories(true).ignored(true);
+        assert_eq!(git::clean(&tmpdir, &all).unwrap(), paths(&["build", "config/bar", "debug.log", "dir", "foo"]));
+        assert_eq!(git::clean(&tmpdir, &all.clone().paths(["dir", "*.log"])).unwrap(), paths(&["debug.log", "dir"]));
+        assert!(tmpdir.mash("foo").exists());
+
+        // Removal leaves tracked changes and nested repos
+        assert_eq!(git::clean(&tmpdir, &git::CleanOptions::new()).unwrap(), paths(&["config/bar", "foo"]));
+        assert_eq!(tmpdir.mash("foo").exists(), false);
+        assert_eq!(tmpdir.mash("config/bar").exists(), false);
+        assert!(tmpdir.mash("dir/sub/baz").exists());
+        let all = all.dry_run(false);
+        assert_eq!(git::clean(&tmpdir, &all).unwrap(), paths(&["build", "debug.log", "dir"]));
+        assert_eq!(tmpdir.mash("dir").exists(), false);
+        assert_eq!(tmpdir.mash("build").exists(), false);
+        assert!(tmpdir.mash("nested/.git").exists());
+        assert_eq!(sys::readstring(tmpdir.mash("README.md")).unwrap(), "modified");
+        assert!(git::clean(&tmpdir, &all).unwrap().is_empty());
+
+        // Errors
+        assert!(git::clean(tmpdir.mash("missing"), &git::CleanOptions::new()).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_clean_matches_git() {
+        let tmpdir = setup("git_clean_matches_git");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let (ours, theirs) = (tmpdir.mash("ours"), tmpdir.mash("theirs"));
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &ours).is_ok());
+        let files = |path: &Path| {
+            let output = Command::new("find").arg(".").arg("-not").arg("-path").arg("./.git*").current_dir(path).output().unwrap();
+            let mut files: Vec<String> = String::from_utf8(output.stdout).unwrap().lines().map(|x| x.to_string()).collect();
+            files.sort();
+            files
+        };
+
+        // Untracked directories holding ignored files, nested untracked directories and repos
+        assert!(sys::write(ours.mash(".git/info/exclude"), "*.env\n").is_ok());
+        for file in &["d/a", "d/secret.env", "d/sub/b", "e/x/c", "f/g.env", "n/m/k.env", "n/z"] {
+            assert!(sys::mkdir(ours.mash(file).dir().unwrap()).is_ok());
+            assert!(sys::write(ours.mash(file), "foo").is_ok());
+        }
+        assert!(git2::Repository::init(ours.mash("d/nested")).is_ok());
+        assert!(sys::write(ours.mash("d/nested/h"), "foo").is_ok());
+
+        // Only untracked contents are removed leaving ignored files, nested repos and their
+        // directories the same as git clean
+        for (opts, args) in [
+            (git::CleanOptions::new(), ["clean", "-f"]),
+            (git::CleanOptions::new().directories(true), ["clean", "-fd"]),
+            (git::CleanOptions::new().directories(true).ignored(true), ["clean", "-fdx"]),
+        ] {
+            assert!(sys::remove_all(&theirs).is_ok());
+            assert_eq!(Command::new("cp").arg("-a").arg(&ours).arg(&theirs).status().unwrap().success(), true);
+            let output = Command::new("git").args(args).arg("-n").current_dir(&theirs).output().unwrap();
+            let mut expected: Vec<PathBuf> = String::from_utf8(output.stdout).unwrap().lines().filter_map(|x| x.strip_prefix("Would remove ")).map(|x| PathBuf::from(x.trim_end_matches('/'))).collect();
+            expected.sort();
+            assert_eq!(git::clean(&theirs, &opts.clone().dry_run(true)).unwrap(), expected);
+            let cleaned = theirs.dir().unwrap().mash("cleaned");
+            assert!(sys::remove_all(&cleaned).is_ok());
+            assert_eq!(Command::new("cp").arg("-a").arg(&theirs).arg(&cleaned).status().unwrap().success(), true);
+            assert_eq!(git::clean(&cleaned, &opts).unwrap(), expected);
+            assert_eq!(Command::new("git").args(args).current_dir(&theirs).status().unwrap().success(), true);
+            assert_eq!(files(&cleaned), files(&theirs));
+        }
+        let opts = git::CleanOptions::new().directories(true);
+        assert_eq!(git::clean(&ours, &opts).unwrap().contains(&PathBuf::from("d/a")), true);
+        assert_eq!(ours.mash("d/a").exists(), false);
+        assert_eq!(ours.mash("d/secret.env").exists(), true);
+        assert_eq!(ours.mash("d/nested/h").exists(), true);
+        assert_eq!(ours.mash("d/sub").exists(), false);
+        assert_eq!(ours.mash("e").exists(), false);
+        assert_eq!(ours.mash("n/m/k.env").exists(), true);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,722 @@
+use super::{is_merged, run_hook, run_message_hooks, stage, stage_all, HookMode, Signer};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{BranchType, Commit, Oid, Repository, Signature, Tree};
+
+/// Commit the repo's current index on top of HEAD with the given message using the configured
+/// `user.name` and `user.email` as the author and committer. When a signer is given the commit
+/// is signed e.g. with gpg honoring `user.signingkey`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_commit_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// let repo = git2::Repository::init(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "test").unwrap();
+/// repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+/// let oid = git::commit(&tmpdir, "Initial commit", None).unwrap();
+/// assert_eq!(repo.head().unwrap().target(), Some(oid));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn commit<T, U>(path: T, message: U, signer: Option<&Signer>) -> Result<Oid>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let mut builder = CommitBuilder::new(path).message(message);
+    builder.signer = signer.cloned();
+    builder.commit()
+}
+
+/// Builder for staging changes and writing a new commit on top of HEAD. The author and committer
+/// default to the configured `user.name` and `user.email` identity.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_commit_builder_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+/// let oid = git::CommitBuilder::new(&tmpdir)
+///     .stage(&["README.md"])
+///     .author("foo", "foo@example.com")
+///     .message("Update the readme")
+///     .trailer("Signed-off-by", "foo <foo@example.com>")
+///     .commit()
+///     .unwrap();
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+#[derive(Clone, Debug)]
+pub struct CommitBuilder {
+    path: PathBuf,                       // Repo location on disk
+    paths: Vec<String>,                  // Pathspecs to stage before committing
+    all: bool,                           // Stage all changes before committing
+    author: Option<(String, String)>,    // Author name and email
+    committer: Option<(String, String)>, // Committer name and email
+    message: String,                     // Commit message
+    trailers: Vec<(String, String)>,     // Trailers to append to the message
+    signer: Option<Signer>,              // Optional signer for the commit
+    pushed: bool,                        // Allow amending commits already pushed
+    hooks: HookMode,                     // Hooks to run while committing
+}
+
+impl CommitBuilder {
+    /// Create a new commit builder for the repo at the given path.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::CommitBuilder::new("foo").message_val(), "");
+    /// ```
+    pub fn new<T: AsRef<Path>>(path: T) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            paths: vec![],
+            all: false,
+            author: None,
+            committer: None,
+            message: String::new(),
+            trailers: vec![],
+            signer: None,
+            pushed: false,
+            hooks: HookMode::Skip,
+        }
+    }
+
+    /// Stage the files matching the given pathspecs before committing.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let builder = git::CommitBuilder::new("foo").stage(&["README.md"]);
+    /// ```
+    pub fn stage<T: AsRef<str>>(mut self, pathspecs: &[T]) -> Self {
+        self.paths.extend(pathspecs.iter().map(|x| x.as_ref().to_string()));
+        self
+    }
+
+    /// Stage all changes including new and deleted files before committing.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let builder = git::CommitBuilder::new("foo").stage_all(true);
+    /// ```
+    pub fn stage_all(mut self, yes: bool) -> Self {
+        self.all = yes;
+        self
+    }
+
+    /// Set the author of the commit.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let builder = git::CommitBuilder::new("foo").author("foo", "foo@example.com");
+    /// ```
+    pub fn author<T: AsRef<str>, U: AsRef<str>>(mut self, name: T, email: U) -> Self {
+        self.author = Some((name.as_ref().to_string(), email.as_ref().to_string()));
+        self
+    }
+
+    /// Set the committer of the commit. Defaults to the configured identity else the author.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let builder = git::CommitBuilder::new("foo").committer("foo", "foo@example.com");
+    /// ```
+    pub fn committer<T: AsRef<str>, U: AsRef<str>>(mut self, name: T, email: U) -> Self {
+        self.committer = Some((name.as_ref().to_string(), email.as_ref().to_string()));
+        self
+    }
+
+    /// Set the commit message.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::CommitBuilder::new("foo").message("bar").message_val(), "bar");
+    /// ```
+    pub fn message<T: AsRef<str>>(mut self, message: T) -> Self {
+        self.message = message.as_ref().to_string();
+        self
+    }
+
+    /// Returns the commit message without trailers.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::CommitBuilder::new("foo").message("bar").message_val(), "bar");
+    /// ```
+    pub fn message_val(&self) -> &str {
+        &self.message
+    }
+
+    /// Append a trailer e.g. `Signed-off-by: foo <foo@example.com>` to the commit message.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let builder = git::CommitBuilder::new("foo").trailer("Signed-off-by", "foo <foo@example.com>");
+    /// assert_eq!(builder.trailers_val(), &[("Signed-off-by".to_string(), "foo <foo@example.com>".to_string())]);
+    /// ```
+    pub fn trailer<T: AsRef<str>, U: AsRef<str>>(mut self, key: T, value: U) -> Self {
+        self.trailers.push((key.as_ref().to_string(), value.as_ref().to_string()));
+        self
+    }
+
+    /// Returns the trailers to append to the commit message.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::CommitBuilder::new("foo").trailer("foo", "bar").trailers_val().len(), 1);
+    /// ```
+    pub fn trailers_val(&self) -> &[(String, String)] {
+        &self.trailers
+    }
+
+    /// Sign the commit with the given signer.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let builder = git::CommitBuilder::new("foo").signer(git::Signer::gpg());
+    /// ```
+    pub fn signer(mut self, signer: Signer) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Allow amending a commit that has already been pushed to a remote.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::CommitBuilder::new("foo").allow_pushed(true).allow_pushed_val(), true);
+    /// ```
+    pub fn allow_pushed(mut self, yes: bool) -> Self {
+        self.pushed = yes;
+        self
+    }
+
+    /// Returns true if amending a commit that has already been pushed is allowed.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::CommitBuilder::new("foo").allow_pushed_val(), false);
+    /// ```
+    pub fn allow_pushed_val(&self) -> bool {
+        self.pushed
+    }
+
+    /// Run the repo's `pre-commit`, `prepare-commit-msg`, `commit-msg` and `post-commit` hooks
+    /// while committing according to the given mode. A failing `pre-commit` or `commit-msg` hook
+    /// aborts the commit while the message hooks may change the message.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let builder = git::CommitBuilder::new("foo").hooks(git::HookMode::Run);
+    /// assert_eq!(builder.hooks_val(), git::HookMode::Run);
+    /// ```
+    pub fn hooks(mut self, mode: HookMode) -> Self {
+        self.hooks = mode;
+        self
+    }
+
+    /// Returns the mode hooks will be run in while committing.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::CommitBuilder::new("foo").hooks_val(), git::HookMode::Skip);
+    /// ```
+    pub fn hooks_val(&self) -> HookMode {
+        self.hooks
+    }
+
+    /// Stage the requested changes then write the commit on top of HEAD, or as the initial
+    /// commit of an unborn branch, returning the new commit's id.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_commit_builder_commit_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+    /// let oid = git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add foo").commit().unwrap();
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn commit(self) -> Result<Oid> {
+        if self.message.trim().is_empty() {
+            return Err(Error::NoMessageWasFound);
+        }
+        self.stage_changes()?;
+        let repo = Repository::open(&self.path)?;
+        self.pre_commit(&repo)?;
+        let (author, committer) = self.identities(&repo, None)?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let parent = match repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(_) => None,
+        };
+        let parents: Vec<&Commit> = parent.iter().collect();
+        let message = with_trailers(&self.message, &self.trailers);
+        let message = run_message_hooks(&repo, self.hooks, &message, &["message"])?;
+        let oid = write_commit(&repo, &author, &committer, &message, &tree, &parents, self.signer.as_ref(), false)?;
+        self.post_commit(&repo);
+        Ok(oid)
+    }
+
+    /// Replace HEAD with a new commit including any requested and already staged changes. The
+    /// message and author are kept from HEAD unless set while trailers are appended. Amending a
+    /// commit that is reachable from a remote-tracking branch is refused unless `allow_pushed`
+    /// is set as it rewrites published history.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_commit_builder_amend_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let builder = git::CommitBuilder::new(&tmpdir).author("foo", "foo@example.com").message("Bump version");
+    /// assert!(builder.clone().amend().is_err());
+    /// assert!(builder.allow_pushed(true).amend().is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn amend(self) -> Result<Oid> {
+        let repo = Repository::open(&self.path)?;
+        let head = repo.head()?.peel_to_commit()?;
+        if !self.pushed && is_pushed(&repo, head.id())? {
+            return Err(Error::already_pushed(head.id().to_string()));
+        }
+        self.stage_changes()?;
+        self.pre_commit(&repo)?;
+        let (author, committer) = self.identities(&repo, Some(&head))?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let parents: Vec<Commit> = head.parents().collect();
+        let parents: Vec<&Commit> = parents.iter().collect();
+        let (message, source) = match self.message.trim().is_empty() {
+            true => (head.message().ok_or(Error::NoMessageWasFound)?.to_string(), vec!["commit", "HEAD"]),
+            false => (self.message.clone(), vec!["message"]),
+        };
+        let message = with_trailers(&message, &self.trailers);
+        let message = run_message_hooks(&repo, self.hooks, &message, &source)?;
+        let oid = write_commit(&repo, &author, &committer, &message, &tree, &parents, self.signer.as_ref(), true)?;
+        self.post_commit(&repo);
+        Ok(oid)
+    }
+
+    // Run the pre-commit hook when requested then reload the index as the hook may change it
+    fn pre_commit(&self, repo: &Repository) -> Result<()> {
+        if self.hooks == HookMode::Run && run_hook(repo, "pre-commit", &[] as &[&str])? {
+            repo.index()?.read(true)?;
+        }
+        Ok(())
+    }
+
+    // Run the post-commit hook when requested ignoring its outcome the same as git does
+    fn post_commit(&self, repo: &Repository) {
+        if self.hooks != HookMode::Skip {
+            run_hook(repo, "post-commit", &[] as &[&str]).ok();
+        }
+    }
+
+    // Stage the requested changes in the repo's index
+    fn stage_changes(&self) -> Result<()> {
+        if self.all {
+            stage_all(&self.path)?;
+        } else if !self.paths.is_empty() {
+            stage(&self.path, &self.paths)?;
+        }
+        Ok(())
+    }
+
+    // Resolve the author and committer falling back on the given commit's author then the
+    // configured identity
+    fn identities(&self, repo: &Repository, amend: Option<&Commit>) -> Result<(Signature<'static>, Signature<'static>)> {
+        let config = repo.signature().ok();
+        let author = match (&self.author, amend) {
+            (Some((name, email)), _) => Signature::now(name, email)?,
+            (None, Some(commit)) => commit.author().to_owned(),
+            (None, None) => config.clone().ok_or_else(|| git2::Error::from_str("no author was set and no identity is configured"))?,
+        };
+        let committer = match &self.committer {
+            Some((name, email)) => Signature::now(name, email)?,
+            None => config.unwrap_or_else(|| author.clone()),
+        };
+        Ok((author, committer))
+    }
+}
+
+// Returns true if the given commit is reachable from any remote-tracking branch
+fn is_pushed(repo: &Repository, oid: Oid) -> Result<bool> {
+    for branch in repo.branches(Some(BranchType::Remote))? {
+        let (branch, _) = branch?;
+        if branch.get().symbolic_target().is_some() {
+            continue;
+        }
+        if is_merged(repo, oid, branch.get().peel_to_commit()?.id())? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Append the given trailers to the message joining an existing trailer block if there is one
+fn with_trailers(message: &str, trailers: &[(String, String)]) -> String {
+    let mut message = message.trim_end().to_string();
+    if trailers.is_empty() {
+        return message;
+    }
+    let existing = git2::message_trailers_strs(&message).map(|x| x.len() > 0).unwrap_or(false);
+    message += if existing { "\n" } else { "\n\n" };
+    message += &trailers.iter().map(|(key, value)| format!("{}: {}", key, value)).collect::<Vec<_>>().join("\n");
+    message + "\n"
+}
+
+// Write the commit optionally signing it and move HEAD, or the branch it points to, to it
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn write_commit(repo: &Repository, author: &Signature, committer: &Signature, message: &str, tree: &Tree, parents: &[&Commit], signer: Option<&Signer>, amend: bool) -> Result<Oid> {
+    let oid = match signer {
+        Some(signer) => {
+            // Signed commits are created from the raw commit content
+            let buf = repo.commit_create_buffer(author, committer, message, tree, parents)?;
+            let content = buf.as_str().ok_or_else(|| Error::signing_failed("commit content isn't valid utf-8"))?;
+            let signature = signer.sign(repo, content)?;
+            repo.commit_signed(content, &signature, None)?
+        },
+        None => repo.commit(None, author, committer, message, tree, parents)?,
+    };
+
+    // Move HEAD recording the same reflog entry git would
+    let kind = match (amend, parents.is_empty()) {
+        (true, _) => " (amend)",
+        (false, true) => " (initial)",
+        (false, false) => "",
+    };
+    let reflog = format!("commit{}: {}", kind, message.lines().next().unwrap_or_default());
+    match repo.find_reference("HEAD")?.symbolic_target() {
+        Some(target) => repo.reference(target, oid, true, &reflog)?,
+        None => repo.reference("HEAD", oid, true, &reflog)?,
+    };
+    Ok(oid)
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Initialize a new repo with a test identity
+    fn init(path: &Path) -> git2::Repository {
+        let repo = git2::Repository::init(path).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        repo
+    }
+
+    #[test]
+    fn test_commit() {
+        let tmpdir = setup("git_commit");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let repo = init(&tmpdir);
+
+        // Initial commit
+        assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("README.md")).unwrap();
+        index.write().unwrap();
+        let oid1 = git::commit(&tmpdir, "Initial commit", None).unwrap();
+        let commit = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(commit.id(), oid1);
+        assert_eq!(commit.parent_count(), 0);
+        assert_eq!(commit.author().name(), Some("test"));
+        assert_eq!(commit.tree().unwrap().get_name("README.md").is_some(), true);
+
+        // Signed with a callback
+        let signer = git::Signer::callback(|content| {
+            assert_eq!(content.contains("Signed commit"), true);
+            Ok("-----BEGIN PGP SIGNATURE-----\nfoo\n-----END PGP SIGNATURE-----".to_string())
+        });
+        let oid2 = git::commit(&tmpdir, "Signed commit", Some(&signer)).unwrap();
+        let commit = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(commit.id(), oid2);
+        assert_eq!(commit.parent_id(0).unwrap(), oid1);
+        assert_eq!(repo.head().unwrap().name(), Some("refs/heads/master"));
+        let (sig, _) = repo.extract_signature(&oid2, None).unwrap();
+        assert_eq!(sig.as_str().unwrap().contains("foo"), true);
+        assert_eq!(repo.reflog("HEAD").unwrap().get(0).unwrap().message(), Some("commit: Signed commit"));
+
+        // Failed signing doesn't move HEAD
+        let signer = git::Signer::callback(|_| Err(git::Error::signing_failed("foo")));
+        assert!(git::commit(&tmpdir, "Failed commit", Some(&signer)).is_err());
+        assert_eq!(repo.head().unwrap().target(), Some(oid2));
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_commit_builder() {
+        let tmpdir = setup("git_commit_builder");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let head = repo.head().unwrap().target().unwrap();
+
+        // No identity configured and no author
+        assert!(git::CommitBuilder::new(&tmpdir).message("foo").commit().is_err());
+        assert_eq!(git::CommitBuilder::new(&tmpdir).author("foo", "foo@example.com").commit().unwrap_err().to_string(), git::Error::NoMessageWasFound.to_string());
+        assert_eq!(repo.head().unwrap().target(), Some(head));
+
+        // Stage only the given paths with the committer defaulting to the author
+        assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+        assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+        let oid = git::CommitBuilder::new(&tmpdir).stage(&["README.md"]).author("foo", "foo@example.com").message("Update readme\n").commit().unwrap();
+        let commit = repo.find_commit(oid).unwrap();
+        assert_eq!(commit.parent_id(0).unwrap(), head);
+        assert_eq!(commit.author().name(), Some("foo"));
+        assert_eq!(commit.committer().name(), Some("foo"));
+        assert_eq!(commit.message(), Some("Update readme"));
+        assert_eq!(commit.tree().unwrap().get_name("foo").is_none(), true);
+        assert_eq!(repo.head().unwrap().target(), Some(oid));
+
+        // Stage all with trailers and a separate committer
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        let oid = git::CommitBuilder::new(&tmpdir)
+            .stage_all(true)
+            .author("foo", "foo@example.com")
+            .message("Add foo\n\nWith a body")
+            .trailer("Signed-off-by", "foo <foo@example.com>")
+            .trailer("Reviewed-by", "bar <bar@example.com>")
+            .commit()
+            .unwrap();
+        let commit = repo.find_commit(oid).unwrap();
+        assert_eq!(commit.author().name(), Some("foo"));
+        assert_eq!(commit.committer().name(), Some("test"));
+        assert_eq!(commit.message(), Some("Add foo\n\nWith a body\n\nSigned-off-by: foo <foo@example.com>\nReviewed-by: bar <bar@example.com>\n"));
+        assert_eq!(commit.tree().unwrap().get_name("foo").is_some(), true);
+        let trailers = git2::message_trailers_strs(commit.message().unwrap()).unwrap();
+        assert_eq!(trailers.len(), 2);
+
+        // Trailers join an existing trailer block
+        let oid = git::CommitBuilder::new(&tmpdir).message("Empty\n\nSigned-off-by: foo <foo@example.com>").trailer("Reviewed-by", "bar").commit().unwrap();
+        let commit = repo.find_commit(oid).unwrap();
+        assert_eq!(commit.message(), Some("Empty\n\nSigned-off-by: foo <foo@example.com>\nReviewed-by: bar\n"));
+        assert_eq!(commit.author().name(), Some("test"));
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_commit_builder_amend() {
+        let tmpdir = setup("git_commit_builder_amend");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(tmpdir.mash("src").to_string().unwrap()).clone().is_ok());
+        let repo = git2::Repository::open(&local).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        let pushed = repo.head().unwrap().peel_to_commit().unwrap();
+
+        // Pushed HEAD is refused by default
+        let err = git::CommitBuilder::new(&local).message("foo").amend().unwrap_err();
+        assert_eq!(err.to_string(), git::Error::already_pushed(pushed.id().to_string()).to_string());
+        assert_eq!(repo.head().unwrap().target(), Some(pushed.id()));
+
+        // Local commit amended with a new message and staged changes
+        assert!(sys::write(local.mash("VERSION"), "1.0.0").is_ok());
+        let oid = git::CommitBuilder::new(&local).stage_all(true).author("foo", "foo@example.com").message("Bump versoin").commit().unwrap();
+        assert!(sys::write(local.mash("VERSION"), "1.0.1").is_ok());
+        let amended = git::CommitBuilder::new(&local).stage(&["VERSION"]).message("Bump version").amend().unwrap();
+        let commit = repo.find_commit(amended).unwrap();
+        assert_ne!(amended, oid);
+        assert_eq!(commit.message(), Some("Bump version"));
+        assert_eq!(commit.author().name(), Some("foo"));
+        assert_eq!(commit.committer().name(), Some("test"));
+        assert_eq!(commit.parent_id(0).unwrap(), pushed.id());
+        assert_eq!(commit.parent_count(), 1);
+        let blob = commit.tree().unwrap().get_name("VERSION").unwrap().to_object(&repo).unwrap().peel_to_blob().unwrap();
+        assert_eq!(blob.content(), b"1.0.1");
+        assert_eq!(repo.head().unwrap().target(), Some(amended));
+        assert_eq!(repo.head().unwrap().shorthand(), Some("master"));
+        assert_eq!(repo.reflog("HEAD").unwrap().get(0).unwrap().message(), Some("commit (amend): Bump version"));
+
+        // Message kept when not given
+        let amended = git::CommitBuilder::new(&local).trailer("Signed-off-by", "foo <foo@example.com>").amend().unwrap();
+        assert_eq!(repo.find_commit(amended).unwrap().message(), Some("Bump version\n\nSigned-off-by: foo <foo@example.com>\n"));
+
+        // Pushed HEAD when allowed
+        let mut head = repo.find_reference("refs/heads/master").unwrap();
+        head.set_target(pushed.id(), "reset").unwrap();
+        let amended = git::CommitBuilder::new(&local).message("foo").allow_pushed(true).amend().unwrap();
+        let commit = repo.find_commit(amended).unwrap();
+        assert_eq!(commit.message(), Some("foo"));
+        assert_eq!(commit.author().name(), pushed.author().name());
+        assert_eq!(commit.parent_id(0).unwrap(), pushed.parent_id(0).unwrap());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_commit_builder_hooks() {
+        let tmpdir = setup("git_commit_builder_hooks");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let repo = init(&tmpdir);
+        let log = tmpdir.mash(".git/hooks.log");
+        for name in &["pre-commit", "prepare-commit-msg", "post-commit"] {
+            let script = format!("#!/bin/sh\necho \"{} $2 $3\" >> {}\n", name, log.display());
+            assert!(git::hook_install(&tmpdir, name, script).is_ok());
+        }
+        assert!(git::hook_install(&tmpdir, "commit-msg", "#!/bin/sh\ngrep -q '^Fix' \"$1\" || { echo bad message >&2; exit 1; }\necho Reviewed-by: foo >> \"$1\"\n").is_ok());
+        let builder = |message: &str| git::CommitBuilder::new(&tmpdir).stage_all(true).message(message);
+
+        // Hooks are skipped by default
+        assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+        let oid = builder("Add foo").commit().unwrap();
+        assert_eq!(repo.find_commit(oid).unwrap().message(), Some("Add foo"));
+        assert_eq!(log.exists(), false);
+
+        // Failing commit-msg hook aborts the commit
+        assert!(sys::write(tmpdir.mash("foo"), "bar").is_ok());
+        let err = builder("Update foo").hooks(git::HookMode::Run).commit().unwrap_err();
+        assert_eq!(err.to_string(), git::Error::hook_failed("commit-msg", "bad message").to_string());
+        assert_eq!(repo.head().unwrap().target(), Some(oid));
+        assert_eq!(sys::readlines(&log).unwrap(), vec!["pre-commit  ", "prepare-commit-msg message "]);
+
+        // Hooks run in order and may change the message
+        assert!(sys::remove(&log).is_ok());
+        let oid = builder("Fix foo").hooks(git::HookMode::Run).commit().unwrap();
+        assert_eq!(repo.find_commit(oid).unwrap().message(), Some("Fix foo\nReviewed-by: foo"));
+        assert_eq!(
+            sys::readlines(&log).unwrap(),
+            vec![
+                "pre-commit  ",
+                "prepare-commit-msg message ",
+                "post-commit  "
+            ]
+        );
+
+        // Verification hooks are bypassed while the others still run
+        assert!(sys::remove(&log).is_ok());
+        assert!(git::hook_install(&tmpdir, "pre-commit", "#!/bin/sh\nexit 1\n").is_ok());
+        assert!(builder("Update foo").hooks(git::HookMode::Run).commit().is_err());
+        let oid = builder("Update foo").hooks(git::HookMode::NoVerify).commit().unwrap();
+        assert_eq!(repo.find_commit(oid).unwrap().message(), Some("Update foo"));
+        assert_eq!(sys::readlines(&log).unwrap(), vec!["prepare-commit-msg message ", "post-commit  "]);
+
+        // Amend keeping the message passes the commit being amended
+        assert!(sys::remove(&log).is_ok());
+        let oid = git::CommitBuilder::new(&tmpdir).hooks(git::HookMode::NoVerify).amend().unwrap();
+        assert_eq!(repo.find_commit(oid).unwrap().message(), Some("Update foo"));
+        assert_eq!(sys::readlines(&log).unwrap(), vec!["prepare-commit-msg commit HEAD", "post-commit  "]);
+
+        // Lines starting with # are kept while whitespace is cleaned up the same as git commit -m
+        let message = "\nUpdate foo  \n\n\n#123 is fixed by this\n  # indented\n\n";
+        assert!(sys::write(tmpdir.mash("foo"), "baz").is_ok());
+        let oid = builder(message).hooks(git::HookMode::NoVerify).commit().unwrap();
+        assert_eq!(repo.find_commit(oid).unwrap().message(), Some("Update foo\n\n#123 is fixed by this\n  # indented"));
+        let output = Command::new("git").args(["commit", "--allow-empty", "--no-verify", "-q", "-m", message]).current_dir(&tmpdir).output().unwrap();
+        assert_eq!(output.status.success(), true, "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(repo.head().unwrap().peel_to_commit().unwrap().message(), Some("Update foo\n\n#123 is fixed by this\n  # indented\n"));
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_commit_gpg() {
+        let tmpdir = setup("git_commit_gpg");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let repo = init(&tmpdir);
+
+        // Isolated gpg home with a test key wrapped as the configured gpg program
+        let home = tmpdir.mash("gnupg");
+        assert!(sys::mkdir(&home).is_ok());
+        assert!(sys::chmod(&home, 0o700).is_ok());
+        let status = Command::new("gpg")
+            .args([
+                "--homedir",
+                home.to_str().unwrap(),
+                "--batch",
+                "--passphrase",
+                "",
+                "--quick-gen-key",
+                "test <test@example.com>",
+                "ed25519",
+                "sign",
+                "never",
+            ])
+            .output()
+            .unwrap()
+            .status;
+        assert_eq!(status.success(), true);
+        let program = tmpdir.mash("gpg.sh");
+        assert!(sys::write(&program, format!("#!/bin/sh\nexec gpg --homedir {} \"$@\"\n", home.display())).is_ok());
+        assert!(sys::chmod(&program, 0o755).is_ok());
+        repo.config().unwrap().set_str("gpg.program", program.to_str().unwrap()).unwrap();
+
+        // Signed with the committer's identity
+        let oid = git::commit(&tmpdir, "Signed commit", Some(&git::Signer::gpg())).unwrap();
+        let (sig, _) = repo.extract_signature(&oid, None).unwrap();
+        assert_eq!(sig.as_str().unwrap().starts_with("-----BEGIN PGP SIGNATURE-----"), true);
+        let output = Command::new("git")
+            .args([
+                "-c",
+                &format!("gpg.program={}", program.display()),
+                "verify-commit",
+                &oid.to_string(),
+            ])
+            .current_dir(&tmpdir)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.success(), true);
+
+        // Signed with user.signingkey
+        repo.config().unwrap().set_str("user.signingkey", "test@example.com").unwrap();
+        assert!(git::commit(&tmpdir, "Signed commit", Some(&git::Signer::gpg())).is_ok());
+
+        // Unknown key
+        assert!(git::commit(&tmpdir, "Failed commit", Some(&git::Signer::gpg_key("missing@example.com"))).is_err());
+
+        let _ = Command::new("gpgconf").args(["--homedir", home.to_str().unwrap(), "--kill", "gpg-agent"]).output();
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,708 @@
+use super::config_values;
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{Config, ErrorCode, Repository};
+
+/// Config file level in order of increasing precedence as returned in `ConfigEntry`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ConfigLevel {
+    /// System wide config e.g. `/etc/gitconfig`
+    System,
+
+    /// XDG user config e.g. `~/.config/git/config`
+    Xdg,
+
+    /// User config e.g. `~/.gitconfig`
+    #[default]
+    Global,
+
+    /// Repo config e.g. `.git/config`
+    Local,
+}
+
+/// Config value and the level it was set at as returned in `ConfigSnapshot`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigEntry {
+    pub name: String,          // Key e.g. `user.email` with the section and variable lowercased
+    pub value: Option<String>, // Value or None for a bare key e.g. `[core] bare` which means true
+    pub level: ConfigLevel,    // Level of the file the value was set in
+}
+
+/// Config values of all levels resolved the same as git does with higher levels overriding lower
+/// ones as returned by `config_snapshot` and `config_global_snapshot`
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_snapshot_struct_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let config = git::config_snapshot(&tmpdir).unwrap();
+/// assert_eq!(config.level("core.bare"), Some(git::ConfigLevel::Local));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigSnapshot {
+    entries: Vec<ConfigEntry>, // Entries ordered from the lowest to the highest level
+}
+
+impl ConfigSnapshot {
+    /// Returns all the entries ordered from the lowest to the highest level.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert!(git::ConfigSnapshot::default().entries().is_empty());
+    /// ```
+    pub fn entries(&self) -> &[ConfigEntry] {
+        &self.entries
+    }
+
+    /// Returns the value of the given key from the highest level it's set at, the same as `git
+    /// config --get <key>`. Bare keys return an empty string.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_snapshot_get_str_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let config = git::config_snapshot(&tmpdir).unwrap();
+    /// assert_eq!(config.get_str("branch.master.remote"), Some("origin"));
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn get_str<T: AsRef<str>>(&self, key: T) -> Option<&str> {
+        self.find(key.as_ref()).last().map(|x| x.value.as_deref().unwrap_or_default())
+    }
+
+    /// Returns the boolean value of the given key from the highest level it's set at accepting
+    /// the same values as git. Errors if the value isn't a boolean.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_snapshot_get_bool_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let config = git::config_snapshot(&tmpdir).unwrap();
+    /// assert_eq!(config.get_bool("core.bare").unwrap(), Some(false));
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn get_bool<T: AsRef<str>>(&self, key: T) -> Result<Option<bool>> {
+        match self.find(key.as_ref()).last() {
+            Some(ConfigEntry { value: Some(value), .. }) => Ok(Some(Config::parse_bool(value.as_str())?)),
+            Some(_) => Ok(Some(true)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the integer value of the given key from the highest level it's set at accepting
+    /// the `k`, `m` and `g` suffixes the same as git. Errors if the value isn't an integer.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_snapshot_get_int_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let config = git::config_snapshot(&tmpdir).unwrap();
+    /// assert_eq!(config.get_int("core.repositoryformatversion").unwrap(), Some(0));
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn get_int<T: AsRef<str>>(&self, key: T) -> Result<Option<i64>> {
+        match self.get_str(key) {
+            Some(value) => Ok(Some(Config::parse_i64(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns all the values of the given multivar key across all levels from the lowest to the
+    /// highest level, the same as `git config --get-all <key>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_snapshot_get_all_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let config = git::config_snapshot(&tmpdir).unwrap();
+    /// assert_eq!(config.get_all("remote.origin.fetch"), vec!["+refs/heads/*:refs/remotes/origin/*"]);
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn get_all<T: AsRef<str>>(&self, key: T) -> Vec<&str> {
+        self.find(key.as_ref()).map(|x| x.value.as_deref().unwrap_or_default()).collect()
+    }
+
+    /// Returns the highest level the given key is set at.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_snapshot_level_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let config = git::config_snapshot(&tmpdir).unwrap();
+    /// assert_eq!(config.level("remote.origin.url"), Some(git::ConfigLevel::Local));
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn level<T: AsRef<str>>(&self, key: T) -> Option<ConfigLevel> {
+        self.find(key.as_ref()).last().map(|x| x.level)
+    }
+
+    // Returns the entries of the given key in order
+    fn find<'a>(&'a self, key: &str) -> impl Iterator<Item=&'a ConfigEntry> {
+        let key = normalize(key);
+        self.entries.iter().filter(move |x| x.name == key)
+    }
+}
+
+/// Get the string value of the given key from the repo's local config e.g. `user.email`. Returns
+/// None if the key isn't set locally. For keys with multiple values the last one is returned the
+/// same as `git config --local --get <key>`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_get_str_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::config_set(&tmpdir, "user.email", "foo@example.com").is_ok());
+/// assert_eq!(git::config_get_str(&tmpdir, "user.email").unwrap(), Some("foo@example.com".to_string()));
+/// assert_eq!(git::config_get_str(&tmpdir, "user.signingkey").unwrap(), None);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn config_get_str<T, U>(path: T, key: U) -> Result<Option<String>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let config = local_config(path.as_ref())?;
+    optional(config.get_string(key.as_ref()))
+}
+
+/// Get the boolean value of the given key from the repo's local config e.g. `pull.rebase`
+/// accepting the same values as git i.e. `true`, `yes`, `on`, `1` and their opposites. Returns
+/// None if the key isn't set locally and an error if its value isn't a boolean.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_get_bool_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::config_set(&tmpdir, "pull.rebase", true).is_ok());
+/// assert_eq!(git::config_get_bool(&tmpdir, "pull.rebase").unwrap(), Some(true));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn config_get_bool<T, U>(path: T, key: U) -> Result<Option<bool>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let config = local_config(path.as_ref())?;
+    optional(config.get_bool(key.as_ref()))
+}
+
+/// Get the integer value of the given key from the repo's local config e.g. `core.abbrev`
+/// accepting the `k`, `m` and `g` suffixes the same as git. Returns None if the key isn't set
+/// locally and an error if its value isn't an integer.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_get_int_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::config_set(&tmpdir, "http.postBuffer", "1k").is_ok());
+/// assert_eq!(git::config_get_int(&tmpdir, "http.postBuffer").unwrap(), Some(1024));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn config_get_int<T, U>(path: T, key: U) -> Result<Option<i64>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let config = local_config(path.as_ref())?;
+    optional(config.get_i64(key.as_ref()))
+}
+
+/// Get all the values of the given multivar key from the repo's local config in the order they
+/// were set e.g. `remote.origin.fetch`, the same as `git config --local --get-all <key>`. Returns
+/// an empty list if the key isn't set locally.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_get_all_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::config_add(&tmpdir, "foo.bar", "1").is_ok());
+/// assert!(git::config_add(&tmpdir, "foo.bar", "2").is_ok());
+/// assert_eq!(git::config_get_all(&tmpdir, "foo.bar").unwrap(), vec!["1".to_string(), "2".to_string()]);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn config_get_all<T, U>(path: T, key: U) -> Result<Vec<String>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let config = local_config(path.as_ref())?;
+    config_values(&config, key.as_ref())
+}
+
+/// Set the given key in the repo's local config to the given string, boolean or integer value,
+/// the same as `git config --local <key> <value>`. Errors if the key has multiple values, use
+/// `config_unset` first to replace them.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_set_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::config_set(&tmpdir, "user.name", "foo").is_ok());
+/// assert!(git::config_set(&tmpdir, "core.abbrev", 12).is_ok());
+/// assert_eq!(git::config_get_int(&tmpdir, "core.abbrev").unwrap(), Some(12));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn config_set<T, U, V>(path: T, key: U, value: V) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: ToString,
+{
+    let mut config = local_config(path.as_ref())?;
+    config.set_str(key.as_ref(), &value.to_string())?;
+    Ok(())
+}
+
+/// Add the given value to the given multivar key in the repo's local config keeping its existing
+/// values, the same as `git config --local --add <key> <value>`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_add_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::config_add(&tmpdir, "remote.origin.fetch", "+refs/tags/*:refs/tags/*").is_ok());
+/// assert_eq!(git::config_get_all(&tmpdir, "remote.origin.fetch").unwrap().len(), 2);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn config_add<T, U, V>(path: T, key: U, value: V) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: ToString,
+{
+    let mut config = local_config(path.as_ref())?;
+    config.set_multivar(key.as_ref(), "^$", &value.to_string())?;
+    Ok(())
+}
+
+/// Remove all the values of the given key from the repo's local config, the same as `git config
+/// --local --unset-all <key>`. Returns the number of values removed.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_unset_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::config_set(&tmpdir, "pull.rebase", true).is_ok());
+/// assert_eq!(git::config_unset(&tmpdir, "pull.rebase").unwrap(), 1);
+/// assert_eq!(git::config_get_bool(&tmpdir, "pull.rebase").unwrap(), None);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn config_unset<T, U>(path: T, key: U) -> Result<usize>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let mut config = local_config(path.as_ref())?;
+    let count = config_values(&config, key.as_ref())?.len();
+    if count > 0 {
+        config.remove_multivar(key.as_ref(), ".*")?;
+    }
+    Ok(count)
+}
+
+/// Read the config of all levels i.e. system, XDG, global and the given repo's local config into
+/// a snapshot that resolves values the same as git. The user's config is found using `$HOME`
+/// and `$XDG_CONFIG_HOME` the same as git.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_config_snapshot_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let config = git::config_snapshot(&tmpdir).unwrap();
+/// assert_eq!(config.get_str("remote.origin.url"), Some("https://github.com/phR0ze/alpine-base.git"));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn config_snapshot<T: AsRef<Path>>(path: T) -> Result<ConfigSnapshot> {
+    let repo = Repository::open(path.as_ref())?;
+    snapshot(&ConfigFiles::new()?, Some(&repo.path().mash("config")))
+}
+
+/// Read the system, XDG and global config into a snapshot that resolves values the same as git
+/// outside of a repo e.g. to check for a user identity on a fresh machine.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let config = git::config_global_snapshot().unwrap();
+/// assert!(config.entries().iter().all(|x| x.level != git::ConfigLevel::Local));
+/// ```
+pub fn config_global_snapshot() -> Result<ConfigSnapshot> {
+    snapshot(&ConfigFiles::new()?, None)
+}
+
+/// Set the given key in the user's global config to the given string, boolean or integer value,
+/// the same as `git config --global <key> <value>`. The value is written to `~/.gitconfig`, or to
+/// the XDG config when it exists and `~/.gitconfig` doesn't, the same as git.
+///
+/// ### Examples
+/// ```ignore
+/// use skellige::prelude::*;
+///
+/// assert!(git::config_global_set("user.email", "foo@example.com").is_ok());
+/// ```
+pub fn config_global_set<T, U>(key: T, value: U) -> Result<()>
+where
+    T: AsRef<str>,
+    U: ToString,
+{
+    let files = ConfigFiles::new()?;
+    set(&files.global_file(), key.as_ref(), &value.to_string())
+}
+
+/// Set the given key in the user's XDG config to the given string, boolean or integer value,
+/// creating `$XDG_CONFIG_HOME/git/config` or `~/.config/git/config` if needed. Values in
+/// `~/.gitconfig` take precedence over those in the XDG config.
+///
+/// ### Examples
+/// ```ignore
+/// use skellige::prelude::*;
+///
+/// assert!(git::config_xdg_set("user.name", "foo").is_ok());
+/// ```
+pub fn config_xdg_set<T, U>(key: T, value: U) -> Result<()>
+where
+    T: AsRef<str>,
+    U: ToString,
+{
+    let files = ConfigFiles::new()?;
+    set(&files.xdg, key.as_ref(), &value.to_string())
+}
+
+/// Remove all the values of the given key from the user's global and XDG config, the same as
+/// `git config --global --unset-all <key>`. Returns the number of values removed.
+///
+/// ### Examples
+/// ```ignore
+/// use skellige::prelude::*;
+///
+/// assert!(git::config_global_unset("user.email").is_ok());
+/// ```
+pub fn config_global_unset<T: AsRef<str>>(key: T) -> Result<usize> {
+    let files = ConfigFiles::new()?;
+    unset(&files, key.as_ref())
+}
+
+// Config files of the levels above the repo
+#[derive(Clone, Debug)]
+struct ConfigFiles {
+    system: PathBuf, // System config e.g. `/etc/gitconfig`
+    xdg: PathBuf,    // XDG config e.g. `~/.config/git/config`
+    global: PathBuf, // Global config e.g. `~/.gitconfig`
+}
+
+impl ConfigFiles {
+    // Locate the config files the same as git using `$HOME` and `$XDG_CONFIG_HOME`
+    fn new() -> Result<Self> {
+        let xdg_home = std::env::var("XDG_CONFIG_HOME").ok().filter(|x| !x.is_empty()).map(PathBuf::from);
+        Ok(Self::at(&user::home_dir()?, xdg_home))
+    }
+
+    // Locate the config files under the given home and XDG config home
+    fn at(home: &Path, xdg_home: Option<PathBuf>) -> Self {
+        Self {
+            system: Config::find_system().unwrap_or_else(|_| PathBuf::from("/etc/gitconfig")),
+            xdg: xdg_home.unwrap_or_else(|| home.mash(".config")).mash("git/config"),
+            global: home.mash(".gitconfig"),
+        }
+    }
+
+    // Returns the file `git config --global` writes to
+    fn global_file(&self) -> PathBuf {
+        match !self.global.exists() && self.xdg.exists() {
+            true => self.xdg.clone(),
+            false => self.global.clone(),
+        }
+    }
+}
+
+// Read the config of the given files and optional repo config into a snapshot
+fn snapshot(files: &ConfigFiles, local: Option<&Path>) -> Result<ConfigSnapshot> {
+    let mut config = Config::new()?;
+    let levels = [
+        (&files.system, git2::ConfigLevel::System),
+        (&files.xdg, git2::ConfigLevel::XDG),
+        (&files.global, git2::ConfigLevel::Global),
+    ];
+    for (file, level) in levels.iter().filter(|(x, _)| x.exists()) {
+        config.add_file(file, *level, false)?;
+    }
+    if let Some(file) = local {
+        config.add_file(file, git2::ConfigLevel::Local, false)?;
+    }
+
+    let mut entries = vec![];
+    let mut iter = config.entries(None)?;
+    while let Some(entry) = iter.next() {
+        let entry = entry?;
+        let level = match entry.level() {
+            git2::ConfigLevel::System | git2::ConfigLevel::ProgramData => ConfigLevel::System,
+            git2::ConfigLevel::XDG => ConfigLevel::Xdg,
+            git2::ConfigLevel::Global => ConfigLevel::Global,
+            _ => ConfigLevel::Local,
+        };
+        entries.push(ConfigEntry {
+            name: entry.name().unwrap_or_default().to_string(),
+            value: if entry.has_value() { entry.value().map(|x| x.to_string()) } else { None },
+            level,
+        });
+    }
+
+    // Keep the file order within each level
+    entries.sort_by_key(|x| x.level);
+    Ok(ConfigSnapshot { entries })
+}
+
+// Set the given key in the given config file creating it if needed
+fn set(file: &Path, key: &str, value: &str) -> Result<()> {
+    if let Some(dir) = file.parent() {
+        sys::mkdir(dir)?;
+    }
+    Config::open(file)?.set_str(key, value)?;
+    Ok(())
+}
+
+// Remove all the values of the given key from the user's global and XDG config
+fn unset(files: &ConfigFiles, key: &str) -> Result<usize> {
+    let mut count = 0;
+    for file in [&files.global, &files.xdg].iter().filter(|x| x.exists()) {
+        let mut config = Config::open(file)?;
+        let values = config_values(&config, key)?.len();
+        if values > 0 {
+            config.remove_multivar(key, ".*")?;
+            count += values;
+        }
+    }
+    Ok(count)
+}
+
+// Returns the given key with its section and variable lowercased the same as git as only the
+// subsection is case sensitive e.g. `remote.Origin.URL` becomes `remote.Origin.url`
+fn normalize(key: &str) -> String {
+    match (key.find('.'), key.rfind('.')) {
+        (Some(first), Some(last)) => format!("{}{}{}", key[..first].to_lowercase(), &key[first..last], key[last..].to_lowercase()),
+        _ => key.to_lowercase(),
+    }
+}
+
+// Returns the repo's local config
+fn local_config(path: &Path) -> Result<Config> {
+    let repo = Repository::open(path)?;
+    Ok(repo.config()?.open_level(git2::ConfigLevel::Local)?)
+}
+
+// Returns None for a missing key rather than an error
+fn optional<T>(result: std::result::Result<T, git2::Error>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if err.code() == ErrorCode::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_config() {
+        let tmpdir = setup("git_config");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let git_config = |args: &[&str]| {
+            let output = Command::new("git").arg("config").args(args).current_dir(&tmpdir).output().unwrap();
+            String::from_utf8(output.stdout).unwrap().trim_end().to_string()
+        };
+
+        // Strings
+        assert_eq!(git::config_get_str(&tmpdir, "user.email").unwrap(), None);
+        assert!(git::config_set(&tmpdir, "user.email", "foo@example.com").is_ok());
+        assert_eq!(git::config_get_str(&tmpdir, "user.email").unwrap(), Some("foo@example.com".to_string()));
+        assert_eq!(git_config(&["--local", "user.email"]), "foo@example.com");
+        assert!(git::config_set(&tmpdir, "user.email", "bar@example.com").is_ok());
+        assert_eq!(git_config(&["--local", "user.email"]), "bar@example.com");
+        assert_eq!(git::config_get_str(&tmpdir, "remote.origin.url").unwrap(), Some("https://github.com/phR0ze/alpine-base.git".to_string()));
+
+        // Booleans accept git's spellings
+        for (value, expected) in &[
+            ("true", true),
+            ("yes", true),
+            ("on", true),
+            ("1", true),
+            ("false", false),
+            ("no", false),
+            ("off", false),
+        ] {
+            assert!(git::config_set(&tmpdir, "pull.rebase", value).is_ok());
+            assert_eq!(git::config_get_bool(&tmpdir, "pull.rebase").unwrap(), Some(*expected));
+        }
+        assert!(git::config_set(&tmpdir, "pull.rebase", false).is_ok());
+        assert_eq!(git_config(&["--local", "--bool", "pull.rebase"]), "false");
+        assert!(git::config_set(&tmpdir, "pull.rebase", "merges").is_ok());
+        assert!(git::config_get_bool(&tmpdir, "pull.rebase").is_err());
+        assert_eq!(git::config_get_bool(&tmpdir, "pull.ff").unwrap(), None);
+
+        // Integers accept git's suffixes
+        assert!(git::config_set(&tmpdir, "core.abbrev", 12).is_ok());
+        assert_eq!(git::config_get_int(&tmpdir, "core.abbrev").unwrap(), Some(12));
+        assert!(git::config_set(&tmpdir, "http.postBuffer", "2m").is_ok());
+        assert_eq!(git::config_get_int(&tmpdir, "http.postBuffer").unwrap(), Some(2 * 1024 * 1024));
+        assert!(git::config_get_int(&tmpdir, "user.email").is_err());
+        assert_eq!(git::config_get_int(&tmpdir, "pack.threads").unwrap(), None);
+
+        // Multivars
+        assert!(git::config_add(&tmpdir, "foo.bar", "1").is_ok());
+        assert!(git::config_add(&tmpdir, "foo.bar", "2").is_ok());
+        assert_eq!(git::config_get_all(&tmpdir, "foo.bar").unwrap(), vec!["1".to_string(), "2".to_string()]);
+        assert_eq!(git::config_get_str(&tmpdir, "foo.bar").unwrap(), Some("2".to_string()));
+        assert_eq!(git_config(&["--local", "--get-all", "foo.bar"]), "1\n2");
+        assert!(git::config_set(&tmpdir, "foo.bar", "3").is_err());
+        assert_eq!(git::config_get_all(&tmpdir, "foo.baz").unwrap(), Vec::<String>::new());
+
+        // Unset
+        assert_eq!(git::config_unset(&tmpdir, "foo.bar").unwrap(), 2);
+        assert_eq!(git::config_unset(&tmpdir, "foo.bar").unwrap(), 0);
+        assert_eq!(git::config_unset(&tmpdir, "user.email").unwrap(), 1);
+        assert_eq!(git::config_get_str(&tmpdir, "user.email").unwrap(), None);
+        assert_eq!(git_config(&["--local", "--get-all", "foo.bar"]), "");
+
+        // Errors
+        assert!(git::config_set(&tmpdir, "invalid", "foo").is_err());
+        assert!(git::config_get_str(tmpdir.mash("../missing"), "user.email").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_config_snapshot() {
+        let tmpdir = setup("git_config_snapshot");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let repo = tmpdir.mash("repo");
+        let home = tmpdir.mash("home");
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &repo).is_ok());
+        let mut files = super::ConfigFiles::at(&home, None);
+        files.system = tmpdir.mash("gitconfig");
+        let git_config = |args: &[&str]| {
+            let output = Command::new("git")
+                .arg("config")
+                .args(args)
+                .env("HOME", &home)
+                .env_remove("XDG_CONFIG_HOME")
+                .env("GIT_CONFIG_SYSTEM", &files.system)
+                .current_dir(&repo)
+                .output()
+                .unwrap();
+            String::from_utf8(output.stdout).unwrap().trim_end().to_string()
+        };
+        let local = repo.mash(".git/config");
+
+        // Writes create the XDG config when needed and prefer ~/.gitconfig once it exists
+        assert_eq!(files.xdg, home.mash(".config/git/config"));
+        assert_eq!(files.global_file(), home.mash(".gitconfig"));
+        assert!(super::set(&files.xdg, "user.name", "xdg").is_ok());
+        assert_eq!(files.global_file(), files.xdg);
+        assert!(super::set(&files.global_file(), "user.email", "xdg@example.com").is_ok());
+        assert!(!files.global.exists());
+        assert_eq!(git_config(&["--global", "user.email"]), "xdg@example.com");
+        assert!(super::set(&files.global, "user.email", "global@example.com").is_ok());
+        assert_eq!(files.global_file(), files.global);
+
+        // Values resolve from the highest level
+        assert!(sys::write(&files.system, "[user]\n\tname = system\n[core]\n\tabbrev = 1k\n[foo]\n\tbar = 1\n\tbare\n").is_ok());
+        assert!(sys::write(home.mash(".gitconfig"), "[user]\n\temail = global@example.com\n[foo]\n\tbar = 2\n[Remote \"Origin\"]\n\tURL = foo\n").is_ok());
+        assert!(git::config_add(&repo, "foo.bar", "3").is_ok());
+        let config = super::snapshot(&files, Some(&local)).unwrap();
+        assert_eq!(config.get_str("user.name"), Some("xdg"));
+        assert_eq!(config.level("user.name"), Some(git::ConfigLevel::Xdg));
+        assert_eq!(config.get_str("user.email"), Some("global@example.com"));
+        assert_eq!(config.level("USER.Email"), Some(git::ConfigLevel::Global));
+        assert_eq!(config.get_str("remote.origin.url"), Some("https://github.com/phR0ze/alpine-base.git"));
+        assert_eq!(config.level("remote.origin.url"), Some(git::ConfigLevel::Local));
+        assert_eq!(config.get_str("remote.Origin.url"), Some("foo"));
+        assert_eq!(config.get_int("core.abbrev").unwrap(), Some(1024));
+        assert_eq!(config.level("core.abbrev"), Some(git::ConfigLevel::System));
+        assert_eq!(config.get_bool("foo.bare").unwrap(), Some(true));
+        assert_eq!(config.get_str("foo.bare"), Some(""));
+        assert!(config.get_bool("user.name").is_err());
+        assert_eq!(config.get_all("foo.bar"), vec!["1", "2", "3"]);
+        assert_eq!(config.get_str("missing.key"), None);
+        assert_eq!(config.get_bool("missing.key").unwrap(), None);
+        for key in &[
+            "user.name",
+            "user.email",
+            "core.abbrev",
+            "remote.origin.url",
+            "remote.Origin.url",
+        ] {
+            assert_eq!(config.get_str(key).unwrap(), git_config(&["--get", key]), "{}", key);
+        }
+        assert_eq!(config.get_all("foo.bar").join("\n"), git_config(&["--get-all", "foo.bar"]));
+
+        // Without a repo
+        let config = super::snapshot(&files, None).unwrap();
+        assert_eq!(config.get_all("foo.bar"), vec!["1", "2"]);
+        assert!(config.entries().iter().all(|x| x.level != git::ConfigLevel::Local));
+
+        // Unset from both global and XDG
+        assert!(super::set(&files.xdg, "user.email", "xdg@example.com").is_ok());
+        assert_eq!(super::unset(&files, "user.email").unwrap(), 2);
+        assert_eq!(super::unset(&files, "user.email").unwrap(), 0);
+        assert_eq!(super::snapshot(&files, None).unwrap().get_str("user.email"), None);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
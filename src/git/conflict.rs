@@ -0,0 +1,418 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{ErrorClass, ErrorCode, FileFavor, FileMode, Index, IndexEntry, Oid, Repository, Tree};
+
+/// Conflicted path in the index with the blob ids of each side of the conflict. A side is None
+/// when the path doesn't exist on that side e.g. `ours` for a path deleted by us.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    pub path: PathBuf,         // Path of the conflicted file relative to the worktree
+    pub ancestor: Option<Oid>, // Blob id of the common ancestor's version
+    pub ours: Option<Oid>,     // Blob id of our version i.e. HEAD's
+    pub theirs: Option<Oid>,   // Blob id of their version i.e. the one being merged in
+}
+
+/// Contents of each side of a conflict as given to the `resolve_conflicts_with` callback. A side
+/// is None when the path doesn't exist on that side.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConflictContents {
+    pub ancestor: Option<Vec<u8>>, // Common ancestor's content
+    pub ours: Option<Vec<u8>>,     // Our content i.e. HEAD's
+    pub theirs: Option<Vec<u8>>,   // Their content i.e. the one being merged in
+}
+
+/// Strategy for resolving a conflict
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolution {
+    /// Keep our version, removing the path if we deleted it
+    Ours,
+
+    /// Keep their version, removing the path if they deleted it
+    Theirs,
+
+    /// Keep the lines of both sides for content conflicts the same as git's union merge
+    Union,
+}
+
+/// Conflicts in the index left by a merge, rebase, cherry pick or revert
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Conflicts {
+    pub entries: Vec<Conflict>, // Conflicted paths in index order
+}
+
+impl Conflicts {
+    /// Returns true if there are no conflicts.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Conflicts::default().is_empty(), true);
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of conflicted paths.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Conflicts::default().len(), 0);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the conflicted paths.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let conflict = git::Conflict { path: PathBuf::from("foo"), ancestor: None, ours: None, theirs: None };
+    /// let conflicts = git::Conflicts { entries: vec![conflict] };
+    /// assert_eq!(conflicts.paths(), vec![PathBuf::from("foo")]);
+    /// ```
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.entries.iter().map(|x| x.path.clone()).collect()
+    }
+
+    // Read the conflicts from the given index
+    pub(crate) fn from_index(index: &Index) -> Result<Self> {
+        let mut entries = vec![];
+        for conflict in index.conflicts()? {
+            let conflict = conflict?;
+            let path = [&conflict.our, &conflict.their, &conflict.ancestor]
+                .iter()
+                .find_map(|x| x.as_ref())
+                .map(|x| PathBuf::from(String::from_utf8_lossy(&x.path).to_string()));
+            if let Some(path) = path {
+                let oid = |entry: &Option<IndexEntry>| entry.as_ref().map(|x| x.id);
+                entries.push(Conflict {
+                    path,
+                    ancestor: oid(&conflict.ancestor),
+                    ours: oid(&conflict.our),
+                    theirs: oid(&conflict.their),
+                });
+            }
+        }
+        Ok(Self { entries })
+    }
+}
+
+/// List the conflicts in the index of the given repo e.g. after a merge, rebase, cherry pick or
+/// revert stopped with `Error::MergeConflicts`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_conflicts_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::conflicts(&tmpdir).unwrap().is_empty(), true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn conflicts<T: AsRef<Path>>(path: T) -> Result<Conflicts> {
+    let repo = Repository::open(path.as_ref())?;
+    let index = repo.index()?;
+    Conflicts::from_index(&index)
+}
+
+/// Resolve the conflict for the given file, relative to the worktree, with the given strategy.
+/// The resolved content is written to the worktree and staged clearing the conflict.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_resolve_conflict_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::resolve_conflict(&tmpdir, "README.md", git::Resolution::Ours).is_err());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn resolve_conflict<T, U>(path: T, file: U, resolution: Resolution) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<Path>,
+{
+    let file = file.as_ref();
+    let found = apply(path.as_ref(), Some(file), |repo, conflict| resolved_content(repo, conflict, resolution))?;
+    if !found {
+        return Err(git2::Error::new(ErrorCode::NotFound, ErrorClass::Index, format!("no conflict for path: {}", file.display())).into());
+    }
+    Ok(())
+}
+
+/// Resolve all conflicts with the given strategy. The resolved content is written to the worktree
+/// and staged clearing the conflicts.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_resolve_conflicts_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::resolve_conflicts(&tmpdir, git::Resolution::Theirs).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn resolve_conflicts<T: AsRef<Path>>(path: T, resolution: Resolution) -> Result<()> {
+    apply(path.as_ref(), None, |repo, conflict| resolved_content(repo, conflict, resolution))?;
+    Ok(())
+}
+
+/// Resolve all conflicts with the content returned by the given callback, which is called with
+/// each conflict and the contents of its sides. Returning None removes the path. The resolved
+/// content is written to the worktree and staged clearing the conflicts.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_resolve_conflicts_with_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::resolve_conflicts_with(&tmpdir, |_, contents| contents.theirs.clone()).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn resolve_conflicts_with<T, F>(path: T, mut resolve: F) -> Result<()>
+where
+    T: AsRef<Path>,
+    F: FnMut(&Conflict, &ConflictContents) -> Option<Vec<u8>>,
+{
+    apply(path.as_ref(), None, |repo, conflict| {
+        let content = |oid: Option<Oid>| -> Result<Option<Vec<u8>>> {
+            match oid {
+                Some(oid) => Ok(Some(repo.find_blob(oid)?.content().to_vec())),
+                None => Ok(None),
+            }
+        };
+        let contents = ConflictContents {
+            ancestor: content(conflict.ancestor)?,
+            ours: content(conflict.ours)?,
+            theirs: content(conflict.theirs)?,
+        };
+        Ok(resolve(conflict, &contents))
+    })?;
+    Ok(())
+}
+
+// Resolve the conflicts, or only the given file's, with the content returned by the given
+// function writing it to the worktree and staging it. Returns true if any conflicts were found.
+fn apply<F>(path: &Path, file: Option<&Path>, mut resolve: F) -> Result<bool>
+where
+    F: FnMut(&Repository, &Conflict) -> Result<Option<Vec<u8>>>,
+{
+    let repo = Repository::open(path)?;
+    let workdir = repo.workdir().ok_or_else(|| git2::Error::from_str("repo has no worktree"))?.to_path_buf();
+    let mut index = repo.index()?;
+    let mut found = false;
+    for conflict in Conflicts::from_index(&index)?.entries.iter().filter(|x| file.is_none_or(|y| x.path == y)) {
+        found = true;
+        let target = workdir.mash(&conflict.path);
+        match resolve(&repo, conflict)? {
+            Some(data) => {
+                sys::write(&target, data)?;
+                index.add_path(&conflict.path)?;
+            },
+            None => {
+                if target.exists() {
+                    sys::remove(&target)?;
+                }
+                index.remove_path(&conflict.path)?;
+            },
+        }
+    }
+    index.write()?;
+    Ok(found)
+}
+
+// Resolved content for the given conflict using the given strategy
+fn resolved_content(repo: &Repository, conflict: &Conflict, resolution: Resolution) -> Result<Option<Vec<u8>>> {
+    let oid = match resolution {
+        Resolution::Ours => conflict.ours,
+        Resolution::Theirs => conflict.theirs,
+        Resolution::Union => {
+            // Merge single file trees of each side favoring both sides' lines
+            let tree = |oid: Option<Oid>| -> Result<Tree> {
+                let mut builder = repo.treebuilder(None)?;
+                if let Some(oid) = oid {
+                    builder.insert("file", oid, FileMode::Blob.into())?;
+                }
+                Ok(repo.find_tree(builder.write()?)?)
+            };
+            let mut opts = git2::MergeOptions::new();
+            opts.file_favor(FileFavor::Union);
+            let index = repo.merge_trees(&tree(conflict.ancestor)?, &tree(conflict.ours)?, &tree(conflict.theirs)?, Some(&opts))?;
+            if index.has_conflicts() {
+                return Err(Error::merge_conflicts(&[&conflict.path]));
+            }
+            index.get_path(Path::new("file"), 0).map(|x| x.id)
+        },
+    };
+    match oid {
+        Some(oid) => Ok(Some(repo.find_blob(oid)?.content().to_vec())),
+        None => Ok(None),
+    }
+}
+
+// Error listing the conflicted paths in the given index
+pub(crate) fn conflicts_error(index: &Index) -> Result<Error> {
+    Ok(Error::merge_conflicts(&Conflicts::from_index(index)?.paths()))
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Commit the given content to the given file
+    fn commit(path: &Path, file: &str, content: Option<&str>) {
+        match content {
+            Some(content) => assert!(sys::write(path.mash(file), content).is_ok()),
+            None => assert!(sys::remove(path.mash(file)).is_ok()),
+        }
+        assert!(git::CommitBuilder::new(path).stage_all(true).message(format!("Update {}", file)).commit().is_ok());
+    }
+
+    // Checkout the given branch
+    fn checkout(path: &Path, branch: &str) {
+        assert!(git::Repo::new(path).unwrap().checkout(branch, false).is_ok());
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let tmpdir = setup("git_conflicts");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        let ancestor = repo.revparse_single("HEAD:README.md").unwrap().id();
+
+        // Content conflict and a modify/delete conflict
+        assert!(git::branch_create(&tmpdir, "feature", None, true).is_ok());
+        commit(&tmpdir, "README.md", Some("theirs"));
+        commit(&tmpdir, "LICENSE", Some("theirs"));
+        checkout(&tmpdir, "master");
+        commit(&tmpdir, "README.md", Some("ours"));
+        commit(&tmpdir, "LICENSE", None);
+        let err = git::merge(&tmpdir, "feature", &git::MergeOptions::new()).unwrap_err();
+        match err {
+            git::Error::MergeConflicts(ref paths) => assert_eq!(paths, &vec![PathBuf::from("LICENSE"), PathBuf::from("README.md")]),
+            _ => panic!("expected merge conflicts"),
+        }
+        assert_eq!(err.to_string(), "merge conflicts in: LICENSE, README.md");
+
+        let conflicts = git::conflicts(&tmpdir).unwrap();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts.paths(), vec![PathBuf::from("LICENSE"), PathBuf::from("README.md")]);
+        let license = &conflicts.entries[0];
+        assert_eq!(license.ancestor.is_some(), true);
+        assert_eq!(license.ours, None);
+        assert_eq!(license.theirs, Some(repo.revparse_single("feature:LICENSE").unwrap().id()));
+        let readme = &conflicts.entries[1];
+        assert_eq!(readme.ancestor, Some(ancestor));
+        assert_eq!(readme.ours, Some(repo.revparse_single("HEAD:README.md").unwrap().id()));
+        assert_eq!(readme.theirs, Some(repo.revparse_single("feature:README.md").unwrap().id()));
+
+        // Errors
+        assert!(git::conflicts(tmpdir.mash("missing")).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    // Diverge README.md on a new branch and master then merge it in leaving conflicts
+    fn conflicted(path: &Path, branch: &str) {
+        assert!(git::branch_create(path, branch, None, true).is_ok());
+        commit(path, "README.md", Some(&format!("theirs {}", branch)));
+        checkout(path, "master");
+        commit(path, "README.md", Some(&format!("ours {}", branch)));
+        assert!(git::merge(path, branch, &git::MergeOptions::new()).is_err());
+    }
+
+    #[test]
+    fn test_resolve_conflicts() {
+        let tmpdir = setup("git_resolve_conflicts");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        assert!(git::merge_finish(&tmpdir, None).is_err());
+
+        // Resolve per path
+        commit(&tmpdir, "foo", Some("a\n"));
+        assert!(git::branch_create(&tmpdir, "feature", None, true).is_ok());
+        commit(&tmpdir, "foo", Some("a\ntheirs\n"));
+        commit(&tmpdir, "README.md", Some("theirs"));
+        commit(&tmpdir, "LICENSE", Some("theirs"));
+        checkout(&tmpdir, "master");
+        commit(&tmpdir, "foo", Some("a\nours\n"));
+        commit(&tmpdir, "README.md", Some("ours"));
+        commit(&tmpdir, "LICENSE", None);
+        let head = repo.head().unwrap().target().unwrap();
+        assert!(git::merge(&tmpdir, "feature", &git::MergeOptions::new()).is_err());
+        assert_eq!(git::conflicts(&tmpdir).unwrap().len(), 3);
+
+        assert!(git::resolve_conflict(&tmpdir, "README.md", git::Resolution::Ours).is_ok());
+        assert_eq!(sys::readstring(tmpdir.mash("README.md")).unwrap(), "ours");
+        assert!(git::resolve_conflict(&tmpdir, "README.md", git::Resolution::Ours).is_err());
+        assert!(git::resolve_conflict(&tmpdir, "foo", git::Resolution::Union).is_ok());
+        assert_eq!(sys::readstring(tmpdir.mash("foo")).unwrap(), "a\nours\ntheirs\n");
+        assert_eq!(git::conflicts(&tmpdir).unwrap().paths(), vec![PathBuf::from("LICENSE")]);
+        let err = git::merge_finish(&tmpdir, None).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::merge_conflicts(&["LICENSE"]).to_string());
+        assert!(git::resolve_conflict(&tmpdir, "LICENSE", git::Resolution::Ours).is_ok());
+        assert_eq!(tmpdir.mash("LICENSE").exists(), false);
+        assert_eq!(git::conflicts(&tmpdir).unwrap().is_empty(), true);
+
+        let merge = repo.find_commit(git::merge_finish(&tmpdir, None).unwrap()).unwrap();
+        assert_eq!(merge.parent_ids().collect::<Vec<_>>(), vec![head, git::resolve(&tmpdir, "feature").unwrap()]);
+        assert_eq!(merge.message(), Some("Merge branch 'feature'"));
+        assert_eq!(repo.head().unwrap().target(), Some(merge.id()));
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+        assert_eq!(repo.find_tree(merge.tree_id()).unwrap().get_name("LICENSE").is_none(), true);
+
+        // Resolve all with theirs and a message
+        conflicted(&tmpdir, "theirs");
+        assert!(git::resolve_conflicts(&tmpdir, git::Resolution::Theirs).is_ok());
+        assert_eq!(sys::readstring(tmpdir.mash("README.md")).unwrap(), "theirs theirs");
+        let merge = repo.find_commit(git::merge_finish(&tmpdir, Some("Take theirs")).unwrap()).unwrap();
+        assert_eq!(merge.message(), Some("Take theirs"));
+        assert_eq!(merge.parent_count(), 2);
+
+        // Resolve all with a callback
+        conflicted(&tmpdir, "custom");
+        let mut paths = vec![];
+        let result = git::resolve_conflicts_with(&tmpdir, |conflict, contents| {
+            paths.push(conflict.path.clone());
+            assert_eq!(contents.ancestor, Some(b"theirs theirs".to_vec()));
+            let mut content = contents.ours.clone().unwrap();
+            content.extend(b" and ");
+            content.extend(contents.theirs.clone().unwrap());
+            Some(content)
+        });
+        assert!(result.is_ok());
+        assert_eq!(paths, vec![PathBuf::from("README.md")]);
+        assert_eq!(sys::readstring(tmpdir.mash("README.md")).unwrap(), "ours custom and theirs custom");
+        assert!(git::merge_finish(&tmpdir, None).is_ok());
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,203 @@
+//! Parse commit messages following the [Conventional Commits](https://www.conventionalcommits.org)
+//! specification and classify their semantic versioning impact.
+use crate::error::*;
+use fungus::prelude::*;
+
+/// Semantic version bump implied by a conventional commit ordered by impact so that the bump
+/// for a set of commits is simply the maximum
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bump {
+    /// No release is required e.g. `docs` or `chore` commits
+    None,
+
+    /// Backwards compatible bug fix i.e. `fix` and `perf` commits
+    Patch,
+
+    /// Backwards compatible new functionality i.e. `feat` commits
+    Minor,
+
+    /// Breaking change i.e. commits marked with `!` or a `BREAKING CHANGE` footer
+    Major,
+}
+
+/// Conventional commit message parts
+#[derive(Clone, Debug, PartialEq)]
+pub struct Commit {
+    pub kind: String,                   // Commit type e.g. `feat` or `fix`
+    pub scope: Option<String>,          // Optional scope e.g. `parser` in `feat(parser): ...`
+    pub breaking: bool,                 // True if marked with `!` or a breaking change footer
+    pub description: String,            // Description following the type and scope
+    pub body: Option<String>,           // Optional free form body
+    pub footers: Vec<(String, String)>, // Footer tokens and values e.g. `Refs: #123`
+}
+
+impl Commit {
+    /// Returns the semantic version bump this commit implies.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::conventional::parse("feat: add foo").unwrap().bump(), git::conventional::Bump::Minor);
+    /// ```
+    pub fn bump(&self) -> Bump {
+        if self.breaking {
+            return Bump::Major;
+        }
+        match self.kind.to_lowercase().as_str() {
+            "feat" => Bump::Minor,
+            "fix" | "perf" => Bump::Patch,
+            _ => Bump::None,
+        }
+    }
+
+    /// Returns the value of the first footer with the given token ignoring case.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let commit = git::conventional::parse("fix: foo\n\nRefs: #123").unwrap();
+    /// assert_eq!(commit.footer("refs"), Some("#123"));
+    /// ```
+    pub fn footer<T: AsRef<str>>(&self, token: T) -> Option<&str> {
+        self.footers.iter().find(|(key, _)| key.eq_ignore_ascii_case(token.as_ref())).map(|(_, value)| value.as_str())
+    }
+}
+
+/// Parse the given commit message as a conventional commit of the form
+/// `type(scope)!: description` followed by an optional body and footers.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let commit = git::conventional::parse("feat(parser)!: support arrays\n\nBREAKING CHANGE: drops tuples").unwrap();
+/// assert_eq!(commit.kind, "feat");
+/// assert_eq!(commit.scope, Some("parser".to_string()));
+/// assert_eq!(commit.breaking, true);
+/// assert_eq!(commit.footer("BREAKING CHANGE"), Some("drops tuples"));
+/// ```
+pub fn parse<T: AsRef<str>>(message: T) -> Result<Commit> {
+    let message = message.as_ref().replace("\r\n", "\n");
+    let mut paragraphs: Vec<&str> = message.trim().split("\n\n").map(|x| x.trim_matches('\n')).filter(|x| !x.is_empty()).collect();
+    if paragraphs.is_empty() {
+        return Err(Error::NoMessageWasFound);
+    }
+
+    // Header
+    let header = paragraphs.remove(0);
+    let (summary, rest) = header.split_once('\n').unwrap_or((header, ""));
+    if !rest.is_empty() {
+        paragraphs.insert(0, rest);
+    }
+    let re = Regex::new(r"^([A-Za-z]+)(?:\(([^()]*)\))?(!)?: (.*\S)\s*$").unwrap();
+    let caps = re.captures(summary).ok_or_else(|| Error::not_conventional(summary))?;
+    let scope = caps.get(2).map(|x| x.as_str().trim().to_string()).filter(|x| !x.is_empty());
+
+    // Footers are the trailing paragraph when it starts with a footer
+    let mut footers: Vec<(String, String)> = vec![];
+    let footer_re = Regex::new(r"^(BREAKING[ -]CHANGE|[A-Za-z][A-Za-z0-9-]*)(?:: | #)(.*)$").unwrap();
+    if paragraphs.last().and_then(|x| x.lines().next()).map(|x| footer_re.is_match(x)).unwrap_or(false) {
+        for line in paragraphs.pop().unwrap().lines() {
+            match footer_re.captures(line) {
+                Some(caps) => footers.push((caps[1].to_string(), caps[2].trim().to_string())),
+                None => {
+                    // Continuation of the previous footer's value
+                    let (_, value) = footers.last_mut().unwrap();
+                    value.push('\n');
+                    value.push_str(line);
+                },
+            }
+        }
+    }
+
+    let body = if paragraphs.is_empty() { None } else { Some(paragraphs.join("\n\n")) };
+    let breaking = caps.get(3).is_some() || footers.iter().any(|(key, _)| key == "BREAKING CHANGE" || key == "BREAKING-CHANGE");
+    Ok(Commit {
+        kind: caps[1].to_string(),
+        scope,
+        breaking,
+        description: caps[4].to_string(),
+        body,
+        footers,
+    })
+}
+
+/// Returns the highest semantic version bump implied by the given commit messages ignoring
+/// messages that aren't conventional commits.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// assert_eq!(git::conventional::bump(&["fix: foo", "feat: bar", "Update readme"]), git::conventional::Bump::Minor);
+/// ```
+pub fn bump<T: AsRef<str>>(messages: &[T]) -> Bump {
+    messages.iter().filter_map(|x| parse(x).ok()).map(|x| x.bump()).max().unwrap_or(Bump::None)
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use git::conventional::{self, Bump};
+
+    #[test]
+    fn test_parse() {
+        // Header only
+        let commit = conventional::parse("fix: correct off by one\n").unwrap();
+        assert_eq!(commit.kind, "fix");
+        assert_eq!(commit.scope, None);
+        assert_eq!(commit.breaking, false);
+        assert_eq!(commit.description, "correct off by one");
+        assert_eq!(commit.body, None);
+        assert_eq!(commit.footers.is_empty(), true);
+
+        // Scope, breaking marker, body and footers
+        let msg = "feat(api)!: drop v1 endpoints\n\nThe v1 endpoints are gone.\n\nUse v2 instead.\n\nRefs #123\nReviewed-by: foo\n  and bar\nBREAKING CHANGE: v1 removed";
+        let commit = conventional::parse(msg).unwrap();
+        assert_eq!(commit.kind, "feat");
+        assert_eq!(commit.scope, Some("api".to_string()));
+        assert_eq!(commit.breaking, true);
+        assert_eq!(commit.description, "drop v1 endpoints");
+        assert_eq!(commit.body, Some("The v1 endpoints are gone.\n\nUse v2 instead.".to_string()));
+        assert_eq!(commit.footers.len(), 3);
+        assert_eq!(commit.footer("refs"), Some("123"));
+        assert_eq!(commit.footer("Reviewed-by"), Some("foo\n  and bar"));
+        assert_eq!(commit.footer("BREAKING CHANGE"), Some("v1 removed"));
+
+        // Breaking change footer alone and body directly after the header
+        let commit = conventional::parse("refactor: foo\nmore detail\n\nBREAKING-CHANGE: bar").unwrap();
+        assert_eq!(commit.breaking, true);
+        assert_eq!(commit.body, Some("more detail".to_string()));
+
+        // Body that isn't a footer
+        let commit = conventional::parse("docs: foo\n\nsee the readme: it explains").unwrap();
+        assert_eq!(commit.body, Some("see the readme: it explains".to_string()));
+        assert_eq!(commit.footers.is_empty(), true);
+
+        // Not conventional
+        assert_eq!(conventional::parse("Update the readme").unwrap_err().to_string(), git::Error::not_conventional("Update the readme").to_string());
+        assert!(conventional::parse("feat:missing space").is_err());
+        assert!(conventional::parse("feat(: foo").is_err());
+        assert!(conventional::parse("feat: ").is_err());
+        assert_eq!(conventional::parse("\n\n").unwrap_err().to_string(), git::Error::NoMessageWasFound.to_string());
+    }
+
+    #[test]
+    fn test_bump() {
+        assert_eq!(conventional::parse("docs: foo").unwrap().bump(), Bump::None);
+        assert_eq!(conventional::parse("fix: foo").unwrap().bump(), Bump::Patch);
+        assert_eq!(conventional::parse("perf(db): foo").unwrap().bump(), Bump::Patch);
+        assert_eq!(conventional::parse("Feat: foo").unwrap().bump(), Bump::Minor);
+        assert_eq!(conventional::parse("chore!: foo").unwrap().bump(), Bump::Major);
+
+        assert_eq!(conventional::bump::<&str>(&[]), Bump::None);
+        assert_eq!(conventional::bump(&["Update readme", "docs: foo"]), Bump::None);
+        assert_eq!(conventional::bump(&["fix: foo", "feat: bar"]), Bump::Minor);
+        assert_eq!(conventional::bump(&["fix: foo", "fix: bar\n\nBREAKING CHANGE: baz"]), Bump::Major);
+    }
+}
@@ -0,0 +1,286 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{DescribeFormatOptions, ErrorClass, Repository};
+
+/// Options for describing a revision with `describe` the same as `git describe`
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let opts = git::DescribeOptions::new().tags(true).pattern("v*").dirty("-dirty");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DescribeOptions {
+    rev: Option<String>,     // Revision to describe instead of the worktree's HEAD
+    tags: bool,              // Consider lightweight tags as well as annotated tags
+    pattern: Option<String>, // Only consider tags matching this glob
+    abbrev: Option<u32>,     // Abbreviated commit id length
+    long: bool,              // Always include the distance and commit id
+    always: bool,            // Fall back on the abbreviated commit id when no tag is found
+    dirty: Option<String>,   // Suffix to append when the worktree has changes
+}
+
+impl DescribeOptions {
+    /// Create new describe options for HEAD considering only annotated tags.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::DescribeOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Describe the given revision rather than the worktree's HEAD. The dirty suffix doesn't
+    /// apply when a revision is given.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::DescribeOptions::new().rev("HEAD~3");
+    /// ```
+    pub fn rev<T: AsRef<str>>(mut self, rev: T) -> Self {
+        self.rev = Some(rev.as_ref().to_string());
+        self
+    }
+
+    /// Consider lightweight tags as well as annotated tags.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::DescribeOptions::new().tags(true);
+    /// ```
+    pub fn tags(mut self, yes: bool) -> Self {
+        self.tags = yes;
+        self
+    }
+
+    /// Only consider tags matching the given glob e.g. `v*`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::DescribeOptions::new().pattern("v*");
+    /// ```
+    pub fn pattern<T: AsRef<str>>(mut self, pattern: T) -> Self {
+        self.pattern = Some(pattern.as_ref().to_string());
+        self
+    }
+
+    /// Use the given number of hex digits for the abbreviated commit id. Zero describes the
+    /// revision using only the tag name.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::DescribeOptions::new().abbrev(10);
+    /// ```
+    pub fn abbrev(mut self, size: u32) -> Self {
+        self.abbrev = Some(size);
+        self
+    }
+
+    /// Always include the distance and commit id even when the revision is tagged.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::DescribeOptions::new().long(true);
+    /// ```
+    pub fn long(mut self, yes: bool) -> Self {
+        self.long = yes;
+        self
+    }
+
+    /// Fall back on the abbreviated commit id when no tag describes the revision.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::DescribeOptions::new().always(true);
+    /// ```
+    pub fn always(mut self, yes: bool) -> Self {
+        self.always = yes;
+        self
+    }
+
+    /// Append the given suffix when the worktree has changes.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::DescribeOptions::new().dirty("-dirty");
+    /// ```
+    pub fn dirty<T: AsRef<str>>(mut self, suffix: T) -> Self {
+        self.dirty = Some(suffix.as_ref().to_string());
+        self
+    }
+}
+
+/// Describe a revision relative to the nearest reachable tag e.g. `v1.2.3-5-gabcdef` the same
+/// as `git describe`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_describe_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::describe(&tmpdir, &git::DescribeOptions::new().tags(true)).unwrap(), "3.11-3-gb61d09a");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn describe<T: AsRef<Path>>(path: T, opts: &DescribeOptions) -> Result<String> {
+    let repo = Repository::open(path.as_ref())?;
+    let mut describe_opts = git2::DescribeOptions::new();
+    if opts.tags {
+        describe_opts.describe_tags();
+    }
+    if let Some(pattern) = &opts.pattern {
+        describe_opts.pattern(pattern);
+    }
+    describe_opts.show_commit_oid_as_fallback(opts.always);
+
+    let mut format_opts = DescribeFormatOptions::new();
+    if let Some(size) = opts.abbrev {
+        format_opts.abbreviated_size(size);
+    }
+    format_opts.always_use_long_format(opts.long);
+
+    Ok(match &opts.rev {
+        Some(rev) => repo.revparse_single(rev)?.describe(&describe_opts)?.format(Some(&format_opts))?,
+        None => {
+            if let Some(suffix) = &opts.dirty {
+                format_opts.dirty_suffix(suffix);
+            }
+            repo.describe(&describe_opts)?.format(Some(&format_opts))?
+        },
+    })
+}
+
+/// Returns the name of the nearest tag reachable from HEAD, annotated or lightweight, or None
+/// if there isn't one.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_latest_tag_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::latest_tag(&tmpdir).unwrap(), Some("3.11".to_string()));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn latest_tag<T: AsRef<Path>>(path: T) -> Result<Option<String>> {
+    match describe(path, &DescribeOptions::new().rev("HEAD").tags(true).abbrev(0)) {
+        Ok(tag) => Ok(Some(tag)),
+        Err(Error::Git2(err)) if err.class() == ErrorClass::Describe => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Returns the description git itself gives with the given arguments
+    fn git_describe(path: &Path, args: &[&str]) -> String {
+        let output = Command::new("git").arg("describe").args(args).current_dir(path).output().unwrap();
+        assert_eq!(output.status.success(), true);
+        String::from_utf8(output.stdout).unwrap().trim_end().to_string()
+    }
+
+    #[test]
+    fn test_describe() {
+        let tmpdir = setup("git_describe");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+
+        // Only a lightweight tag
+        assert!(git::describe(&tmpdir, &git::DescribeOptions::new()).is_err());
+        let always = git::describe(&tmpdir, &git::DescribeOptions::new().always(true)).unwrap();
+        assert_eq!(always, git_describe(&tmpdir, &["--always"]));
+        assert_eq!(git::describe(&tmpdir, &git::DescribeOptions::new().tags(true)).unwrap(), git_describe(&tmpdir, &["--tags"]));
+        let long = git::describe(&tmpdir, &git::DescribeOptions::new().tags(true).long(true)).unwrap();
+        assert_eq!(long, git_describe(&tmpdir, &["--tags", "--long"]));
+
+        // Annotated tags with distance
+        git::tag_annotated(&tmpdir, "v1.2.3", "HEAD~5", "Release 1.2.3", Some(("foo", "foo@example.com"))).unwrap();
+        git::tag_annotated(&tmpdir, "other", "HEAD~2", "Other", Some(("foo", "foo@example.com"))).unwrap();
+        let desc = git::describe(&tmpdir, &git::DescribeOptions::new()).unwrap();
+        assert_eq!(desc, git_describe(&tmpdir, &[]));
+        assert_eq!(desc.starts_with("other-2-g"), true);
+        let desc = git::describe(&tmpdir, &git::DescribeOptions::new().pattern("v*")).unwrap();
+        assert_eq!(desc, git_describe(&tmpdir, &["--match", "v*"]));
+        assert_eq!(desc.starts_with("v1.2.3-5-g"), true);
+        let desc = git::describe(&tmpdir, &git::DescribeOptions::new().pattern("v*").abbrev(10)).unwrap();
+        assert_eq!(desc, git_describe(&tmpdir, &["--match", "v*", "--abbrev=10"]));
+        let desc = git::describe(&tmpdir, &git::DescribeOptions::new().rev("HEAD~1").pattern("v*")).unwrap();
+        assert_eq!(desc, git_describe(&tmpdir, &["--match", "v*", "HEAD~1"]));
+        assert_eq!(desc.starts_with("v1.2.3-4-g"), true);
+        let desc = git::describe(&tmpdir, &git::DescribeOptions::new().rev("HEAD~5").abbrev(0)).unwrap();
+        assert_eq!(desc, "v1.2.3");
+
+        // Dirty worktree
+        assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+        let desc = git::describe(&tmpdir, &git::DescribeOptions::new().tags(true).dirty("-dirty")).unwrap();
+        assert_eq!(desc, git_describe(&tmpdir, &["--tags", "--dirty"]));
+        assert_eq!(desc.ends_with("-dirty"), true);
+        let desc = git::describe(&tmpdir, &git::DescribeOptions::new().rev("HEAD").tags(true).dirty("-dirty")).unwrap();
+        assert_eq!(desc, git_describe(&tmpdir, &["--tags", "HEAD"]));
+
+        // Errors
+        assert!(git::describe(&tmpdir, &git::DescribeOptions::new().rev("missing")).is_err());
+        assert!(git::describe(&tmpdir, &git::DescribeOptions::new().pattern("missing*")).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_latest_tag() {
+        let tmpdir = setup("git_latest_tag");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let repo = git2::Repository::init(&tmpdir).unwrap();
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+
+        // No tags
+        let tree = repo.find_tree(repo.index().unwrap().write_tree().unwrap()).unwrap();
+        let oid = repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[]).unwrap();
+        assert_eq!(git::latest_tag(&tmpdir).unwrap(), None);
+
+        // Nearest tag wins
+        git::tag_lightweight(&tmpdir, "v0.1.0", "HEAD").unwrap();
+        let parent = repo.find_commit(oid).unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "Second commit", &tree, &[&parent]).unwrap();
+        assert_eq!(git::latest_tag(&tmpdir).unwrap(), Some("v0.1.0".to_string()));
+        git::tag_annotated(&tmpdir, "v0.2.0", "HEAD", "Release 0.2.0", Some(("test", "test@example.com"))).unwrap();
+        assert_eq!(git::latest_tag(&tmpdir).unwrap(), Some("v0.2.0".to_string()));
+
+        // Errors
+        assert!(git::latest_tag(tmpdir.mash("missing")).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
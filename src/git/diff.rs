@@ -0,0 +1,953 @@
+use super::FileState;
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{Delta, DiffDelta, DiffFindOptions, DiffFormat, Patch, Repository};
+
+/// Per file diff stats as returned in `DiffStats`
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileStats {
+    pub path: PathBuf,             // File path in the newer revision or old path if deleted
+    pub old_path: Option<PathBuf>, // Previous path if the file was renamed
+    pub insertions: usize,         // Number of lines added
+    pub deletions: usize,          // Number of lines removed
+    pub binary: bool,              // True if the file is binary and has no line stats
+}
+
+/// Diff stats between two revisions as returned by `diff_stats`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiffStats {
+    pub files_changed: usize,  // Number of files changed
+    pub insertions: usize,     // Total number of lines added
+    pub deletions: usize,      // Total number of lines removed
+    pub files: Vec<FileStats>, // Per file stats
+}
+
+/// Kind of a line in a diff hunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineKind {
+    /// Line that is the same in both revisions
+    Context,
+
+    /// Line that was added in the newer revision
+    Addition,
+
+    /// Line that was removed from the older revision
+    Deletion,
+}
+
+/// Line of a diff hunk as returned in `DiffHunk`
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffLine {
+    pub kind: LineKind,          // Whether the line is context, added or removed
+    pub old_lineno: Option<u32>, // Line number in the older revision if it exists there
+    pub new_lineno: Option<u32>, // Line number in the newer revision if it exists there
+    pub content: String,         // Line content without the trailing newline
+}
+
+/// Run of text within a `WordLine` as returned by `DiffHunk::words`
+#[derive(Clone, Debug, PartialEq)]
+pub struct WordSpan {
+    pub text: String,  // Text of the span
+    pub changed: bool, // True if the text differs from the paired line
+}
+
+/// Hunk line split into changed and unchanged spans as returned by `DiffHunk::words`
+#[derive(Clone, Debug, PartialEq)]
+pub struct WordLine {
+    pub kind: LineKind,          // Whether the line is context, added or removed
+    pub old_lineno: Option<u32>, // Line number in the older revision if it exists there
+    pub new_lineno: Option<u32>, // Line number in the newer revision if it exists there
+    pub spans: Vec<WordSpan>,    // Spans that together make up the line content
+}
+
+/// Hunk of a file's diff as returned in `FileDiff`
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffHunk {
+    pub header: String,       // Hunk header e.g. `@@ -1,3 +1,4 @@` without the trailing newline
+    pub old_start: u32,       // First line of the hunk in the older revision
+    pub old_lines: u32,       // Number of lines of the hunk in the older revision
+    pub new_start: u32,       // First line of the hunk in the newer revision
+    pub new_lines: u32,       // Number of lines of the hunk in the newer revision
+    pub lines: Vec<DiffLine>, // Context, added and removed lines of the hunk
+}
+
+/// Per file diff as returned in `Diff`
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileDiff {
+    pub path: PathBuf,             // File path in the newer revision or old path if deleted
+    pub old_path: Option<PathBuf>, // Previous path if the file was renamed or the source if copied
+    pub state: FileState,          // Whether the file was added, modified, deleted, renamed or copied
+    pub binary: bool,              // True if the file is binary and has no hunks
+    pub hunks: Vec<DiffHunk>,      // Changed regions of the file
+}
+
+/// Diff between two revisions as returned by `diff`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diff {
+    pub files: Vec<FileDiff>, // Per file diffs
+    pub patch: String,        // Unified patch text the same as `git diff` prints
+}
+
+/// Options for diffing with `diff`
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let opts = git::DiffOptions::new().context(1).paths(&["src"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct DiffOptions {
+    context: u32,       // Number of unchanged lines shown around each change
+    renames: bool,      // Detect renamed files
+    copies: bool,       // Detect copied files
+    similarity: u16,    // Similarity percentage for a file to be considered renamed or copied
+    paths: Vec<String>, // Pathspecs to limit the diff to
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self { context: 3, renames: true, copies: false, similarity: 50, paths: vec![] }
+    }
+}
+
+impl DiffOptions {
+    /// Create new diff options with 3 lines of context and rename detection the same as
+    /// `git diff` does by default.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::DiffOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of unchanged lines shown around each change, the same as `git diff -U<n>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::DiffOptions::new().context(1).context_val(), 1);
+    /// ```
+    pub fn context(mut self, lines: u32) -> Self {
+        self.context = lines;
+        self
+    }
+
+    /// Get the number of unchanged lines shown around each change.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::DiffOptions::new().context_val(), 3);
+    /// ```
+    pub fn context_val(&self) -> u32 {
+        self.context
+    }
+
+    /// Set to detect renamed files, the same as `git diff --find-renames`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::DiffOptions::new().renames(false).renames_val(), false);
+    /// ```
+    pub fn renames(mut self, yes: bool) -> Self {
+        self.renames = yes;
+        self
+    }
+
+    /// Get whether renamed files are detected.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::DiffOptions::new().renames_val(), true);
+    /// ```
+    pub fn renames_val(&self) -> bool {
+        self.renames
+    }
+
+    /// Set to detect files copied from other files modified in the same diff, the same as
+    /// `git diff --find-copies`. Unlike git the patch text of a copied file doesn't include the
+    /// `copy from` and `copy to` header lines.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::DiffOptions::new().copies(true).copies_val(), true);
+    /// ```
+    pub fn copies(mut self, yes: bool) -> Self {
+        self.copies = yes;
+        self
+    }
+
+    /// Get whether copied files are detected.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::DiffOptions::new().copies_val(), false);
+    /// ```
+    pub fn copies_val(&self) -> bool {
+        self.copies
+    }
+
+    /// Set the percentage of a file's content that must be unchanged for it to be considered
+    /// renamed or copied, the same as `git diff -M<n>% -C<n>%`. Defaults to 50 and is capped at
+    /// 100.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::DiffOptions::new().similarity(90).similarity_val(), 90);
+    /// ```
+    pub fn similarity(mut self, percent: u16) -> Self {
+        self.similarity = percent.min(100);
+        self
+    }
+
+    /// Get the similarity percentage for a file to be considered renamed or copied.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::DiffOptions::new().similarity_val(), 50);
+    /// ```
+    pub fn similarity_val(&self) -> u16 {
+        self.similarity
+    }
+
+    /// Set the pathspecs to limit the diff to e.g. `src` or `*.rs`, the same as `git diff
+    /// <from> <to> -- <pathspecs>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::DiffOptions::new().paths(&["src"]);
+    /// assert_eq!(opts.paths_val(), &["src".to_string()]);
+    /// ```
+    pub fn paths<T, U>(mut self, paths: T) -> Self
+    where
+        T: IntoIterator<Item=U>,
+        U: AsRef<str>,
+    {
+        self.paths = paths.into_iter().map(|x| x.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Get the pathspecs the diff is limited to. Empty when all paths are included.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert!(git::DiffOptions::new().paths_val().is_empty());
+    /// ```
+    pub fn paths_val(&self) -> &[String] {
+        &self.paths
+    }
+}
+
+/// Returns the diff between the `from` and `to` revisions as both unified patch text and per
+/// file hunks and lines, the same as `git diff from to`. Renames and optionally copies are
+/// detected using the similarity threshold of the given options. Files whose gitattributes diff
+/// driver has a `diff.<driver>.textconv` command are diffed on the converted text as git does.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_diff_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let diff = git::diff(&tmpdir, "HEAD~1", "HEAD", &git::DiffOptions::new()).unwrap();
+/// assert!(diff.patch.starts_with("diff --git"));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn diff<T, U, V>(path: T, from: U, to: V, opts: &DiffOptions) -> Result<Diff>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let from = repo.revparse_single(from.as_ref())?.peel_to_tree()?;
+    let to = repo.revparse_single(to.as_ref())?.peel_to_tree()?;
+    let mut diffopts = git2::DiffOptions::new();
+    diffopts.context_lines(opts.context);
+    for path in opts.paths.iter() {
+        diffopts.pathspec(path);
+    }
+    let mut diff = repo.diff_tree_to_tree(Some(&from), Some(&to), Some(&mut diffopts))?;
+    if opts.renames || opts.copies {
+        let mut findopts = DiffFindOptions::new();
+        findopts.renames(opts.renames).copies(opts.copies).rename_threshold(opts.similarity).copy_threshold(opts.similarity);
+        diff.find_similar(Some(&mut findopts))?;
+    }
+
+    // Split the patch text per file so files with a textconv diff driver can be replaced
+    let mut result = Diff::default();
+    let mut segments: Vec<Vec<u8>> = vec![];
+    diff.print(DiffFormat::Patch, |_, _, line| {
+        if line.origin() == 'F' || segments.is_empty() {
+            segments.push(vec![]);
+        }
+        print_line(segments.last_mut().unwrap(), &line);
+        true
+    })?;
+
+    let mut patch_text = vec![];
+    for i in 0..diff.deltas().len() {
+        let converted = textconv_content(&repo, &diff.get_delta(i).unwrap())?;
+        let patch = Patch::from_diff(&diff, i)?;
+        let delta = patch.as_ref().map(|x| x.delta()).unwrap_or_else(|| diff.get_delta(i).unwrap());
+        let mut segment = segments.get(i).cloned().unwrap_or_default();
+        let mut conv = None;
+        if let Some((old, new)) = &converted {
+            let mut convopts = git2::DiffOptions::new();
+            convopts.context_lines(opts.context).force_text(true);
+            let mut patch = Patch::from_buffers(old, None, new, None, Some(&mut convopts))?;
+            segment = textconv_header(&delta, &segment);
+            patch.print(&mut |_, _, line| {
+                if line.origin() != 'F' {
+                    print_line(&mut segment, &line);
+                }
+                true
+            })?;
+            conv = Some(patch);
+        }
+        patch_text.extend(segment);
+        let (new, old) = (delta.new_file().path(), delta.old_file().path());
+        let mut file = FileDiff {
+            path: new.or(old).map(|x| x.to_path_buf()).unwrap_or_default(),
+            old_path: if old != new { old.map(|x| x.to_path_buf()) } else { None },
+            state: match delta.status() {
+                Delta::Added => FileState::Added,
+                Delta::Deleted => FileState::Deleted,
+                Delta::Renamed => FileState::Renamed,
+                Delta::Copied => FileState::Copied,
+                Delta::Typechange => FileState::TypeChanged,
+                _ => FileState::Modified,
+            },
+            binary: delta.flags().is_binary() && converted.is_none(),
+            hunks: vec![],
+        };
+        if let Some(patch) = conv.as_ref().or(patch.as_ref()).filter(|_| !file.binary) {
+            for h in 0..patch.num_hunks() {
+                let (hunk, count) = patch.hunk(h)?;
+                let mut lines = vec![];
+                for l in 0..count {
+                    let line = patch.line_in_hunk(h, l)?;
+                    let kind = match line.origin() {
+                        '+' => LineKind::Addition,
+                        '-' => LineKind::Deletion,
+                        ' ' => LineKind::Context,
+                        _ => continue,
+                    };
+                    let content = String::from_utf8_lossy(line.content());
+                    lines.push(DiffLine {
+                        kind,
+                        old_lineno: line.old_lineno(),
+                        new_lineno: line.new_lineno(),
+                        content: content.strip_suffix('\n').unwrap_or(&content).to_string(),
+                    });
+                }
+                file.hunks.push(DiffHunk {
+                    header: String::from_utf8_lossy(hunk.header()).trim_end().to_string(),
+                    old_start: hunk.old_start(),
+                    old_lines: hunk.old_lines(),
+                    new_start: hunk.new_start(),
+                    new_lines: hunk.new_lines(),
+                    lines,
+                });
+            }
+        }
+        result.files.push(file);
+    }
+    result.patch = String::from_utf8_lossy(&patch_text).to_string();
+    Ok(result)
+}
+
+/// Returns the diff stats between the `from` and `to` revisions the same as
+/// `git diff --stat from to` including rename detection.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_diff_stats_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let stats = git::diff_stats(&tmpdir, "HEAD~1", "HEAD").unwrap();
+/// assert_eq!(stats.files_changed, stats.files.len());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn diff_stats<T, U, V>(path: T, from: U, to: V) -> Result<DiffStats>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let from = repo.revparse_single(from.as_ref())?.peel_to_tree()?;
+    let to = repo.revparse_single(to.as_ref())?.peel_to_tree()?;
+    let mut diff = repo.diff_tree_to_tree(Some(&from), Some(&to), None)?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+    let mut stats = DiffStats::default();
+    for i in 0..diff.deltas().len() {
+        // Binary detection is only complete once the patch has loaded the content
+        let patch = Patch::from_diff(&diff, i)?;
+        let delta = patch.as_ref().map(|x| x.delta()).unwrap_or_else(|| diff.get_delta(i).unwrap());
+        let (new, old) = (delta.new_file().path(), delta.old_file().path());
+        let binary = delta.flags().is_binary();
+        let (_, insertions, deletions) = match &patch {
+            Some(patch) if !binary => patch.line_stats()?,
+            _ => (0, 0, 0),
+        };
+        stats.insertions += insertions;
+        stats.deletions += deletions;
+        stats.files.push(FileStats {
+            path: new.or(old).map(|x| x.to_path_buf()).unwrap_or_default(),
+            old_path: if old != new { old.map(|x| x.to_path_buf()) } else { None },
+            insertions,
+            deletions,
+            binary,
+        });
+    }
+    stats.files_changed = stats.files.len();
+    Ok(stats)
+}
+
+impl FileStats {
+    /// Returns the number of lines touched i.e. insertions plus deletions.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let stats = git::FileStats { path: PathBuf::from("foo"), old_path: None, insertions: 2, deletions: 1, binary: false };
+    /// assert_eq!(stats.churn(), 3);
+    /// ```
+    pub fn churn(&self) -> usize {
+        self.insertions + self.deletions
+    }
+}
+
+impl DiffStats {
+    /// Returns the total number of lines touched i.e. insertions plus deletions.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::DiffStats::default().churn(), 0);
+    /// ```
+    pub fn churn(&self) -> usize {
+        self.insertions + self.deletions
+    }
+}
+
+impl DiffHunk {
+    /// Returns the hunk's lines split into spans for word-level highlighting, the same as
+    /// `git diff --word-diff` marks them. Each run of removed lines is paired line by line with
+    /// the run of added lines following it and only the words that differ are marked as
+    /// changed. Lines without a pair and context lines are a single changed or unchanged span.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let line = |kind, content: &str| git::DiffLine { kind, old_lineno: None, new_lineno: None, content: content.to_string() };
+    /// let hunk = git::DiffHunk {
+    ///     header: "@@ -1 +1 @@".to_string(),
+    ///     old_start: 1,
+    ///     old_lines: 1,
+    ///     new_start: 1,
+    ///     new_lines: 1,
+    ///     lines: vec![line(git::LineKind::Deletion, "let foo = 1;"), line(git::LineKind::Addition, "let bar = 1;")],
+    /// };
+    /// let words = hunk.words();
+    /// assert_eq!(words[1].spans.iter().filter(|x| x.changed).map(|x| x.text.as_str()).collect::<Vec<_>>(), vec!["bar"]);
+    /// ```
+    pub fn words(&self) -> Vec<WordLine> {
+        let word_line = |line: &DiffLine, spans: Vec<WordSpan>| WordLine { kind: line.kind, old_lineno: line.old_lineno, new_lineno: line.new_lineno, spans };
+        let whole = |line: &DiffLine| {
+            let spans = if line.content.is_empty() { vec![] } else { vec![WordSpan { text: line.content.clone(), changed: line.kind != LineKind::Context }] };
+            word_line(line, spans)
+        };
+
+        let mut words = vec![];
+        let mut i = 0;
+        while i < self.lines.len() {
+            if self.lines[i].kind != LineKind::Deletion {
+                words.push(whole(&self.lines[i]));
+                i += 1;
+                continue;
+            }
+
+            // Pair the run of deletions with the run of additions that follows it
+            let deletions: Vec<&DiffLine> = self.lines[i..].iter().take_while(|x| x.kind == LineKind::Deletion).collect();
+            i += deletions.len();
+            let additions: Vec<&DiffLine> = self.lines[i..].iter().take_while(|x| x.kind == LineKind::Addition).collect();
+            i += additions.len();
+            let mut added = vec![];
+            for (j, deletion) in deletions.iter().enumerate() {
+                match additions.get(j) {
+                    Some(addition) => {
+                        let (old, new) = word_diff(&deletion.content, &addition.content);
+                        words.push(word_line(deletion, old));
+                        added.push(word_line(addition, new));
+                    },
+                    None => words.push(whole(deletion)),
+                }
+            }
+            words.extend(added);
+            words.extend(additions.iter().skip(deletions.len()).map(|x| whole(x)));
+        }
+        words
+    }
+}
+
+impl Diff {
+    /// Returns the aggregate churn stats of the diff, the same as `diff_stats` would for the
+    /// same revisions and options.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_diff_diff_stats_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let diff = git::diff(&tmpdir, "HEAD~1", "HEAD", &git::DiffOptions::new()).unwrap();
+    /// assert_eq!(diff.stats(), git::diff_stats(&tmpdir, "HEAD~1", "HEAD").unwrap());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn stats(&self) -> DiffStats {
+        let mut stats = DiffStats::default();
+        for file in self.files.iter() {
+            let lines = file.hunks.iter().flat_map(|x| x.lines.iter());
+            let insertions = lines.clone().filter(|x| x.kind == LineKind::Addition).count();
+            let deletions = lines.filter(|x| x.kind == LineKind::Deletion).count();
+            stats.insertions += insertions;
+            stats.deletions += deletions;
+            stats.files.push(FileStats {
+                path: file.path.clone(),
+                old_path: if file.state == FileState::Renamed { file.old_path.clone() } else { None },
+                insertions,
+                deletions,
+                binary: file.binary,
+            });
+        }
+        stats.files_changed = stats.files.len();
+        stats
+    }
+}
+
+// Append the given patch line to the given text. Headers and hunk headers include their own text
+// while other lines need their origin.
+fn print_line(text: &mut Vec<u8>, line: &git2::DiffLine) {
+    if let '+' | '-' | ' ' = line.origin() {
+        text.push(line.origin() as u8);
+    }
+    text.extend_from_slice(line.content());
+}
+
+// Returns the old and new content of the given delta converted with the textconv command of its
+// diff driver, or None if it doesn't have one
+fn textconv_content(repo: &Repository, delta: &DiffDelta) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let (old, new) = (delta.old_file(), delta.new_file());
+    let cmd = match new.path().or_else(|| old.path()) {
+        Some(path) if old.id() != new.id() => super::textconv(repo, path)?,
+        _ => None,
+    };
+    let cmd = match cmd {
+        Some(cmd) => cmd,
+        None => return Ok(None),
+    };
+
+    // Missing sides are empty the same as git and submodules can't be converted
+    let mut content = vec![];
+    for file in [old, new] {
+        content.push(match file.id().is_zero() {
+            true => vec![],
+            false => match repo.find_blob(file.id()) {
+                Ok(blob) => super::run_textconv(repo, &cmd, blob.content())?,
+                Err(_) => return Ok(None),
+            },
+        });
+    }
+    let new = content.pop().unwrap_or_default();
+    Ok(Some((content.pop().unwrap_or_default(), new)))
+}
+
+// Returns the given file header with the `---` and `+++` lines replaced to suit converted
+// content, as binary files don't have them
+fn textconv_header(delta: &DiffDelta, header: &[u8]) -> Vec<u8> {
+    let mut text = vec![];
+    for line in header.split_inclusive(|x| *x == b'\n') {
+        if !line.starts_with(b"--- ") && !line.starts_with(b"+++ ") && !line.starts_with(b"Binary files ") {
+            text.extend_from_slice(line);
+        }
+    }
+    let path = |file: git2::DiffFile, prefix: &str| match file.id().is_zero() {
+        true => "/dev/null".to_string(),
+        false => format!("{}{}", prefix, file.path().map(|x| x.display().to_string()).unwrap_or_default()),
+    };
+    text.extend(format!("--- {}\n+++ {}\n", path(delta.old_file(), "a/"), path(delta.new_file(), "b/")).into_bytes());
+    text
+}
+
+// Split the given text into words, runs of whitespace and single punctuation characters
+fn tokenize(text: &str) -> Vec<&str> {
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+    let mut tokens = vec![];
+    let mut start = 0;
+    let mut prev = None;
+    for (i, c) in text.char_indices() {
+        let cls = class(c);
+        if i > start && (cls == 2 || prev != Some(cls)) {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+        prev = Some(cls);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+// Diff the words of the given old and new lines returning the spans of each with the words not
+// in their longest common subsequence marked as changed
+fn word_diff(old: &str, new: &str) -> (Vec<WordSpan>, Vec<WordSpan>) {
+    let (a, b) = (tokenize(old), tokenize(new));
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    // Merge neighbouring tokens with the same state into a single span
+    let push = |spans: &mut Vec<WordSpan>, text: &str, changed: bool| match spans.last_mut() {
+        Some(last) if last.changed == changed => last.text.push_str(text),
+        _ => spans.push(WordSpan { text: text.to_string(), changed }),
+    };
+    let (mut old, mut new) = (vec![], vec![]);
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            push(&mut old, a[i], false);
+            push(&mut new, b[j], false);
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push(&mut old, a[i], true);
+            i += 1;
+        } else {
+            push(&mut new, b[j], true);
+            j += 1;
+        }
+    }
+    (old, new)
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Returns the `insertions deletions path` lines git itself reports
+    fn git_numstat(path: &Path, from: &str, to: &str) -> Vec<String> {
+        let output = Command::new("git").args(["diff", "--numstat", "-M", from, to]).current_dir(path).output().unwrap();
+        assert_eq!(output.status.success(), true);
+        String::from_utf8(output.stdout).unwrap().lines().map(|x| x.to_string()).collect()
+    }
+
+    // Returns the given stats formatted like git's numstat output
+    fn numstat(stats: &git::DiffStats) -> Vec<String> {
+        stats
+            .files
+            .iter()
+            .map(|x| match x.binary {
+                true => format!("-\t-\t{}", x.path.display()),
+                false => format!("{}\t{}\t{}", x.insertions, x.deletions, x.path.display()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let tmpdir = setup("git_diff");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let git_diff = |args: &[&str]| {
+            let output = Command::new("git").arg("diff").args(args).current_dir(&tmpdir).output().unwrap();
+            assert_eq!(output.status.success(), true);
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        // Patch text matches git across history
+        let diff = git::diff(&tmpdir, "HEAD~20", "HEAD", &git::DiffOptions::new()).unwrap();
+        assert_eq!(diff.patch, git_diff(&["-M", "HEAD~20", "HEAD"]));
+        let stats = git::diff_stats(&tmpdir, "HEAD~20", "HEAD").unwrap();
+        assert_eq!(diff.files.iter().map(|x| x.path.clone()).collect::<Vec<_>>(), stats.files.iter().map(|x| x.path.clone()).collect::<Vec<_>>());
+        for (file, stats) in diff.files.iter().zip(stats.files.iter()) {
+            let lines = file.hunks.iter().flat_map(|x| x.lines.iter());
+            assert_eq!(lines.clone().filter(|x| x.kind == git::LineKind::Addition).count(), stats.insertions);
+            assert_eq!(lines.filter(|x| x.kind == git::LineKind::Deletion).count(), stats.deletions);
+        }
+        assert_eq!(git::diff(&tmpdir, "HEAD", "HEAD", &git::DiffOptions::new()).unwrap(), git::Diff::default());
+
+        // Context and path filters
+        let opts = git::DiffOptions::new().context(1).paths(["README.md"]);
+        let diff = git::diff(&tmpdir, "HEAD~20", "HEAD", &opts).unwrap();
+        assert_eq!(diff.patch, git_diff(&["-M", "-U1", "HEAD~20", "HEAD", "--", "README.md"]));
+        assert_eq!(diff.files.len(), 1);
+        assert_eq!(diff.files[0].path, PathBuf::from("README.md"));
+
+        // Hunks and lines
+        assert!(sys::write(tmpdir.mash("foo"), "1\n2\n3\n4\n5\n6\n7\n8\n").is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add foo").commit().is_ok());
+        assert!(sys::write(tmpdir.mash("foo"), "one\n2\n3\n4\n5\n6\n7\n8\n9\n").is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Update foo").commit().is_ok());
+        let diff = git::diff(&tmpdir, "HEAD~1", "HEAD", &git::DiffOptions::new().context(1)).unwrap();
+        let file = &diff.files[0];
+        assert_eq!((file.state, file.binary, file.hunks.len()), (git::FileState::Modified, false, 2));
+        let hunk = &file.hunks[0];
+        assert_eq!(hunk.header, "@@ -1,2 +1,2 @@");
+        assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (1, 2, 1, 2));
+        assert_eq!(
+            hunk.lines,
+            vec![
+                git::DiffLine {
+                    kind: git::LineKind::Deletion,
+                    old_lineno: Some(1),
+                    new_lineno: None,
+                    content: "1".to_string()
+                },
+                git::DiffLine {
+                    kind: git::LineKind::Addition,
+                    old_lineno: None,
+                    new_lineno: Some(1),
+                    content: "one".to_string()
+                },
+                git::DiffLine {
+                    kind: git::LineKind::Context,
+                    old_lineno: Some(2),
+                    new_lineno: Some(2),
+                    content: "2".to_string()
+                },
+            ]
+        );
+        assert_eq!(file.hunks[1].lines.last().unwrap().content, "9");
+        assert_eq!(diff.patch, git_diff(&["-U1", "HEAD~1", "HEAD"]));
+
+        // Renames, deletions and binary files
+        assert!(sys::move_p(tmpdir.mash("README.md"), tmpdir.mash("DOCS.md")).is_ok());
+        assert!(sys::remove(tmpdir.mash("LICENSE")).is_ok());
+        assert!(std::fs::write(tmpdir.mash("data.bin"), [0u8, 1, 2, 0, 3]).is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Changes").commit().is_ok());
+        let diff = git::diff(&tmpdir, "HEAD~1", "HEAD", &git::DiffOptions::new()).unwrap();
+        assert_eq!(diff.patch, git_diff(&["-M", "HEAD~1", "HEAD"]));
+        let states = diff.files.iter().map(|x| (x.path.to_string().unwrap(), x.state)).collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            vec![
+                ("DOCS.md".to_string(), git::FileState::Renamed),
+                ("LICENSE".to_string(), git::FileState::Deleted),
+                ("data.bin".to_string(), git::FileState::Added),
+            ]
+        );
+        assert_eq!(diff.files[0].old_path, Some(PathBuf::from("README.md")));
+        assert_eq!((diff.files[2].binary, diff.files[2].hunks.len()), (true, 0));
+        let diff = git::diff(&tmpdir, "HEAD~1", "HEAD", &git::DiffOptions::new().renames(false)).unwrap();
+        assert_eq!(diff.files.len(), 4);
+
+        // Similarity thresholds for renames and copies
+        let content = (1..=10).map(|x| format!("line {}\n", x)).collect::<String>();
+        assert!(sys::write(tmpdir.mash("src"), &content).is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add src").commit().is_ok());
+        assert!(sys::move_p(tmpdir.mash("foo"), tmpdir.mash("moved")).is_ok());
+        assert!(sys::write(tmpdir.mash("src"), format!("{}line 11\n", content)).is_ok());
+        assert!(sys::write(tmpdir.mash("copy"), content.replace("line 10", "line ten")).is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Copy src").commit().is_ok());
+        let states = |opts: &git::DiffOptions| {
+            let diff = git::diff(&tmpdir, "HEAD~1", "HEAD", opts).unwrap();
+            diff.files.iter().map(|x| (x.path.to_string().unwrap(), x.old_path.as_ref().map(|y| y.to_string().unwrap()), x.state)).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            states(&git::DiffOptions::new().copies(true)),
+            vec![
+                ("copy".to_string(), Some("src".to_string()), git::FileState::Copied),
+                ("moved".to_string(), Some("foo".to_string()), git::FileState::Renamed),
+                ("src".to_string(), None, git::FileState::Modified),
+            ]
+        );
+        assert_eq!(
+            states(&git::DiffOptions::new().copies(true).similarity(95)),
+            vec![
+                ("copy".to_string(), None, git::FileState::Added),
+                ("moved".to_string(), Some("foo".to_string()), git::FileState::Renamed),
+                ("src".to_string(), None, git::FileState::Modified),
+            ]
+        );
+        let opts = git::DiffOptions::new().similarity(80);
+        assert_eq!(git::diff(&tmpdir, "HEAD~1", "HEAD", &opts).unwrap().patch, git_diff(&["-M80%", "HEAD~1", "HEAD"]));
+        assert_eq!(states(&git::DiffOptions::new().renames(false)).len(), 4);
+
+        // Textconv diff drivers convert content before diffing but don't change the stats
+        assert!(git2::Repository::open(&tmpdir).unwrap().config().unwrap().set_str("diff.hex.textconv", "od -c").is_ok());
+        assert!(sys::write(tmpdir.mash(".gitattributes"), "*.bin diff=hex\n").is_ok());
+        assert!(std::fs::write(tmpdir.mash("data.bin"), [0u8, 1, 2, 0, 4, 10]).is_ok());
+        assert!(std::fs::write(tmpdir.mash("new.bin"), [0u8, 5]).is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Convert").commit().is_ok());
+        let diff = git::diff(&tmpdir, "HEAD~1", "HEAD", &git::DiffOptions::new()).unwrap();
+        assert_eq!(diff.patch, git_diff(&["-M", "HEAD~1", "HEAD"]));
+        let file = diff.files.iter().find(|x| x.path == Path::new("data.bin")).unwrap();
+        assert_eq!((file.binary, file.hunks.len()), (false, 1));
+        assert!(file.hunks[0].lines.iter().any(|x| x.kind == git::LineKind::Addition && x.content.contains("\\0 004  \\n")));
+        assert_eq!(numstat(&git::diff_stats(&tmpdir, "HEAD~1", "HEAD").unwrap()), git_numstat(&tmpdir, "HEAD~1", "HEAD"));
+
+        // Errors
+        assert!(git::diff(&tmpdir, "missing", "HEAD", &git::DiffOptions::new()).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_diff_stats() {
+        let tmpdir = setup("git_diff_stats");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+
+        // Matches git across history
+        let stats = git::diff_stats(&tmpdir, "HEAD~20", "HEAD").unwrap();
+        assert_eq!(numstat(&stats), git_numstat(&tmpdir, "HEAD~20", "HEAD"));
+        assert_eq!(stats.files_changed, stats.files.len());
+        assert_eq!(stats.insertions, stats.files.iter().map(|x| x.insertions).sum::<usize>());
+        assert_eq!(stats.deletions, stats.files.iter().map(|x| x.deletions).sum::<usize>());
+        assert_eq!(git::diff_stats(&tmpdir, "HEAD", "HEAD").unwrap(), git::DiffStats::default());
+
+        // Renames, deletions and binary files
+        assert!(sys::move_p(tmpdir.mash("README.md"), tmpdir.mash("DOCS.md")).is_ok());
+        assert!(sys::remove(tmpdir.mash("LICENSE")).is_ok());
+        assert!(sys::write(tmpdir.mash("Dockerfile"), "FROM scratch\n").is_ok());
+        assert!(std::fs::write(tmpdir.mash("data.bin"), [0u8, 1, 2, 0, 3]).is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Changes").commit().is_ok());
+        let stats = git::diff_stats(&tmpdir, "HEAD~1", "HEAD").unwrap();
+        assert_eq!(stats.files_changed, 4);
+        let docs = stats.files.iter().find(|x| x.path == Path::new("DOCS.md")).unwrap();
+        assert_eq!(docs.old_path, Some(PathBuf::from("README.md")));
+        assert_eq!((docs.insertions, docs.deletions), (0, 0));
+        let license = stats.files.iter().find(|x| x.path == Path::new("LICENSE")).unwrap();
+        assert_eq!(license.old_path, None);
+        assert_eq!(license.insertions, 0);
+        assert_eq!(license.deletions > 0, true);
+        let data = stats.files.iter().find(|x| x.path == Path::new("data.bin")).unwrap();
+        assert_eq!(data.binary, true);
+        let shortstat = Command::new("git").args(["diff", "--shortstat", "-M", "HEAD~1", "HEAD"]).current_dir(&tmpdir).output().unwrap().stdout;
+        let expected = format!(" {} files changed, {} insertion(+), {} deletions(-)", stats.files_changed, stats.insertions, stats.deletions);
+        assert_eq!(String::from_utf8(shortstat).unwrap().trim_end(), expected);
+
+        // Churn and stats aggregated from a diff
+        assert_eq!(stats.churn(), stats.insertions + stats.deletions);
+        assert_eq!(license.churn(), license.deletions);
+        let diff = git::diff(&tmpdir, "HEAD~1", "HEAD", &git::DiffOptions::new()).unwrap();
+        assert_eq!(diff.stats(), stats);
+        let diff = git::diff(&tmpdir, "HEAD~20", "HEAD", &git::DiffOptions::new()).unwrap();
+        assert_eq!(diff.stats(), git::diff_stats(&tmpdir, "HEAD~20", "HEAD").unwrap());
+
+        // Errors
+        assert!(git::diff_stats(&tmpdir, "missing", "HEAD").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    // Returns the given line's spans as text and changed pairs
+    fn spans(line: &git::WordLine) -> Vec<(&str, bool)> {
+        line.spans.iter().map(|x| (x.text.as_str(), x.changed)).collect()
+    }
+
+    #[test]
+    fn test_diff_words() {
+        let tmpdir = setup("git_diff_words");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+
+        // Paired lines only mark the words that changed
+        assert!(sys::write(tmpdir.mash("foo"), "let foo = bar(1);\nkeep\nremoved\n\nold\n").is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add foo").commit().is_ok());
+        assert!(sys::write(tmpdir.mash("foo"), "let foo = baz(1, 2);\nkeep\n\nnew line\nadded\n").is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Update foo").commit().is_ok());
+        let diff = git::diff(&tmpdir, "HEAD~1", "HEAD", &git::DiffOptions::new()).unwrap();
+        let words = diff.files[0].hunks[0].words();
+        let kinds = words.iter().map(|x| x.kind).collect::<Vec<_>>();
+        let lines = diff.files[0].hunks[0].lines.iter().map(|x| x.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, lines);
+        assert_eq!(spans(&words[0]), vec![("let foo = ", false), ("bar", true), ("(1);", false)]);
+        assert_eq!(
+            spans(&words[1]),
+            vec![
+                ("let foo = ", false),
+                ("baz", true),
+                ("(1", false),
+                (", 2", true),
+                (");", false)
+            ]
+        );
+        assert_eq!((words[0].old_lineno, words[1].new_lineno), (Some(1), Some(1)));
+        assert_eq!(spans(&words[2]), vec![("keep", false)]);
+
+        // Unpaired lines are changed as a whole
+        let rest = words[3..].iter().map(|x| (x.kind, spans(x))).collect::<Vec<_>>();
+        assert_eq!(
+            rest,
+            vec![
+                (git::LineKind::Deletion, vec![("removed", true)]),
+                (git::LineKind::Context, vec![]),
+                (git::LineKind::Deletion, vec![("old", true)]),
+                (git::LineKind::Addition, vec![("new line", true)]),
+                (git::LineKind::Addition, vec![("added", true)]),
+            ]
+        );
+        for (word, line) in words.iter().zip(diff.files[0].hunks[0].lines.iter()) {
+            assert_eq!(word.spans.iter().map(|x| x.text.as_str()).collect::<String>(), line.content);
+        }
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
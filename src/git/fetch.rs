@@ -0,0 +1,310 @@
+use super::{fetch_opts, Net, Repo, SharedProgress};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{AutotagOption, FetchPrune, Repository};
+use std::cell::RefCell;
+
+/// Tag handling used by `Repo::fetch`
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum FetchTags {
+    /// Fetch tags pointing into the fetched history unless the remote is configured otherwise
+    #[default]
+    Auto,
+
+    /// Fetch all tags from the remote, the same as `git fetch --tags`
+    All,
+
+    /// Don't fetch any tags, the same as `git fetch --no-tags`
+    None,
+}
+
+/// Options for fetching with `Repo::fetch`
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let opts = git::FetchOptions::new().prune(true).tags(git::FetchTags::None);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FetchOptions {
+    prune: bool,           // Remove remote-tracking refs that no longer exist on the remote
+    tags: FetchTags,       // Tag handling
+    refspecs: Vec<String>, // Refspecs to fetch rather than the remote's configured ones
+}
+
+impl FetchOptions {
+    /// Create new fetch options that fetch the remote's configured refspecs.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::FetchOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set to remove remote-tracking refs that no longer exist on the remote, the same as
+    /// `git fetch --prune`. When not set the repo's `fetch.prune` configuration is used.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::FetchOptions::new().prune(true).prune_val(), true);
+    /// ```
+    pub fn prune(mut self, yes: bool) -> Self {
+        self.prune = yes;
+        self
+    }
+
+    /// Get whether stale remote-tracking refs are removed.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::FetchOptions::new().prune_val(), false);
+    /// ```
+    pub fn prune_val(&self) -> bool {
+        self.prune
+    }
+
+    /// Set the tag handling.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::FetchOptions::new().tags(git::FetchTags::All).tags_val(), git::FetchTags::All);
+    /// ```
+    pub fn tags(mut self, tags: FetchTags) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Get the tag handling.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::FetchOptions::new().tags_val(), git::FetchTags::Auto);
+    /// ```
+    pub fn tags_val(&self) -> FetchTags {
+        self.tags
+    }
+
+    /// Set the refspecs to fetch e.g. `+refs/heads/main:refs/remotes/origin/main` rather than
+    /// the remote's configured ones.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::FetchOptions::new().refspecs(&["refs/heads/main"]);
+    /// assert_eq!(opts.refspecs_val(), &["refs/heads/main".to_string()]);
+    /// ```
+    pub fn refspecs<T, U>(mut self, refspecs: T) -> Self
+    where
+        T: IntoIterator<Item=U>,
+        U: AsRef<str>,
+    {
+        self.refspecs = refspecs.into_iter().map(|x| x.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Get the refspecs to fetch. Empty when the remote's configured ones are used.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert!(git::FetchOptions::new().refspecs_val().is_empty());
+    /// ```
+    pub fn refspecs_val(&self) -> &[String] {
+        &self.refspecs
+    }
+}
+
+impl<'a> Repo<'a> {
+    /// Fetch from the repo's remote, `origin` by default, using the repo's network settings and
+    /// transfer progress callback, the same as `git fetch <remote>`. Only remote-tracking refs
+    /// and any refs named by the options' refspecs are updated, the worktree is left as is.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_fetch_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+    /// let local = tmpdir.mash("local");
+    /// assert!(git::Repo::new(&local).unwrap().url(tmpdir.mash("src").to_string().unwrap()).clone().is_ok());
+    /// let opts = git::FetchOptions::new().prune(true).tags(git::FetchTags::All);
+    /// assert!(git::Repo::new(&local).unwrap().fetch(&opts).is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn fetch(mut self, opts: &FetchOptions) -> Result<()> {
+        let xfer = self.xfer_progress.take().map(RefCell::new);
+        let repo = Repository::open(self.path_val())?;
+        fetch_remote(&repo, self.remote_val(), &self.net, xfer.as_ref(), opts)
+    }
+
+    /// Fetch from every remote configured for the repo in turn, the same as `git fetch --all`,
+    /// sharing the repo's network settings and transfer progress callback across all of them.
+    /// A failure to fetch from one remote doesn't stop the others from being fetched rather
+    /// all failures are returned together as `Error::FetchFailed`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_fetch_all_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+    /// let local = tmpdir.mash("local");
+    /// assert!(git::Repo::new(&local).unwrap().url(tmpdir.mash("src").to_string().unwrap()).clone().is_ok());
+    /// assert!(git::remote_add(&local, "upstream", tmpdir.mash("src").to_string().unwrap()).is_ok());
+    /// assert!(git::Repo::new(&local).unwrap().fetch_all(&git::FetchOptions::new()).is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn fetch_all(mut self, opts: &FetchOptions) -> Result<()> {
+        let xfer = self.xfer_progress.take().map(RefCell::new);
+        let repo = Repository::open(self.path_val())?;
+        let remotes = repo.remotes()?;
+        let mut failures = vec![];
+        for name in remotes.iter().flatten() {
+            if let Err(err) = fetch_remote(&repo, name, &self.net, xfer.as_ref(), opts) {
+                failures.push((name, err));
+            }
+        }
+        match failures.is_empty() {
+            true => Ok(()),
+            false => Err(Error::fetch_failed(failures)),
+        }
+    }
+}
+
+// Fetch from the given remote with the given options
+fn fetch_remote(repo: &Repository, name: &str, net: &Net, xfer: Option<&SharedProgress>, opts: &FetchOptions) -> Result<()> {
+    let mut fetchopts = fetch_opts(net, xfer);
+    if opts.prune_val() {
+        fetchopts.prune(FetchPrune::On);
+    }
+    fetchopts.download_tags(match opts.tags_val() {
+        FetchTags::Auto => AutotagOption::Unspecified,
+        FetchTags::All => AutotagOption::All,
+        FetchTags::None => AutotagOption::None,
+    });
+    let mut remote = repo.find_remote(name)?;
+    net.auth.settle(|| remote.fetch(opts.refspecs_val(), Some(&mut fetchopts), None))?;
+    Ok(())
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_fetch() {
+        let tmpdir = setup("git_fetch");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+        let remote = tmpdir.mash("remote");
+        assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+        let bare = git2::Repository::open(&remote).unwrap();
+        let repo = git2::Repository::open(&local).unwrap();
+        let head = bare.refname_to_id("refs/heads/master").unwrap();
+        let parent = bare.revparse_single("master~1").unwrap().id();
+
+        // New branch and tags on the remote
+        bare.reference("refs/heads/feature", parent, false, "").unwrap();
+        bare.reference("refs/tags/v9.0.0", head, false, "").unwrap();
+
+        // No tags
+        let opts = git::FetchOptions::new().tags(git::FetchTags::None);
+        assert!(git::Repo::new(&local).unwrap().fetch(&opts).is_ok());
+        assert_eq!(repo.refname_to_id("refs/remotes/origin/feature").unwrap(), parent);
+        assert!(repo.refname_to_id("refs/tags/v9.0.0").is_err());
+
+        // All tags
+        let opts = git::FetchOptions::new().tags(git::FetchTags::All);
+        assert!(git::Repo::new(&local).unwrap().fetch(&opts).is_ok());
+        assert_eq!(repo.refname_to_id("refs/tags/v9.0.0").unwrap(), head);
+
+        // Stale remote-tracking refs are only removed when pruning
+        bare.find_reference("refs/heads/feature").unwrap().delete().unwrap();
+        assert!(git::Repo::new(&local).unwrap().fetch(&git::FetchOptions::new()).is_ok());
+        assert!(repo.refname_to_id("refs/remotes/origin/feature").is_ok());
+        assert!(git::Repo::new(&local).unwrap().fetch(&git::FetchOptions::new().prune(true)).is_ok());
+        assert!(repo.refname_to_id("refs/remotes/origin/feature").is_err());
+        assert!(repo.refname_to_id("refs/remotes/origin/master").is_ok());
+
+        // Specific refspecs
+        let opts = git::FetchOptions::new().refspecs(["+refs/heads/master:refs/heads/copy"]);
+        assert!(git::Repo::new(&local).unwrap().fetch(&opts).is_ok());
+        assert_eq!(repo.refname_to_id("refs/heads/copy").unwrap(), head);
+
+        // Other remotes
+        assert!(git::remote_add(&local, "upstream", tmpdir.mash("src").to_string().unwrap()).is_ok());
+        assert!(git::Repo::new(&local).unwrap().remote("upstream").fetch(&git::FetchOptions::new()).is_ok());
+        assert_eq!(repo.refname_to_id("refs/remotes/upstream/master").unwrap(), head);
+        assert!(git::Repo::new(&local).unwrap().remote("missing").fetch(&git::FetchOptions::new()).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_fetch_all() {
+        let tmpdir = setup("git_fetch_all");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+        let (one, two) = (tmpdir.mash("one"), tmpdir.mash("two"));
+        assert!(git::Repo::new(&one).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        assert!(git::Repo::new(&two).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(one.to_string().unwrap()).clone().is_ok());
+        assert!(git::remote_add(&local, "two", two.to_string().unwrap()).is_ok());
+        let repo = git2::Repository::open(&local).unwrap();
+
+        // New commit on the second remote is fetched with progress
+        let bare = git2::Repository::open(&two).unwrap();
+        let head = bare.head().unwrap().peel_to_commit().unwrap();
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let oid = bare.commit(Some("HEAD"), &sig, &sig, "Empty", &head.tree().unwrap(), &[&head]).unwrap();
+        let mut progress = 0;
+        assert!(git::Repo::new(&local).unwrap().xfer_progress(|total, _| progress += total).fetch_all(&git::FetchOptions::new()).is_ok());
+        assert!(progress > 0);
+        assert_eq!(repo.refname_to_id("refs/remotes/two/master").unwrap(), oid);
+        assert_eq!(repo.refname_to_id("refs/remotes/origin/master").unwrap(), head.id());
+
+        // Failures are aggregated without stopping the other remotes
+        assert!(git::remote_add(&local, "bad", tmpdir.mash("missing").to_string().unwrap()).is_ok());
+        assert!(git::remote_add(&local, "worse", tmpdir.mash("also-missing").to_string().unwrap()).is_ok());
+        bare.reference("refs/heads/feature", oid, false, "").unwrap();
+        let err = git::Repo::new(&local).unwrap().fetch_all(&git::FetchOptions::new()).unwrap_err();
+        match err {
+            git::Error::FetchFailed(ref failures) => {
+                assert_eq!(failures.iter().map(|x| x.0.as_str()).collect::<Vec<_>>(), vec!["bad", "worse"]);
+            },
+            _ => panic!("unexpected error: {}", err),
+        }
+        assert_eq!(repo.refname_to_id("refs/remotes/two/feature").unwrap(), oid);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,372 @@
+use super::loose_objects;
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{ErrorCode, ObjectType, Odb, Oid, Repository};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
+
+/// Kind of problem found by `verify`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsckIssueKind {
+    /// An object referenced by a ref, reflog, the index or another object isn't in the repo
+    Missing,
+
+    /// An object's content doesn't hash to its id
+    HashMismatch,
+
+    /// An object can't be read or parsed e.g. a truncated loose object or a damaged pack
+    Corrupt,
+
+    /// An object is of a different type than its referrer expects e.g. a tree whose blob entry
+    /// is a commit
+    WrongType,
+}
+
+/// Problem found by `verify`
+#[derive(Clone, Debug, PartialEq)]
+pub struct FsckIssue {
+    pub oid: Oid,                 // Id of the object with the problem
+    pub kind: FsckIssueKind,      // Kind of problem
+    pub referrer: Option<String>, // Ref, reflog, `index` or object id referencing the object if any
+    pub message: String,          // Description of the problem
+}
+
+/// Unreachable object no other unreachable object references as returned in `FsckReport`
+#[derive(Clone, Debug, PartialEq)]
+pub struct DanglingObject {
+    pub oid: Oid,         // Id of the object
+    pub kind: ObjectType, // Type of the object
+}
+
+/// Outcome of verifying a repository as returned by `verify`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FsckReport {
+    pub objects: usize,                // Number of objects checked
+    pub reachable: usize,              // Number of objects reachable from refs, reflogs and the index
+    pub issues: Vec<FsckIssue>,        // Problems found ordered by object id
+    pub dangling: Vec<DanglingObject>, // Dangling objects ordered by object id
+}
+
+impl FsckReport {
+    /// Returns true if no problems were found. Dangling objects aren't problems.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::FsckReport::default().is_ok(), true);
+    /// ```
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Verify the integrity of the given repo the same as `git fsck --full`. Every object in the loose
+/// and packed object stores is read and its content checked against its id. The objects
+/// reachable from the refs, HEAD, the reflogs and the index are walked to check that everything
+/// they reference exists and is of the right type. Unreachable objects that no other unreachable
+/// object references are reported as dangling. Useful for validating backups.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_verify_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let report = git::verify(&tmpdir).unwrap();
+/// assert_eq!(report.is_ok(), true);
+/// assert_eq!(report.dangling, vec![]);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn verify<T: AsRef<Path>>(path: T) -> Result<FsckReport> {
+    let repo = Repository::open(path.as_ref())?;
+    let odb = repo.odb()?;
+    let mut report = FsckReport::default();
+    let loose: HashMap<Oid, PathBuf> = loose_objects(&repo)?.into_iter().collect();
+    let mut checked: HashMap<Oid, Option<ObjectType>> = HashMap::new();
+
+    // Walk the reachable objects checking each once
+    let mut pending = roots(&repo)?;
+    let mut reachable = HashSet::new();
+    while let Some((oid, expected, referrer)) = pending.pop() {
+        let kind = match checked.get(&oid) {
+            Some(kind) => *kind,
+            None => {
+                let kind = check(&odb, &loose, oid, Some(&referrer), &mut report);
+                checked.insert(oid, kind);
+                kind
+            },
+        };
+        if let (Some(kind), Some(expected)) = (kind, expected) {
+            if kind != expected {
+                report.issues.push(FsckIssue {
+                    oid,
+                    kind: FsckIssueKind::WrongType,
+                    referrer: Some(referrer),
+                    message: format!("expected {} but found {}", expected, kind),
+                });
+                continue;
+            }
+        }
+        if kind.is_some() && reachable.insert(oid) {
+            pending.extend(references(&repo, oid)?.into_iter().map(|(x, y)| (x, y, oid.to_string())));
+        }
+    }
+    report.reachable = reachable.len();
+
+    // Check the unreachable objects and find the dangling ones among them
+    let mut all = vec![];
+    odb.foreach(|oid| {
+        all.push(*oid);
+        true
+    })?;
+    all.sort();
+    all.dedup();
+    let mut unreachable = vec![];
+    for oid in all.into_iter().filter(|x| !reachable.contains(x)) {
+        let kind = match checked.get(&oid) {
+            Some(kind) => *kind,
+            None => check(&odb, &loose, oid, None, &mut report),
+        };
+        checked.insert(oid, kind);
+        if let Some(kind) = kind {
+            unreachable.push(DanglingObject { oid, kind });
+        }
+    }
+    let mut referenced = HashSet::new();
+    for object in unreachable.iter() {
+        referenced.extend(references(&repo, object.oid)?.into_iter().map(|(x, _)| x));
+    }
+    report.dangling = unreachable.into_iter().filter(|x| !referenced.contains(&x.oid)).collect();
+    report.objects = checked.len();
+    report.issues.sort_by_key(|x| x.oid);
+    Ok(report)
+}
+
+// Returns the objects the walk starts from with the ref, reflog or index they come from
+fn roots(repo: &Repository) -> Result<Vec<(Oid, Option<ObjectType>, String)>> {
+    let mut roots = vec![];
+    let mut names = vec!["HEAD".to_string()];
+    for reference in repo.references()? {
+        let reference = reference?;
+        let name = reference.name().unwrap_or_default().to_string();
+        if let Some(oid) = reference.target() {
+            roots.push((oid, None, name.clone()));
+        }
+        names.push(name);
+    }
+    if let Ok(oid) = repo.refname_to_id("HEAD") {
+        roots.push((oid, None, "HEAD".to_string()));
+    }
+    for name in names.iter() {
+        for (i, entry) in repo.reflog(name)?.iter().enumerate() {
+            for oid in [entry.id_old(), entry.id_new()].iter().filter(|x| !x.is_zero()) {
+                roots.push((*oid, None, format!("{}@{{{}}}", name, i)));
+            }
+        }
+    }
+    if !repo.is_bare() {
+        for entry in repo.index()?.iter().filter(|x| x.mode != 0o160000) {
+            roots.push((entry.id, Some(ObjectType::Blob), "index".to_string()));
+        }
+    }
+    roots.reverse();
+    Ok(roots)
+}
+
+// Returns the objects the given object references with the type each is expected to be.
+// Submodule commits in trees live in another repo so aren't included.
+fn references(repo: &Repository, oid: Oid) -> Result<Vec<(Oid, Option<ObjectType>)>> {
+    let object = match repo.find_object(oid, None) {
+        Ok(object) => object,
+        Err(_) => return Ok(vec![]),
+    };
+    let mut references = vec![];
+    if let Some(commit) = object.as_commit() {
+        references.push((commit.tree_id(), Some(ObjectType::Tree)));
+        references.extend(commit.parent_ids().map(|x| (x, Some(ObjectType::Commit))));
+    } else if let Some(tree) = object.as_tree() {
+        references.extend(tree.iter().filter(|x| x.filemode() != 0o160000).map(|x| (x.id(), x.kind())));
+    } else if let Some(tag) = object.as_tag() {
+        references.push((tag.target_id(), tag.target_type()));
+    }
+    Ok(references)
+}
+
+// Read the given object and check its content against its id recording any problem in the
+// report. Returns the object's type if it's intact.
+fn check(odb: &Odb, loose: &HashMap<Oid, PathBuf>, oid: Oid, referrer: Option<&str>, report: &mut FsckReport) -> Option<ObjectType> {
+    let issue = |kind, message: String| FsckIssue { oid, kind, referrer: referrer.map(|x| x.to_string()), message };
+    if let Some(file) = loose.get(&oid) {
+        if !inflates(file) {
+            report.issues.push(issue(FsckIssueKind::Corrupt, format!("failed to inflate loose object {}", file.display())));
+            return None;
+        }
+    }
+    let object = match odb.read(oid) {
+        Ok(object) => object,
+        Err(err) => {
+            let kind = match err.code() {
+                ErrorCode::NotFound => FsckIssueKind::Missing,
+                ErrorCode::HashsumMismatch => FsckIssueKind::HashMismatch,
+                _ => FsckIssueKind::Corrupt,
+            };
+            report.issues.push(issue(kind, err.message().to_string()));
+            return None;
+        },
+    };
+    match Oid::hash_object(object.kind(), object.data()) {
+        Ok(hash) if hash == oid => Some(object.kind()),
+        Ok(hash) => {
+            report.issues.push(issue(FsckIssueKind::HashMismatch, format!("content hashes to {}", hash)));
+            None
+        },
+        Err(err) => {
+            report.issues.push(issue(FsckIssueKind::Corrupt, err.message().to_string()));
+            None
+        },
+    }
+}
+
+// Returns true if the given loose object's zlib stream is complete. libgit2 1.3 never returns
+// from reading a truncated loose object so they're checked before it reads them.
+fn inflates(file: &Path) -> bool {
+    let data = match fs::read(file) {
+        Ok(data) => data,
+        Err(_) => return false,
+    };
+
+    // Grow the buffer while it's too small up to zlib's maximum ratio
+    let max = data.len() * 1032 + 1024;
+    let mut size = data.len() * 4 + 1024;
+    loop {
+        let mut buf = vec![0u8; size];
+        let mut len = size as libz_sys::uLongf;
+        let code = unsafe { libz_sys::uncompress(buf.as_mut_ptr(), &mut len, data.as_ptr(), data.len() as libz_sys::uLong) };
+        match code {
+            libz_sys::Z_BUF_ERROR if size < max => size = (size * 2).min(max),
+            code => return code == libz_sys::Z_OK,
+        }
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Returns the exit status and output of the given git command
+    fn git(path: &Path, args: &[&str]) -> (bool, String) {
+        let output = Command::new("git").args(args).current_dir(path).output().unwrap();
+        (output.status.success(), String::from_utf8(output.stdout).unwrap())
+    }
+
+    // Returns the path of the given loose object
+    fn loose(path: &Path, oid: git2::Oid) -> PathBuf {
+        path.mash(".git/objects").mash(&oid.to_string()[..2]).mash(&oid.to_string()[2..])
+    }
+
+    #[test]
+    fn test_verify() {
+        let tmpdir = setup("git_verify");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let objects = git(
+            &tmpdir,
+            &[
+                "rev-list",
+                "--all",
+                "--objects",
+                "--reflog",
+                "--indexed-objects",
+            ],
+        )
+        .1
+        .lines()
+        .count();
+
+        // Intact repo
+        let report = git::verify(&tmpdir).unwrap();
+        assert_eq!(report.is_ok(), true);
+        assert_eq!((report.objects, report.reachable), (objects, objects));
+        assert_eq!(report.dangling, vec![]);
+
+        // Dangling objects are the unreachable tips the same as git
+        assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+        let foo = git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add foo").commit().unwrap();
+        let blob = repo.blob(b"dangling").unwrap();
+        assert!(git::reset(&tmpdir, "HEAD~1", git::ResetMode::Hard, true).is_ok());
+        assert!(git::reflog_expire(&tmpdir, None, i64::MAX).is_ok());
+        let report = git::verify(&tmpdir).unwrap();
+        assert_eq!(report.is_ok(), true);
+        assert_eq!(report.objects, objects + 4);
+        assert_eq!(report.dangling, {
+            let mut dangling = vec![
+                git::DanglingObject { oid: foo, kind: git2::ObjectType::Commit },
+                git::DanglingObject { oid: blob, kind: git2::ObjectType::Blob },
+            ];
+            dangling.sort_by_key(|x| x.oid);
+            dangling
+        });
+        let mut expected: Vec<String> = git(&tmpdir, &["fsck", "--full", "--no-progress"]).1.lines().map(|x| x.split(' ').nth(2).unwrap().to_string()).collect();
+        expected.sort();
+        assert_eq!(report.dangling.iter().map(|x| x.oid.to_string()).collect::<Vec<_>>(), expected);
+
+        // Missing reachable objects are reported with their referrer
+        assert!(sys::write(tmpdir.mash("bar"), "bar").is_ok());
+        let bar = git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add bar").commit().unwrap();
+        let tree = repo.find_commit(bar).unwrap().tree_id();
+        let barblob = repo.find_commit(bar).unwrap().tree().unwrap().get_name("bar").unwrap().id();
+        assert!(sys::remove(loose(&tmpdir, barblob)).is_ok());
+        let report = git::verify(&tmpdir).unwrap();
+        assert_eq!(report.is_ok(), false);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].oid, barblob);
+        assert_eq!(report.issues[0].kind, git::FsckIssueKind::Missing);
+        assert!(report.issues[0].referrer == Some(tree.to_string()) || report.issues[0].referrer == Some("index".to_string()));
+        assert_eq!(git(&tmpdir, &["fsck", "--full", "--no-progress"]).0, false);
+
+        // Objects whose content doesn't match their id
+        let data = std::fs::read(loose(&tmpdir, blob)).unwrap();
+        std::fs::write(loose(&tmpdir, barblob), &data).unwrap();
+        let report = git::verify(&tmpdir).unwrap();
+        assert_eq!(report.issues.iter().map(|x| (x.oid, x.kind)).collect::<Vec<_>>(), vec![(barblob, git::FsckIssueKind::HashMismatch)]);
+
+        // Corrupt objects
+        std::fs::write(loose(&tmpdir, barblob), &data[..data.len() / 2]).unwrap();
+        let report = git::verify(&tmpdir).unwrap();
+        assert_eq!(report.issues.iter().map(|x| (x.oid, x.kind)).collect::<Vec<_>>(), vec![(barblob, git::FsckIssueKind::Corrupt)]);
+
+        // Objects of the wrong type
+        assert!(sys::remove(loose(&tmpdir, barblob)).is_ok());
+        let mut data = b"100644 bad\0".to_vec();
+        data.extend(foo.as_bytes());
+        let bad = repo.odb().unwrap().write(git2::ObjectType::Tree, &data).unwrap();
+        let sig = git2::Signature::now("foo", "foo@example.com").unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let commit = repo.commit(None, &sig, &sig, "Bad", &repo.find_tree(bad).unwrap(), &[&head]).unwrap();
+        repo.reference("refs/heads/bad", commit, true, "bad").unwrap();
+        let report = git::verify(&tmpdir).unwrap();
+        let issue = report.issues.iter().find(|x| x.kind == git::FsckIssueKind::WrongType).unwrap();
+        assert_eq!((issue.oid, issue.referrer.clone()), (foo, Some(bad.to_string())));
+
+        // Errors
+        assert!(git::verify(tmpdir.mash("../missing")).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,179 @@
+use super::resolve_commit;
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{ErrorCode, Oid, Repository};
+
+/// Returns the best common ancestor of the two given revisions, the same as `git merge-base`,
+/// or None if they have no history in common.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_merge_base_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::merge_base(&tmpdir, "HEAD", "HEAD~2").unwrap(), Some(git::resolve(&tmpdir, "HEAD~2").unwrap()));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn merge_base<T, U, V>(path: T, one: U, two: V) -> Result<Option<Oid>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let (one, two) = (resolve_commit(&repo, one.as_ref())?, resolve_commit(&repo, two.as_ref())?);
+    base(&repo, one, two)
+}
+
+/// Returns the best common ancestor of all the given revisions for an octopus merge, the same as
+/// `git merge-base --octopus`, or None if they have no history in common or none are given.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_merge_base_octopus_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let base = git::merge_base_octopus(&tmpdir, &["HEAD", "HEAD~1", "HEAD~3"]).unwrap();
+/// assert_eq!(base, Some(git::resolve(&tmpdir, "HEAD~3").unwrap()));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn merge_base_octopus<T, U>(path: T, revs: &[U]) -> Result<Option<Oid>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let mut oids = vec![];
+    for rev in revs.iter() {
+        oids.push(resolve_commit(&repo, rev.as_ref())?);
+    }
+
+    // Reduce to the common ancestor of each revision in turn
+    let mut result = oids.first().copied();
+    for oid in oids.iter().skip(1) {
+        result = match result {
+            Some(current) => base(&repo, current, *oid)?,
+            None => break,
+        };
+    }
+    Ok(result)
+}
+
+/// Returns true if `commit` is a descendant of `ancestor` i.e. `ancestor` is reachable from
+/// `commit`. A commit isn't considered a descendant of itself.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_is_descendant_of_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::is_descendant_of(&tmpdir, "HEAD", "HEAD~1").unwrap(), true);
+/// assert_eq!(git::is_descendant_of(&tmpdir, "HEAD~1", "HEAD").unwrap(), false);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn is_descendant_of<T, U, V>(path: T, commit: U, ancestor: V) -> Result<bool>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let (commit, ancestor) = (resolve_commit(&repo, commit.as_ref())?, resolve_commit(&repo, ancestor.as_ref())?);
+    Ok(repo.graph_descendant_of(commit, ancestor)?)
+}
+
+// Merge base of the two given commits mapping no common history to None
+fn base(repo: &Repository, one: Oid, two: Oid) -> Result<Option<Oid>> {
+    match repo.merge_base(one, two) {
+        Ok(oid) => Ok(Some(oid)),
+        Err(err) if err.code() == ErrorCode::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Commit the given content to the given file
+    fn commit(path: &Path, file: &str, content: &str) -> git2::Oid {
+        assert!(sys::write(path.mash(file), content).is_ok());
+        git::CommitBuilder::new(path).stage_all(true).author("foo", "foo@example.com").message(format!("Update {}", file)).commit().unwrap()
+    }
+
+    // Returns the merge base git itself gives with the given arguments
+    fn git_merge_base(path: &Path, args: &[&str]) -> git2::Oid {
+        let output = Command::new("git").arg("merge-base").args(args).current_dir(path).output().unwrap();
+        assert_eq!(output.status.success(), true);
+        git2::Oid::from_str(String::from_utf8(output.stdout).unwrap().trim()).unwrap()
+    }
+
+    #[test]
+    fn test_merge_base() {
+        let tmpdir = setup("git_merge_base");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+
+        // Three branches diverging from different points
+        assert!(git::branch_create(&tmpdir, "one", Some("HEAD~1"), false).is_ok());
+        assert!(git::branch_create(&tmpdir, "two", Some("HEAD~3"), false).is_ok());
+        commit(&tmpdir, "foo", "foo");
+        assert!(git::Repo::new(&tmpdir).unwrap().checkout("one", false).is_ok());
+        commit(&tmpdir, "bar", "bar");
+        assert!(git::Repo::new(&tmpdir).unwrap().checkout("two", false).is_ok());
+        commit(&tmpdir, "baz", "baz");
+
+        let base = git::merge_base(&tmpdir, "master", "one").unwrap();
+        assert_eq!(base, Some(git_merge_base(&tmpdir, &["master", "one"])));
+        assert_eq!(base, Some(repo.revparse_single("master~2").unwrap().id()));
+        assert_eq!(git::merge_base(&tmpdir, "one", "two").unwrap(), Some(git_merge_base(&tmpdir, &["one", "two"])));
+        assert_eq!(git::merge_base(&tmpdir, "master", "master").unwrap(), Some(git::resolve(&tmpdir, "master").unwrap()));
+
+        // Octopus
+        let base = git::merge_base_octopus(&tmpdir, &["master", "one", "two"]).unwrap();
+        assert_eq!(base, Some(git_merge_base(&tmpdir, &["--octopus", "master", "one", "two"])));
+        assert_eq!(base, Some(repo.revparse_single("master~4").unwrap().id()));
+        assert_eq!(git::merge_base_octopus(&tmpdir, &["one"]).unwrap(), Some(git::resolve(&tmpdir, "one").unwrap()));
+        assert_eq!(git::merge_base_octopus::<_, &str>(&tmpdir, &[]).unwrap(), None);
+
+        // Unrelated history
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = repo.find_tree(repo.treebuilder(None).unwrap().write().unwrap()).unwrap();
+        let orphan = repo.commit(Some("refs/heads/orphan"), &sig, &sig, "Orphan", &tree, &[]).unwrap();
+        assert_eq!(git::merge_base(&tmpdir, "master", "orphan").unwrap(), None);
+        assert_eq!(git::merge_base_octopus(&tmpdir, &["master", "one", "orphan"]).unwrap(), None);
+        assert_eq!(git::merge_base_octopus(&tmpdir, &["orphan", "master", "one"]).unwrap(), None);
+
+        // Descendants
+        assert_eq!(git::is_descendant_of(&tmpdir, "master", "master~2").unwrap(), true);
+        assert_eq!(git::is_descendant_of(&tmpdir, "master~2", "master").unwrap(), false);
+        assert_eq!(git::is_descendant_of(&tmpdir, "master", "master").unwrap(), false);
+        assert_eq!(git::is_descendant_of(&tmpdir, "master", "one").unwrap(), false);
+        assert_eq!(git::is_descendant_of(&tmpdir, "orphan", orphan.to_string()).unwrap(), false);
+
+        // Errors
+        assert!(git::merge_base(&tmpdir, "master", "missing").is_err());
+        assert!(git::merge_base_octopus(&tmpdir, &["master", "missing"]).is_err());
+        assert!(git::is_descendant_of(&tmpdir, "missing", "master").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,438 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{ErrorCode, Repository};
+use std::{
+    fs,
+    process::{Command, Stdio},
+};
+
+/// Names of the hooks git runs, see `man githooks`
+pub const HOOKS: [&str; 28] = [
+    "applypatch-msg",
+    "commit-msg",
+    "fsmonitor-watchman",
+    "p4-changelist",
+    "p4-post-changelist",
+    "p4-pre-submit",
+    "p4-prepare-changelist",
+    "post-applypatch",
+    "post-checkout",
+    "post-commit",
+    "post-index-change",
+    "post-merge",
+    "post-receive",
+    "post-rewrite",
+    "post-update",
+    "pre-applypatch",
+    "pre-auto-gc",
+    "pre-commit",
+    "pre-merge-commit",
+    "pre-push",
+    "pre-rebase",
+    "pre-receive",
+    "prepare-commit-msg",
+    "proc-receive",
+    "push-to-checkout",
+    "reference-transaction",
+    "sendemail-validate",
+    "update",
+];
+
+/// Hooks run by operations that create commits e.g. `CommitBuilder` and `merge`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum HookMode {
+    /// Don't run any hooks
+    #[default]
+    Skip,
+
+    /// Run all of the applicable hooks the same as the git CLI does
+    Run,
+
+    /// Run the applicable hooks except the `pre-commit`, `pre-merge-commit` and `commit-msg`
+    /// verification hooks, the same as `git commit --no-verify`
+    NoVerify,
+}
+
+/// Hook as returned by `hooks`
+#[derive(Clone, Debug, PartialEq)]
+pub struct HookInfo {
+    pub name: String,            // Name of the hook e.g. `pre-commit`
+    pub path: PathBuf,           // Location of the hook script
+    pub target: Option<PathBuf>, // Script the hook links to if it is a symlink
+    pub executable: bool,        // True if git will run the hook
+}
+
+/// Returns the directory the repo's hooks are run from, which is `core.hooksPath` if configured
+/// or the `hooks` directory of the repo's git directory. A relative `core.hooksPath` is resolved
+/// against the worktree, or the git directory for bare repos, the same as git does.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_hooks_dir_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::hooks_dir(&tmpdir).unwrap(), tmpdir.mash(".git/hooks"));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn hooks_dir<T: AsRef<Path>>(path: T) -> Result<PathBuf> {
+    let repo = Repository::open(path.as_ref())?;
+    repo_hooks_dir(&repo)
+}
+
+/// Set `core.hooksPath` to the given directory so that the repo runs the hooks in it e.g. a
+/// directory of hooks shared by many repos, or unset it when None is given so that the repo's
+/// own hooks are run again.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_hooks_path_set_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::hooks_path_set(&tmpdir, Some("/usr/share/hooks")).is_ok());
+/// assert_eq!(git::hooks_dir(&tmpdir).unwrap(), PathBuf::from("/usr/share/hooks"));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn hooks_path_set<T: AsRef<Path>>(path: T, dir: Option<&str>) -> Result<()> {
+    let repo = Repository::open(path.as_ref())?;
+    let mut config = repo.config()?.open_level(git2::ConfigLevel::Local)?;
+    match dir {
+        Some(dir) => config.set_str("core.hooksPath", dir)?,
+        None => match config.remove("core.hooksPath") {
+            Err(err) if err.code() != ErrorCode::NotFound => return Err(err.into()),
+            _ => (),
+        },
+    }
+    Ok(())
+}
+
+/// List the hooks installed in the repo's hooks directory sorted by name, see `hooks_dir`.
+/// The `.sample` scripts git creates are skipped.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_hooks_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::hook_install(&tmpdir, "pre-commit", "#!/bin/sh\nexit 0\n").is_ok());
+/// assert_eq!(git::hooks(&tmpdir).unwrap()[0].name, "pre-commit");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn hooks<T: AsRef<Path>>(path: T) -> Result<Vec<HookInfo>> {
+    let dir = hooks_dir(path)?;
+    let mut hooks = vec![];
+    if !dir.is_dir() {
+        return Ok(hooks);
+    }
+    for path in sys::paths(&dir)? {
+        let name = path.base()?;
+        if !HOOKS.contains(&name.as_str()) || path.is_dir() {
+            continue;
+        }
+        let target = if path.is_symlink() { Some(path.readlink()?) } else { None };
+        hooks.push(HookInfo { name, executable: path.is_exec(), path, target });
+    }
+    hooks.sort_by(|x, y| x.name.cmp(&y.name));
+    Ok(hooks)
+}
+
+/// Install the given script as the hook with the given name in the repo's hooks directory, see
+/// `hooks_dir`, making it executable. Any existing hook with the same name is replaced. Returns
+/// the location of the installed hook.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_hook_install_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let hook = git::hook_install(&tmpdir, "pre-commit", "#!/bin/sh\nexit 0\n").unwrap();
+/// assert_eq!(hook.is_exec(), true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn hook_install<T, U, V>(path: T, name: U, script: V) -> Result<PathBuf>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let hook = hook_path(path.as_ref(), name.as_ref())?;
+
+    // Writing through an existing symlink would change the shared script it links to
+    remove_hook(&hook)?;
+    sys::write(&hook, script.as_ref())?;
+    sys::chmod(&hook, 0o755)?;
+    Ok(hook)
+}
+
+/// Install the hook with the given name as a symlink to the given script e.g. one kept in a
+/// directory of hooks shared by many repos, so that updating the script updates the hook of
+/// every repo linked to it. Any existing hook with the same name is replaced. Returns the
+/// location of the installed hook.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_hook_link_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("repo")).is_ok());
+/// assert!(sys::mkdir(tmpdir.mash("shared")).is_ok());
+/// assert!(sys::write(tmpdir.mash("shared/pre-commit"), "#!/bin/sh\nexit 0\n").is_ok());
+/// let hook = git::hook_link(tmpdir.mash("repo"), "pre-commit", tmpdir.mash("shared/pre-commit")).unwrap();
+/// assert_eq!(hook.readlink().unwrap(), tmpdir.mash("shared/pre-commit"));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn hook_link<T, U, V>(path: T, name: U, target: V) -> Result<PathBuf>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<Path>,
+{
+    let hook = hook_path(path.as_ref(), name.as_ref())?;
+    let target = target.as_ref().abs()?;
+    if !target.is_file() {
+        return Err(Error::path_not_found(target.to_string_lossy()));
+    }
+    remove_hook(&hook)?;
+    sys::symlink(&hook, &target)?;
+    Ok(hook)
+}
+
+/// Run the hook with the given name with the given arguments the same as git would, from the
+/// top of the worktree with `GIT_INDEX_FILE` set. Returns true if the hook was run or false if
+/// it isn't installed or isn't executable, in which case git doesn't run it either. Returns
+/// `Error::HookFailed` with the hook's output if it exits with a non-zero status.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_hook_run_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::hook_run(&tmpdir, "pre-commit", &[] as &[&str]).unwrap(), false);
+/// assert!(git::hook_install(&tmpdir, "pre-commit", "#!/bin/sh\necho failed; exit 1\n").is_ok());
+/// assert!(git::hook_run(&tmpdir, "pre-commit", &[] as &[&str]).is_err());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn hook_run<T, U, V>(path: T, name: U, args: &[V]) -> Result<bool>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    run_hook(&repo, name.as_ref(), args)
+}
+
+/// Remove the hook with the given name from the repo's hooks directory, see `hooks_dir`.
+/// Symlinked hooks are unlinked leaving the script they link to as is. Returns true if the hook
+/// existed.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_hook_remove_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::hook_install(&tmpdir, "pre-commit", "#!/bin/sh\nexit 0\n").is_ok());
+/// assert_eq!(git::hook_remove(&tmpdir, "pre-commit").unwrap(), true);
+/// assert_eq!(git::hook_remove(&tmpdir, "pre-commit").unwrap(), false);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn hook_remove<T, U>(path: T, name: U) -> Result<bool>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let hook = hook_path(path.as_ref(), name.as_ref())?;
+    remove_hook(&hook)
+}
+
+// Returns the directory the given repo's hooks are run from, see `hooks_dir`
+fn repo_hooks_dir(repo: &Repository) -> Result<PathBuf> {
+    let gitdir = repo.path().to_string_lossy().trim_end_matches('/').to_string();
+    match repo.config()?.get_path("core.hooksPath") {
+        Ok(dir) if dir.is_absolute() => Ok(dir),
+        Ok(dir) => Ok(repo.workdir().map(|x| x.to_path_buf()).unwrap_or_else(|| PathBuf::from(&gitdir)).mash(dir)),
+        Err(err) if err.code() == ErrorCode::NotFound => Ok(PathBuf::from(gitdir).mash("hooks")),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Returns the location of the hook with the given name creating the hooks directory if needed
+fn hook_path(path: &Path, name: &str) -> Result<PathBuf> {
+    if !HOOKS.contains(&name) {
+        return Err(Error::invalid_hook(name));
+    }
+    let dir = sys::mkdir(hooks_dir(path)?)?;
+    Ok(dir.mash(name))
+}
+
+// Run the hook with the given name if installed, see `hook_run`
+pub(crate) fn run_hook<T: AsRef<str>>(repo: &Repository, name: &str, args: &[T]) -> Result<bool> {
+    if !HOOKS.contains(&name) {
+        return Err(Error::invalid_hook(name));
+    }
+    let hook = repo_hooks_dir(repo)?.mash(name);
+    if !hook.is_file() || !hook.is_exec() {
+        return Ok(false);
+    }
+    let output = Command::new(&hook)
+        .args(args.iter().map(|x| x.as_ref()))
+        .current_dir(repo.workdir().unwrap_or_else(|| repo.path()))
+        .env("GIT_INDEX_FILE", repo.path().mash("index"))
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|x| Error::hook_failed(name, x.to_string()))?;
+    if !output.status.success() {
+        let mut text = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if text.is_empty() {
+            text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        }
+        return Err(Error::hook_failed(name, text));
+    }
+    Ok(true)
+}
+
+// Run the message hooks for a commit being created with the given message in the given mode
+// passing the given source arguments to `prepare-commit-msg` the same as git does. Returns the
+// message as possibly changed by the hooks, cleaned up the same as git does for messages that
+// weren't edited i.e. trailing whitespace and surplus blank lines are removed while lines
+// starting with `#` are kept.
+pub(crate) fn run_message_hooks(repo: &Repository, mode: HookMode, message: &str, source: &[&str]) -> Result<String> {
+    if mode == HookMode::Skip {
+        return Ok(message.to_string());
+    }
+    let file = repo.path().mash("COMMIT_EDITMSG");
+    sys::write(&file, format!("{}\n", message.trim_end()))?;
+    let file = file.to_string_lossy().to_string();
+    let mut args = vec![file.as_str()];
+    args.extend(source);
+    run_hook(repo, "prepare-commit-msg", &args)?;
+    if mode == HookMode::Run {
+        run_hook(repo, "commit-msg", &[&file])?;
+    }
+    let message = sys::readstring(&file)?;
+    Ok(git2::message_prettify(message, None)?.trim_end().to_string())
+}
+
+// Remove the given hook without following it if it is a symlink. Returns true if it existed.
+fn remove_hook(hook: &Path) -> Result<bool> {
+    match fs::symlink_metadata(hook) {
+        Ok(meta) if !meta.is_dir() => {
+            fs::remove_file(hook).map_err(FuError::from)?;
+            Ok(true)
+        },
+        _ => Ok(false),
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_hooks() {
+        let tmpdir = setup("git_hooks");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("repo")).is_ok());
+        let repodir = tmpdir.mash("repo");
+        let repo = git2::Repository::open(&repodir).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        let commit = || Command::new("git").args(["commit", "--allow-empty", "-m", "Empty"]).current_dir(&repodir).output().unwrap().status.success();
+        let names = || git::hooks(&repodir).unwrap().into_iter().map(|x| x.name).collect::<Vec<_>>();
+        assert!(sys::write(repodir.mash(".git/hooks/pre-commit.sample"), "#!/bin/sh\n").is_ok());
+        assert!(names().is_empty());
+
+        // Installed hooks are run by git
+        let hook = git::hook_install(&repodir, "pre-commit", "#!/bin/sh\nexit 1\n").unwrap();
+        assert_eq!(hook, repodir.mash(".git/hooks/pre-commit"));
+        assert_eq!(commit(), false);
+        assert!(git::hook_install(&repodir, "pre-commit", "#!/bin/sh\nexit 0\n").is_ok());
+        assert_eq!(commit(), true);
+        let hooks = git::hooks(&repodir).unwrap();
+        assert_eq!(
+            hooks,
+            vec![git::HookInfo {
+                name: "pre-commit".to_string(),
+                path: hook.clone(),
+                target: None,
+                executable: true
+            }]
+        );
+
+        // Linked hooks share the script and replacing them leaves it as is
+        let shared = sys::mkdir(tmpdir.mash("shared")).unwrap().mash("commit-msg");
+        assert!(sys::write(&shared, "#!/bin/sh\ngrep -q '^Fix' \"$1\"\n").is_ok());
+        assert!(sys::chmod(&shared, 0o755).is_ok());
+        let link = git::hook_link(&repodir, "commit-msg", &shared).unwrap();
+        assert_eq!(git::hooks(&repodir).unwrap()[0].target, Some(shared.clone()));
+        assert_eq!(commit(), false);
+        assert!(git::hook_install(&repodir, "commit-msg", "#!/bin/sh\nexit 0\n").is_ok());
+        assert_eq!(commit(), true);
+        assert_eq!(link.is_symlink(), false);
+        assert!(sys::readstring(&shared).unwrap().contains("grep"));
+        assert!(git::hook_link(&repodir, "commit-msg", &shared).is_ok());
+        assert_eq!(names(), vec!["commit-msg", "pre-commit"]);
+
+        // Removing a link or a dangling link leaves the script
+        assert_eq!(git::hook_remove(&repodir, "commit-msg").unwrap(), true);
+        assert!(shared.exists());
+        assert!(git::hook_link(&repodir, "commit-msg", &shared).is_ok());
+        assert!(sys::remove(&shared).is_ok());
+        assert_eq!(git::hook_remove(&repodir, "commit-msg").unwrap(), true);
+        assert_eq!(git::hook_remove(&repodir, "commit-msg").unwrap(), false);
+        assert_eq!(names(), vec!["pre-commit"]);
+
+        // Hooks path relative to the worktree
+        assert!(git::hooks_path_set(&repodir, Some("hooks")).is_ok());
+        assert_eq!(git::hooks_dir(&repodir).unwrap(), repodir.mash("hooks"));
+        assert!(names().is_empty());
+        assert!(git::hook_install(&repodir, "pre-commit", "#!/bin/sh\nexit 1\n").is_ok());
+        assert!(repodir.mash("hooks/pre-commit").exists());
+        assert_eq!(commit(), false);
+        assert!(git::hooks_path_set(&repodir, None).is_ok());
+        assert!(git::hooks_path_set(&repodir, None).is_ok());
+        assert_eq!(git::hooks_dir(&repodir).unwrap(), repodir.mash(".git/hooks"));
+        assert_eq!(commit(), true);
+
+        // Hooks run directly with arguments from the worktree
+        let hook = git::hook_install(&repodir, "post-checkout", "#!/bin/sh\n[ \"$1\" = foo ] && [ -d .git ] || { echo bad >&2; exit 1; }\n").unwrap();
+        assert_eq!(git::hook_run(&repodir, "post-checkout", &["foo"]).unwrap(), true);
+        let err = git::hook_run(&repodir, "post-checkout", &["bar"]).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::hook_failed("post-checkout", "bad").to_string());
+        assert!(sys::chmod(&hook, 0o644).is_ok());
+        assert_eq!(git::hook_run(&repodir, "post-checkout", &["bar"]).unwrap(), false);
+        assert_eq!(git::hook_run(&repodir, "post-rewrite", &["bar"]).unwrap(), false);
+
+        // Errors
+        let err = git::hook_install(&repodir, "pre-foo", "").unwrap_err();
+        assert_eq!(err.to_string(), git::Error::invalid_hook("pre-foo").to_string());
+        assert!(git::hook_link(&repodir, "pre-commit", tmpdir.mash("missing")).is_err());
+        assert!(git::hook_remove(&repodir, "../config").is_err());
+        assert!(git::hooks(tmpdir.mash("missing")).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
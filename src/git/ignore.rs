@@ -0,0 +1,226 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::Repository;
+
+/// Ignore file managed by `ignore_add`, `ignore_remove` and `ignore_patterns`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum IgnoreFile {
+    /// The `.gitignore` at the top of the worktree which is committed with the repo
+    #[default]
+    Gitignore,
+
+    /// The repo's `info/exclude` which is local to the clone and never committed
+    Exclude,
+}
+
+/// Returns true if the given path, relative to the top of the repo or absolute, matches the repo's
+/// ignore rules from `.gitignore` files, `info/exclude` and `core.excludesFile`, the same as `git
+/// check-ignore --no-index <file>`. Tracked files are reported by their rules too. The path doesn't
+/// need to exist.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_is_ignored_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::ignore_add(&tmpdir, &["target/"], git::IgnoreFile::Gitignore).is_ok());
+/// assert_eq!(git::is_ignored(&tmpdir, "target/debug").unwrap(), true);
+/// assert_eq!(git::is_ignored(&tmpdir, "README.md").unwrap(), false);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn is_ignored<T, U>(path: T, file: U) -> Result<bool>
+where
+    T: AsRef<Path>,
+    U: AsRef<Path>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let workdir = repo.workdir().ok_or_else(|| git2::Error::from_str("cannot check ignore rules of a bare repository"))?;
+    let file = file.as_ref();
+    let file = match file.is_absolute() {
+        true => file.strip_prefix(workdir.abs()?).map_err(|_| git2::Error::from_str(&format!("{} is outside the repository", file.display())))?,
+        false => file,
+    };
+    Ok(repo.is_path_ignored(file)?)
+}
+
+/// Append the given patterns to the given ignore file skipping those it already has, creating it
+/// if needed. Returns the number of patterns added.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_ignore_add_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::ignore_add(&tmpdir, &["*.log", "target/"], git::IgnoreFile::Exclude).unwrap(), 2);
+/// assert_eq!(git::ignore_add(&tmpdir, &["*.log"], git::IgnoreFile::Exclude).unwrap(), 0);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn ignore_add<T, U>(path: T, patterns: &[U], file: IgnoreFile) -> Result<usize>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let file = ignore_file(&repo, file)?;
+    let mut lines = read_lines(&file)?;
+    let mut added = 0;
+    for pattern in patterns.iter().map(|x| x.as_ref().trim()).filter(|x| !x.is_empty()) {
+        if !lines.iter().any(|x| x.trim() == pattern) {
+            lines.push(pattern.to_string());
+            added += 1;
+        }
+    }
+    if added > 0 {
+        write_lines(&file, &lines)?;
+    }
+    Ok(added)
+}
+
+/// Remove the given patterns from the given ignore file leaving its other lines and comments as
+/// they are. Returns the number of lines removed.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_ignore_remove_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::ignore_add(&tmpdir, &["*.log", "target/"], git::IgnoreFile::Gitignore).is_ok());
+/// assert_eq!(git::ignore_remove(&tmpdir, &["*.log"], git::IgnoreFile::Gitignore).unwrap(), 1);
+/// assert_eq!(git::ignore_patterns(&tmpdir, git::IgnoreFile::Gitignore).unwrap(), vec!["target/".to_string()]);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn ignore_remove<T, U>(path: T, patterns: &[U], file: IgnoreFile) -> Result<usize>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let file = ignore_file(&repo, file)?;
+    let lines = read_lines(&file)?;
+    let kept: Vec<String> = lines.iter().filter(|x| !patterns.iter().any(|y| y.as_ref().trim() == x.trim())).cloned().collect();
+    let removed = lines.len() - kept.len();
+    if removed > 0 {
+        write_lines(&file, &kept)?;
+    }
+    Ok(removed)
+}
+
+/// List the patterns of the given ignore file without blank lines and comments. Returns an empty
+/// list if the file doesn't exist.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_ignore_patterns_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::ignore_patterns(&tmpdir, git::IgnoreFile::Gitignore).unwrap().is_empty());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn ignore_patterns<T: AsRef<Path>>(path: T, file: IgnoreFile) -> Result<Vec<String>> {
+    let repo = Repository::open(path.as_ref())?;
+    let file = ignore_file(&repo, file)?;
+    Ok(read_lines(&file)?.into_iter().map(|x| x.trim().to_string()).filter(|x| !x.is_empty() && !x.starts_with('#')).collect())
+}
+
+// Returns the path of the given ignore file
+fn ignore_file(repo: &Repository, file: IgnoreFile) -> Result<PathBuf> {
+    Ok(match file {
+        IgnoreFile::Gitignore => repo.workdir().ok_or_else(|| git2::Error::from_str("cannot ignore in a bare repository"))?.mash(".gitignore"),
+        IgnoreFile::Exclude => repo.path().mash("info/exclude"),
+    })
+}
+
+// Returns the lines of the given file or an empty list if it doesn't exist
+fn read_lines(file: &Path) -> Result<Vec<String>> {
+    Ok(match file.exists() {
+        true => sys::readlines(file)?,
+        false => vec![],
+    })
+}
+
+// Write the given lines to the given file creating its parent directory if needed
+fn write_lines(file: &Path, lines: &[String]) -> Result<()> {
+    if let Some(dir) = file.parent() {
+        sys::mkdir(dir)?;
+    }
+    sys::write(file, lines.iter().map(|x| format!("{}\n", x)).collect::<String>())?;
+    Ok(())
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_ignore() {
+        let tmpdir = setup("git_ignore");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let gitignore = git::IgnoreFile::Gitignore;
+        let exclude = git::IgnoreFile::Exclude;
+
+        // Add to a missing .gitignore skipping duplicates and blanks
+        assert_eq!(git::ignore_patterns(&tmpdir, gitignore).unwrap(), Vec::<String>::new());
+        assert_eq!(git::ignore_add(&tmpdir, &["*.log", "target/", "*.log", " "], gitignore).unwrap(), 2);
+        assert_eq!(sys::readstring(tmpdir.mash(".gitignore")).unwrap(), "*.log\ntarget/\n");
+        assert_eq!(git::is_ignored(&tmpdir, "foo.log").unwrap(), true);
+        assert_eq!(git::is_ignored(&tmpdir, "src/foo.log").unwrap(), true);
+        assert_eq!(git::is_ignored(&tmpdir, tmpdir.mash("target/debug")).unwrap(), true);
+        assert_eq!(git::is_ignored(&tmpdir, "README.md").unwrap(), false);
+        assert!(git::is_ignored(&tmpdir, "/missing/foo.log").is_err());
+
+        // Existing lines and comments are kept and a missing trailing newline is added
+        assert!(sys::write(tmpdir.mash(".gitignore"), "# Build\ntarget/\n\n*.log").is_ok());
+        assert_eq!(git::ignore_add(&tmpdir, &["*.tmp"], gitignore).unwrap(), 1);
+        assert_eq!(sys::readstring(tmpdir.mash(".gitignore")).unwrap(), "# Build\ntarget/\n\n*.log\n*.tmp\n");
+        assert_eq!(
+            git::ignore_patterns(&tmpdir, gitignore).unwrap(),
+            vec![
+                "target/".to_string(),
+                "*.log".to_string(),
+                "*.tmp".to_string()
+            ]
+        );
+
+        // Remove patterns
+        assert_eq!(git::ignore_remove(&tmpdir, &["*.log", "missing"], gitignore).unwrap(), 1);
+        assert_eq!(git::ignore_remove(&tmpdir, &["*.log"], gitignore).unwrap(), 0);
+        assert_eq!(sys::readstring(tmpdir.mash(".gitignore")).unwrap(), "# Build\ntarget/\n\n*.tmp\n");
+        assert_eq!(git::is_ignored(&tmpdir, "foo.log").unwrap(), false);
+
+        // The local exclude file keeps its existing comments
+        assert_eq!(git::ignore_patterns(&tmpdir, exclude).unwrap(), Vec::<String>::new());
+        assert_eq!(git::ignore_add(&tmpdir, &["*.swp"], exclude).unwrap(), 1);
+        assert_eq!(git::is_ignored(&tmpdir, "foo.swp").unwrap(), true);
+        assert!(sys::readstring(tmpdir.mash(".git/info/exclude")).unwrap().starts_with("# File patterns to ignore"));
+        assert_eq!(git::ignore_remove(&tmpdir, &["*.swp"], exclude).unwrap(), 1);
+        assert_eq!(git::is_ignored(&tmpdir, "foo.swp").unwrap(), false);
+
+        // Matches git
+        assert!(git::ignore_add(&tmpdir, &["*.log", "!keep.log"], gitignore).is_ok());
+        for file in &["foo.log", "keep.log", "target", "src/foo.tmp", "README.md"] {
+            let status = std::process::Command::new("git").args(["check-ignore", "-q", "--no-index", file]).current_dir(&tmpdir).status().unwrap();
+            assert_eq!(git::is_ignored(&tmpdir, file).unwrap(), status.success(), "{}", file);
+        }
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,375 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{IndexAddOption, Pathspec, PathspecFlags, Repository, Status};
+
+/// Stage the files matching the given pathspecs, e.g. `src/*.rs`, in the repo's index. New and
+/// modified files are added while deleted files are removed from the index. Ignored files are
+/// skipped.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_stage_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+/// assert!(git::stage(&tmpdir, &["README.md"]).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn stage<T, U>(path: T, pathspecs: &[U]) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let pathspecs: Vec<&str> = pathspecs.iter().map(|x| x.as_ref()).collect();
+    let mut index = repo.index()?;
+    index.add_all(&pathspecs, IndexAddOption::DEFAULT, None)?;
+    index.update_all(&pathspecs, None)?;
+    index.write()?;
+    Ok(())
+}
+
+/// Stage all changes in the repo's worktree including new and deleted files, the same as
+/// `git add --all`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_stage_all_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+/// assert!(git::stage_all(&tmpdir).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn stage_all<T: AsRef<Path>>(path: T) -> Result<()> {
+    stage(path, &["*"])
+}
+
+/// Unstage the files matching the given pathspecs by resetting their index entries to HEAD
+/// leaving the worktree untouched, the same as `git reset -- <pathspecs>`. Newly added files
+/// become untracked again.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_unstage_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+/// assert!(git::stage(&tmpdir, &["README.md"]).is_ok());
+/// assert!(git::unstage(&tmpdir, &["README.md"]).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn unstage<T, U>(path: T, pathspecs: &[U]) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let pathspecs: Vec<&str> = pathspecs.iter().map(|x| x.as_ref()).collect();
+
+    // Without any commits the entries are simply removed
+    let head = repo.head().ok().and_then(|x| x.peel(git2::ObjectType::Commit).ok());
+    repo.reset_default(head.as_ref(), pathspecs.iter())?;
+    Ok(())
+}
+
+/// Remove the tracked files matching the given pathspecs from the repo's index and unless
+/// `cached` is set from the worktree too, the same as `git rm` or `git rm --cached`. As a safety
+/// check removing files from the worktree whose worktree or staged content differs from HEAD is
+/// refused with `Error::DirtyWorktree` unless `force` is set, the same as `git rm -f`, which
+/// discards the uncommitted changes. Returns the paths that were removed or `Error::PathNotFound`
+/// without removing anything if a pathspec doesn't match any tracked file.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_index_remove_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::index_remove(&tmpdir, &["README.md"], true, false).unwrap(), vec![PathBuf::from("README.md")]);
+/// assert_eq!(tmpdir.mash("README.md").exists(), true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn index_remove<T, U>(path: T, pathspecs: &[U], cached: bool, force: bool) -> Result<Vec<PathBuf>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let workdir = repo.workdir().ok_or_else(|| git2::Error::from_str("cannot remove files from a bare repository"))?.to_path_buf();
+    let mut index = repo.index()?;
+    let spec = Pathspec::new(pathspecs.iter().map(|x| x.as_ref()))?;
+    let matches = spec.match_index(&index, PathspecFlags::FIND_FAILURES)?;
+    if let Some(failed) = matches.failed_entries().next() {
+        return Err(Error::path_not_found(String::from_utf8_lossy(failed)));
+    }
+
+    let paths: Vec<PathBuf> = matches.entries().map(|x| PathBuf::from(String::from_utf8_lossy(x).to_string())).collect();
+
+    // Check every file for uncommitted changes before removing anything
+    if !cached && !force {
+        for file in paths.iter() {
+            if repo.status_file(file)? != Status::CURRENT {
+                return Err(Error::dirty_worktree(workdir.mash(file)));
+            }
+        }
+    }
+    for file in paths.iter() {
+        index.remove_path(file)?;
+        if !cached && workdir.mash(file).exists() {
+            sys::remove(workdir.mash(file))?;
+        }
+    }
+    index.write()?;
+    Ok(paths)
+}
+
+/// Move or rename the given tracked file or directory in both the worktree and the repo's index,
+/// the same as `git mv <from> <to>`, so that the change is staged as a rename. Any staged
+/// content is kept. Fails if `from` isn't tracked or `to` already exists.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_index_move_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::index_move(&tmpdir, "README.md", "DOCS.md").is_ok());
+/// assert_eq!(tmpdir.mash("DOCS.md").exists(), true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn index_move<T, U, V>(path: T, from: U, to: V) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<Path>,
+    V: AsRef<Path>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let workdir = repo.workdir().ok_or_else(|| git2::Error::from_str("cannot move files in a bare repository"))?.to_path_buf();
+    let (from, to) = (from.as_ref(), to.as_ref());
+    if workdir.mash(to).exists() {
+        return Err(git2::Error::from_str(&format!("destination already exists: {}", to.display())).into());
+    }
+
+    // Entries of the file itself or of any file under the directory
+    let mut index = repo.index()?;
+    let entries: Vec<_> = index.iter().filter(|x| Path::new(&String::from_utf8_lossy(&x.path).to_string()).starts_with(from)).collect();
+    if entries.is_empty() {
+        return Err(Error::path_not_found(from.display().to_string()));
+    }
+
+    if let Some(dir) = workdir.mash(to).parent() {
+        sys::mkdir(dir)?;
+    }
+    sys::move_p(workdir.mash(from), workdir.mash(to))?;
+    for mut entry in entries {
+        let old = PathBuf::from(String::from_utf8_lossy(&entry.path).to_string());
+        let new = match old.strip_prefix(from) {
+            Ok(rest) if rest != Path::new("") => to.join(rest),
+            _ => to.to_path_buf(),
+        };
+        index.remove_path(&old)?;
+        entry.path = new.to_string()?.into_bytes();
+        index.add(&entry)?;
+    }
+    index.write()?;
+    Ok(())
+}
+
+/// Remove all entries from the repo's index leaving the worktree untouched so that the next
+/// commit starts from an empty tree.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_index_clear_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::index_clear(&tmpdir).is_ok());
+/// assert_eq!(tmpdir.mash("README.md").exists(), true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn index_clear<T: AsRef<Path>>(path: T) -> Result<()> {
+    let repo = Repository::open(path.as_ref())?;
+    let mut index = repo.index()?;
+    index.clear()?;
+    index.write()?;
+    Ok(())
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_stage() {
+        let tmpdir = setup("git_stage");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let staged = |path: &str| {
+            let status = repo.status_file(Path::new(path)).unwrap();
+            status.is_index_new() || status.is_index_modified() || status.is_index_deleted()
+        };
+
+        // Only matching paths are staged
+        assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+        assert!(sys::write(tmpdir.mash("bar"), "bar").is_ok());
+        assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+        assert!(git::stage(&tmpdir, &["foo", "*.md"]).is_ok());
+        assert_eq!(staged("foo"), true);
+        assert_eq!(staged("README.md"), true);
+        assert_eq!(staged("bar"), false);
+
+        // Deletions are staged too
+        assert!(sys::remove(tmpdir.mash("LICENSE")).is_ok());
+        assert!(git::stage_all(&tmpdir).is_ok());
+        assert_eq!(staged("LICENSE"), true);
+        assert_eq!(staged("bar"), true);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_unstage() {
+        let tmpdir = setup("git_unstage");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let codes = || git::status(&tmpdir, &git::StatusOptions::new()).unwrap().iter().map(|x| x.code()).collect::<Vec<_>>();
+
+        assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+        assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+        assert!(sys::remove(tmpdir.mash("LICENSE")).is_ok());
+        assert!(git::stage_all(&tmpdir).is_ok());
+        assert_eq!(codes(), vec!["D.", "M.", "A."]);
+        assert!(git::unstage(&tmpdir, &["*.md", "foo"]).is_ok());
+        assert_eq!(codes(), vec!["D.", ".M", "??"]);
+        assert!(git::unstage(&tmpdir, &["LICENSE"]).is_ok());
+        assert_eq!(codes(), vec![".D", ".M", "??"]);
+        assert_eq!(sys::readstring(tmpdir.mash("README.md")).unwrap(), "foo");
+
+        // Repos without commits
+        let empty = tmpdir.mash("empty");
+        assert!(git2::Repository::init(&empty).is_ok());
+        assert!(sys::write(empty.mash("foo"), "foo").is_ok());
+        assert!(git::stage_all(&empty).is_ok());
+        assert!(git::unstage(&empty, &["foo"]).is_ok());
+        assert!(git2::Repository::open(&empty).unwrap().index().unwrap().is_empty());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_index_remove() {
+        let tmpdir = setup("git_index_remove");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+
+        // Cached removal keeps the files
+        assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+        assert_eq!(git::index_remove(&tmpdir, &["README.md"], true, false).unwrap(), vec![PathBuf::from("README.md")]);
+        assert_eq!(sys::readstring(tmpdir.mash("README.md")).unwrap(), "foo");
+        let entries = git::status(&tmpdir, &git::StatusOptions::new()).unwrap();
+        assert_eq!((entries[0].staged, entries[0].untracked), (git::FileState::Deleted, true));
+
+        // Removal from the worktree with pathspecs
+        let removed = git::index_remove(&tmpdir, &["config/*", "LICENSE"], false, false).unwrap();
+        assert_eq!(
+            removed,
+            vec![
+                PathBuf::from("LICENSE"),
+                PathBuf::from("config/.bashrc"),
+                PathBuf::from("config/.dircolors")
+            ]
+        );
+        assert_eq!(tmpdir.mash("LICENSE").exists(), false);
+        assert_eq!(tmpdir.mash("config/.bashrc").exists(), false);
+
+        // Unmatched pathspecs remove nothing
+        let err = git::index_remove(&tmpdir, &["Dockerfile", "missing"], false, false).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::path_not_found("missing").to_string());
+        assert_eq!(tmpdir.mash("Dockerfile").exists(), true);
+        assert!(git::index_remove(&tmpdir, &["README.md"], false, false).is_err());
+
+        // Files with local or staged changes are only removed when forced
+        assert!(sys::write(tmpdir.mash("Dockerfile"), "foo").is_ok());
+        let err = git::index_remove(&tmpdir, &["Dockerfile"], false, false).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::dirty_worktree(tmpdir.mash("Dockerfile")).to_string());
+        assert_eq!(sys::readstring(tmpdir.mash("Dockerfile")).unwrap(), "foo");
+        assert!(git::stage(&tmpdir, &["Dockerfile"]).is_ok());
+        assert!(git::index_remove(&tmpdir, &["Dockerfile"], false, false).is_err());
+        assert_eq!(tmpdir.mash("Dockerfile").exists(), true);
+        assert_eq!(git::index_remove(&tmpdir, &["Dockerfile"], false, true).unwrap(), vec![PathBuf::from("Dockerfile")]);
+        assert_eq!(tmpdir.mash("Dockerfile").exists(), false);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_index_move() {
+        let tmpdir = setup("git_index_move");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let codes = || git::status(&tmpdir, &git::StatusOptions::new()).unwrap().iter().map(|x| (x.path.to_string().unwrap(), x.code())).collect::<Vec<_>>();
+        let code = |path: &str, code: &str| (path.to_string(), code.to_string());
+
+        // Files are staged as renames keeping unstaged changes
+        assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+        assert!(git::index_move(&tmpdir, "README.md", "docs/README.md").is_ok());
+        assert_eq!(tmpdir.mash("README.md").exists(), false);
+        assert_eq!(sys::readstring(tmpdir.mash("docs/README.md")).unwrap(), "foo");
+        let entries = git::status(&tmpdir, &git::StatusOptions::new()).unwrap();
+        assert_eq!(entries[0].renamed_from, Some(PathBuf::from("README.md")));
+        assert_eq!(codes(), vec![code("docs/README.md", "RM")]);
+
+        // Directories move all their files
+        assert!(git::index_move(&tmpdir, "config", "etc").is_ok());
+        assert!(tmpdir.mash("etc/.bashrc").exists());
+        assert_eq!(
+            codes(),
+            vec![
+                code("docs/README.md", "RM"),
+                code("etc/.bashrc", "R."),
+                code("etc/.dircolors", "R.")
+            ]
+        );
+
+        // Errors
+        assert!(git::index_move(&tmpdir, "missing", "foo").is_err());
+        assert!(git::index_move(&tmpdir, "LICENSE", "Dockerfile").is_err());
+        assert!(tmpdir.mash("LICENSE").exists());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_index_clear() {
+        let tmpdir = setup("git_index_clear");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        assert_eq!(git2::Repository::open(&tmpdir).unwrap().index().unwrap().is_empty(), false);
+        assert!(git::index_clear(&tmpdir).is_ok());
+        assert_eq!(git2::Repository::open(&tmpdir).unwrap().index().unwrap().is_empty(), true);
+        assert_eq!(tmpdir.mash("README.md").exists(), true);
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
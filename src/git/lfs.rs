@@ -0,0 +1,488 @@
+use super::SharedProgress;
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{IndexEntry, IndexEntryExtendedFlag, Repository};
+use std::{
+    cell::RefCell,
+    fmt,
+    fs::Metadata,
+    io::Write,
+    process::{Command, Stdio},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// Pointer files are never bigger than this which saves reading large blobs
+const MAX_POINTER_SIZE: usize = 1024;
+
+// Version line every LFS pointer starts with
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// Pointer stored in git in place of a file's content which is kept in LFS storage
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LfsPointer {
+    pub oid: String, // Sha256 of the content the pointer stands in for
+    pub size: u64,   // Size of the content in bytes
+}
+
+impl LfsPointer {
+    /// Parse the given data as an LFS pointer returning `None` if it isn't one.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let oid = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+    /// let data = format!("version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize 12345\n", oid);
+    /// assert_eq!(git::LfsPointer::parse(data.as_bytes()).unwrap().size, 12345);
+    /// assert_eq!(git::LfsPointer::parse(b"foo"), None);
+    /// ```
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() > MAX_POINTER_SIZE {
+            return None;
+        }
+        let text = std::str::from_utf8(data).ok()?;
+        let mut lines = text.lines();
+        if lines.next()? != POINTER_VERSION {
+            return None;
+        }
+        let (mut oid, mut size) = (None, None);
+        for line in lines {
+            let (key, value) = line.split_once(' ')?;
+            match key {
+                "oid" => oid = value.strip_prefix("sha256:").filter(|x| x.len() == 64 && x.chars().all(|y| y.is_ascii_hexdigit())),
+                "size" => size = value.parse::<u64>().ok(),
+                _ => (),
+            }
+        }
+        Some(Self { oid: oid?.to_string(), size: size? })
+    }
+}
+
+impl fmt::Display for LfsPointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\noid sha256:{}\nsize {}\n", POINTER_VERSION, self.oid, self.size)
+    }
+}
+
+/// File in the worktree that still holds its LFS pointer rather than its content as returned
+/// by `lfs_pointers`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LfsFile {
+    pub path: PathBuf,       // Path of the file relative to the top of the repo
+    pub pointer: LfsPointer, // Pointer the file holds
+}
+
+/// Returns the files in the given repo's worktree that hold LFS pointers rather than their
+/// content, which is what a clone or checkout without git-lfs leaves behind. Paths excluded by
+/// a sparse checkout are skipped.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_lfs_pointers_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::lfs_pointers(&tmpdir).unwrap(), vec![]);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn lfs_pointers<T: AsRef<Path>>(path: T) -> Result<Vec<LfsFile>> {
+    let repo = Repository::open(path.as_ref())?;
+    pointers(&repo)
+}
+
+/// Replace the LFS pointers in the given repo's worktree with their content, the same as
+/// `git lfs pull`. Objects are taken from the repo's LFS storage, then from the LFS storage of
+/// `origin` when it's a local repo, and are otherwise downloaded with the git-lfs binary. The
+/// given callback reports progress as the total and smudged bytes. The index is updated so the
+/// files don't show as modified and the repo's LFS filter is configured for later checkouts if
+/// it isn't already. Returns the paths of the files that were smudged.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_lfs_pull_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::lfs_pull(&tmpdir, |_, _| {}).unwrap(), Vec::<PathBuf>::new());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn lfs_pull<T, F>(path: T, progress: F) -> Result<Vec<PathBuf>>
+where
+    T: AsRef<Path>,
+    F: FnMut(u64, u64),
+{
+    let repo = Repository::open(path.as_ref())?;
+    let progress: SharedProgress = RefCell::new(Box::new(progress));
+    smudge(&repo, Some(&progress))
+}
+
+// Replace the LFS pointers in the given repo's worktree with their content reporting progress
+// through the given callback
+pub(crate) fn smudge(repo: &Repository, progress: Option<&SharedProgress>) -> Result<Vec<PathBuf>> {
+    let files = pointers(repo)?;
+    if files.is_empty() {
+        return Ok(vec![]);
+    }
+    configure_filter(repo)?;
+    let workdir = repo.workdir().ok_or_else(|| git2::Error::from_str("cannot pull lfs objects in a bare repository"))?;
+    let total = files.iter().map(|x| x.pointer.size).sum();
+    let mut cur = 0;
+    if let Some(progress) = progress {
+        (progress.borrow_mut())(total, cur);
+    }
+
+    let mut index = repo.index()?;
+    let mut smudged = vec![];
+    for file in files {
+        let content = object(repo, &file)?;
+        let dst = workdir.mash(&file.path);
+        std::fs::write(&dst, &content).map_err(FuError::from)?;
+
+        // Record the new file stats so git doesn't see a change. Backdating the file keeps it
+        // older than the index as git otherwise rehashes it without the filter and sees a change.
+        let mtime = SystemTime::now() - Duration::from_secs(1);
+        std::fs::OpenOptions::new().write(true).open(&dst).and_then(|x| x.set_modified(mtime)).map_err(FuError::from)?;
+        if let Some(mut entry) = index.get_path(&file.path, 0) {
+            set_stat(&mut entry, &std::fs::metadata(&dst).map_err(FuError::from)?);
+            index.add(&entry)?;
+        }
+        cur += file.pointer.size;
+        if let Some(progress) = progress {
+            (progress.borrow_mut())(total, cur);
+        }
+        smudged.push(file.path);
+    }
+    index.write()?;
+    Ok(smudged)
+}
+
+// Returns the worktree files that hold LFS pointers according to the index
+fn pointers(repo: &Repository) -> Result<Vec<LfsFile>> {
+    let workdir = match repo.workdir() {
+        Some(workdir) => workdir,
+        None => return Ok(vec![]),
+    };
+    let mut files = vec![];
+    for entry in repo.index()?.iter() {
+        let skip = IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended).is_skip_worktree();
+        if skip || entry.file_size as usize > MAX_POINTER_SIZE || (entry.flags >> 12) & 0x3 != 0 {
+            continue;
+        }
+        let path = PathBuf::from(String::from_utf8_lossy(&entry.path).to_string());
+        let blob = match repo.find_blob(entry.id) {
+            Ok(blob) => blob,
+            Err(_) => continue,
+        };
+        if let Some(pointer) = LfsPointer::parse(blob.content()) {
+            if std::fs::read(workdir.mash(&path)).map(|x| LfsPointer::parse(&x).is_some()).unwrap_or(false) {
+                files.push(LfsFile { path, pointer });
+            }
+        }
+    }
+    Ok(files)
+}
+
+// Returns the content of the given file's LFS object storing it in the repo's LFS storage if
+// it had to be copied or downloaded
+fn object(repo: &Repository, file: &LfsFile) -> Result<Vec<u8>> {
+    let path = object_path(repo, &file.pointer.oid)?;
+    if let Some(content) = read_object(&path, &file.pointer) {
+        return Ok(content);
+    }
+
+    // Copy the object from a local origin's storage
+    let content = match local_origin(repo).and_then(|x| read_object(&object_path(&x, &file.pointer.oid).ok()?, &file.pointer)) {
+        Some(content) => content,
+        None => download(repo, file)?,
+    };
+    std::fs::create_dir_all(path.dir()?).map_err(FuError::from)?;
+    std::fs::write(&path, &content).map_err(FuError::from)?;
+    Ok(content)
+}
+
+// Returns the content of the given LFS object if it's stored at the given path with the size
+// and sha256 the pointer gives. Corrupt objects are treated as missing.
+fn read_object(path: &Path, pointer: &LfsPointer) -> Option<Vec<u8>> {
+    std::fs::read(path).ok().filter(|x| x.len() as u64 == pointer.size && sha256(x) == pointer.oid)
+}
+
+// Returns the path of the given object in the repo's LFS storage which is `lfs/objects` in the
+// git dir unless `lfs.storage` is configured
+fn object_path(repo: &Repository, oid: &str) -> Result<PathBuf> {
+    let storage = match repo.config()?.get_path("lfs.storage") {
+        Ok(storage) if storage.is_absolute() => storage,
+        Ok(storage) => repo.path().mash(storage),
+        Err(_) => repo.path().mash("lfs"),
+    };
+    Ok(storage.mash("objects").mash(&oid[0..2]).mash(&oid[2..4]).mash(oid))
+}
+
+// Returns the repo `origin` points to if it's on the local filesystem
+fn local_origin(repo: &Repository) -> Option<Repository> {
+    let remote = repo.find_remote("origin").ok()?;
+    let url = remote.url()?;
+    let path = url.strip_prefix("file://").unwrap_or(url);
+    if path.contains("://") {
+        return None;
+    }
+    Repository::open(path).ok()
+}
+
+// Download the given file's LFS object with the git-lfs binary which is given the pointer and
+// returns the content
+fn download(repo: &Repository, file: &LfsFile) -> Result<Vec<u8>> {
+    let workdir = repo.workdir().unwrap_or_else(|| repo.path());
+    let mut child = Command::new("git")
+        .args(["lfs", "smudge", "--"])
+        .arg(&file.path)
+        .current_dir(workdir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|x| Error::lfs_failed(&file.path, format!("git-lfs: {}", x)))?;
+    // Writing fails if git-lfs exits early which is reported by its status instead
+    let _ = child.stdin.take().unwrap().write_all(file.pointer.to_string().as_bytes());
+    let output = child.wait_with_output().map_err(|x| Error::lfs_failed(&file.path, format!("git-lfs: {}", x)))?;
+    if !output.status.success() {
+        return Err(Error::lfs_failed(&file.path, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    if output.stdout.len() as u64 != file.pointer.size {
+        return Err(Error::lfs_failed(&file.path, format!("expected {} bytes but got {}", file.pointer.size, output.stdout.len())));
+    }
+    let oid = sha256(&output.stdout);
+    if oid != file.pointer.oid {
+        return Err(Error::lfs_failed(&file.path, format!("expected sha256 {} but got {}", file.pointer.oid, oid)));
+    }
+    Ok(output.stdout)
+}
+
+// Returns the hex sha256 of the given data which LFS identifies objects by
+fn sha256(data: &[u8]) -> String {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+    ];
+    let mut hash: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+
+    // Pad with a one bit, zeros and the length in bits to a multiple of 64 bytes
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    padded.resize((padded.len() + 8).div_ceil(64) * 64 - 8, 0);
+    padded.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for chunk in padded.chunks(64) {
+        let mut w = [0u32; 64];
+        for i in 0..64 {
+            w[i] = match i {
+                0..=15 => u32::from_be_bytes([chunk[i * 4], chunk[i * 4 + 1], chunk[i * 4 + 2], chunk[i * 4 + 3]]),
+                _ => {
+                    let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+                    let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+                    w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1)
+                },
+            };
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = hash;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (x, y) in hash.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *x = x.wrapping_add(y);
+        }
+    }
+    hash.iter().map(|x| format!("{:08x}", x)).collect()
+}
+
+// Configure the LFS filter driver the same as `git lfs install --local` unless it already is
+fn configure_filter(repo: &Repository) -> Result<()> {
+    let mut config = repo.config()?;
+    if config.get_string("filter.lfs.process").is_ok() || config.get_string("filter.lfs.smudge").is_ok() {
+        return Ok(());
+    }
+    config.set_str("filter.lfs.clean", "git-lfs clean -- %f")?;
+    config.set_str("filter.lfs.smudge", "git-lfs smudge -- %f")?;
+    config.set_str("filter.lfs.process", "git-lfs filter-process")?;
+    config.set_bool("filter.lfs.required", true)?;
+    Ok(())
+}
+
+// Update the given index entry with the given file stats
+fn set_stat(entry: &mut IndexEntry, meta: &Metadata) {
+    entry.file_size = meta.len() as u32;
+    if let Ok(Ok(mtime)) = meta.modified().map(|x| x.duration_since(UNIX_EPOCH)) {
+        entry.mtime = git2::IndexTime::new(mtime.as_secs() as i32, mtime.subsec_nanos());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        entry.ctime = git2::IndexTime::new(meta.ctime() as i32, meta.ctime_nsec() as u32);
+        entry.dev = meta.dev() as u32;
+        entry.ino = meta.ino() as u32;
+        entry.uid = meta.uid();
+        entry.gid = meta.gid();
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::{
+        cell::RefCell,
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Returns the output of the given git command
+    fn git(path: &Path, args: &[&str]) -> String {
+        let output = Command::new("git").args(args).current_dir(path).output().unwrap();
+        assert_eq!(output.status.success(), true, "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    // Returns a pointer for the given content
+    fn pointer(content: &str) -> git::LfsPointer {
+        let mut child = Command::new("sha256sum").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+        child.stdin.take().unwrap().write_all(content.as_bytes()).unwrap();
+        let output = String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap();
+        git::LfsPointer { oid: output[..64].to_string(), size: content.len() as u64 }
+    }
+
+    // Store the given content in the repo's LFS storage
+    fn store(path: &Path, pointer: &git::LfsPointer, content: &str) {
+        let oid = &pointer.oid;
+        let dir = sys::mkdir(path.mash(".git/lfs/objects").mash(&oid[0..2]).mash(&oid[2..4])).unwrap();
+        assert!(sys::write(dir.mash(oid), content).is_ok());
+    }
+
+    #[test]
+    fn test_lfs_pointer() {
+        let data = format!("version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize 3\n", "a".repeat(64));
+        let parsed = git::LfsPointer::parse(data.as_bytes()).unwrap();
+        assert_eq!(parsed, git::LfsPointer { oid: "a".repeat(64), size: 3 });
+        assert_eq!(parsed.to_string(), data);
+
+        // Not pointers
+        assert_eq!(git::LfsPointer::parse(b""), None);
+        assert_eq!(git::LfsPointer::parse(data.replace("size 3\n", "").as_bytes()), None);
+        assert_eq!(git::LfsPointer::parse(data.replace("sha256:a", "sha256:z").as_bytes()), None);
+        assert_eq!(git::LfsPointer::parse(data.replace("size 3", "size foo").as_bytes()), None);
+        assert_eq!(git::LfsPointer::parse(data.replace("v1", "v2").as_bytes()), None);
+        assert_eq!(git::LfsPointer::parse(format!("{}{}", data, "x".repeat(1024)).as_bytes()), None);
+    }
+
+    #[test]
+    fn test_lfs() {
+        let tmpdir = setup("git_lfs");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let src = tmpdir.mash("src");
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+
+        // Commit pointers the way git-lfs does with the content in LFS storage
+        let (foo, bar) = (pointer("foo content"), pointer("bar"));
+        assert!(sys::write(src.mash(".gitattributes"), "*.bin filter=lfs diff=lfs merge=lfs -text\n").is_ok());
+        assert!(sys::write(src.mash("foo.bin"), foo.to_string()).is_ok());
+        assert!(sys::write(sys::mkdir(src.mash("data")).unwrap().mash("bar.bin"), bar.to_string()).is_ok());
+        assert!(git::CommitBuilder::new(&src).stage_all(true).author("foo", "foo@example.com").message("Add lfs files").commit().is_ok());
+        store(&src, &foo, "foo content");
+        store(&src, &bar, "bar");
+        let files = git::lfs_pointers(&src).unwrap();
+        assert_eq!(files, vec![
+            git::LfsFile { path: PathBuf::from("data/bar.bin"), pointer: bar.clone() },
+            git::LfsFile { path: PathBuf::from("foo.bin"), pointer: foo.clone() },
+        ]);
+
+        // Clones leave pointers in place unless LFS is enabled
+        let plain = tmpdir.mash("plain");
+        assert!(git::Repo::new(&plain).unwrap().url(src.to_string().unwrap()).clone().is_ok());
+        assert_eq!(sys::readstring(plain.mash("foo.bin")).unwrap(), foo.to_string());
+        assert_eq!(git::lfs_pointers(&plain).unwrap(), files);
+
+        // Pull the objects from the local origin reporting progress
+        let calls = RefCell::new(vec![]);
+        let smudged = git::lfs_pull(&plain, |total, cur| calls.borrow_mut().push((total, cur))).unwrap();
+        assert_eq!(smudged, vec![PathBuf::from("data/bar.bin"), PathBuf::from("foo.bin")]);
+        assert_eq!(calls.into_inner(), vec![(14, 0), (14, 3), (14, 14)]);
+        assert_eq!(sys::readstring(plain.mash("foo.bin")).unwrap(), "foo content");
+        assert_eq!(sys::readstring(plain.mash("data/bar.bin")).unwrap(), "bar");
+        assert!(plain.mash(".git/lfs/objects").mash(&foo.oid[0..2]).mash(&foo.oid[2..4]).mash(&foo.oid).exists());
+        assert_eq!(git::lfs_pointers(&plain).unwrap(), vec![]);
+        assert_eq!(git::lfs_pull(&plain, |_, _| {}).unwrap(), Vec::<PathBuf>::new());
+        assert_eq!(git(&plain, &["config", "filter.lfs.process"]).trim(), "git-lfs filter-process");
+
+        // Smudged files don't show as changed
+        assert!(git::status(&plain, &git::StatusOptions::new()).unwrap().is_empty());
+        assert_eq!(git(&plain, &["diff-files", "--name-only"]), "");
+
+        // Clone with LFS enabled
+        let dst = tmpdir.mash("dst");
+        let calls = RefCell::new(vec![]);
+        let repo = git::Repo::new(&dst).unwrap().url(src.to_string().unwrap()).lfs(true);
+        assert_eq!(repo.lfs_val(), true);
+        assert!(repo.lfs_progress(|total, cur| calls.borrow_mut().push((total, cur))).clone().is_ok());
+        assert_eq!(calls.into_inner().last(), Some(&(14, 14)));
+        assert_eq!(sys::readstring(dst.mash("foo.bin")).unwrap(), "foo content");
+
+        // Objects not matching their sha256 are rejected in favor of the origin's
+        let content = "x".repeat(64 * 3 + 55);
+        let big = pointer(&content);
+        assert!(sys::write(src.mash("big.bin"), big.to_string()).is_ok());
+        assert!(git::CommitBuilder::new(&src).stage_all(true).author("foo", "foo@example.com").message("Add big").commit().is_ok());
+        store(&src, &big, &content);
+        assert!(git::Repo::new(&plain).unwrap().url(src.to_string().unwrap()).update().is_ok());
+        store(&plain, &big, &"y".repeat(content.len()));
+        assert_eq!(git::lfs_pull(&plain, |_, _| {}).unwrap(), vec![PathBuf::from("big.bin")]);
+        assert_eq!(sys::readstring(plain.mash("big.bin")).unwrap(), content);
+        let stored = plain.mash(".git/lfs/objects").mash(&big.oid[0..2]).mash(&big.oid[2..4]).mash(&big.oid);
+        assert_eq!(sys::readstring(&stored).unwrap(), content);
+        store(&src, &big, &"y".repeat(content.len()));
+        store(&plain, &big, &"y".repeat(content.len()));
+        assert!(sys::write(plain.mash("big.bin"), big.to_string()).is_ok());
+        let err = git::lfs_pull(&plain, |_, _| {}).unwrap_err();
+        assert!(err.to_string().starts_with("failed to fetch lfs object for big.bin: "), "{}", err);
+        assert_eq!(sys::readstring(plain.mash("big.bin")).unwrap(), big.to_string());
+        store(&src, &big, &content);
+
+        // Missing objects are downloaded with git-lfs which fails without a server
+        let missing = pointer("missing");
+        assert!(sys::write(src.mash("missing.bin"), missing.to_string()).is_ok());
+        assert!(git::CommitBuilder::new(&src).stage_all(true).author("foo", "foo@example.com").message("Add missing").commit().is_ok());
+        assert!(git::Repo::new(&dst).unwrap().url(src.to_string().unwrap()).lfs(true).update().is_err());
+        assert_eq!(sys::readstring(dst.mash("missing.bin")).unwrap(), missing.to_string());
+        let err = git::lfs_pull(&dst, |_, _| {}).unwrap_err();
+        assert!(err.to_string().starts_with("failed to fetch lfs object for missing.bin: "));
+
+        // Errors
+        assert!(git::lfs_pointers(tmpdir.mash("missing")).is_err());
+        assert!(git::lfs_pull(tmpdir.mash("missing"), |_, _| {}).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,537 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{Commit, Delta, DiffFindOptions, DiffOptions, Oid, Repository, Sort};
+
+/// Commit details as returned by `log`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommitInfo {
+    pub oid: Oid,          // Id of the commit
+    pub parents: Vec<Oid>, // Ids of the commit's parents
+    pub author: String,    // Author's name
+    pub email: String,     // Author's email
+    pub time: i64,         // Author time in seconds since the epoch
+    pub committer: String, // Committer's name
+    pub summary: String,   // First line of the message
+    pub message: String,   // Full commit message
+}
+
+impl CommitInfo {
+    // Create the commit details from the given commit
+    pub(crate) fn from_commit(commit: &Commit) -> Self {
+        Self {
+            oid: commit.id(),
+            parents: commit.parent_ids().collect(),
+            author: commit.author().name().unwrap_or_default().to_string(),
+            email: commit.author().email().unwrap_or_default().to_string(),
+            time: commit.author().when().seconds(),
+            committer: commit.committer().name().unwrap_or_default().to_string(),
+            summary: commit.summary().unwrap_or_default().to_string(),
+            message: commit.message().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// HEAD commit details as returned by `head_info`
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeadInfo {
+    pub oid: Oid,                // Id of the commit
+    pub short: String,           // Abbreviated id of the commit e.g. `3a5e2f1`
+    pub branch: Option<String>,  // Branch checked out or None when HEAD is detached
+    pub summary: String,         // First line of the message
+    pub message: String,         // Full commit message
+    pub author: String,          // Author's name
+    pub author_email: String,    // Author's email
+    pub author_time: i64,        // Author time in seconds since the epoch
+    pub committer: String,       // Committer's name
+    pub committer_email: String, // Committer's email
+    pub commit_time: i64,        // Commit time in seconds since the epoch
+    pub parents: usize,          // Number of parents
+}
+
+/// Order in which `log` returns commits
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LogOrder {
+    /// Newest commits first by commit time, the same as `git log --date-order`
+    #[default]
+    Date,
+
+    /// Parents are never shown before all of their children, the same as `git log --topo-order`
+    Topo,
+}
+
+/// Options for selecting commits with `log`. By default all commits reachable from HEAD are
+/// returned newest first.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let opts = git::LogOptions::new().since("3.11").until("master").path("README.md").author("foo").max_count(10);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LogOptions {
+    since: Option<String>,    // Exclude commits reachable from this revision
+    until: Option<String>,    // Start from this revision instead of HEAD
+    paths: Vec<String>,       // Only commits changing files matching these pathspecs
+    author: Option<String>,   // Only commits with an author name or email containing this
+    max_count: Option<usize>, // Return at most this many commits
+    order: LogOrder,          // Order to return commits in
+    follow: bool,             // Continue a single file's history across renames
+    similarity: Option<u16>,  // Similarity percentage for a file to be considered renamed
+}
+
+impl LogOptions {
+    /// Create new log options selecting all commits reachable from HEAD.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::LogOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Exclude commits reachable from the given revision, i.e. the start of a `since..until`
+    /// range.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::LogOptions::new().since("3.11");
+    /// ```
+    pub fn since<T: AsRef<str>>(mut self, rev: T) -> Self {
+        self.since = Some(rev.as_ref().to_string());
+        self
+    }
+
+    /// Start from the given revision rather than HEAD, i.e. the end of a `since..until` range.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::LogOptions::new().until("master");
+    /// ```
+    pub fn until<T: AsRef<str>>(mut self, rev: T) -> Self {
+        self.until = Some(rev.as_ref().to_string());
+        self
+    }
+
+    /// Only select commits changing files matching the given pathspec. May be called multiple
+    /// times to select commits changing any of the pathspecs.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::LogOptions::new().path("README.md").path("src/*.rs");
+    /// ```
+    pub fn path<T: AsRef<str>>(mut self, pathspec: T) -> Self {
+        self.paths.push(pathspec.as_ref().to_string());
+        self
+    }
+
+    /// Only select commits whose author name or email contains the given value ignoring case.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::LogOptions::new().author("foo@example.com");
+    /// ```
+    pub fn author<T: AsRef<str>>(mut self, author: T) -> Self {
+        self.author = Some(author.as_ref().to_lowercase());
+        self
+    }
+
+    /// Return at most the given number of commits.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::LogOptions::new().max_count(10);
+    /// ```
+    pub fn max_count(mut self, count: usize) -> Self {
+        self.max_count = Some(count);
+        self
+    }
+
+    /// Set the order to return commits in.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::LogOptions::new().order(git::LogOrder::Topo);
+    /// ```
+    pub fn order(mut self, order: LogOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Set to continue the history of a single file under its previous name when renamed, the
+    /// same as `git log --follow`. Only applies when a single path is given.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::LogOptions::new().path("README.md").follow(true);
+    /// ```
+    pub fn follow(mut self, yes: bool) -> Self {
+        self.follow = yes;
+        self
+    }
+
+    /// Set the percentage of a file's content that must be unchanged for it to be considered
+    /// renamed when following, the same as `git log --follow -M<n>%`. Defaults to 50 and is
+    /// capped at 100.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::LogOptions::new().path("README.md").follow(true).similarity(90);
+    /// ```
+    pub fn similarity(mut self, percent: u16) -> Self {
+        self.similarity = Some(percent.min(100));
+        self
+    }
+
+    // Returns true if the given commit's author matches the author filter
+    fn author_matches(&self, commit: &Commit) -> bool {
+        match &self.author {
+            Some(author) => {
+                let sig = commit.author();
+                let name = sig.name().unwrap_or_default().to_lowercase();
+                let email = sig.email().unwrap_or_default().to_lowercase();
+                name.contains(author) || email.contains(author)
+            },
+            None => true,
+        }
+    }
+
+    // Returns true if the given commit changes files matching the path filter compared to every
+    // one of its parents, the same way git simplifies history for path limited logs
+    fn path_matches(&self, repo: &Repository, commit: &Commit) -> Result<bool> {
+        if self.paths.is_empty() {
+            return Ok(true);
+        }
+        let mut opts = DiffOptions::new();
+        for path in &self.paths {
+            opts.pathspec(path);
+        }
+        let tree = commit.tree()?;
+        if commit.parent_count() == 0 {
+            return Ok(repo.diff_tree_to_tree(None, Some(&tree), Some(&mut opts))?.deltas().len() > 0);
+        }
+        for parent in commit.parents() {
+            if repo.diff_tree_to_tree(Some(&parent.tree()?), Some(&tree), Some(&mut opts))?.deltas().len() == 0 {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Returns true if the given commit changes the followed file compared to every one of its
+    // parents updating the file to its previous name if the commit renamed it
+    fn follow_matches(&self, repo: &Repository, commit: &Commit, current: &mut PathBuf) -> Result<bool> {
+        let tree = commit.tree()?;
+
+        // Root commits changed the file if they contain it
+        if commit.parent_count() == 0 {
+            return Ok(tree.get_path(current).is_ok());
+        }
+
+        // Otherwise the file must differ from every parent tracking where it was renamed from
+        let mut findopts = DiffFindOptions::new();
+        findopts.renames(true).rename_threshold(self.similarity.unwrap_or(50));
+        let mut renamed = None;
+        for parent in commit.parents() {
+            let mut diff = repo.diff_tree_to_tree(Some(&parent.tree()?), Some(&tree), None)?;
+            diff.find_similar(Some(&mut findopts))?;
+            match diff.deltas().find(|x| x.new_file().path() == Some(current.as_path())) {
+                Some(delta) => {
+                    if delta.status() == Delta::Renamed {
+                        renamed = delta.old_file().path().map(|x| x.to_path_buf());
+                    }
+                },
+                None => return Ok(false),
+            }
+        }
+        if let Some(old) = renamed {
+            *current = old;
+        }
+        Ok(true)
+    }
+}
+
+/// Returns the commits selected by the given options, newest first by default. This is the
+/// building block for changelog style tooling.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_log_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let commits = git::log(&tmpdir, &git::LogOptions::new().max_count(1)).unwrap();
+/// assert_eq!(commits[0].summary, "Use the workflow name for the badge");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn log<T: AsRef<Path>>(path: T, opts: &LogOptions) -> Result<Vec<CommitInfo>> {
+    let repo = Repository::open(path.as_ref())?;
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(match opts.order {
+        LogOrder::Date => Sort::TIME,
+        LogOrder::Topo => Sort::TOPOLOGICAL | Sort::TIME,
+    })?;
+    match &opts.until {
+        Some(rev) => walk.push(repo.revparse_single(rev)?.peel_to_commit()?.id())?,
+        None => walk.push_head()?,
+    }
+    if let Some(rev) = &opts.since {
+        walk.hide(repo.revparse_single(rev)?.peel_to_commit()?.id())?;
+    }
+
+    // A single followed file is tracked under its previous names
+    let mut followed = match opts.paths.as_slice() {
+        [path] if opts.follow => Some(PathBuf::from(path)),
+        _ => None,
+    };
+    let mut commits = vec![];
+    for oid in walk {
+        if opts.max_count.map(|x| commits.len() >= x).unwrap_or(false) {
+            break;
+        }
+        let commit = repo.find_commit(oid?)?;
+        let changed = match followed.as_mut() {
+            Some(current) => opts.follow_matches(&repo, &commit, current)?,
+            None => opts.path_matches(&repo, &commit)?,
+        };
+        if changed && opts.author_matches(&commit) {
+            commits.push(CommitInfo::from_commit(&commit));
+        }
+    }
+    Ok(commits)
+}
+
+/// Returns the details of the HEAD commit for status lines and the like.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_head_info_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let info = git::head_info(&tmpdir).unwrap();
+/// assert_eq!(info.branch, Some("master".to_string()));
+/// assert_eq!(info.summary, "Use the workflow name for the badge");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn head_info<T: AsRef<Path>>(path: T) -> Result<HeadInfo> {
+    let repo = Repository::open(path.as_ref())?;
+    let head = repo.head()?;
+    let commit = head.peel_to_commit()?;
+    let short = commit.as_object().short_id()?;
+    let (author, committer) = (commit.author(), commit.committer());
+    Ok(HeadInfo {
+        oid: commit.id(),
+        short: short.as_str().unwrap_or_default().to_string(),
+        branch: if head.is_branch() { head.shorthand().map(|x| x.to_string()) } else { None },
+        summary: commit.summary().unwrap_or_default().to_string(),
+        message: commit.message().unwrap_or_default().to_string(),
+        author: author.name().unwrap_or_default().to_string(),
+        author_email: author.email().unwrap_or_default().to_string(),
+        author_time: author.when().seconds(),
+        committer: committer.name().unwrap_or_default().to_string(),
+        committer_email: committer.email().unwrap_or_default().to_string(),
+        commit_time: commit.time().seconds(),
+        parents: commit.parent_count(),
+    })
+}
+
+/// Returns the commits that changed the given file, relative to the repo root, newest first.
+/// When `follow` is set renames are detected and the file's history is continued under its
+/// previous name the same as `git log --follow`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_file_history_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let commits = git::file_history(&tmpdir, "README.md", true).unwrap();
+/// assert_eq!(commits[0].summary, "Use the workflow name for the badge");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn file_history<T, U>(path: T, file: U, follow: bool) -> Result<Vec<CommitInfo>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    log(path, &LogOptions::new().path(file).follow(follow))
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Returns the commit ids git itself returns for the given log arguments
+    fn git_log(path: &Path, args: &[&str]) -> Vec<String> {
+        let output = Command::new("git").arg("log").arg("--format=%H").args(args).current_dir(path).output().unwrap();
+        assert_eq!(output.status.success(), true);
+        String::from_utf8(output.stdout).unwrap().lines().map(|x| x.to_string()).collect()
+    }
+
+    // Returns the commit ids of the given commits
+    fn oids(commits: &[git::CommitInfo]) -> Vec<String> {
+        commits.iter().map(|x| x.oid.to_string()).collect()
+    }
+
+    #[test]
+    fn test_log() {
+        let tmpdir = setup("git_log");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+
+        // All commits from HEAD
+        let commits = git::log(&tmpdir, &git::LogOptions::new()).unwrap();
+        assert_eq!(commits.len(), 38);
+        assert_eq!(oids(&commits), git_log(&tmpdir, &["--date-order"]));
+        assert_eq!(commits[0].summary, "Use the workflow name for the badge");
+        assert_eq!(commits[37].parents.len(), 0);
+
+        // Topological order
+        let commits = git::log(&tmpdir, &git::LogOptions::new().order(git::LogOrder::Topo)).unwrap();
+        assert_eq!(oids(&commits), git_log(&tmpdir, &["--topo-order"]));
+
+        // Ranges and max count
+        let commits = git::log(&tmpdir, &git::LogOptions::new().since("HEAD~5")).unwrap();
+        assert_eq!(oids(&commits), git_log(&tmpdir, &["HEAD~5..HEAD"]));
+        let commits = git::log(&tmpdir, &git::LogOptions::new().since("HEAD~5").until("HEAD~2")).unwrap();
+        assert_eq!(oids(&commits), git_log(&tmpdir, &["HEAD~5..HEAD~2"]));
+        let commits = git::log(&tmpdir, &git::LogOptions::new().max_count(3)).unwrap();
+        assert_eq!(oids(&commits), git_log(&tmpdir, &["-3"]));
+
+        // Path and author filters
+        let commits = git::log(&tmpdir, &git::LogOptions::new().path("README.md")).unwrap();
+        assert_eq!(commits.is_empty(), false);
+        assert_eq!(oids(&commits), git_log(&tmpdir, &["--", "README.md"]));
+        let commits = git::log(&tmpdir, &git::LogOptions::new().path(".github/*").path("Dockerfile").max_count(2)).unwrap();
+        assert_eq!(oids(&commits), git_log(&tmpdir, &["-2", "--", ".github", "Dockerfile"]));
+        let author = commits[0].author.clone();
+        let commits = git::log(&tmpdir, &git::LogOptions::new().author(author.to_uppercase())).unwrap();
+        assert_eq!(commits.is_empty(), false);
+        assert_eq!(commits.iter().all(|x| x.author == author), true);
+        assert_eq!(git::log(&tmpdir, &git::LogOptions::new().author("missing")).unwrap().is_empty(), true);
+
+        // Errors
+        assert!(git::log(&tmpdir, &git::LogOptions::new().until("missing")).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_head_info() {
+        let tmpdir = setup("git_head_info");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let field = |format: &str| {
+            let output = Command::new("git").args(["log", "-1", &format!("--format={}", format)]).current_dir(&tmpdir).output().unwrap();
+            String::from_utf8(output.stdout).unwrap().trim_end_matches('\n').to_string()
+        };
+
+        // Matches git
+        let info = git::head_info(&tmpdir).unwrap();
+        assert_eq!(info.oid.to_string(), field("%H"));
+        assert_eq!(info.short, field("%h"));
+        assert_eq!(info.branch, Some("master".to_string()));
+        assert_eq!(info.summary, field("%s"));
+        assert_eq!(info.message.trim_end(), field("%B").trim_end());
+        assert_eq!(info.author, field("%an"));
+        assert_eq!(info.author_email, field("%ae"));
+        assert_eq!(info.author_time.to_string(), field("%at"));
+        assert_eq!(info.committer, field("%cn"));
+        assert_eq!(info.committer_email, field("%ce"));
+        assert_eq!(info.commit_time.to_string(), field("%ct"));
+        assert_eq!(info.parents, 1);
+
+        // Detached
+        assert!(git::Repo::new(&tmpdir).unwrap().checkout("HEAD~1", false).is_ok());
+        let info = git::head_info(&tmpdir).unwrap();
+        assert_eq!(info.branch, None);
+        assert_eq!(info.oid.to_string(), field("%H"));
+
+        // Unborn
+        let empty = tmpdir.mash("empty");
+        git2::Repository::init(&empty).unwrap();
+        assert!(git::head_info(&empty).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_file_history() {
+        let tmpdir = setup("git_file_history");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let readme = git::file_history(&tmpdir, "README.md", false).unwrap();
+        assert_eq!(oids(&readme), git_log(&tmpdir, &["--", "README.md"]));
+        assert_eq!(oids(&git::file_history(&tmpdir, "README.md", true).unwrap()), oids(&readme));
+
+        // Rename then modify the readme
+        assert!(sys::move_p(tmpdir.mash("README.md"), tmpdir.mash("DOCS.md")).is_ok());
+        let rename = git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Rename readme").commit().unwrap();
+        let mut data = sys::readstring(tmpdir.mash("DOCS.md")).unwrap();
+        data.push_str("\nfoo\n");
+        assert!(sys::write(tmpdir.mash("DOCS.md"), &data).is_ok());
+        let modify = git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Update docs").commit().unwrap();
+
+        // Without following only the new name's history
+        let commits = git::file_history(&tmpdir, "DOCS.md", false).unwrap();
+        assert_eq!(commits.iter().map(|x| x.oid).collect::<Vec<_>>(), vec![modify, rename]);
+
+        // Following continues with the old name's history
+        let commits = git::file_history(&tmpdir, "DOCS.md", true).unwrap();
+        assert_eq!(commits.len(), readme.len() + 2);
+        assert_eq!(oids(&commits[2..]), oids(&readme));
+        assert_eq!(oids(&commits), git_log(&tmpdir, &["--follow", "--", "DOCS.md"]));
+
+        // Renames with changes are only followed above the similarity threshold
+        let content = (1..=10).map(|x| format!("line {}\n", x)).collect::<String>();
+        assert!(sys::write(tmpdir.mash("foo"), &content).is_ok());
+        let add = git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add foo").commit().unwrap();
+        assert!(sys::remove(tmpdir.mash("foo")).is_ok());
+        assert!(sys::write(tmpdir.mash("bar"), content.replace("line 10", "line ten")).is_ok());
+        let rename = git::CommitBuilder::new(&tmpdir).stage_all(true).author("bar", "bar@example.com").message("Rename foo").commit().unwrap();
+        let oids = |opts: git::LogOptions| git::log(&tmpdir, &opts).unwrap().iter().map(|x| x.oid).collect::<Vec<_>>();
+        assert_eq!(oids(git::LogOptions::new().path("bar").follow(true)), vec![rename, add]);
+        assert_eq!(oids(git::LogOptions::new().path("bar").follow(true).similarity(95)), vec![rename]);
+        assert_eq!(oids(git::LogOptions::new().path("bar").follow(true).author("foo")), vec![add]);
+        assert_eq!(oids(git::LogOptions::new().path("bar").follow(true).max_count(1)), vec![rename]);
+
+        // Missing file has no history
+        assert_eq!(git::file_history(&tmpdir, "missing", true).unwrap().is_empty(), true);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,120 @@
+use super::{remote_callbacks, Net, Repo};
+use crate::error::*;
+use git2::{Direction, Oid, Remote};
+
+/// Ref as listed by `ls_remote`
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemoteRef {
+    pub name: String,           // Full ref name e.g. `refs/heads/master` or `HEAD`
+    pub oid: Oid,               // Id of the object the ref points to
+    pub symref: Option<String>, // Target of a symbolic ref e.g. `refs/heads/master` for `HEAD`
+}
+
+/// List the refs of the remote repo at the given url along with their ids without needing a
+/// local repo, the same as `git ls-remote <url>`. The default credential chain is used, see
+/// `Repo::ls_remote` to use custom network settings.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_ls_remote_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let refs = git::ls_remote(tmpdir.to_string().unwrap()).unwrap();
+/// assert!(refs.iter().any(|x| x.name == "refs/heads/master"));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn ls_remote<T: AsRef<str>>(url: T) -> Result<Vec<RemoteRef>> {
+    list(url.as_ref(), &Net::default())
+}
+
+impl<'a> Repo<'a> {
+    /// List the refs of the repo's url along with their ids using the repo's network settings
+    /// and url rewrite rules without needing a local clone, the same as `git ls-remote <url>`.
+    /// Useful for checking that a branch or tag exists before cloning.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_repo_ls_remote_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let refs = git::Repo::new("foo").unwrap().url(tmpdir.to_string().unwrap()).ls_remote().unwrap();
+    /// assert!(refs.iter().any(|x| x.name == "refs/tags/3.11"));
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn ls_remote(self) -> Result<Vec<RemoteRef>> {
+        let url = self.net.rewrite.rewrite(self.url_val().ok_or(Error::UrlNotSet)?);
+        list(&url, &self.net)
+    }
+}
+
+// Connect to the given url and list its refs
+fn list(url: &str, net: &Net) -> Result<Vec<RemoteRef>> {
+    let mut remote = Remote::create_detached(url)?;
+    let conn = net.auth.settle(|| remote.connect_auth(Direction::Fetch, Some(remote_callbacks(net, None)), Some(net.proxy.options())))?;
+    let refs = conn
+        .list()?
+        .iter()
+        .map(|x| RemoteRef {
+            name: x.name().to_string(),
+            oid: x.oid(),
+            symref: x.symref_target().map(|x| x.to_string()),
+        })
+        .collect();
+    Ok(refs)
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_ls_remote() {
+        let tmpdir = setup("git_ls_remote");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+        let remote = tmpdir.mash("remote");
+        let url = remote.to_string().unwrap();
+        assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        let bare = git2::Repository::open(&remote).unwrap();
+        let head = bare.refname_to_id("refs/heads/master").unwrap();
+
+        // Branches, tags and HEAD
+        let refs = git::ls_remote(&url).unwrap();
+        let find = |refs: &[git::RemoteRef], name: &str| refs.iter().find(|x| x.name == name).cloned();
+        assert_eq!(find(&refs, "refs/heads/master").unwrap().oid, head);
+        assert_eq!(find(&refs, "refs/tags/3.11").unwrap().oid, bare.refname_to_id("refs/tags/3.11").unwrap());
+        assert_eq!(find(&refs, "HEAD").unwrap().oid, head);
+        assert_eq!(find(&refs, "refs/heads/missing"), None);
+
+        // New refs are seen without fetching
+        bare.reference("refs/heads/feature", head, false, "").unwrap();
+        let refs = git::Repo::new("unused").unwrap().url(&url).ls_remote().unwrap();
+        assert_eq!(find(&refs, "refs/heads/feature").unwrap().oid, head);
+
+        // Url rewrites
+        let rewrite = git::UrlRewrite::new().instead_of(format!("{}/", tmpdir.to_string().unwrap()), "internal:");
+        let refs = git::Repo::new("unused").unwrap().url("internal:remote").url_rewrite(rewrite).ls_remote().unwrap();
+        assert_eq!(find(&refs, "refs/heads/feature").unwrap().oid, head);
+
+        // Errors
+        assert!(git::ls_remote(tmpdir.mash("missing").to_string().unwrap()).is_err());
+        assert_eq!(git::Repo::new("unused").unwrap().ls_remote().unwrap_err().to_string(), git::Error::UrlNotSet.to_string());
+        assert_eq!(Path::new("unused").exists(), false);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
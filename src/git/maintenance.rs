@@ -0,0 +1,1250 @@
+use super::reflog_expire;
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{ObjectType, Oid, PackBuilder, Repository};
+use std::{
+    collections::HashSet,
+    ffi::CString,
+    fs,
+    io::Write,
+    ptr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Task of a maintenance run as reported in `MaintenanceStep`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MaintenanceTask {
+    /// Packing loose refs into the packed-refs file
+    PackRefs,
+
+    /// Removing old reflog entries
+    ExpireReflogs,
+
+    /// Finding the objects reachable from refs, reflogs and the index
+    CountObjects,
+
+    /// Writing the objects to keep into a single pack
+    PackObjects,
+
+    /// Removing unreachable loose objects
+    PruneObjects,
+}
+
+/// Progress of a maintenance run as reported to the callback of `maintain`, `repack` and `prune`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaintenanceStep {
+    pub task: MaintenanceTask, // Task being run
+    pub current: usize,        // Number of items processed so far
+    pub total: usize,          // Total number of items or zero if unknown
+}
+
+/// Outcome of consolidating the object packs as returned by `repack` and in `MaintenanceReport`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RepackReport {
+    pub objects: usize,       // Number of objects written to the new pack
+    pub unreachable: usize,   // Number of unreachable packed objects dropped
+    pub packs_removed: usize, // Number of old packs removed
+    pub loose_removed: usize, // Number of loose objects removed now that they're packed
+    pub size_before: u64,     // Size in bytes of the packs and loose objects before
+    pub size_after: u64,      // Size in bytes of the packs and loose objects after
+}
+
+/// Outcome of pruning loose objects as returned by `prune` and in `MaintenanceReport`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PruneReport {
+    pub objects: usize, // Number of unreachable loose objects removed
+    pub size: u64,      // Size in bytes of the removed objects
+}
+
+impl RepackReport {
+    /// Returns the number of bytes freed, or that would be freed by a dry run, i.e. the size
+    /// before less the size after.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let report = git::RepackReport { size_before: 10, size_after: 4, ..Default::default() };
+    /// assert_eq!(report.reclaimable(), 6);
+    /// ```
+    pub fn reclaimable(&self) -> u64 {
+        self.size_before.saturating_sub(self.size_after)
+    }
+}
+
+/// Outcome of a maintenance run as returned by `maintain`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MaintenanceReport {
+    pub refs_packed: usize,           // Number of loose refs packed
+    pub reflog_entries: usize,        // Number of reflog entries removed
+    pub repack: Option<RepackReport>, // Outcome of consolidating the packs if run
+    pub prune: Option<PruneReport>,   // Outcome of pruning loose objects if run
+}
+
+/// Options for repository maintenance with `maintain`
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let opts = git::MaintenanceOptions::new().prune_expiry(0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MaintenanceOptions {
+    refs: bool,         // Pack loose refs into the packed-refs file
+    reflogs: bool,      // Remove reflog entries older than the reflog expiry
+    reflog_expiry: i64, // Age in seconds of reflog entries to remove
+    repack: bool,       // Consolidate the objects into a single pack
+    prune: bool,        // Remove unreachable loose objects older than the prune expiry
+    prune_expiry: i64,  // Age in seconds of unreachable objects to remove
+}
+
+impl Default for MaintenanceOptions {
+    fn default() -> Self {
+        Self {
+            refs: true,
+            reflogs: true,
+            reflog_expiry: 90 * DAY,
+            repack: true,
+            prune: true,
+            prune_expiry: 14 * DAY,
+        }
+    }
+}
+
+impl MaintenanceOptions {
+    /// Create new maintenance options that run all tasks with the same expiries as `git gc`
+    /// i.e. reflog entries older than 90 days and unreachable objects older than 2 weeks.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::MaintenanceOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set to pack loose refs into the packed-refs file. Defaults to true.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MaintenanceOptions::new().refs(false).refs_val(), false);
+    /// ```
+    pub fn refs(mut self, yes: bool) -> Self {
+        self.refs = yes;
+        self
+    }
+
+    /// Get whether loose refs are packed into the packed-refs file.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MaintenanceOptions::new().refs_val(), true);
+    /// ```
+    pub fn refs_val(&self) -> bool {
+        self.refs
+    }
+
+    /// Set to remove reflog entries older than the reflog expiry. Defaults to true.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MaintenanceOptions::new().reflogs(false).reflogs_val(), false);
+    /// ```
+    pub fn reflogs(mut self, yes: bool) -> Self {
+        self.reflogs = yes;
+        self
+    }
+
+    /// Get whether reflog entries older than the reflog expiry are removed.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MaintenanceOptions::new().reflogs_val(), true);
+    /// ```
+    pub fn reflogs_val(&self) -> bool {
+        self.reflogs
+    }
+
+    /// Set the age in seconds of reflog entries to remove, the same as `gc.reflogExpire`.
+    /// Defaults to 90 days.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MaintenanceOptions::new().reflog_expiry(60).reflog_expiry_val(), 60);
+    /// ```
+    pub fn reflog_expiry(mut self, secs: i64) -> Self {
+        self.reflog_expiry = secs;
+        self
+    }
+
+    /// Get the age in seconds of reflog entries to remove.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MaintenanceOptions::new().reflog_expiry_val(), 90 * 24 * 60 * 60);
+    /// ```
+    pub fn reflog_expiry_val(&self) -> i64 {
+        self.reflog_expiry
+    }
+
+    /// Set to consolidate the objects into a single pack. Defaults to true.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MaintenanceOptions::new().repack(false).repack_val(), false);
+    /// ```
+    pub fn repack(mut self, yes: bool) -> Self {
+        self.repack = yes;
+        self
+    }
+
+    /// Get whether the objects are consolidated into a single pack.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MaintenanceOptions::new().repack_val(), true);
+    /// ```
+    pub fn repack_val(&self) -> bool {
+        self.repack
+    }
+
+    /// Set to remove unreachable loose objects older than the prune expiry. Defaults to true.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MaintenanceOptions::new().prune(false).prune_val(), false);
+    /// ```
+    pub fn prune(mut self, yes: bool) -> Self {
+        self.prune = yes;
+        self
+    }
+
+    /// Get whether unreachable loose objects older than the prune expiry are removed.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MaintenanceOptions::new().prune_val(), true);
+    /// ```
+    pub fn prune_val(&self) -> bool {
+        self.prune
+    }
+
+    /// Set the age in seconds of unreachable objects to remove, the same as `gc.pruneExpire`.
+    /// Defaults to 2 weeks so objects being written by concurrent operations are left alone.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MaintenanceOptions::new().prune_expiry(0).prune_expiry_val(), 0);
+    /// ```
+    pub fn prune_expiry(mut self, secs: i64) -> Self {
+        self.prune_expiry = secs;
+        self
+    }
+
+    /// Get the age in seconds of unreachable objects to remove.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MaintenanceOptions::new().prune_expiry_val(), 14 * 24 * 60 * 60);
+    /// ```
+    pub fn prune_expiry_val(&self) -> i64 {
+        self.prune_expiry
+    }
+}
+
+/// Options for consolidating the object packs with `repack`
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let opts = git::RepackOptions::new().dry_run(true);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RepackOptions {
+    dry_run: bool,       // Only report what would be done
+    expiry: Option<i64>, // Age in seconds of unreachable packed objects to drop
+}
+
+impl RepackOptions {
+    /// Create new repack options that keep all unreachable packed objects.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::RepackOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set to only report what would be done including the space that would be reclaimed
+    /// without changing the repo.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::RepackOptions::new().dry_run(true).dry_run_val(), true);
+    /// ```
+    pub fn dry_run(mut self, yes: bool) -> Self {
+        self.dry_run = yes;
+        self
+    }
+
+    /// Get whether only what would be done is reported.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::RepackOptions::new().dry_run_val(), false);
+    /// ```
+    pub fn dry_run_val(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Set the age in seconds of unreachable packed objects to drop rather than keep in the new
+    /// pack based on the age of the pack they're in. By default they're all kept.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::RepackOptions::new().expiry(0).expiry_val(), Some(0));
+    /// ```
+    pub fn expiry(mut self, secs: i64) -> Self {
+        self.expiry = Some(secs);
+        self
+    }
+
+    /// Get the age in seconds of unreachable packed objects to drop if any.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::RepackOptions::new().expiry_val(), None);
+    /// ```
+    pub fn expiry_val(&self) -> Option<i64> {
+        self.expiry
+    }
+}
+
+/// Options for removing unreachable loose objects with `prune`
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let opts = git::PruneOptions::new().expiry(0).dry_run(true);
+/// ```
+#[derive(Clone, Debug)]
+pub struct PruneOptions {
+    dry_run: bool, // Only report what would be removed
+    expiry: i64,   // Age in seconds of unreachable objects to remove
+}
+
+impl Default for PruneOptions {
+    fn default() -> Self {
+        Self { dry_run: false, expiry: 14 * DAY }
+    }
+}
+
+impl PruneOptions {
+    /// Create new prune options that remove unreachable loose objects older than 2 weeks the
+    /// same as `git gc`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::PruneOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set to only report what would be removed without changing the repo, the same as `git
+    /// prune --dry-run`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::PruneOptions::new().dry_run(true).dry_run_val(), true);
+    /// ```
+    pub fn dry_run(mut self, yes: bool) -> Self {
+        self.dry_run = yes;
+        self
+    }
+
+    /// Get whether only what would be removed is reported.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::PruneOptions::new().dry_run_val(), false);
+    /// ```
+    pub fn dry_run_val(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Set the age in seconds of unreachable loose objects to remove, the same as `git prune
+    /// --expire`. Defaults to 2 weeks.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::PruneOptions::new().expiry(0).expiry_val(), 0);
+    /// ```
+    pub fn expiry(mut self, secs: i64) -> Self {
+        self.expiry = secs;
+        self
+    }
+
+    /// Get the age in seconds of unreachable loose objects to remove.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::PruneOptions::new().expiry_val(), 14 * 24 * 60 * 60);
+    /// ```
+    pub fn expiry_val(&self) -> i64 {
+        self.expiry
+    }
+}
+
+/// Run repository maintenance the same as `git gc`. Loose refs are packed first. Old reflog
+/// entries are removed next so the
+/// objects only they referenced become unreachable, then the objects reachable from refs, reflogs
+/// and the index are consolidated into a single pack replacing the existing packs and the loose
+/// objects it contains, and finally unreachable loose objects older than the prune expiry are
+/// removed. Unreachable packed objects younger than the prune expiry are kept in the new pack.
+/// Packs with a `.keep` file are left as is. The `progress` callback is called as each task
+/// advances.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_maintain_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let report = git::maintain(&tmpdir, &git::MaintenanceOptions::new(), |_| {}).unwrap();
+/// assert!(report.repack.unwrap().objects > 0);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn maintain<T, F>(path: T, opts: &MaintenanceOptions, mut progress: F) -> Result<MaintenanceReport>
+where
+    T: AsRef<Path>,
+    F: FnMut(&MaintenanceStep),
+{
+    let path = path.as_ref();
+    let now = now();
+    let mut report = MaintenanceReport::default();
+    if opts.refs {
+        progress(&MaintenanceStep { task: MaintenanceTask::PackRefs, current: 0, total: 1 });
+        report.refs_packed = pack_refs(path)?;
+        progress(&MaintenanceStep { task: MaintenanceTask::PackRefs, current: 1, total: 1 });
+    }
+    if opts.reflogs {
+        progress(&MaintenanceStep { task: MaintenanceTask::ExpireReflogs, current: 0, total: 1 });
+        report.reflog_entries = reflog_expire(path, None, now - opts.reflog_expiry)?;
+        progress(&MaintenanceStep { task: MaintenanceTask::ExpireReflogs, current: 1, total: 1 });
+    }
+
+    let repo = Repository::open(path)?;
+    let prune_before = now - opts.prune_expiry;
+    if opts.repack {
+        let expire = if opts.prune { Some(prune_before) } else { None };
+        report.repack = Some(repack_objects(&repo, expire, false, &mut progress)?);
+    }
+    if opts.prune {
+        report.prune = Some(prune_objects(&repo, prune_before, false, &mut progress)?);
+    }
+    Ok(report)
+}
+
+/// Consolidate the objects reachable from refs, reflogs and the index into a single pack
+/// replacing the existing packs and the loose objects it contains, the same as `git repack -a -d
+/// -k`. Unreachable packed objects are kept in the new pack unless they're in packs older than
+/// the expiry. Unreachable loose objects are left for `prune`. Packs with a `.keep` file are left
+/// as is. A dry run reports the same outcome including the space that would be reclaimed without
+/// changing the repo.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_repack_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let report = git::repack(&tmpdir, &git::RepackOptions::new().dry_run(true), |_| {}).unwrap();
+/// println!("{} bytes reclaimable", report.reclaimable());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn repack<T, F>(path: T, opts: &RepackOptions, mut progress: F) -> Result<RepackReport>
+where
+    T: AsRef<Path>,
+    F: FnMut(&MaintenanceStep),
+{
+    let repo = Repository::open(path.as_ref())?;
+    repack_objects(&repo, opts.expiry.map(|x| now() - x), opts.dry_run, &mut progress)
+}
+
+/// Remove the loose objects older than the expiry that aren't reachable from refs, reflogs or the
+/// index, the same as `git prune --expire <time>`. Packed objects are left for `repack`. A dry
+/// run reports the objects and space that would be reclaimed without changing the repo.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_prune_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let report = git::prune(&tmpdir, &git::PruneOptions::new().dry_run(true), |_| {}).unwrap();
+/// assert_eq!(report.objects, 0);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn prune<T, F>(path: T, opts: &PruneOptions, mut progress: F) -> Result<PruneReport>
+where
+    T: AsRef<Path>,
+    F: FnMut(&MaintenanceStep),
+{
+    let repo = Repository::open(path.as_ref())?;
+    prune_objects(&repo, now() - opts.expiry, opts.dry_run, &mut progress)
+}
+
+/// Pack the loose refs i.e. branches, tags, remote branches, notes and so on into the packed-refs
+/// file and remove the loose ref files, the same as `git pack-refs --all --prune`. Tags are
+/// stored with their peeled commit. Lookups in repos with many refs are faster once packed.
+/// Returns the number of loose refs packed.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_pack_refs_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::branch_create(&tmpdir, "foo", None, false).is_ok());
+/// assert!(git::pack_refs(&tmpdir).unwrap() > 0);
+/// assert_eq!(tmpdir.mash(".git/refs/heads/foo").exists(), false);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn pack_refs<T: AsRef<Path>>(path: T) -> Result<usize> {
+    let repo = Repository::open(path.as_ref())?;
+    let before = loose_refs(&repo)?;
+    let path = CString::new(repo.commondir().to_string()?).map_err(|x| git2::Error::from_str(&x.to_string()))?;
+
+    // git2 doesn't expose compressing the refdb. The shared refs are packed from the commondir as
+    // a linked worktree has no refdb of its own to compress.
+    libgit2_sys::init();
+    let code = unsafe {
+        let mut raw = ptr::null_mut();
+        let mut code = libgit2_sys::git_repository_open(&mut raw, path.as_ptr());
+        if code == 0 {
+            let mut refdb = ptr::null_mut();
+            code = libgit2_sys::git_repository_refdb(&mut refdb, raw);
+            if code == 0 {
+                code = libgit2_sys::git_refdb_compress(refdb);
+                libgit2_sys::git_refdb_free(refdb);
+            }
+            libgit2_sys::git_repository_free(raw);
+        }
+        code
+    };
+    if code < 0 {
+        return Err(git2::Error::last_error(code).into());
+    }
+    Ok(before.saturating_sub(loose_refs(&repo)?))
+}
+
+// Returns the number of loose ref files in the repo's shared refs directory
+fn loose_refs(repo: &Repository) -> Result<usize> {
+    let mut count = 0;
+    let mut dirs = vec![repo.commondir().mash("refs")];
+    while let Some(dir) = dirs.pop() {
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&dir).map_err(FuError::from)? {
+            let path = entry.map_err(FuError::from)?.path();
+            match path.is_dir() {
+                true => dirs.push(path),
+                false if !path.to_string()?.ends_with(".lock") => count += 1,
+                false => {},
+            }
+        }
+    }
+    Ok(count)
+}
+
+// Pack the reachable objects and the unreachable packed objects newer than the given time, or all
+// of them when None is given, into a single new pack then remove the old packs and the loose
+// objects the new pack contains. A dry run only reports what would be done.
+fn repack_objects<F>(repo: &Repository, expire: Option<i64>, dry_run: bool, progress: &mut F) -> Result<RepackReport>
+where
+    F: FnMut(&MaintenanceStep),
+{
+    let mut report = RepackReport { size_before: objects_size(repo)?, ..Default::default() };
+    let reachable = reachable_objects(repo, progress)?;
+    let odb = repo.odb()?;
+
+    // Keep the unreachable objects of packs that aren't old enough to drop
+    let (kept_packs, old_packs): (Vec<PathBuf>, Vec<PathBuf>) = packs(repo)?.into_iter().partition(|x| x.with_extension("keep").exists());
+    let mut kept = HashSet::new();
+    for pack in kept_packs.iter() {
+        kept.extend(pack_oids(&pack.with_extension("idx"))?);
+    }
+    let mut objects: HashSet<Oid> = reachable.iter().cloned().filter(|x| !kept.contains(x) && odb.exists(*x)).collect();
+    let mut dropped = HashSet::new();
+    for pack in old_packs.iter() {
+        let keep = expire.map(|x| mtime(pack) >= x).unwrap_or(true);
+        for oid in pack_oids(&pack.with_extension("idx"))?.into_iter().filter(|x| !reachable.contains(x) && !kept.contains(x)) {
+            if keep {
+                objects.insert(oid);
+            } else {
+                dropped.insert(oid);
+            }
+        }
+    }
+    report.unreachable = dropped.difference(&objects).count();
+
+    // Objects are sorted so the pack is the same for the same objects
+    let mut objects: Vec<Oid> = objects.into_iter().collect();
+    objects.sort();
+    let mut builder: PackBuilder = repo.packbuilder()?;
+    for (i, oid) in objects.iter().enumerate() {
+        builder.insert_object(*oid, None)?;
+        progress(&MaintenanceStep { task: MaintenanceTask::PackObjects, current: i + 1, total: objects.len() });
+    }
+    report.objects = objects.len();
+    let mut buf = git2::Buf::new();
+    builder.write_buf(&mut buf)?;
+    drop(builder);
+
+    // Index the new pack before removing what it replaces. A dry run accounts for the new pack
+    // and its index without writing them.
+    let new_pack = repo.commondir().mash("objects/pack").mash(format!("pack-{}.pack", pack_hash(&buf)));
+    let mut size_after = report.size_before;
+    if !objects.is_empty() && !new_pack.exists() {
+        match dry_run {
+            true => size_after += (buf.len() + pack_index_size(objects.len())) as u64,
+            false => {
+                let mut writer = odb.packwriter()?;
+                writer.write_all(&buf).map_err(FuError::from)?;
+                writer.commit()?;
+            },
+        }
+    }
+    for pack in old_packs.iter().filter(|x| objects.is_empty() || **x != new_pack) {
+        for ext in &["pack", "idx", "bitmap", "rev"] {
+            let file = pack.with_extension(ext);
+            if file.exists() {
+                match dry_run {
+                    true if *ext == "pack" || *ext == "idx" => size_after -= file_size(&file),
+                    true => {},
+                    false => fs::remove_file(&file).map_err(FuError::from)?,
+                }
+            }
+        }
+        report.packs_removed += 1;
+    }
+    let packed: HashSet<Oid> = objects.into_iter().collect();
+    for (oid, file) in loose_objects(repo)? {
+        if packed.contains(&oid) || kept.contains(&oid) {
+            match dry_run {
+                true => size_after -= file_size(&file),
+                false => remove_loose(&file)?,
+            }
+            report.loose_removed += 1;
+        }
+    }
+    report.size_after = if dry_run { size_after } else { objects_size(repo)? };
+    Ok(report)
+}
+
+// Remove the unreachable loose objects older than the given time. A dry run only reports what
+// would be removed.
+fn prune_objects<F>(repo: &Repository, before: i64, dry_run: bool, progress: &mut F) -> Result<PruneReport>
+where
+    F: FnMut(&MaintenanceStep),
+{
+    let mut report = PruneReport::default();
+    let reachable = reachable_objects(repo, progress)?;
+    let loose = loose_objects(repo)?;
+    for (i, (oid, file)) in loose.iter().enumerate() {
+        if !reachable.contains(oid) && mtime(file) < before {
+            report.size += file_size(file);
+            if !dry_run {
+                remove_loose(file)?;
+            }
+            report.objects += 1;
+        }
+        progress(&MaintenanceStep { task: MaintenanceTask::PruneObjects, current: i + 1, total: loose.len() });
+    }
+    Ok(report)
+}
+
+// Returns the ids of the objects reachable from the refs, the reflogs and the HEAD and index of
+// every worktree the same as git uses to decide what to keep. Missing objects are included so
+// callers can detect them.
+fn reachable_objects<F>(repo: &Repository, progress: &mut F) -> Result<HashSet<Oid>>
+where
+    F: FnMut(&MaintenanceStep),
+{
+    let mut pending = vec![];
+    let mut names = vec![];
+    for reference in repo.references()? {
+        let reference = reference?;
+        if let Some(oid) = reference.target() {
+            pending.push(oid);
+        }
+        if let Some(name) = reference.name() {
+            names.push(name.to_string());
+        }
+    }
+    for name in names.iter() {
+        for entry in repo.reflog(name)?.iter() {
+            pending.extend([entry.id_old(), entry.id_new()].iter().filter(|x| !x.is_zero()));
+        }
+    }
+    for worktree in worktree_repos(repo)? {
+        if let Ok(oid) = worktree.refname_to_id("HEAD") {
+            pending.push(oid);
+        }
+        for entry in worktree.reflog("HEAD")?.iter() {
+            pending.extend([entry.id_old(), entry.id_new()].iter().filter(|x| !x.is_zero()));
+        }
+        if !worktree.is_bare() {
+            pending.extend(worktree.index()?.iter().filter(|x| x.mode != 0o160000).map(|x| x.id));
+        }
+    }
+
+    let mut reachable = HashSet::new();
+    while let Some(oid) = pending.pop() {
+        if !reachable.insert(oid) {
+            continue;
+        }
+        if reachable.len() % 1000 == 0 {
+            progress(&MaintenanceStep { task: MaintenanceTask::CountObjects, current: reachable.len(), total: 0 });
+        }
+        let object = match repo.find_object(oid, None) {
+            Ok(object) => object,
+            Err(_) => continue,
+        };
+        match object.kind() {
+            Some(ObjectType::Commit) => {
+                let commit = object.peel_to_commit()?;
+                pending.push(commit.tree_id());
+                pending.extend(commit.parent_ids());
+            },
+            Some(ObjectType::Tree) => {
+                let tree = object.peel_to_tree()?;
+                pending.extend(tree.iter().filter(|x| x.kind() != Some(ObjectType::Commit)).map(|x| x.id()));
+            },
+            Some(ObjectType::Tag) => {
+                if let Some(tag) = object.as_tag() {
+                    pending.push(tag.target_id());
+                }
+            },
+            _ => {},
+        }
+    }
+    progress(&MaintenanceStep {
+        task: MaintenanceTask::CountObjects,
+        current: reachable.len(),
+        total: reachable.len(),
+    });
+    Ok(reachable)
+}
+
+// Returns the main worktree and the linked worktrees sharing the repo's object store. Linked
+// worktrees whose directory is gone are opened from their admin directory in the commondir.
+fn worktree_repos(repo: &Repository) -> Result<Vec<Repository>> {
+    let main = Repository::open(repo.commondir())?;
+    let mut repos = vec![];
+    for name in main.worktrees()?.iter().flatten() {
+        let worktree = match main.find_worktree(name).and_then(|x| Repository::open_from_worktree(&x)) {
+            Ok(worktree) => worktree,
+            Err(_) => Repository::open(repo.commondir().mash("worktrees").mash(name))?,
+        };
+        repos.push(worktree);
+    }
+    repos.insert(0, main);
+    Ok(repos)
+}
+
+// Returns the packs of the repo's object store
+pub(crate) fn packs(repo: &Repository) -> Result<Vec<PathBuf>> {
+    let dir = repo.commondir().mash("objects/pack");
+    let mut packs = vec![];
+    if dir.exists() {
+        for entry in fs::read_dir(&dir).map_err(FuError::from)? {
+            let path = entry.map_err(FuError::from)?.path();
+            if path.extension().map(|x| x == "pack").unwrap_or(false) && path.with_extension("idx").exists() {
+                packs.push(path);
+            }
+        }
+    }
+    packs.sort();
+    Ok(packs)
+}
+
+// Returns the ids of the objects in the pack of the given version 2 pack index
+pub(crate) fn pack_oids(idx: &Path) -> Result<Vec<Oid>> {
+    let data = fs::read(idx).map_err(FuError::from)?;
+    let invalid = || git2::Error::from_str(&format!("invalid pack index {}", idx.display()));
+    if data.len() < 8 + 256 * 4 || data[..8] != [0xff, 0x74, 0x4f, 0x63, 0, 0, 0, 2] {
+        return Err(invalid().into());
+    }
+    let count = u32::from_be_bytes([data[1028], data[1029], data[1030], data[1031]]) as usize;
+    let start = 8 + 256 * 4;
+    if data.len() < start + count * 20 {
+        return Err(invalid().into());
+    }
+    let mut oids = vec![];
+    for i in 0..count {
+        oids.push(Oid::from_bytes(&data[start + i * 20..start + (i + 1) * 20])?);
+    }
+    Ok(oids)
+}
+
+// Returns the loose objects of the repo's object store
+pub(crate) fn loose_objects(repo: &Repository) -> Result<Vec<(Oid, PathBuf)>> {
+    let dir = repo.commondir().mash("objects");
+    let mut objects = vec![];
+    for entry in fs::read_dir(&dir).map_err(FuError::from)? {
+        let fanout = entry.map_err(FuError::from)?.path();
+        let prefix = fanout.file_name().and_then(|x| x.to_str()).unwrap_or_default().to_string();
+        if prefix.len() != 2 || !prefix.chars().all(|x| x.is_ascii_hexdigit()) || !fanout.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&fanout).map_err(FuError::from)? {
+            let file = entry.map_err(FuError::from)?.path();
+            let name = file.file_name().and_then(|x| x.to_str()).unwrap_or_default();
+            if name.len() == 38 {
+                if let Ok(oid) = Oid::from_str(&format!("{}{}", prefix, name)) {
+                    objects.push((oid, file));
+                }
+            }
+        }
+    }
+    objects.sort();
+    Ok(objects)
+}
+
+// Returns the size in bytes of the repo's packs and loose objects
+fn objects_size(repo: &Repository) -> Result<u64> {
+    let mut size = 0;
+    for pack in packs(repo)? {
+        size += file_size(&pack) + file_size(&pack.with_extension("idx"));
+    }
+    for (_, file) in loose_objects(repo)? {
+        size += file_size(&file);
+    }
+    Ok(size)
+}
+
+// Returns the size in bytes of the version 2 index of a pack with the given number of objects
+// i.e. the header, fanout table, ids, crcs, offsets and checksums
+fn pack_index_size(objects: usize) -> usize {
+    8 + 256 * 4 + objects * (20 + 4 + 4) + 20 + 20
+}
+
+// Returns the size in bytes of the given file or zero if it doesn't exist
+pub(crate) fn file_size(file: &Path) -> u64 {
+    file.metadata().map(|x| x.len()).unwrap_or(0)
+}
+
+// Remove the given loose object and its fanout directory once empty
+fn remove_loose(file: &Path) -> Result<()> {
+    fs::remove_file(file).map_err(FuError::from)?;
+    if let Some(dir) = file.parent() {
+        let _ = fs::remove_dir(dir);
+    }
+    Ok(())
+}
+
+// Returns the hex hash of the given pack which names its files
+fn pack_hash(buf: &[u8]) -> String {
+    buf[buf.len().saturating_sub(20)..].iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+// Returns the modification time of the given file in seconds since the epoch
+fn mtime(file: &Path) -> i64 {
+    file.metadata().and_then(|x| x.modified()).ok().and_then(|x| x.duration_since(UNIX_EPOCH).ok()).map(|x| x.as_secs() as i64).unwrap_or(0)
+}
+
+// Returns the current time in seconds since the epoch
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs() as i64).unwrap_or(0)
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::{
+        process::Command,
+        time::{Duration, SystemTime},
+    };
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Returns the output of the given git command
+    fn git(path: &Path, args: &[&str]) -> String {
+        let output = Command::new("git").args(args).current_dir(path).output().unwrap();
+        assert_eq!(output.status.success(), true, "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    // Set the modification time of the given file to the given number of days ago
+    fn age(file: &Path, days: u64) {
+        let file = std::fs::File::options().write(true).open(file).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(days * 24 * 60 * 60)).unwrap();
+    }
+
+    #[test]
+    fn test_maintain() {
+        let tmpdir = setup("git_maintain");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let objects = git(&tmpdir, &["rev-list", "--all", "--objects"]).lines().count();
+
+        // Loose objects both reachable and not
+        assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add foo").commit().is_ok());
+        let dangling = repo.blob(b"dangling").unwrap();
+        let loose = tmpdir.mash(".git/objects").mash(&dangling.to_string()[..2]).mash(&dangling.to_string()[2..]);
+        assert_eq!(loose.exists(), true);
+
+        // Consolidate keeping young unreachable objects after expiring the fixture's old reflogs
+        let mut steps = vec![];
+        let report = git::maintain(&tmpdir, &git::MaintenanceOptions::new(), |x| steps.push(x.clone())).unwrap();
+        assert!(report.refs_packed > 0);
+        assert_eq!(report.reflog_entries, 3);
+        let repack = report.repack.unwrap();
+        assert_eq!((repack.objects, repack.unreachable, repack.loose_removed), (objects + 3, 0, 3));
+        assert!(repack.size_after > 0);
+        assert_eq!(report.prune, Some(git::PruneReport::default()));
+        assert_eq!(loose.exists(), true);
+        assert_eq!(steps.first().unwrap().task, git::MaintenanceTask::PackRefs);
+        assert!(steps.iter().any(|x| x.task == git::MaintenanceTask::ExpireReflogs));
+        assert!(steps.iter().any(|x| x.task == git::MaintenanceTask::PackObjects && x.current == x.total));
+        assert_eq!(steps.last().unwrap().task, git::MaintenanceTask::PruneObjects);
+        let counts = git(&tmpdir, &["count-objects", "-v"]);
+        assert!(counts.contains("count: 1\n") && counts.contains("packs: 1\n"), "{}", counts);
+        git(&tmpdir, &["fsck", "--full", "--strict"]);
+
+        // Old unreachable loose objects are pruned
+        age(&loose, 30);
+        let report = git::maintain(&tmpdir, &git::MaintenanceOptions::new().repack(false), |_| {}).unwrap();
+        assert_eq!(report.repack, None);
+        assert_eq!(report.prune.as_ref().map(|x| x.objects), Some(1));
+        assert!(report.prune.unwrap().size > 0);
+        assert_eq!(loose.exists(), false);
+        assert_eq!(loose.dir().unwrap().exists(), false);
+
+        // Objects only reachable from reflogs are packed
+        assert!(sys::write(tmpdir.mash("bar"), "bar").is_ok());
+        let bar = git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add bar").commit().unwrap();
+        assert!(git::reset(&tmpdir, "HEAD~1", git::ResetMode::Hard, true).is_ok());
+        let report = git::maintain(&tmpdir, &git::MaintenanceOptions::new(), |_| {}).unwrap();
+        assert_eq!(report.repack.unwrap().objects, objects + 6);
+
+        // Unreachable packed objects are kept when not pruning or while young
+        let opts = git::MaintenanceOptions::new().reflog_expiry(-10);
+        let report = git::maintain(&tmpdir, &opts.clone().prune(false), |_| {}).unwrap();
+        assert!(report.reflog_entries > 0);
+        assert_eq!(report.repack.unwrap().objects, objects + 6);
+        let report = git::maintain(&tmpdir, &opts, |_| {}).unwrap();
+        assert_eq!(report.repack.unwrap().objects, objects + 6);
+        assert_eq!(git(&tmpdir, &["cat-file", "-t", &bar.to_string()]), "commit\n");
+
+        // Old unreachable packed objects are dropped
+        for entry in std::fs::read_dir(tmpdir.mash(".git/objects/pack")).unwrap() {
+            age(&entry.unwrap().path(), 30);
+        }
+        let report = git::maintain(&tmpdir, &opts, |_| {}).unwrap();
+        let repack = report.repack.unwrap();
+        assert_eq!((repack.objects, repack.unreachable, repack.packs_removed), (objects + 3, 3, 1));
+        assert!(repack.size_after < repack.size_before);
+        assert_eq!(git2::Repository::open(&tmpdir).unwrap().find_commit(bar).is_err(), true);
+        git(&tmpdir, &["fsck", "--full", "--strict"]);
+        assert_eq!(git(&tmpdir, &["rev-list", "--all", "--objects"]).lines().count(), objects + 3);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_maintain_matches_git() {
+        let tmpdir = setup("git_maintain_matches_git");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let (ours, theirs) = (tmpdir.mash("ours"), tmpdir.mash("theirs"));
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &ours).is_ok());
+        let reflogs = |path: &Path| git(path, &["log", "--walk-reflogs", "--all", "--format=%gD %H %gs"]);
+        let objects = |path: &Path| {
+            let mut objects: Vec<String> = git(path, &["cat-file", "--batch-all-objects", "--batch-check=%(objectname)"]).lines().map(|x| x.to_string()).collect();
+            objects.sort();
+            objects
+        };
+
+        // Old and young dangling loose objects plus a commit only reachable from the reflogs
+        let repo = git2::Repository::open(&ours).unwrap();
+        let (old, young) = (repo.blob(b"old").unwrap(), repo.blob(b"young").unwrap());
+        age(&ours.mash(".git/objects").mash(&old.to_string()[..2]).mash(&old.to_string()[2..]), 30);
+        assert!(sys::write(ours.mash("bar"), "bar").is_ok());
+        let bar = git::CommitBuilder::new(&ours).stage_all(true).author("foo", "foo@example.com").message("Add bar").commit().unwrap();
+        assert!(git::reset(&ours, "HEAD~1", git::ResetMode::Hard, true).is_ok());
+        assert_eq!(Command::new("cp").arg("-a").arg(&ours).arg(&theirs).status().unwrap().success(), true);
+
+        // Old reflog entries expire and old unreachable objects are pruned the same as git gc
+        let before = reflogs(&ours);
+        let report = git::maintain(&ours, &git::MaintenanceOptions::new(), |_| {}).unwrap();
+        git(&theirs, &["-c", "gc.reflogExpire=90.days.ago", "-c", "gc.reflogExpireUnreachable=90.days.ago", "gc", "--prune=2.weeks.ago"]);
+        assert_eq!(reflogs(&ours), reflogs(&theirs));
+        assert_eq!(before.lines().count() - reflogs(&ours).lines().count(), report.reflog_entries);
+        assert_eq!(reflogs(&ours).contains(&bar.to_string()), true);
+        assert_eq!(objects(&ours), objects(&theirs));
+        assert_eq!(objects(&ours).contains(&old.to_string()), false);
+        assert_eq!(objects(&ours).contains(&young.to_string()), true);
+        assert_eq!(objects(&ours).contains(&bar.to_string()), true);
+        git(&ours, &["fsck", "--full", "--strict"]);
+
+        // Expiring every reflog entry prunes the objects only they referenced the same as git gc
+        let opts = git::MaintenanceOptions::new().reflog_expiry(-10).prune_expiry(-10);
+        assert!(git::maintain(&ours, &opts, |_| {}).unwrap().reflog_entries > 0);
+        git(&theirs, &["-c", "gc.reflogExpire=all", "-c", "gc.reflogExpireUnreachable=all", "gc", "--prune=all"]);
+        assert_eq!(reflogs(&ours), "");
+        assert_eq!(reflogs(&ours), reflogs(&theirs));
+        assert_eq!(objects(&ours), objects(&theirs));
+        assert_eq!(objects(&ours).contains(&young.to_string()), false);
+        assert_eq!(objects(&ours).contains(&bar.to_string()), false);
+        git(&ours, &["fsck", "--full", "--strict"]);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_maintain_worktrees() {
+        let tmpdir = setup("git_maintain_worktrees");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let (repo, wt) = (tmpdir.mash("repo"), tmpdir.mash("wt"));
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &repo).is_ok());
+
+        // A detached worktree with a commit and a staged file only it references
+        git(&repo, &["worktree", "add", "--detach", wt.to_str().unwrap()]);
+        assert!(sys::write(wt.mash("foo"), "foo").is_ok());
+        let foo = git::CommitBuilder::new(&wt).stage_all(true).author("foo", "foo@example.com").message("Add foo").commit().unwrap();
+        assert!(sys::write(wt.mash("bar"), "bar").is_ok());
+        git(&wt, &["add", "bar"]);
+        let bar = git(&wt, &["rev-parse", ":bar"]);
+
+        // Expiring every reflog entry and pruning everything unreachable keeps the worktree's objects
+        let opts = git::MaintenanceOptions::new().reflog_expiry(-10).prune_expiry(-10);
+        assert!(git::maintain(&repo, &opts, |_| {}).is_ok());
+        assert_eq!(git(&wt, &["cat-file", "-t", &foo.to_string()]), "commit\n");
+        assert_eq!(git(&wt, &["cat-file", "-t", bar.trim()]), "blob\n");
+        git(&wt, &["log", "--oneline"]);
+        git(&repo, &["fsck", "--full", "--strict"]);
+
+        // Maintenance run from the worktree uses the shared object store
+        assert!(sys::write(wt.mash("baz"), "baz").is_ok());
+        let baz = git::CommitBuilder::new(&wt).stage_all(true).author("foo", "foo@example.com").message("Add baz").commit().unwrap();
+        let report = git::maintain(&wt, &opts, |_| {}).unwrap();
+        assert!(report.repack.unwrap().loose_removed > 0);
+        assert!(git(&repo, &["count-objects", "-v"]).contains("count: 0\n"));
+        assert_eq!(git(&wt, &["cat-file", "-t", &baz.to_string()]), "commit\n");
+        git(&wt, &["fsck", "--full", "--strict"]);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_pack_refs() {
+        let tmpdir = setup("git_pack_refs");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let refs = || git(&tmpdir, &["show-ref", "--head", "-d"]);
+
+        // Loose branches, tags and notes are packed with peeled tags
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        repo.config().unwrap().set_str("user.name", "foo").unwrap();
+        repo.config().unwrap().set_str("user.email", "foo@example.com").unwrap();
+        for i in 0..20 {
+            assert!(git::branch_create(&tmpdir, format!("feature/{}", i), None, false).is_ok());
+        }
+        git(&tmpdir, &["tag", "-a", "v1.0.0", "-m", "Release"]);
+        assert!(git::note_add(&tmpdir, "HEAD", "foo", None, false).is_ok());
+        let before = refs();
+        let loose = git(&tmpdir, &["for-each-ref", "--format=%(refname)"]).lines().filter(|x| tmpdir.mash(".git").mash(x).exists()).count();
+        assert!(loose >= 23);
+        assert_eq!(git::pack_refs(&tmpdir).unwrap(), loose);
+        assert_eq!(refs(), before);
+        assert_eq!(tmpdir.mash(".git/refs/heads/master").exists(), false);
+        assert_eq!(tmpdir.mash(".git/refs/tags/v1.0.0").exists(), false);
+        let packed = sys::readstring(tmpdir.mash(".git/packed-refs")).unwrap();
+        assert!(packed.contains(" refs/heads/feature/19\n") && packed.contains(" refs/tags/v1.0.0\n^"), "{}", packed);
+        git(&tmpdir, &["fsck", "--full", "--strict"]);
+
+        // Nothing left to pack and updates still work
+        assert_eq!(git::pack_refs(&tmpdir).unwrap(), 0);
+        assert!(git::branch_delete_local(&tmpdir, "feature/0").is_ok());
+        assert!(git::branch_create(&tmpdir, "bar", None, false).is_ok());
+        assert_eq!(git::pack_refs(&tmpdir).unwrap(), 1);
+        assert!(!refs().contains("refs/heads/feature/0\n"));
+        assert!(refs().contains("refs/heads/bar\n"));
+
+        // Maintenance packs refs first
+        assert!(git::branch_create(&tmpdir, "baz", None, false).is_ok());
+        let opts = git::MaintenanceOptions::new().reflogs(false).repack(false).prune(false);
+        let mut steps = vec![];
+        let report = git::maintain(&tmpdir, &opts, |x| steps.push(x.task)).unwrap();
+        assert_eq!(report.refs_packed, 1);
+        assert_eq!(
+            steps,
+            vec![
+                git::MaintenanceTask::PackRefs,
+                git::MaintenanceTask::PackRefs
+            ]
+        );
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_repack_and_prune() {
+        let tmpdir = setup("git_repack");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let tree = || git(&tmpdir, &["count-objects", "-v"]);
+
+        // Dry run repack reports the outcome of a real repack without changing anything
+        assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add foo").commit().is_ok());
+        let dangling = repo.blob(b"dangling").unwrap();
+        let before = tree();
+        let dry = git::repack(&tmpdir, &git::RepackOptions::new().dry_run(true), |_| {}).unwrap();
+        assert_eq!(tree(), before);
+        assert_eq!(dry.loose_removed, 3);
+        let report = git::repack(&tmpdir, &git::RepackOptions::new(), |_| {}).unwrap();
+        assert_eq!(report, dry);
+        assert!(report.reclaimable() > 0);
+        let counts = tree();
+        assert!(counts.contains("count: 1\n") && counts.contains("packs: 1\n"), "{}", counts);
+        git(&tmpdir, &["fsck", "--full", "--strict"]);
+
+        // Repacking again changes nothing
+        let report = git::repack(&tmpdir, &git::RepackOptions::new(), |_| {}).unwrap();
+        assert_eq!((report.packs_removed, report.loose_removed, report.reclaimable()), (0, 0, 0));
+
+        // Dry run prune reports the young unreachable loose object only once expired
+        let opts = git::PruneOptions::new().dry_run(true);
+        assert_eq!(git::prune(&tmpdir, &opts, |_| {}).unwrap(), git::PruneReport::default());
+        let dry = git::prune(&tmpdir, &opts.clone().expiry(-10), |_| {}).unwrap();
+        assert_eq!(dry.objects, 1);
+        assert!(dry.size > 0);
+        assert_eq!(repo.find_blob(dangling).is_ok(), true);
+        assert_eq!(git::prune(&tmpdir, &git::PruneOptions::new().expiry(-10), |_| {}).unwrap(), dry);
+        assert_eq!(git2::Repository::open(&tmpdir).unwrap().find_blob(dangling).is_err(), true);
+        assert!(tree().contains("count: 0\n"));
+
+        // Kept packs are left as is
+        let pack = std::fs::read_dir(tmpdir.mash(".git/objects/pack")).unwrap().map(|x| x.unwrap().path()).find(|x| x.extension().unwrap() == "pack").unwrap();
+        assert!(sys::write(pack.with_extension("keep"), "").is_ok());
+        assert!(sys::write(tmpdir.mash("bar"), "bar").is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add bar").commit().is_ok());
+        let report = git::repack(&tmpdir, &git::RepackOptions::new(), |_| {}).unwrap();
+        assert_eq!((report.objects, report.packs_removed, report.loose_removed), (3, 0, 3));
+        assert_eq!(pack.exists(), true);
+        assert!(tree().contains("packs: 2\n"));
+        git(&tmpdir, &["fsck", "--full", "--strict"]);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_repack_and_prune_worktrees() {
+        let tmpdir = setup("git_repack_worktrees");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let (repo, wt) = (tmpdir.mash("repo"), tmpdir.mash("wt"));
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &repo).is_ok());
+
+        // A linked worktree on a branch later deleted so only its HEAD, index and reflog reference
+        // its commits and staged file
+        git(&repo, &["worktree", "add", "-b", "foo", wt.to_str().unwrap()]);
+        assert!(sys::write(wt.mash("foo"), "foo").is_ok());
+        let foo = git::CommitBuilder::new(&wt).stage_all(true).author("foo", "foo@example.com").message("Add foo").commit().unwrap();
+        assert!(sys::write(wt.mash("bar"), "bar").is_ok());
+        let bar = git::CommitBuilder::new(&wt).stage_all(true).author("foo", "foo@example.com").message("Add bar").commit().unwrap();
+        git(&wt, &["checkout", "-q", "--detach", &foo.to_string()]);
+        git(&repo, &["branch", "-D", "foo"]);
+        assert!(sys::write(wt.mash("baz"), "baz").is_ok());
+        git(&wt, &["add", "baz"]);
+        let baz = git(&wt, &["rev-parse", ":baz"]);
+        let dangling = git2::Repository::open(&repo).unwrap().blob(b"dangling").unwrap();
+
+        // Pruning only drops the object nothing references
+        let report = git::prune(&repo, &git::PruneOptions::new().expiry(-10), |_| {}).unwrap();
+        assert_eq!(report.objects, 1);
+        assert_eq!(git(&wt, &["cat-file", "-t", &bar.to_string()]), "commit\n");
+        assert_eq!(git(&wt, &["cat-file", "-t", baz.trim()]), "blob\n");
+        assert_eq!(git2::Repository::open(&repo).unwrap().find_blob(dangling).is_err(), true);
+
+        // Repacking from the worktree packs its objects into the shared store
+        let report = git::repack(&wt, &git::RepackOptions::new().expiry(-10), |_| {}).unwrap();
+        assert_eq!(report.unreachable, 0);
+        assert!(git(&repo, &["count-objects", "-v"]).contains("count: 0\n"));
+        assert_eq!(git(&wt, &["cat-file", "-t", &bar.to_string()]), "commit\n");
+        git(&wt, &["log", "--oneline", "-1"]);
+        git(&repo, &["fsck", "--full", "--strict"]);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,728 @@
+use super::{conflicts_error, is_dirty, resolve_commit, run_hook, run_message_hooks, write_commit, Conflicts, HookMode};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{build::CheckoutBuilder, BranchType, ErrorClass, ErrorCode, Oid, Repository, RepositoryState, Sort};
+
+/// Merge strategy used by `merge`
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum MergeMode {
+    /// Fast-forward when possible else create a merge commit
+    #[default]
+    FastForward,
+
+    /// Always create a merge commit even when a fast-forward is possible
+    NoFastForward,
+
+    /// Only fast-forward and fail if the histories have diverged
+    FastForwardOnly,
+}
+
+/// Outcome of a merge as returned by `merge`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MergeOutcome {
+    /// Nothing to merge as HEAD already contains the given revision
+    UpToDate,
+
+    /// HEAD was fast-forwarded to the given commit
+    FastForward(Oid),
+
+    /// The given merge commit was created
+    Merged(Oid),
+}
+
+/// Options for merging with `merge`
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let opts = git::MergeOptions::new().mode(git::MergeMode::NoFastForward).message("Merge foo");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MergeOptions {
+    mode: MergeMode,         // Merge strategy
+    message: Option<String>, // Merge commit message
+    hooks: HookMode,         // Hooks to run while merging
+}
+
+impl MergeOptions {
+    /// Create new merge options that fast-forward when possible.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::MergeOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the merge strategy.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::MergeOptions::new().mode(git::MergeMode::FastForwardOnly);
+    /// assert_eq!(opts.mode_val(), git::MergeMode::FastForwardOnly);
+    /// ```
+    pub fn mode(mut self, mode: MergeMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Get the merge strategy.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MergeOptions::new().mode_val(), git::MergeMode::FastForward);
+    /// ```
+    pub fn mode_val(&self) -> MergeMode {
+        self.mode
+    }
+
+    /// Set the merge commit message rather than the default e.g. `Merge branch 'foo'`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::MergeOptions::new().message("Merge foo");
+    /// assert_eq!(opts.message_val(), Some("Merge foo"));
+    /// ```
+    pub fn message<T: AsRef<str>>(mut self, message: T) -> Self {
+        self.message = Some(message.as_ref().to_string());
+        self
+    }
+
+    /// Get the merge commit message if set.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MergeOptions::new().message_val(), None);
+    /// ```
+    pub fn message_val(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Run the repo's `pre-merge-commit`, `prepare-commit-msg`, `commit-msg` and `post-merge`
+    /// hooks while merging according to the given mode. When a hook rejects the merge commit the
+    /// repo is left mid merge, the same as for conflicts, to be completed with `merge_finish`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::MergeOptions::new().hooks(git::HookMode::NoVerify);
+    /// assert_eq!(opts.hooks_val(), git::HookMode::NoVerify);
+    /// ```
+    pub fn hooks(mut self, mode: HookMode) -> Self {
+        self.hooks = mode;
+        self
+    }
+
+    /// Get the mode hooks will be run in while merging.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::MergeOptions::new().hooks_val(), git::HookMode::Skip);
+    /// ```
+    pub fn hooks_val(&self) -> HookMode {
+        self.hooks
+    }
+}
+
+/// Strategy used by `Repo::update` to reconcile diverged histories
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum PullStrategy {
+    /// Only fast-forward and fail if the histories have diverged
+    #[default]
+    FastForwardOnly,
+
+    /// Merge the fetched commits creating a merge commit when the histories have diverged
+    Merge,
+
+    /// Rebase the local commits onto the fetched commits when the histories have diverged
+    Rebase,
+}
+
+/// Options for pulling with `Repo::update`
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let opts = git::PullOptions::new().strategy(git::PullStrategy::Merge).message("Merge upstream");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PullOptions {
+    strategy: PullStrategy,  // Strategy for diverged histories
+    message: Option<String>, // Merge commit message
+}
+
+impl PullOptions {
+    /// Create new pull options that only fast-forward.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::PullOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the strategy used to reconcile diverged histories.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::PullOptions::new().strategy(git::PullStrategy::Rebase);
+    /// assert_eq!(opts.strategy_val(), git::PullStrategy::Rebase);
+    /// ```
+    pub fn strategy(mut self, strategy: PullStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Get the strategy used to reconcile diverged histories.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::PullOptions::new().strategy_val(), git::PullStrategy::FastForwardOnly);
+    /// ```
+    pub fn strategy_val(&self) -> PullStrategy {
+        self.strategy
+    }
+
+    /// Set the merge commit message for the merge strategy rather than the default e.g.
+    /// `Merge branch 'master' of <url>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::PullOptions::new().message("Merge upstream");
+    /// assert_eq!(opts.message_val(), Some("Merge upstream"));
+    /// ```
+    pub fn message<T: AsRef<str>>(mut self, message: T) -> Self {
+        self.message = Some(message.as_ref().to_string());
+        self
+    }
+
+    /// Get the merge commit message if set.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::PullOptions::new().message_val(), None);
+    /// ```
+    pub fn message_val(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+/// Merge the given branch, or any other revision, into HEAD using the configured `user.name`
+/// and `user.email` identity for merge commits. Merging is refused if the worktree has
+/// uncommitted changes. On conflicts `Error::MergeConflicts` is returned and the repo is left mid
+/// merge with the conflicts in the worktree and index the same as `git merge`. Once resolved the
+/// merge is completed with `merge_finish`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_merge_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::branch_create(&tmpdir, "old", Some("HEAD~2"), true).is_ok());
+/// assert!(matches!(git::merge(&tmpdir, "master", &git::MergeOptions::new()).unwrap(), git::MergeOutcome::FastForward(_)));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn merge<T, U>(path: T, branch: U, opts: &MergeOptions) -> Result<MergeOutcome>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let (path, branch) = (path.as_ref(), branch.as_ref());
+    let repo = Repository::open(path)?;
+    if is_dirty(path)? {
+        return Err(Error::dirty_worktree(path));
+    }
+    let theirs = repo.find_commit(resolve_commit(&repo, branch)?)?;
+    let annotated = repo.find_annotated_commit(theirs.id())?;
+    let (analysis, _) = repo.merge_analysis(&[&annotated])?;
+    if analysis.is_up_to_date() {
+        return Ok(MergeOutcome::UpToDate);
+    }
+
+    // Fast-forward by updating the worktree then moving HEAD, or the branch it points to
+    if analysis.is_fast_forward() && opts.mode != MergeMode::NoFastForward {
+        repo.checkout_tree(theirs.as_object(), Some(CheckoutBuilder::new().safe()))?;
+        let reflog = format!("merge {}: Fast-forward", branch);
+        match repo.find_reference("HEAD")?.symbolic_target() {
+            Some(target) => repo.reference(target, theirs.id(), true, &reflog)?,
+            None => repo.reference("HEAD", theirs.id(), true, &reflog)?,
+        };
+        post_merge(&repo, opts.hooks);
+        return Ok(MergeOutcome::FastForward(theirs.id()));
+    }
+    if opts.mode == MergeMode::FastForwardOnly {
+        return Err(Error::FastForwardOnly);
+    }
+
+    // Merge into the index and worktree then commit the result
+    let head = repo.head()?.peel_to_commit()?;
+    let message = opts.message.clone().unwrap_or_else(|| merge_message(&repo, branch));
+    repo.merge(&[&annotated], None, Some(CheckoutBuilder::new().safe().allow_conflicts(true).conflict_style_merge(true)))?;
+    let mut index = repo.index()?;
+    if index.has_conflicts() {
+        // Record the message for `merge_finish` in place of libgit2's
+        sys::write(repo.path().mash("MERGE_MSG"), format!("{}\n", message))?;
+        return Err(conflicts_error(&index)?);
+    }
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let sig = repo.signature()?;
+    let message = match merge_commit_hooks(&repo, opts.hooks, &message) {
+        Ok(message) => message,
+        Err(err) => {
+            sys::write(repo.path().mash("MERGE_MSG"), format!("{}\n", message))?;
+            return Err(err);
+        },
+    };
+    let oid = write_commit(&repo, &sig, &sig, &message, &tree, &[&head, &theirs], None, false)?;
+    repo.cleanup_state()?;
+    post_merge(&repo, opts.hooks);
+    Ok(MergeOutcome::Merged(oid))
+}
+
+// Run the hooks for a merge commit returning the message as possibly changed by them
+fn merge_commit_hooks(repo: &Repository, mode: HookMode, message: &str) -> Result<String> {
+    if mode == HookMode::Run {
+        run_hook(repo, "pre-merge-commit", &[] as &[&str])?;
+    }
+    run_message_hooks(repo, mode, message, &["merge"])
+}
+
+// Run the post-merge hook when requested ignoring its outcome the same as git does
+fn post_merge(repo: &Repository, mode: HookMode) {
+    if mode != HookMode::Skip {
+        run_hook(repo, "post-merge", &["0"]).ok();
+    }
+}
+
+/// Complete a merge stopped on conflicts once they've all been resolved and staged by creating the
+/// merge commit. The message defaults to the one prepared by `merge` e.g. `Merge branch 'foo'`.
+/// Returns the id of the merge commit.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_merge_finish_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::merge_finish(&tmpdir, None).is_err());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn merge_finish<T: AsRef<Path>>(path: T, message: Option<&str>) -> Result<Oid> {
+    let mut repo = Repository::open(path.as_ref())?;
+    if repo.state() != RepositoryState::Merge {
+        return Err(git2::Error::new(ErrorCode::NotFound, ErrorClass::Merge, "no merge in progress").into());
+    }
+    let mut index = repo.index()?;
+    if index.has_conflicts() {
+        return Err(Error::merge_conflicts(&Conflicts::from_index(&index)?.paths()));
+    }
+
+    // Merge commit parents are HEAD followed by the merged in commits
+    let mut oids = vec![];
+    repo.mergehead_foreach(|oid| {
+        oids.push(*oid);
+        true
+    })?;
+    let head = repo.head()?.peel_to_commit()?;
+    let mut parents = vec![head];
+    for oid in oids {
+        parents.push(repo.find_commit(oid)?);
+    }
+
+    // Prepared message without git's commented out conflict listing
+    let message = match message {
+        Some(message) => message.to_string(),
+        None => repo.message()?.lines().filter(|x| !x.starts_with('#')).collect::<Vec<_>>().join("\n").trim_end().to_string(),
+    };
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let sig = repo.signature()?;
+    let oid = write_commit(&repo, &sig, &sig, &message, &tree, &parents.iter().collect::<Vec<_>>(), None, false)?;
+    repo.cleanup_state()?;
+    Ok(oid)
+}
+
+/// Squash merge the given branch, or any other revision, into HEAD as a single commit with HEAD
+/// as its only parent, the same as `git merge --squash` followed by `git commit`. The message
+/// defaults to one composed from the squashed commits the same as git's. The configured
+/// `user.name` and `user.email` are used as the author and committer. Squashing is refused if
+/// the worktree has uncommitted changes. On conflicts `Error::MergeConflicts` is returned with
+/// the conflicts left in the worktree and index. Returns the id of the new commit or None if
+/// HEAD already contains the given revision.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_merge_squash_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "test").unwrap();
+/// repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+/// assert!(git::branch_create(&tmpdir, "old", Some("HEAD~2"), true).is_ok());
+/// assert!(git::merge_squash(&tmpdir, "master", Some("Squash master")).unwrap().is_some());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn merge_squash<T, U>(path: T, branch: U, message: Option<&str>) -> Result<Option<Oid>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let path = path.as_ref();
+    let repo = Repository::open(path)?;
+    let message = match squash(&repo, path, branch.as_ref())? {
+        Some(composed) => message.map(|x| x.to_string()).unwrap_or(composed),
+        None => return Ok(None),
+    };
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    let head = repo.head()?.peel_to_commit()?;
+    let sig = repo.signature()?;
+    Ok(Some(write_commit(&repo, &sig, &sig, &message, &tree, &[&head], None, false)?))
+}
+
+/// Squash merge the given branch, or any other revision, the same as `merge_squash` but only
+/// stage the changes as a single pending change rather than committing them, the same as `git
+/// merge --squash`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_merge_squash_no_commit_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::branch_create(&tmpdir, "old", Some("HEAD~2"), true).is_ok());
+/// assert!(git::merge_squash_no_commit(&tmpdir, "master").is_ok());
+/// assert_eq!(git::is_dirty(&tmpdir).unwrap(), true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn merge_squash_no_commit<T, U>(path: T, branch: U) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let path = path.as_ref();
+    let repo = Repository::open(path)?;
+    squash(&repo, path, branch.as_ref())?;
+    Ok(())
+}
+
+// Merge the given revision into the index and worktree without recording a merge returning the
+// message composed from the squashed commits or None if there was nothing to merge
+fn squash(repo: &Repository, path: &Path, branch: &str) -> Result<Option<String>> {
+    if is_dirty(path)? {
+        return Err(Error::dirty_worktree(path));
+    }
+    let theirs = resolve_commit(repo, branch)?;
+    let annotated = repo.find_annotated_commit(theirs)?;
+    let (analysis, _) = repo.merge_analysis(&[&annotated])?;
+    if analysis.is_up_to_date() {
+        return Ok(None);
+    }
+
+    // Compose the message from the squashed commits newest first the same as git
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    revwalk.push(theirs)?;
+    revwalk.hide(repo.head()?.peel_to_commit()?.id())?;
+    let mut message = "Squashed commit of the following:\n".to_string();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        let author = commit.author();
+        message += &format!("\ncommit {}\nAuthor: {} <{}>\n\n", commit.id(), author.name().unwrap_or_default(), author.email().unwrap_or_default());
+        for line in commit.message().unwrap_or_default().trim_end().lines() {
+            message += &format!("    {}\n", line).replace("    \n", "\n");
+        }
+    }
+
+    // Merge then drop the merge state so that committing doesn't record a merge
+    repo.merge(&[&annotated], None, Some(CheckoutBuilder::new().safe().allow_conflicts(true).conflict_style_merge(true)))?;
+    repo.cleanup_state()?;
+    let index = repo.index()?;
+    if index.has_conflicts() {
+        return Err(conflicts_error(&index)?);
+    }
+    Ok(Some(message))
+}
+
+// Default merge commit message the same as git's
+fn merge_message(repo: &Repository, rev: &str) -> String {
+    let kind = if repo.find_branch(rev, BranchType::Local).is_ok() {
+        "branch"
+    } else if repo.find_branch(rev, BranchType::Remote).is_ok() {
+        "remote-tracking branch"
+    } else if repo.find_reference(&format!("refs/tags/{}", rev)).is_ok() {
+        "tag"
+    } else {
+        "commit"
+    };
+    let mut message = format!("Merge {} '{}'", kind, rev);
+    if let Some(head) = repo.head().ok().filter(|x| x.is_branch()).and_then(|x| x.shorthand().map(|y| y.to_string())) {
+        if head != "master" && head != "main" {
+            message += &format!(" into {}", head);
+        }
+    }
+    message
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Extract the test repo with a test identity
+    fn init(path: &Path) -> git2::Repository {
+        assert!(sys::remove_all(path).is_ok());
+        assert!(tar::extract_all(path.mash("../../alpine-base.tgz"), path).is_ok());
+        let repo = git2::Repository::open(path).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        repo
+    }
+
+    // Commit the given content to the given file
+    fn commit(path: &Path, file: &str, content: &str) -> git2::Oid {
+        assert!(sys::write(path.mash(file), content).is_ok());
+        git::CommitBuilder::new(path).stage_all(true).message(format!("Update {}", file)).commit().unwrap()
+    }
+
+    // Checkout the given branch
+    fn checkout(path: &Path, branch: &str) {
+        assert!(git::Repo::new(path).unwrap().checkout(branch, false).is_ok());
+    }
+
+    #[test]
+    fn test_merge() {
+        let tmpdir = setup("git_merge");
+        let repo = init(&tmpdir);
+        let opts = git::MergeOptions::new();
+
+        // Fast-forward and up to date
+        assert!(git::branch_create(&tmpdir, "feature", None, true).is_ok());
+        let oid = commit(&tmpdir, "foo", "foo");
+        checkout(&tmpdir, "master");
+        assert_eq!(git::merge(&tmpdir, "feature", &opts).unwrap(), git::MergeOutcome::FastForward(oid));
+        assert_eq!(repo.head().unwrap().target(), Some(oid));
+        assert_eq!(repo.head().unwrap().shorthand(), Some("master"));
+        assert_eq!(sys::readstring(tmpdir.mash("foo")).unwrap(), "foo");
+        assert_eq!(repo.reflog("HEAD").unwrap().get(0).unwrap().message(), Some("merge feature: Fast-forward"));
+        assert_eq!(git::merge(&tmpdir, "feature", &opts).unwrap(), git::MergeOutcome::UpToDate);
+
+        // No fast-forward
+        checkout(&tmpdir, "feature");
+        let oid = commit(&tmpdir, "bar", "bar");
+        checkout(&tmpdir, "master");
+        let head = repo.head().unwrap().target().unwrap();
+        let outcome = git::merge(&tmpdir, "feature", &git::MergeOptions::new().mode(git::MergeMode::NoFastForward)).unwrap();
+        let merge = match outcome {
+            git::MergeOutcome::Merged(merge) => repo.find_commit(merge).unwrap(),
+            _ => panic!("expected a merge commit"),
+        };
+        assert_eq!(merge.parent_ids().collect::<Vec<_>>(), vec![head, oid]);
+        assert_eq!(merge.message(), Some("Merge branch 'feature'"));
+        assert_eq!(merge.author().name(), Some("test"));
+        assert_eq!(repo.head().unwrap().target(), Some(merge.id()));
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+
+        // Fast-forward only with diverged histories
+        checkout(&tmpdir, "feature");
+        let theirs = commit(&tmpdir, "baz", "baz");
+        checkout(&tmpdir, "master");
+        let ours = commit(&tmpdir, "qux", "qux");
+        let err = git::merge(&tmpdir, "feature", &git::MergeOptions::new().mode(git::MergeMode::FastForwardOnly)).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::FastForwardOnly.to_string());
+        assert_eq!(repo.head().unwrap().target(), Some(ours));
+
+        // Diverged histories with a message
+        let outcome = git::merge(&tmpdir, "feature", &git::MergeOptions::new().message("Merge the feature")).unwrap();
+        let merge = match outcome {
+            git::MergeOutcome::Merged(merge) => repo.find_commit(merge).unwrap(),
+            _ => panic!("expected a merge commit"),
+        };
+        assert_eq!(merge.parent_ids().collect::<Vec<_>>(), vec![ours, theirs]);
+        assert_eq!(merge.message(), Some("Merge the feature"));
+        assert_eq!(sys::readstring(tmpdir.mash("baz")).unwrap(), "baz");
+        assert_eq!(sys::readstring(tmpdir.mash("qux")).unwrap(), "qux");
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+
+        // Default message for other branches
+        assert!(git::branch_create(&tmpdir, "other", Some("HEAD~1"), true).is_ok());
+        commit(&tmpdir, "other", "other");
+        let outcome = git::merge(&tmpdir, "feature", &git::MergeOptions::new().mode(git::MergeMode::NoFastForward)).unwrap();
+        let merge = match outcome {
+            git::MergeOutcome::Merged(merge) => repo.find_commit(merge).unwrap(),
+            _ => panic!("expected a merge commit"),
+        };
+        assert_eq!(merge.message(), Some("Merge branch 'feature' into other"));
+        checkout(&tmpdir, "master");
+
+        // Conflicts leave the repo mid merge
+        checkout(&tmpdir, "feature");
+        commit(&tmpdir, "README.md", "theirs");
+        checkout(&tmpdir, "master");
+        let head = commit(&tmpdir, "README.md", "ours");
+        let err = git::merge(&tmpdir, "feature", &opts).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::merge_conflicts(&["README.md"]).to_string());
+        assert_eq!(repo.state(), git2::RepositoryState::Merge);
+        assert_eq!(repo.head().unwrap().target(), Some(head));
+        assert_eq!(sys::readstring(tmpdir.mash("README.md")).unwrap().contains("<<<<<<<"), true);
+
+        // Dirty worktree and missing branch
+        let err = git::merge(&tmpdir, "feature", &opts).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::dirty_worktree(&tmpdir).to_string());
+        repo.cleanup_state().unwrap();
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force())).unwrap();
+        assert!(git::merge(&tmpdir, "missing", &opts).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_merge_hooks() {
+        let tmpdir = setup("git_merge_hooks");
+        let repo = init(&tmpdir);
+        let log = tmpdir.mash(".git/hooks.log");
+        for name in &["prepare-commit-msg", "post-merge"] {
+            let script = format!("#!/bin/sh\necho \"{} $2 $1\" >> {}\n", name, log.display());
+            assert!(git::hook_install(&tmpdir, name, script).is_ok());
+        }
+        assert!(git::hook_install(&tmpdir, "pre-merge-commit", "#!/bin/sh\necho rejected; exit 1\n").is_ok());
+        let opts = git::MergeOptions::new().hooks(git::HookMode::Run);
+
+        // Fast-forward only runs post-merge
+        assert!(git::branch_create(&tmpdir, "feature", None, true).is_ok());
+        let oid = commit(&tmpdir, "foo", "foo");
+        checkout(&tmpdir, "master");
+        assert_eq!(git::merge(&tmpdir, "feature", &opts).unwrap(), git::MergeOutcome::FastForward(oid));
+        assert_eq!(sys::readlines(&log).unwrap(), vec!["post-merge  0"]);
+
+        // Rejected merge commit is left to be finished
+        assert!(sys::remove(&log).is_ok());
+        checkout(&tmpdir, "feature");
+        commit(&tmpdir, "bar", "bar");
+        checkout(&tmpdir, "master");
+        let head = commit(&tmpdir, "baz", "baz");
+        let err = git::merge(&tmpdir, "feature", &opts).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::hook_failed("pre-merge-commit", "rejected").to_string());
+        assert_eq!(repo.state(), git2::RepositoryState::Merge);
+        assert_eq!(repo.head().unwrap().target(), Some(head));
+        assert_eq!(log.exists(), false);
+        let merge = repo.find_commit(git::merge_finish(&tmpdir, None).unwrap()).unwrap();
+        assert_eq!(merge.message(), Some("Merge branch 'feature'"));
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+
+        // Verification hooks are bypassed
+        checkout(&tmpdir, "feature");
+        commit(&tmpdir, "qux", "qux");
+        checkout(&tmpdir, "master");
+        let outcome = git::merge(&tmpdir, "feature", &opts.clone().hooks(git::HookMode::NoVerify)).unwrap();
+        assert!(matches!(outcome, git::MergeOutcome::Merged(_)));
+        let msg = tmpdir.mash(".git/COMMIT_EDITMSG");
+        assert_eq!(
+            sys::readlines(&log).unwrap(),
+            vec![
+                format!("prepare-commit-msg merge {}", msg.display()),
+                "post-merge  0".to_string()
+            ]
+        );
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_merge_squash() {
+        let tmpdir = setup("git_merge_squash");
+        let repo = init(&tmpdir);
+
+        // Squash into a single commit with a composed message
+        assert!(git::branch_create(&tmpdir, "feature", None, true).is_ok());
+        let foo = commit(&tmpdir, "foo", "foo");
+        let bar = commit(&tmpdir, "bar", "bar");
+        checkout(&tmpdir, "master");
+        let head = commit(&tmpdir, "baz", "baz");
+        let oid = git::merge_squash(&tmpdir, "feature", None).unwrap().unwrap();
+        let squashed = repo.find_commit(oid).unwrap();
+        assert_eq!(squashed.parent_ids().collect::<Vec<_>>(), vec![head]);
+        let expected = format!("Squashed commit of the following:\n\ncommit {}\nAuthor: test <test@example.com>\n\n    Update bar\n\ncommit {}\nAuthor: test <test@example.com>\n\n    Update foo\n", bar, foo);
+        assert_eq!(squashed.message().unwrap(), expected);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("master"));
+        assert_eq!(repo.head().unwrap().target(), Some(oid));
+        assert_eq!(sys::readstring(tmpdir.mash("foo")).unwrap(), "foo");
+        assert_eq!(sys::readstring(tmpdir.mash("bar")).unwrap(), "bar");
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+
+        // Nothing to squash when HEAD contains the branch
+        assert_eq!(git::merge_squash(&tmpdir, "HEAD~1", None).unwrap(), None);
+
+        // Staged only with a fast-forwardable branch
+        assert!(git::branch_create(&tmpdir, "other", None, true).is_ok());
+        commit(&tmpdir, "qux", "qux");
+        checkout(&tmpdir, "master");
+        assert!(git::merge_squash_no_commit(&tmpdir, "other").is_ok());
+        assert_eq!(repo.head().unwrap().target(), Some(oid));
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+        assert_eq!(repo.status_file(Path::new("qux")).unwrap(), git2::Status::INDEX_NEW);
+        let oid = git::commit(&tmpdir, "Squash other", None).unwrap();
+        assert_eq!(repo.find_commit(oid).unwrap().parent_count(), 1);
+
+        // Custom message
+        assert!(git::branch_create(&tmpdir, "custom", Some("HEAD~1"), true).is_ok());
+        commit(&tmpdir, "custom", "custom");
+        checkout(&tmpdir, "master");
+        let oid = git::merge_squash(&tmpdir, "custom", Some("Add custom")).unwrap().unwrap();
+        assert_eq!(repo.find_commit(oid).unwrap().message(), Some("Add custom"));
+
+        // Conflicts and dirty worktree
+        assert!(git::branch_create(&tmpdir, "conflict", Some("HEAD~1"), true).is_ok());
+        commit(&tmpdir, "README.md", "theirs");
+        checkout(&tmpdir, "master");
+        commit(&tmpdir, "README.md", "ours");
+        let err = git::merge_squash(&tmpdir, "conflict", None).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::merge_conflicts(&["README.md"]).to_string());
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+        let err = git::merge_squash_no_commit(&tmpdir, "conflict").unwrap_err();
+        assert_eq!(err.to_string(), git::Error::dirty_worktree(&tmpdir).to_string());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
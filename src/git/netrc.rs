@@ -0,0 +1,104 @@
+use super::auth::host;
+use fungus::prelude::*;
+use std::env;
+
+// Machine entry parsed from a netrc file. The `default` entry has no name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Machine {
+    pub(crate) name: Option<String>,     // Host name the entry applies to
+    pub(crate) login: Option<String>,    // Login name for the host
+    pub(crate) password: Option<String>, // Password for the host
+}
+
+// Parse the given netrc content into its machine entries. Macro definitions are skipped and
+// unknown tokens are ignored the same way ftp and curl do.
+pub(crate) fn parse<T: AsRef<str>>(data: T) -> Vec<Machine> {
+    let mut machines: Vec<Machine> = Vec::new();
+    let mut lines = data.as_ref().lines();
+    while let Some(line) = lines.next() {
+        let mut tokens = line.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match token {
+                "machine" => machines.push(Machine { name: tokens.next().map(|x| x.to_string()), ..Default::default() }),
+                "default" => machines.push(Machine::default()),
+                "login" => {
+                    if let Some(machine) = machines.last_mut() {
+                        machine.login = tokens.next().map(|x| x.to_string());
+                    }
+                },
+                "password" => {
+                    if let Some(machine) = machines.last_mut() {
+                        machine.password = tokens.next().map(|x| x.to_string());
+                    }
+                },
+                "account" => {
+                    tokens.next();
+                },
+                "macdef" => {
+                    // Macro bodies run until the next blank line
+                    for line in &mut lines {
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                    }
+                    break;
+                },
+                _ => {},
+            }
+        }
+    }
+    machines
+}
+
+// Find the entry for the given host falling back on the `default` entry
+pub(crate) fn find<'a>(machines: &'a [Machine], host: &str) -> Option<&'a Machine> {
+    machines.iter().find(|x| x.name.as_deref() == Some(host)).or_else(|| machines.iter().find(|x| x.name.is_none()))
+}
+
+// Returns the netrc file to use i.e. `$NETRC` else `~/.netrc` else `~/_netrc`
+pub(crate) fn path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("NETRC") {
+        return Some(PathBuf::from(path));
+    }
+    let home = user::home_dir().ok()?;
+    [".netrc", "_netrc"].iter().map(|x| home.mash(x)).find(|x| x.exists())
+}
+
+// Lookup the login and password for the given url from the user's netrc file
+pub(crate) fn lookup(url: &str) -> Option<(Option<String>, String)> {
+    let data = sys::readstring(path()?).ok()?;
+    let machines = parse(data);
+    let machine = find(&machines, host(url)?)?;
+    Some((machine.login.clone(), machine.password.clone()?))
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_netrc_parse() {
+        let data = "machine github.com login foo password bar\n\
+                    macdef init\n  cd /pub\n  binary\n\n\
+                    machine gitlab.com\n  login foo2\n  account acct\n  password bar2\n\
+                    default login anonymous password guest\n";
+        let machines = parse(data);
+        assert_eq!(machines.len(), 3);
+        assert_eq!(machines[0].name, Some("github.com".to_string()));
+        assert_eq!(machines[0].login, Some("foo".to_string()));
+        assert_eq!(machines[0].password, Some("bar".to_string()));
+        assert_eq!(machines[1].name, Some("gitlab.com".to_string()));
+        assert_eq!(machines[1].login, Some("foo2".to_string()));
+        assert_eq!(machines[1].password, Some("bar2".to_string()));
+        assert_eq!(machines[2].name, None);
+        assert_eq!(machines[2].login, Some("anonymous".to_string()));
+        assert_eq!(machines[2].password, Some("guest".to_string()));
+
+        // Find with default fallback
+        assert_eq!(find(&machines, "gitlab.com").unwrap().login, Some("foo2".to_string()));
+        assert_eq!(find(&machines, "example.com").unwrap().login, Some("anonymous".to_string()));
+        assert_eq!(find(&machines[..2], "example.com"), None);
+    }
+}
@@ -0,0 +1,373 @@
+use super::{fetch_opts, resolve_commit, Repo};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{ErrorCode, Oid, Repository};
+use std::cell::RefCell;
+
+/// Note as returned by `notes`
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoteInfo {
+    pub oid: Oid,        // Id of the note's blob
+    pub target: Oid,     // Id of the object the note is attached to
+    pub message: String, // Note content
+}
+
+/// List the notes in the given notes ref, or the default `refs/notes/commits` unless changed by
+/// `core.notesRef` when None is given, the same as `git notes list`. Notes refs may be given in
+/// full or short form e.g. `review` for `refs/notes/review`. Returns an empty list if the notes
+/// ref doesn't exist yet.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_notes_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "test").unwrap();
+/// repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+/// assert!(git::note_add(&tmpdir, "HEAD", "Reviewed", Some("review"), false).is_ok());
+/// assert_eq!(git::notes(&tmpdir, Some("review")).unwrap().len(), 1);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn notes<T: AsRef<Path>>(path: T, notes_ref: Option<&str>) -> Result<Vec<NoteInfo>> {
+    let repo = Repository::open(path.as_ref())?;
+    let notes_ref = notes_refname(&repo, notes_ref)?;
+    let iter = match repo.notes(Some(&notes_ref)) {
+        Ok(iter) => iter,
+        Err(err) if err.code() == ErrorCode::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+    let mut notes = vec![];
+    for item in iter {
+        let (oid, target) = item?;
+        let note = repo.find_note(Some(&notes_ref), target)?;
+        notes.push(NoteInfo { oid, target, message: note.message().unwrap_or_default().to_string() });
+    }
+    Ok(notes)
+}
+
+/// Get the note attached to the given revision in the given notes ref, or the default notes ref
+/// when None is given, the same as `git notes show`. Returns None if there is no note.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_note_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::note(&tmpdir, "HEAD", None).unwrap(), None);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn note<T, U>(path: T, rev: U, notes_ref: Option<&str>) -> Result<Option<String>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let target = resolve_commit(&repo, rev.as_ref())?;
+    find_note(&repo, &notes_refname(&repo, notes_ref)?, target)
+}
+
+/// Attach a note with the given message to the given revision in the given notes ref, or the
+/// default notes ref when None is given, the same as `git notes add -m`. The configured
+/// `user.name` and `user.email` are used for the notes commit. Fails if the revision already has
+/// a note unless `force` is set in which case it is replaced. Returns the id of the note's blob.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_note_add_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "test").unwrap();
+/// repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+/// assert!(git::note_add(&tmpdir, "HEAD", "Tested-by: foo", None, false).is_ok());
+/// assert_eq!(git::note(&tmpdir, "HEAD", None).unwrap(), Some("Tested-by: foo\n".to_string()));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn note_add<T, U, V>(path: T, rev: U, message: V, notes_ref: Option<&str>, force: bool) -> Result<Oid>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    if message.as_ref().trim().is_empty() {
+        return Err(Error::NoMessageWasFound);
+    }
+    let repo = Repository::open(path.as_ref())?;
+    let target = resolve_commit(&repo, rev.as_ref())?;
+    write_note(&repo, &notes_refname(&repo, notes_ref)?, target, message.as_ref(), force)
+}
+
+/// Append the given message to the note attached to the given revision in the given notes ref,
+/// or the default notes ref when None is given, separated by a blank line the same as `git notes
+/// append -m`. The note is created if the revision doesn't have one yet. The configured
+/// `user.name` and `user.email` are used for the notes commit. Returns the id of the note's blob.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_note_append_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "test").unwrap();
+/// repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+/// assert!(git::note_append(&tmpdir, "HEAD", "Tested-by: foo", None).is_ok());
+/// assert!(git::note_append(&tmpdir, "HEAD", "Tested-by: bar", None).is_ok());
+/// assert_eq!(git::note(&tmpdir, "HEAD", None).unwrap(), Some("Tested-by: foo\n\nTested-by: bar\n".to_string()));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn note_append<T, U, V>(path: T, rev: U, message: V, notes_ref: Option<&str>) -> Result<Oid>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    if message.as_ref().trim().is_empty() {
+        return Err(Error::NoMessageWasFound);
+    }
+    let repo = Repository::open(path.as_ref())?;
+    let target = resolve_commit(&repo, rev.as_ref())?;
+    let notes_ref = notes_refname(&repo, notes_ref)?;
+    let message = match find_note(&repo, &notes_ref, target)? {
+        Some(note) => format!("{}\n\n{}", note.trim_end(), message.as_ref()),
+        None => message.as_ref().to_string(),
+    };
+    write_note(&repo, &notes_ref, target, &message, true)
+}
+
+/// Remove the note attached to the given revision in the given notes ref, or the default notes
+/// ref when None is given, the same as `git notes remove`. Returns true if a note was removed or
+/// false if the revision didn't have one.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_note_remove_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "test").unwrap();
+/// repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+/// assert!(git::note_add(&tmpdir, "HEAD", "Reviewed", None, false).is_ok());
+/// assert_eq!(git::note_remove(&tmpdir, "HEAD", None).unwrap(), true);
+/// assert_eq!(git::note_remove(&tmpdir, "HEAD", None).unwrap(), false);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn note_remove<T, U>(path: T, rev: U, notes_ref: Option<&str>) -> Result<bool>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let target = resolve_commit(&repo, rev.as_ref())?;
+    let notes_ref = notes_refname(&repo, notes_ref)?;
+    if find_note(&repo, &notes_ref, target)?.is_none() {
+        return Ok(false);
+    }
+    let sig = repo.signature()?;
+    repo.note_delete(target, Some(&notes_ref), &sig, &sig)?;
+    Ok(true)
+}
+
+impl<'a> Repo<'a> {
+    /// Fetch the given notes ref, or the default notes ref when None is given, from the repo's
+    /// remote, `origin` by default, into the same local notes ref using the repo's network
+    /// settings and transfer progress callback, the same as `git fetch <remote>
+    /// refs/notes/<ref>:refs/notes/<ref>`. Notes aren't fetched by default so this is needed to
+    /// see notes others have pushed. Local notes that have diverged from the remote's are
+    /// replaced.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_fetch_notes_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+    /// let local = tmpdir.mash("local");
+    /// assert!(git::Repo::new(&local).unwrap().url(tmpdir.mash("src").to_string().unwrap()).clone().is_ok());
+    /// assert!(git::Repo::new(&local).unwrap().fetch_notes(Some("review")).is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn fetch_notes(mut self, notes_ref: Option<&str>) -> Result<()> {
+        let xfer = self.xfer_progress.take().map(RefCell::new);
+        let repo = Repository::open(self.path_val())?;
+        let notes_ref = notes_refname(&repo, notes_ref)?;
+        let refspec = format!("+{0}:{0}", notes_ref);
+        let mut remote = repo.find_remote(self.remote_val())?;
+        self.net.auth.settle(|| remote.fetch(&[&refspec], Some(&mut fetch_opts(&self.net, xfer.as_ref())), None))?;
+        Ok(())
+    }
+
+    /// Push the given notes ref, or the default notes ref when None is given, to the same notes
+    /// ref on the repo's remote, `origin` by default, using the repo's network settings and
+    /// transfer progress callback, the same as `git push <remote> refs/notes/<ref>`. Pushing
+    /// notes that have diverged from the remote's is rejected so fetch and re-apply them first.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_push_notes_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+    /// let remote = tmpdir.mash("remote");
+    /// assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+    /// let local = tmpdir.mash("local");
+    /// assert!(git::Repo::new(&local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+    /// let repo = git2::Repository::open(&local).unwrap();
+    /// repo.config().unwrap().set_str("user.name", "test").unwrap();
+    /// repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+    /// assert!(git::note_add(&local, "HEAD", "Reviewed", Some("review"), false).is_ok());
+    /// assert!(git::Repo::new(&local).unwrap().push_notes(Some("review")).is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn push_notes(mut self, notes_ref: Option<&str>) -> Result<()> {
+        let repo = Repository::open(self.path_val())?;
+        let notes_ref = notes_refname(&repo, notes_ref)?;
+        self.push(&[format!("{0}:{0}", notes_ref)])
+    }
+}
+
+// Returns the full name of the given notes ref expanded the same way git does e.g. `review` to
+// `refs/notes/review`, or the repo's default notes ref when None is given
+fn notes_refname(repo: &Repository, notes_ref: Option<&str>) -> Result<String> {
+    Ok(match notes_ref {
+        Some(name) if name.starts_with("refs/notes/") => name.to_string(),
+        Some(name) if name.starts_with("notes/") => format!("refs/{}", name),
+        Some(name) => format!("refs/notes/{}", name),
+        None => repo.note_default_ref()?,
+    })
+}
+
+// Returns the note attached to the given object in the given notes ref if any
+fn find_note(repo: &Repository, notes_ref: &str, target: Oid) -> Result<Option<String>> {
+    match repo.find_note(Some(notes_ref), target) {
+        Ok(note) => Ok(Some(note.message().unwrap_or_default().to_string())),
+        Err(err) if err.code() == ErrorCode::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Write the given note with a trailing newline the same as git does
+fn write_note(repo: &Repository, notes_ref: &str, target: Oid, message: &str, force: bool) -> Result<Oid> {
+    let sig = repo.signature()?;
+    let message = format!("{}\n", message.trim_end());
+    Ok(repo.note(&sig, &sig, Some(notes_ref), target, &message, force)?)
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Set a test identity on the given repo
+    fn identity(path: &Path) -> git2::Repository {
+        let repo = git2::Repository::open(path).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        repo
+    }
+
+    #[test]
+    fn test_notes() {
+        let tmpdir = setup("git_notes");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = identity(&tmpdir);
+        let head = repo.head().unwrap().target().unwrap();
+        let parent = repo.revparse_single("HEAD~1").unwrap().id();
+        let show = |args: &[&str]| {
+            let output = Command::new("git").arg("notes").args(args).current_dir(&tmpdir).output().unwrap();
+            String::from_utf8_lossy(&output.stdout).to_string()
+        };
+        assert!(git::notes(&tmpdir, None).unwrap().is_empty());
+        assert_eq!(git::note(&tmpdir, "HEAD", None).unwrap(), None);
+
+        // Default notes ref is readable by git
+        let oid = git::note_add(&tmpdir, "HEAD", "Reviewed-by: foo", None, false).unwrap();
+        assert_eq!(show(&["show", "HEAD"]), "Reviewed-by: foo\n");
+        assert!(git::note_add(&tmpdir, "HEAD", "Reviewed-by: bar", None, false).is_err());
+        assert_eq!(git::notes(&tmpdir, None).unwrap(), vec![git::NoteInfo { oid, target: head, message: "Reviewed-by: foo\n".to_string() }]);
+        assert!(git::note_add(&tmpdir, "HEAD", "Reviewed-by: bar", None, true).is_ok());
+        assert_eq!(git::note(&tmpdir, "HEAD", None).unwrap(), Some("Reviewed-by: bar\n".to_string()));
+        assert_eq!(git::note_add(&tmpdir, "HEAD", " ", None, true).unwrap_err().to_string(), git::Error::NoMessageWasFound.to_string());
+
+        // Custom notes refs in short and full form
+        assert!(git::note_append(&tmpdir, "HEAD~1", "CI: passed", Some("review")).is_ok());
+        assert!(git::note_append(&tmpdir, "HEAD~1", "Reviewed-by: foo\n", Some("refs/notes/review")).is_ok());
+        assert_eq!(git::note(&tmpdir, "HEAD~1", Some("notes/review")).unwrap(), Some("CI: passed\n\nReviewed-by: foo\n".to_string()));
+        assert_eq!(show(&["--ref", "review", "show", "HEAD~1"]), "CI: passed\n\nReviewed-by: foo\n");
+        assert_eq!(git::note(&tmpdir, "HEAD~1", None).unwrap(), None);
+        let targets = git::notes(&tmpdir, Some("review")).unwrap().into_iter().map(|x| x.target).collect::<Vec<_>>();
+        assert_eq!(targets, vec![parent]);
+
+        // Default notes ref changed by config
+        repo.config().unwrap().set_str("core.notesRef", "refs/notes/review").unwrap();
+        assert_eq!(git::notes(&tmpdir, None).unwrap().len(), 1);
+        repo.config().unwrap().remove("core.notesRef").unwrap();
+
+        // Remove
+        assert_eq!(git::note_remove(&tmpdir, "HEAD", None).unwrap(), true);
+        assert_eq!(git::note_remove(&tmpdir, "HEAD", None).unwrap(), false);
+        assert_eq!(show(&["list"]), "");
+        assert!(git::note(&tmpdir, "missing", None).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_notes_fetch_push() {
+        let tmpdir = setup("git_notes_fetch_push");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+        let remote = tmpdir.mash("remote");
+        assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        let (local1, local2) = (tmpdir.mash("local1"), tmpdir.mash("local2"));
+        for local in &[&local1, &local2] {
+            assert!(git::Repo::new(local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+            identity(local);
+        }
+
+        // Missing notes on the remote fetch nothing
+        assert!(git::Repo::new(&local2).unwrap().fetch_notes(Some("review")).is_ok());
+        assert!(git::notes(&local2, Some("review")).unwrap().is_empty());
+
+        // Pushed notes are fetched by others
+        assert!(git::note_add(&local1, "HEAD", "Reviewed-by: foo", Some("review"), false).is_ok());
+        assert!(git::Repo::new(&local1).unwrap().push_notes(Some("review")).is_ok());
+        assert!(git::notes(&remote, Some("review")).unwrap().len() == 1);
+        assert!(git::Repo::new(&local2).unwrap().fetch_notes(Some("review")).is_ok());
+        assert_eq!(git::note(&local2, "HEAD", Some("review")).unwrap(), Some("Reviewed-by: foo\n".to_string()));
+        assert_eq!(git::note(&local2, "HEAD", None).unwrap(), None);
+
+        // Diverged notes are rejected on push
+        assert!(git::note_append(&local2, "HEAD", "Tested-by: bar", Some("review")).is_ok());
+        assert!(git::Repo::new(&local2).unwrap().push_notes(Some("review")).is_ok());
+        assert!(git::note_append(&local1, "HEAD", "Tested-by: baz", Some("review")).is_ok());
+        assert!(git::Repo::new(&local1).unwrap().push_notes(Some("review")).is_err());
+        assert!(git::Repo::new(&local1).unwrap().fetch_notes(Some("review")).is_ok());
+        assert_eq!(git::note(&local1, "HEAD", Some("review")).unwrap(), Some("Reviewed-by: foo\n\nTested-by: bar\n".to_string()));
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,59 @@
+use git2::ProxyOptions;
+
+/// Proxy configuration to use for network operations
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Proxy {
+    /// Don't use a proxy even if one is configured
+    None,
+
+    /// Detect the proxy from git config i.e. `remote.<name>.proxy`, `http.<url>.proxy` and
+    /// `http.proxy` falling back on the `https_proxy` and `http_proxy` environment variables
+    /// honoring `no_proxy` (default)
+    #[default]
+    Auto,
+
+    /// Use the given proxy url e.g. `http://proxy.example.com:8080`
+    Url(String),
+}
+
+impl Proxy {
+    /// Create an explicit proxy configuration from the given proxy url.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Proxy::url("http://foo:8080"), git::Proxy::Url("http://foo:8080".to_string()));
+    /// ```
+    pub fn url<T: AsRef<str>>(url: T) -> Self {
+        Proxy::Url(url.as_ref().to_string())
+    }
+
+    // Build git2's proxy options for this configuration
+    pub(crate) fn options(&self) -> ProxyOptions<'static> {
+        let mut opts = ProxyOptions::new();
+        match self {
+            Proxy::None => {},
+            Proxy::Auto => {
+                opts.auto();
+            },
+            Proxy::Url(url) => {
+                opts.url(url);
+            },
+        }
+        opts
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn test_proxy() {
+        assert_eq!(git::Proxy::default(), git::Proxy::Auto);
+        assert_eq!(git::Proxy::url("http://foo:8080"), git::Proxy::Url("http://foo:8080".to_string()));
+    }
+}
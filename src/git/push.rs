@@ -0,0 +1,594 @@
+use super::{push_opts, push_remote, remote_callbacks, remote_push_urls, Repo, RepoGroup};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{Direction, Oid, Remote, Repository};
+use indicatif::ProgressBar;
+use std::{cell::RefCell, collections::HashMap, thread};
+
+/// Status the remote reported for a single ref during a push
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PushStatus {
+    pub refname: String,          // Remote ref that was updated e.g. `refs/heads/master`
+    pub rejected: Option<String>, // Reason the remote gave for rejecting the update if any
+}
+
+impl PushStatus {
+    /// Returns true if the remote accepted the update for this ref
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let status = git::PushStatus { refname: "refs/heads/master".to_string(), rejected: None };
+    /// assert_eq!(status.is_ok(), true);
+    /// ```
+    pub fn is_ok(&self) -> bool {
+        self.rejected.is_none()
+    }
+}
+
+/// Result of pushing to one of a remote's push urls
+#[derive(Debug)]
+pub struct PushUrlStatus {
+    pub url: String,                     // Push url after any url rewrites were applied
+    pub result: Result<Vec<PushStatus>>, // Status reported for each ref or the error pushing to the url
+}
+
+impl PushUrlStatus {
+    /// Returns true if the push to this url succeeded and every ref was accepted
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let status = git::PushUrlStatus { url: "foo".to_string(), result: Ok(vec![]) };
+    /// assert_eq!(status.is_ok(), true);
+    /// ```
+    pub fn is_ok(&self) -> bool {
+        self.result.as_ref().map(|x| x.iter().all(|x| x.is_ok())).unwrap_or(false)
+    }
+}
+
+impl<'a> Repo<'a> {
+    /// Push the given refspecs to the repo's remote, `origin` by default, using the repo's
+    /// network settings and transfer progress callback, the same as `git push <remote>
+    /// <refspecs>`. Glob refspecs e.g. `refs/notes/*:refs/notes/*` are expanded against the
+    /// local refs. Unlike the other push operations a ref the remote rejects isn't treated as an
+    /// error rather the status the remote reported for each ref is returned.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_push_refs_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+    /// let remote = tmpdir.mash("remote");
+    /// assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+    /// let local = tmpdir.mash("local");
+    /// assert!(git::Repo::new(&local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+    /// let statuses = git::Repo::new(&local).unwrap().push_refs(&["refs/heads/master:refs/heads/release"]).unwrap();
+    /// assert_eq!(statuses[0].refname, "refs/heads/release");
+    /// assert_eq!(statuses[0].is_ok(), true);
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn push_refs<T: AsRef<str>>(mut self, refspecs: &[T]) -> Result<Vec<PushStatus>> {
+        self.push_statuses(refspecs)
+    }
+
+    /// Push the given refspecs to every push url configured for the repo's remote, `origin` by
+    /// default, the same as `git push <remote> <refspecs>` with multiple `remote.<name>.pushurl`
+    /// entries e.g. to publish to GitHub and an internal server at the same time. See
+    /// `remote_add_push_url` to configure them. The remote's url is used if it has no push urls.
+    /// A failure pushing to one url doesn't stop the push to the others rather the result for
+    /// each url is returned in the order they are configured.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_push_urls_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+    /// let local = tmpdir.mash("local");
+    /// assert!(git::Repo::new(&local).unwrap().url(tmpdir.mash("src").to_string().unwrap()).clone().is_ok());
+    /// for name in ["github", "gitea"].iter() {
+    ///     let remote = tmpdir.mash(name);
+    ///     assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+    ///     assert!(git::remote_add_push_url(&local, "origin", remote.to_string().unwrap()).is_ok());
+    /// }
+    /// let results = git::Repo::new(&local).unwrap().push_urls(&["refs/heads/master:refs/heads/release"]).unwrap();
+    /// assert_eq!(results.len(), 2);
+    /// assert!(results.iter().all(|x| x.is_ok()));
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn push_urls<T: AsRef<str>>(mut self, refspecs: &[T]) -> Result<Vec<PushUrlStatus>> {
+        let xfer = self.xfer_progress.take().map(RefCell::new);
+        let repo = Repository::open(self.path_val())?;
+        let refspecs = expand_refspecs(&repo, refspecs)?;
+        let mut results = vec![];
+        for url in remote_push_urls(&repo, self.remote_val())? {
+            let statuses = RefCell::new(vec![]);
+            let result = repo
+                .remote_anonymous(&url)
+                .and_then(|mut x| self.net.auth.settle(|| x.push(&refspecs, Some(&mut push_opts(&self.net, xfer.as_ref(), &statuses)))))
+                .map(|_| statuses.into_inner())
+                .map_err(Error::from);
+            results.push(PushUrlStatus { url, result });
+        }
+        Ok(results)
+    }
+
+    /// Force push the given refspecs to the repo's remote, `origin` by default, guarded by a
+    /// lease on each destination ref, the same as `git push --force-with-lease`. Each refspec is
+    /// paired with the oid the remote ref is expected to have or None to expect the value of its
+    /// remote-tracking ref i.e. where it was last fetched. Use `Oid::zero()` to expect the remote
+    /// ref not to exist. Short destinations e.g. `master` are expanded to their full refname. If
+    /// any remote ref has moved from its expected value nothing is pushed and `Error::RemoteMoved`
+    /// is returned. The push reuses the connection the leases were checked on so a ref that moves
+    /// in the meantime is rejected by the remote and reported in the returned statuses.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_push_with_lease_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+    /// let remote = tmpdir.mash("remote");
+    /// assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+    /// let local = tmpdir.mash("local");
+    /// assert!(git::Repo::new(&local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+    /// assert!(git::branch_create(&local, "rewrite", Some("HEAD~2"), false).is_ok());
+    /// let leases = [("rewrite:master", None)];
+    /// assert!(git::Repo::new(&local).unwrap().push_with_lease(&leases).is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn push_with_lease<T: AsRef<str>>(mut self, leases: &[(T, Option<Oid>)]) -> Result<Vec<PushStatus>> {
+        let xfer = self.xfer_progress.take().map(RefCell::new);
+        let statuses = RefCell::new(vec![]);
+        let repo = Repository::open(self.path_val())?;
+        let remote = repo.find_remote(self.remote_val())?;
+
+        // Check the leases against the refs advertised on the same connection that's pushed
+        // over so the remote only accepts the update if the ref still has the checked value
+        let mut push = push_remote(&repo, self.remote_val())?;
+        let mut conn = self.net.auth.settle(|| push.connect_auth(Direction::Push, Some(remote_callbacks(&self.net, None)), Some(self.net.proxy.options())))?;
+        let heads: HashMap<String, Oid> = conn.list()?.iter().map(|x| (x.name().to_string(), x.oid())).collect();
+
+        // Check every lease before pushing anything
+        let mut refspecs = vec![];
+        for (refspec, expected) in leases.iter() {
+            let refspec = refspec.as_ref().trim_start_matches('+');
+            let (src, dst) = refspec.split_once(':').unwrap_or((refspec, refspec));
+            let dst = full_refname(&repo, &heads, src, dst);
+            let expected = match expected {
+                Some(oid) => Some(*oid).filter(|x| !x.is_zero()),
+                None => tracking_ref(&remote, &dst).and_then(|x| repo.refname_to_id(&x).ok()),
+            };
+            if heads.get(&dst).copied() != expected {
+                return Err(Error::remote_moved(dst));
+            }
+
+            // Only rewrites need forcing as libgit2 refuses non fast-forward updates
+            let rewrite = match (expected, src.is_empty()) {
+                (Some(old), false) => {
+                    let new = repo.revparse_single(src)?.id();
+                    new != old && !repo.graph_descendant_of(new, old).unwrap_or(false)
+                },
+                _ => false,
+            };
+            refspecs.push(format!("{}{}:{}", if rewrite { "+" } else { "" }, src, dst));
+        }
+        let remote = conn.remote();
+        self.net.auth.settle(|| remote.push(&refspecs, Some(&mut push_opts(&self.net, xfer.as_ref(), &statuses))))?;
+        Ok(statuses.into_inner())
+    }
+
+    /// Push all local refs under `refs/` to the repo's remote, `origin` by default, force
+    /// updating them and deleting any remote refs that don't exist locally so that the remote
+    /// becomes an exact mirror of the repo, the same as `git push --mirror <remote>`. Pairs well
+    /// with a mirror clone to keep an internal mirror in sync. The remote shouldn't have fetch
+    /// refspecs, the same as `git remote add --mirror=push`, else its remote-tracking refs are
+    /// mirrored too. Returns the status the remote reported for each ref.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_push_mirror_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+    /// let local = tmpdir.mash("local");
+    /// assert!(git::Repo::new(&local).unwrap().url(tmpdir.mash("src").to_string().unwrap()).mirror(true).clone().is_ok());
+    /// assert!(git::Repo::new(tmpdir.mash("backup")).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+    /// assert!(git::remote_add(&local, "backup", tmpdir.mash("backup").to_string().unwrap()).is_ok());
+    /// assert!(git::Repo::new(&local).unwrap().remote("backup").push_mirror().is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn push_mirror(mut self) -> Result<Vec<PushStatus>> {
+        let repo = Repository::open(self.path_val())?;
+        let heads = remote_heads(&mut push_remote(&repo, self.remote_val())?, &self)?;
+
+        // Force update every local ref
+        let mut refspecs = vec![];
+        for reference in repo.references_glob("refs/*")? {
+            let reference = reference?;
+            if let (Some(name), Some(_)) = (reference.name(), reference.target()) {
+                refspecs.push(format!("+{0}:{0}", name));
+            }
+        }
+
+        // Delete remote refs that no longer exist locally skipping peeled tag entries
+        let mut deleted: Vec<&String> = heads.keys().filter(|x| x.starts_with("refs/") && !x.ends_with("^{}")).filter(|x| repo.find_reference(x).is_err()).collect();
+        deleted.sort();
+        refspecs.extend(deleted.iter().map(|x| format!(":{}", x)));
+        self.push_statuses(&refspecs)
+    }
+}
+
+impl<'a> RepoGroup<'a> {
+    /// Push the given refspecs from each of the repos to their remote, `origin` by default,
+    /// emitting terminal progress when enabled. This method spins off threads to handle pushing
+    /// all the repos in parallel. Refs the remotes reject are reported as an error.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_repo_push_many_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+    /// let remote = tmpdir.mash("remote");
+    /// assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+    /// let local = tmpdir.mash("local");
+    /// assert!(git::Repo::new(&local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+    /// let repos = git::RepoGroup::new().with_progress(true).add(git::Repo::new(&local).unwrap());
+    /// assert!(repos.push(&["refs/heads/master:refs/heads/release"]).is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn push<T: AsRef<str>>(&self, refspecs: &[T]) -> Result<()> {
+        let refspecs: Vec<String> = refspecs.iter().map(|x| x.as_ref().to_string()).collect();
+        let mut threads = Vec::new();
+        for repo in &self.repos {
+            // Owned types are needed for the thread lifetime
+            let path = repo.path_val().to_path_buf();
+            let remote = repo.remote_val().to_string();
+            let net = repo.net.clone();
+            let refspecs = refspecs.clone();
+
+            if let Some(progress) = &self.progress {
+                let progress_bar = progress.add(ProgressBar::new(0).with_style(self.style.as_ref().unwrap().clone()));
+                let msg = path.to_string()?;
+                progress_bar.set_message(&msg);
+
+                threads.push(thread::spawn(move || {
+                    let mut xfer_init = false;
+                    let result = Repo::new(path)
+                        .unwrap()
+                        .remote(remote)
+                        .net(net)
+                        .xfer_progress(|total, cur| {
+                            if !xfer_init {
+                                progress_bar.set_length(total);
+                                xfer_init = true;
+                            }
+                            progress_bar.set_position(cur);
+                        })
+                        .push(&refspecs);
+
+                    progress_bar.finish_with_message(&msg);
+                    result
+                }));
+            } else {
+                threads.push(thread::spawn(move || Repo::new(path).unwrap().remote(remote).net(net).push(&refspecs)));
+            }
+        }
+
+        // Wait for other threads to finish reporting the first failure
+        if let Some(progress) = &self.progress {
+            progress.join()?;
+        }
+        let mut result = Ok(());
+        for thread in threads {
+            let res = thread.join().unwrap();
+            if result.is_ok() {
+                result = res;
+            }
+        }
+        result
+    }
+}
+
+// Expand any glob refspecs e.g. `refs/notes/*:refs/notes/*` into one refspec per matching local
+// ref as libgit2 only supports globs when fetching
+pub(crate) fn expand_refspecs<T: AsRef<str>>(repo: &Repository, refspecs: &[T]) -> Result<Vec<String>> {
+    let mut expanded = vec![];
+    for refspec in refspecs.iter().map(|x| x.as_ref()) {
+        let (force, spec) = match refspec.strip_prefix('+') {
+            Some(spec) => ("+", spec),
+            None => ("", refspec),
+        };
+        let (src, dst) = spec.split_once(':').unwrap_or((spec, spec));
+        let (src_prefix, src_suffix) = match src.split_once('*') {
+            Some(x) => x,
+            None => {
+                expanded.push(refspec.to_string());
+                continue;
+            },
+        };
+        let (dst_prefix, dst_suffix) = dst.split_once('*').unwrap_or((dst, ""));
+        for reference in repo.references_glob(src)? {
+            if let Some(name) = reference?.name() {
+                let matched = &name[src_prefix.len()..name.len() - src_suffix.len()];
+                expanded.push(format!("{}{}:{}{}{}", force, name, dst_prefix, matched, dst_suffix));
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+// List the refs on the given remote and their current targets
+fn remote_heads(remote: &mut Remote, repo: &Repo) -> Result<HashMap<String, Oid>> {
+    let conn = repo.net.auth.settle(|| remote.connect_auth(Direction::Push, Some(remote_callbacks(&repo.net, None)), Some(repo.net.proxy.options())))?;
+    Ok(conn.list()?.iter().map(|x| (x.name().to_string(), x.oid())).collect())
+}
+
+// Expand the given short destination ref e.g. `master` into its full refname using the remote's
+// advertised refs, falling back on the namespace of the source ref the same as git
+fn full_refname(repo: &Repository, heads: &HashMap<String, Oid>, src: &str, dst: &str) -> String {
+    if dst.starts_with("refs/") {
+        return dst.to_string();
+    }
+    let tag = format!("refs/tags/{}", dst);
+    match heads.contains_key(&tag) || (!heads.contains_key(&format!("refs/heads/{}", dst)) && repo.resolve_reference_from_short_name(src).map(|x| x.is_tag()).unwrap_or(false)) {
+        true => tag,
+        false => format!("refs/heads/{}", dst),
+    }
+}
+
+// Returns the remote-tracking ref the given remote ref is fetched into if any
+fn tracking_ref(remote: &Remote, refname: &str) -> Option<String> {
+    remote
+        .refspecs()
+        .filter(|x| x.direction() == Direction::Fetch && x.src_matches(refname))
+        .find_map(|x| x.transform(refname).ok())
+        .and_then(|x| x.as_str().map(|x| x.to_string()))
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Commit the given content to the given file
+    fn commit(path: &Path, file: &str, content: &str) -> git2::Oid {
+        assert!(sys::write(path.mash(file), content).is_ok());
+        git::CommitBuilder::new(path).stage_all(true).author("foo", "foo@example.com").message(format!("Update {}", file)).commit().unwrap()
+    }
+
+    #[test]
+    fn test_push_refs() {
+        let tmpdir = setup("git_push_refs");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+        let remote = tmpdir.mash("remote");
+        assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        let (one, two) = (tmpdir.mash("one"), tmpdir.mash("two"));
+        assert!(git::Repo::new(&one).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+        assert!(git::Repo::new(&two).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+        let bare = git2::Repository::open(&remote).unwrap();
+
+        // Push several refs with progress
+        let oid = commit(&one, "foo", "foo");
+        let mut progress = 0;
+        let statuses = git::Repo::new(&one)
+            .unwrap()
+            .xfer_progress(|_, cur| progress = cur)
+            .push_refs(&["refs/heads/master", "refs/heads/master:refs/heads/release"])
+            .unwrap();
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses.iter().all(|x| x.is_ok()), true);
+        assert_eq!(statuses.iter().any(|x| x.refname == "refs/heads/release"), true);
+        assert!(progress > 0);
+        assert_eq!(bare.refname_to_id("refs/heads/master").unwrap(), oid);
+        assert_eq!(bare.refname_to_id("refs/heads/release").unwrap(), oid);
+
+        // Non fast forward updates are refused before reaching the remote
+        commit(&two, "bar", "bar");
+        assert!(git::Repo::new(&two).unwrap().push_refs(&["refs/heads/master"]).is_err());
+        assert_eq!(bare.refname_to_id("refs/heads/master").unwrap(), oid);
+
+        // Deleting a ref
+        let statuses = git::Repo::new(&two).unwrap().push_refs(&[":refs/heads/release"]).unwrap();
+        assert_eq!(statuses, vec![git::PushStatus { refname: "refs/heads/release".to_string(), rejected: None }]);
+        assert!(bare.refname_to_id("refs/heads/release").is_err());
+
+        // Groups report failures as errors
+        let repos = git::RepoGroup::new().add(git::Repo::new(&one).unwrap()).add(git::Repo::new(&two).unwrap());
+        assert!(repos.push(&["refs/heads/master"]).is_err());
+        let repos = git::RepoGroup::new().add(git::Repo::new(&one).unwrap());
+        assert!(repos.push(&["refs/heads/master:refs/heads/other"]).is_ok());
+        assert_eq!(bare.refname_to_id("refs/heads/other").unwrap(), oid);
+
+        // Missing remote
+        assert!(git::Repo::new(&one).unwrap().remote("missing").push_refs(&["refs/heads/master"]).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_push_urls() {
+        let tmpdir = setup("git_push_urls");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+        let (github, gitea) = (tmpdir.mash("github"), tmpdir.mash("gitea"));
+        for remote in [&github, &gitea].iter() {
+            assert!(git::Repo::new(remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        }
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(github.to_string().unwrap()).clone().is_ok());
+        let oid = commit(&local, "foo", "foo");
+
+        // Without push urls the remote's url is used
+        let results = git::Repo::new(&local).unwrap().push_urls(&["refs/heads/master"]).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, github.to_string().unwrap());
+        assert_eq!(results[0].is_ok(), true);
+
+        // Every push url is pushed to even when one fails
+        let missing = tmpdir.mash("missing");
+        for remote in [&gitea, &missing, &github].iter() {
+            assert!(git::remote_add_push_url(&local, "origin", remote.to_string().unwrap()).is_ok());
+        }
+        let results = git::Repo::new(&local).unwrap().push_urls(&["refs/heads/master:refs/heads/release"]).unwrap();
+        assert_eq!(
+            results.iter().map(|x| x.url.clone()).collect::<Vec<_>>(),
+            vec![
+                gitea.to_string().unwrap(),
+                missing.to_string().unwrap(),
+                github.to_string().unwrap()
+            ]
+        );
+        assert_eq!(results.iter().map(|x| x.is_ok()).collect::<Vec<_>>(), vec![true, false, true]);
+        assert!(results[1].result.is_err());
+        assert_eq!(results[0].result.as_ref().unwrap()[0].refname, "refs/heads/release");
+        for remote in [&github, &gitea].iter() {
+            assert_eq!(git2::Repository::open(remote).unwrap().refname_to_id("refs/heads/release").unwrap(), oid);
+        }
+
+        // Push urls have url rewrites applied
+        assert!(git::remote_set_push_url(&local, "origin", Some("internal:gitea")).is_ok());
+        assert!(git::url_rewrite_add(&local, format!("{}/", tmpdir.to_string().unwrap()), "internal:", false).is_ok());
+        let results = git::Repo::new(&local).unwrap().push_urls(&[":refs/heads/release"]).unwrap();
+        assert_eq!(results[0].url, gitea.to_string().unwrap());
+        assert_eq!(results[0].is_ok(), true);
+        assert!(git2::Repository::open(&gitea).unwrap().refname_to_id("refs/heads/release").is_err());
+
+        // Missing remote
+        assert!(git::Repo::new(&local).unwrap().remote("missing").push_urls(&["refs/heads/master"]).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_push_with_lease() {
+        let tmpdir = setup("git_push_with_lease");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+        let remote = tmpdir.mash("remote");
+        assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        let (one, two) = (tmpdir.mash("one"), tmpdir.mash("two"));
+        assert!(git::Repo::new(&one).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+        assert!(git::Repo::new(&two).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+        let bare = git2::Repository::open(&remote).unwrap();
+
+        // Concurrent push moves the remote ref
+        let pushed = commit(&one, "foo", "foo");
+        assert!(git::Repo::new(&one).unwrap().push_refs(&["refs/heads/master"]).is_ok());
+        assert!(git::branch_create(&two, "rewrite", Some("HEAD~2"), false).is_ok());
+        let rewrite = git::resolve(&two, "rewrite").unwrap();
+        let leases = [("refs/heads/rewrite:refs/heads/master", None)];
+        let err = git::Repo::new(&two).unwrap().push_with_lease(&leases).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::remote_moved("refs/heads/master").to_string());
+        assert_eq!(bare.refname_to_id("refs/heads/master").unwrap(), pushed);
+
+        // Explicit expected value
+        let leases = [("refs/heads/rewrite:refs/heads/master", Some(rewrite))];
+        assert!(git::Repo::new(&two).unwrap().push_with_lease(&leases).is_err());
+        let leases = [("refs/heads/rewrite:refs/heads/master", Some(pushed))];
+        let statuses = git::Repo::new(&two).unwrap().push_with_lease(&leases).unwrap();
+        assert_eq!(statuses, vec![git::PushStatus { refname: "refs/heads/master".to_string(), rejected: None }]);
+        assert_eq!(bare.refname_to_id("refs/heads/master").unwrap(), rewrite);
+
+        // Lease taken from the remote-tracking ref after fetching
+        let repo = git2::Repository::open(&one).unwrap();
+        repo.find_remote("origin").unwrap().fetch(&["refs/heads/master:refs/remotes/origin/master"], None, None).unwrap();
+        let leases = [("+refs/heads/master", None)];
+        assert!(git::Repo::new(&one).unwrap().push_with_lease(&leases).is_ok());
+        assert_eq!(bare.refname_to_id("refs/heads/master").unwrap(), pushed);
+
+        // Expecting the remote ref not to exist
+        let leases = [("refs/heads/master:refs/heads/new", Some(git2::Oid::zero()))];
+        assert!(git::Repo::new(&one).unwrap().push_with_lease(&leases).is_ok());
+        assert_eq!(bare.refname_to_id("refs/heads/new").unwrap(), pushed);
+        let err = git::Repo::new(&one).unwrap().push_with_lease(&leases).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::remote_moved("refs/heads/new").to_string());
+
+        // Short destinations are expanded before checking the lease
+        let leases = [("rewrite:master", Some(rewrite))];
+        let err = git::Repo::new(&two).unwrap().push_with_lease(&leases).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::remote_moved("refs/heads/master").to_string());
+        let forward = commit(&one, "bar", "bar");
+        let leases = [("master:master", Some(pushed))];
+        let statuses = git::Repo::new(&one).unwrap().push_with_lease(&leases).unwrap();
+        assert_eq!(statuses, vec![git::PushStatus { refname: "refs/heads/master".to_string(), rejected: None }]);
+        assert_eq!(bare.refname_to_id("refs/heads/master").unwrap(), forward);
+
+        // Nothing is pushed if any lease is stale
+        let leases = [
+            ("refs/heads/master:refs/heads/other", None),
+            ("refs/heads/master:refs/heads/new", Some(rewrite)),
+        ];
+        assert!(git::Repo::new(&one).unwrap().push_with_lease(&leases).is_err());
+        assert!(bare.refname_to_id("refs/heads/other").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_push_mirror() {
+        let tmpdir = setup("git_push_mirror");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(tmpdir.mash("src").to_string().unwrap()).mirror(true).clone().is_ok());
+        let backup = tmpdir.mash("backup");
+        assert!(git::Repo::new(&backup).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        assert!(git::remote_add(&local, "backup", backup.to_string().unwrap()).is_ok());
+        assert!(git::remote_set_fetch::<_, _, &str>(&local, "backup", &[]).is_ok());
+        let target = git2::Repository::open(&backup).unwrap();
+        let repo = git2::Repository::open(&local).unwrap();
+
+        // Names and targets of all direct refs in the given repo
+        let refs = |repo: &git2::Repository| {
+            let mut refs: Vec<(String, git2::Oid)> = repo.references_glob("refs/*").unwrap().map(|x| x.unwrap()).filter_map(|x| x.target().map(|y| (x.name().unwrap().to_string(), y))).collect();
+            refs.sort();
+            refs
+        };
+
+        // Initial mirror of all refs
+        let statuses = git::Repo::new(&local).unwrap().remote("backup").push_mirror().unwrap();
+        assert_eq!(statuses.iter().all(|x| x.is_ok()), true);
+        assert_eq!(refs(&target), refs(&repo));
+        assert_eq!(refs(&target).iter().any(|x| x.0 == "refs/tags/3.11"), true);
+
+        // Deletions, rewrites and new refs are all mirrored
+        let head = repo.refname_to_id("refs/heads/master").unwrap();
+        let parent = repo.revparse_single("master~1").unwrap().id();
+        repo.find_reference("refs/tags/3.11").unwrap().delete().unwrap();
+        repo.reference("refs/heads/master", parent, true, "").unwrap();
+        repo.reference("refs/heads/feature", head, false, "").unwrap();
+        target.reference("refs/heads/stale", head, false, "").unwrap();
+        let statuses = git::Repo::new(&local).unwrap().remote("backup").push_mirror().unwrap();
+        assert_eq!(statuses.iter().all(|x| x.is_ok()), true);
+        assert_eq!(refs(&target), refs(&repo));
+        assert!(target.refname_to_id("refs/tags/3.11").is_err());
+        assert!(target.refname_to_id("refs/heads/stale").is_err());
+        assert_eq!(target.refname_to_id("refs/heads/master").unwrap(), parent);
+
+        // Missing remote
+        assert!(git::Repo::new(&local).unwrap().remote("missing").push_mirror().is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,536 @@
+use super::{conflicts_error, is_dirty, resolve_commit};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{build::CheckoutBuilder, ErrorClass, ErrorCode, Oid, Rebase, Repository, Signature, Sort};
+
+/// Action to take for a commit in a rebase plan, the same as the commands of an interactive
+/// rebase's todo list
+#[derive(Clone, Debug, PartialEq)]
+pub enum RebaseAction {
+    /// Replay the commit as is
+    Pick,
+
+    /// Replay the commit with the given message
+    Reword(String),
+
+    /// Meld the commit into the previous one appending its message
+    Squash,
+
+    /// Meld the commit into the previous one keeping only the previous message
+    Fixup,
+
+    /// Leave the commit out
+    Drop,
+}
+
+/// Commit and the action to take for it in a rebase plan
+#[derive(Clone, Debug, PartialEq)]
+pub struct RebaseTodo {
+    pub oid: Oid,             // Id of the commit
+    pub action: RebaseAction, // Action to take for the commit
+}
+
+/// Progress of a rebase as reported to the callback after each replayed commit
+#[derive(Clone, Debug, PartialEq)]
+pub struct RebaseStep {
+    pub index: usize,        // Zero based index of the replayed commit
+    pub total: usize,        // Total number of commits being replayed
+    pub original: Oid,       // Id of the original commit
+    pub commit: Option<Oid>, // Id of the new commit or None if it was already applied upstream
+    pub summary: String,     // Summary line of the original commit
+}
+
+/// Rebase the commits of HEAD that aren't in `upstream` onto `upstream`, or onto `onto` when
+/// given, the same as `git rebase [--onto <onto>] <upstream>`. The `progress` callback is called
+/// after each commit is replayed. Commits keep their original author while the configured
+/// `user.name` and `user.email` are used as the committer. HEAD is left as is when it's already
+/// based on `upstream` and no `onto` is given. Rebasing is refused if the worktree has
+/// uncommitted changes. On conflicts the rebase stops with `Error::MergeConflicts` leaving the
+/// conflicts in the worktree and index to be resolved and continued with `rebase_continue` or
+/// undone with `rebase_abort`. Returns the id of the new HEAD commit.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_rebase_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "test").unwrap();
+/// repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+/// let head = git::resolve(&tmpdir, "HEAD").unwrap();
+/// assert_eq!(git::rebase(&tmpdir, "HEAD~2", None, |_| {}).unwrap(), head);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn rebase<T, F>(path: T, upstream: &str, onto: Option<&str>, progress: F) -> Result<Oid>
+where
+    T: AsRef<Path>,
+    F: FnMut(&RebaseStep),
+{
+    let path = path.as_ref();
+    let repo = Repository::open(path)?;
+    if is_dirty(path)? {
+        return Err(Error::dirty_worktree(path));
+    }
+    let head = repo.head()?.peel_to_commit()?.id();
+    let upstream = resolve_commit(&repo, upstream)?;
+
+    // Already up to date when HEAD is based on upstream, the same as git
+    if onto.is_none() && repo.merge_base(head, upstream)? == upstream {
+        return Ok(head);
+    }
+    let upstream = repo.find_annotated_commit(upstream)?;
+    let onto = match onto {
+        Some(onto) => Some(repo.find_annotated_commit(resolve_commit(&repo, onto)?)?),
+        None => None,
+    };
+    let sig = repo.signature()?;
+    let mut rebase = repo.rebase(None, Some(&upstream), onto.as_ref(), None)?;
+    replay(&repo, &mut rebase, &sig, progress)
+}
+
+/// Continue a rebase stopped on conflicts once they've been resolved and staged. The current
+/// commit is committed and the remaining commits replayed reporting through the `progress`
+/// callback the same as `rebase`. Returns the id of the new HEAD commit.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_rebase_continue_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::rebase_continue(&tmpdir, |_| {}).is_err());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn rebase_continue<T, F>(path: T, mut progress: F) -> Result<Oid>
+where
+    T: AsRef<Path>,
+    F: FnMut(&RebaseStep),
+{
+    let repo = Repository::open(path.as_ref())?;
+    let sig = repo.signature()?;
+    let mut rebase = repo.open_rebase(None)?;
+    if let Some(index) = rebase.operation_current() {
+        let original = rebase.nth(index).map(|x| x.id()).unwrap_or_else(Oid::zero);
+        progress(&commit_step(&repo, &mut rebase, &sig, index, original)?);
+    }
+    replay(&repo, &mut rebase, &sig, progress)
+}
+
+/// Abort a rebase in progress restoring HEAD, the index and the worktree to their state
+/// before the rebase started.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_rebase_abort_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::rebase_abort(&tmpdir).is_err());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn rebase_abort<T: AsRef<Path>>(path: T) -> Result<()> {
+    let repo = Repository::open(path.as_ref())?;
+    repo.open_rebase(None)?.abort()?;
+    Ok(())
+}
+
+/// Returns the default rebase plan for rebasing HEAD onto `upstream` i.e. a `Pick` for each
+/// commit of HEAD that isn't in `upstream` oldest first, ready to be edited and given to
+/// `rebase_plan`. Merge commits are left out the same as git's todo list without
+/// `--rebase-merges` so that the commits they merged are replayed as a linear history.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_rebase_todo_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let todo = git::rebase_todo(&tmpdir, "HEAD~3").unwrap();
+/// assert_eq!(todo.len(), 3);
+/// assert_eq!(todo[0].action, git::RebaseAction::Pick);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn rebase_todo<T: AsRef<Path>>(path: T, upstream: &str) -> Result<Vec<RebaseTodo>> {
+    let repo = Repository::open(path.as_ref())?;
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    revwalk.push(repo.head()?.peel_to_commit()?.id())?;
+    revwalk.hide(resolve_commit(&repo, upstream)?)?;
+    let mut todo = vec![];
+    for oid in revwalk {
+        let oid = oid?;
+        if repo.find_commit(oid)?.parent_count() <= 1 {
+            todo.push(RebaseTodo { oid, action: RebaseAction::Pick });
+        }
+    }
+    Ok(todo)
+}
+
+/// Execute the given rebase plan non-interactively replaying its commits in order onto `onto`,
+/// the same as an interactive rebase with the plan as its todo list. The `progress` callback is
+/// called as each step is replayed with the new commit, or None for dropped commits. Commits keep
+/// their original author, squashed and fixed up commits the author of the commit they're melded
+/// into, while the configured `user.name` and `user.email` are used as the committer. Rebasing is
+/// refused if the worktree has uncommitted changes as are plans replaying merge commits, which
+/// can only be dropped. The plan is replayed in memory so on
+/// conflicts `Error::MergeConflicts` is returned with the repo left untouched. Returns the id of
+/// the new HEAD commit with the old one recorded as `ORIG_HEAD`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_rebase_plan_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "test").unwrap();
+/// repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+/// let mut todo = git::rebase_todo(&tmpdir, "HEAD~2").unwrap();
+/// todo[1].action = git::RebaseAction::Fixup;
+/// assert!(git::rebase_plan(&tmpdir, "HEAD~2", &todo, |_| {}).is_ok());
+/// assert_eq!(git::resolve(&tmpdir, "HEAD~1").unwrap(), git::resolve(&tmpdir, "ORIG_HEAD~2").unwrap());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn rebase_plan<T, F>(path: T, onto: &str, plan: &[RebaseTodo], mut progress: F) -> Result<Oid>
+where
+    T: AsRef<Path>,
+    F: FnMut(&RebaseStep),
+{
+    let path = path.as_ref();
+    let repo = Repository::open(path)?;
+    if is_dirty(path)? {
+        return Err(Error::dirty_worktree(path));
+    }
+    for todo in plan.iter().filter(|x| x.action != RebaseAction::Drop) {
+        if repo.find_commit(todo.oid)?.parent_count() > 1 {
+            let msg = format!("cannot replay merge commit: {}", todo.oid);
+            return Err(git2::Error::new(ErrorCode::Invalid, ErrorClass::Rebase, msg).into());
+        }
+    }
+    let sig = repo.signature()?;
+    let mut current = repo.find_commit(resolve_commit(&repo, onto)?)?;
+    let mut replayed = false;
+
+    // Replay each commit in memory onto the current commit
+    for (index, todo) in plan.iter().enumerate() {
+        let commit = repo.find_commit(todo.oid)?;
+        let oid = match &todo.action {
+            RebaseAction::Drop => None,
+            action => {
+                let meld = *action == RebaseAction::Squash || *action == RebaseAction::Fixup;
+                if meld && !replayed {
+                    let msg = format!("cannot {} without a previous commit: {}", if *action == RebaseAction::Squash { "squash" } else { "fixup" }, todo.oid);
+                    return Err(git2::Error::new(ErrorCode::Invalid, ErrorClass::Rebase, msg).into());
+                }
+                let mut merged = repo.cherrypick_commit(&commit, &current, 0, None)?;
+                if merged.has_conflicts() {
+                    return Err(conflicts_error(&merged)?);
+                }
+                let tree = repo.find_tree(merged.write_tree_to(&repo)?)?;
+                let oid = match action {
+                    RebaseAction::Squash | RebaseAction::Fixup => {
+                        let mut message = current.message().unwrap_or_default().to_string();
+                        if *action == RebaseAction::Squash {
+                            message = format!("{}\n\n{}", message.trim_end(), commit.message().unwrap_or_default());
+                        }
+                        let parents: Vec<_> = current.parents().collect();
+                        repo.commit(None, &current.author(), &sig, &message, &tree, &parents.iter().collect::<Vec<_>>())?
+                    },
+                    RebaseAction::Reword(message) => repo.commit(None, &commit.author(), &sig, message, &tree, &[&current])?,
+                    _ => repo.commit(None, &commit.author(), &sig, commit.message().unwrap_or_default(), &tree, &[&current])?,
+                };
+                current = repo.find_commit(oid)?;
+                replayed = true;
+                Some(oid)
+            },
+        };
+        progress(&RebaseStep {
+            index,
+            total: plan.len(),
+            original: todo.oid,
+            commit: oid,
+            summary: commit.summary().unwrap_or_default().to_string(),
+        });
+    }
+
+    // Update the worktree then move HEAD, or the branch it points to, keeping the old HEAD
+    let head = repo.head()?.peel_to_commit()?.id();
+    repo.checkout_tree(current.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    let target = repo.find_reference("HEAD")?.symbolic_target().unwrap_or("HEAD").to_string();
+    let reflog = format!("rebase (finish): {} onto {}", target, current.id());
+    repo.reference(&target, current.id(), true, &reflog)?;
+    repo.reference("ORIG_HEAD", head, true, "rebase")?;
+    Ok(current.id())
+}
+
+// Replay the remaining commits of the rebase then finish it returning the new HEAD
+fn replay<F>(repo: &Repository, rebase: &mut Rebase, sig: &Signature, mut progress: F) -> Result<Oid>
+where
+    F: FnMut(&RebaseStep),
+{
+    while let Some(op) = rebase.next() {
+        let original = op?.id();
+        let index = rebase.operation_current().unwrap_or_default();
+        progress(&commit_step(repo, rebase, sig, index, original)?);
+    }
+    rebase.finish(Some(sig))?;
+    Ok(repo.head()?.peel_to_commit()?.id())
+}
+
+// Commit the current rebase operation keeping the original author, failing on conflicts
+fn commit_step(repo: &Repository, rebase: &mut Rebase, sig: &Signature, index: usize, original: Oid) -> Result<RebaseStep> {
+    let conflicts = repo.index()?;
+    if conflicts.has_conflicts() {
+        return Err(conflicts_error(&conflicts)?);
+    }
+    let commit = match rebase.commit(None, sig, None) {
+        Ok(oid) => Some(oid),
+        Err(err) if err.code() == ErrorCode::Applied => None,
+        Err(err) => return Err(err.into()),
+    };
+    Ok(RebaseStep {
+        index,
+        total: rebase.len(),
+        original,
+        commit,
+        summary: repo.find_commit(original)?.summary().unwrap_or_default().to_string(),
+    })
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Extract the test repo with a test identity
+    fn init(path: &Path) -> git2::Repository {
+        assert!(sys::remove_all(path).is_ok());
+        assert!(tar::extract_all(path.mash("../../alpine-base.tgz"), path).is_ok());
+        let repo = git2::Repository::open(path).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        repo
+    }
+
+    // Commit the given content to the given file as a different author
+    fn commit(path: &Path, file: &str, content: &str) -> git2::Oid {
+        assert!(sys::write(path.mash(file), content).is_ok());
+        git::CommitBuilder::new(path).stage_all(true).author("foo", "foo@example.com").message(format!("Update {}", file)).commit().unwrap()
+    }
+
+    // Checkout the given branch
+    fn checkout(path: &Path, branch: &str) {
+        assert!(git::Repo::new(path).unwrap().checkout(branch, false).is_ok());
+    }
+
+    #[test]
+    fn test_rebase() {
+        let tmpdir = setup("git_rebase");
+        let repo = init(&tmpdir);
+
+        // Replay the feature commits onto master
+        assert!(git::branch_create(&tmpdir, "feature", Some("HEAD~2"), true).is_ok());
+        let foo = commit(&tmpdir, "foo", "foo");
+        let bar = commit(&tmpdir, "bar", "bar");
+        checkout(&tmpdir, "master");
+        let master = commit(&tmpdir, "baz", "baz");
+        checkout(&tmpdir, "feature");
+        let mut steps = vec![];
+        let head = git::rebase(&tmpdir, "master", None, |x| steps.push(x.clone())).unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!((steps[0].index, steps[0].total, steps[0].original), (0, 2, foo));
+        assert_eq!((steps[1].index, steps[1].total, steps[1].original), (1, 2, bar));
+        assert_eq!(steps[1].commit, Some(head));
+        assert_eq!(steps[0].summary, "Update foo");
+        let rebased = repo.find_commit(head).unwrap();
+        assert_eq!(rebased.author().name(), Some("foo"));
+        assert_eq!(rebased.committer().name(), Some("test"));
+        assert_eq!(rebased.parent(0).unwrap().id(), steps[0].commit.unwrap());
+        assert_eq!(rebased.parent(0).unwrap().parent_id(0).unwrap(), master);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("feature"));
+        assert_eq!(sys::readstring(tmpdir.mash("baz")).unwrap(), "baz");
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+
+        // Rebase only the last commit onto another base
+        assert_eq!(git::rebase(&tmpdir, "HEAD~1", Some("master~3"), |_| {}).unwrap(), git::resolve(&tmpdir, "HEAD").unwrap());
+        assert_eq!(git::resolve(&tmpdir, "HEAD~1").unwrap(), git::resolve(&tmpdir, "master~3").unwrap());
+        assert_eq!(tmpdir.mash("foo").exists(), false);
+        assert_eq!(sys::readstring(tmpdir.mash("bar")).unwrap(), "bar");
+
+        // Stop on conflicts then abort
+        assert!(git::branch_create(&tmpdir, "conflict", Some("master~1"), true).is_ok());
+        commit(&tmpdir, "baz", "theirs");
+        commit(&tmpdir, "qux", "qux");
+        let before = repo.head().unwrap().target().unwrap();
+        let err = git::rebase(&tmpdir, "master", None, |_| {}).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::merge_conflicts(&["baz"]).to_string());
+        assert_eq!(repo.state(), git2::RepositoryState::RebaseMerge);
+        assert!(git::rebase_continue(&tmpdir, |_| {}).is_err());
+        assert!(git::rebase_abort(&tmpdir).is_ok());
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("conflict"));
+        assert_eq!(repo.head().unwrap().target(), Some(before));
+        assert_eq!(sys::readstring(tmpdir.mash("baz")).unwrap(), "theirs");
+
+        // Stop on conflicts then resolve and continue
+        assert!(git::rebase(&tmpdir, "master", None, |_| {}).is_err());
+        assert!(sys::write(tmpdir.mash("baz"), "resolved").is_ok());
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("baz")).unwrap();
+        index.write().unwrap();
+        let mut steps = vec![];
+        let head = git::rebase_continue(&tmpdir, |x| steps.push(x.clone())).unwrap();
+        assert_eq!(steps.iter().map(|x| x.index).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("conflict"));
+        assert_eq!(git::resolve(&tmpdir, "HEAD~2").unwrap(), master);
+        assert_eq!(repo.head().unwrap().target(), Some(head));
+        assert_eq!(sys::readstring(tmpdir.mash("baz")).unwrap(), "resolved");
+
+        // Dirty worktree and nothing to abort
+        assert!(sys::write(tmpdir.mash("README.md"), "dirty").is_ok());
+        let err = git::rebase(&tmpdir, "master", None, |_| {}).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::dirty_worktree(&tmpdir).to_string());
+        assert!(git::rebase_abort(&tmpdir).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_rebase_plan() {
+        let tmpdir = setup("git_rebase_plan");
+        let repo = init(&tmpdir);
+        let base = repo.head().unwrap().target().unwrap();
+        let a = commit(&tmpdir, "a", "a");
+        commit(&tmpdir, "b", "b");
+        let c = commit(&tmpdir, "c", "c");
+        commit(&tmpdir, "d", "d");
+        commit(&tmpdir, "c", "e");
+        let head = repo.head().unwrap().target().unwrap();
+
+        // Default plan picks each commit oldest first
+        let mut todo = git::rebase_todo(&tmpdir, "HEAD~5").unwrap();
+        assert_eq!(todo.len(), 5);
+        assert_eq!(todo[0], git::RebaseTodo { oid: a, action: git::RebaseAction::Pick });
+        assert_eq!(todo.iter().all(|x| x.action == git::RebaseAction::Pick), true);
+
+        // Execute an edited plan
+        todo[1].action = git::RebaseAction::Squash;
+        todo[2].action = git::RebaseAction::Reword("Add c".to_string());
+        todo[3].action = git::RebaseAction::Drop;
+        todo[4].action = git::RebaseAction::Fixup;
+        let mut steps = vec![];
+        let oid = git::rebase_plan(&tmpdir, "HEAD~5", &todo, |x| steps.push(x.clone())).unwrap();
+        assert_eq!(steps.len(), 5);
+        assert_eq!(steps.iter().map(|x| (x.index, x.total)).collect::<Vec<_>>(), vec![(0, 5), (1, 5), (2, 5), (3, 5), (4, 5)]);
+        assert_eq!(steps[3].commit, None);
+        assert_eq!(steps[4].commit, Some(oid));
+        assert_eq!(steps[2].original, c);
+        assert_eq!(steps[2].summary, "Update c");
+
+        let last = repo.find_commit(oid).unwrap();
+        assert_eq!(last.message(), Some("Add c"));
+        assert_eq!(last.author().name(), Some("foo"));
+        assert_eq!(last.committer().name(), Some("test"));
+        let first = last.parent(0).unwrap();
+        assert_eq!(first.message(), Some("Update a\n\nUpdate b"));
+        assert_eq!(first.parent_id(0).unwrap(), base);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("master"));
+        assert_eq!(repo.head().unwrap().target(), Some(oid));
+        assert_eq!(git::resolve(&tmpdir, "ORIG_HEAD").unwrap(), head);
+        assert_eq!(sys::readstring(tmpdir.mash("b")).unwrap(), "b");
+        assert_eq!(sys::readstring(tmpdir.mash("c")).unwrap(), "e");
+        assert_eq!(tmpdir.mash("d").exists(), false);
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+
+        // Conflicts leave the repo untouched
+        let f = commit(&tmpdir, "c", "f");
+        let plan = vec![git::RebaseTodo { oid: f, action: git::RebaseAction::Pick }];
+        let err = git::rebase_plan(&tmpdir, "HEAD~3", &plan, |_| {}).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::merge_conflicts(&["c"]).to_string());
+        assert_eq!(repo.head().unwrap().target(), Some(f));
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+
+        // Nothing to squash into
+        let plan = vec![
+            git::RebaseTodo { oid, action: git::RebaseAction::Drop },
+            git::RebaseTodo { oid: f, action: git::RebaseAction::Fixup },
+        ];
+        assert!(git::rebase_plan(&tmpdir, "HEAD~2", &plan, |_| {}).is_err());
+        assert_eq!(repo.head().unwrap().target(), Some(f));
+
+        // Dirty worktree
+        assert!(sys::write(tmpdir.mash("README.md"), "dirty").is_ok());
+        let err = git::rebase_plan(&tmpdir, "HEAD~1", &plan[1..], |_| {}).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::dirty_worktree(&tmpdir).to_string());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_rebase_merges() {
+        let tmpdir = setup("git_rebase_merges");
+        let repo = init(&tmpdir);
+        let base = repo.head().unwrap().target().unwrap();
+        assert!(git::branch_create(&tmpdir, "side", None, true).is_ok());
+        commit(&tmpdir, "side", "side");
+        checkout(&tmpdir, "master");
+        let a = commit(&tmpdir, "a", "a");
+        let args = ["merge", "-q", "--no-ff", "-m", "Merge side", "side"];
+        assert!(Command::new("git").args(args).current_dir(&tmpdir).output().unwrap().status.success());
+        let merge = repo.head().unwrap().target().unwrap();
+        let b = commit(&tmpdir, "b", "b");
+
+        // Default plan leaves out merge commits the same as git
+        let todo = git::rebase_todo(&tmpdir, &base.to_string()).unwrap();
+        let mut oids = todo.iter().map(|x| x.oid).collect::<Vec<_>>();
+        let args = ["rev-list", "--reverse", "--topo-order", "--no-merges", &format!("{}..HEAD", base)];
+        let output = Command::new("git").args(args).current_dir(&tmpdir).output().unwrap();
+        let mut expected = String::from_utf8(output.stdout).unwrap().lines().map(|x| git2::Oid::from_str(x).unwrap()).collect::<Vec<_>>();
+        assert_eq!(oids.len(), 3);
+        assert_eq!(oids.last(), Some(&b));
+        oids.sort();
+        expected.sort();
+        assert_eq!(oids, expected);
+        assert_eq!(oids.iter().any(|x| x == &merge), false);
+
+        // Merge commits can't be replayed but can be dropped
+        let mut plan = vec![git::RebaseTodo { oid: merge, action: git::RebaseAction::Pick }, git::RebaseTodo { oid: b, action: git::RebaseAction::Pick }];
+        let err = git::rebase_plan(&tmpdir, &a.to_string(), &plan, |_| {}).unwrap_err();
+        assert_eq!(err.to_string().starts_with(&format!("cannot replay merge commit: {}", merge)), true);
+        assert_eq!(repo.head().unwrap().target(), Some(b));
+        plan[0].action = git::RebaseAction::Drop;
+        assert!(git::rebase_plan(&tmpdir, &a.to_string(), &plan, |_| {}).is_ok());
+        assert_eq!(git::resolve(&tmpdir, "HEAD~1").unwrap(), a);
+        assert_eq!(tmpdir.mash("side").exists(), false);
+        assert!(git::reset(&tmpdir, b.to_string(), git::ResetMode::Hard, false).is_ok());
+
+        // The default plan replays the merged history linearly
+        let head = git::rebase_plan(&tmpdir, &base.to_string(), &todo, |_| {}).unwrap();
+        let commits = (0..3).map(|i| repo.find_commit(git::resolve(&tmpdir, format!("{}~{}", head, i)).unwrap()).unwrap()).collect::<Vec<_>>();
+        assert_eq!(commits.iter().all(|x| x.parent_count() == 1), true);
+        assert_eq!(commits[2].parent_id(0).unwrap(), base);
+        assert_eq!(commits.iter().filter_map(|x| x.summary().map(|x| x.to_string())).collect::<Vec<_>>().contains(&"Update side".to_string()), true);
+        assert_eq!(sys::readstring(tmpdir.mash("side")).unwrap(), "side");
+        assert_eq!(repo.head().unwrap().peel_to_commit().unwrap().tree_id(), repo.find_commit(b).unwrap().tree_id());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
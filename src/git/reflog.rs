@@ -0,0 +1,209 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{Oid, Repository};
+
+/// Reflog entry as returned by `reflog`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReflogEntry {
+    pub old: Oid,          // Id the ref pointed to before the update or zero when it was created
+    pub new: Oid,          // Id the ref pointed to after the update
+    pub message: String,   // Reason for the update e.g. `commit: Add foo`
+    pub committer: String, // Name of who updated the ref
+    pub email: String,     // Email of who updated the ref
+    pub time: i64,         // Time of the update in seconds since the epoch
+}
+
+/// List the reflog entries of the given ref newest first, the same as `git reflog show <ref>`.
+/// The ref may be `HEAD`, a full ref name or a short name e.g. `master` for `refs/heads/master`.
+/// Returns an empty list if the ref has no reflog.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_reflog_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::branch_create(&tmpdir, "foo", None, true).is_ok());
+/// let entries = git::reflog(&tmpdir, "HEAD").unwrap();
+/// assert_eq!(entries[0].message, "checkout: moving from master to foo");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn reflog<T, U>(path: T, refname: U) -> Result<Vec<ReflogEntry>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let refname = full_refname(&repo, refname.as_ref())?;
+    let mut entries = vec![];
+    for entry in repo.reflog(&refname)?.iter() {
+        let committer = entry.committer();
+        entries.push(ReflogEntry {
+            old: entry.id_old(),
+            new: entry.id_new(),
+            message: entry.message().unwrap_or_default().to_string(),
+            committer: committer.name().unwrap_or_default().to_string(),
+            email: committer.email().unwrap_or_default().to_string(),
+            time: committer.when().seconds(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Get the id the given ref pointed to at the given time in seconds since the epoch according to
+/// its reflog, the same as `git rev-parse <ref>@{<time>}` e.g. to find what HEAD was an hour ago.
+/// Returns None if the reflog doesn't go back that far.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_reflog_at_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::branch_create(&tmpdir, "foo", None, true).is_ok());
+/// let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64;
+/// assert_eq!(git::reflog_at(&tmpdir, "HEAD", now).unwrap(), Some(git::reflog(&tmpdir, "HEAD").unwrap()[0].new));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn reflog_at<T, U>(path: T, refname: U, time: i64) -> Result<Option<Oid>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    Ok(reflog(path, refname)?.into_iter().find(|x| x.time <= time).map(|x| x.new))
+}
+
+/// Remove the reflog entries older than the given time in seconds since the epoch from the given
+/// ref's reflog, or from the reflogs of all refs and HEAD when None is given, the same as `git
+/// reflog expire --expire=<time> [--all]`. Returns the number of entries removed. Objects only
+/// reachable from the removed entries can then be pruned.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_reflog_expire_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::branch_create(&tmpdir, "foo", None, true).is_ok());
+/// assert!(git::reflog_expire(&tmpdir, None, i64::MAX).unwrap() > 0);
+/// assert!(git::reflog(&tmpdir, "HEAD").unwrap().is_empty());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn reflog_expire<T: AsRef<Path>>(path: T, refname: Option<&str>, before: i64) -> Result<usize> {
+    let repo = Repository::open(path.as_ref())?;
+    let refnames = match refname {
+        Some(name) => vec![full_refname(&repo, name)?],
+        None => {
+            let mut names = vec!["HEAD".to_string()];
+            for reference in repo.references()? {
+                if let Some(name) = reference?.name() {
+                    names.push(name.to_string());
+                }
+            }
+            names
+        },
+    };
+    let mut removed = 0;
+    for name in refnames {
+        let mut reflog = repo.reflog(&name)?;
+
+        // Remove oldest first so the indices of the remaining entries are unchanged
+        let expired: Vec<usize> = reflog.iter().enumerate().filter(|(_, x)| x.committer().when().seconds() < before).map(|(i, _)| i).collect();
+        for i in expired.iter().rev() {
+            reflog.remove(*i, false)?;
+        }
+        if !expired.is_empty() {
+            reflog.write()?;
+            removed += expired.len();
+        }
+    }
+    Ok(removed)
+}
+
+// Returns the full name of the given ref resolving short names e.g. `master` to
+// `refs/heads/master`
+fn full_refname(repo: &Repository, name: &str) -> Result<String> {
+    if name == "HEAD" || name.starts_with("refs/") {
+        return Ok(name.to_string());
+    }
+    let reference = repo.resolve_reference_from_short_name(name)?;
+    Ok(reference.name().unwrap_or(name).to_string())
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use git2::{Signature, Time};
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_reflog() {
+        let tmpdir = setup("git_reflog");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let head = repo.head().unwrap().target().unwrap();
+        let parent = repo.revparse_single("HEAD~1").unwrap().id();
+
+        // Replace the reflogs with entries at known times
+        for name in &["HEAD", "refs/heads/master"] {
+            let mut reflog = repo.reflog(name).unwrap();
+            while !reflog.is_empty() {
+                reflog.remove(0, false).unwrap();
+            }
+            let sig = |time| Signature::new("foo", "foo@example.com", &Time::new(time, 0)).unwrap();
+            reflog.append(parent, &sig(1000), Some("clone: from foo")).unwrap();
+            reflog.append(head, &sig(2000), Some("pull: Fast-forward")).unwrap();
+            reflog.write().unwrap();
+        }
+
+        // Entries newest first for HEAD and short names
+        let entries = git::reflog(&tmpdir, "HEAD").unwrap();
+        assert_eq!(
+            entries[0],
+            git::ReflogEntry {
+                old: parent,
+                new: head,
+                message: "pull: Fast-forward".to_string(),
+                committer: "foo".to_string(),
+                email: "foo@example.com".to_string(),
+                time: 2000
+            }
+        );
+        assert_eq!(entries[1].old, git2::Oid::zero());
+        assert_eq!(entries[1].new, parent);
+        assert_eq!(git::reflog(&tmpdir, "master").unwrap(), entries);
+        assert_eq!(git::reflog(&tmpdir, "refs/heads/master").unwrap(), entries);
+        assert!(git::reflog(&tmpdir, "missing").is_err());
+
+        // Value at a given time
+        assert_eq!(git::reflog_at(&tmpdir, "HEAD", 999).unwrap(), None);
+        assert_eq!(git::reflog_at(&tmpdir, "HEAD", 1000).unwrap(), Some(parent));
+        assert_eq!(git::reflog_at(&tmpdir, "HEAD", 1999).unwrap(), Some(parent));
+        assert_eq!(git::reflog_at(&tmpdir, "master", 3000).unwrap(), Some(head));
+
+        // Expire a single ref then all refs
+        assert_eq!(git::reflog_expire(&tmpdir, Some("master"), 1500).unwrap(), 1);
+        assert_eq!(git::reflog(&tmpdir, "master").unwrap().len(), 1);
+        assert_eq!(git::reflog(&tmpdir, "HEAD").unwrap().len(), 2);
+        assert_eq!(git::reflog_expire(&tmpdir, Some("master"), 1500).unwrap(), 0);
+        assert_eq!(git::reflog_expire(&tmpdir, None, 1500).unwrap(), 1);
+        assert_eq!(git::reflog(&tmpdir, "HEAD").unwrap()[0].time, 2000);
+        assert_eq!(git::reflog_expire(&tmpdir, None, 3000).unwrap(), 2);
+        assert!(git::reflog(&tmpdir, "HEAD").unwrap().is_empty());
+        assert_eq!(repo.head().unwrap().target(), Some(head));
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,143 @@
+use super::{conventional, conventional::Bump, is_merged, tags, TagOrder, Version};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{ObjectType, Repository};
+
+/// Suggested next release as returned by `next_version`
+#[derive(Clone, Debug, PartialEq)]
+pub struct NextVersion {
+    pub tag: Option<String>,      // Last semantic version tag reachable from HEAD
+    pub current: Option<Version>, // Version of the last tag
+    pub commits: usize,           // Number of commits since the last tag
+    pub bump: Bump,               // Highest bump implied by the conventional commits since the last tag
+    pub version: Version,         // Suggested next version
+}
+
+/// Suggest the next version by inspecting the conventional commits made since the last semantic
+/// version tag reachable from HEAD. Commits that aren't conventional commits are ignored and
+/// `0.0.0` is used as the current version when there is no such tag.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_next_version_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::tag_lightweight(&tmpdir, "v1.2.3", "HEAD").is_ok());
+/// assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+/// assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("feat: add foo").commit().is_ok());
+/// assert_eq!(git::next_version(&tmpdir).unwrap().version.to_string(), "1.3.0");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn next_version<T: AsRef<Path>>(path: T) -> Result<NextVersion> {
+    let repo = Repository::open(path.as_ref())?;
+    let head = repo.head()?.peel_to_commit()?.id();
+
+    // Highest version tag that is part of HEAD's history
+    let mut last = None;
+    for tag in tags(path.as_ref(), TagOrder::Semver)?.into_iter().rev() {
+        if tag.version.is_none() {
+            break;
+        }
+        if repo.find_object(tag.target, None)?.kind() == Some(ObjectType::Commit) && is_merged(&repo, tag.target, head)? {
+            last = Some(tag);
+            break;
+        }
+    }
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(head)?;
+    if let Some(tag) = &last {
+        revwalk.hide(tag.target)?;
+    }
+    let mut messages = vec![];
+    for oid in revwalk {
+        messages.push(repo.find_commit(oid?)?.message().unwrap_or_default().to_string());
+    }
+
+    let bump = conventional::bump(&messages);
+    let current = last.as_ref().and_then(|x| x.version.clone());
+    Ok(NextVersion {
+        tag: last.map(|x| x.name),
+        version: current.clone().unwrap_or_default().bump(bump),
+        current,
+        commits: messages.len(),
+        bump,
+    })
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use git::conventional::Bump;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Commit a change to the given file with the given message
+    fn commit(path: &Path, file: &str, message: &str) {
+        assert!(sys::write(path.mash(file), message).is_ok());
+        assert!(git::CommitBuilder::new(path).stage_all(true).author("foo", "foo@example.com").message(message).commit().is_ok());
+    }
+
+    #[test]
+    fn test_next_version() {
+        let tmpdir = setup("git_next_version");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+
+        // No version tags and no conventional commits
+        let next = git::next_version(&tmpdir).unwrap();
+        assert_eq!(next.tag, None);
+        assert_eq!(next.current, None);
+        assert_eq!(next.commits, 38);
+        assert_eq!(next.bump, Bump::None);
+        assert_eq!(next.version, git::Version::new(0, 0, 0));
+
+        // Nothing since the tag
+        assert!(git::tag_annotated(&tmpdir, "v1.2.3", "HEAD", "Release 1.2.3", Some(("foo", "foo@example.com"))).is_ok());
+        let next = git::next_version(&tmpdir).unwrap();
+        assert_eq!(next.tag, Some("v1.2.3".to_string()));
+        assert_eq!(next.current, Some(git::Version::new(1, 2, 3)));
+        assert_eq!(next.commits, 0);
+        assert_eq!(next.version, git::Version::new(1, 2, 3));
+
+        // Bumps accumulate to the highest
+        commit(&tmpdir, "foo", "docs: foo");
+        assert_eq!(git::next_version(&tmpdir).unwrap().version.to_string(), "1.2.3");
+        commit(&tmpdir, "foo", "fix: foo");
+        let next = git::next_version(&tmpdir).unwrap();
+        assert_eq!((next.commits, next.bump), (2, Bump::Patch));
+        assert_eq!(next.version.to_string(), "1.2.4");
+        commit(&tmpdir, "bar", "feat(bar): add bar");
+        commit(&tmpdir, "foo", "fix: foo again");
+        assert_eq!(git::next_version(&tmpdir).unwrap().version.to_string(), "1.3.0");
+        commit(&tmpdir, "bar", "refactor!: rework bar");
+        assert_eq!(git::next_version(&tmpdir).unwrap().version.to_string(), "2.0.0");
+
+        // Highest reachable version tag is used
+        assert!(git::tag_lightweight(&tmpdir, "v1.3.0", "HEAD").is_ok());
+        assert!(git::tag_lightweight(&tmpdir, "v1.2.9", "HEAD~1").is_ok());
+        assert!(git::branch_create(&tmpdir, "other", Some("HEAD~2"), false).is_ok());
+        commit(&tmpdir, "foo", "fix: foo once more");
+        let next = git::next_version(&tmpdir).unwrap();
+        assert_eq!(next.tag, Some("v1.3.0".to_string()));
+        assert_eq!(next.version.to_string(), "1.3.1");
+
+        // Tags on other branches are ignored
+        assert!(git::Repo::new(&tmpdir).unwrap().checkout("other", false).is_ok());
+        assert!(git::tag_lightweight(&tmpdir, "v9.0.0", "master").is_ok());
+        let next = git::next_version(&tmpdir).unwrap();
+        assert_eq!(next.tag, Some("v1.2.3".to_string()));
+        assert_eq!((next.commits, next.bump), (3, Bump::Minor));
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,428 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{Config, ConfigLevel, ErrorCode, Repository};
+
+/// Remote details as returned by `remotes`
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemoteInfo {
+    pub name: String,           // Name of the remote e.g. `origin`
+    pub url: Option<String>,    // Url used for fetching and pushing
+    pub push_urls: Vec<String>, // Urls used for pushing instead of `url` if set
+    pub fetch: Vec<String>,     // Refspecs used when fetching
+    pub push: Vec<String>,      // Refspecs used when pushing
+}
+
+/// List the remotes configured for the repo at the given path sorted by name, the same as
+/// `git remote -v`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_remotes_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::remotes(&tmpdir).unwrap()[0].name, "origin");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn remotes<T: AsRef<Path>>(path: T) -> Result<Vec<RemoteInfo>> {
+    let repo = Repository::open(path.as_ref())?;
+    let config = repo.config()?;
+    let mut infos = vec![];
+    for name in repo.remotes()?.iter().flatten() {
+        let remote = repo.find_remote(name)?;
+        infos.push(RemoteInfo {
+            name: name.to_string(),
+            url: remote.url().map(|x| x.to_string()),
+            push_urls: config_values(&config, &format!("remote.{}.pushurl", name))?,
+            fetch: remote.fetch_refspecs()?.iter().flatten().map(|x| x.to_string()).collect(),
+            push: remote.push_refspecs()?.iter().flatten().map(|x| x.to_string()).collect(),
+        });
+    }
+    infos.sort_by(|x, y| x.name.cmp(&y.name));
+    Ok(infos)
+}
+
+/// Add a new remote with the given name and url using the default fetch refspec
+/// `+refs/heads/*:refs/remotes/<name>/*`, the same as `git remote add <name> <url>`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_remote_add_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::remote_add(&tmpdir, "upstream", "https://github.com/phR0ze/alpine-base").is_ok());
+/// assert_eq!(git::remotes(&tmpdir).unwrap()[1].name, "upstream");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn remote_add<T, U, V>(path: T, name: U, url: V) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    repo.remote(name.as_ref(), url.as_ref())?;
+    Ok(())
+}
+
+/// Remove the given remote along with its remote-tracking branches and configuration, the same
+/// as `git remote remove <name>`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_remote_remove_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::remote_remove(&tmpdir, "origin").is_ok());
+/// assert!(git::remotes(&tmpdir).unwrap().is_empty());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn remote_remove<T, U>(path: T, name: U) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    repo.remote_delete(name.as_ref())?;
+    Ok(())
+}
+
+/// Rename the given remote updating its remote-tracking branches and default fetch refspec,
+/// the same as `git remote rename <old> <new>`. Returns any non-default fetch refspecs that
+/// couldn't be updated and need to be fixed by hand.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_remote_rename_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::remote_rename(&tmpdir, "origin", "upstream").unwrap().is_empty());
+/// assert_eq!(git::remotes(&tmpdir).unwrap()[0].name, "upstream");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn remote_rename<T, U, V>(path: T, old: U, new: V) -> Result<Vec<String>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let problems = repo.remote_rename(old.as_ref(), new.as_ref())?;
+    Ok(problems.iter().flatten().map(|x| x.to_string()).collect())
+}
+
+/// Set the url of the given remote e.g. to switch between SSH and HTTPS, the same as
+/// `git remote set-url <name> <url>`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_remote_set_url_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::remote_set_url(&tmpdir, "origin", "git@github.com:phR0ze/alpine-base.git").is_ok());
+/// assert_eq!(git::remotes(&tmpdir).unwrap()[0].url, Some("git@github.com:phR0ze/alpine-base.git".to_string()));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn remote_set_url<T, U, V>(path: T, name: U, url: V) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    repo.find_remote(name.as_ref())?;
+    repo.remote_set_url(name.as_ref(), url.as_ref())?;
+    Ok(())
+}
+
+/// Set the url used for pushing to the given remote replacing any others or clear them with None
+/// to push to the fetch url, the same as `git remote set-url --push <name> <url>`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_remote_set_push_url_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::remote_set_push_url(&tmpdir, "origin", Some("git@github.com:phR0ze/alpine-base.git")).is_ok());
+/// assert_eq!(git::remotes(&tmpdir).unwrap()[0].push_urls, vec!["git@github.com:phR0ze/alpine-base.git"]);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn remote_set_push_url<T, U>(path: T, name: U, url: Option<&str>) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    repo.find_remote(name.as_ref())?;
+    let key = format!("remote.{}.pushurl", name.as_ref());
+    let mut config = repo.config()?.open_level(ConfigLevel::Local)?;
+    remove_values(&mut config, &key)?;
+    if let Some(url) = url {
+        config.set_str(&key, url)?;
+    }
+    Ok(())
+}
+
+/// Add another url used for pushing to the given remote so that pushes with `Repo::push_urls`
+/// publish to all of them e.g. to GitHub and an internal server at the same time, the same as
+/// `git remote set-url --add --push <name> <url>`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_remote_add_push_url_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::remote_add_push_url(&tmpdir, "origin", "git@github.com:phR0ze/alpine-base.git").is_ok());
+/// assert!(git::remote_add_push_url(&tmpdir, "origin", "git@git.example.com:mirror/alpine-base.git").is_ok());
+/// assert_eq!(git::remotes(&tmpdir).unwrap()[0].push_urls.len(), 2);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn remote_add_push_url<T, U, V>(path: T, name: U, url: V) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    repo.find_remote(name.as_ref())?;
+
+    // A regex that matches no existing value appends a new one
+    let key = format!("remote.{}.pushurl", name.as_ref());
+    repo.config()?.open_level(ConfigLevel::Local)?.set_multivar(&key, "^$", url.as_ref())?;
+    Ok(())
+}
+
+/// Replace the fetch refspecs of the given remote, the same as setting `remote.<name>.fetch`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_remote_set_fetch_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::remote_set_fetch(&tmpdir, "origin", &["+refs/heads/master:refs/remotes/origin/master"]).is_ok());
+/// assert_eq!(git::remotes(&tmpdir).unwrap()[0].fetch, vec!["+refs/heads/master:refs/remotes/origin/master"]);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn remote_set_fetch<T, U, V>(path: T, name: U, refspecs: &[V]) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    set_refspecs(path.as_ref(), name.as_ref(), "fetch", refspecs)
+}
+
+/// Replace the push refspecs of the given remote, the same as setting `remote.<name>.push`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_remote_set_push_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::remote_set_push(&tmpdir, "origin", &["refs/heads/master:refs/heads/release"]).is_ok());
+/// assert_eq!(git::remotes(&tmpdir).unwrap()[0].push, vec!["refs/heads/master:refs/heads/release"]);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn remote_set_push<T, U, V>(path: T, name: U, refspecs: &[V]) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    set_refspecs(path.as_ref(), name.as_ref(), "push", refspecs)
+}
+
+/// Add a fetch refspec to the given remote, the same as `git config --add remote.<name>.fetch`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_remote_add_fetch_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::remote_add_fetch(&tmpdir, "origin", "+refs/notes/*:refs/notes/*").is_ok());
+/// assert_eq!(git::remotes(&tmpdir).unwrap()[0].fetch.len(), 2);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn remote_add_fetch<T, U, V>(path: T, name: U, refspec: V) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    repo.find_remote(name.as_ref())?;
+    repo.remote_add_fetch(name.as_ref(), refspec.as_ref())?;
+    Ok(())
+}
+
+/// Add a push refspec to the given remote, the same as `git config --add remote.<name>.push`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_remote_add_push_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::remote_add_push(&tmpdir, "origin", "refs/notes/*:refs/notes/*").is_ok());
+/// assert_eq!(git::remotes(&tmpdir).unwrap()[0].push.len(), 1);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn remote_add_push<T, U, V>(path: T, name: U, refspec: V) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    repo.find_remote(name.as_ref())?;
+    repo.remote_add_push(name.as_ref(), refspec.as_ref())?;
+    Ok(())
+}
+
+// Replace the refspecs of the given direction for the given remote
+fn set_refspecs<T: AsRef<str>>(path: &Path, name: &str, direction: &str, refspecs: &[T]) -> Result<()> {
+    let repo = Repository::open(path)?;
+    repo.find_remote(name)?;
+    remove_values(&mut repo.config()?, &format!("remote.{}.{}", name, direction))?;
+    for refspec in refspecs.iter() {
+        match direction {
+            "fetch" => repo.remote_add_fetch(name, refspec.as_ref())?,
+            _ => repo.remote_add_push(name, refspec.as_ref())?,
+        }
+    }
+    Ok(())
+}
+
+// Returns all the values of the given multivar config key
+pub(crate) fn config_values(config: &Config, key: &str) -> Result<Vec<String>> {
+    let mut values = vec![];
+    match config.multivar(key, None) {
+        Ok(mut entries) => {
+            while let Some(entry) = entries.next() {
+                if let Some(value) = entry?.value() {
+                    values.push(value.to_string());
+                }
+            }
+        },
+        Err(err) if err.code() != ErrorCode::NotFound => return Err(err.into()),
+        Err(_) => {},
+    }
+    Ok(values)
+}
+
+// Remove all the values of the given multivar config key if any
+fn remove_values(config: &mut Config, key: &str) -> Result<()> {
+    match config.remove_multivar(key, ".*") {
+        Err(err) if err.code() != ErrorCode::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_remotes() {
+        let tmpdir = setup("git_remotes");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let origin = git::remotes(&tmpdir).unwrap().remove(0);
+        assert_eq!(origin.name, "origin");
+        assert_eq!(origin.fetch, vec!["+refs/heads/*:refs/remotes/origin/*"]);
+        assert_eq!(origin.push_urls, Vec::<String>::new());
+
+        // Add
+        let url = "https://github.com/phR0ze/alpine-base";
+        assert!(git::remote_add(&tmpdir, "upstream", url).is_ok());
+        let infos = git::remotes(&tmpdir).unwrap();
+        assert_eq!(infos.iter().map(|x| x.name.as_str()).collect::<Vec<_>>(), vec!["origin", "upstream"]);
+        assert_eq!(infos[1].url, Some(url.to_string()));
+        assert_eq!(infos[1].fetch, vec!["+refs/heads/*:refs/remotes/upstream/*"]);
+        assert!(git::remote_add(&tmpdir, "upstream", url).is_err());
+        assert!(git::remote_add(&tmpdir, "bad name", url).is_err());
+
+        // Urls
+        let ssh = "git@github.com:phR0ze/alpine-base.git";
+        assert!(git::remote_set_url(&tmpdir, "upstream", ssh).is_ok());
+        assert!(git::remote_set_push_url(&tmpdir, "upstream", Some(url)).is_ok());
+        let upstream = git::remotes(&tmpdir).unwrap().remove(1);
+        assert_eq!(upstream.url, Some(ssh.to_string()));
+        assert_eq!(upstream.push_urls, vec![url]);
+        assert!(git::remote_add_push_url(&tmpdir, "upstream", ssh).is_ok());
+        assert_eq!(git::remotes(&tmpdir).unwrap()[1].push_urls, vec![url, ssh]);
+        assert!(git::remote_set_push_url(&tmpdir, "upstream", Some(ssh)).is_ok());
+        assert_eq!(git::remotes(&tmpdir).unwrap()[1].push_urls, vec![ssh]);
+        assert!(git::remote_set_push_url(&tmpdir, "upstream", None).is_ok());
+        assert_eq!(git::remotes(&tmpdir).unwrap()[1].push_urls, Vec::<String>::new());
+        assert!(git::remote_add_push_url(&tmpdir, "missing", ssh).is_err());
+        assert!(git::remote_set_url(&tmpdir, "missing", ssh).is_err());
+        assert!(git::remote_set_push_url(&tmpdir, "missing", None).is_err());
+
+        // Refspecs
+        assert!(git::remote_add_fetch(&tmpdir, "upstream", "+refs/notes/*:refs/notes/*").is_ok());
+        assert!(git::remote_add_push(&tmpdir, "upstream", "refs/heads/master:refs/heads/release").is_ok());
+        let upstream = git::remotes(&tmpdir).unwrap().remove(1);
+        assert_eq!(
+            upstream.fetch,
+            vec![
+                "+refs/heads/*:refs/remotes/upstream/*",
+                "+refs/notes/*:refs/notes/*"
+            ]
+        );
+        assert_eq!(upstream.push, vec!["refs/heads/master:refs/heads/release"]);
+        assert!(git::remote_set_fetch(&tmpdir, "upstream", &["+refs/heads/master:refs/remotes/upstream/master"]).is_ok());
+        assert!(git::remote_set_push::<_, _, &str>(&tmpdir, "upstream", &[]).is_ok());
+        let upstream = git::remotes(&tmpdir).unwrap().remove(1);
+        assert_eq!(upstream.fetch, vec!["+refs/heads/master:refs/remotes/upstream/master"]);
+        assert_eq!(upstream.push, Vec::<String>::new());
+        assert!(git::remote_add_fetch(&tmpdir, "missing", "+refs/notes/*:refs/notes/*").is_err());
+        assert!(git::remote_set_fetch(&tmpdir, "missing", &["+refs/notes/*:refs/notes/*"]).is_err());
+
+        // Rename moves remote-tracking branches
+        assert!(git::remote_rename(&tmpdir, "origin", "old").unwrap().is_empty());
+        assert!(repo.find_reference("refs/remotes/old/master").is_ok());
+        assert!(repo.find_reference("refs/remotes/origin/master").is_err());
+        assert_eq!(git::remotes(&tmpdir).unwrap()[0].fetch, vec!["+refs/heads/*:refs/remotes/old/*"]);
+        assert_eq!(git::remote_rename(&tmpdir, "upstream", "new").unwrap(), vec!["+refs/heads/master:refs/remotes/upstream/master"]);
+        assert!(git::remote_rename(&tmpdir, "missing", "other").is_err());
+
+        // Remove
+        assert!(git::remote_remove(&tmpdir, "old").is_ok());
+        assert!(repo.find_reference("refs/remotes/old/master").is_err());
+        assert_eq!(git::remotes(&tmpdir).unwrap().iter().map(|x| x.name.as_str()).collect::<Vec<_>>(), vec!["new"]);
+        assert!(git::remote_remove(&tmpdir, "missing").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,116 @@
+use super::is_dirty;
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{Oid, Repository, ResetType};
+
+/// Mode used by `reset` to determine what is reset besides the current branch
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ResetMode {
+    /// Only move the current branch leaving the index and worktree as is, the same as
+    /// `git reset --soft`
+    Soft,
+
+    /// Move the current branch and reset the index leaving the worktree as is, the same as
+    /// `git reset --mixed`
+    #[default]
+    Mixed,
+
+    /// Move the current branch and reset the index and worktree discarding any uncommitted
+    /// changes to tracked files, the same as `git reset --hard`
+    Hard,
+}
+
+/// Reset the current branch to the given revision using the given mode, the same as
+/// `git reset --<mode> <rev>`, returning the id of the commit reset to. As a safety check a hard
+/// reset is refused with `Error::DirtyWorktree` if the worktree has uncommitted changes to
+/// tracked files unless `force` is set. Untracked files are always left in place.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_reset_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+/// assert!(git::reset(&tmpdir, "HEAD~1", git::ResetMode::Hard, false).is_err());
+/// assert!(git::reset(&tmpdir, "HEAD~1", git::ResetMode::Hard, true).is_ok());
+/// assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn reset<T, U>(path: T, rev: U, mode: ResetMode, force: bool) -> Result<Oid>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let commit = repo.revparse_single(rev.as_ref()).map_err(|_| Error::revision_not_found(rev.as_ref()))?.peel_to_commit()?;
+    if mode == ResetMode::Hard && !force && is_dirty(path.as_ref())? {
+        return Err(Error::dirty_worktree(path.as_ref()));
+    }
+    let kind = match mode {
+        ResetMode::Soft => ResetType::Soft,
+        ResetMode::Mixed => ResetType::Mixed,
+        ResetMode::Hard => ResetType::Hard,
+    };
+    repo.reset(commit.as_object(), kind, None)?;
+    Ok(commit.id())
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_reset() {
+        let tmpdir = setup("git_reset");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let head = || repo.head().unwrap().target().unwrap();
+        let codes = || git::status(&tmpdir, &git::StatusOptions::new()).unwrap().iter().map(|x| x.code()).collect::<Vec<_>>();
+        let (first, second) = (repo.revparse_single("HEAD~2").unwrap().id(), repo.revparse_single("HEAD~1").unwrap().id());
+        let original = head();
+
+        // Soft keeps the changes staged
+        assert_eq!(git::reset(&tmpdir, "HEAD~1", git::ResetMode::Soft, false).unwrap(), second);
+        assert_eq!(head(), second);
+        assert_eq!(codes().is_empty(), false);
+        assert!(codes().iter().all(|x| x.ends_with('.')));
+
+        // Mixed keeps the changes unstaged
+        assert_eq!(git::reset(&tmpdir, original.to_string(), git::ResetMode::Soft, false).unwrap(), original);
+        assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+        assert_eq!(git::reset(&tmpdir, "HEAD~1", git::ResetMode::default(), false).unwrap(), second);
+        assert_eq!(codes().is_empty(), false);
+        assert!(codes().iter().all(|x| x.starts_with('.')));
+        assert_eq!(sys::readstring(tmpdir.mash("README.md")).unwrap(), "foo");
+
+        // Hard requires force when dirty and leaves untracked files
+        let err = git::reset(&tmpdir, "HEAD~1", git::ResetMode::Hard, false).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::dirty_worktree(&tmpdir).to_string());
+        assert_eq!(head(), second);
+        assert_eq!(sys::readstring(tmpdir.mash("README.md")).unwrap(), "foo");
+        assert!(sys::write(tmpdir.mash("untracked"), "untracked").is_ok());
+        assert_eq!(git::reset(&tmpdir, "HEAD~1", git::ResetMode::Hard, true).unwrap(), first);
+        assert_eq!(codes(), vec!["??"]);
+        assert!(git::reset(&tmpdir, original.to_string(), git::ResetMode::Hard, false).is_ok());
+        assert_eq!(head(), original);
+
+        // Errors
+        let err = git::reset(&tmpdir, "missing", git::ResetMode::Soft, false).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::revision_not_found("missing").to_string());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
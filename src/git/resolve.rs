@@ -0,0 +1,123 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{ErrorCode, Oid, Repository};
+
+// Reference lookup rules for names the same as git's in priority order
+const REF_RULES: [&str; 5] = ["refs/{}", "refs/tags/{}", "refs/heads/{}", "refs/remotes/{}", "refs/remotes/{}/HEAD"];
+
+/// Resolve the given revision to a commit id. Anything `git rev-parse` accepts is supported
+/// e.g. tags, which are peeled, branch names, short commit ids and `HEAD~N` syntax. A name that
+/// matches different commits through different refs e.g. both a branch and a tag, or a short
+/// commit id matching multiple objects, is reported as ambiguous.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_resolve_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert_eq!(git::resolve(&tmpdir, "3.11").unwrap(), git::resolve(&tmpdir, "HEAD~3").unwrap());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn resolve<T, U>(path: T, rev: U) -> Result<Oid>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    resolve_commit(&repo, rev.as_ref())
+}
+
+// Resolve the given revision to a commit id in the given repo
+pub(crate) fn resolve_commit(repo: &Repository, rev: &str) -> Result<Oid> {
+    // Check the leading name for refs that resolve to different commits
+    let name = rev.split(|c| "~^:".contains(c)).next().unwrap_or_default();
+    if !name.is_empty() && !name.contains("@{") {
+        let mut matches: Vec<(String, Oid)> = vec![];
+        for rule in REF_RULES.iter() {
+            let refname = rule.replace("{}", name);
+            if let Ok(oid) = repo.find_reference(&refname).and_then(|x| x.peel_to_commit()).map(|x| x.id()) {
+                matches.push((refname, oid));
+            }
+        }
+        if matches.iter().any(|(_, oid)| *oid != matches[0].1) {
+            let refs: Vec<&str> = matches.iter().map(|(refname, _)| refname.as_str()).collect();
+            return Err(Error::ambiguous_revision(name, format!("matches {}", refs.join(", "))));
+        }
+    }
+
+    let object = match repo.revparse_single(rev) {
+        Ok(object) => object,
+        Err(err) if err.code() == ErrorCode::Ambiguous => return Err(Error::ambiguous_revision(rev, "short id matches multiple objects")),
+        Err(err) if err.code() == ErrorCode::NotFound || err.code() == ErrorCode::InvalidSpec => return Err(Error::revision_not_found(rev)),
+        Err(err) => return Err(err.into()),
+    };
+    Ok(object.peel_to_commit()?.id())
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use std::collections::HashMap;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_resolve() {
+        let tmpdir = setup("git_resolve");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let parent = head.parent(0).unwrap().id();
+
+        // Branches, remote branches, HEAD syntax and commit ids
+        assert_eq!(git::resolve(&tmpdir, "HEAD").unwrap(), head.id());
+        assert_eq!(git::resolve(&tmpdir, "master").unwrap(), head.id());
+        assert_eq!(git::resolve(&tmpdir, "origin/master").unwrap(), repo.find_reference("refs/remotes/origin/master").unwrap().peel_to_commit().unwrap().id());
+        assert_eq!(git::resolve(&tmpdir, "HEAD~1").unwrap(), parent);
+        assert_eq!(git::resolve(&tmpdir, "master^").unwrap(), parent);
+        assert_eq!(git::resolve(&tmpdir, head.id().to_string()).unwrap(), head.id());
+        assert_eq!(git::resolve(&tmpdir, &head.id().to_string()[..7]).unwrap(), head.id());
+
+        // Tags are peeled
+        assert_eq!(git::resolve(&tmpdir, "3.11").unwrap(), repo.revparse_single("HEAD~3").unwrap().id());
+        assert!(git::tag_annotated(&tmpdir, "v1.0.0", "HEAD~1", "Release 1.0.0", Some(("foo", "foo@example.com"))).is_ok());
+        assert_eq!(git::resolve(&tmpdir, "v1.0.0").unwrap(), parent);
+        assert_eq!(git::resolve(&tmpdir, "v1.0.0~1").unwrap(), repo.revparse_single("HEAD~2").unwrap().id());
+
+        // Branch and tag with the same name
+        assert!(git::branch_create(&tmpdir, "v1.0.0", None, false).is_ok());
+        let err = git::resolve(&tmpdir, "v1.0.0~1").unwrap_err();
+        assert_eq!(err.to_string(), git::Error::ambiguous_revision("v1.0.0", "matches refs/tags/v1.0.0, refs/heads/v1.0.0").to_string());
+        assert!(git::branch_create(&tmpdir, "3.11", Some("3.11"), false).is_ok());
+        assert_eq!(git::resolve(&tmpdir, "3.11").unwrap(), repo.revparse_single("HEAD~3").unwrap().id());
+
+        // Short id matching multiple objects
+        let odb = repo.odb().unwrap();
+        let mut prefixes: HashMap<String, git2::Oid> = HashMap::new();
+        let prefix = (0..100000)
+            .find_map(|i| {
+                let oid = odb.write(git2::ObjectType::Blob, format!("{}", i).as_bytes()).unwrap();
+                let prefix = oid.to_string()[..4].to_string();
+                prefixes.insert(prefix.clone(), oid).map(|_| prefix)
+            })
+            .unwrap();
+        assert_eq!(git::resolve(&tmpdir, &prefix).unwrap_err().to_string(), git::Error::ambiguous_revision(&prefix, "short id matches multiple objects").to_string());
+
+        // Not found or not a commit
+        assert_eq!(git::resolve(&tmpdir, "missing").unwrap_err().to_string(), git::Error::revision_not_found("missing").to_string());
+        assert_eq!(git::resolve(&tmpdir, "HEAD~100").unwrap_err().to_string(), git::Error::revision_not_found("HEAD~100").to_string());
+        assert!(git::resolve(&tmpdir, "HEAD:README.md").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
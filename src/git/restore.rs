@@ -0,0 +1,158 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{build::CheckoutBuilder, Pathspec, PathspecFlags, PathspecMatchList, Repository};
+
+/// Location paths are restored to by `restore`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum RestoreLocation {
+    /// Restore the worktree only from the index by default, the same as `git restore`
+    #[default]
+    Worktree,
+
+    /// Restore the index only from HEAD by default i.e. unstage, the same as
+    /// `git restore --staged`
+    Index,
+
+    /// Restore both the index and worktree from HEAD by default, the same as
+    /// `git restore --staged --worktree`
+    Both,
+}
+
+/// Restore the given paths or pathspecs e.g. `src` or `*.rs` to their content in the given
+/// source revision, the same as `git restore --source=<source> -- <paths>`, leaving the rest of
+/// the tree untouched. Without a source the worktree is restored from the index and the index
+/// from HEAD. Returns `Error::PathNotFound` without changing anything if a path doesn't match
+/// any file in the source.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_restore_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+/// assert!(git::restore(&tmpdir, &["README.md"], None, git::RestoreLocation::Worktree).is_ok());
+/// assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn restore<T, U>(path: T, paths: &[U], source: Option<&str>, location: RestoreLocation) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let paths: Vec<&str> = paths.iter().map(|x| x.as_ref()).collect();
+    let commit = match source {
+        Some(rev) => Some(repo.revparse_single(rev).map_err(|_| Error::revision_not_found(rev))?.peel_to_commit()?),
+        None if location != RestoreLocation::Worktree => Some(repo.head()?.peel_to_commit()?),
+        None => None,
+    };
+
+    // Paths only need to match in the index or the source to be restored
+    let spec = Pathspec::new(paths.iter())?;
+    let failures = |list: PathspecMatchList| list.failed_entries().map(|x| String::from_utf8_lossy(x).to_string()).collect::<Vec<_>>();
+    let mut missing = match &commit {
+        Some(commit) => failures(spec.match_tree(&commit.tree()?, PathspecFlags::FIND_FAILURES)?),
+        None => paths.iter().map(|x| x.to_string()).collect(),
+    };
+    if commit.is_none() || location != RestoreLocation::Worktree {
+        let failed = failures(spec.match_index(&repo.index()?, PathspecFlags::FIND_FAILURES)?);
+        missing.retain(|x| failed.contains(x));
+    }
+    if let Some(path) = missing.first() {
+        return Err(Error::path_not_found(path));
+    }
+
+    if location != RestoreLocation::Worktree {
+        repo.reset_default(commit.as_ref().map(|x| x.as_object()), paths.iter())?;
+    }
+    if location != RestoreLocation::Index {
+        let mut checkout = CheckoutBuilder::new();
+        checkout.force();
+        for path in paths.iter() {
+            checkout.path(*path);
+        }
+        match &commit {
+            Some(commit) if location == RestoreLocation::Worktree => {
+                checkout.update_index(false);
+                repo.checkout_tree(commit.as_object(), Some(&mut checkout))?;
+            },
+            _ => repo.checkout_index(None, Some(&mut checkout))?,
+        }
+    }
+    Ok(())
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_restore() {
+        let tmpdir = setup("git_restore");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        let codes = || git::status(&tmpdir, &git::StatusOptions::new()).unwrap().iter().map(|x| (x.path.to_string().unwrap(), x.code())).collect::<Vec<_>>();
+        let code = |path: &str, code: &str| (path.to_string(), code.to_string());
+        let read = |path: &str| sys::readstring(tmpdir.mash(path)).unwrap();
+        assert!(sys::write(tmpdir.mash("foo"), "one\n").is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).message("One").commit().is_ok());
+        assert!(sys::write(tmpdir.mash("foo"), "two\n").is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).message("Two").commit().is_ok());
+
+        // Worktree from the index leaving other paths alone
+        assert!(sys::write(tmpdir.mash("foo"), "staged\n").is_ok());
+        assert!(git::stage(&tmpdir, &["foo"]).is_ok());
+        assert!(sys::write(tmpdir.mash("foo"), "unstaged\n").is_ok());
+        assert!(sys::write(tmpdir.mash("README.md"), "readme\n").is_ok());
+        assert!(sys::remove(tmpdir.mash("LICENSE")).is_ok());
+        assert!(git::restore(&tmpdir, &["foo", "LICENSE"], None, git::RestoreLocation::Worktree).is_ok());
+        assert_eq!(read("foo"), "staged\n");
+        assert_eq!(codes(), vec![code("README.md", ".M"), code("foo", "M.")]);
+
+        // Index from HEAD i.e. unstage
+        assert!(git::restore(&tmpdir, &["foo"], None, git::RestoreLocation::Index).is_ok());
+        assert_eq!(codes(), vec![code("README.md", ".M"), code("foo", ".M")]);
+        assert_eq!(read("foo"), "staged\n");
+
+        // Both from a source revision with pathspecs
+        assert!(git::restore(&tmpdir, &["fo*"], Some("HEAD~1"), git::RestoreLocation::Both).is_ok());
+        assert_eq!(read("foo"), "one\n");
+        assert_eq!(codes(), vec![code("README.md", ".M"), code("foo", "M.")]);
+
+        // Worktree from a source revision leaves the index
+        assert!(git::restore(&tmpdir, &["foo"], Some("HEAD"), git::RestoreLocation::Worktree).is_ok());
+        assert_eq!(read("foo"), "two\n");
+        assert_eq!(codes(), vec![code("README.md", ".M"), code("foo", "MM")]);
+
+        // Newly staged files are unstaged
+        assert!(sys::write(tmpdir.mash("new"), "new\n").is_ok());
+        assert!(git::stage(&tmpdir, &["new"]).is_ok());
+        assert!(git::restore(&tmpdir, &["new"], None, git::RestoreLocation::Index).is_ok());
+        assert_eq!(codes().last().unwrap(), &code("new", "??"));
+
+        // Errors leave everything as is
+        let err = git::restore(&tmpdir, &["foo", "missing"], Some("HEAD~1"), git::RestoreLocation::Both).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::path_not_found("missing").to_string());
+        assert_eq!(read("foo"), "two\n");
+        assert!(git::restore(&tmpdir, &["new"], None, git::RestoreLocation::Worktree).is_err());
+        assert!(git::restore(&tmpdir, &["foo"], Some("HEAD~2"), git::RestoreLocation::Worktree).is_err());
+        let err = git::restore(&tmpdir, &["foo"], Some("bogus"), git::RestoreLocation::Worktree).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::revision_not_found("bogus").to_string());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,163 @@
+use super::{conflicts_error, is_dirty, resolve_commit, write_commit};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{build::CheckoutBuilder, Commit, Oid, Repository, RevertOptions};
+
+/// Revert the given commit by creating a new commit that undoes its changes with the message
+/// `Revert "<summary>"`, the same as `git revert`. The configured `user.name` and `user.email`
+/// are used as the author and committer. Reverting is refused if the worktree has uncommitted
+/// changes. On conflicts `Error::MergeConflicts` is returned and the repo is left mid revert
+/// with the conflicts in the worktree and index. Returns the id of the new commit.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_revert_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "test").unwrap();
+/// repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+/// assert!(git::revert(&tmpdir, "HEAD").is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn revert<T, U>(path: T, rev: U) -> Result<Oid>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let path = path.as_ref();
+    let repo = Repository::open(path)?;
+    let commit = apply(&repo, path, rev.as_ref())?;
+    let message = format!("Revert \"{}\"\n\nThis reverts commit {}.\n", commit.summary().unwrap_or_default(), commit.id());
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    let head = repo.head()?.peel_to_commit()?;
+    let sig = repo.signature()?;
+    let oid = write_commit(&repo, &sig, &sig, &message, &tree, &[&head], None, false)?;
+    repo.cleanup_state()?;
+    Ok(oid)
+}
+
+/// Revert the given commit the same as `revert` but only stage the changes that undo it
+/// rather than committing them, the same as `git revert --no-commit`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_revert_no_commit_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::revert_no_commit(&tmpdir, "HEAD").is_ok());
+/// assert_eq!(git::is_dirty(&tmpdir).unwrap(), true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn revert_no_commit<T, U>(path: T, rev: U) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let path = path.as_ref();
+    let repo = Repository::open(path)?;
+    apply(&repo, path, rev.as_ref())?;
+    repo.cleanup_state()?;
+    Ok(())
+}
+
+// Apply the inverse of the given commit to the index and worktree returning the commit
+fn apply<'a>(repo: &'a Repository, path: &Path, rev: &str) -> Result<Commit<'a>> {
+    if is_dirty(path)? {
+        return Err(Error::dirty_worktree(path));
+    }
+    let commit = repo.find_commit(resolve_commit(repo, rev)?)?;
+    let mut opts = RevertOptions::new();
+    let mut checkout = CheckoutBuilder::new();
+    checkout.safe().allow_conflicts(true).conflict_style_merge(true);
+    opts.checkout_builder(checkout);
+    repo.revert(&commit, Some(&mut opts))?;
+
+    let index = repo.index()?;
+    if index.has_conflicts() {
+        return Err(conflicts_error(&index)?);
+    }
+    Ok(commit)
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Extract the test repo with a test identity
+    fn init(path: &Path) -> git2::Repository {
+        assert!(sys::remove_all(path).is_ok());
+        assert!(tar::extract_all(path.mash("../../alpine-base.tgz"), path).is_ok());
+        let repo = git2::Repository::open(path).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        repo
+    }
+
+    // Commit the given content to the given file
+    fn commit(path: &Path, file: &str, content: &str) -> git2::Oid {
+        assert!(sys::write(path.mash(file), content).is_ok());
+        git::CommitBuilder::new(path).stage_all(true).message(format!("Update {}", file)).commit().unwrap()
+    }
+
+    #[test]
+    fn test_revert() {
+        let tmpdir = setup("git_revert");
+        let repo = init(&tmpdir);
+        let readme = sys::readstring(tmpdir.mash("README.md")).unwrap();
+        let bad = commit(&tmpdir, "README.md", "bad");
+        commit(&tmpdir, "foo", "foo");
+
+        // Revert commit
+        let oid = git::revert(&tmpdir, bad.to_string()).unwrap();
+        let reverted = repo.find_commit(oid).unwrap();
+        assert_eq!(reverted.message().unwrap(), format!("Revert \"Update README.md\"\n\nThis reverts commit {}.\n", bad));
+        assert_eq!(reverted.author().name(), Some("test"));
+        assert_eq!(repo.head().unwrap().target(), Some(oid));
+        assert_eq!(sys::readstring(tmpdir.mash("README.md")).unwrap(), readme);
+        assert_eq!(sys::readstring(tmpdir.mash("foo")).unwrap(), "foo");
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+
+        // Staged only
+        git::revert_no_commit(&tmpdir, "HEAD~1").unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(oid));
+        assert_eq!(repo.state(), git2::RepositoryState::Clean);
+        assert_eq!(tmpdir.mash("foo").exists(), false);
+        let status = repo.status_file(Path::new("foo")).unwrap();
+        assert_eq!(status, git2::Status::INDEX_DELETED);
+
+        // Dirty worktree
+        let err = git::revert(&tmpdir, "HEAD").unwrap_err();
+        assert_eq!(err.to_string(), git::Error::dirty_worktree(&tmpdir).to_string());
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force())).unwrap();
+
+        // Conflicts leave the repo mid revert
+        commit(&tmpdir, "README.md", "changed");
+        let err = git::revert(&tmpdir, oid.to_string()).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::merge_conflicts(&["README.md"]).to_string());
+        assert_eq!(repo.state(), git2::RepositoryState::Revert);
+        repo.cleanup_state().unwrap();
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force())).unwrap();
+
+        // Missing commit
+        assert!(git::revert(&tmpdir, "missing").is_err());
+        assert!(git::revert_no_commit(&tmpdir, "missing").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,306 @@
+use super::config_values;
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{Config, Remote, Repository};
+
+/// Url rewrite rules equivalent to git's `url.<base>.insteadOf` and `url.<base>.pushInsteadOf`
+/// configuration e.g. to redirect GitHub urls to an internal mirror. Rules configured in git
+/// config are always honored when cloning, fetching and pushing, these rules allow the same to
+/// be done programmatically for the urls given to `Repo`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let rewrite = git::UrlRewrite::new().instead_of("https://git.example.com/mirror/", "https://github.com/");
+/// assert_eq!(rewrite.rewrite("https://github.com/phR0ze/alpine-base"), "https://git.example.com/mirror/phR0ze/alpine-base");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UrlRewrite {
+    fetch: Vec<(String, String)>, // Base and prefix pairs rewriting all urls
+    push: Vec<(String, String)>,  // Base and prefix pairs rewriting push urls only
+}
+
+impl UrlRewrite {
+    /// Create a new empty set of rewrite rules that leave urls as is.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::UrlRewrite::new().is_empty(), true);
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the rewrite rules from the user's global and system git config, the same rules git
+    /// uses outside of a repo.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert!(git::UrlRewrite::load().is_ok());
+    /// ```
+    pub fn load() -> Result<Self> {
+        Self::from_config(&Config::open_default()?)
+    }
+
+    /// Rewrite urls starting with `prefix` to start with `base` instead, the same as
+    /// `url.<base>.insteadOf = <prefix>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let rewrite = git::UrlRewrite::new().instead_of("git@github.com:", "gh:");
+    /// assert_eq!(rewrite.rewrite("gh:phR0ze/skellige"), "git@github.com:phR0ze/skellige");
+    /// ```
+    pub fn instead_of<T: AsRef<str>, U: AsRef<str>>(mut self, base: T, prefix: U) -> Self {
+        self.fetch.push((base.as_ref().to_string(), prefix.as_ref().to_string()));
+        self
+    }
+
+    /// Rewrite urls used for pushing that start with `prefix` to start with `base` instead,
+    /// the same as `url.<base>.pushInsteadOf = <prefix>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let rewrite = git::UrlRewrite::new().push_instead_of("git@github.com:", "https://github.com/");
+    /// assert_eq!(rewrite.rewrite_push("https://github.com/phR0ze/skellige"), "git@github.com:phR0ze/skellige");
+    /// assert_eq!(rewrite.rewrite("https://github.com/phR0ze/skellige"), "https://github.com/phR0ze/skellige");
+    /// ```
+    pub fn push_instead_of<T: AsRef<str>, U: AsRef<str>>(mut self, base: T, prefix: U) -> Self {
+        self.push.push((base.as_ref().to_string(), prefix.as_ref().to_string()));
+        self
+    }
+
+    /// Returns true if there are no rewrite rules.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::UrlRewrite::new().instead_of("foo", "bar").is_empty(), false);
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.fetch.is_empty() && self.push.is_empty()
+    }
+
+    /// Rewrite the given url using the rule with the longest matching prefix, the same way git
+    /// rewrites fetch urls. Urls that match no rule are returned as is.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let rewrite = git::UrlRewrite::new().instead_of("https://github.com/", "gh:");
+    /// assert_eq!(rewrite.rewrite("gh:phR0ze/skellige"), "https://github.com/phR0ze/skellige");
+    /// assert_eq!(rewrite.rewrite("foo"), "foo");
+    /// ```
+    pub fn rewrite<T: AsRef<str>>(&self, url: T) -> String {
+        let url = url.as_ref();
+        apply(&self.fetch, url).unwrap_or_else(|| url.to_string())
+    }
+
+    /// Rewrite the given url for pushing. Push rules take precedence and the fetch rules are
+    /// only used if no push rule matches, the same way git rewrites push urls.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let rewrite = git::UrlRewrite::new().instead_of("https://github.com/", "gh:");
+    /// assert_eq!(rewrite.rewrite_push("gh:phR0ze/skellige"), "https://github.com/phR0ze/skellige");
+    /// ```
+    pub fn rewrite_push<T: AsRef<str>>(&self, url: T) -> String {
+        let url = url.as_ref();
+        apply(&self.push, url).unwrap_or_else(|| self.rewrite(url))
+    }
+
+    // Read the rewrite rules from the given config
+    fn from_config(config: &Config) -> Result<Self> {
+        let mut rewrite = Self::new();
+        let mut entries = config.entries(Some(r"^url\..*\.(push)?insteadof$"))?;
+        while let Some(entry) = entries.next() {
+            let entry = entry?;
+            let (name, prefix) = match (entry.name(), entry.value()) {
+                (Some(name), Some(prefix)) => (name, prefix),
+                _ => continue,
+            };
+            if let Some((base, key)) = name.strip_prefix("url.").and_then(|x| x.rsplit_once('.')) {
+                match key.to_lowercase().as_str() {
+                    "pushinsteadof" => rewrite = rewrite.push_instead_of(base, prefix),
+                    _ => rewrite = rewrite.instead_of(base, prefix),
+                }
+            }
+        }
+        Ok(rewrite)
+    }
+}
+
+/// Load the rewrite rules that apply to the repo at the given path including the user's global
+/// and system git config.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_url_rewrites_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::url_rewrite_add(&tmpdir, "https://github.com/", "gh:", false).is_ok());
+/// assert_eq!(git::url_rewrites(&tmpdir).unwrap().rewrite("gh:foo"), "https://github.com/foo");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn url_rewrites<T: AsRef<Path>>(path: T) -> Result<UrlRewrite> {
+    let repo = Repository::open(path.as_ref())?;
+    UrlRewrite::from_config(&repo.config()?)
+}
+
+/// Add a rewrite rule to the config of the repo at the given path, the same as `git config
+/// --add url.<base>.insteadOf <prefix>` or `url.<base>.pushInsteadOf` when `push` is set. The
+/// rule is honored by all later operations on the repo's remotes.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_url_rewrite_add_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::url_rewrite_add(&tmpdir, "git@github.com:", "https://github.com/", true).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn url_rewrite_add<T, U, V>(path: T, base: U, prefix: V, push: bool) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let key = format!("url.{}.{}", base.as_ref(), if push { "pushInsteadOf" } else { "insteadOf" });
+
+    // A regex that matches no existing value appends a new one
+    repo.config()?.open_level(git2::ConfigLevel::Local)?.set_multivar(&key, "^$", prefix.as_ref())?;
+    Ok(())
+}
+
+// Find the given remote for pushing. libgit2 reports the `pushInsteadOf` rewrite of the remote's
+// url as its push url but still pushes to the url, so without an explicit push url the url is
+// rewritten here falling back to an anonymous remote when it changes.
+pub(crate) fn push_remote<'a>(repo: &'a Repository, name: &str) -> Result<Remote<'a>> {
+    let remote = repo.find_remote(name)?;
+    let config = repo.config()?;
+    if config_values(&config, &format!("remote.{}.pushurl", name))?.is_empty() {
+        if let Ok(url) = config.get_string(&format!("remote.{}.url", name)) {
+            if let Some(url) = apply(&UrlRewrite::from_config(&config)?.push, &url) {
+                return Ok(repo.remote_anonymous(&url)?);
+            }
+        }
+    }
+    Ok(remote)
+}
+
+// Resolve the urls to push to for the given remote. Explicit push urls only have `insteadOf`
+// rules applied, the same as git, else the remote's url is rewritten for pushing.
+pub(crate) fn remote_push_urls(repo: &Repository, name: &str) -> Result<Vec<String>> {
+    repo.find_remote(name)?;
+    let config = repo.config()?;
+    let rewrite = UrlRewrite::from_config(&config)?;
+    let urls = config_values(&config, &format!("remote.{}.pushurl", name))?;
+    if !urls.is_empty() {
+        return Ok(urls.iter().map(|x| rewrite.rewrite(x)).collect());
+    }
+    let url = config.get_string(&format!("remote.{}.url", name)).map_err(|_| Error::UrlNotSet)?;
+    Ok(vec![rewrite.rewrite_push(url)])
+}
+
+// Apply the rule with the longest matching prefix if any
+fn apply(rules: &[(String, String)], url: &str) -> Option<String> {
+    rules
+        .iter()
+        .filter(|(_, prefix)| url.starts_with(prefix.as_str()))
+        .max_by_key(|(_, prefix)| prefix.len())
+        .map(|(base, prefix)| format!("{}{}", base, &url[prefix.len()..]))
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_url_rewrite() {
+        let rewrite = git::UrlRewrite::new()
+            .instead_of("https://github.com/", "gh:")
+            .instead_of("https://mirror.example.com/github/", "https://github.com/")
+            .instead_of("https://mirror.example.com/skellige/", "https://github.com/phR0ze/skellige")
+            .push_instead_of("git@github.com:", "https://github.com/");
+
+        // Longest prefix wins
+        assert_eq!(rewrite.rewrite("https://github.com/phR0ze/alpine-base"), "https://mirror.example.com/github/phR0ze/alpine-base");
+        assert_eq!(rewrite.rewrite("https://github.com/phR0ze/skellige"), "https://mirror.example.com/skellige/");
+        assert_eq!(rewrite.rewrite("gh:phR0ze/skellige"), "https://github.com/phR0ze/skellige");
+        assert_eq!(rewrite.rewrite("https://gitlab.com/foo"), "https://gitlab.com/foo");
+
+        // Push rules take precedence for push urls
+        assert_eq!(rewrite.rewrite_push("https://github.com/phR0ze/skellige"), "git@github.com:phR0ze/skellige");
+        assert_eq!(rewrite.rewrite_push("gh:phR0ze/skellige"), "https://github.com/phR0ze/skellige");
+        assert_eq!(git::UrlRewrite::new().is_empty(), true);
+        assert_eq!(rewrite.is_empty(), false);
+    }
+
+    #[test]
+    fn test_url_rewrite_remotes() {
+        let tmpdir = setup("git_url_rewrite_remotes");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let src = tmpdir.mash("src");
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+        let mirror = tmpdir.mash("mirror");
+        assert!(git::Repo::new(&mirror).unwrap().url(src.to_string().unwrap()).bare(true).clone().is_ok());
+        let base = format!("{}/", tmpdir.to_string().unwrap());
+
+        // Programmatic rules rewrite the clone url
+        let local = tmpdir.mash("local");
+        let rewrite = git::UrlRewrite::new().instead_of(&base, "internal:");
+        assert!(git::Repo::new(&local).unwrap().url("internal:mirror").url_rewrite(rewrite.clone()).clone().is_ok());
+        assert_eq!(local.mash("README.md").exists(), true);
+        assert!(git::Repo::new(tmpdir.mash("other")).unwrap().url("internal:mirror").clone().is_err());
+
+        // Rules in the repo's config are honored when fetching
+        assert!(git::url_rewrite_add(&local, &base, "internal:", false).is_ok());
+        assert!(git::remote_set_url(&local, "origin", "internal:mirror").is_ok());
+        assert_eq!(git::url_rewrites(&local).unwrap().rewrite("internal:mirror"), mirror.to_string().unwrap());
+        let bare = git2::Repository::open(&mirror).unwrap();
+        let head = bare.head().unwrap().peel_to_commit().unwrap();
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let oid = bare.commit(Some("HEAD"), &sig, &sig, "Empty", &head.tree().unwrap(), &[&head]).unwrap();
+        assert!(git::Repo::new(&local).unwrap().fetch(&git::FetchOptions::new()).is_ok());
+        let repo = git2::Repository::open(&local).unwrap();
+        assert_eq!(repo.refname_to_id("refs/remotes/origin/master").unwrap(), oid);
+
+        // Push rules redirect only pushes
+        let push = tmpdir.mash("push");
+        assert!(git::Repo::new(&push).unwrap().url(src.to_string().unwrap()).bare(true).clone().is_ok());
+        assert!(git::url_rewrite_add(&local, format!("{}push", base), "internal:mirror", true).is_ok());
+        assert_eq!(git::url_rewrites(&local).unwrap().rewrite_push("internal:mirror"), push.to_string().unwrap());
+        assert!(git::Repo::new(&local).unwrap().push_refs(&["refs/heads/master:refs/heads/pushed"]).is_ok());
+        assert!(git2::Repository::open(&push).unwrap().refname_to_id("refs/heads/pushed").is_ok());
+        assert!(bare.refname_to_id("refs/heads/pushed").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
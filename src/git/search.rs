@@ -0,0 +1,338 @@
+use super::CommitInfo;
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{Oid, Repository};
+use std::collections::{BinaryHeap, HashSet};
+
+// Pattern to match commit fields against
+#[derive(Clone, Debug)]
+enum Pattern {
+    Substring(String),
+    Regex(String),
+}
+
+impl Pattern {
+    // Compile the pattern into a matcher optionally ignoring case
+    fn compile(&self, ignore_case: bool) -> Result<Regex> {
+        let re = match self {
+            Pattern::Substring(x) => x.chars().fold(String::new(), |mut re, c| {
+                if "\\.+*?()|[]{}^$".contains(c) {
+                    re.push('\\');
+                }
+                re.push(c);
+                re
+            }),
+            Pattern::Regex(x) => x.clone(),
+        };
+        let re = if ignore_case { format!("(?i){}", re) } else { re };
+        Regex::new(&re).map_err(|x| Error::invalid_pattern(x.to_string()))
+    }
+}
+
+/// Options for selecting commits with `search`. All given criteria must match. By default all
+/// commits reachable from HEAD are selected.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let opts = git::SearchOptions::new().message_regex("^fix").author("foo").after(1577836800);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SearchOptions {
+    from: Option<String>,     // Start from this revision instead of HEAD
+    message: Option<Pattern>, // Only commits with a matching message
+    author: Option<Pattern>,  // Only commits with a matching author name or email
+    after: Option<i64>,       // Only commits made at or after this time
+    before: Option<i64>,      // Only commits made before this time
+    ignore_case: bool,        // Match patterns ignoring case
+}
+
+impl SearchOptions {
+    /// Create new search options selecting all commits reachable from HEAD.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::SearchOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start searching from the given revision rather than HEAD.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::SearchOptions::new().from("3.11");
+    /// ```
+    pub fn from<T: AsRef<str>>(mut self, rev: T) -> Self {
+        self.from = Some(rev.as_ref().to_string());
+        self
+    }
+
+    /// Only select commits whose message contains the given value.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::SearchOptions::new().message("badge");
+    /// ```
+    pub fn message<T: AsRef<str>>(mut self, value: T) -> Self {
+        self.message = Some(Pattern::Substring(value.as_ref().to_string()));
+        self
+    }
+
+    /// Only select commits whose message matches the given regular expression.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::SearchOptions::new().message_regex("^(feat|fix):");
+    /// ```
+    pub fn message_regex<T: AsRef<str>>(mut self, re: T) -> Self {
+        self.message = Some(Pattern::Regex(re.as_ref().to_string()));
+        self
+    }
+
+    /// Only select commits whose author name or email contains the given value.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::SearchOptions::new().author("foo@example.com");
+    /// ```
+    pub fn author<T: AsRef<str>>(mut self, value: T) -> Self {
+        self.author = Some(Pattern::Substring(value.as_ref().to_string()));
+        self
+    }
+
+    /// Only select commits whose author name or email matches the given regular expression.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::SearchOptions::new().author_regex("@example\\.com$");
+    /// ```
+    pub fn author_regex<T: AsRef<str>>(mut self, re: T) -> Self {
+        self.author = Some(Pattern::Regex(re.as_ref().to_string()));
+        self
+    }
+
+    /// Only select commits made at or after the given time in seconds since the epoch.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::SearchOptions::new().after(1577836800);
+    /// ```
+    pub fn after(mut self, time: i64) -> Self {
+        self.after = Some(time);
+        self
+    }
+
+    /// Only select commits made before the given time in seconds since the epoch.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::SearchOptions::new().before(1577836800);
+    /// ```
+    pub fn before(mut self, time: i64) -> Self {
+        self.before = Some(time);
+        self
+    }
+
+    /// Match the message and author patterns ignoring case.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::SearchOptions::new().message("BADGE").ignore_case(true);
+    /// ```
+    pub fn ignore_case(mut self, yes: bool) -> Self {
+        self.ignore_case = yes;
+        self
+    }
+}
+
+/// Lazy iterator over the commits matching a search as returned by `search`
+pub struct Search {
+    repo: Repository,              // Repo being searched
+    queue: BinaryHeap<(i64, Oid)>, // Commits to visit newest first
+    seen: HashSet<Oid>,            // Commits already queued
+    message: Option<Regex>,        // Message matcher
+    author: Option<Regex>,         // Author matcher
+    after: Option<i64>,            // Stop once commits are older than this
+    before: Option<i64>,           // Skip commits at or newer than this
+}
+
+impl Search {
+    // Visit the next commit newest first returning its details if it matches
+    fn visit(&mut self) -> Result<Option<Option<CommitInfo>>> {
+        let (time, oid) = match self.queue.pop() {
+            Some(next) => next,
+            None => return Ok(None),
+        };
+        if self.after.map(|x| time < x).unwrap_or(false) {
+            self.queue.clear();
+            return Ok(None);
+        }
+        let commit = self.repo.find_commit(oid)?;
+        for parent in commit.parents() {
+            if self.seen.insert(parent.id()) {
+                self.queue.push((parent.time().seconds(), parent.id()));
+            }
+        }
+
+        if self.before.map(|x| time >= x).unwrap_or(false) {
+            return Ok(Some(None));
+        }
+        if let Some(re) = &self.message {
+            if !re.is_match(commit.message().unwrap_or_default()) {
+                return Ok(Some(None));
+            }
+        }
+        if let Some(re) = &self.author {
+            let sig = commit.author();
+            if !re.is_match(sig.name().unwrap_or_default()) && !re.is_match(sig.email().unwrap_or_default()) {
+                return Ok(Some(None));
+            }
+        }
+        Ok(Some(Some(CommitInfo::from_commit(&commit))))
+    }
+}
+
+impl Iterator for Search {
+    type Item = Result<CommitInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.visit() {
+                Ok(Some(Some(commit))) => return Some(Ok(commit)),
+                Ok(Some(None)) => continue,
+                Ok(None) => return None,
+                Err(err) => {
+                    self.queue.clear();
+                    return Some(Err(err));
+                },
+            }
+        }
+    }
+}
+
+/// Search the history newest first for commits matching the given options. Commits are
+/// visited lazily as the returned iterator is advanced and the walk stops as soon as commits
+/// are older than the `after` time.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_search_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let mut search = git::search(&tmpdir, &git::SearchOptions::new().message("badge")).unwrap();
+/// assert_eq!(search.next().unwrap().unwrap().summary, "Use the workflow name for the badge");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn search<T: AsRef<Path>>(path: T, opts: &SearchOptions) -> Result<Search> {
+    let repo = Repository::open(path.as_ref())?;
+    let start = match &opts.from {
+        Some(rev) => repo.revparse_single(rev)?.peel_to_commit()?,
+        None => repo.head()?.peel_to_commit()?,
+    };
+    let mut queue = BinaryHeap::new();
+    let mut seen = HashSet::new();
+    queue.push((start.time().seconds(), start.id()));
+    seen.insert(start.id());
+    drop(start);
+
+    Ok(Search {
+        repo,
+        queue,
+        seen,
+        message: opts.message.as_ref().map(|x| x.compile(opts.ignore_case)).transpose()?,
+        author: opts.author.as_ref().map(|x| x.compile(opts.ignore_case)).transpose()?,
+        after: opts.after,
+        before: opts.before,
+    })
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Returns the commits matching the given search
+    fn search(path: &Path, opts: &git::SearchOptions) -> Vec<git::CommitInfo> {
+        git::search(path, opts).unwrap().collect::<git::Result<Vec<_>>>().unwrap()
+    }
+
+    #[test]
+    fn test_search() {
+        let tmpdir = setup("git_search");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let all = git::log(&tmpdir, &git::LogOptions::new()).unwrap();
+
+        // Everything matches the same as log
+        assert_eq!(search(&tmpdir, &git::SearchOptions::new()), all);
+
+        // Message substring and regex
+        let commits = search(&tmpdir, &git::SearchOptions::new().message("badge"));
+        assert_eq!(commits.is_empty(), false);
+        assert_eq!(commits, all.iter().filter(|x| x.message.contains("badge")).cloned().collect::<Vec<_>>());
+        assert_eq!(search(&tmpdir, &git::SearchOptions::new().message("BADGE")).is_empty(), true);
+        assert_eq!(search(&tmpdir, &git::SearchOptions::new().message("BADGE").ignore_case(true)), commits);
+        let commits = search(&tmpdir, &git::SearchOptions::new().message("(").ignore_case(true));
+        assert_eq!(commits, all.iter().filter(|x| x.message.contains('(')).cloned().collect::<Vec<_>>());
+        let commits = search(&tmpdir, &git::SearchOptions::new().message_regex("^Use "));
+        assert_eq!(commits, all.iter().filter(|x| x.message.starts_with("Use ")).cloned().collect::<Vec<_>>());
+
+        // Author
+        let author = all[0].author.clone();
+        let commits = search(&tmpdir, &git::SearchOptions::new().author(&author));
+        assert_eq!(commits, all.iter().filter(|x| x.author.contains(&author) || x.email.contains(&author)).cloned().collect::<Vec<_>>());
+        assert_eq!(search(&tmpdir, &git::SearchOptions::new().author_regex("^missing$")).is_empty(), true);
+
+        // Time range
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let time = |x: &git::CommitInfo| repo.find_commit(x.oid).unwrap().time().seconds();
+        let (after, before) = (time(&all[30]), time(&all[5]));
+        let commits = search(&tmpdir, &git::SearchOptions::new().after(after).before(before));
+        assert_eq!(commits, all.iter().filter(|x| time(x) >= after && time(x) < before).cloned().collect::<Vec<_>>());
+
+        // Start revision
+        assert_eq!(search(&tmpdir, &git::SearchOptions::new().from("HEAD~3")), all[3..].to_vec());
+
+        // Lazily walks only as far as needed
+        let mut iter = git::search(&tmpdir, &git::SearchOptions::new()).unwrap();
+        assert_eq!(iter.next().unwrap().unwrap(), all[0]);
+
+        // Errors
+        assert_eq!(git::search(&tmpdir, &git::SearchOptions::new().message_regex("(")).err().unwrap().to_string().starts_with("invalid pattern"), true);
+        assert!(git::search(&tmpdir, &git::SearchOptions::new().from("missing")).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
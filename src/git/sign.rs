@@ -0,0 +1,158 @@
+use crate::error::*;
+use git2::Repository;
+use std::{
+    fmt,
+    io::Write,
+    process::{Command, Stdio},
+    sync::Arc,
+};
+
+// Signing callback shared across threads
+type SignFn = Arc<dyn Fn(&str) -> Result<String>+Send+Sync>;
+
+/// Signing method used to sign commits and tags
+#[derive(Clone)]
+pub enum Signer {
+    /// Sign with the gpg binary, or `gpg.program` when configured, using the given key else
+    /// `user.signingkey` when configured else the committer's identity the same way git does
+    Gpg(Option<String>),
+
+    /// Sign with a caller supplied callback that is given the content to sign and returns the
+    /// ascii armored detached signature
+    Callback(SignFn),
+}
+
+impl fmt::Debug for Signer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Signer::Gpg(key) => f.debug_tuple("Gpg").field(key).finish(),
+            Signer::Callback(_) => f.debug_tuple("Callback").finish(),
+        }
+    }
+}
+
+impl Signer {
+    /// Create a signer using the gpg binary with the configured `user.signingkey`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(format!("{:?}", git::Signer::gpg()), "Gpg(None)");
+    /// ```
+    pub fn gpg() -> Self {
+        Signer::Gpg(None)
+    }
+
+    /// Create a signer using the gpg binary with the given key id.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(format!("{:?}", git::Signer::gpg_key("ABCD")), "Gpg(Some(\"ABCD\"))");
+    /// ```
+    pub fn gpg_key<T: AsRef<str>>(key: T) -> Self {
+        Signer::Gpg(Some(key.as_ref().to_string()))
+    }
+
+    /// Create a signer using the given callback which is given the content to sign and returns
+    /// the ascii armored detached signature.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(format!("{:?}", git::Signer::callback(|_| Ok("sig".to_string()))), "Callback");
+    /// ```
+    pub fn callback<T>(func: T) -> Self
+    where
+        T: Fn(&str) -> Result<String>+Send+Sync+'static,
+    {
+        Signer::Callback(Arc::new(func))
+    }
+
+    // Sign the given content returning the detached signature
+    pub(crate) fn sign(&self, repo: &Repository, content: &str) -> Result<String> {
+        match self {
+            Signer::Gpg(key) => {
+                let config = repo.config()?;
+                let program = config.get_string("gpg.program").unwrap_or_else(|_| "gpg".to_string());
+                let key = match key {
+                    Some(key) => key.clone(),
+                    None => match config.get_string("user.signingkey") {
+                        Ok(key) => key,
+                        Err(_) => {
+                            let sig = repo.signature()?;
+                            format!("{} <{}>", sig.name().unwrap_or_default(), sig.email().unwrap_or_default())
+                        },
+                    },
+                };
+                gpg_sign(&program, &key, content)
+            },
+            Signer::Callback(func) => func(content),
+        }
+    }
+}
+
+// Sign the given content with the given gpg program and key the same way git does
+fn gpg_sign(program: &str, key: &str, content: &str) -> Result<String> {
+    let mut child = Command::new(program)
+        .args(["--status-fd=2", "-bsau", key])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|x| Error::signing_failed(format!("{}: {}", program, x)))?;
+    // Writing fails if gpg exits early which is reported by its status instead
+    let _ = child.stdin.take().unwrap().write_all(content.as_bytes());
+    let output = child.wait_with_output().map_err(|x| Error::signing_failed(format!("{}: {}", program, x)))?;
+    let status = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(Error::signing_failed(format!("{} {}: {}", program, output.status, status.trim())));
+    }
+    if !status.contains("\n[GNUPG:] SIG_CREATED ") && !status.starts_with("[GNUPG:] SIG_CREATED ") {
+        return Err(Error::signing_failed(status.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_signer() {
+        assert_eq!(format!("{:?}", git::Signer::gpg()), "Gpg(None)");
+        assert_eq!(format!("{:?}", git::Signer::gpg_key("ABCD")), "Gpg(Some(\"ABCD\"))");
+        assert_eq!(format!("{:?}", git::Signer::callback(|_| Ok("sig".to_string()))), "Callback");
+    }
+
+    #[test]
+    fn test_signer_gpg_exits_early() {
+        let tmpdir = setup("git_signer_gpg_exits_early");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let repo = git2::Repository::init(&tmpdir).unwrap();
+
+        // A program exiting without reading the content reports its status and stderr rather
+        // than the broken pipe of writing to it
+        let program = tmpdir.mash("gpg");
+        assert!(sys::write(&program, "#!/bin/sh\necho no secret key >&2\nexit 2\n").is_ok());
+        assert!(std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).is_ok());
+        repo.config().unwrap().set_str("gpg.program", &program.to_string().unwrap()).unwrap();
+        let err = git::Signer::gpg_key("ABCD").sign(&repo, &"x".repeat(1024 * 1024)).unwrap_err();
+        assert_eq!(err.to_string(), format!("failed to sign: {} exit status: 2: no secret key", program.display()));
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,449 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{build::CheckoutBuilder, IndexEntryExtendedFlag, IndexEntryFlag, Pathspec, PathspecFlags, Repository, StatusOptions};
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs,
+};
+
+/// Enable cone mode sparse checkout limiting the worktree to the given directories, recursively,
+/// along with the files at the top of the repo and directly in the directories leading to them,
+/// the same as `git sparse-checkout set --cone <dirs>`. Files outside the cone are removed from
+/// the worktree and flagged as skip-worktree unless they have uncommitted changes in which case
+/// they're left as is the same as git does.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_sparse_set_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::sparse_set(&tmpdir, &["config"]).is_ok());
+/// assert_eq!(tmpdir.mash("config").exists(), true);
+/// assert_eq!(tmpdir.mash("README.md").exists(), true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn sparse_set<T, U>(path: T, dirs: &[U]) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    write_cone(&repo, &cone_dirs(dirs)?)?;
+    reapply(&repo)
+}
+
+/// Add the given directories to the cone mode sparse checkout materializing their files in the
+/// worktree, the same as `git sparse-checkout add <dirs>`. When cone mode sparse checkout isn't
+/// enabled yet this is the same as `sparse_set`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_sparse_add_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::sparse_set(&tmpdir, &["config"]).is_ok());
+/// assert!(git::sparse_add(&tmpdir, &[".github"]).is_ok());
+/// assert_eq!(git::sparse_patterns(&tmpdir).unwrap(), vec![".github".to_string(), "config".to_string()]);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn sparse_add<T, U>(path: T, dirs: &[U]) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let mut cone = match is_cone(&repo)? {
+        true => read_patterns(&repo)?.into_iter().collect(),
+        false => BTreeSet::new(),
+    };
+    cone.extend(cone_dirs(dirs)?);
+    write_cone(&repo, &cone)?;
+    reapply(&repo)
+}
+
+/// Remove the given directories from the cone mode sparse checkout removing their files from the
+/// worktree. Removing a directory that isn't in the cone is not an error. Fails if cone mode
+/// sparse checkout isn't enabled.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_sparse_remove_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::sparse_set(&tmpdir, &["config", ".github"]).is_ok());
+/// assert!(git::sparse_remove(&tmpdir, &[".github"]).is_ok());
+/// assert_eq!(git::sparse_patterns(&tmpdir).unwrap(), vec!["config".to_string()]);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn sparse_remove<T, U>(path: T, dirs: &[U]) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    if !is_cone(&repo)? {
+        return Err(git2::Error::from_str("cone mode sparse checkout is not enabled").into());
+    }
+    let remove = cone_dirs(dirs)?;
+    let cone = read_patterns(&repo)?.into_iter().filter(|x| !remove.contains(x)).collect();
+    write_cone(&repo, &cone)?;
+    reapply(&repo)
+}
+
+/// List the sparse checkout directories in cone mode, or the raw patterns otherwise e.g. those
+/// recorded by `Repo::sparse` at clone time, the same as `git sparse-checkout list`. Returns an
+/// empty list if sparse checkout isn't enabled.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_sparse_patterns_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::sparse_patterns(&tmpdir).unwrap().is_empty());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn sparse_patterns<T: AsRef<Path>>(path: T) -> Result<Vec<String>> {
+    let repo = Repository::open(path.as_ref())?;
+    if !is_sparse(&repo)? {
+        return Ok(vec![]);
+    }
+    read_patterns(&repo)
+}
+
+/// Reapply the sparse checkout patterns to the worktree, the same as `git sparse-checkout
+/// reapply`. Files that now match are materialized while the others are removed and flagged as
+/// skip-worktree unless they have uncommitted changes. libgit2 doesn't honour sparse checkout so
+/// this restores the sparse worktree after checkouts that materialized everything.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_sparse_reapply_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::sparse_set(&tmpdir, &["config"]).is_ok());
+/// assert!(git::Repo::new(&tmpdir).unwrap().checkout("master", true).is_ok());
+/// assert!(git::sparse_reapply(&tmpdir).is_ok());
+/// assert_eq!(tmpdir.mash(".github").exists(), false);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn sparse_reapply<T: AsRef<Path>>(path: T) -> Result<()> {
+    let repo = Repository::open(path.as_ref())?;
+    reapply(&repo)
+}
+
+/// Disable sparse checkout materializing all files in the worktree, the same as `git
+/// sparse-checkout disable`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_sparse_disable_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::sparse_set(&tmpdir, &["config"]).is_ok());
+/// assert!(git::sparse_disable(&tmpdir).is_ok());
+/// assert_eq!(tmpdir.mash(".github").exists(), true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn sparse_disable<T: AsRef<Path>>(path: T) -> Result<()> {
+    let repo = Repository::open(path.as_ref())?;
+    repo.config()?.set_bool("core.sparseCheckout", false)?;
+    reapply(&repo)
+}
+
+// Returns true if sparse checkout is enabled for the repo
+fn is_sparse(repo: &Repository) -> Result<bool> {
+    Ok(repo.config()?.get_bool("core.sparseCheckout").unwrap_or(false))
+}
+
+// Returns true if cone mode sparse checkout is enabled for the repo
+fn is_cone(repo: &Repository) -> Result<bool> {
+    Ok(is_sparse(repo)? && repo.config()?.get_bool("core.sparseCheckoutCone").unwrap_or(false))
+}
+
+// Normalize the given cone directories e.g. `/docs/` to `docs`
+fn cone_dirs<T: AsRef<str>>(dirs: &[T]) -> Result<BTreeSet<String>> {
+    let mut cone = BTreeSet::new();
+    for dir in dirs {
+        let dir = dir.as_ref().trim_matches('/');
+        if dir.is_empty() || dir.split('/').any(|x| x.is_empty() || x == "." || x == "..") {
+            return Err(Error::invalid_pattern(format!("not a directory in the repo: {}", dir)));
+        }
+        if dir.contains(|c| "*?[]\\!".contains(c)) {
+            return Err(Error::invalid_pattern(format!("cone mode requires directories not patterns: {}", dir)));
+        }
+        cone.insert(dir.to_string());
+    }
+    Ok(cone)
+}
+
+// Returns the parent directories of the given cone directories that aren't in the cone
+fn cone_parents(cone: &BTreeSet<String>) -> BTreeSet<String> {
+    let mut parents = BTreeSet::new();
+    for dir in cone {
+        let mut parent = dir.as_str();
+        while let Some((rest, _)) = parent.rsplit_once('/') {
+            parent = rest;
+            parents.insert(parent.to_string());
+        }
+    }
+    parents.into_iter().filter(|x| !cone.iter().any(|y| x == y || x.starts_with(&format!("{}/", y)))).collect()
+}
+
+// Record the given cone directories as cone mode patterns the same way git does
+fn write_cone(repo: &Repository, cone: &BTreeSet<String>) -> Result<()> {
+    let mut config = repo.config()?;
+    config.set_bool("core.sparseCheckout", true)?;
+    config.set_bool("core.sparseCheckoutCone", true)?;
+    let mut patterns = vec!["/*".to_string(), "!/*/".to_string()];
+    for parent in cone_parents(cone) {
+        patterns.push(format!("/{}/", parent));
+        patterns.push(format!("!/{}/*/", parent));
+    }
+    patterns.extend(cone.iter().filter(|x| !cone.iter().any(|y| x.starts_with(&format!("{}/", y)))).map(|x| format!("/{}/", x)));
+    let info = sys::mkdir(repo.path().mash("info"))?;
+    sys::write(info.mash("sparse-checkout"), format!("{}\n", patterns.join("\n")))?;
+    Ok(())
+}
+
+// Returns the recorded sparse checkout patterns reduced to the cone directories in cone mode
+fn read_patterns(repo: &Repository) -> Result<Vec<String>> {
+    let file = repo.path().mash("info/sparse-checkout");
+    if !file.exists() {
+        return Ok(vec![]);
+    }
+    let lines: Vec<String> = sys::readlines(&file)?.into_iter().map(|x| x.trim().to_string()).filter(|x| !x.is_empty() && !x.starts_with('#')).collect();
+    if !is_cone(repo)? {
+        return Ok(lines);
+    }
+
+    // Cone directories are those included without excluding their subdirectories
+    let excluded: HashSet<&str> = lines.iter().filter_map(|x| x.strip_prefix('!')).filter_map(|x| x.strip_suffix("*/")).collect();
+    Ok(lines
+        .iter()
+        .filter(|x| x.starts_with('/') && x.ends_with('/') && x.len() > 1 && !excluded.contains(x.as_str()))
+        .map(|x| x.trim_matches('/').to_string())
+        .collect())
+}
+
+// Update the worktree and skip-worktree flags to match the sparse checkout patterns
+fn reapply(repo: &Repository) -> Result<()> {
+    let workdir = repo.workdir().ok_or_else(|| git2::Error::from_str("cannot sparse checkout a bare repository"))?.to_path_buf();
+    let (sparse, cone) = (is_sparse(repo)?, is_cone(repo)?);
+    let patterns = match sparse {
+        true => read_patterns(repo)?,
+        false => vec![],
+    };
+    let pathspec = Pathspec::new(patterns.iter())?;
+    let parents = cone_parents(&patterns.iter().cloned().collect());
+    let included = |path: &str| match (sparse, cone) {
+        (false, _) => true,
+        (true, true) => {
+            let dir = path.rsplit_once('/').map(|x| x.0).unwrap_or_default();
+            dir.is_empty() || parents.contains(dir) || patterns.iter().any(|x| dir == x || dir.starts_with(&format!("{}/", x)))
+        },
+        (true, false) => pathspec.matches_path(Path::new(path), PathspecFlags::DEFAULT),
+    };
+
+    // Files with uncommitted changes are left as is
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false).include_ignored(false);
+    let modified: HashSet<String> = repo.statuses(Some(&mut opts))?.iter().filter_map(|x| x.path().map(|y| y.to_string())).collect();
+
+    let mut index = repo.index()?;
+    let mut restore = vec![];
+    let mut removed = vec![];
+    for mut entry in index.iter().collect::<Vec<_>>() {
+        if (entry.flags >> 12) & 0x3 != 0 {
+            continue;
+        }
+        let path = String::from_utf8_lossy(&entry.path).to_string();
+        let skipped = IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended).is_skip_worktree();
+        if included(&path) && skipped {
+            entry.flags_extended &= !IndexEntryExtendedFlag::SKIP_WORKTREE.bits();
+            if entry.flags_extended == 0 {
+                entry.flags &= !IndexEntryFlag::EXTENDED.bits();
+            }
+            index.add(&entry)?;
+            restore.push(path);
+        } else if !included(&path) && !skipped && !modified.contains(&path) {
+            entry.flags |= IndexEntryFlag::EXTENDED.bits();
+            entry.flags_extended |= IndexEntryExtendedFlag::SKIP_WORKTREE.bits();
+            index.add(&entry)?;
+            removed.push(workdir.mash(&path));
+        }
+    }
+    index.write()?;
+
+    // Materialize the newly included files
+    if !restore.is_empty() {
+        let mut checkout = CheckoutBuilder::new();
+        checkout.safe().recreate_missing(true);
+        for path in &restore {
+            checkout.path(path);
+        }
+        repo.checkout_index(Some(&mut index), Some(&mut checkout))?;
+    }
+
+    // Remove the excluded files along with any directories left empty
+    for file in removed {
+        if file.is_symlink() || file.exists() {
+            fs::remove_file(&file).map_err(FuError::from)?;
+        }
+        let mut dir = file.parent();
+        while let Some(path) = dir.filter(|x| *x != workdir && fs::remove_dir(x).is_ok()) {
+            dir = path.parent();
+        }
+    }
+    Ok(())
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Initialize a new repo with nested directories committed
+    fn init(path: &Path) -> git2::Repository {
+        let repo = git2::Repository::init(path).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        for file in &[
+            "README.md",
+            "docs/index.md",
+            "docs/api/foo.md",
+            "docs/api/v1/bar.md",
+            "src/lib.rs",
+            "src/git/mod.rs",
+        ] {
+            assert!(sys::mkdir(path.mash(file).dir().unwrap()).is_ok());
+            assert!(sys::write(path.mash(file), file).is_ok());
+        }
+        assert!(git::CommitBuilder::new(path).stage_all(true).message("Initial commit").commit().is_ok());
+        repo
+    }
+
+    #[test]
+    fn test_sparse() {
+        let tmpdir = setup("git_sparse");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let repo = init(&tmpdir);
+        let exists = |files: &[&str]| files.iter().map(|x| tmpdir.mash(x).exists()).collect::<Vec<_>>();
+        let files = [
+            "README.md",
+            "docs/index.md",
+            "docs/api/foo.md",
+            "docs/api/v1/bar.md",
+            "src/lib.rs",
+            "src/git/mod.rs",
+        ];
+        let list = || {
+            let output = Command::new("git").args(["sparse-checkout", "list"]).current_dir(&tmpdir).output().unwrap();
+            String::from_utf8_lossy(&output.stdout).lines().map(|x| x.to_string()).collect::<Vec<_>>()
+        };
+        assert!(git::sparse_patterns(&tmpdir).unwrap().is_empty());
+
+        // Nested directory includes the files directly in its parents
+        assert!(git::sparse_set(&tmpdir, &["/docs/api/v1/"]).is_ok());
+        assert_eq!(exists(&files), vec![true, true, true, true, false, false]);
+        assert_eq!(tmpdir.mash("src").exists(), false);
+        assert_eq!(
+            sys::readlines(tmpdir.mash(".git/info/sparse-checkout")).unwrap(),
+            vec![
+                "/*",
+                "!/*/",
+                "/docs/",
+                "!/docs/*/",
+                "/docs/api/",
+                "!/docs/api/*/",
+                "/docs/api/v1/"
+            ]
+        );
+        assert_eq!(git::sparse_patterns(&tmpdir).unwrap(), vec!["docs/api/v1"]);
+        assert_eq!(list(), vec!["docs/api/v1"]);
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+
+        // Widen the cone
+        assert!(git::sparse_add(&tmpdir, &["src", "docs/api"]).is_ok());
+        assert_eq!(exists(&files), vec![true, true, true, true, true, true]);
+        assert_eq!(git::sparse_patterns(&tmpdir).unwrap(), vec!["docs/api", "src"]);
+        assert_eq!(list(), vec!["docs/api", "src"]);
+
+        // Narrow the cone leaving modified files
+        assert!(sys::write(tmpdir.mash("src/git/mod.rs"), "foo").is_ok());
+        assert!(git::sparse_remove(&tmpdir, &["src", "missing"]).is_ok());
+        assert_eq!(exists(&files), vec![true, true, true, true, false, true]);
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), true);
+        assert!(git::Repo::new(&tmpdir).unwrap().checkout("master", true).is_ok());
+        assert_eq!(exists(&files), vec![true, true, true, true, true, true]);
+        assert!(git::sparse_reapply(&tmpdir).is_ok());
+        assert_eq!(exists(&files), vec![true, true, true, true, false, false]);
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+        let skipped = |path: &str| {
+            let mut index = repo.index().unwrap();
+            index.read(true).unwrap();
+            let entry = index.get_path(Path::new(path), 0).unwrap();
+            git2::IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended).is_skip_worktree()
+        };
+        assert_eq!(skipped("src/lib.rs"), true);
+        assert_eq!(skipped("docs/api/foo.md"), false);
+
+        // Disable
+        assert!(git::sparse_disable(&tmpdir).is_ok());
+        assert_eq!(exists(&files), vec![true, true, true, true, true, true]);
+        assert_eq!(skipped("src/lib.rs"), false);
+        assert!(git::sparse_patterns(&tmpdir).unwrap().is_empty());
+        assert!(git::sparse_remove(&tmpdir, &["src"]).is_err());
+
+        // Invalid directories
+        assert!(git::sparse_set(&tmpdir, &["/"]).is_err());
+        assert!(git::sparse_set(&tmpdir, &["docs/*.md"]).is_err());
+        assert!(git::sparse_set(&tmpdir, &["docs/../src"]).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_sparse_clone() {
+        let tmpdir = setup("git_sparse_clone");
+        let src = tmpdir.mash("src");
+        let dst = tmpdir.mash("dst");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &src).is_ok());
+
+        // Clone time patterns are reapplied as is and replaced by cone mode
+        assert!(git::Repo::new(&dst).unwrap().url(src.to_string().unwrap()).sparse(["config", "*.md"]).clone().is_ok());
+        assert_eq!(git::sparse_patterns(&dst).unwrap(), vec!["config", "*.md"]);
+        assert!(git::sparse_reapply(&dst).is_ok());
+        assert_eq!(dst.mash("LICENSE").exists(), false);
+        assert_eq!(dst.mash("config/.bashrc").exists(), true);
+        assert!(git::sparse_add(&dst, &[".github"]).is_ok());
+        assert_eq!(git::sparse_patterns(&dst).unwrap(), vec![".github"]);
+        assert_eq!(dst.mash("LICENSE").exists(), true);
+        assert_eq!(dst.mash(".github/workflows/build.yaml").exists(), true);
+        assert_eq!(dst.mash("config").exists(), false);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
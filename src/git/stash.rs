@@ -0,0 +1,228 @@
+use super::conflicts;
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{ErrorCode, Oid, Repository, StashFlags};
+
+/// Stash entry as returned by `stashes`
+#[derive(Clone, Debug, PartialEq)]
+pub struct StashInfo {
+    pub index: usize,    // Position in the stash list with 0 being the most recent
+    pub message: String, // Stash message e.g. `On master: before update`
+    pub oid: Oid,        // Id of the stash commit
+}
+
+/// Stash the repo's uncommitted changes to tracked files with the given message, the same as
+/// `git stash push -m <message>`, leaving the worktree clean. Untracked files are stashed too if
+/// `untracked` is set, the same as `git stash push --include-untracked`. Returns the id of the
+/// stash commit or None if there was nothing to stash. Requires `user.name` and `user.email`
+/// to be configured.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_stash_save_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "foo").unwrap();
+/// repo.config().unwrap().set_str("user.email", "foo@example.com").unwrap();
+/// assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+/// assert!(git::stash_save(&tmpdir, Some("before update"), false).unwrap().is_some());
+/// assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn stash_save<T: AsRef<Path>>(path: T, message: Option<&str>, untracked: bool) -> Result<Option<Oid>> {
+    let mut repo = Repository::open(path.as_ref())?;
+    let sig = repo.signature()?;
+    let flags = if untracked { StashFlags::INCLUDE_UNTRACKED } else { StashFlags::DEFAULT };
+    match repo.stash_save2(&sig, message, Some(flags)) {
+        Ok(oid) => Ok(Some(oid)),
+        Err(err) if err.code() == ErrorCode::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// List the repo's stashes with the most recent first, the same as `git stash list`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_stashes_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::stashes(&tmpdir).unwrap().is_empty());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn stashes<T: AsRef<Path>>(path: T) -> Result<Vec<StashInfo>> {
+    let mut repo = Repository::open(path.as_ref())?;
+    let mut stashes = vec![];
+    repo.stash_foreach(|index, message, oid| {
+        stashes.push(StashInfo { index, message: message.to_string(), oid: *oid });
+        true
+    })?;
+    Ok(stashes)
+}
+
+/// Apply the stash at the given index, 0 being the most recent, to the worktree keeping it in
+/// the stash list, the same as `git stash apply stash@{<index>}`. Changes that were staged when
+/// stashed are restored as unstaged changes. Returns `Error::MergeConflicts` if the stash
+/// conflicts with HEAD leaving conflict markers to be resolved. Fails without changing anything
+/// if the stash conflicts with uncommitted changes.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_stash_apply_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "foo").unwrap();
+/// repo.config().unwrap().set_str("user.email", "foo@example.com").unwrap();
+/// assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+/// assert!(git::stash_save(&tmpdir, None, false).is_ok());
+/// assert!(git::stash_apply(&tmpdir, 0).is_ok());
+/// assert_eq!(git::stashes(&tmpdir).unwrap().len(), 1);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn stash_apply<T: AsRef<Path>>(path: T, index: usize) -> Result<()> {
+    let mut repo = Repository::open(path.as_ref())?;
+    repo.stash_apply(index, None)?;
+    check_conflicts(path.as_ref())
+}
+
+/// Apply the stash at the given index, 0 being the most recent, to the worktree then remove it
+/// from the stash list, the same as `git stash pop stash@{<index>}`. The stash is kept if
+/// applying it fails or leaves conflicts, see `stash_apply`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_stash_pop_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let repo = git2::Repository::open(&tmpdir).unwrap();
+/// repo.config().unwrap().set_str("user.name", "foo").unwrap();
+/// repo.config().unwrap().set_str("user.email", "foo@example.com").unwrap();
+/// assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+/// assert!(git::stash_save(&tmpdir, None, false).is_ok());
+/// assert!(git::stash_pop(&tmpdir, 0).is_ok());
+/// assert!(git::stashes(&tmpdir).unwrap().is_empty());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn stash_pop<T: AsRef<Path>>(path: T, index: usize) -> Result<()> {
+    // libgit2 drops the stash even when applying it leaves conflicts
+    let mut repo = Repository::open(path.as_ref())?;
+    repo.stash_apply(index, None)?;
+    check_conflicts(path.as_ref())?;
+    repo.stash_drop(index)?;
+    Ok(())
+}
+
+/// Remove the stash at the given index, 0 being the most recent, from the stash list without
+/// applying it, the same as `git stash drop stash@{<index>}`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_stash_drop_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::stash_drop(&tmpdir, 0).is_err());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn stash_drop<T: AsRef<Path>>(path: T, index: usize) -> Result<()> {
+    let mut repo = Repository::open(path.as_ref())?;
+    repo.stash_drop(index)?;
+    Ok(())
+}
+
+// Returns an error with the conflicted paths if applying a stash left conflicts in the index
+fn check_conflicts(path: &Path) -> Result<()> {
+    let conflicts = conflicts(path)?;
+    if !conflicts.is_empty() {
+        return Err(Error::merge_conflicts(&conflicts.paths()));
+    }
+    Ok(())
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_stash() {
+        let tmpdir = setup("git_stash");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+
+        // Nothing to stash
+        assert_eq!(git::stash_save(&tmpdir, None, false).unwrap(), None);
+        assert!(git::stashes(&tmpdir).unwrap().is_empty());
+
+        // Untracked files are left unless included
+        assert!(sys::write(tmpdir.mash("README.md"), "one").is_ok());
+        assert!(sys::write(tmpdir.mash("untracked"), "untracked").is_ok());
+        let first = git::stash_save(&tmpdir, Some("first"), false).unwrap().unwrap();
+        assert_eq!(git::is_dirty(&tmpdir).unwrap(), false);
+        assert!(tmpdir.mash("untracked").exists());
+        assert!(sys::write(tmpdir.mash("Dockerfile"), "two").is_ok());
+        let second = git::stash_save(&tmpdir, None, true).unwrap().unwrap();
+        assert_eq!(tmpdir.mash("untracked").exists(), false);
+        assert_eq!(git::status(&tmpdir, &git::StatusOptions::new()).unwrap(), vec![]);
+
+        // Most recent first with messages
+        let stashes = git::stashes(&tmpdir).unwrap();
+        assert_eq!(stashes.iter().map(|x| (x.index, x.oid)).collect::<Vec<_>>(), vec![(0, second), (1, first)]);
+        assert_eq!(stashes[1].message, "On master: first");
+        assert!(stashes[0].message.starts_with("WIP on master:"));
+
+        // Apply keeps the stash while pop removes it
+        assert!(git::stash_apply(&tmpdir, 1).is_ok());
+        assert_eq!(sys::readstring(tmpdir.mash("README.md")).unwrap(), "one");
+        assert_eq!(git::stashes(&tmpdir).unwrap().len(), 2);
+        assert!(git::stash_pop(&tmpdir, 0).is_ok());
+        assert_eq!(sys::readstring(tmpdir.mash("Dockerfile")).unwrap(), "two");
+        assert_eq!(sys::readstring(tmpdir.mash("untracked")).unwrap(), "untracked");
+        assert_eq!(git::stashes(&tmpdir).unwrap().iter().map(|x| x.oid).collect::<Vec<_>>(), vec![first]);
+
+        // Conflicting changes keep the stash
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).message("Update").commit().is_ok());
+        assert!(sys::write(tmpdir.mash("README.md"), "conflict").is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).message("Conflict").commit().is_ok());
+        let err = git::stash_pop(&tmpdir, 0).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::merge_conflicts(&["README.md"]).to_string());
+        assert_eq!(git::stashes(&tmpdir).unwrap().len(), 1);
+        repo.reset(&repo.head().unwrap().peel(git2::ObjectType::Commit).unwrap(), git2::ResetType::Hard, None).unwrap();
+
+        // Conflicting uncommitted changes refuse to apply
+        assert!(sys::write(tmpdir.mash("README.md"), "dirty").is_ok());
+        assert!(git::stash_apply(&tmpdir, 0).is_err());
+        assert_eq!(sys::readstring(tmpdir.mash("README.md")).unwrap(), "dirty");
+        assert_eq!(git::stashes(&tmpdir).unwrap().len(), 1);
+
+        // Drop
+        assert!(git::stash_drop(&tmpdir, 1).is_err());
+        assert!(git::stash_drop(&tmpdir, 0).is_ok());
+        assert!(git::stashes(&tmpdir).unwrap().is_empty());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,289 @@
+use super::{file_size, loose_objects, pack_oids, packs};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{ObjectType, Oid, Repository, Sort};
+
+// Number of biggest objects reported by `stats`
+const BIGGEST: usize = 10;
+
+/// Number of objects of each type as returned in `RepoStats`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjectCounts {
+    pub commits: usize, // Number of commit objects
+    pub trees: usize,   // Number of tree objects
+    pub blobs: usize,   // Number of blob objects
+    pub tags: usize,    // Number of annotated tag objects
+}
+
+impl ObjectCounts {
+    /// Returns the total number of objects.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let counts = git::ObjectCounts { commits: 1, trees: 2, blobs: 3, tags: 0 };
+    /// assert_eq!(counts.total(), 6);
+    /// ```
+    pub fn total(&self) -> usize {
+        self.commits + self.trees + self.blobs + self.tags
+    }
+}
+
+/// Number of refs of each kind as returned in `RepoStats`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RefCounts {
+    pub branches: usize,        // Number of local branches under `refs/heads`
+    pub remote_branches: usize, // Number of remote tracking branches under `refs/remotes`
+    pub tags: usize,            // Number of tags under `refs/tags`
+    pub other: usize,           // Number of other refs e.g. notes and the stash
+}
+
+/// Object and its uncompressed size as returned in `RepoStats`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectSize {
+    pub oid: Oid,         // Id of the object
+    pub kind: ObjectType, // Type of the object
+    pub size: usize,      // Uncompressed size in bytes
+}
+
+/// Statistics of a repository as returned by `stats`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RepoStats {
+    pub objects: ObjectCounts,    // Number of objects of each type in the object store
+    pub loose_objects: usize,     // Number of loose objects
+    pub loose_size: u64,          // Size in bytes of the loose object files
+    pub packs: usize,             // Number of packs
+    pub packed_objects: usize,    // Number of objects in the packs
+    pub pack_size: u64,           // Size in bytes of the packs and their indexes on disk
+    pub biggest: Vec<ObjectSize>, // Biggest objects by uncompressed size, biggest first
+    pub refs: RefCounts,          // Number of refs of each kind
+    pub commits: usize,           // Number of commits reachable from the refs
+    pub head_commits: usize,      // Number of commits reachable from HEAD
+}
+
+impl RepoStats {
+    /// Returns the size in bytes of the object store on disk i.e. the loose objects and packs.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let stats = git::RepoStats { loose_size: 10, pack_size: 20, ..Default::default() };
+    /// assert_eq!(stats.disk_size(), 30);
+    /// ```
+    pub fn disk_size(&self) -> u64 {
+        self.loose_size + self.pack_size
+    }
+}
+
+/// Gather statistics of the given repo without shelling out to git. The loose and packed object
+/// counts and the pack size match `git count-objects -v`. Objects are counted by type once each
+/// even if they're both loose and packed. The ten biggest objects by uncompressed size are
+/// included. Commits are counted the same as `git rev-list --all --count` and `git rev-list --count
+/// HEAD`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_stats_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let stats = git::stats(&tmpdir).unwrap();
+/// assert_eq!(stats.refs.branches, 1);
+/// assert!(stats.commits > 0);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn stats<T: AsRef<Path>>(path: T) -> Result<RepoStats> {
+    let repo = Repository::open(path.as_ref())?;
+    let mut stats = RepoStats::default();
+
+    // Object store on disk
+    for (_, file) in loose_objects(&repo)? {
+        stats.loose_objects += 1;
+        stats.loose_size += file_size(&file);
+    }
+    for pack in packs(&repo)? {
+        let idx = pack.with_extension("idx");
+        stats.packs += 1;
+        stats.packed_objects += pack_oids(&idx)?.len();
+        stats.pack_size += file_size(&pack) + file_size(&idx);
+    }
+
+    // Objects by type and size
+    let odb = repo.odb()?;
+    let mut oids = vec![];
+    odb.foreach(|oid| {
+        oids.push(*oid);
+        true
+    })?;
+    oids.sort();
+    oids.dedup();
+    let mut sizes = vec![];
+    for oid in oids {
+        let (size, kind) = odb.read_header(oid)?;
+        match kind {
+            ObjectType::Commit => stats.objects.commits += 1,
+            ObjectType::Tree => stats.objects.trees += 1,
+            ObjectType::Blob => stats.objects.blobs += 1,
+            ObjectType::Tag => stats.objects.tags += 1,
+            _ => {},
+        }
+        sizes.push(ObjectSize { oid, kind, size });
+    }
+    sizes.sort_by(|x, y| y.size.cmp(&x.size).then(x.oid.cmp(&y.oid)));
+    sizes.truncate(BIGGEST);
+    stats.biggest = sizes;
+
+    // Refs
+    let mut tips = vec![];
+    for reference in repo.references()? {
+        let reference = reference?;
+        let name = reference.name().unwrap_or_default();
+        if name.starts_with("refs/heads/") {
+            stats.refs.branches += 1;
+        } else if name.starts_with("refs/remotes/") {
+            if reference.symbolic_target().is_some() {
+                continue;
+            }
+            stats.refs.remote_branches += 1;
+        } else if name.starts_with("refs/tags/") {
+            stats.refs.tags += 1;
+        } else {
+            stats.refs.other += 1;
+        }
+        if let Ok(commit) = reference.peel_to_commit() {
+            tips.push(commit.id());
+        }
+    }
+
+    // Commits
+    stats.commits = count_commits(&repo, &tips)?;
+    if let Ok(head) = repo.head() {
+        stats.head_commits = count_commits(&repo, &[head.peel_to_commit()?.id()])?;
+    }
+    Ok(stats)
+}
+
+// Returns the number of commits reachable from the given commits
+fn count_commits(repo: &Repository, tips: &[Oid]) -> Result<usize> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::NONE)?;
+    for oid in tips {
+        revwalk.push(*oid)?;
+    }
+    let mut count = 0;
+    for oid in revwalk {
+        oid?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Returns the output of the given git command
+    fn git(path: &Path, args: &[&str]) -> String {
+        let output = Command::new("git").args(args).current_dir(path).output().unwrap();
+        assert_eq!(output.status.success(), true, "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    // Returns the value of the given field of `git count-objects -v`
+    fn count_objects(path: &Path, field: &str) -> u64 {
+        let output = git(path, &["count-objects", "-v"]);
+        let line = output.lines().find(|x| x.starts_with(&format!("{}: ", field))).unwrap();
+        line.split(' ').nth(1).unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn test_stats() {
+        let tmpdir = setup("git_stats");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+
+        // Add loose objects, a big blob, branches, an unmerged branch and a note
+        let big = "x".repeat(100_000);
+        assert!(sys::write(tmpdir.mash("big"), &big).is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add big").commit().is_ok());
+        assert!(git::branch_create(&tmpdir, "foo", None, true).is_ok());
+        assert!(sys::write(tmpdir.mash("foo"), "foo").is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).author("foo", "foo@example.com").message("Add foo").commit().is_ok());
+        assert!(git::Repo::new(&tmpdir).unwrap().checkout("master", false).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        repo.config().unwrap().set_str("user.name", "foo").unwrap();
+        repo.config().unwrap().set_str("user.email", "foo@example.com").unwrap();
+        assert!(git::note_add(&tmpdir, "HEAD", "foo", None, false).is_ok());
+        let stats = git::stats(&tmpdir).unwrap();
+
+        // Object store matches git
+        assert_eq!(stats.loose_objects as u64, count_objects(&tmpdir, "count"));
+        assert!(stats.loose_size > 0);
+        assert_eq!(stats.packs as u64, count_objects(&tmpdir, "packs"));
+        assert_eq!(stats.packed_objects as u64, count_objects(&tmpdir, "in-pack"));
+        assert_eq!(stats.pack_size / 1024, count_objects(&tmpdir, "size-pack"));
+        assert_eq!(stats.disk_size(), stats.loose_size + stats.pack_size);
+
+        // Objects by type and the biggest match git
+        let objects = git(
+            &tmpdir,
+            &[
+                "cat-file",
+                "--batch-all-objects",
+                "--batch-check=%(objecttype) %(objectsize) %(objectname)",
+            ],
+        );
+        let count = |kind: &str| objects.lines().filter(|x| x.starts_with(&format!("{} ", kind))).count();
+        assert_eq!(
+            stats.objects,
+            git::ObjectCounts {
+                commits: count("commit"),
+                trees: count("tree"),
+                blobs: count("blob"),
+                tags: count("tag")
+            }
+        );
+        assert_eq!(stats.objects.total(), objects.lines().count());
+        let mut sizes: Vec<(usize, String)> = objects.lines().map(|x| x.split(' ').collect::<Vec<_>>()).map(|x| (x[1].parse().unwrap(), x[2].to_string())).collect();
+        sizes.sort_by(|x, y| y.0.cmp(&x.0).then(x.1.cmp(&y.1)));
+        sizes.truncate(10);
+        assert_eq!(stats.biggest.iter().map(|x| (x.size, x.oid.to_string())).collect::<Vec<_>>(), sizes);
+        assert_eq!((stats.biggest[0].size, stats.biggest[0].kind), (big.len(), git2::ObjectType::Blob));
+
+        // Refs and commits match git with the unmerged branch and notes commits counted
+        let refs = |prefix: &str| git(&tmpdir, &["for-each-ref", "--format=%(refname) %(symref)", prefix]).lines().filter(|x| x.ends_with(' ')).count();
+        assert_eq!(
+            stats.refs,
+            git::RefCounts {
+                branches: refs("refs/heads"),
+                remote_branches: refs("refs/remotes"),
+                tags: refs("refs/tags"),
+                other: refs("refs/notes")
+            }
+        );
+        assert_eq!((stats.refs.branches, stats.refs.other), (2, 1));
+        let commits: usize = git(&tmpdir, &["rev-list", "--all", "--count"]).trim().parse().unwrap();
+        let head: usize = git(&tmpdir, &["rev-list", "--count", "HEAD"]).trim().parse().unwrap();
+        assert_eq!((stats.commits, stats.head_commits), (commits, head));
+        assert_eq!(stats.commits, stats.head_commits + 2);
+
+        // Errors
+        assert!(git::stats(tmpdir.mash("../missing")).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,457 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{DiffDelta, Repository, Status};
+
+/// State of a path on one side of a status entry i.e. the index compared to HEAD or the worktree
+/// compared to the index
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum FileState {
+    /// Path is unchanged
+    #[default]
+    Unmodified,
+
+    /// Path was added
+    Added,
+
+    /// Path's content or mode was modified
+    Modified,
+
+    /// Path was deleted
+    Deleted,
+
+    /// Path was renamed, see `StatusEntry::renamed_from` for the original path
+    Renamed,
+
+    /// Path was copied from another path, only reported by `diff` with copy detection
+    Copied,
+
+    /// Path changed type e.g. a file replaced by a symlink
+    TypeChanged,
+}
+
+impl FileState {
+    /// Returns the single character code used by `git status --porcelain=v2` for this state with
+    /// `.` for unmodified.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::FileState::Modified.code(), 'M');
+    /// assert_eq!(git::FileState::Unmodified.code(), '.');
+    /// ```
+    pub fn code(&self) -> char {
+        match self {
+            FileState::Unmodified => '.',
+            FileState::Added => 'A',
+            FileState::Modified => 'M',
+            FileState::Deleted => 'D',
+            FileState::Renamed => 'R',
+            FileState::Copied => 'C',
+            FileState::TypeChanged => 'T',
+        }
+    }
+}
+
+/// Status of a single path as returned by `status`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StatusEntry {
+    pub path: PathBuf,                 // Path relative to the worktree, the new path for renames
+    pub staged: FileState,             // State of the index compared to HEAD
+    pub unstaged: FileState,           // State of the worktree compared to the index
+    pub renamed_from: Option<PathBuf>, // Original path if the path was renamed
+    pub untracked: bool,               // Path isn't tracked in the index
+    pub ignored: bool,                 // Path is ignored, only reported if requested
+    pub conflicted: bool,              // Path has unresolved conflicts in the index
+}
+
+impl StatusEntry {
+    /// Returns the two character `XY` code used by `git status --porcelain=v2` for this entry
+    /// i.e. the staged then unstaged state codes, `??` for untracked, `!!` for ignored and `UU`
+    /// for conflicted paths.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let entry = git::StatusEntry { staged: git::FileState::Added, unstaged: git::FileState::Modified, ..Default::default() };
+    /// assert_eq!(entry.code(), "AM");
+    /// ```
+    pub fn code(&self) -> String {
+        if self.conflicted {
+            "UU".to_string()
+        } else if self.untracked {
+            "??".to_string()
+        } else if self.ignored {
+            "!!".to_string()
+        } else {
+            format!("{}{}", self.staged.code(), self.unstaged.code())
+        }
+    }
+
+    /// Returns true if the path has changes in the index that are ready to be committed.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let entry = git::StatusEntry { staged: git::FileState::Added, ..Default::default() };
+    /// assert_eq!(entry.is_staged(), true);
+    /// ```
+    pub fn is_staged(&self) -> bool {
+        self.staged != FileState::Unmodified
+    }
+
+    /// Returns true if the path has changes in the worktree that aren't staged. Untracked and
+    /// ignored paths are not considered unstaged.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let entry = git::StatusEntry { unstaged: git::FileState::Deleted, ..Default::default() };
+    /// assert_eq!(entry.is_unstaged(), true);
+    /// ```
+    pub fn is_unstaged(&self) -> bool {
+        self.unstaged != FileState::Unmodified
+    }
+}
+
+/// Options for listing the status of a repo with `status`
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let opts = git::StatusOptions::new().ignored(true).paths(&["src"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct StatusOptions {
+    untracked: bool,    // Include untracked paths
+    ignored: bool,      // Include ignored paths
+    renames: bool,      // Detect renames in the index and worktree
+    paths: Vec<String>, // Pathspecs to limit the status to
+}
+
+impl Default for StatusOptions {
+    fn default() -> Self {
+        Self { untracked: true, ignored: false, renames: true, paths: vec![] }
+    }
+}
+
+impl StatusOptions {
+    /// Create new status options that include untracked paths and detect renames the same as
+    /// `git status` does by default.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::StatusOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set to include untracked paths, the same as `git status --untracked-files=all`.
+    /// Untracked directories are listed by the paths of the files they contain.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::StatusOptions::new().untracked(false).untracked_val(), false);
+    /// ```
+    pub fn untracked(mut self, yes: bool) -> Self {
+        self.untracked = yes;
+        self
+    }
+
+    /// Get whether untracked paths are included.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::StatusOptions::new().untracked_val(), true);
+    /// ```
+    pub fn untracked_val(&self) -> bool {
+        self.untracked
+    }
+
+    /// Set to include ignored paths, the same as `git status --ignored`. Ignored directories
+    /// are listed as a single entry rather than by the files they contain.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::StatusOptions::new().ignored(true).ignored_val(), true);
+    /// ```
+    pub fn ignored(mut self, yes: bool) -> Self {
+        self.ignored = yes;
+        self
+    }
+
+    /// Get whether ignored paths are included.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::StatusOptions::new().ignored_val(), false);
+    /// ```
+    pub fn ignored_val(&self) -> bool {
+        self.ignored
+    }
+
+    /// Set to detect renamed paths in the index and worktree, the same as `git status
+    /// --find-renames`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::StatusOptions::new().renames(false).renames_val(), false);
+    /// ```
+    pub fn renames(mut self, yes: bool) -> Self {
+        self.renames = yes;
+        self
+    }
+
+    /// Get whether renames are detected.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::StatusOptions::new().renames_val(), true);
+    /// ```
+    pub fn renames_val(&self) -> bool {
+        self.renames
+    }
+
+    /// Set the pathspecs to limit the status to e.g. `src` or `*.rs`, the same as `git status
+    /// -- <pathspecs>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::StatusOptions::new().paths(&["src"]);
+    /// assert_eq!(opts.paths_val(), &["src".to_string()]);
+    /// ```
+    pub fn paths<T, U>(mut self, paths: T) -> Self
+    where
+        T: IntoIterator<Item=U>,
+        U: AsRef<str>,
+    {
+        self.paths = paths.into_iter().map(|x| x.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Get the pathspecs the status is limited to. Empty when all paths are included.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert!(git::StatusOptions::new().paths_val().is_empty());
+    /// ```
+    pub fn paths_val(&self) -> &[String] {
+        &self.paths
+    }
+}
+
+/// List the paths of the repo that differ between HEAD, the index and the worktree along with
+/// their staged and unstaged state, roughly the same as `git status --porcelain=v2`. Entries
+/// are sorted by path.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_status_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+/// let entries = git::status(&tmpdir, &git::StatusOptions::new()).unwrap();
+/// assert_eq!(entries[0].path, PathBuf::from("README.md"));
+/// assert_eq!(entries[0].code(), ".M");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn status<T: AsRef<Path>>(path: T, opts: &StatusOptions) -> Result<Vec<StatusEntry>> {
+    let repo = Repository::open(path.as_ref())?;
+    let mut statusopts = git2::StatusOptions::new();
+    statusopts
+        .include_untracked(opts.untracked)
+        .recurse_untracked_dirs(opts.untracked)
+        .include_ignored(opts.ignored)
+        .renames_head_to_index(opts.renames)
+        .renames_index_to_workdir(opts.renames);
+    for path in opts.paths.iter() {
+        statusopts.pathspec(path);
+    }
+
+    let mut entries = vec![];
+    for entry in repo.statuses(Some(&mut statusopts))?.iter() {
+        let status = entry.status();
+        let (head_to_index, index_to_workdir) = (entry.head_to_index(), entry.index_to_workdir());
+
+        // Report renames by their new path and the original path from HEAD if staged
+        let path = index_to_workdir.as_ref().or(head_to_index.as_ref()).and_then(|x| x.new_file().path().map(|x| x.to_path_buf()));
+        let renamed_from = if status.is_index_renamed() {
+            old_path(head_to_index)
+        } else if status.is_wt_renamed() {
+            old_path(index_to_workdir)
+        } else {
+            None
+        };
+        entries.push(StatusEntry {
+            path: path.unwrap_or_else(|| PathBuf::from(String::from_utf8_lossy(entry.path_bytes()).to_string())),
+            staged: staged(status),
+            unstaged: unstaged(status),
+            renamed_from,
+            untracked: status.is_wt_new(),
+            ignored: status.is_ignored(),
+            conflicted: status.is_conflicted(),
+        });
+    }
+    entries.sort_by(|x, y| x.path.cmp(&y.path));
+    Ok(entries)
+}
+
+// Get the original path of the given delta
+fn old_path(delta: Option<DiffDelta>) -> Option<PathBuf> {
+    delta.and_then(|x| x.old_file().path().map(|x| x.to_path_buf()))
+}
+
+// Map the index flags of the given status to a file state
+fn staged(status: Status) -> FileState {
+    if status.is_index_new() {
+        FileState::Added
+    } else if status.is_index_modified() {
+        FileState::Modified
+    } else if status.is_index_deleted() {
+        FileState::Deleted
+    } else if status.is_index_renamed() {
+        FileState::Renamed
+    } else if status.is_index_typechange() {
+        FileState::TypeChanged
+    } else {
+        FileState::Unmodified
+    }
+}
+
+// Map the worktree flags of the given status to a file state. New worktree paths are reported
+// as untracked instead.
+fn unstaged(status: Status) -> FileState {
+    if status.is_wt_modified() {
+        FileState::Modified
+    } else if status.is_wt_deleted() {
+        FileState::Deleted
+    } else if status.is_wt_renamed() {
+        FileState::Renamed
+    } else if status.is_wt_typechange() {
+        FileState::TypeChanged
+    } else {
+        FileState::Unmodified
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_status() {
+        let tmpdir = setup("git_status");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        assert!(git::status(&tmpdir, &git::StatusOptions::new()).unwrap().is_empty());
+        let codes = |opts: &git::StatusOptions| git::status(&tmpdir, opts).unwrap().iter().map(|x| (x.path.to_string().unwrap(), x.code())).collect::<Vec<_>>();
+
+        // Staged, unstaged and untracked changes
+        assert!(sys::write(tmpdir.mash("README.md"), "foo").is_ok());
+        assert!(sys::write(tmpdir.mash("new"), "new").is_ok());
+        assert!(git::stage(&tmpdir, &["new"]).is_ok());
+        assert!(sys::write(tmpdir.mash("new"), "newer").is_ok());
+        assert!(sys::remove(tmpdir.mash("Dockerfile")).is_ok());
+        assert!(git::stage(&tmpdir, &["Dockerfile"]).is_ok());
+        assert!(sys::mkdir(tmpdir.mash("dir")).is_ok());
+        assert!(sys::write(tmpdir.mash("dir/untracked"), "untracked").is_ok());
+        let entries = git::status(&tmpdir, &git::StatusOptions::new()).unwrap();
+        let entry = entries.iter().find(|x| x.path == Path::new("new")).unwrap();
+        assert_eq!((entry.staged, entry.unstaged), (git::FileState::Added, git::FileState::Modified));
+        assert_eq!((entry.is_staged(), entry.is_unstaged(), entry.untracked), (true, true, false));
+        assert_eq!(
+            codes(&git::StatusOptions::new()),
+            vec![
+                ("Dockerfile".to_string(), "D.".to_string()),
+                ("README.md".to_string(), ".M".to_string()),
+                ("dir/untracked".to_string(), "??".to_string()),
+                ("new".to_string(), "AM".to_string()),
+            ]
+        );
+
+        // Path filters and untracked toggle
+        assert_eq!(
+            codes(&git::StatusOptions::new().paths(["README.md", "dir"])),
+            vec![
+                ("README.md".to_string(), ".M".to_string()),
+                ("dir/untracked".to_string(), "??".to_string()),
+            ]
+        );
+        assert_eq!(codes(&git::StatusOptions::new().untracked(false).paths(["dir"])), vec![]);
+
+        // Ignored paths
+        assert!(sys::write(tmpdir.mash(".git/info/exclude"), "*.log\n").is_ok());
+        assert!(sys::write(tmpdir.mash("debug.log"), "log").is_ok());
+        assert_eq!(codes(&git::StatusOptions::new().paths(["*.log"])), vec![]);
+        let entries = git::status(&tmpdir, &git::StatusOptions::new().ignored(true).paths(["*.log"])).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].ignored, entries[0].code()), (true, "!!".to_string()));
+
+        // Staged renames
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).message("Update").commit().is_ok());
+        assert!(sys::move_p(tmpdir.mash("LICENSE"), tmpdir.mash("COPYING")).is_ok());
+        assert!(git::stage(&tmpdir, &["LICENSE", "COPYING"]).is_ok());
+        let entries = git::status(&tmpdir, &git::StatusOptions::new()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, PathBuf::from("COPYING"));
+        assert_eq!(entries[0].renamed_from, Some(PathBuf::from("LICENSE")));
+        assert_eq!(entries[0].code(), "R.");
+        let entries = git::status(&tmpdir, &git::StatusOptions::new().renames(false)).unwrap();
+        assert_eq!(entries.iter().map(|x| x.code()).collect::<Vec<_>>(), vec!["A.", "D."]);
+
+        // Conflicts
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).message("Rename").commit().is_ok());
+        assert!(git::branch_create(&tmpdir, "feature", None, true).is_ok());
+        assert!(sys::write(tmpdir.mash("README.md"), "theirs").is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).message("Theirs").commit().is_ok());
+        assert!(git::Repo::new(&tmpdir).unwrap().checkout("master", false).is_ok());
+        assert!(sys::write(tmpdir.mash("README.md"), "ours").is_ok());
+        assert!(git::CommitBuilder::new(&tmpdir).stage_all(true).message("Ours").commit().is_ok());
+        assert!(git::merge(&tmpdir, "feature", &git::MergeOptions::new()).is_err());
+        let entries = git::status(&tmpdir, &git::StatusOptions::new()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].path.clone(), entries[0].conflicted, entries[0].code()), (PathBuf::from("README.md"), true, "UU".to_string()));
+
+        // Errors
+        assert!(git::status(tmpdir.mash("missing"), &git::StatusOptions::new()).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
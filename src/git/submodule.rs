@@ -0,0 +1,769 @@
+use super::{checkout_opts, fetch_opts, Net, Repo, SharedProgress};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{build::CheckoutBuilder, Config, ErrorCode, Oid, Repository, Submodule, SubmoduleIgnore, SubmoduleStatus};
+use std::cell::RefCell;
+
+/// State of a submodule's worktree as reported in `SubmoduleInfo`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SubmoduleState {
+    /// Submodule is checked out at the commit recorded in the index without local changes
+    #[default]
+    Clean,
+
+    /// Submodule hasn't been initialized or cloned yet, the same as git's `-` status prefix
+    Uninitialized,
+
+    /// Submodule is checked out at a different commit than the one recorded in the index, the
+    /// same as git's `+` status prefix
+    Modified,
+
+    /// Submodule has uncommitted changes or untracked files of its own
+    Dirty,
+}
+
+/// Submodule status as returned by `submodules`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubmoduleInfo {
+    pub name: String,             // Name of the submodule in `.gitmodules`
+    pub path: PathBuf,            // Path of the submodule relative to the worktree
+    pub url: Option<String>,      // Url of the submodule in `.gitmodules`
+    pub branch: Option<String>,   // Branch the submodule tracks if configured
+    pub head_oid: Option<Oid>,    // Commit recorded in HEAD if the submodule is committed
+    pub index_oid: Option<Oid>,   // Commit recorded in the index
+    pub workdir_oid: Option<Oid>, // Commit checked out in the submodule if cloned
+    pub state: SubmoduleState,    // Whether the submodule is initialized, modified or dirty
+}
+
+/// Options for updating submodules with `Repo::submodule_update`
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let opts = git::SubmoduleUpdateOptions::new().init(true).recursive(true);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SubmoduleUpdateOptions {
+    init: bool,         // Initialize submodules that haven't been yet
+    recursive: bool,    // Update nested submodules as well
+    paths: Vec<String>, // Names or paths of the submodules to limit the update to
+}
+
+impl SubmoduleUpdateOptions {
+    /// Create new submodule update options that update only initialized submodules, the same
+    /// as `git submodule update`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::SubmoduleUpdateOptions::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set to initialize submodules that haven't been yet before updating them, the same as
+    /// `git submodule update --init`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::SubmoduleUpdateOptions::new().init(true).init_val(), true);
+    /// ```
+    pub fn init(mut self, yes: bool) -> Self {
+        self.init = yes;
+        self
+    }
+
+    /// Get whether uninitialized submodules are initialized.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::SubmoduleUpdateOptions::new().init_val(), false);
+    /// ```
+    pub fn init_val(&self) -> bool {
+        self.init
+    }
+
+    /// Set to update nested submodules as well, the same as `git submodule update --recursive`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::SubmoduleUpdateOptions::new().recursive(true).recursive_val(), true);
+    /// ```
+    pub fn recursive(mut self, yes: bool) -> Self {
+        self.recursive = yes;
+        self
+    }
+
+    /// Get whether nested submodules are updated.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::SubmoduleUpdateOptions::new().recursive_val(), false);
+    /// ```
+    pub fn recursive_val(&self) -> bool {
+        self.recursive
+    }
+
+    /// Set the names or paths of the submodules to limit the update to, the same as
+    /// `git submodule update -- <paths>`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let opts = git::SubmoduleUpdateOptions::new().paths(&["lib"]);
+    /// assert_eq!(opts.paths_val(), &["lib".to_string()]);
+    /// ```
+    pub fn paths<T, U>(mut self, paths: T) -> Self
+    where
+        T: IntoIterator<Item=U>,
+        U: AsRef<str>,
+    {
+        self.paths = paths.into_iter().map(|x| x.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Get the names or paths of the submodules the update is limited to. Empty when all
+    /// submodules are updated.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert!(git::SubmoduleUpdateOptions::new().paths_val().is_empty());
+    /// ```
+    pub fn paths_val(&self) -> &[String] {
+        &self.paths
+    }
+}
+
+impl<'a> Repo<'a> {
+    /// Add the repo at the given url as a submodule at the given path relative to the worktree,
+    /// the same as `git submodule add <url> <path>`, using the repo's network settings, transfer
+    /// and checkout progress callbacks. The submodule is cloned and checked out and both it and
+    /// `.gitmodules` are staged ready to be committed. Returns the submodule's location on disk.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_submodule_add_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("lib")).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("repo")).is_ok());
+    /// let url = tmpdir.mash("lib").to_string().unwrap();
+    /// let path = git::Repo::new(tmpdir.mash("repo")).unwrap().submodule_add(&url, "lib").unwrap();
+    /// assert!(path.mash("README.md").exists());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn submodule_add<T, U>(mut self, url: T, path: U) -> Result<PathBuf>
+    where
+        T: AsRef<str>,
+        U: AsRef<Path>,
+    {
+        let xfer = self.xfer_progress.take().map(RefCell::new);
+        let checkout = self.checkout_progress.take().map(RefCell::new);
+        let repo = Repository::open(self.path_val())?;
+        let mut submodule = repo.submodule(url.as_ref(), path.as_ref(), true)?;
+        self.net.auth.settle(|| submodule.clone(Some(&mut update_opts(&self.net, xfer.as_ref(), checkout.as_ref()))))?;
+        submodule.add_finalize()?;
+        Ok(self.path_val().mash(path.as_ref()))
+    }
+
+    /// Update the repo's submodules to the commits recorded in the index, the same as
+    /// `git submodule update`, using the repo's network settings, transfer and checkout progress
+    /// callbacks. Submodules are cloned if need be and checked out detached. Submodules that
+    /// haven't been initialized are skipped unless the options' `init` is set.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_submodule_update_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// let opts = git::SubmoduleUpdateOptions::new().init(true).recursive(true);
+    /// assert!(git::Repo::new(&tmpdir).unwrap().submodule_update(&opts).is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn submodule_update(mut self, opts: &SubmoduleUpdateOptions) -> Result<()> {
+        let xfer = self.xfer_progress.take().map(RefCell::new);
+        let checkout = self.checkout_progress.take().map(RefCell::new);
+        update_submodules(self.path_val(), &self.net, xfer.as_ref(), checkout.as_ref(), opts)
+    }
+}
+
+/// List the status of the repo's submodules sorted by path, the same as `git submodule status`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_submodules_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::submodules(&tmpdir).unwrap().is_empty());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn submodules<T: AsRef<Path>>(path: T) -> Result<Vec<SubmoduleInfo>> {
+    let repo = Repository::open(path.as_ref())?;
+    let mut infos = vec![];
+    for submodule in repo.submodules()? {
+        let name = submodule_name(&submodule);
+        let status = repo.submodule_status(&name, SubmoduleIgnore::None)?;
+        let state = if !is_initialized(&repo, &name)? || status.contains(SubmoduleStatus::WD_UNINITIALIZED) {
+            SubmoduleState::Uninitialized
+        } else if status.contains(SubmoduleStatus::WD_MODIFIED) {
+            SubmoduleState::Modified
+        } else if status.intersects(SubmoduleStatus::WD_INDEX_MODIFIED | SubmoduleStatus::WD_WD_MODIFIED | SubmoduleStatus::WD_UNTRACKED) {
+            SubmoduleState::Dirty
+        } else {
+            SubmoduleState::Clean
+        };
+        infos.push(SubmoduleInfo {
+            name,
+            path: submodule.path().to_path_buf(),
+            url: submodule.url().map(|x| x.to_string()),
+            branch: submodule.branch().map(|x| x.to_string()),
+            head_oid: submodule.head_id(),
+            index_oid: submodule.index_id(),
+            workdir_oid: submodule.workdir_id(),
+            state,
+        });
+    }
+    infos.sort_by(|x, y| x.path.cmp(&y.path));
+    Ok(infos)
+}
+
+/// Initialize the submodules with the given names or paths, or all of them if none are given,
+/// the same as `git submodule init`. The submodules' urls are copied from `.gitmodules` into
+/// the repo's configuration, leaving any url already configured as is.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_submodule_init_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::submodule_init(&tmpdir, &[] as &[&str]).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn submodule_init<T, U>(path: T, paths: &[U]) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    for mut submodule in find_submodules(&repo, paths)? {
+        submodule.init(false)?;
+    }
+    Ok(())
+}
+
+/// Copy the submodules' urls from `.gitmodules` into the repo's configuration and the remotes
+/// of the cloned submodules, the same as `git submodule sync`. Useful after a submodule's url
+/// has changed upstream or with `submodule_set_url`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_submodule_sync_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::submodule_sync(&tmpdir).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn submodule_sync<T: AsRef<Path>>(path: T) -> Result<()> {
+    let repo = Repository::open(path.as_ref())?;
+    for mut submodule in repo.submodules()? {
+        submodule.sync()?;
+    }
+    Ok(())
+}
+
+/// Set the url of the submodule with the given name in `.gitmodules`, the same as
+/// `git submodule set-url <path> <url>`. Call `submodule_sync` to apply it to the repo's
+/// configuration and the cloned submodule.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_submodule_set_url_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::submodule_set_url(&tmpdir, "missing", "https://example.com/foo").is_err());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn submodule_set_url<T, U, V>(path: T, name: U, url: V) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut repo = Repository::open(path.as_ref())?;
+    repo.find_submodule(name.as_ref())?;
+    repo.submodule_set_url(name.as_ref(), url.as_ref())?;
+    Ok(())
+}
+
+/// Set the branch the submodule with the given name tracks in `.gitmodules` or remove it when
+/// None is given, the same as `git submodule set-branch --branch <branch> <path>`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_submodule_set_branch_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::submodule_set_branch(&tmpdir, "missing", Some("main")).is_err());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn submodule_set_branch<T, U>(path: T, name: U, branch: Option<&str>) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let mut repo = Repository::open(path.as_ref())?;
+    repo.find_submodule(name.as_ref())?;
+    match branch {
+        Some(branch) => repo.submodule_set_branch(name.as_ref(), branch)?,
+        None => {
+            let workdir = repo.workdir().ok_or_else(|| git2::Error::from_str("cannot set a submodule branch in a bare repository"))?;
+            let mut config = Config::open(&workdir.mash(".gitmodules"))?;
+            match config.remove(&format!("submodule.{}.branch", name.as_ref())) {
+                Err(err) if err.code() != ErrorCode::NotFound => return Err(err.into()),
+                _ => (),
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Deinitialize the submodule with the given name, the same as `git submodule deinit <path>`.
+/// The submodule's configuration is removed from the repo's configuration and its worktree is
+/// emptied, leaving `.gitmodules` and the recorded commit as is so that it can be initialized
+/// again later. As a safety check a submodule with uncommitted changes is refused with
+/// `Error::DirtyWorktree` unless `force` is set. A submodule with an embedded `.git` directory
+/// has it absorbed into the repo's `.git/modules/<name>` first, the same as
+/// `git submodule absorbgitdirs`, so that its history isn't lost.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_submodule_deinit_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::submodule_deinit(&tmpdir, "missing", false).is_err());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn submodule_deinit<T, U>(path: T, name: U, force: bool) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let submodule = repo.find_submodule(name.as_ref())?;
+    let name = submodule_name(&submodule);
+    let subpath = path.as_ref().mash(submodule.path());
+    let status = repo.submodule_status(&name, SubmoduleIgnore::None)?;
+    let dirty = SubmoduleStatus::WD_INDEX_MODIFIED | SubmoduleStatus::WD_WD_MODIFIED | SubmoduleStatus::WD_UNTRACKED;
+    if !force && status.intersects(dirty) {
+        return Err(Error::dirty_worktree(&subpath));
+    }
+    if subpath.exists() {
+        absorb_gitdir(&repo, &name, &subpath)?;
+    }
+
+    // Remove all of the submodule's configuration e.g. url, active and update
+    let mut config = repo.config()?.open_level(git2::ConfigLevel::Local)?;
+    let prefix = format!("submodule.{}.", name);
+    let mut keys = vec![];
+    let mut entries = config.entries(None)?;
+    while let Some(entry) = entries.next() {
+        if let Some(key) = entry?.name() {
+            if key.starts_with(&prefix) {
+                keys.push(key.to_string());
+            }
+        }
+    }
+    drop(entries);
+    for key in keys.iter() {
+        config.remove_multivar(key, ".*")?;
+    }
+
+    if subpath.exists() {
+        sys::remove_all(&subpath)?;
+        sys::mkdir(&subpath)?;
+    }
+    Ok(())
+}
+
+// Move the embedded `.git` directory of the submodule with the given name and worktree into the
+// repo's `.git/modules/<name>` replacing it with a `.git` file linking to the moved directory
+fn absorb_gitdir(repo: &Repository, name: &str, subpath: &Path) -> Result<()> {
+    let embedded = subpath.mash(".git");
+    if !embedded.is_dir() {
+        return Ok(());
+    }
+    let gitdir = repo.path().mash("modules").mash(name);
+    if gitdir.exists() {
+        return Err(git2::Error::from_str(&format!("cannot absorb the git directory of submodule {}: {} already exists", name, gitdir.display())).into());
+    }
+    sys::mkdir(gitdir.dir()?)?;
+    fs::rename(&embedded, &gitdir).map_err(FuError::from)?;
+    Config::open(&gitdir.mash("config"))?.set_str("core.worktree", &subpath.to_string()?)?;
+    sys::write(&embedded, format!("gitdir: {}\n", gitdir.display()))?;
+    Ok(())
+}
+
+// Returns the repo's submodules with the given names or paths, or all of them if none are given
+fn find_submodules<'a, T: AsRef<str>>(repo: &'a Repository, paths: &[T]) -> Result<Vec<Submodule<'a>>> {
+    if paths.is_empty() {
+        return Ok(repo.submodules()?);
+    }
+    let mut submodules = vec![];
+    for path in paths.iter() {
+        submodules.push(repo.find_submodule(path.as_ref().trim_end_matches('/'))?);
+    }
+    Ok(submodules)
+}
+
+// Returns true if the submodule with the given name has been initialized i.e. its url has been
+// copied into the repo's configuration
+fn is_initialized(repo: &Repository, name: &str) -> Result<bool> {
+    match repo.config()?.get_string(&format!("submodule.{}.url", name)) {
+        Ok(_) => Ok(true),
+        Err(err) if err.code() == ErrorCode::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Returns the name of the given submodule
+fn submodule_name(submodule: &Submodule) -> String {
+    String::from_utf8_lossy(submodule.name_bytes()).to_string()
+}
+
+// Update the submodules of the repo at the given path with the given options recursing into
+// nested submodules with the same network settings and progress callbacks if requested
+fn update_submodules<'a>(path: &Path, net: &Net, xfer: Option<&SharedProgress<'a>>, checkout: Option<&SharedProgress<'a>>, opts: &SubmoduleUpdateOptions) -> Result<()> {
+    let repo = Repository::open(path)?;
+    for mut submodule in find_submodules(&repo, &opts.paths)? {
+        let name = submodule_name(&submodule);
+        if !opts.init && !is_initialized(&repo, &name)? {
+            continue;
+        }
+
+        // libgit2 refuses to clone over the repo a deinitialized submodule leaves behind
+        let subpath = path.mash(submodule.path());
+        let gitdir = repo.path().mash("modules").mash(&name);
+        let relink = !subpath.mash(".git").exists() && gitdir.exists();
+        if relink {
+            sys::mkdir(&subpath)?;
+            sys::write(subpath.mash(".git"), format!("gitdir: {}\n", gitdir.display()))?;
+        }
+        net.auth.settle(|| submodule.update(opts.init, Some(&mut update_opts(net, xfer, checkout))))?;
+        if relink {
+            Repository::open(&subpath)?.checkout_head(Some(CheckoutBuilder::new().force()))?;
+        }
+        if opts.recursive {
+            let nested = SubmoduleUpdateOptions::new().init(opts.init).recursive(true);
+            update_submodules(&subpath, net, xfer, checkout, &nested)?;
+        }
+    }
+    Ok(())
+}
+
+// Build submodule update options that use the given network settings and optionally report
+// transfer and checkout progress through the given callbacks
+fn update_opts<'a, 'b>(net: &Net, xfer: Option<&'b SharedProgress<'a>>, checkout: Option<&'b SharedProgress<'a>>) -> git2::SubmoduleUpdateOptions<'b> {
+    let mut opts = git2::SubmoduleUpdateOptions::new();
+    opts.fetch(fetch_opts(net, xfer));
+    if let Some(checkout) = checkout {
+        opts.checkout(checkout_opts(checkout));
+    }
+    opts
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Extract the fixture repo to the given path configuring a committer
+    fn fixture(tmpdir: &Path, name: &str) -> PathBuf {
+        let path = tmpdir.mash(name);
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &path).is_ok());
+        let repo = git2::Repository::open(&path).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        path
+    }
+
+    // Returns the status prefix, commit and path of each submodule the same as `git submodule status`
+    fn status(path: &Path) -> Vec<String> {
+        git::submodules(path)
+            .unwrap()
+            .iter()
+            .map(|x| match x.state {
+                git::SubmoduleState::Uninitialized => format!("-{} {}", x.index_oid.unwrap(), x.path.display()),
+                git::SubmoduleState::Modified => format!("+{} {}", x.workdir_oid.unwrap(), x.path.display()),
+                _ => format!(" {} {}", x.workdir_oid.unwrap(), x.path.display()),
+            })
+            .collect()
+    }
+
+    // Returns the output of `git submodule status` without the trailing describe of each line
+    fn git_status(path: &Path) -> Vec<String> {
+        let output = Command::new("git").args(["submodule", "status"]).current_dir(path).output().unwrap();
+        assert_eq!(output.status.success(), true);
+        String::from_utf8(output.stdout).unwrap().lines().map(|x| x.split(" (").next().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_submodule() {
+        let tmpdir = setup("git_submodule");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("lib")).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("repo")).is_ok());
+        let (url, repodir) = (tmpdir.mash("lib").to_string().unwrap(), tmpdir.mash("repo"));
+        let repo = git2::Repository::open(&repodir).unwrap();
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        let info = || git::submodules(&repodir).unwrap().remove(0);
+        assert!(git::submodules(&repodir).unwrap().is_empty());
+
+        // Add with progress and commit
+        let mut progress = 0;
+        let subdir = git::Repo::new(&repodir).unwrap().checkout_progress(|_, _| progress += 1).submodule_add(&url, "lib").unwrap();
+        assert!(progress > 0);
+        assert_eq!(subdir, repodir.mash("lib"));
+        assert!(subdir.mash("README.md").exists());
+        assert!(repodir.mash(".gitmodules").exists());
+        assert!(git::CommitBuilder::new(&repodir).message("Add lib").commit().is_ok());
+        let head = git2::Repository::open(&subdir).unwrap().head().unwrap().target();
+        let sub = info();
+        assert_eq!((sub.name.as_str(), sub.path.as_path(), sub.url.as_deref()), ("lib", Path::new("lib"), Some(url.as_str())));
+        assert_eq!((sub.head_oid, sub.index_oid, sub.workdir_oid), (head, head, head));
+        assert_eq!((sub.branch, sub.state), (None, git::SubmoduleState::Clean));
+
+        // Dirty and modified submodules
+        assert!(sys::write(subdir.mash("untracked"), "foo").is_ok());
+        assert_eq!(info().state, git::SubmoduleState::Dirty);
+        let err = git::submodule_deinit(&repodir, "lib", false).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::dirty_worktree(&subdir).to_string());
+        assert!(sys::remove(subdir.mash("untracked")).is_ok());
+        assert!(git::reset(&subdir, "HEAD~1", git::ResetMode::Hard, false).is_ok());
+        assert_eq!(info().state, git::SubmoduleState::Modified);
+        assert!(git::Repo::new(&repodir).unwrap().submodule_update(&git::SubmoduleUpdateOptions::new()).is_ok());
+        assert_eq!((info().workdir_oid, info().state), (head, git::SubmoduleState::Clean));
+
+        // Branch and url configuration
+        assert!(git::submodule_set_branch(&repodir, "lib", Some("master")).is_ok());
+        assert_eq!(info().branch, Some("master".to_string()));
+        assert!(git::submodule_set_branch(&repodir, "lib", None).is_ok());
+        assert!(git::submodule_set_branch(&repodir, "lib", None).is_ok());
+        assert_eq!(info().branch, None);
+        assert!(git::submodule_set_url(&repodir, "lib", "https://example.com/lib").is_ok());
+        assert_eq!(info().url, Some("https://example.com/lib".to_string()));
+        assert_eq!(repo.config().unwrap().snapshot().unwrap().get_string("submodule.lib.url").unwrap(), url);
+        assert!(git::submodule_sync(&repodir).is_ok());
+        assert_eq!(repo.config().unwrap().snapshot().unwrap().get_string("submodule.lib.url").unwrap(), "https://example.com/lib");
+        assert_eq!(git::remotes(&subdir).unwrap()[0].url.as_deref(), Some("https://example.com/lib"));
+        assert!(git::submodule_set_url(&repodir, "lib", &url).is_ok());
+        assert!(git::submodule_sync(&repodir).is_ok());
+
+        // Deinit then init and update again
+        assert!(git::submodule_deinit(&repodir, "lib", false).is_ok());
+        assert_eq!(info().state, git::SubmoduleState::Uninitialized);
+        assert!(subdir.exists());
+        assert_eq!(subdir.mash("README.md").exists(), false);
+        assert!(repo.config().unwrap().snapshot().unwrap().get_string("submodule.lib.url").is_err());
+        let output = Command::new("git").args(["submodule", "status"]).current_dir(&repodir).output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("-{} lib\n", head.unwrap()));
+        assert!(git::Repo::new(&repodir).unwrap().submodule_update(&git::SubmoduleUpdateOptions::new()).is_ok());
+        assert_eq!(info().state, git::SubmoduleState::Uninitialized);
+        assert!(git::submodule_init(&repodir, &["lib"]).is_ok());
+        let opts = git::SubmoduleUpdateOptions::new().paths(["lib"]).recursive(true);
+        assert!(git::Repo::new(&repodir).unwrap().submodule_update(&opts).is_ok());
+        assert_eq!((info().workdir_oid, info().state), (head, git::SubmoduleState::Clean));
+        let output = Command::new("git").args(["status", "--porcelain"]).current_dir(&repodir).output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+
+        // Fresh clones need init
+        let clone = tmpdir.mash("clone");
+        assert!(git::Repo::new(&clone).unwrap().url(repodir.to_string().unwrap()).clone().is_ok());
+        assert_eq!(git::submodules(&clone).unwrap()[0].state, git::SubmoduleState::Uninitialized);
+        let opts = git::SubmoduleUpdateOptions::new().init(true);
+        assert!(git::Repo::new(&clone).unwrap().submodule_update(&opts).is_ok());
+        assert_eq!(git::submodules(&clone).unwrap()[0].state, git::SubmoduleState::Clean);
+        assert!(clone.mash("lib/README.md").exists());
+
+        // Errors
+        assert!(git::submodule_init(&repodir, &["missing"]).is_err());
+        assert!(git::submodule_set_url(&repodir, "missing", &url).is_err());
+        assert!(git::submodule_deinit(&repodir, "missing", true).is_err());
+        assert!(git::Repo::new(&repodir).unwrap().submodule_add(&url, "lib").is_err());
+        assert!(git::submodules(tmpdir.mash("missing")).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_submodule_status() {
+        let tmpdir = setup("git_submodule_status");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let lib = fixture(&tmpdir, "lib");
+        let repodir = fixture(&tmpdir, "repo");
+        let url = lib.to_string().unwrap();
+        assert!(git::Repo::new(&repodir).unwrap().submodule_add(&url, "a").is_ok());
+        assert!(git::Repo::new(&repodir).unwrap().submodule_add(&url, "b").is_ok());
+        assert!(git::CommitBuilder::new(&repodir).message("Add a and b").commit().is_ok());
+        assert_eq!(status(&repodir).len(), 2);
+        assert_eq!(status(&repodir), git_status(&repodir));
+
+        // Dirty submodules are reported clean by git with their commit
+        assert!(sys::write(repodir.mash("a/untracked"), "foo").is_ok());
+        assert_eq!(git::submodules(&repodir).unwrap()[0].state, git::SubmoduleState::Dirty);
+        assert_eq!(status(&repodir), git_status(&repodir));
+
+        // Modified submodules report their checked out commit
+        assert!(git::reset(repodir.mash("b"), "HEAD~2", git::ResetMode::Hard, false).is_ok());
+        assert_eq!(git::submodules(&repodir).unwrap()[1].state, git::SubmoduleState::Modified);
+        assert_eq!(status(&repodir), git_status(&repodir));
+
+        // Forced deinit discards the dirty submodule
+        assert!(git::submodule_deinit(&repodir, "a", true).is_ok());
+        assert_eq!(repodir.mash("a/untracked").exists(), false);
+        assert_eq!(git::submodules(&repodir).unwrap()[0].state, git::SubmoduleState::Uninitialized);
+        assert_eq!(status(&repodir), git_status(&repodir));
+
+        // Updates limited to the given paths leave the others as is
+        assert!(git::submodule_init(&repodir, &[] as &[&str]).is_ok());
+        let opts = git::SubmoduleUpdateOptions::new().paths(["b"]);
+        assert!(git::Repo::new(&repodir).unwrap().submodule_update(&opts).is_ok());
+        let infos = git::submodules(&repodir).unwrap();
+        assert_eq!((infos[0].workdir_oid, infos[1].state), (None, git::SubmoduleState::Clean));
+        assert_eq!(status(&repodir), git_status(&repodir));
+        assert!(git::Repo::new(&repodir).unwrap().submodule_update(&git::SubmoduleUpdateOptions::new()).is_ok());
+        assert!(git::submodules(&repodir).unwrap().iter().all(|x| x.state == git::SubmoduleState::Clean));
+        assert_eq!(status(&repodir), git_status(&repodir));
+
+        // Init leaves urls already configured as is
+        let repo = git2::Repository::open(&repodir).unwrap();
+        assert!(git::submodule_deinit(&repodir, "a", false).is_ok());
+        repo.config().unwrap().set_str("submodule.a.url", "https://example.com/a").unwrap();
+        assert!(git::submodule_init(&repodir, &["a/"]).is_ok());
+        assert_eq!(repo.config().unwrap().snapshot().unwrap().get_string("submodule.a.url").unwrap(), "https://example.com/a");
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_submodule_deinit_embedded() {
+        let tmpdir = setup("git_submodule_deinit_embedded");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let lib = fixture(&tmpdir, "lib");
+        let repodir = fixture(&tmpdir, "repo");
+        let (url, subdir) = (lib.to_string().unwrap(), repodir.mash("lib"));
+        let gitdir = repodir.mash(".git/modules/lib");
+
+        // Submodule added from an existing repo keeps its embedded .git directory
+        assert!(Command::new("git").args(["clone", "-q", &url, "lib"]).current_dir(&repodir).output().unwrap().status.success());
+        let args = ["-c", "protocol.file.allow=always", "submodule", "-q", "add", &url, "lib"];
+        assert!(Command::new("git").args(args).current_dir(&repodir).output().unwrap().status.success());
+        assert!(git::CommitBuilder::new(&repodir).message("Add lib").commit().is_ok());
+        assert!(subdir.mash(".git").is_dir());
+        let head = git2::Repository::open(&subdir).unwrap().head().unwrap().target();
+
+        // Deinit absorbs the embedded .git directory rather than deleting its history
+        assert!(git::submodule_deinit(&repodir, "lib", false).is_ok());
+        assert_eq!(sys::all_files(&subdir).unwrap().len(), 0);
+        assert_eq!(git2::Repository::open(&gitdir).unwrap().head().unwrap().target(), head);
+        let output = Command::new("git").args(["submodule", "status"]).current_dir(&repodir).output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("-{} lib\n", head.unwrap()));
+
+        // The absorbed git directory is reused when initialized again
+        assert!(git::submodule_init(&repodir, &["lib"]).is_ok());
+        assert!(git::Repo::new(&repodir).unwrap().submodule_update(&git::SubmoduleUpdateOptions::new()).is_ok());
+        assert_eq!(git::submodules(&repodir).unwrap()[0].state, git::SubmoduleState::Clean);
+        assert!(subdir.mash("README.md").exists());
+        assert!(subdir.mash(".git").is_file());
+        let output = Command::new("git").args(["status", "--porcelain"]).current_dir(&repodir).output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+
+        // Embedded .git directories that can't be absorbed are left as is
+        assert!(sys::remove_all(&subdir).is_ok());
+        assert!(Command::new("git").args(["clone", "-q", &url, "lib"]).current_dir(&repodir).output().unwrap().status.success());
+        assert!(git::submodule_deinit(&repodir, "lib", false).is_err());
+        assert_eq!(git::submodules(&repodir).unwrap()[0].state, git::SubmoduleState::Clean);
+        assert!(subdir.mash(".git").is_dir());
+        assert!(subdir.mash("README.md").exists());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_submodule_nested() {
+        let tmpdir = setup("git_submodule_nested");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let lib = fixture(&tmpdir, "lib");
+        let mid = fixture(&tmpdir, "mid");
+        let top = fixture(&tmpdir, "top");
+        assert!(git::Repo::new(&mid).unwrap().submodule_add(lib.to_string().unwrap(), "lib").is_ok());
+        assert!(git::CommitBuilder::new(&mid).message("Add lib").commit().is_ok());
+        assert!(git::Repo::new(&top).unwrap().submodule_add(mid.to_string().unwrap(), "mid").is_ok());
+        assert!(git::CommitBuilder::new(&top).message("Add mid").commit().is_ok());
+
+        // Non recursive updates leave nested submodules uninitialized
+        let clone = tmpdir.mash("clone");
+        assert!(git::Repo::new(&clone).unwrap().url(top.to_string().unwrap()).clone().is_ok());
+        assert!(git::Repo::new(&clone).unwrap().submodule_update(&git::SubmoduleUpdateOptions::new().init(true)).is_ok());
+        assert_eq!(clone.mash("mid/README.md").exists(), true);
+        assert_eq!(clone.mash("mid/lib/README.md").exists(), false);
+        assert_eq!(git::submodules(clone.mash("mid")).unwrap()[0].state, git::SubmoduleState::Uninitialized);
+
+        // Recursive updates initialize and check out nested submodules
+        let opts = git::SubmoduleUpdateOptions::new().init(true).recursive(true);
+        assert!(git::Repo::new(&clone).unwrap().submodule_update(&opts).is_ok());
+        assert_eq!(clone.mash("mid/lib/README.md").exists(), true);
+        assert_eq!(git::submodules(clone.mash("mid")).unwrap()[0].state, git::SubmoduleState::Clean);
+        assert_eq!(status(&clone.mash("mid")), git_status(&clone.mash("mid")));
+        let output = Command::new("git").args(["submodule", "status", "--recursive"]).current_dir(&clone).output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 2);
+
+        // Cloning with submodules checks out the whole tree
+        let clone = tmpdir.mash("clone-recursive");
+        assert!(git::Repo::new(&clone).unwrap().url(top.to_string().unwrap()).with_submodules(true).clone().is_ok());
+        assert_eq!(clone.mash("mid/lib/README.md").exists(), true);
+        assert_eq!(git::submodules(&clone).unwrap()[0].state, git::SubmoduleState::Clean);
+        assert_eq!(git::submodules(clone.mash("mid")).unwrap()[0].state, git::SubmoduleState::Clean);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,648 @@
+use super::{signature_start, Repo, Signer, Version};
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{ErrorClass, ErrorCode, ObjectType, Oid, Reference, Repository, Signature, Tag};
+
+/// Tag details as returned by `tags`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagInfo {
+    pub name: String,               // Tag name e.g. `v1.0.0`
+    pub oid: Oid,                   // Id of the tag object for annotated tags else the target
+    pub target: Oid,                // Id of the tagged object with any tags peeled
+    pub annotation: Option<String>, // Annotated tag's message
+    pub time: i64,                  // Tagger time else the tagged commit's time in seconds since the epoch
+    pub version: Option<Version>,   // Semantic version parsed from the name
+}
+
+/// Annotated tag's message and tagger as returned by `tag_annotation`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagAnnotation {
+    pub message: String, // Annotation message without any signature
+    pub tagger: String,  // Tagger's name
+    pub email: String,   // Tagger's email
+    pub time: i64,       // Tagger time in seconds since the epoch
+}
+
+/// Order of the tags returned by `tags`
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum TagOrder {
+    /// Lexical order of the tag names
+    #[default]
+    Name,
+
+    /// Ascending semantic version order so that `v0.10.0` follows `v0.9.0` with tags that aren't
+    /// semantic versions listed first in lexical order
+    Semver,
+}
+
+/// Create a lightweight tag with the given name pointing at the given revision returning the
+/// id of the tagged object. Fails if the tag already exists.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_tag_lightweight_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let oid = git::tag_lightweight(&tmpdir, "v1.0.0", "HEAD").unwrap();
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn tag_lightweight<T, U, V>(path: T, name: U, target: V) -> Result<Oid>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let target = repo.revparse_single(target.as_ref())?;
+    Ok(repo.tag_lightweight(name.as_ref(), &target, false)?)
+}
+
+/// Create an annotated tag with the given name and message pointing at the given revision
+/// returning the id of the new tag object. The tagger is given as a name and email and defaults
+/// to the configured `user.name` and `user.email` identity. Fails if the tag already exists.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_tag_annotated_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let oid = git::tag_annotated(&tmpdir, "v1.0.0", "HEAD", "Release 1.0.0", Some(("foo", "foo@example.com"))).unwrap();
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn tag_annotated<T, U, V, W>(path: T, name: U, target: V, message: W, tagger: Option<(&str, &str)>) -> Result<Oid>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+    W: AsRef<str>,
+{
+    if message.as_ref().trim().is_empty() {
+        return Err(Error::NoMessageWasFound);
+    }
+    let repo = Repository::open(path.as_ref())?;
+    let target = repo.revparse_single(target.as_ref())?;
+    let tagger = tagger_signature(&repo, tagger)?;
+    Ok(repo.tag(name.as_ref(), &target, &tagger, message.as_ref(), false)?)
+}
+
+/// Create a signed annotated tag with the given name and message pointing at the given revision
+/// returning the id of the new tag object. The tag is signed the same way `git tag -s` does so
+/// that it can be checked with `verify_tag` or `git verify-tag`. The tagger is given as a name and
+/// email and defaults to the configured `user.name` and `user.email` identity. Fails if the tag
+/// already exists.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_tag_signed_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let signer = git::Signer::callback(|_| Ok("-----BEGIN PGP SIGNATURE-----\n...\n-----END PGP SIGNATURE-----\n".to_string()));
+/// assert!(git::tag_signed(&tmpdir, "v1.0.0", "HEAD", "Release 1.0.0", Some(("foo", "foo@example.com")), &signer).is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn tag_signed<T, U, V, W>(path: T, name: U, target: V, message: W, tagger: Option<(&str, &str)>, signer: &Signer) -> Result<Oid>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<str>,
+    W: AsRef<str>,
+{
+    if message.as_ref().trim().is_empty() {
+        return Err(Error::NoMessageWasFound);
+    }
+    let name = name.as_ref();
+    let refname = format!("refs/tags/{}", name);
+    if !Reference::is_valid_name(&refname) {
+        return Err(git2::Error::new(ErrorCode::InvalidSpec, ErrorClass::Tag, format!("invalid tag name: {}", name)).into());
+    }
+    let repo = Repository::open(path.as_ref())?;
+    if repo.find_reference(&refname).is_ok() {
+        return Err(git2::Error::new(ErrorCode::Exists, ErrorClass::Tag, format!("tag already exists: {}", name)).into());
+    }
+    let target = repo.revparse_single(target.as_ref())?;
+    let kind = target.kind().map(|x| x.str()).unwrap_or("commit");
+    let tagger = tagger_signature(&repo, tagger)?;
+    let when = tagger.when();
+    let offset = when.offset_minutes().abs();
+
+    // Signed tags are created from the raw tag content with the signature appended
+    let content = format!(
+        "object {}\ntype {}\ntag {}\ntagger {} <{}> {} {}{:02}{:02}\n\n{}\n",
+        target.id(),
+        kind,
+        name,
+        tagger.name().unwrap_or_default(),
+        tagger.email().unwrap_or_default(),
+        when.seconds(),
+        when.sign(),
+        offset / 60,
+        offset % 60,
+        message.as_ref().trim_end()
+    );
+    let mut signature = signer.sign(&repo, &content)?;
+    if !signature.ends_with('\n') {
+        signature.push('\n');
+    }
+    let oid = repo.odb()?.write(ObjectType::Tag, (content + &signature).as_bytes())?;
+    repo.reference(&refname, oid, false, "tag: signed")?;
+    Ok(oid)
+}
+
+/// Returns the repo's tags in the given order.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_tags_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let tags = git::tags(&tmpdir, git::TagOrder::Semver).unwrap();
+/// assert_eq!(tags[0].name, "3.11");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn tags<T: AsRef<Path>>(path: T, order: TagOrder) -> Result<Vec<TagInfo>> {
+    let repo = Repository::open(path.as_ref())?;
+    let mut tags = repo.tag_names(None)?.iter().flatten().map(|name| tag_info(&repo, name)).collect::<Result<Vec<_>>>()?;
+    match order {
+        TagOrder::Name => tags.sort_by(|x, y| x.name.cmp(&y.name)),
+        TagOrder::Semver => tags.sort_by(|x, y| x.version.cmp(&y.version).then_with(|| x.name.cmp(&y.name))),
+    }
+    Ok(tags)
+}
+
+/// Delete the given local tag.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_tag_delete_local_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::tag_delete_local(&tmpdir, "3.11").is_ok());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn tag_delete_local<T, U>(path: T, name: U) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let mut reference = repo.find_reference(&format!("refs/tags/{}", name.as_ref())).map_err(|_| Error::tag_not_found(name.as_ref()))?;
+    reference.delete()?;
+    Ok(())
+}
+
+impl<'a> Repo<'a> {
+    /// Delete the given tag on the repo's remote, `origin` by default, by pushing a delete
+    /// refspec using the repo's network settings and transfer progress callback. The local tag
+    /// is left as is so that it can be moved and pushed again.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_tag_delete_remote_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+    /// let remote = tmpdir.mash("remote");
+    /// assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+    /// let local = tmpdir.mash("local");
+    /// assert!(git::Repo::new(&local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+    /// assert!(git::Repo::new(&local).unwrap().tag_delete_remote("3.11").is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn tag_delete_remote<T: AsRef<str>>(mut self, name: T) -> Result<()> {
+        self.push(&[format!(":refs/tags/{}", name.as_ref())])
+    }
+
+    /// Push the given local tag to the repo's remote, `origin` by default, using the repo's
+    /// network settings and transfer progress callback. A tag that already exists on the remote
+    /// with a different target is rejected.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_push_tag_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+    /// let remote = tmpdir.mash("remote");
+    /// assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+    /// let local = tmpdir.mash("local");
+    /// assert!(git::Repo::new(&local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+    /// assert!(git::tag_lightweight(&local, "v1.0.0", "HEAD").is_ok());
+    /// assert!(git::Repo::new(&local).unwrap().push_tag("v1.0.0").is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn push_tag<T: AsRef<str>>(mut self, name: T) -> Result<()> {
+        let refname = format!("refs/tags/{}", name.as_ref());
+        Repository::open(self.path_val())?.find_reference(&refname).map_err(|_| Error::tag_not_found(name.as_ref()))?;
+        self.push(&[format!("{}:{}", refname, refname)])
+    }
+
+    /// Push all local tags to the repo's remote, `origin` by default, using the repo's network
+    /// settings and transfer progress callback. Tags already on the remote are left as is while
+    /// any that exist with a different target are rejected.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_push_tags_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("src")).is_ok());
+    /// let remote = tmpdir.mash("remote");
+    /// assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+    /// let local = tmpdir.mash("local");
+    /// assert!(git::Repo::new(&local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+    /// assert!(git::tag_lightweight(&local, "v1.0.0", "HEAD").is_ok());
+    /// assert!(git::Repo::new(&local).unwrap().push_tags().is_ok());
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn push_tags(mut self) -> Result<()> {
+        let repo = Repository::open(self.path_val())?;
+        let refspecs: Vec<String> = repo.tag_names(None)?.iter().flatten().map(|x| format!("refs/tags/{}:refs/tags/{}", x, x)).collect();
+        if refspecs.is_empty() {
+            return Ok(());
+        }
+        self.push(&refspecs)
+    }
+}
+
+/// Returns the annotation message and tagger of the given tag or None if it is a lightweight
+/// tag. Any signature is stripped from the message so it can be reused e.g. as release notes.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_tag_annotation_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// assert!(git::tag_annotated(&tmpdir, "v1.0.0", "HEAD", "Release 1.0.0", Some(("foo", "foo@example.com"))).is_ok());
+/// assert_eq!(git::tag_annotation(&tmpdir, "v1.0.0").unwrap().unwrap().message, "Release 1.0.0");
+/// assert_eq!(git::tag_annotation(&tmpdir, "3.11").unwrap(), None);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn tag_annotation<T, U>(path: T, name: U) -> Result<Option<TagAnnotation>>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let reference = repo.find_reference(&format!("refs/tags/{}", name.as_ref())).map_err(|_| Error::tag_not_found(name.as_ref()))?.resolve()?;
+    let object = repo.find_object(reference.target().ok_or_else(|| Error::tag_not_found(name.as_ref()))?, None)?;
+    Ok(object.as_tag().map(|tag| {
+        let tagger = tag.tagger();
+        TagAnnotation {
+            message: annotation_message(tag),
+            tagger: tagger.as_ref().and_then(|x| x.name()).unwrap_or_default().to_string(),
+            email: tagger.as_ref().and_then(|x| x.email()).unwrap_or_default().to_string(),
+            time: tagger.as_ref().map(|x| x.when().seconds()).unwrap_or_default(),
+        }
+    }))
+}
+
+// Returns the given tag's message without the signature signed tags have appended to it
+fn annotation_message(tag: &Tag) -> String {
+    let message = tag.message().unwrap_or_default();
+    match signature_start(message) {
+        Some(start) => message[..start + 1].to_string(),
+        None => message.to_string(),
+    }
+}
+
+// Returns the details of the given tag
+fn tag_info(repo: &Repository, name: &str) -> Result<TagInfo> {
+    let reference = repo.find_reference(&format!("refs/tags/{}", name))?.resolve()?;
+    let object = repo.find_object(reference.target().ok_or_else(|| git2::Error::from_str("tag has no target"))?, None)?;
+    let mut target = object.clone();
+    while target.kind() == Some(ObjectType::Tag) {
+        target = target.peel(ObjectType::Any)?;
+    }
+    let commit_time = target.as_commit().map(|x| x.time().seconds()).unwrap_or_default();
+    let (annotation, time) = match object.as_tag() {
+        Some(tag) => (Some(annotation_message(tag)), tag.tagger().map(|x| x.when().seconds()).unwrap_or(commit_time)),
+        None => (None, commit_time),
+    };
+    Ok(TagInfo {
+        name: name.to_string(),
+        oid: object.id(),
+        target: target.id(),
+        annotation,
+        time,
+        version: Version::parse(name).ok(),
+    })
+}
+
+// Resolve the tagger from the given name and email falling back on the configured identity
+fn tagger_signature(repo: &Repository, tagger: Option<(&str, &str)>) -> Result<Signature<'static>> {
+    Ok(match tagger {
+        Some((name, email)) => Signature::now(name, email)?,
+        None => repo.signature().map_err(|_| git2::Error::from_str("no tagger was set and no identity is configured"))?,
+    })
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Create an isolated gpg home with a test key
+    fn gpg_home(path: &Path) -> PathBuf {
+        let home = path.mash("gnupg");
+        assert!(sys::mkdir(&home).is_ok());
+        assert!(sys::chmod(&home, 0o700).is_ok());
+        let status = Command::new("gpg")
+            .args(["--homedir", home.to_str().unwrap(), "--batch", "--passphrase", "", "--quick-gen-key", "test <test@example.com>", "ed25519", "sign", "never"])
+            .output()
+            .unwrap()
+            .status;
+        assert_eq!(status.success(), true);
+        home
+    }
+
+    // Sign the given content with the test gpg key
+    fn gpg_sign(home: &Path, content: &str) -> git::Result<String> {
+        let mut child = Command::new("gpg")
+            .args(["--homedir", home.to_str().unwrap(), "-bsau", "test@example.com"])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        std::io::Write::write_all(&mut child.stdin.take().unwrap(), content.as_bytes()).unwrap();
+        Ok(String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap())
+    }
+
+    #[test]
+    fn test_tag_lightweight() {
+        let tmpdir = setup("git_tag_lightweight");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+
+        // Points directly at the target commit
+        let oid = git::tag_lightweight(&tmpdir, "v1.0.0", "HEAD~2").unwrap();
+        assert_eq!(oid, repo.revparse_single("HEAD~2").unwrap().id());
+        assert_eq!(repo.find_reference("refs/tags/v1.0.0").unwrap().target(), Some(oid));
+
+        // Errors
+        assert!(git::tag_lightweight(&tmpdir, "v1.0.0", "HEAD").is_err());
+        assert!(git::tag_lightweight(&tmpdir, "v1.0.1", "missing").is_err());
+        assert!(git::tag_lightweight(&tmpdir, "bad..name", "HEAD").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_tag_annotated() {
+        let tmpdir = setup("git_tag_annotated");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+
+        // Explicit tagger
+        let oid = git::tag_annotated(&tmpdir, "v1.0.0", "HEAD~1", "Release 1.0.0", Some(("foo", "foo@example.com"))).unwrap();
+        let tag = repo.find_tag(oid).unwrap();
+        assert_eq!(tag.name(), Some("v1.0.0"));
+        assert_eq!(tag.target_id(), repo.revparse_single("HEAD~1").unwrap().id());
+        assert_eq!(tag.message(), Some("Release 1.0.0"));
+        assert_eq!(tag.tagger().unwrap().name(), Some("foo"));
+        assert_eq!(tag.tagger().unwrap().email(), Some("foo@example.com"));
+        assert_eq!(repo.find_reference("refs/tags/v1.0.0").unwrap().target(), Some(oid));
+
+        // Configured tagger
+        repo.config().unwrap().set_str("user.name", "test").unwrap();
+        repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+        let oid = git::tag_annotated(&tmpdir, "v1.0.1", "HEAD", "Release 1.0.1", None).unwrap();
+        assert_eq!(repo.find_tag(oid).unwrap().tagger().unwrap().name(), Some("test"));
+
+        // Errors
+        assert!(git::tag_annotated(&tmpdir, "v1.0.0", "HEAD", "Again", None).is_err());
+        assert_eq!(git::tag_annotated(&tmpdir, "v1.0.2", "HEAD", " \n", None).unwrap_err().to_string(), git::Error::NoMessageWasFound.to_string());
+        assert!(git::tag_annotated(&tmpdir, "v1.0.2", "missing", "Release", None).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_tag_signed() {
+        let tmpdir = setup("git_tag_signed");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let home = gpg_home(&tmpdir);
+        let signer = {
+            let home = home.clone();
+            git::Signer::callback(move |content| gpg_sign(&home, content))
+        };
+
+        // Verifies with skellige and git
+        let oid = git::tag_signed(&tmpdir, "v1.0.0", "HEAD~1", "Release 1.0.0", Some(("test", "test@example.com")), &signer).unwrap();
+        let tag = repo.find_tag(oid).unwrap();
+        assert_eq!(tag.name(), Some("v1.0.0"));
+        assert_eq!(tag.target_id(), repo.revparse_single("HEAD~1").unwrap().id());
+        assert_eq!(tag.tagger().unwrap().name(), Some("test"));
+        assert_eq!(repo.find_reference("refs/tags/v1.0.0").unwrap().target(), Some(oid));
+        let result = git::verify_tag(&tmpdir, "v1.0.0", &git::Verifier::new().gpg_home(&home)).unwrap();
+        assert_eq!(result.status, git::SignatureStatus::Good);
+        assert_eq!(result.kind, Some(git::SignatureKind::Gpg));
+        assert_eq!(result.signer, Some("test <test@example.com>".to_string()));
+        let output = Command::new("git").args(["verify-tag", "v1.0.0"]).env("GNUPGHOME", &home).current_dir(&tmpdir).output().unwrap();
+        assert_eq!(output.status.success(), true);
+        let output = Command::new("git").args(["tag", "-l", "--format=%(contents:subject)", "v1.0.0"]).current_dir(&tmpdir).output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "Release 1.0.0\n");
+        let tags = git::tags(&tmpdir, git::TagOrder::Name).unwrap();
+        assert_eq!(tags[1].annotation, Some("Release 1.0.0\n".to_string()));
+
+        // Configured tagger
+        repo.config().unwrap().set_str("user.name", "foo").unwrap();
+        repo.config().unwrap().set_str("user.email", "foo@example.com").unwrap();
+        let oid = git::tag_signed(&tmpdir, "v1.0.1", "HEAD", "Release 1.0.1", None, &signer).unwrap();
+        assert_eq!(repo.find_tag(oid).unwrap().tagger().unwrap().name(), Some("foo"));
+
+        // Errors
+        let failing = git::Signer::callback(|_| Err(git::Error::signing_failed("foo")));
+        assert_eq!(git::tag_signed(&tmpdir, "v1.0.2", "HEAD", "Release", None, &failing).unwrap_err().to_string(), "failed to sign: foo");
+        assert!(repo.find_reference("refs/tags/v1.0.2").is_err());
+        assert!(git::tag_signed(&tmpdir, "v1.0.0", "HEAD", "Again", None, &signer).is_err());
+        assert!(git::tag_signed(&tmpdir, "bad..name", "HEAD", "Release", None, &signer).is_err());
+        assert!(git::tag_signed(&tmpdir, "v1.0.2", "missing", "Release", None, &signer).is_err());
+        assert_eq!(git::tag_signed(&tmpdir, "v1.0.2", "HEAD", "\n", None, &signer).unwrap_err().to_string(), git::Error::NoMessageWasFound.to_string());
+
+        let _ = Command::new("gpgconf").args(["--homedir", home.to_str().unwrap(), "--kill", "gpg-agent"]).output();
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_tag_annotation() {
+        let tmpdir = setup("git_tag_annotation");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+
+        // Annotated
+        let notes = "Release 1.0.0\n\n* Add foo\n* Fix bar\n";
+        let oid = git::tag_annotated(&tmpdir, "v1.0.0", "HEAD", notes, Some(("foo", "foo@example.com"))).unwrap();
+        let annotation = git::tag_annotation(&tmpdir, "v1.0.0").unwrap().unwrap();
+        assert_eq!(annotation.message, notes);
+        assert_eq!(annotation.tagger, "foo");
+        assert_eq!(annotation.email, "foo@example.com");
+        assert_eq!(annotation.time, repo.find_tag(oid).unwrap().tagger().unwrap().when().seconds());
+
+        // Signed without the signature
+        let signer = git::Signer::callback(|_| Ok("-----BEGIN PGP SIGNATURE-----\n\nfoo\n-----END PGP SIGNATURE-----\n".to_string()));
+        assert!(git::tag_signed(&tmpdir, "v1.0.1", "HEAD", notes, Some(("foo", "foo@example.com")), &signer).is_ok());
+        assert_eq!(git::tag_annotation(&tmpdir, "v1.0.1").unwrap().unwrap().message, notes);
+
+        // Lightweight
+        assert_eq!(git::tag_annotation(&tmpdir, "3.11").unwrap(), None);
+
+        // Errors
+        assert_eq!(git::tag_annotation(&tmpdir, "missing").unwrap_err().to_string(), git::Error::tag_not_found("missing").to_string());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_tag_delete() {
+        let tmpdir = setup("git_tag_delete");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+
+        // Bare remote with a couple tags and a clone of it
+        let remote = tmpdir.mash("remote");
+        assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        assert!(git::tag_annotated(&remote, "v1.0.0", "HEAD", "Release 1.0.0", Some(("foo", "foo@example.com"))).is_ok());
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+        let repo = git2::Repository::open(&local).unwrap();
+        let remote_repo = git2::Repository::open(&remote).unwrap();
+        assert!(repo.find_reference("refs/tags/v1.0.0").is_ok());
+
+        // Local
+        assert!(git::tag_delete_local(&local, "v1.0.0").is_ok());
+        assert!(repo.find_reference("refs/tags/v1.0.0").is_err());
+        assert!(remote_repo.find_reference("refs/tags/v1.0.0").is_ok());
+        assert_eq!(git::tag_delete_local(&local, "v1.0.0").unwrap_err().to_string(), git::Error::tag_not_found("v1.0.0").to_string());
+
+        // Remote with progress leaving the local tag
+        assert!(git::Repo::new(&local).unwrap().xfer_progress(|_, _| {}).tag_delete_remote("v1.0.0").is_ok());
+        assert!(remote_repo.find_reference("refs/tags/v1.0.0").is_err());
+        assert!(remote_repo.find_reference("refs/tags/3.11").is_ok());
+        assert!(git::Repo::new(&local).unwrap().tag_delete_remote("3.11").is_ok());
+        assert!(remote_repo.find_reference("refs/tags/3.11").is_err());
+        assert!(repo.find_reference("refs/tags/3.11").is_ok());
+
+        // Missing remote
+        assert!(git::Repo::new(&local).unwrap().remote("missing").tag_delete_remote("3.11").is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_push_tags() {
+        let tmpdir = setup("git_push_tags");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("src")).is_ok());
+
+        // Bare remote and a clone of it
+        let remote = tmpdir.mash("remote");
+        assert!(git::Repo::new(&remote).unwrap().url(tmpdir.mash("src").to_string().unwrap()).bare(true).clone().is_ok());
+        let local = tmpdir.mash("local");
+        assert!(git::Repo::new(&local).unwrap().url(remote.to_string().unwrap()).clone().is_ok());
+        let remote_repo = git2::Repository::open(&remote).unwrap();
+        let target = |name: &str| remote_repo.find_reference(&format!("refs/tags/{}", name)).ok().and_then(|x| x.target());
+
+        // Single tag with progress
+        let oid = git::tag_annotated(&local, "v1.0.0", "HEAD~1", "Release 1.0.0", Some(("foo", "foo@example.com"))).unwrap();
+        assert!(git::tag_lightweight(&local, "v1.0.1", "HEAD").is_ok());
+        let mut called = false;
+        assert!(git::Repo::new(&local).unwrap().xfer_progress(|_, _| called = true).push_tag("v1.0.0").is_ok());
+        assert_eq!(called, true);
+        assert_eq!(target("v1.0.0"), Some(oid));
+        assert_eq!(target("v1.0.1"), None);
+        assert_eq!(git::Repo::new(&local).unwrap().push_tag("missing").unwrap_err().to_string(), git::Error::tag_not_found("missing").to_string());
+
+        // All tags
+        assert!(git::Repo::new(&local).unwrap().push_tags().is_ok());
+        assert_eq!(target("v1.0.1"), Some(remote_repo.revparse_single("HEAD").unwrap().id()));
+        assert_eq!(target("v1.0.0"), Some(oid));
+        assert!(git::Repo::new(&local).unwrap().push_tags().is_ok());
+
+        // Moved tag is rejected
+        assert!(git::tag_delete_local(&local, "v1.0.1").is_ok());
+        assert!(git::tag_lightweight(&local, "v1.0.1", "HEAD~2").is_ok());
+        assert!(git::Repo::new(&local).unwrap().push_tag("v1.0.1").is_err());
+        assert_eq!(target("v1.0.1"), Some(remote_repo.revparse_single("HEAD").unwrap().id()));
+
+        // Missing remote
+        assert!(git::Repo::new(&local).unwrap().remote("missing").push_tags().is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_tags() {
+        let tmpdir = setup("git_tags");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let commit = |rev: &str| repo.revparse_single(rev).unwrap().peel_to_commit().unwrap();
+
+        // Lightweight
+        let tags = git::tags(&tmpdir, git::TagOrder::Name).unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].name, "3.11");
+        assert_eq!(tags[0].oid, tags[0].target);
+        assert_eq!(tags[0].target, commit("3.11").id());
+        assert_eq!(tags[0].annotation, None);
+        assert_eq!(tags[0].time, commit("3.11").time().seconds());
+        assert_eq!(tags[0].version, None);
+
+        // Annotated
+        for (name, rev) in [
+            ("v0.10.0", "HEAD"),
+            ("v0.9.0", "HEAD~1"),
+            ("v0.10.0-rc.1", "HEAD~2"),
+            ("v0.2.0", "HEAD~3"),
+        ] {
+            git::tag_annotated(&tmpdir, name, rev, format!("Release {}", name), Some(("foo", "foo@example.com"))).unwrap();
+        }
+        let tags = git::tags(&tmpdir, git::TagOrder::Name).unwrap();
+        let names: Vec<&str> = tags.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, vec!["3.11", "v0.10.0", "v0.10.0-rc.1", "v0.2.0", "v0.9.0"]);
+        let tag = &tags[1];
+        assert_eq!(tag.oid, repo.find_reference("refs/tags/v0.10.0").unwrap().target().unwrap());
+        assert_eq!(tag.target, commit("HEAD").id());
+        assert_eq!(tag.annotation, Some("Release v0.10.0".to_string()));
+        assert_eq!(tag.time, repo.find_tag(tag.oid).unwrap().tagger().unwrap().when().seconds());
+        assert_eq!(tag.version, Some(git::Version::new(0, 10, 0)));
+
+        // Semver
+        let tags = git::tags(&tmpdir, git::TagOrder::Semver).unwrap();
+        let names: Vec<&str> = tags.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, vec!["3.11", "v0.2.0", "v0.9.0", "v0.10.0-rc.1", "v0.10.0"]);
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
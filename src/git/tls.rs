@@ -0,0 +1,164 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{cert::Cert, CertificateCheckStatus, RemoteCallbacks};
+use std::{ffi::CString, fmt, os::raw::c_int, ptr, sync::Arc};
+
+// Certificate check callback shared across threads
+type CertCheck = Arc<dyn Fn(&Cert<'_>, &str) -> bool+Send+Sync>;
+
+/// TLS settings for https transports. By default certificates are verified against the
+/// system's CA certificates. A custom certificate check callback can be supplied to accept or
+/// reject certificates e.g. pinning an internal server's self-signed certificate, or
+/// verification can be skipped entirely with `insecure`.
+#[derive(Clone, Default)]
+pub struct Tls {
+    insecure: bool,           // Skip certificate verification
+    check: Option<CertCheck>, // Custom certificate check callback
+}
+
+impl fmt::Debug for Tls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tls").field("insecure", &self.insecure).field("check", &self.check.is_some()).finish()
+    }
+}
+
+impl Tls {
+    /// Create new TLS settings that verify certificates against the system's CA certificates.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Tls::new().insecure_val(), false);
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if certificate verification will be skipped.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Tls::new().insecure(true).insecure_val(), true);
+    /// ```
+    pub fn insecure_val(&self) -> bool {
+        self.insecure
+    }
+
+    /// Returns true if a custom certificate check callback has been set.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Tls::new().certificate_check(|_, host| host == "git.example.com").has_certificate_check(), true);
+    /// ```
+    pub fn has_certificate_check(&self) -> bool {
+        self.check.is_some()
+    }
+
+    /// Set to skip certificate verification entirely, accepting any certificate the server
+    /// presents. Only use this for trusted internal servers with self-signed certificates.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Tls::new().insecure(true).insecure_val(), true);
+    /// ```
+    pub fn insecure(mut self, yes: bool) -> Self {
+        self.insecure = yes;
+        self
+    }
+
+    /// Set a custom certificate check callback that is given the server's certificate and
+    /// hostname and returns true to accept it. The callback replaces the default verification
+    /// so it is responsible for all checks it needs.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Tls::new().certificate_check(|_, host| host == "git.example.com").has_certificate_check(), true);
+    /// ```
+    pub fn certificate_check<T>(mut self, func: T) -> Self
+    where
+        T: Fn(&Cert<'_>, &str) -> bool+Send+Sync+'static,
+    {
+        self.check = Some(Arc::new(func));
+        self
+    }
+
+    // Apply the certificate check to the given remote callbacks if needed
+    pub(crate) fn apply(&self, callbacks: &mut RemoteCallbacks<'_>) {
+        if self.insecure {
+            callbacks.certificate_check(|_, _| Ok(CertificateCheckStatus::CertificateOk));
+        } else if let Some(check) = &self.check {
+            let check = check.clone();
+            callbacks.certificate_check(move |cert, host| match check(cert, host) {
+                true => Ok(CertificateCheckStatus::CertificateOk),
+                false => Err(git2::Error::from_str(&format!("certificate for {} was rejected", host))),
+            });
+        }
+    }
+}
+
+/// Set the CA bundle file and/or directory of hashed CA certificates used to verify https
+/// servers in place of the system's defaults. This is a process wide setting affecting all
+/// subsequent network operations.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// assert!(git::set_ca_bundle(Some("/etc/ssl/certs/ca-certificates.crt"), None::<&str>).is_ok());
+/// ```
+pub fn set_ca_bundle<T, U>(file: Option<T>, dir: Option<U>) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<Path>,
+{
+    let file = file.map(cstring).transpose()?;
+    let dir = dir.map(cstring).transpose()?;
+    libgit2_sys::init();
+    let code = unsafe {
+        libgit2_sys::git_libgit2_opts(
+            libgit2_sys::GIT_OPT_SET_SSL_CERT_LOCATIONS as c_int,
+            file.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+            dir.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+        )
+    };
+    match code < 0 {
+        true => Err(git2::Error::last_error(code).into()),
+        false => Ok(()),
+    }
+}
+
+// Convert the given path to an absolute C string for libgit2
+fn cstring<T: AsRef<Path>>(path: T) -> Result<CString> {
+    Ok(CString::new(path.as_ref().abs()?.to_string()?).map_err(|x| git2::Error::from_str(&x.to_string()))?)
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn test_tls() {
+        assert_eq!(git::Tls::new().insecure_val(), false);
+        assert_eq!(git::Tls::new().has_certificate_check(), false);
+        assert_eq!(git::Tls::new().insecure(true).insecure_val(), true);
+        assert_eq!(git::Tls::new().certificate_check(|_, _| false).has_certificate_check(), true);
+        assert_eq!(format!("{:?}", git::Tls::new()), "Tls { insecure: false, check: false }");
+    }
+
+    #[test]
+    fn test_set_ca_bundle() {
+        assert!(git::set_ca_bundle(Some("tests/temp/git_tls_missing/ca.crt"), None::<&str>).is_err());
+    }
+}
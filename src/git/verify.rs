@@ -0,0 +1,588 @@
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{ErrorCode, ObjectType, Repository};
+use std::{
+    collections::hash_map::RandomState,
+    env, fs,
+    hash::BuildHasher,
+    io::{self, Write},
+    os::unix::fs::DirBuilderExt,
+    process::{Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const PGP_HEADER: &str = "-----BEGIN PGP SIGNATURE-----";
+const SSH_HEADER: &str = "-----BEGIN SSH SIGNATURE-----";
+
+/// Kind of signature found on a commit or tag
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SignatureKind {
+    /// OpenPGP signature verified with gpg
+    Gpg,
+
+    /// SSH signature verified with ssh-keygen against an allowed signers file
+    Ssh,
+}
+
+/// Outcome of verifying a signature
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SignatureStatus {
+    /// The object isn't signed
+    Unsigned,
+
+    /// The signature is valid and made by a known key
+    Good,
+
+    /// The signature doesn't match the content or the key was revoked
+    Bad,
+
+    /// The signature is valid but it or the key has expired
+    Expired,
+
+    /// The signing key isn't in the keyring or allowed signers file
+    UnknownKey,
+}
+
+/// Signature verification result as returned by `verify_commit` and `verify_tag`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Verification {
+    pub status: SignatureStatus,     // Outcome of the verification
+    pub kind: Option<SignatureKind>, // Kind of signature if signed
+    pub signer: Option<String>,      // Signer's identity e.g. `foo <foo@example.com>` or ssh principal
+    pub key: Option<String>,         // Signing key's fingerprint or id
+}
+
+impl Verification {
+    /// Returns true if the signature is good.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_verification_is_good_doc");
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+    /// assert_eq!(git::verify_commit(&tmpdir, "HEAD", &git::Verifier::new()).unwrap().is_good(), false);
+    /// assert!(sys::remove_all(&tmpdir).is_ok());
+    /// ```
+    pub fn is_good(&self) -> bool {
+        self.status == SignatureStatus::Good
+    }
+
+    // Create an unsigned result
+    fn unsigned() -> Self {
+        Self { status: SignatureStatus::Unsigned, kind: None, signer: None, key: None }
+    }
+}
+
+/// Keys to verify signatures against. By default gpg's default keyring is used for gpg
+/// signatures and the configured `gpg.ssh.allowedSignersFile` for ssh signatures. The
+/// configured `gpg.program` and `gpg.ssh.program` are honored.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let verifier = git::Verifier::new().gpg_home("~/.gnupg").allowed_signers("allowed_signers");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Verifier {
+    gpg_home: Option<PathBuf>,        // Gpg home directory to use instead of the default
+    keyring: Option<PathBuf>,         // Gpg keyring file to use instead of the default
+    allowed_signers: Option<PathBuf>, // Allowed signers file for ssh signatures
+}
+
+impl Verifier {
+    /// Create a new verifier using the default keys.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let verifier = git::Verifier::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the given gpg home directory rather than the default.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Verifier::new().gpg_home("foo").gpg_home_val(), Some(Path::new("foo")));
+    /// ```
+    pub fn gpg_home<T: AsRef<Path>>(mut self, path: T) -> Self {
+        self.gpg_home = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Returns the gpg home directory if set.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Verifier::new().gpg_home_val(), None);
+    /// ```
+    pub fn gpg_home_val(&self) -> Option<&Path> {
+        self.gpg_home.as_deref()
+    }
+
+    /// Use only the given gpg keyring file rather than the default keyring.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Verifier::new().keyring("foo.kbx").keyring_val(), Some(Path::new("foo.kbx")));
+    /// ```
+    pub fn keyring<T: AsRef<Path>>(mut self, path: T) -> Self {
+        self.keyring = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Returns the gpg keyring file if set.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Verifier::new().keyring_val(), None);
+    /// ```
+    pub fn keyring_val(&self) -> Option<&Path> {
+        self.keyring.as_deref()
+    }
+
+    /// Use the given allowed signers file, in the `ssh-keygen` format, for ssh signatures.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Verifier::new().allowed_signers("foo").allowed_signers_val(), Some(Path::new("foo")));
+    /// ```
+    pub fn allowed_signers<T: AsRef<Path>>(mut self, path: T) -> Self {
+        self.allowed_signers = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Returns the allowed signers file if set.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Verifier::new().allowed_signers_val(), None);
+    /// ```
+    pub fn allowed_signers_val(&self) -> Option<&Path> {
+        self.allowed_signers.as_deref()
+    }
+
+    // Verify the given signature over the given content
+    pub(crate) fn verify(&self, repo: &Repository, content: &[u8], signature: &str) -> Result<Verification> {
+        let config = repo.config()?;
+        if signature.trim_start().starts_with(SSH_HEADER) {
+            let program = config.get_string("gpg.ssh.program").unwrap_or_else(|_| "ssh-keygen".to_string());
+            let allowed = match &self.allowed_signers {
+                Some(path) => path.clone(),
+                None => config.get_path("gpg.ssh.allowedSignersFile").map_err(|_| Error::verify_failed("no allowed signers file configured"))?,
+            };
+            with_tempfile(signature, |sig| ssh_verify(&program, &allowed, sig, content))
+        } else {
+            let program = config.get_string("gpg.program").unwrap_or_else(|_| "gpg".to_string());
+            with_tempfile(signature, |sig| self.gpg_verify(&program, sig, content))
+        }
+    }
+
+    // Verify the given detached signature file over the given content with gpg
+    fn gpg_verify(&self, program: &str, sig: &Path, content: &[u8]) -> Result<Verification> {
+        let mut cmd = Command::new(program);
+        cmd.arg("--status-fd=1");
+        if let Some(home) = &self.gpg_home {
+            cmd.arg("--homedir").arg(home);
+        }
+        if let Some(keyring) = &self.keyring {
+            cmd.arg("--no-default-keyring").arg("--keyring").arg(keyring);
+        }
+        cmd.arg("--verify").arg(sig).arg("-");
+        let output = run(cmd, content)?;
+
+        // Parse the machine readable status lines
+        let mut result = Verification {
+            status: SignatureStatus::Bad,
+            kind: Some(SignatureKind::Gpg),
+            signer: None,
+            key: None,
+        };
+        let mut found = false;
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let mut fields = line.splitn(4, ' ').skip(1);
+            let (status, key, rest) = (fields.next().unwrap_or_default(), fields.next().map(|x| x.to_string()), fields.next().map(|x| x.to_string()));
+            let status = match status {
+                "GOODSIG" => SignatureStatus::Good,
+                "BADSIG" | "REVKEYSIG" => SignatureStatus::Bad,
+                "EXPSIG" | "EXPKEYSIG" => SignatureStatus::Expired,
+                "ERRSIG" => SignatureStatus::UnknownKey,
+                "VALIDSIG" => {
+                    result.key = key;
+                    continue;
+                },
+                _ => continue,
+            };
+            found = true;
+            result.status = status;
+            result.key = result.key.or(key);
+            if status != SignatureStatus::UnknownKey {
+                result.signer = rest;
+            }
+        }
+        if !found {
+            return Err(Error::verify_failed(String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(result)
+    }
+}
+
+/// Verify the signature of the given commit, e.g. `HEAD`, against the given verifier's keys.
+/// Unsigned commits are reported with the `Unsigned` status rather than as an error.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_verify_commit_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let result = git::verify_commit(&tmpdir, "HEAD", &git::Verifier::new()).unwrap();
+/// assert_eq!(result.status, git::SignatureStatus::Unsigned);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn verify_commit<T, U>(path: T, rev: U, verifier: &Verifier) -> Result<Verification>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let oid = repo.revparse_single(rev.as_ref())?.peel_to_commit()?.id();
+    match repo.extract_signature(&oid, None) {
+        Ok((signature, content)) => {
+            let signature = signature.as_str().ok_or_else(|| Error::verify_failed("signature isn't valid utf-8"))?;
+            verifier.verify(&repo, &content, signature)
+        },
+        Err(err) if err.code() == ErrorCode::NotFound => Ok(Verification::unsigned()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Verify the signature of the given annotated tag against the given verifier's keys.
+/// Lightweight and unsigned tags are reported with the `Unsigned` status.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_verify_tag_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let result = git::verify_tag(&tmpdir, "3.11", &git::Verifier::new()).unwrap();
+/// assert_eq!(result.status, git::SignatureStatus::Unsigned);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn verify_tag<T, U>(path: T, name: U, verifier: &Verifier) -> Result<Verification>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let reference = repo.find_reference(&format!("refs/tags/{}", name.as_ref()))?.resolve()?;
+    let object = repo.find_object(reference.target().unwrap(), None)?;
+    if object.kind() != Some(ObjectType::Tag) {
+        return Ok(Verification::unsigned());
+    }
+
+    // Tag signatures are appended to the raw tag content which is what was signed
+    let odb = repo.odb()?;
+    let raw = odb.read(object.id())?;
+    let data = String::from_utf8_lossy(raw.data());
+    match signature_start(&data) {
+        Some(start) => verifier.verify(&repo, data[..start + 1].as_bytes(), &data[start + 1..]),
+        None => Ok(Verification::unsigned()),
+    }
+}
+
+// Returns the position of the newline preceding the signature appended to the given raw tag
+// content if there is one
+pub(crate) fn signature_start(data: &str) -> Option<usize> {
+    [PGP_HEADER, SSH_HEADER].iter().filter_map(|x| data.rfind(&format!("\n{}", x))).max()
+}
+
+// Verify the given signature file over the given content with ssh-keygen
+fn ssh_verify(program: &str, allowed: &Path, sig: &Path, content: &[u8]) -> Result<Verification> {
+    let mut result = Verification {
+        status: SignatureStatus::UnknownKey,
+        kind: Some(SignatureKind::Ssh),
+        signer: None,
+        key: None,
+    };
+
+    // Find the principal the signing key is allowed for
+    let mut cmd = Command::new(program);
+    cmd.args(["-Y", "find-principals", "-f"]).arg(allowed).arg("-s").arg(sig);
+    let output = run(cmd, &[])?;
+    let principal = match String::from_utf8_lossy(&output.stdout).lines().next() {
+        Some(principal) if output.status.success() => principal.to_string(),
+        _ => return Ok(result),
+    };
+
+    // Verify the signature for the principal
+    let mut cmd = Command::new(program);
+    cmd.args(["-Y", "verify", "-n", "git", "-f"]).arg(allowed).arg("-I").arg(&principal).arg("-s").arg(sig);
+    let output = run(cmd, content)?;
+    result.signer = Some(principal);
+    if output.status.success() {
+        result.status = SignatureStatus::Good;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        result.key = stdout.rsplit(" key ").next().filter(|_| stdout.contains(" key ")).map(|x| x.trim().to_string());
+    } else {
+        result.status = SignatureStatus::Bad;
+    }
+    Ok(result)
+}
+
+// Run the given command feeding it the given input
+fn run(mut cmd: Command, input: &[u8]) -> Result<std::process::Output> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|x| Error::verify_failed(format!("{:?}: {}", cmd.get_program(), x)))?;
+
+    // Writing fails if the command exits early which is reported by its output instead
+    let _ = child.stdin.take().unwrap().write_all(input);
+    child.wait_with_output().map_err(|x| Error::verify_failed(format!("{:?}: {}", cmd.get_program(), x)))
+}
+
+// Write the given content to a temporary file for the duration of the given function. The file
+// is created in a new directory with a random name only the user can access so another user
+// can't plant or read it.
+fn with_tempfile<T, F>(content: &str, func: F) -> Result<T>
+where
+    F: FnOnce(&Path) -> Result<T>,
+{
+    let dir = loop {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_nanos()).unwrap_or_default();
+        let dir = env::temp_dir().join(format!("skellige-sig-{:016x}", RandomState::new().hash_one(nanos)));
+        match fs::DirBuilder::new().mode(0o700).create(&dir) {
+            Ok(()) => break dir,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(FuError::from(err).into()),
+        }
+    };
+    let path = dir.join("signature");
+    let result = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut x| x.write_all(content.as_bytes()))
+        .map_err(|x| FuError::from(x).into())
+        .and_then(|_| func(&path));
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    // Create an isolated gpg home with a test key
+    fn gpg_home(path: &Path) -> PathBuf {
+        let home = path.mash("gnupg");
+        assert!(sys::mkdir(&home).is_ok());
+        assert!(sys::chmod(&home, 0o700).is_ok());
+        let status = Command::new("gpg")
+            .args(["--homedir", home.to_str().unwrap(), "--batch", "--passphrase", "", "--quick-gen-key", "test <test@example.com>", "ed25519", "sign", "never"])
+            .output()
+            .unwrap()
+            .status;
+        assert_eq!(status.success(), true);
+        home
+    }
+
+    // Sign the given content with the test gpg key
+    fn gpg_sign(home: &Path, content: &str) -> git::Result<String> {
+        let mut child = Command::new("gpg")
+            .args(["--homedir", home.to_str().unwrap(), "-bsau", "test@example.com"])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        std::io::Write::write_all(&mut child.stdin.take().unwrap(), content.as_bytes()).unwrap();
+        Ok(String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap())
+    }
+
+    // Write a signed tag object pointing at HEAD
+    fn signed_tag(repo: &git2::Repository, name: &str, sign: impl Fn(&str) -> String) {
+        let head = repo.head().unwrap().target().unwrap();
+        let content = format!("object {}\ntype commit\ntag {}\ntagger test <test@example.com> 1577836800 +0000\n\nRelease\n", head, name);
+        let data = format!("{}{}", content, sign(&content));
+        let oid = repo.odb().unwrap().write(git2::ObjectType::Tag, data.as_bytes()).unwrap();
+        repo.reference(&format!("refs/tags/{}", name), oid, false, "tag").unwrap();
+    }
+
+    #[test]
+    fn test_verify_gpg() {
+        let tmpdir = setup("git_verify_gpg");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+        let home = gpg_home(&tmpdir);
+        let verifier = git::Verifier::new().gpg_home(&home);
+
+        // Unsigned
+        assert_eq!(git::verify_commit(&tmpdir, "HEAD", &verifier).unwrap(), git::Verification::unsigned());
+        assert_eq!(git::verify_tag(&tmpdir, "3.11", &verifier).unwrap().status, git::SignatureStatus::Unsigned);
+
+        // Good commit
+        let signer = {
+            let home = home.clone();
+            git::Signer::callback(move |content| gpg_sign(&home, content))
+        };
+        assert!(git::CommitBuilder::new(&tmpdir).author("test", "test@example.com").message("Signed").signer(signer).commit().is_ok());
+        let result = git::verify_commit(&tmpdir, "HEAD", &verifier).unwrap();
+        assert_eq!(result.status, git::SignatureStatus::Good);
+        assert_eq!(result.kind, Some(git::SignatureKind::Gpg));
+        assert_eq!(result.signer, Some("test <test@example.com>".to_string()));
+        assert_eq!(result.key.as_ref().unwrap().len(), 40);
+        assert_eq!(result.is_good(), true);
+
+        // Bad commit signed over different content
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let buf = repo.commit_create_buffer(&sig, &sig, "Tampered", &head.tree().unwrap(), &[&head]).unwrap();
+        let oid = repo.commit_signed(buf.as_str().unwrap(), &gpg_sign(&home, "foo").unwrap(), None).unwrap();
+        let result = git::verify_commit(&tmpdir, oid.to_string(), &verifier).unwrap();
+        assert_eq!(result.status, git::SignatureStatus::Bad);
+        assert_eq!(result.signer, Some("test <test@example.com>".to_string()));
+
+        // Unknown key
+        let empty = tmpdir.mash("empty");
+        assert!(sys::mkdir(&empty).is_ok());
+        assert!(sys::chmod(&empty, 0o700).is_ok());
+        let result = git::verify_commit(&tmpdir, "HEAD", &git::Verifier::new().gpg_home(&empty)).unwrap();
+        assert_eq!(result.status, git::SignatureStatus::UnknownKey);
+        assert_eq!(result.signer, None);
+        assert_eq!(result.key.is_some(), true);
+
+        // Good tag
+        signed_tag(&repo, "v1.0.0", |content| gpg_sign(&home, content).unwrap());
+        let result = git::verify_tag(&tmpdir, "v1.0.0", &verifier).unwrap();
+        assert_eq!(result.status, git::SignatureStatus::Good);
+        assert_eq!(result.signer, Some("test <test@example.com>".to_string()));
+
+        // Errors
+        assert!(git::verify_commit(&tmpdir, "missing", &verifier).is_err());
+        assert!(git::verify_tag(&tmpdir, "missing", &verifier).is_err());
+
+        let _ = Command::new("gpgconf").args(["--homedir", home.to_str().unwrap(), "--kill", "gpg-agent"]).output();
+        let _ = Command::new("gpgconf").args(["--homedir", empty.to_str().unwrap(), "--kill", "gpg-agent"]).output();
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_verify_exits_early() {
+        let tmpdir = setup("git_verify_exits_early");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        let repo = git2::Repository::init(&tmpdir).unwrap();
+
+        // The signature is given in a private directory and a program exiting without reading
+        // the content reports its stderr rather than the broken pipe of writing to it
+        let program = tmpdir.mash("gpg");
+        let script = "#!/bin/sh\nsig=\"$3\"\necho \"$(stat -c %a \"$(dirname \"$sig\")\") $(cat \"$sig\")\" >&2\nexit 2\n";
+        assert!(sys::write(&program, script).is_ok());
+        assert!(sys::chmod(&program, 0o755).is_ok());
+        repo.config().unwrap().set_str("gpg.program", &program.to_string().unwrap()).unwrap();
+        let content = "x".repeat(1024 * 1024);
+        let err = git::Verifier::new().verify(&repo, content.as_bytes(), "-----BEGIN PGP SIGNATURE-----").unwrap_err();
+        assert_eq!(err.to_string(), git::Error::verify_failed("700 -----BEGIN PGP SIGNATURE-----").to_string());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+
+    #[test]
+    fn test_verify_ssh() {
+        let tmpdir = setup("git_verify_ssh");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), &tmpdir).is_ok());
+        let repo = git2::Repository::open(&tmpdir).unwrap();
+
+        // Test key and allowed signers files
+        let key = tmpdir.mash("id_ed25519");
+        let status = Command::new("ssh-keygen").args(["-q", "-t", "ed25519", "-N", "", "-C", "test", "-f", key.to_str().unwrap()]).status().unwrap();
+        assert_eq!(status.success(), true);
+        let public = sys::readstring(key.with_extension("pub")).unwrap();
+        let allowed = tmpdir.mash("allowed_signers");
+        assert!(sys::write(&allowed, format!("test@example.com {}", public)).is_ok());
+        let other = tmpdir.mash("other_signers");
+        assert!(sys::write(&other, "").is_ok());
+        let sign = |content: &str| {
+            let mut child = Command::new("ssh-keygen")
+                .args(["-Y", "sign", "-n", "git", "-f", key.to_str().unwrap()])
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::null())
+                .spawn()
+                .unwrap();
+            std::io::Write::write_all(&mut child.stdin.take().unwrap(), content.as_bytes()).unwrap();
+            String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap()
+        };
+
+        // No allowed signers configured
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let buf = repo.commit_create_buffer(&sig, &sig, "Signed", &head.tree().unwrap(), &[&head]).unwrap();
+        let oid = repo.commit_signed(buf.as_str().unwrap(), &sign(buf.as_str().unwrap()), None).unwrap().to_string();
+        assert_eq!(git::verify_commit(&tmpdir, &oid, &git::Verifier::new()).unwrap_err().to_string(), git::Error::verify_failed("no allowed signers file configured").to_string());
+
+        // Good commit using the configured allowed signers
+        repo.config().unwrap().set_str("gpg.ssh.allowedSignersFile", allowed.to_str().unwrap()).unwrap();
+        let result = git::verify_commit(&tmpdir, &oid, &git::Verifier::new()).unwrap();
+        assert_eq!(result.status, git::SignatureStatus::Good);
+        assert_eq!(result.kind, Some(git::SignatureKind::Ssh));
+        assert_eq!(result.signer, Some("test@example.com".to_string()));
+        assert_eq!(result.key.unwrap().starts_with("SHA256:"), true);
+
+        // Unknown key
+        let result = git::verify_commit(&tmpdir, &oid, &git::Verifier::new().allowed_signers(&other)).unwrap();
+        assert_eq!(result.status, git::SignatureStatus::UnknownKey);
+
+        // Bad commit signed over different content
+        let buf = repo.commit_create_buffer(&sig, &sig, "Tampered", &head.tree().unwrap(), &[&head]).unwrap();
+        let oid = repo.commit_signed(buf.as_str().unwrap(), &sign("foo"), None).unwrap().to_string();
+        let result = git::verify_commit(&tmpdir, &oid, &git::Verifier::new()).unwrap();
+        assert_eq!(result.status, git::SignatureStatus::Bad);
+        assert_eq!(result.signer, Some("test@example.com".to_string()));
+
+        // Good tag
+        signed_tag(&repo, "v1.0.0", sign);
+        let result = git::verify_tag(&tmpdir, "v1.0.0", &git::Verifier::new()).unwrap();
+        assert_eq!(result.status, git::SignatureStatus::Good);
+        assert_eq!(result.signer, Some("test@example.com".to_string()));
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}
@@ -0,0 +1,255 @@
+use super::conventional::Bump;
+use crate::error::*;
+use std::{cmp::Ordering, fmt};
+
+/// Semantic version as described by the [Semantic Versioning](https://semver.org) specification
+/// ordered by precedence with build metadata only used to break ties
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Version {
+    pub major: u64,            // Major version for incompatible changes
+    pub minor: u64,            // Minor version for backwards compatible functionality
+    pub patch: u64,            // Patch version for backwards compatible bug fixes
+    pub pre: Vec<String>,      // Pre-release identifiers e.g. `["rc", "1"]` for `1.0.0-rc.1`
+    pub build: Option<String>, // Build metadata e.g. `5114f85` for `1.0.0+5114f85`
+}
+
+impl Version {
+    /// Create a new release version from the given components.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Version::new(1, 2, 3).to_string(), "1.2.3");
+    /// ```
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self { major, minor, patch, ..Default::default() }
+    }
+
+    /// Parse the given value as a semantic version allowing an optional leading `v` as is common
+    /// for release tags.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// let version = git::Version::parse("v1.2.3-rc.1+5114f85").unwrap();
+    /// assert_eq!((version.major, version.minor, version.patch), (1, 2, 3));
+    /// assert_eq!(version.pre, vec!["rc", "1"]);
+    /// assert_eq!(version.build, Some("5114f85".to_string()));
+    /// assert!(git::Version::parse("3.11").is_err());
+    /// ```
+    pub fn parse<T: AsRef<str>>(value: T) -> Result<Self> {
+        let value = value.as_ref();
+        let err = || Error::invalid_version(value);
+        let version = value.strip_prefix(|c| c == 'v' || c == 'V').unwrap_or(value);
+        let (version, build) = match version.split_once('+') {
+            Some((version, build)) => (version, Some(build)),
+            None => (version, None),
+        };
+        let (core, pre) = match version.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (version, None),
+        };
+
+        // Numeric identifiers must not have leading zeros
+        let numeric = |x: &str| !x.is_empty() && x.chars().all(|c| c.is_ascii_digit()) && (x == "0" || !x.starts_with('0'));
+        let ident = |x: &str| !x.is_empty() && x.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+
+        let core: Vec<&str> = core.split('.').collect();
+        if core.len() != 3 || !core.iter().all(|x| numeric(x)) {
+            return Err(err());
+        }
+        let pre: Vec<String> = match pre {
+            Some(pre) => pre.split('.').map(|x| x.to_string()).collect(),
+            None => vec![],
+        };
+        if pre.iter().any(|x| !ident(x) || (x.chars().all(|c| c.is_ascii_digit()) && !numeric(x))) {
+            return Err(err());
+        }
+        if build.map(|x| !x.split('.').all(ident)).unwrap_or(false) {
+            return Err(err());
+        }
+
+        Ok(Self {
+            major: core[0].parse().map_err(|_| err())?,
+            minor: core[1].parse().map_err(|_| err())?,
+            patch: core[2].parse().map_err(|_| err())?,
+            pre,
+            build: build.map(|x| x.to_string()),
+        })
+    }
+
+    /// Returns true if this is a pre-release version.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    ///
+    /// assert_eq!(git::Version::parse("1.0.0-alpha").unwrap().is_prerelease(), true);
+    /// ```
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
+
+    /// Returns the next version after applying the given bump dropping any pre-release and build
+    /// metadata. A pre-release is released as is when it already carries the bump e.g. a minor
+    /// bump of `1.1.0-rc.1` gives `1.1.0` while a major bump gives `2.0.0`.
+    ///
+    /// ### Examples
+    /// ```
+    /// use skellige::prelude::*;
+    /// use git::conventional::Bump;
+    ///
+    /// assert_eq!(git::Version::new(1, 2, 3).bump(Bump::Minor), git::Version::new(1, 3, 0));
+    /// assert_eq!(git::Version::parse("1.1.0-rc.1").unwrap().bump(Bump::Patch), git::Version::new(1, 1, 0));
+    /// ```
+    pub fn bump(&self, bump: Bump) -> Self {
+        let pre = self.is_prerelease();
+        let (major, minor, patch) = (self.major, self.minor, self.patch);
+        match bump {
+            Bump::None => self.clone(),
+            Bump::Major if pre && minor == 0 && patch == 0 => Self::new(major, 0, 0),
+            Bump::Major => Self::new(major + 1, 0, 0),
+            Bump::Minor if pre && patch == 0 => Self::new(major, minor, 0),
+            Bump::Minor => Self::new(major, minor + 1, 0),
+            Bump::Patch if pre => Self::new(major, minor, patch),
+            Bump::Patch => Self::new(major, minor, patch + 1),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre.join("."))?;
+        }
+        if let Some(build) = &self.build {
+            write!(f, "+{}", build)?;
+        }
+        Ok(())
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                // A release has higher precedence than its pre-releases
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => {
+                    for (x, y) in self.pre.iter().zip(other.pre.iter()) {
+                        let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+                            (Ok(x), Ok(y)) => x.cmp(&y),
+                            (Ok(_), Err(_)) => Ordering::Less,
+                            (Err(_), Ok(_)) => Ordering::Greater,
+                            (Err(_), Err(_)) => x.cmp(y),
+                        };
+                        if ordering != Ordering::Equal {
+                            return ordering;
+                        }
+                    }
+                    self.pre.len().cmp(&other.pre.len())
+                },
+            })
+            .then_with(|| self.build.cmp(&other.build))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn test_version_parse() {
+        let version = git::Version::parse("1.2.3").unwrap();
+        assert_eq!(version, git::Version::new(1, 2, 3));
+        assert_eq!(version.is_prerelease(), false);
+        assert_eq!(git::Version::parse("v0.10.0").unwrap(), git::Version::new(0, 10, 0));
+        let version = git::Version::parse("1.0.0-alpha.1+build.5").unwrap();
+        assert_eq!(version.pre, vec!["alpha", "1"]);
+        assert_eq!(version.build, Some("build.5".to_string()));
+        assert_eq!(version.is_prerelease(), true);
+        assert_eq!(version.to_string(), "1.0.0-alpha.1+build.5");
+        assert_eq!(git::Version::parse("1.0.0-x-y.0").unwrap().pre, vec!["x-y", "0"]);
+
+        // Errors
+        for value in [
+            "",
+            "3.11",
+            "1.2.3.4",
+            "01.2.3",
+            "1.2.x",
+            "1.2.3-",
+            "1.2.3-01",
+            "1.2.3-a..b",
+            "1.2.3+",
+            "1.2.3+a_b",
+            "x1.2.3",
+        ] {
+            assert_eq!(git::Version::parse(value).unwrap_err().to_string(), git::Error::invalid_version(value).to_string());
+        }
+    }
+
+    #[test]
+    fn test_version_bump() {
+        use git::conventional::Bump;
+        let bump = |x: &str, bump: Bump| git::Version::parse(x).unwrap().bump(bump).to_string();
+
+        // Releases
+        assert_eq!(bump("1.2.3", Bump::None), "1.2.3");
+        assert_eq!(bump("1.2.3", Bump::Patch), "1.2.4");
+        assert_eq!(bump("1.2.3", Bump::Minor), "1.3.0");
+        assert_eq!(bump("1.2.3", Bump::Major), "2.0.0");
+        assert_eq!(bump("v0.9.9+build", Bump::Minor), "0.10.0");
+
+        // Pre-releases
+        assert_eq!(bump("1.2.3-rc.1", Bump::None), "1.2.3-rc.1");
+        assert_eq!(bump("1.2.3-rc.1", Bump::Patch), "1.2.3");
+        assert_eq!(bump("1.2.3-rc.1", Bump::Minor), "1.3.0");
+        assert_eq!(bump("1.2.0-rc.1", Bump::Minor), "1.2.0");
+        assert_eq!(bump("1.2.0-rc.1", Bump::Major), "2.0.0");
+        assert_eq!(bump("2.0.0-rc.1", Bump::Major), "2.0.0");
+    }
+
+    #[test]
+    fn test_version_ordering() {
+        // Precedence example from the specification
+        let versions = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+        ];
+        let versions: Vec<git::Version> = versions.iter().map(|x| git::Version::parse(x).unwrap()).collect();
+        for pair in versions.windows(2) {
+            assert!(pair[0] < pair[1], "{} < {}", pair[0], pair[1]);
+        }
+
+        // Numeric rather than lexical components
+        assert!(git::Version::parse("v0.9.0").unwrap() < git::Version::parse("v0.10.0").unwrap());
+        assert!(git::Version::new(1, 10, 0) > git::Version::new(1, 9, 9));
+        assert!(git::Version::new(2, 0, 0) > git::Version::new(1, 99, 99));
+
+        // Build metadata only breaks ties
+        assert!(git::Version::parse("1.0.0+a").unwrap() < git::Version::parse("1.0.0+b").unwrap());
+        assert!(git::Version::parse("1.0.0+z").unwrap() < git::Version::parse("1.0.1+a").unwrap());
+    }
+}
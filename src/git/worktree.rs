@@ -0,0 +1,353 @@
+use super::checkout::has_changes;
+use crate::error::*;
+use fungus::prelude::*;
+use git2::{BranchType, Oid, Repository, WorktreeAddOptions, WorktreeLockStatus, WorktreePruneOptions};
+
+/// Worktree as returned by `worktrees`
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorktreeInfo {
+    pub name: Option<String>,        // Name of a linked worktree or None for the main worktree
+    pub path: PathBuf,               // Location of the worktree on disk
+    pub head: Option<Oid>,           // Commit checked out if the worktree exists and has one
+    pub branch: Option<String>,      // Branch checked out or None if detached
+    pub locked: bool,                // True if the worktree is locked against pruning
+    pub lock_reason: Option<String>, // Reason given when the worktree was locked
+    pub prunable: bool,              // True if the location no longer exists and it isn't locked
+}
+
+/// List the repo's worktrees with the main worktree first followed by the linked worktrees
+/// sorted by name, the same as `git worktree list`. Bare repos have no main worktree.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_worktrees_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", &tmpdir).is_ok());
+/// let worktrees = git::worktrees(&tmpdir).unwrap();
+/// assert_eq!(worktrees.len(), 1);
+/// assert_eq!(worktrees[0].branch, Some("master".to_string()));
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn worktrees<T: AsRef<Path>>(path: T) -> Result<Vec<WorktreeInfo>> {
+    let repo = Repository::open(path.as_ref())?;
+    let mut infos = vec![];
+    if let Some(workdir) = repo.workdir() {
+        let (head, branch) = head_info(&repo);
+        infos.push(WorktreeInfo {
+            name: None,
+            path: trim_slash(workdir),
+            head,
+            branch,
+            locked: false,
+            lock_reason: None,
+            prunable: false,
+        });
+    }
+
+    let mut names: Vec<String> = repo.worktrees()?.iter().flatten().map(|x| x.to_string()).collect();
+    names.sort();
+    for name in names {
+        let worktree = repo.find_worktree(&name)?;
+        let (head, branch) = match Repository::open_from_worktree(&worktree) {
+            Ok(repo) => head_info(&repo),
+            Err(_) => (None, None),
+        };
+        let (locked, lock_reason) = match worktree.is_locked()? {
+            WorktreeLockStatus::Locked(reason) => (true, reason.map(|x| x.trim_end().to_string()).filter(|x| !x.is_empty())),
+            WorktreeLockStatus::Unlocked => (false, None),
+        };
+        infos.push(WorktreeInfo {
+            name: Some(name),
+            path: trim_slash(worktree.path()),
+            head,
+            branch,
+            locked,
+            lock_reason,
+            prunable: worktree.is_prunable(None)?,
+        });
+    }
+    Ok(infos)
+}
+
+/// Add a linked worktree with the given name at the given location checking out the given
+/// branch, the same as `git worktree add <dest> <branch>`. The branch is created from HEAD if
+/// it doesn't exist yet and when no branch is given a new one named after the worktree is
+/// created, the same as `git worktree add <dest>`. A branch can only be checked out in one
+/// worktree at a time. Returns the location of the new worktree.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_worktree_add_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("repo")).is_ok());
+/// let dest = git::worktree_add(tmpdir.mash("repo"), "build", tmpdir.mash("build"), None).unwrap();
+/// assert!(dest.mash("README.md").exists());
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn worktree_add<T, U, V>(path: T, name: U, dest: V, branch: Option<&str>) -> Result<PathBuf>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+    V: AsRef<Path>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let dest = dest.as_ref().abs()?;
+    match branch {
+        Some(branch) => {
+            let reference = match repo.find_branch(branch, BranchType::Local) {
+                Ok(branch) => branch.into_reference(),
+                Err(_) => repo.branch(branch, &repo.head()?.peel_to_commit()?, false)?.into_reference(),
+            };
+            let mut opts = WorktreeAddOptions::new();
+            opts.reference(Some(&reference));
+            repo.worktree(name.as_ref(), &dest, Some(&opts))?;
+        },
+        None => {
+            repo.worktree(name.as_ref(), &dest, None)?;
+        },
+    }
+    Ok(dest)
+}
+
+/// Lock the linked worktree with the given name with an optional reason, the same as
+/// `git worktree lock --reason <reason> <name>`, to keep it from being pruned or removed e.g.
+/// while it lives on removable media.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_worktree_lock_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("repo")).is_ok());
+/// assert!(git::worktree_add(tmpdir.mash("repo"), "build", tmpdir.mash("build"), None).is_ok());
+/// assert!(git::worktree_lock(tmpdir.mash("repo"), "build", Some("in use")).is_ok());
+/// assert_eq!(git::worktrees(tmpdir.mash("repo")).unwrap()[1].locked, true);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn worktree_lock<T, U>(path: T, name: U, reason: Option<&str>) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    repo.find_worktree(name.as_ref())?.lock(reason)?;
+    Ok(())
+}
+
+/// Unlock the linked worktree with the given name, the same as `git worktree unlock <name>`.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_worktree_unlock_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("repo")).is_ok());
+/// assert!(git::worktree_add(tmpdir.mash("repo"), "build", tmpdir.mash("build"), None).is_ok());
+/// assert!(git::worktree_lock(tmpdir.mash("repo"), "build", None).is_ok());
+/// assert!(git::worktree_unlock(tmpdir.mash("repo"), "build").is_ok());
+/// assert_eq!(git::worktrees(tmpdir.mash("repo")).unwrap()[1].locked, false);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn worktree_unlock<T, U>(path: T, name: U) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    repo.find_worktree(name.as_ref())?.unlock()?;
+    Ok(())
+}
+
+/// Remove the linked worktree with the given name deleting its location on disk, the same as
+/// `git worktree remove <name>`. The branch it had checked out is kept. As a safety check a
+/// worktree with uncommitted changes or untracked files is refused with `Error::DirtyWorktree`
+/// and a locked worktree with `Error::WorktreeLocked` unless `force` is set. Ignored files don't
+/// prevent removal.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_worktree_remove_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("repo")).is_ok());
+/// assert!(git::worktree_add(tmpdir.mash("repo"), "build", tmpdir.mash("build"), None).is_ok());
+/// assert!(git::worktree_remove(tmpdir.mash("repo"), "build", false).is_ok());
+/// assert_eq!(tmpdir.mash("build").exists(), false);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn worktree_remove<T, U>(path: T, name: U, force: bool) -> Result<()>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    let repo = Repository::open(path.as_ref())?;
+    let worktree = repo.find_worktree(name.as_ref())?;
+    if !force {
+        if let WorktreeLockStatus::Locked(_) = worktree.is_locked()? {
+            return Err(Error::worktree_locked(name.as_ref()));
+        }
+        if worktree.path().exists() && has_changes(worktree.path(), true)? {
+            return Err(Error::dirty_worktree(worktree.path()));
+        }
+    }
+    worktree.prune(Some(WorktreePruneOptions::new().valid(true).locked(force).working_tree(true)))?;
+    Ok(())
+}
+
+/// Prune the administrative data of linked worktrees whose location no longer exists and that
+/// aren't locked, the same as `git worktree prune`. Returns the names of the pruned worktrees.
+///
+/// ### Examples
+/// ```
+/// use skellige::prelude::*;
+///
+/// let tmpdir = PathBuf::from("tests/temp").abs().unwrap().mash("git_worktree_prune_doc");
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// assert!(tar::extract_all("tests/alpine-base.tgz", tmpdir.mash("repo")).is_ok());
+/// assert!(git::worktree_add(tmpdir.mash("repo"), "build", tmpdir.mash("build"), None).is_ok());
+/// assert!(sys::remove_all(tmpdir.mash("build")).is_ok());
+/// assert_eq!(git::worktree_prune(tmpdir.mash("repo")).unwrap(), vec!["build".to_string()]);
+/// assert!(sys::remove_all(&tmpdir).is_ok());
+/// ```
+pub fn worktree_prune<T: AsRef<Path>>(path: T) -> Result<Vec<String>> {
+    let repo = Repository::open(path.as_ref())?;
+    let mut pruned = vec![];
+    for name in repo.worktrees()?.iter().flatten() {
+        let worktree = repo.find_worktree(name)?;
+        if worktree.is_prunable(None)? {
+            worktree.prune(None)?;
+            pruned.push(name.to_string());
+        }
+    }
+    pruned.sort();
+    Ok(pruned)
+}
+
+// Returns the given path without the trailing slash libgit2 reports worktree paths with
+fn trim_slash(path: &Path) -> PathBuf {
+    PathBuf::from(path.to_string_lossy().trim_end_matches('/'))
+}
+
+// Returns the commit and branch checked out in the given repo if any
+fn head_info(repo: &Repository) -> (Option<Oid>, Option<String>) {
+    match repo.head() {
+        Ok(head) => (head.target(), if head.is_branch() { head.shorthand().map(|x| x.to_string()) } else { None }),
+        Err(_) => (None, None),
+    }
+}
+
+// Unit tests
+// -------------------------------------------------------------------------------------------------
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use crate::prelude::*;
+    use std::process::Command;
+
+    // Test setup
+    fn setup<T: AsRef<Path>>(path: T) -> PathBuf {
+        let temp = PathBuf::from("tests/temp").abs().unwrap();
+        sys::mkdir(&temp).unwrap();
+        temp.mash(path.as_ref())
+    }
+
+    #[test]
+    fn test_worktree() {
+        let tmpdir = setup("git_worktree");
+        assert!(sys::remove_all(&tmpdir).is_ok());
+        assert!(tar::extract_all(tmpdir.mash("../../alpine-base.tgz"), tmpdir.mash("repo")).is_ok());
+        let repodir = tmpdir.mash("repo");
+        let repo = git2::Repository::open(&repodir).unwrap();
+        let head = repo.head().unwrap().target();
+        let names = || git::worktrees(&repodir).unwrap().into_iter().map(|x| x.name).collect::<Vec<_>>();
+
+        // Main worktree only
+        let main = git::worktrees(&repodir).unwrap().remove(0);
+        assert_eq!((main.name, main.path, main.head, main.branch), (None, repodir.clone(), head, Some("master".to_string())));
+        assert_eq!((main.locked, main.prunable), (false, false));
+
+        // New branch named after the worktree, new and existing given branches
+        let build = git::worktree_add(&repodir, "build", tmpdir.mash("build"), None).unwrap();
+        assert_eq!(build, tmpdir.mash("build"));
+        assert!(build.mash("README.md").exists());
+        assert!(git::branch_create(&repodir, "existing", None, false).is_ok());
+        assert!(git::worktree_add(&repodir, "existing", tmpdir.mash("existing"), Some("existing")).is_ok());
+        assert!(git::worktree_add(&repodir, "feature", tmpdir.mash("feature"), Some("feature")).is_ok());
+        assert!(git::worktree_add(&repodir, "dup", tmpdir.mash("dup"), Some("feature")).is_err());
+        let worktrees = git::worktrees(&repodir).unwrap();
+        assert_eq!(
+            names(),
+            vec![
+                None,
+                Some("build".to_string()),
+                Some("existing".to_string()),
+                Some("feature".to_string())
+            ]
+        );
+        let branches = worktrees.iter().map(|x| x.branch.clone().unwrap()).collect::<Vec<_>>();
+        assert_eq!(branches, vec!["master", "build", "existing", "feature"]);
+        assert!(worktrees.iter().all(|x| x.head == head));
+        assert_eq!(worktrees[3].path, tmpdir.mash("feature"));
+        let output = Command::new("git").args(["worktree", "list", "--porcelain"]).current_dir(&repodir).output().unwrap();
+        let listed = String::from_utf8(output.stdout).unwrap();
+        let paths = listed.lines().filter_map(|x| x.strip_prefix("worktree ")).map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(paths, worktrees.iter().map(|x| x.path.clone()).collect::<Vec<_>>());
+
+        // Locking keeps stale worktrees from being pruned
+        assert!(git::worktree_lock(&repodir, "existing", Some("in use")).is_ok());
+        assert!(git::worktree_lock(&repodir, "existing", None).is_err());
+        let existing = git::worktrees(&repodir).unwrap().remove(2);
+        assert_eq!((existing.locked, existing.lock_reason), (true, Some("in use".to_string())));
+        assert!(sys::remove_all(tmpdir.mash("existing")).is_ok());
+        assert!(sys::remove_all(tmpdir.mash("feature")).is_ok());
+        let worktrees = git::worktrees(&repodir).unwrap();
+        assert_eq!(worktrees.iter().map(|x| x.prunable).collect::<Vec<_>>(), vec![false, false, false, true]);
+        assert_eq!((worktrees[3].head, worktrees[3].branch.as_ref()), (None, None));
+        assert_eq!(git::worktree_prune(&repodir).unwrap(), vec!["feature".to_string()]);
+        assert!(git::worktree_unlock(&repodir, "existing").is_ok());
+        assert_eq!(git::worktrees(&repodir).unwrap()[2].locked, false);
+        assert_eq!(git::worktree_prune(&repodir).unwrap(), vec!["existing".to_string()]);
+        assert_eq!(names(), vec![None, Some("build".to_string())]);
+        assert!(git::worktree_prune(&repodir).unwrap().is_empty());
+
+        // Removal refuses dirty and locked worktrees without force
+        assert!(sys::write(build.mash("README.md"), "dirty").is_ok());
+        let err = git::worktree_remove(&repodir, "build", false).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::dirty_worktree(&build).to_string());
+        assert!(git::reset(&build, "HEAD", git::ResetMode::Hard, true).is_ok());
+        assert!(sys::write(build.mash("notes.txt"), "notes").is_ok());
+        let err = git::worktree_remove(&repodir, "build", false).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::dirty_worktree(&build).to_string());
+        assert_eq!(build.mash("notes.txt").exists(), true);
+        assert!(git::worktree_lock(&repodir, "build", None).is_ok());
+        let err = git::worktree_remove(&repodir, "build", false).unwrap_err();
+        assert_eq!(err.to_string(), git::Error::worktree_locked("build").to_string());
+        assert!(git::worktree_remove(&repodir, "build", true).is_ok());
+        assert_eq!(build.exists(), false);
+        assert_eq!(names(), vec![None]);
+        assert!(repo.find_branch("build", git2::BranchType::Local).is_ok());
+
+        // Ignored files don't prevent removal
+        let scratch = tmpdir.mash("scratch");
+        assert!(git::worktree_add(&repodir, "scratch", &scratch, None).is_ok());
+        assert!(sys::write(repodir.mash(".git/info/exclude"), "*.log\n").is_ok());
+        assert!(sys::write(scratch.mash("debug.log"), "log").is_ok());
+        assert!(git::worktree_remove(&repodir, "scratch", false).is_ok());
+        assert_eq!(scratch.exists(), false);
+
+        // Errors
+        assert!(git::worktree_lock(&repodir, "missing", None).is_err());
+        assert!(git::worktree_unlock(&repodir, "missing").is_err());
+        assert!(git::worktree_remove(&repodir, "missing", true).is_err());
+        assert!(git::worktrees(tmpdir.mash("missing")).is_err());
+
+        assert!(sys::remove_all(&tmpdir).is_ok());
+    }
+}